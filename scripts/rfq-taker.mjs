@@ -866,7 +866,7 @@ async function main() {
     if (!prepay.ok) throw new Error(`verify-prepay failed: ${prepay.error}`);
     // Defense-in-depth: ensure the on-chain escrow fee receiver settings match the negotiated TERMS, otherwise
     // claim could fail (wrong trade fee vault PDA) or fees could be misrepresented.
-    if (prepay?.onchain?.state?.v === 3) {
+    if (Number(prepay?.onchain?.state?.v) >= 3) {
       const st = prepay.onchain.state;
       const wantTradeFeeCollector = String(swapCtx.trade.terms?.trade_fee_collector || '').trim();
       if (wantTradeFeeCollector && st.tradeFeeCollector?.toBase58?.() !== wantTradeFeeCollector) {
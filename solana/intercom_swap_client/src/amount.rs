@@ -0,0 +1,263 @@
+//! Conversions between Lightning millisatoshis and SPL token base units, and the program's fee math.
+//!
+//! Quotes should go through these helpers rather than floating point: conversions are exact integer
+//! arithmetic with explicit [`Rounding`], and fees come from [`EscrowAmounts`], the same code the program
+//! runs during Init.
+
+use std::{fmt, str::FromStr};
+
+pub use intercom_swap_core::{compute_fee, total_with_fee, EscrowAmounts};
+
+pub const MSAT_PER_BTC: u64 = 100_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    #[default]
+    Down,
+    Up,
+    /// Half away from zero.
+    Nearest,
+}
+
+fn div_round(n: u128, d: u128, rounding: Rounding) -> u128 {
+    let (q, r) = (n / d, n % d);
+    let bump = match rounding {
+        Rounding::Down => false,
+        Rounding::Up => r > 0,
+        Rounding::Nearest => r >= d - r,
+    };
+    q + u128::from(bump)
+}
+
+/// Whole tokens per BTC as a fixed-point decimal: `per_btc / 10^scale` (65000.25 is `{ 6500025, 2 }`). Parsed
+/// from plain decimals only: `65000` or `65000.25`, not `65000.`, `.25`, signs or exponents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Price {
+    pub per_btc: u64,
+    pub scale: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePriceError;
+
+impl fmt::Display for ParsePriceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "price must be a positive decimal such as 65000.25")
+    }
+}
+
+impl std::error::Error for ParsePriceError {}
+
+impl FromStr for Price {
+    type Err = ParsePriceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (int, frac) = match s.split_once('.') {
+            // As likely a cut-off price as a whole one.
+            Some((_, "")) => return Err(ParsePriceError),
+            Some(parts) => parts,
+            None => (s, ""),
+        };
+        if int.is_empty() || !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit()) || frac.len() > 18 {
+            return Err(ParsePriceError);
+        }
+        let per_btc: u64 = format!("{int}{frac}").parse().map_err(|_| ParsePriceError)?;
+        if per_btc == 0 {
+            return Err(ParsePriceError);
+        }
+        Ok(Self {
+            per_btc,
+            scale: frac.len() as u8,
+        })
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scale = u32::from(self.scale);
+        if scale == 0 {
+            return write!(f, "{}", self.per_btc);
+        }
+        let div = 10u64.pow(scale);
+        write!(f, "{}.{:0width$}", self.per_btc / div, self.per_btc % div, width = scale as usize)
+    }
+}
+
+impl Price {
+    /// Token base units per BTC for a mint with `decimals`, as numerator/denominator.
+    fn units_per_btc(&self, decimals: u8) -> Option<(u128, u128)> {
+        let num = u128::from(self.per_btc).checked_mul(10u128.checked_pow(u32::from(decimals))?)?;
+        let den = 10u128.checked_pow(u32::from(self.scale))?;
+        Some((num, den))
+    }
+
+    /// Token base units worth `msat`. `None` on overflow.
+    pub fn msat_to_units(&self, msat: u64, decimals: u8, rounding: Rounding) -> Option<u64> {
+        let (num, den) = self.units_per_btc(decimals)?;
+        let n = u128::from(msat).checked_mul(num)?;
+        let d = den.checked_mul(u128::from(MSAT_PER_BTC))?;
+        u64::try_from(div_round(n, d, rounding)).ok()
+    }
+
+    /// Millisatoshis worth `units` token base units. `None` on overflow or a zero price.
+    pub fn units_to_msat(&self, units: u64, decimals: u8, rounding: Rounding) -> Option<u64> {
+        let (num, den) = self.units_per_btc(decimals)?;
+        if num == 0 {
+            return None;
+        }
+        let n = u128::from(units).checked_mul(den)?.checked_mul(u128::from(MSAT_PER_BTC))?;
+        u64::try_from(div_round(n, num, rounding)).ok()
+    }
+}
+
+/// Largest escrow whose total (net plus fees) fits in `budget`, e.g. to quote from a payer's balance.
+pub fn amounts_for_budget(budget: u64, platform_fee_bps: u16, trade_fee_bps: u16) -> Option<EscrowAmounts> {
+    let total_bps = 10_000u128 + u128::from(platform_fee_bps) + u128::from(trade_fee_bps);
+    // Fees round down, so this estimate is at most a few units below the answer; walk up to it.
+    let mut net = u64::try_from(u128::from(budget) * 10_000 / total_bps).ok()?;
+    let mut best = EscrowAmounts::compute(net, platform_fee_bps, trade_fee_bps)?;
+    while let Some(next) = net
+        .checked_add(1)
+        .and_then(|n| EscrowAmounts::compute(n, platform_fee_bps, trade_fee_bps))
+        .filter(|a| a.total_amount <= budget)
+    {
+        net += 1;
+        best = next;
+    }
+    Some(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(s: &str) -> Price {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_plain_decimals() {
+        assert_eq!(
+            price("65000"),
+            Price {
+                per_btc: 65_000,
+                scale: 0
+            }
+        );
+        assert_eq!(
+            price("65000.25"),
+            Price {
+                per_btc: 6_500_025,
+                scale: 2
+            }
+        );
+        assert_eq!(
+            price("65000.250"),
+            Price {
+                per_btc: 65_000_250,
+                scale: 3
+            }
+        );
+        assert_eq!(price("0.05"), Price { per_btc: 5, scale: 2 });
+        assert_eq!(price("007"), Price { per_btc: 7, scale: 0 });
+        assert_eq!(
+            price(&format!("0.{}1", "0".repeat(17))),
+            Price { per_btc: 1, scale: 18 }
+        );
+    }
+
+    #[test]
+    fn rejects_anything_else() {
+        for bad in [
+            "",
+            ".",
+            "65000.",
+            ".25",
+            "0",
+            "0.000",
+            "-1",
+            "+1",
+            " 1",
+            "1 ",
+            "1e5",
+            "1,5",
+            "1.2.3",
+            "0x10",
+            "18446744073709551616",
+        ] {
+            assert_eq!(bad.parse::<Price>(), Err(ParsePriceError), "{bad:?}");
+        }
+        let too_fine = format!("0.{}1", "0".repeat(18));
+        assert_eq!(too_fine.parse::<Price>(), Err(ParsePriceError));
+    }
+
+    #[test]
+    fn displays_as_parsed() {
+        for s in ["65000", "65000.25", "65000.250", "0.05", "1.000000000000000001"] {
+            assert_eq!(price(s).to_string(), s);
+        }
+    }
+
+    #[test]
+    fn msat_to_units_rounds_as_asked() {
+        // 1 msat at 65000 per BTC is 0.65 units of a 6-decimal token.
+        let p = price("65000");
+        assert_eq!(p.msat_to_units(1, 6, Rounding::Down), Some(0));
+        assert_eq!(p.msat_to_units(1, 6, Rounding::Up), Some(1));
+        assert_eq!(p.msat_to_units(1, 6, Rounding::Nearest), Some(1));
+        // Exact amounts are the same every way.
+        for rounding in [Rounding::Down, Rounding::Up, Rounding::Nearest] {
+            assert_eq!(p.msat_to_units(100_000_000, 6, rounding), Some(65_000_000));
+        }
+        assert_eq!(
+            price("65000.25").msat_to_units(MSAT_PER_BTC, 6, Rounding::Down),
+            Some(65_000_250_000)
+        );
+    }
+
+    #[test]
+    fn nearest_rounds_half_away_from_zero() {
+        // One token per BTC with no decimals: half a BTC is half a unit.
+        let p = price("1");
+        let half = MSAT_PER_BTC / 2;
+        assert_eq!(p.msat_to_units(half, 0, Rounding::Nearest), Some(1));
+        assert_eq!(p.msat_to_units(half - 1, 0, Rounding::Nearest), Some(0));
+        assert_eq!(p.msat_to_units(half, 0, Rounding::Down), Some(0));
+        assert_eq!(p.msat_to_units(half + MSAT_PER_BTC, 0, Rounding::Nearest), Some(2));
+    }
+
+    #[test]
+    fn units_to_msat_rounds_as_asked() {
+        // 1 unit of a 6-decimal token at 65000 per BTC is 1.538... msat.
+        let p = price("65000");
+        assert_eq!(p.units_to_msat(1, 6, Rounding::Down), Some(1));
+        assert_eq!(p.units_to_msat(1, 6, Rounding::Up), Some(2));
+        assert_eq!(p.units_to_msat(1, 6, Rounding::Nearest), Some(2));
+        assert_eq!(p.units_to_msat(65_000_000, 6, Rounding::Down), Some(100_000_000));
+        // Rounding up never gives the payer less than the units are worth.
+        let msat = p.units_to_msat(1_234_567, 6, Rounding::Up).unwrap();
+        assert!(p.msat_to_units(msat, 6, Rounding::Down).unwrap() >= 1_234_567);
+    }
+
+    #[test]
+    fn overflow_is_none() {
+        let p = Price {
+            per_btc: u64::MAX,
+            scale: 0,
+        };
+        assert_eq!(p.msat_to_units(u64::MAX, 18, Rounding::Down), None);
+        assert_eq!(price("1").msat_to_units(1, 39, Rounding::Down), None);
+        assert_eq!(price("0.000001").units_to_msat(u64::MAX, 0, Rounding::Down), None);
+        assert_eq!(Price { per_btc: 0, scale: 0 }.units_to_msat(1, 6, Rounding::Down), None);
+    }
+
+    #[test]
+    fn budget_buys_the_largest_escrow_that_fits() {
+        for (budget, platform, trade) in [(10_000, 50, 30), (1, 0, 0), (999_999, 100, 0), (0, 50, 50)] {
+            let amounts = amounts_for_budget(budget, platform, trade).unwrap();
+            assert!(amounts.total_amount <= budget);
+            let next = EscrowAmounts::compute(amounts.net_amount + 1, platform, trade).unwrap();
+            assert!(next.total_amount > budget, "{budget} {platform} {trade}");
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! Chunked, bounded-concurrency account fetching for large escrow lists.
+//!
+//! Listing goes in two steps: [`EscrowClient::escrow_addresses`] runs the `getProgramAccounts` filters with a
+//! zero-length data slice (addresses only), then [`EscrowClient::stream_escrows`] fetches the accounts in
+//! `getMultipleAccounts` pages and yields each page as it arrives, so memory stays bounded by
+//! `chunk_size * concurrency` decoded accounts.
+
+use std::collections::HashSet;
+
+use futures::stream::{self, Stream, StreamExt};
+use solana_account_decoder::UiDataSliceConfig;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    client::{EscrowClient, FetchError},
+    filters::EscrowQuery,
+    pda,
+    state::{self, EscrowState},
+};
+
+/// Most accounts a single `getMultipleAccounts` request may ask for.
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchOptions {
+    /// Accounts per request, capped at [`MAX_MULTIPLE_ACCOUNTS`].
+    pub chunk_size: usize,
+    /// Requests in flight at once.
+    pub concurrency: usize,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: MAX_MULTIPLE_ACCOUNTS,
+            concurrency: 4,
+        }
+    }
+}
+
+fn dedup(addresses: impl IntoIterator<Item = Pubkey>) -> Vec<Pubkey> {
+    let mut seen = HashSet::new();
+    addresses.into_iter().filter(|a| seen.insert(*a)).collect()
+}
+
+impl EscrowClient {
+    /// Addresses of escrows matching `query`'s server-side filters, without account data.
+    pub async fn escrow_addresses(&self, query: &EscrowQuery) -> Result<Vec<Pubkey>, FetchError> {
+        let mut out = Vec::new();
+        for mut config in query.configs(self.commitment()) {
+            config.account_config.data_slice = Some(UiDataSliceConfig { offset: 0, length: 0 });
+            let accounts = self
+                .rpc()
+                .get_program_accounts_with_config(self.program_id(), config)
+                .await?;
+            out.extend(accounts.into_iter().map(|(address, _)| address));
+        }
+        Ok(dedup(out))
+    }
+
+    /// Fetches escrows at `addresses` (deduplicated) page by page, in input order. Closed or missing accounts
+    /// are skipped; a page that fails to fetch or decode yields an error and the stream continues.
+    pub fn stream_escrows(
+        &self,
+        addresses: impl IntoIterator<Item = Pubkey>,
+        opts: BatchOptions,
+    ) -> impl Stream<Item = Result<Vec<(Pubkey, EscrowState)>, FetchError>> + '_ {
+        let chunk_size = opts.chunk_size.clamp(1, MAX_MULTIPLE_ACCOUNTS);
+        let chunks: Vec<Vec<Pubkey>> = dedup(addresses).chunks(chunk_size).map(<[Pubkey]>::to_vec).collect();
+        stream::iter(chunks)
+            .map(move |chunk| async move {
+                let accounts = self
+                    .rpc()
+                    .get_multiple_accounts_with_commitment(&chunk, self.commitment())
+                    .await?
+                    .value;
+                chunk
+                    .into_iter()
+                    .zip(accounts)
+                    .filter_map(|(address, account)| account.map(|a| (address, a)))
+                    .map(|(address, account)| {
+                        state::decode_escrow(&account.data)
+                            .map(|s| (address, s))
+                            .map_err(|source| FetchError::Decode { address, source })
+                    })
+                    .collect()
+            })
+            .buffered(opts.concurrency.max(1))
+    }
+
+    /// Escrows for `payment_hashes`, paged like [`Self::stream_escrows`].
+    pub fn stream_escrows_by_hash<'a>(
+        &'a self,
+        payment_hashes: &[[u8; 32]],
+        opts: BatchOptions,
+    ) -> impl Stream<Item = Result<Vec<(Pubkey, EscrowState)>, FetchError>> + 'a {
+        let addresses: Vec<Pubkey> = payment_hashes
+            .iter()
+            .map(|h| pda::find_escrow_pda(self.program_id(), h).0)
+            .collect();
+        self.stream_escrows(addresses, opts)
+    }
+
+    /// Paged equivalent of [`Self::list_escrows`] for large result sets.
+    pub async fn list_escrows_paged<'a>(
+        &'a self,
+        query: &'a EscrowQuery,
+        opts: BatchOptions,
+    ) -> Result<impl Stream<Item = Result<Vec<(Pubkey, EscrowState)>, FetchError>> + 'a, FetchError> {
+        let addresses = self.escrow_addresses(query).await?;
+        Ok(self
+            .stream_escrows(addresses, opts)
+            .map(move |page| page.map(|escrows| escrows.into_iter().filter(|(_, s)| query.matches(s)).collect())))
+    }
+}
@@ -0,0 +1,130 @@
+//! Cross-checks a BOLT11 invoice against the escrow that is supposed to back it.
+
+use std::{fmt, str::FromStr};
+
+use lightning_invoice::Bolt11Invoice;
+
+use crate::state::{EscrowState, EscrowStatus};
+
+const MSAT_PER_BTC: u128 = 100_000_000_000;
+
+/// Default gap required between invoice expiry and `refund_after`, matching the JS tooling's minimum refund window.
+pub const DEFAULT_REFUND_MARGIN_SECS: i64 = 3600;
+
+/// Quoted price: token base units paid per whole BTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    pub token_units_per_btc: u64,
+    /// Allowed deviation of the escrowed net amount from the rate-implied amount.
+    pub tolerance_bps: u16,
+}
+
+impl Rate {
+    /// Token base units implied by `amount_msat` at this rate (rounded down).
+    pub fn token_amount(&self, amount_msat: u64) -> u64 {
+        let v = u128::from(amount_msat) * u128::from(self.token_units_per_btc) / MSAT_PER_BTC;
+        u64::try_from(v).unwrap_or(u64::MAX)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvoiceCheck {
+    /// Invoice must expire at least this many seconds before the escrow becomes refundable.
+    pub refund_margin_secs: i64,
+    pub rate: Option<Rate>,
+}
+
+impl Default for InvoiceCheck {
+    fn default() -> Self {
+        Self {
+            refund_margin_secs: DEFAULT_REFUND_MARGIN_SECS,
+            rate: None,
+        }
+    }
+}
+
+/// Fields of the invoice the checks looked at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckedInvoice {
+    pub payment_hash: [u8; 32],
+    pub amount_msat: Option<u64>,
+    pub timestamp: i64,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvoiceError {
+    Parse(String),
+    EscrowNotActive(EscrowStatus),
+    PaymentHashMismatch,
+    /// Invoice is still payable when (or after) the escrow can be refunded, minus the margin.
+    ExpiryTooLate { expires_at: i64, refund_after: i64, margin_secs: i64 },
+    MissingAmount,
+    AmountMismatch { expected: u64, actual: u64 },
+}
+
+impl fmt::Display for InvoiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(e) => write!(f, "invalid bolt11 invoice: {e}"),
+            Self::EscrowNotActive(s) => write!(f, "escrow is not active ({s:?})"),
+            Self::PaymentHashMismatch => f.write_str("bolt11 payment_hash mismatch vs escrow"),
+            Self::ExpiryTooLate {
+                expires_at,
+                refund_after,
+                margin_secs,
+            } => write!(
+                f,
+                "bolt11 expires at {expires_at}, less than {margin_secs}s before escrow refund_after {refund_after}"
+            ),
+            Self::MissingAmount => f.write_str("bolt11 has no amount; cannot check it against the rate"),
+            Self::AmountMismatch { expected, actual } => {
+                write!(f, "escrow net amount {actual} does not match rate-implied amount {expected}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvoiceError {}
+
+/// Answers "is this escrow actually backing this invoice?" before a taker pays it.
+pub fn check_invoice(bolt11: &str, escrow: &EscrowState, check: &InvoiceCheck) -> Result<CheckedInvoice, InvoiceError> {
+    let invoice = Bolt11Invoice::from_str(bolt11.trim()).map_err(|e| InvoiceError::Parse(e.to_string()))?;
+    let hash: &[u8] = invoice.payment_hash().as_ref();
+    let payment_hash: [u8; 32] = hash.try_into().map_err(|_| InvoiceError::Parse("payment hash".into()))?;
+    let timestamp = i64::try_from(invoice.duration_since_epoch().as_secs()).unwrap_or(i64::MAX);
+    let expires_at = timestamp.saturating_add(i64::try_from(invoice.expiry_time().as_secs()).unwrap_or(i64::MAX));
+    let checked = CheckedInvoice {
+        payment_hash,
+        amount_msat: invoice.amount_milli_satoshis(),
+        timestamp,
+        expires_at,
+    };
+
+    if escrow.status != EscrowStatus::Active {
+        return Err(InvoiceError::EscrowNotActive(escrow.status));
+    }
+    if checked.payment_hash != escrow.payment_hash {
+        return Err(InvoiceError::PaymentHashMismatch);
+    }
+    if checked.expires_at.saturating_add(check.refund_margin_secs) > escrow.refund_after {
+        return Err(InvoiceError::ExpiryTooLate {
+            expires_at: checked.expires_at,
+            refund_after: escrow.refund_after,
+            margin_secs: check.refund_margin_secs,
+        });
+    }
+    if let Some(rate) = check.rate {
+        let amount_msat = checked.amount_msat.ok_or(InvoiceError::MissingAmount)?;
+        let expected = rate.token_amount(amount_msat);
+        let slack = u128::from(expected) * u128::from(rate.tolerance_bps) / 10_000;
+        let diff = u128::from(expected.abs_diff(escrow.net_amount));
+        if diff > slack {
+            return Err(InvoiceError::AmountMismatch {
+                expected,
+                actual: escrow.net_amount,
+            });
+        }
+    }
+    Ok(checked)
+}
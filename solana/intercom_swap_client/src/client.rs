@@ -0,0 +1,182 @@
+//! Async RPC client returning typed escrow/config state.
+
+use std::{fmt, sync::Arc};
+
+use solana_client::{client_error::ClientError, nonblocking::rpc_client::RpcClient};
+use solana_sdk::{commitment_config::CommitmentConfig, program_pack::Pack, pubkey::Pubkey};
+
+use crate::{
+    filters::{self, EscrowQuery},
+    pda,
+    state::{self, ConfigState, DecodeError, EscrowState, EscrowIndexState, TradeConfigState},
+    PROGRAM_ID,
+};
+
+#[derive(Debug)]
+pub enum FetchError {
+    Rpc(ClientError),
+    Decode { address: Pubkey, source: DecodeError },
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rpc(e) => write!(f, "rpc error: {e}"),
+            Self::Decode { address, source } => write!(f, "failed to decode account {address}: {source}"),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Rpc(e) => Some(e),
+            Self::Decode { source, .. } => Some(source),
+        }
+    }
+}
+
+impl From<ClientError> for FetchError {
+    fn from(e: ClientError) -> Self {
+        Self::Rpc(e)
+    }
+}
+
+/// Typed read access to escrow program accounts.
+#[derive(Clone)]
+pub struct EscrowClient {
+    rpc: Arc<RpcClient>,
+    program_id: Pubkey,
+    commitment: CommitmentConfig,
+}
+
+impl EscrowClient {
+    /// Connects to `rpc_url` for the production program at `confirmed` commitment.
+    pub fn new(rpc_url: impl Into<String>) -> Self {
+        let commitment = CommitmentConfig::confirmed();
+        Self::with_rpc(Arc::new(RpcClient::new_with_commitment(rpc_url.into(), commitment)), PROGRAM_ID)
+    }
+
+    pub fn with_rpc(rpc: Arc<RpcClient>, program_id: Pubkey) -> Self {
+        let commitment = rpc.commitment();
+        Self {
+            rpc,
+            program_id,
+            commitment,
+        }
+    }
+
+    pub fn with_commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    pub fn rpc(&self) -> &Arc<RpcClient> {
+        &self.rpc
+    }
+
+    pub fn program_id(&self) -> &Pubkey {
+        &self.program_id
+    }
+
+    pub fn commitment(&self) -> CommitmentConfig {
+        self.commitment
+    }
+
+    async fn account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>, FetchError> {
+        let resp = self.rpc.get_account_with_commitment(address, self.commitment).await?;
+        Ok(resp.value.map(|a| a.data))
+    }
+
+    async fn decode_at<T>(
+        &self,
+        address: Pubkey,
+        decode: impl FnOnce(&[u8]) -> Result<T, DecodeError>,
+    ) -> Result<Option<T>, FetchError> {
+        match self.account_data(&address).await? {
+            Some(data) => decode(&data)
+                .map(Some)
+                .map_err(|source| FetchError::Decode { address, source }),
+            None => Ok(None),
+        }
+    }
+
+    /// Escrow keyed by `payment_hash`, or `None` if it was never initialized.
+    pub async fn get_escrow(&self, payment_hash: &[u8; 32]) -> Result<Option<EscrowState>, FetchError> {
+        let address = pda::find_escrow_pda(&self.program_id, payment_hash).0;
+        self.decode_at(address, state::decode_escrow).await
+    }
+
+    pub async fn get_config(&self) -> Result<Option<ConfigState>, FetchError> {
+        let address = pda::find_config_pda(&self.program_id).0;
+        self.decode_at(address, state::decode_config).await
+    }
+
+    pub async fn get_trade_config(&self, fee_collector: &Pubkey) -> Result<Option<TradeConfigState>, FetchError> {
+        let address = pda::find_trade_config_pda(&self.program_id, fee_collector).0;
+        self.decode_at(address, state::decode_trade_config).await
+    }
+
+    /// Most recent escrows recorded for `recipient` by the on-chain index (no program scan).
+    pub async fn get_recipient_index(&self, recipient: &Pubkey) -> Result<Option<EscrowIndexState>, FetchError> {
+        let address = pda::find_index_pda(&self.program_id, pda::RECIPIENT_INDEX_SEED, recipient).0;
+        self.decode_at(address, state::decode_escrow_index).await
+    }
+
+    /// Most recent escrows recorded for `refund` by the on-chain index (no program scan).
+    pub async fn get_refund_index(&self, refund: &Pubkey) -> Result<Option<EscrowIndexState>, FetchError> {
+        let address = pda::find_index_pda(&self.program_id, pda::REFUND_INDEX_SEED, refund).0;
+        self.decode_at(address, state::decode_escrow_index).await
+    }
+
+    /// Platform fee vault balance for `mint` (0 if the vault ATA does not exist yet).
+    pub async fn get_fee_vault_balance(&self, mint: &Pubkey) -> Result<u64, FetchError> {
+        let config = pda::find_config_pda(&self.program_id).0;
+        self.token_balance(pda::fee_vault_ata_for(&config, mint)).await
+    }
+
+    /// Trade fee vault balance of `fee_collector` for `mint` (0 if the vault ATA does not exist yet).
+    pub async fn get_trade_fee_vault_balance(&self, fee_collector: &Pubkey, mint: &Pubkey) -> Result<u64, FetchError> {
+        let trade_config = pda::find_trade_config_pda(&self.program_id, fee_collector).0;
+        self.token_balance(pda::fee_vault_ata_for(&trade_config, mint)).await
+    }
+
+    /// Balance of `owner`'s associated token account for `mint` (0 if it does not exist yet).
+    pub async fn get_token_balance(&self, owner: &Pubkey, mint: &Pubkey) -> Result<u64, FetchError> {
+        self.token_balance(spl_associated_token_account::get_associated_token_address(owner, mint)).await
+    }
+
+    async fn token_balance(&self, address: Pubkey) -> Result<u64, FetchError> {
+        let balance = self
+            .decode_at(address, |data| {
+                spl_token::state::Account::unpack(data)
+                    .map(|a| a.amount)
+                    .map_err(|_| DecodeError::Invalid("token"))
+            })
+            .await?;
+        Ok(balance.unwrap_or(0))
+    }
+
+    /// Runs `query` over every escrow layout and returns the matching escrows with their addresses.
+    pub async fn list_escrows(&self, query: &EscrowQuery) -> Result<Vec<(Pubkey, EscrowState)>, FetchError> {
+        let mut out = Vec::new();
+        for config in query.configs(self.commitment) {
+            let accounts = self
+                .rpc
+                .get_program_accounts_with_config(&self.program_id, config)
+                .await?;
+            for (address, account) in accounts {
+                let state =
+                    state::decode_escrow(&account.data).map_err(|source| FetchError::Decode { address, source })?;
+                if query.matches(&state) {
+                    out.push((address, state));
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    pub async fn list_active_by_recipient(&self, recipient: &Pubkey) -> Result<Vec<(Pubkey, EscrowState)>, FetchError> {
+        self.list_escrows(&filters::active_by_recipient(*recipient)).await
+    }
+}
@@ -0,0 +1,217 @@
+//! Send-and-confirm pipeline with commitment tracking and expiry detection.
+
+use std::time::{Duration, Instant};
+
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_sdk::{
+    clock::Slot,
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{uses_durable_nonce, Transaction, TransactionError},
+};
+use solana_transaction_status::TransactionConfirmationStatus;
+
+use crate::client::{EscrowClient, FetchError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfirmOptions {
+    /// Commitment at which the transaction counts as confirmed.
+    pub target: CommitmentLevel,
+    pub timeout: Duration,
+    pub poll_interval: Duration,
+    /// Rebroadcast the same signed transaction at this interval while it is unseen.
+    pub resend_interval: Option<Duration>,
+    pub skip_preflight: bool,
+}
+
+impl Default for ConfirmOptions {
+    fn default() -> Self {
+        Self {
+            target: CommitmentLevel::Confirmed,
+            timeout: Duration::from_secs(90),
+            poll_interval: Duration::from_millis(500),
+            resend_interval: Some(Duration::from_secs(2)),
+            skip_preflight: false,
+        }
+    }
+}
+
+/// Progress reported to the caller whenever the observed status changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "event", rename_all = "snake_case")
+)]
+pub enum ConfirmProgress {
+    Sent {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+        signature: Signature,
+    },
+    Resent {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+        signature: Signature,
+        attempt: u32,
+    },
+    Seen {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+        signature: Signature,
+        slot: Slot,
+        status: CommitmentLevel,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "outcome", rename_all = "snake_case")
+)]
+pub enum ConfirmOutcome {
+    /// Reached the target commitment without error.
+    Confirmed {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+        signature: Signature,
+        slot: Slot,
+    },
+    /// Landed but the program (or runtime) rejected it; fees were charged.
+    Failed {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+        signature: Signature,
+        slot: Slot,
+        error: TransactionError,
+    },
+    /// Never landed and can no longer land: its blockhash expired (or its durable nonce moved on).
+    Dropped {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+        signature: Signature,
+    },
+    /// Still pending when `timeout` elapsed; it may yet land.
+    TimedOut {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+        signature: Signature,
+        last_seen: Option<CommitmentLevel>,
+    },
+}
+
+impl ConfirmOutcome {
+    pub fn signature(&self) -> &Signature {
+        match self {
+            Self::Confirmed { signature, .. }
+            | Self::Failed { signature, .. }
+            | Self::Dropped { signature }
+            | Self::TimedOut { signature, .. } => signature,
+        }
+    }
+}
+
+fn level_of(status: &Option<TransactionConfirmationStatus>) -> CommitmentLevel {
+    match status {
+        Some(TransactionConfirmationStatus::Finalized) => CommitmentLevel::Finalized,
+        Some(TransactionConfirmationStatus::Confirmed) => CommitmentLevel::Confirmed,
+        _ => CommitmentLevel::Processed,
+    }
+}
+
+fn rank(level: CommitmentLevel) -> u8 {
+    match level {
+        CommitmentLevel::Finalized => 2,
+        CommitmentLevel::Confirmed => 1,
+        _ => 0,
+    }
+}
+
+impl EscrowClient {
+    /// Sends a signed `tx` and tracks it until it reaches `opts.target`, fails, expires or times out.
+    pub async fn send_and_confirm(
+        &self,
+        tx: &Transaction,
+        opts: &ConfirmOptions,
+        mut progress: impl FnMut(&ConfirmProgress),
+    ) -> Result<ConfirmOutcome, FetchError> {
+        let rpc = self.rpc();
+        let send_config = RpcSendTransactionConfig {
+            skip_preflight: opts.skip_preflight,
+            preflight_commitment: Some(self.commitment().commitment),
+            max_retries: Some(0),
+            ..RpcSendTransactionConfig::default()
+        };
+        let signature = rpc.send_transaction_with_config(tx, send_config).await?;
+        progress(&ConfirmProgress::Sent { signature });
+
+        // Durable nonce transactions never expire by blockhash; they die when the nonce account moves on.
+        let nonce_account: Option<Pubkey> = uses_durable_nonce(tx)
+            .and_then(|ix| ix.accounts.first())
+            .and_then(|idx| tx.message.account_keys.get(*idx as usize))
+            .copied();
+
+        let started = Instant::now();
+        let mut last_sent = Instant::now();
+        let mut attempt = 0u32;
+        let mut last_seen: Option<CommitmentLevel> = None;
+        loop {
+            let status = rpc.get_signature_statuses(&[signature]).await?.value.into_iter().next().flatten();
+            if let Some(status) = status {
+                let level = level_of(&status.confirmation_status);
+                if last_seen != Some(level) {
+                    last_seen = Some(level);
+                    progress(&ConfirmProgress::Seen {
+                        signature,
+                        slot: status.slot,
+                        status: level,
+                    });
+                }
+                if let Some(error) = status.err {
+                    return Ok(ConfirmOutcome::Failed {
+                        signature,
+                        slot: status.slot,
+                        error,
+                    });
+                }
+                if rank(level) >= rank(opts.target) {
+                    return Ok(ConfirmOutcome::Confirmed {
+                        signature,
+                        slot: status.slot,
+                    });
+                }
+            } else if last_seen.is_none() && !self.still_landable(tx, nonce_account.as_ref()).await? {
+                // Re-check once: the transaction may have landed between the two calls.
+                let status = rpc.get_signature_statuses(&[signature]).await?.value.into_iter().next().flatten();
+                if status.is_none() {
+                    return Ok(ConfirmOutcome::Dropped { signature });
+                }
+                continue;
+            }
+
+            if started.elapsed() >= opts.timeout {
+                return Ok(ConfirmOutcome::TimedOut { signature, last_seen });
+            }
+            if let Some(interval) = opts.resend_interval {
+                if last_seen.is_none() && last_sent.elapsed() >= interval {
+                    attempt += 1;
+                    // Same signed bytes, so a rebroadcast can never double-execute.
+                    let resend_config = RpcSendTransactionConfig {
+                        skip_preflight: true,
+                        ..send_config
+                    };
+                    if rpc.send_transaction_with_config(tx, resend_config).await.is_ok() {
+                        progress(&ConfirmProgress::Resent { signature, attempt });
+                    }
+                    last_sent = Instant::now();
+                }
+            }
+            tokio::time::sleep(opts.poll_interval).await;
+        }
+    }
+
+    async fn still_landable(&self, tx: &Transaction, nonce_account: Option<&Pubkey>) -> Result<bool, FetchError> {
+        match nonce_account {
+            Some(nonce_account) => self.nonce_still_valid(tx, nonce_account).await,
+            None => Ok(self
+                .rpc()
+                .is_blockhash_valid(&tx.message.recent_blockhash, CommitmentConfig::processed())
+                .await?),
+        }
+    }
+}
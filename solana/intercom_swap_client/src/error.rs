@@ -0,0 +1,256 @@
+//! Program error codes (`ProgramError::Custom(n)`) as a typed enum, and [`SwapClientError`], which attaches the
+//! failing account and a suggested fix to SDK failures.
+
+use std::fmt;
+
+use solana_client::client_error::ClientError;
+use solana_sdk::{
+    instruction::InstructionError,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    transaction::{Transaction, TransactionError},
+};
+
+pub use intercom_swap_core::error::EscrowError;
+
+use crate::{client::FetchError, preflight::Violation};
+
+/// Extracts the escrow error from `ProgramError::Custom`; `None` for other errors and unknown codes.
+pub fn from_program_error(err: &ProgramError) -> Option<EscrowError> {
+    match err {
+        ProgramError::Custom(code) => EscrowError::from_code(*code),
+        _ => None,
+    }
+}
+
+/// Extracts the escrow error from a failed transaction, if the failing instruction returned a known custom code.
+pub fn from_transaction_error(err: &TransactionError) -> Option<EscrowError> {
+    match err {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => EscrowError::from_code(*code),
+        _ => None,
+    }
+}
+
+/// What to change so the program stops failing with `err`.
+pub fn remediation(err: EscrowError) -> &'static str {
+    match err {
+        EscrowError::InvalidInstruction => {
+            "rebuild the instruction with `instruction::*`; amount plus fees must fit in a u64"
+        }
+        EscrowError::InvalidEscrowPda => "derive the escrow with `pda::find_escrow_pda` from the payment hash",
+        EscrowError::InvalidVaultAta => "the vault must be the escrow PDA's associated token account for the mint",
+        EscrowError::InvalidTokenAccount => {
+            "check the token account's mint, owner and balance (Init debits net amount plus fees)"
+        }
+        EscrowError::InvalidSigner => {
+            "Claim is signed by the recipient, Refund by the refund key, config and withdrawals by the fee collector"
+        }
+        EscrowError::InvalidPreimage => "pass the 32-byte preimage whose SHA-256 is the payment hash",
+        EscrowError::NotActive => "the escrow was already claimed or refunded; fetch its state before settling",
+        EscrowError::TooEarly => "wait until cluster time passes refund_after (`EscrowClient::get_unix_timestamp`)",
+        EscrowError::InvalidConfigPda => "derive the config with `pda::find_config_pda` for this program id",
+        EscrowError::InvalidConfigState => "initialize the platform config (InitConfig) on this cluster first",
+        EscrowError::FeeTooHigh => "keep fees within MAX_PLATFORM_FEE_BPS, MAX_TRADE_FEE_BPS and MAX_TOTAL_FEE_BPS",
+        EscrowError::AlreadyInitialized => "the account already exists; use a new payment hash or the Set* instruction",
+        EscrowError::InvalidFeeVaultAta => "create the platform fee vault ATA (owner: config PDA) for this mint first",
+        EscrowError::InvalidTradeConfigPda => "derive the trade config with `pda::find_trade_config_pda`",
+        EscrowError::InvalidTradeConfigState => "the trade fee collector must run InitTradeConfig first",
+        EscrowError::InvalidTradeFeeVaultAta => {
+            "create the trade fee vault ATA (owner: trade config PDA) for this mint first"
+        }
+        EscrowError::FeeMismatch => "re-read both configs' fee_bps and rebuild Init with them as the expected fees",
+        EscrowError::InvalidIndexPda => "derive the index PDAs with `pda::find_index_pda` from recipient and refund",
+        EscrowError::InvalidIndexState => "the index account has an unexpected layout; check the program id",
+        EscrowError::InvalidCreator => "pass `escrow.creator` for v4 escrows and omit it for v3",
+    }
+}
+
+fn violation_hint(v: &Violation) -> Option<&'static str> {
+    match v {
+        Violation::EscrowMissing => Some("check the payment hash and program id; the escrow may be closed"),
+        Violation::TokenAccountMissing(_) => Some("create the associated token account and fund it first"),
+        Violation::InsufficientBalance { .. } => Some("fund the token account with net amount plus fees"),
+        Violation::VaultUnderfunded { .. } => Some("the vault no longer holds the escrowed amount; do not settle"),
+        other => other.escrow_error().map(remediation),
+    }
+}
+
+fn transaction_hint(err: &TransactionError) -> Option<&'static str> {
+    match err {
+        TransactionError::BlockhashNotFound => Some("rebuild and re-sign with a fresh blockhash"),
+        TransactionError::InsufficientFundsForFee | TransactionError::AccountNotFound => {
+            Some("fund the fee payer with SOL")
+        }
+        TransactionError::InstructionError(_, InstructionError::ComputationalBudgetExceeded) => {
+            Some("raise `TxOptions::compute_unit_limit`")
+        }
+        _ => None,
+    }
+}
+
+/// Account of the failing escrow instruction that an error code points at, by instruction tag. Accounts are
+/// in the order of the `instruction::*` builders.
+fn failing_account_index(tag: u8, err: EscrowError) -> Option<(&'static str, usize)> {
+    use EscrowError::*;
+    Some(match (tag, err) {
+        // Init (absolute or relative timelock).
+        (0 | 9, InvalidSigner) => ("payer", 0),
+        (0 | 9, InvalidTokenAccount) => ("payer token account", 1),
+        (0 | 9, InvalidEscrowPda | AlreadyInitialized) => ("escrow", 2),
+        (0 | 9, InvalidVaultAta) => ("vault", 3),
+        (0 | 9, InvalidConfigPda | InvalidConfigState) => ("config", 9),
+        (0 | 9, InvalidFeeVaultAta) => ("platform fee vault", 10),
+        (0 | 9, InvalidTradeConfigPda | InvalidTradeConfigState) => ("trade config", 11),
+        (0 | 9, InvalidTradeFeeVaultAta) => ("trade fee vault", 12),
+        // Claim and Refund share the first four positions.
+        (1 | 2, InvalidSigner) => (if tag == 1 { "recipient" } else { "refund" }, 0),
+        (1 | 2, InvalidEscrowPda | NotActive | InvalidPreimage | TooEarly) => ("escrow", 1),
+        (1 | 2, InvalidVaultAta) => ("vault", 2),
+        (1 | 2, InvalidTokenAccount) => ("destination token account", 3),
+        (1, InvalidFeeVaultAta) => ("platform fee vault", 4),
+        (1, InvalidTradeFeeVaultAta) => ("trade fee vault", 5),
+        (1, InvalidCreator) => ("creator", 7),
+        (2, InvalidCreator) => ("creator", 6),
+        // Config instructions.
+        (3 | 4 | 6 | 7, InvalidSigner) => ("authority", 0),
+        (3 | 4 | 6 | 7, _) => ("config", 1),
+        // Withdrawals.
+        (5 | 8, InvalidSigner) => ("fee collector", 0),
+        (5 | 8, InvalidConfigPda | InvalidConfigState | InvalidTradeConfigPda | InvalidTradeConfigState) => {
+            ("config", 1)
+        }
+        (5 | 8, InvalidFeeVaultAta | InvalidTradeFeeVaultAta) => ("fee vault", 2),
+        (5 | 8, InvalidTokenAccount) => ("destination token account", 3),
+        _ => return None,
+    })
+}
+
+/// An account named by a [`SwapClientError::Program`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FailedAccount {
+    pub role: &'static str,
+    pub address: Pubkey,
+}
+
+#[derive(Debug)]
+pub enum SwapClientError {
+    Fetch(FetchError),
+    /// An escrow instruction failed with one of the program's codes.
+    Program {
+        error: EscrowError,
+        /// Index of the failing instruction within the transaction.
+        instruction: u8,
+        account: Option<FailedAccount>,
+    },
+    /// Any other failure: runtime, fee payer, compute budget, or another program's instruction.
+    Transaction(TransactionError),
+    /// Caught client-side before sending; each violation carries expected and actual values.
+    Preflight(Vec<Violation>),
+}
+
+impl SwapClientError {
+    /// Classifies `err` from sending or simulating `tx` against `program_id`.
+    pub fn from_transaction(program_id: &Pubkey, tx: &Transaction, err: TransactionError) -> Self {
+        Self::program_failure(program_id, tx, &err).unwrap_or(Self::Transaction(err))
+    }
+
+    fn program_failure(program_id: &Pubkey, tx: &Transaction, err: &TransactionError) -> Option<Self> {
+        let TransactionError::InstructionError(index, InstructionError::Custom(code)) = err else {
+            return None;
+        };
+        let keys = &tx.message.account_keys;
+        let ix = tx
+            .message
+            .instructions
+            .get(*index as usize)
+            .filter(|ix| keys.get(ix.program_id_index as usize) == Some(program_id))?;
+        let error = EscrowError::from_code(*code)?;
+        let account = ix
+            .data
+            .first()
+            .and_then(|tag| failing_account_index(*tag, error))
+            .and_then(|(role, i)| {
+                let address = *keys.get(*ix.accounts.get(i)? as usize)?;
+                Some(FailedAccount { role, address })
+            });
+        Some(Self::Program {
+            error,
+            instruction: *index,
+            account,
+        })
+    }
+
+    /// The program error code behind this failure, if any.
+    pub fn escrow_error(&self) -> Option<EscrowError> {
+        match self {
+            Self::Program { error, .. } => Some(*error),
+            Self::Preflight(violations) => violations.iter().find_map(Violation::escrow_error),
+            Self::Fetch(_) | Self::Transaction(_) => None,
+        }
+    }
+
+    /// Suggested fix, when there is one beyond retrying.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::Fetch(_) => None,
+            Self::Program { error, .. } => Some(remediation(*error)),
+            Self::Transaction(err) => transaction_hint(err),
+            Self::Preflight(violations) => violations.iter().find_map(violation_hint),
+        }
+    }
+}
+
+impl fmt::Display for SwapClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fetch(e) => write!(f, "{e}")?,
+            Self::Program {
+                error,
+                instruction,
+                account,
+            } => {
+                write!(f, "instruction {instruction} failed: {error}")?;
+                if let Some(FailedAccount { role, address }) = account {
+                    write!(f, " ({role} {address})")?;
+                }
+            }
+            Self::Transaction(e) => write!(f, "transaction failed: {e}")?,
+            Self::Preflight(violations) => {
+                let reasons: Vec<String> = violations.iter().map(ToString::to_string).collect();
+                write!(f, "preflight failed: {}", reasons.join("; "))?;
+            }
+        }
+        match self.hint() {
+            Some(hint) => write!(f, "; fix: {hint}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for SwapClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Fetch(e) => Some(e),
+            Self::Transaction(e) => Some(e),
+            Self::Program { .. } | Self::Preflight(_) => None,
+        }
+    }
+}
+
+impl From<FetchError> for SwapClientError {
+    fn from(e: FetchError) -> Self {
+        Self::Fetch(e)
+    }
+}
+
+impl From<ClientError> for SwapClientError {
+    fn from(e: ClientError) -> Self {
+        Self::Fetch(e.into())
+    }
+}
+
+impl From<Vec<Violation>> for SwapClientError {
+    fn from(violations: Vec<Violation>) -> Self {
+        Self::Preflight(violations)
+    }
+}
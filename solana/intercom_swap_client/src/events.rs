@@ -0,0 +1,311 @@
+//! Typed escrow events recovered from transaction logs.
+//!
+//! The program emits each event with `sol_log_data`; this module walks the log lines, keeps `Program data:`
+//! payloads logged while the escrow program is the innermost invocation, and decodes them with
+//! `intercom-swap-core`. Failed transactions yield no events, since their state changes were rolled back.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use intercom_swap_core::events::EscrowEvent as WireEvent;
+pub use intercom_swap_core::FeeSource;
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::UiTransactionStatusMeta;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "event", rename_all = "snake_case")
+)]
+pub enum EscrowEvent {
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    EscrowInitialized {
+        #[cfg_attr(
+            feature = "serde",
+            serde(rename = "paymentHashHex", with = "crate::serde_fmt::hex32")
+        )]
+        payment_hash: [u8; 32],
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+        recipient: Pubkey,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+        refund: Pubkey,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+        mint: Pubkey,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+        trade_fee_collector: Pubkey,
+        refund_after: i64,
+        net_amount: u64,
+        platform_fee_amount: u64,
+        trade_fee_amount: u64,
+    },
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    Claimed {
+        #[cfg_attr(
+            feature = "serde",
+            serde(rename = "paymentHashHex", with = "crate::serde_fmt::hex32")
+        )]
+        payment_hash: [u8; 32],
+        /// Revealed on-chain; the payer side uses it to settle the Lightning invoice.
+        #[cfg_attr(feature = "serde", serde(rename = "preimageHex", with = "crate::serde_fmt::hex32"))]
+        preimage: [u8; 32],
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+        recipient: Pubkey,
+        net_amount: u64,
+        platform_fee_amount: u64,
+        trade_fee_amount: u64,
+    },
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    Refunded {
+        #[cfg_attr(
+            feature = "serde",
+            serde(rename = "paymentHashHex", with = "crate::serde_fmt::hex32")
+        )]
+        payment_hash: [u8; 32],
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+        refund: Pubkey,
+        amount: u64,
+    },
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    FeesWithdrawn {
+        source: FeeSource,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+        fee_collector: Pubkey,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+        mint: Pubkey,
+        amount: u64,
+    },
+}
+
+impl EscrowEvent {
+    /// Payment hash of the escrow the event belongs to (`None` for fee withdrawals).
+    pub fn payment_hash(&self) -> Option<&[u8; 32]> {
+        match self {
+            Self::EscrowInitialized { payment_hash, .. }
+            | Self::Claimed { payment_hash, .. }
+            | Self::Refunded { payment_hash, .. } => Some(payment_hash),
+            Self::FeesWithdrawn { .. } => None,
+        }
+    }
+}
+
+impl From<WireEvent> for EscrowEvent {
+    fn from(e: WireEvent) -> Self {
+        let pk = Pubkey::new_from_array;
+        match e {
+            WireEvent::EscrowInitialized {
+                payment_hash,
+                recipient,
+                refund,
+                mint,
+                trade_fee_collector,
+                refund_after,
+                net_amount,
+                platform_fee_amount,
+                trade_fee_amount,
+            } => Self::EscrowInitialized {
+                payment_hash,
+                recipient: pk(recipient),
+                refund: pk(refund),
+                mint: pk(mint),
+                trade_fee_collector: pk(trade_fee_collector),
+                refund_after,
+                net_amount,
+                platform_fee_amount,
+                trade_fee_amount,
+            },
+            WireEvent::Claimed {
+                payment_hash,
+                preimage,
+                recipient,
+                net_amount,
+                platform_fee_amount,
+                trade_fee_amount,
+            } => Self::Claimed {
+                payment_hash,
+                preimage,
+                recipient: pk(recipient),
+                net_amount,
+                platform_fee_amount,
+                trade_fee_amount,
+            },
+            WireEvent::Refunded {
+                payment_hash,
+                refund,
+                amount,
+            } => Self::Refunded {
+                payment_hash,
+                refund: pk(refund),
+                amount,
+            },
+            WireEvent::FeesWithdrawn {
+                source,
+                fee_collector,
+                mint,
+                amount,
+            } => Self::FeesWithdrawn {
+                source,
+                fee_collector: pk(fee_collector),
+                mint: pk(mint),
+                amount,
+            },
+        }
+    }
+}
+
+/// Events emitted by `program_id`, in log order, from a transaction's log messages.
+pub fn parse_logs<S: AsRef<str>>(program_id: &Pubkey, logs: &[S]) -> Vec<EscrowEvent> {
+    let program = program_id.to_string();
+    let mut stack: Vec<&str> = Vec::new();
+    let mut events = Vec::new();
+    for line in logs.iter().map(AsRef::as_ref) {
+        if let Some(data) = line.strip_prefix("Program data: ") {
+            if stack.last() == Some(&program.as_str()) {
+                events.extend(
+                    data.split(' ')
+                        .filter_map(|chunk| STANDARD.decode(chunk).ok())
+                        .filter_map(|bytes| WireEvent::unpack(&bytes))
+                        .map(EscrowEvent::from),
+                );
+            }
+        } else if let Some(rest) = line.strip_prefix("Program ") {
+            let mut words = rest.split(' ');
+            match (words.next(), words.next()) {
+                (Some(id), Some("invoke")) => stack.push(id),
+                (Some(_), Some("success")) => {
+                    stack.pop();
+                }
+                // Any failure aborts the whole transaction.
+                (Some(_), Some("failed:")) => return Vec::new(),
+                _ => {}
+            }
+        }
+    }
+    events
+}
+
+/// Events from a fetched transaction's status meta; empty if it failed or has no logs.
+pub fn parse_meta(program_id: &Pubkey, meta: &UiTransactionStatusMeta) -> Vec<EscrowEvent> {
+    if meta.err.is_some() {
+        return Vec::new();
+    }
+    let logs: Option<Vec<String>> = meta.log_messages.clone().into();
+    parse_logs(program_id, logs.as_deref().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use intercom_swap_core::events::MAX_EVENT_LEN;
+
+    use super::*;
+
+    fn program() -> Pubkey {
+        ln_usdt_escrow::ID
+    }
+
+    fn refunded(amount: u64) -> WireEvent {
+        WireEvent::Refunded {
+            payment_hash: [1; 32],
+            refund: [2; 32],
+            amount,
+        }
+    }
+
+    fn data(events: &[WireEvent]) -> String {
+        let chunks: Vec<String> = events
+            .iter()
+            .map(|event| {
+                let mut out = [0u8; MAX_EVENT_LEN];
+                let len = event.pack_into(&mut out);
+                STANDARD.encode(&out[..len])
+            })
+            .collect();
+        format!("Program data: {}", chunks.join(" "))
+    }
+
+    /// An invocation of `program` at `depth` logging `lines`, then succeeding.
+    fn invocation(program: &Pubkey, depth: u8, lines: Vec<String>) -> Vec<String> {
+        let mut logs = vec![format!("Program {program} invoke [{depth}]")];
+        logs.extend(lines);
+        logs.push(format!("Program {program} consumed 1000 of 200000 compute units"));
+        logs.push(format!("Program {program} success"));
+        logs
+    }
+
+    #[test]
+    fn decodes_events_in_log_order() {
+        let mut logs = invocation(
+            &program(),
+            1,
+            vec![data(&[refunded(1)]), data(&[refunded(2), refunded(3)])],
+        );
+        logs.extend(invocation(&program(), 1, vec![data(&[refunded(4)])]));
+        let amounts: Vec<u64> = parse_logs(&program(), &logs)
+            .iter()
+            .map(|e| match e {
+                EscrowEvent::Refunded { amount, .. } => *amount,
+                other => panic!("{other:?}"),
+            })
+            .collect();
+        assert_eq!(amounts, [1, 2, 3, 4]);
+        assert_eq!(parse_logs(&program(), &logs)[0].payment_hash(), Some(&[1; 32]));
+    }
+
+    #[test]
+    fn ignores_data_logged_by_other_programs() {
+        let other = Pubkey::new_unique();
+        let mut inner = vec![data(&[refunded(1)])];
+        // An inner call logging what looks like an escrow event, then the escrow program again.
+        inner.extend(invocation(&other, 2, vec![data(&[refunded(2)])]));
+        inner.push(data(&[refunded(3)]));
+        let mut logs = invocation(&other, 1, vec![data(&[refunded(4)])]);
+        logs.extend(invocation(&program(), 1, inner));
+        logs.push(data(&[refunded(5)]));
+        assert_eq!(
+            parse_logs(&program(), &logs),
+            [refunded(1), refunded(3)].map(EscrowEvent::from)
+        );
+    }
+
+    #[test]
+    fn skips_malformed_payloads() {
+        let event = data(&[refunded(1)]);
+        let valid = event.strip_prefix("Program data: ").unwrap();
+        let mut payload = [0u8; MAX_EVENT_LEN];
+        let len = refunded(1).pack_into(&mut payload);
+        let truncated = STANDARD.encode(&payload[..len - 1]);
+        let logs = invocation(
+            &program(),
+            1,
+            vec![
+                format!("Program data: not*base64 {valid}"),
+                format!("Program data: {truncated}"),
+                format!("Program data: {}", STANDARD.encode(b"some other program's data")),
+                "Program data: ".to_string(),
+                "Program data:".to_string(),
+                "Program log: Program data: ".to_string() + valid,
+            ],
+        );
+        assert_eq!(parse_logs(&program(), &logs), [EscrowEvent::from(refunded(1))]);
+    }
+
+    #[test]
+    fn truncated_logs_keep_the_events_before_the_cut() {
+        let mut logs = invocation(&program(), 1, vec![data(&[refunded(1)])]);
+        logs.truncate(2);
+        logs.push("Log truncated".to_string());
+        assert_eq!(parse_logs(&program(), &logs), [EscrowEvent::from(refunded(1))]);
+        // Data with no invocation on record is nobody's.
+        assert!(parse_logs(&program(), &[data(&[refunded(1)])]).is_empty());
+        assert!(parse_logs::<&str>(&program(), &[]).is_empty());
+    }
+
+    #[test]
+    fn failed_transactions_yield_nothing() {
+        let mut logs = invocation(&program(), 1, vec![data(&[refunded(1)])]);
+        logs.extend([
+            format!("Program {} invoke [1]", program()),
+            data(&[refunded(2)]),
+            format!("Program {} failed: custom program error: 0x1", program()),
+        ]);
+        assert!(parse_logs(&program(), &logs).is_empty());
+    }
+}
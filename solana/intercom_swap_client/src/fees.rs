@@ -0,0 +1,144 @@
+//! Priority fee recommendations from recent prioritization fees on the accounts an instruction writes.
+
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+use crate::{
+    client::{EscrowClient, FetchError},
+    instruction::InitArgs,
+    state::EscrowState,
+    transaction::{self, TxOptions, CLAIM_COMPUTE_UNITS, INIT_COMPUTE_UNITS, REFUND_COMPUTE_UNITS},
+};
+
+/// Percentile of recent non-zero fees to pay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PriorityLevel {
+    Low,
+    #[default]
+    Medium,
+    High,
+    /// Settlement close to its deadline.
+    Urgent,
+}
+
+impl PriorityLevel {
+    fn percentile(self) -> usize {
+        match self {
+            Self::Low => 25,
+            Self::Medium => 50,
+            Self::High => 75,
+            Self::Urgent => 95,
+        }
+    }
+}
+
+/// Bounds applied to the recommended price (micro-lamports per compute unit).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeBounds {
+    pub min_micro_lamports: u64,
+    pub max_micro_lamports: u64,
+}
+
+impl Default for FeeBounds {
+    fn default() -> Self {
+        Self {
+            min_micro_lamports: 1_000,
+            max_micro_lamports: 5_000_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeRecommendation {
+    pub compute_unit_limit: u32,
+    pub compute_unit_price_micro_lamports: u64,
+    /// Number of recent slots with a non-zero fee the recommendation is based on.
+    pub samples: usize,
+}
+
+impl FeeRecommendation {
+    /// Priority fee in lamports if the whole limit is consumed.
+    pub fn max_priority_fee_lamports(&self) -> u64 {
+        (self.compute_unit_limit as u128 * self.compute_unit_price_micro_lamports as u128 / 1_000_000) as u64
+    }
+
+    pub fn tx_options(&self) -> TxOptions {
+        TxOptions {
+            compute_unit_limit: Some(self.compute_unit_limit),
+            compute_unit_price_micro_lamports: Some(self.compute_unit_price_micro_lamports),
+        }
+    }
+}
+
+/// Picks the `level` percentile of non-zero `fees`, clamped to `bounds`.
+pub fn recommend_price(fees: &[u64], level: PriorityLevel, bounds: FeeBounds) -> u64 {
+    let mut nonzero: Vec<u64> = fees.iter().copied().filter(|f| *f > 0).collect();
+    if nonzero.is_empty() {
+        return bounds.min_micro_lamports;
+    }
+    nonzero.sort_unstable();
+    let idx = (nonzero.len() - 1) * level.percentile() / 100;
+    nonzero[idx].clamp(bounds.min_micro_lamports, bounds.max_micro_lamports)
+}
+
+fn writable_accounts(ixs: &[Instruction]) -> Vec<Pubkey> {
+    let mut out: Vec<Pubkey> = Vec::new();
+    for meta in ixs.iter().flat_map(|ix| ix.accounts.iter()) {
+        if meta.is_writable && !out.contains(&meta.pubkey) {
+            out.push(meta.pubkey);
+        }
+    }
+    out
+}
+
+impl EscrowClient {
+    /// Recommends a compute unit price for `ixs` from recent fees paid on the accounts they write.
+    pub async fn recommend_fee(
+        &self,
+        ixs: &[Instruction],
+        compute_unit_limit: u32,
+        level: PriorityLevel,
+        bounds: FeeBounds,
+    ) -> Result<FeeRecommendation, FetchError> {
+        // getRecentPrioritizationFees accepts at most 128 addresses.
+        let accounts: Vec<Pubkey> = writable_accounts(ixs).into_iter().take(128).collect();
+        let recent = self.rpc().get_recent_prioritization_fees(&accounts).await?;
+        let fees: Vec<u64> = recent.iter().map(|f| f.prioritization_fee).collect();
+        Ok(FeeRecommendation {
+            compute_unit_limit,
+            compute_unit_price_micro_lamports: recommend_price(&fees, level, bounds),
+            samples: fees.iter().filter(|f| **f > 0).count(),
+        })
+    }
+
+    pub async fn recommend_init_fee(
+        &self,
+        payer: &Pubkey,
+        mint: &Pubkey,
+        args: &InitArgs,
+        level: PriorityLevel,
+    ) -> Result<FeeRecommendation, FetchError> {
+        let ixs = transaction::init_instructions(self.program_id(), payer, mint, args, &TxOptions::default());
+        self.recommend_fee(&ixs, INIT_COMPUTE_UNITS, level, FeeBounds::default()).await
+    }
+
+    pub async fn recommend_claim_fee(
+        &self,
+        escrow: &EscrowState,
+        fee_payer: &Pubkey,
+        level: PriorityLevel,
+    ) -> Result<FeeRecommendation, FetchError> {
+        // The preimage does not affect which accounts are written.
+        let ixs = transaction::claim_instructions(self.program_id(), escrow, fee_payer, &[0u8; 32], &TxOptions::default());
+        self.recommend_fee(&ixs, CLAIM_COMPUTE_UNITS, level, FeeBounds::default()).await
+    }
+
+    pub async fn recommend_refund_fee(
+        &self,
+        escrow: &EscrowState,
+        fee_payer: &Pubkey,
+        level: PriorityLevel,
+    ) -> Result<FeeRecommendation, FetchError> {
+        let ixs = transaction::refund_instructions(self.program_id(), escrow, fee_payer, &TxOptions::default());
+        self.recommend_fee(&ixs, REFUND_COMPUTE_UNITS, level, FeeBounds::default()).await
+    }
+}
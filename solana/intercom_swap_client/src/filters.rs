@@ -0,0 +1,158 @@
+//! `getProgramAccounts` filter builders for escrow queries.
+//!
+//! memcmp can only test equality, so every query is issued once per escrow layout (v3, v4) and range
+//! conditions such as "past refund_after" are applied to the decoded accounts with [`EscrowQuery::matches`].
+
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+
+use crate::state::{EscrowState, EscrowStatus, ESCROW_V3_LEN, ESCROW_V4_LEN};
+
+/// Byte offsets of escrow fields (identical in v3 and v4; v4 appends `creator`).
+pub mod offsets {
+    pub const VERSION: usize = 0;
+    pub const STATUS: usize = 1;
+    pub const PAYMENT_HASH: usize = 2;
+    pub const RECIPIENT: usize = 34;
+    pub const REFUND: usize = 66;
+    pub const REFUND_AFTER: usize = 98;
+    pub const MINT: usize = 106;
+    pub const NET_AMOUNT: usize = 138;
+    pub const PLATFORM_FEE_COLLECTOR: usize = 156;
+    pub const TRADE_FEE_COLLECTOR: usize = 198;
+    pub const VAULT: usize = 230;
+    pub const CREATOR: usize = 263;
+}
+
+/// Escrow layouts a query is fanned out over, as (version, account size).
+pub const ESCROW_LAYOUTS: [(u8, usize); 2] = [(3, ESCROW_V3_LEN), (4, ESCROW_V4_LEN)];
+
+/// Equality filters over escrow accounts plus client-side range conditions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EscrowQuery {
+    pub status: Option<EscrowStatus>,
+    pub recipient: Option<Pubkey>,
+    pub refund: Option<Pubkey>,
+    pub mint: Option<Pubkey>,
+    pub trade_fee_collector: Option<Pubkey>,
+    /// Only v4 escrows record a creator; setting this skips the v3 layout.
+    pub creator: Option<Pubkey>,
+    /// Client-side: keep escrows whose `refund_after` is at or before this unix timestamp.
+    pub refundable_at: Option<i64>,
+}
+
+impl EscrowQuery {
+    pub fn status(mut self, status: EscrowStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn recipient(mut self, recipient: Pubkey) -> Self {
+        self.recipient = Some(recipient);
+        self
+    }
+
+    pub fn refund(mut self, refund: Pubkey) -> Self {
+        self.refund = Some(refund);
+        self
+    }
+
+    pub fn mint(mut self, mint: Pubkey) -> Self {
+        self.mint = Some(mint);
+        self
+    }
+
+    pub fn trade_fee_collector(mut self, collector: Pubkey) -> Self {
+        self.trade_fee_collector = Some(collector);
+        self
+    }
+
+    pub fn creator(mut self, creator: Pubkey) -> Self {
+        self.creator = Some(creator);
+        self
+    }
+
+    pub fn refundable_at(mut self, now_unix: i64) -> Self {
+        self.refundable_at = Some(now_unix);
+        self
+    }
+
+    /// RPC filters for one escrow layout.
+    pub fn filters(&self, version: u8, account_len: usize) -> Vec<RpcFilterType> {
+        let mut out = vec![
+            RpcFilterType::DataSize(account_len as u64),
+            memcmp(offsets::VERSION, &[version]),
+        ];
+        if let Some(status) = self.status {
+            out.push(memcmp(offsets::STATUS, &[status.as_u8()]));
+        }
+        for (offset, key) in [
+            (offsets::RECIPIENT, self.recipient),
+            (offsets::REFUND, self.refund),
+            (offsets::MINT, self.mint),
+            (offsets::TRADE_FEE_COLLECTOR, self.trade_fee_collector),
+            (offsets::CREATOR, self.creator),
+        ] {
+            if let Some(key) = key {
+                out.push(memcmp(offset, key.as_ref()));
+            }
+        }
+        out
+    }
+
+    /// One `getProgramAccounts` config per escrow layout the query can match.
+    pub fn configs(&self, commitment: CommitmentConfig) -> Vec<RpcProgramAccountsConfig> {
+        ESCROW_LAYOUTS
+            .iter()
+            .filter(|(version, _)| self.creator.is_none() || *version >= 4)
+            .map(|&(version, len)| RpcProgramAccountsConfig {
+                filters: Some(self.filters(version, len)),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    commitment: Some(commitment),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            })
+            .collect()
+    }
+
+    /// Applies the conditions memcmp cannot express to a decoded escrow.
+    pub fn matches(&self, state: &EscrowState) -> bool {
+        match self.refundable_at {
+            Some(now) => state.refund_after <= now,
+            None => true,
+        }
+    }
+}
+
+/// Active escrows paying out to `recipient`.
+pub fn active_by_recipient(recipient: Pubkey) -> EscrowQuery {
+    EscrowQuery::default().status(EscrowStatus::Active).recipient(recipient)
+}
+
+/// Active escrows refundable to `refund` whose timelock has expired at `now_unix`.
+pub fn refundable_by(refund: Pubkey, now_unix: i64) -> EscrowQuery {
+    EscrowQuery::default()
+        .status(EscrowStatus::Active)
+        .refund(refund)
+        .refundable_at(now_unix)
+}
+
+/// Active escrows (any refund key) whose timelock has expired at `now_unix`.
+pub fn past_refund_after(now_unix: i64) -> EscrowQuery {
+    EscrowQuery::default().status(EscrowStatus::Active).refundable_at(now_unix)
+}
+
+/// All escrows denominated in `mint`.
+pub fn by_mint(mint: Pubkey) -> EscrowQuery {
+    EscrowQuery::default().mint(mint)
+}
+
+fn memcmp(offset: usize, bytes: &[u8]) -> RpcFilterType {
+    RpcFilterType::Memcmp(Memcmp::new_raw_bytes(offset, bytes.to_vec()))
+}
@@ -0,0 +1,262 @@
+//! Typed instruction builders.
+//!
+//! Every builder derives the PDAs and ATAs the program checks, so callers only supply the keys that
+//! cannot be derived (signers, their token accounts, the mint).
+
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+
+use crate::pda::{self, fee_vault_ata_for, vault_ata, RECIPIENT_INDEX_SEED, REFUND_INDEX_SEED};
+
+pub use intercom_swap_core::instruction::{EscrowInstruction, RefundAfter, MAX_INSTRUCTION_LEN};
+
+fn pack(ix: &EscrowInstruction) -> Vec<u8> {
+    let mut buf = [0u8; MAX_INSTRUCTION_LEN];
+    let len = ix.pack_into(&mut buf);
+    buf[..len].to_vec()
+}
+
+fn escrow_pda(program_id: &Pubkey, payment_hash: &[u8; 32]) -> Pubkey {
+    pda::find_escrow_pda(program_id, payment_hash).0
+}
+
+fn config_pda(program_id: &Pubkey) -> Pubkey {
+    pda::find_config_pda(program_id).0
+}
+
+fn trade_config_pda(program_id: &Pubkey, fee_collector: &Pubkey) -> Pubkey {
+    pda::find_trade_config_pda(program_id, fee_collector).0
+}
+
+fn index_pda(program_id: &Pubkey, seed: &[u8], owner: &Pubkey) -> Pubkey {
+    pda::find_index_pda(program_id, seed, owner).0
+}
+
+/// Arguments of an escrow Init that are not account keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitArgs {
+    pub payment_hash: [u8; 32],
+    pub recipient: Pubkey,
+    pub refund: Pubkey,
+    pub refund_after: RefundAfter,
+    /// Net amount the recipient receives; fees are charged on top.
+    pub amount: u64,
+    pub expected_platform_fee_bps: u16,
+    pub expected_trade_fee_bps: u16,
+    pub trade_fee_collector: Pubkey,
+}
+
+/// Init: locks `amount` plus fees from `payer_token` into a new escrow keyed by the payment hash.
+pub fn init(program_id: &Pubkey, payer: &Pubkey, payer_token: &Pubkey, mint: &Pubkey, args: &InitArgs) -> Instruction {
+    let escrow = escrow_pda(program_id, &args.payment_hash);
+    let config = config_pda(program_id);
+    let trade_config = trade_config_pda(program_id, &args.trade_fee_collector);
+    let data = pack(&EscrowInstruction::Init {
+        payment_hash: args.payment_hash,
+        recipient: args.recipient.to_bytes(),
+        refund: args.refund.to_bytes(),
+        refund_after: args.refund_after,
+        amount: args.amount,
+        expected_platform_fee_bps: args.expected_platform_fee_bps,
+        expected_trade_fee_bps: args.expected_trade_fee_bps,
+        trade_fee_collector: args.trade_fee_collector.to_bytes(),
+    });
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*payer_token, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new(vault_ata(&escrow, mint), false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new(fee_vault_ata_for(&config, mint), false),
+            AccountMeta::new_readonly(trade_config, false),
+            AccountMeta::new(fee_vault_ata_for(&trade_config, mint), false),
+            AccountMeta::new(index_pda(program_id, RECIPIENT_INDEX_SEED, &args.recipient), false),
+            AccountMeta::new(index_pda(program_id, REFUND_INDEX_SEED, &args.refund), false),
+        ],
+        data,
+    }
+}
+
+/// Claim: pays the net amount to `recipient_token` and fees to the fee vaults.
+///
+/// `creator` must be `Some` for v4+ escrows (it receives the vault rent) and `None` for v3.
+#[allow(clippy::too_many_arguments)]
+pub fn claim(
+    program_id: &Pubkey,
+    recipient: &Pubkey,
+    recipient_token: &Pubkey,
+    mint: &Pubkey,
+    payment_hash: &[u8; 32],
+    preimage: &[u8; 32],
+    trade_fee_collector: &Pubkey,
+    creator: Option<&Pubkey>,
+) -> Instruction {
+    let escrow = escrow_pda(program_id, payment_hash);
+    let config = config_pda(program_id);
+    let trade_config = trade_config_pda(program_id, trade_fee_collector);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*recipient, true),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new(vault_ata(&escrow, mint), false),
+        AccountMeta::new(*recipient_token, false),
+        AccountMeta::new(fee_vault_ata_for(&config, mint), false),
+        AccountMeta::new(fee_vault_ata_for(&trade_config, mint), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    if let Some(creator) = creator {
+        accounts.push(AccountMeta::new(*creator, false));
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: pack(&EscrowInstruction::Claim { preimage: *preimage }),
+    }
+}
+
+/// Refund: returns the full escrowed amount to `refund_token` once `refund_after` has passed.
+///
+/// `creator` must be `Some` for v4+ escrows (it receives the vault rent) and `None` for v3.
+pub fn refund(
+    program_id: &Pubkey,
+    refund: &Pubkey,
+    refund_token: &Pubkey,
+    mint: &Pubkey,
+    payment_hash: &[u8; 32],
+    creator: Option<&Pubkey>,
+) -> Instruction {
+    let escrow = escrow_pda(program_id, payment_hash);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*refund, true),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new(vault_ata(&escrow, mint), false),
+        AccountMeta::new(*refund_token, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    if let Some(creator) = creator {
+        accounts.push(AccountMeta::new(*creator, false));
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: pack(&EscrowInstruction::Refund),
+    }
+}
+
+/// InitConfig: creates the platform config PDA. The payer becomes the authority and must be `fee_collector`.
+pub fn init_config(program_id: &Pubkey, payer: &Pubkey, fee_collector: &Pubkey, fee_bps: u16) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(config_pda(program_id), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data: pack(&EscrowInstruction::InitConfig {
+            fee_collector: fee_collector.to_bytes(),
+            fee_bps,
+        }),
+    }
+}
+
+/// SetConfig: updates the platform fee collector and rate.
+pub fn set_config(program_id: &Pubkey, authority: &Pubkey, fee_collector: &Pubkey, fee_bps: u16) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(config_pda(program_id), false),
+        ],
+        data: pack(&EscrowInstruction::SetConfig {
+            fee_collector: fee_collector.to_bytes(),
+            fee_bps,
+        }),
+    }
+}
+
+/// WithdrawFees: moves `amount` (0 = everything) from the platform fee vault for `mint` to `dest_token`.
+pub fn withdraw_fees(
+    program_id: &Pubkey,
+    fee_collector: &Pubkey,
+    dest_token: &Pubkey,
+    mint: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let config = config_pda(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*fee_collector, true),
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new(fee_vault_ata_for(&config, mint), false),
+            AccountMeta::new(*dest_token, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: pack(&EscrowInstruction::WithdrawFees { amount }),
+    }
+}
+
+/// InitTradeConfig: creates the trade config PDA seeded by (and owned by) `fee_collector`.
+pub fn init_trade_config(program_id: &Pubkey, fee_collector: &Pubkey, fee_bps: u16) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*fee_collector, true),
+            AccountMeta::new(trade_config_pda(program_id, fee_collector), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data: pack(&EscrowInstruction::InitTradeConfig {
+            fee_collector: fee_collector.to_bytes(),
+            fee_bps,
+        }),
+    }
+}
+
+/// SetTradeConfig: updates the trade fee rate of `fee_collector`'s trade config.
+pub fn set_trade_config(program_id: &Pubkey, fee_collector: &Pubkey, fee_bps: u16) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*fee_collector, true),
+            AccountMeta::new(trade_config_pda(program_id, fee_collector), false),
+        ],
+        data: pack(&EscrowInstruction::SetTradeConfig {
+            fee_collector: fee_collector.to_bytes(),
+            fee_bps,
+        }),
+    }
+}
+
+/// WithdrawTradeFees: moves `amount` (0 = everything) from the trade fee vault for `mint` to `dest_token`.
+pub fn withdraw_trade_fees(
+    program_id: &Pubkey,
+    fee_collector: &Pubkey,
+    dest_token: &Pubkey,
+    mint: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let trade_config = trade_config_pda(program_id, fee_collector);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*fee_collector, true),
+            AccountMeta::new_readonly(trade_config, false),
+            AccountMeta::new(fee_vault_ata_for(&trade_config, mint), false),
+            AccountMeta::new(*dest_token, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: pack(&EscrowInstruction::WithdrawTradeFees { amount }),
+    }
+}
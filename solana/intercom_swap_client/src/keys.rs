@@ -0,0 +1,184 @@
+//! Keypair loading shared by the CLI and daemon.
+//!
+//! A [`KeySource`] is written as one string so it fits in a flag or config value:
+//!
+//! - `path/to/id.json` or `file:path/to/id.json`: the standard Solana JSON byte array
+//! - `env:NAME`: base58 secret key (or a JSON byte array) in an environment variable
+//! - `mnemonic-env:NAME[?key=A/C]`: BIP39 phrase in an environment variable
+//! - `prompt[?key=A/C]`: BIP39 phrase typed at the terminal, with the derived pubkey shown for confirmation
+//!
+//! `?key=A/C` selects the derivation path `m/44'/501'/A'/C'` as used by most wallets. Without it the key is
+//! derived the way `solana-keygen recover` does (first 32 bytes of the BIP39 seed).
+
+use std::{
+    fmt, fs,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use bip39::{Language, Mnemonic};
+use solana_sdk::{
+    derivation_path::DerivationPath,
+    signature::Keypair,
+    signer::{
+        keypair::{
+            generate_seed_from_seed_phrase_and_passphrase, keypair_from_seed, keypair_from_seed_and_derivation_path,
+            read_keypair,
+        },
+        Signer,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeySource {
+    File(PathBuf),
+    Env(String),
+    MnemonicEnv {
+        var: String,
+        derivation_path: Option<DerivationPath>,
+    },
+    Prompt {
+        derivation_path: Option<DerivationPath>,
+    },
+}
+
+#[derive(Debug)]
+pub enum KeyError {
+    Io(io::Error),
+    MissingEnv(String),
+    InvalidKeypair(String),
+    InvalidMnemonic(String),
+    InvalidDerivationPath(String),
+    InvalidSource(String),
+    /// The operator declined the derived pubkey at the prompt.
+    Aborted,
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::Io(e) => write!(f, "io error: {e}"),
+            KeyError::MissingEnv(name) => write!(f, "environment variable {name} is not set"),
+            KeyError::InvalidKeypair(e) => write!(f, "invalid keypair: {e}"),
+            KeyError::InvalidMnemonic(e) => write!(f, "invalid mnemonic: {e}"),
+            KeyError::InvalidDerivationPath(e) => write!(f, "invalid derivation path: {e}"),
+            KeyError::InvalidSource(s) => write!(f, "unrecognized key source: {s}"),
+            KeyError::Aborted => write!(f, "aborted by user"),
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+impl From<io::Error> for KeyError {
+    fn from(e: io::Error) -> Self {
+        KeyError::Io(e)
+    }
+}
+
+fn split_key_query(s: &str) -> Result<(&str, Option<DerivationPath>), KeyError> {
+    match s.split_once("?key=") {
+        None => Ok((s, None)),
+        Some((head, key)) => {
+            let path =
+                DerivationPath::from_key_str(key).map_err(|e| KeyError::InvalidDerivationPath(e.to_string()))?;
+            Ok((head, Some(path)))
+        }
+    }
+}
+
+impl FromStr for KeySource {
+    type Err = KeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("file:") {
+            return Ok(KeySource::File(path.into()));
+        }
+        if let Some(var) = s.strip_prefix("env:") {
+            return Ok(KeySource::Env(var.to_string()));
+        }
+        if let Some(rest) = s.strip_prefix("mnemonic-env:") {
+            let (var, derivation_path) = split_key_query(rest)?;
+            return Ok(KeySource::MnemonicEnv {
+                var: var.to_string(),
+                derivation_path,
+            });
+        }
+        if s == "prompt" || s.starts_with("prompt?") {
+            let (_, derivation_path) = split_key_query(s)?;
+            return Ok(KeySource::Prompt { derivation_path });
+        }
+        if s.is_empty() || s.contains("://") {
+            return Err(KeyError::InvalidSource(s.to_string()));
+        }
+        Ok(KeySource::File(s.into()))
+    }
+}
+
+impl KeySource {
+    /// Loads the keypair. `Prompt` reads from the controlling terminal and blocks.
+    pub fn load(&self) -> Result<Keypair, KeyError> {
+        match self {
+            KeySource::File(path) => {
+                let mut file = fs::File::open(path)?;
+                read_keypair(&mut file).map_err(|e| KeyError::InvalidKeypair(e.to_string()))
+            }
+            KeySource::Env(var) => keypair_from_secret_str(&env_var(var)?),
+            KeySource::MnemonicEnv { var, derivation_path } => {
+                keypair_from_mnemonic(&env_var(var)?, "", derivation_path.clone())
+            }
+            KeySource::Prompt { derivation_path } => prompt_mnemonic(derivation_path.clone()),
+        }
+    }
+}
+
+fn env_var(name: &str) -> Result<String, KeyError> {
+    std::env::var(name).map_err(|_| KeyError::MissingEnv(name.to_string()))
+}
+
+/// Parses a base58 secret key or a JSON byte array.
+pub fn keypair_from_secret_str(secret: &str) -> Result<Keypair, KeyError> {
+    let secret = secret.trim();
+    if secret.starts_with('[') {
+        return read_keypair(&mut secret.as_bytes()).map_err(|e| KeyError::InvalidKeypair(e.to_string()));
+    }
+    let bytes = bs58::decode(secret)
+        .into_vec()
+        .map_err(|e| KeyError::InvalidKeypair(e.to_string()))?;
+    Keypair::from_bytes(&bytes).map_err(|e| KeyError::InvalidKeypair(e.to_string()))
+}
+
+/// Derives a keypair from a BIP39 phrase. The phrase checksum is verified so a mistyped word fails loudly
+/// instead of silently producing a different key.
+pub fn keypair_from_mnemonic(
+    phrase: &str,
+    passphrase: &str,
+    derivation_path: Option<DerivationPath>,
+) -> Result<Keypair, KeyError> {
+    let phrase = phrase.split_whitespace().collect::<Vec<_>>().join(" ");
+    Mnemonic::from_phrase(&phrase, Language::English).map_err(|e| KeyError::InvalidMnemonic(e.to_string()))?;
+    let seed = generate_seed_from_seed_phrase_and_passphrase(&phrase, passphrase);
+    match derivation_path {
+        Some(path) => keypair_from_seed_and_derivation_path(&seed, Some(path)),
+        None => keypair_from_seed(&seed),
+    }
+    .map_err(|e| KeyError::InvalidKeypair(e.to_string()))
+}
+
+fn prompt_mnemonic(derivation_path: Option<DerivationPath>) -> Result<Keypair, KeyError> {
+    let phrase = rpassword::prompt_password("Seed phrase: ")?;
+    let passphrase = rpassword::prompt_password("BIP39 passphrase (empty for none): ")?;
+    let keypair = keypair_from_mnemonic(&phrase, &passphrase, derivation_path)?;
+
+    let mut stderr = io::stderr();
+    write!(stderr, "Recovered pubkey {}. Continue? (y/n): ", keypair.pubkey())?;
+    stderr.flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    if answer.trim().eq_ignore_ascii_case("y") {
+        Ok(keypair)
+    } else {
+        Err(KeyError::Aborted)
+    }
+}
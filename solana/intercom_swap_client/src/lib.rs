@@ -0,0 +1,70 @@
+//! Client SDK for the `ln_usdt_escrow` Solana program.
+//!
+//! Instruction builders produce [`solana_sdk::instruction::Instruction`] values with the exact account
+//! ordering the on-chain program expects; [`state`] decodes program accounts and [`error`] maps custom program
+//! error codes back to [`error::EscrowError`]. Byte layouts and error codes come from `intercom-swap-core`, the
+//! same crate the program uses. [`client::EscrowClient`] wraps an RPC connection for typed fetch/list operations,
+//! which [`pool::RpcPool`] can spread over several providers with failover.
+//!
+//! With `default-features = false` only [`pda`], [`amount`], [`instruction`], [`state`] and [`uri`] are
+//! built, which is enough for wasm32 and other targets without an RPC stack.
+
+pub mod amount;
+#[cfg(feature = "full")]
+pub mod batch;
+#[cfg(feature = "bolt11")]
+pub mod bolt11;
+#[cfg(feature = "full")]
+pub mod client;
+#[cfg(feature = "full")]
+pub mod confirm;
+#[cfg(feature = "full")]
+pub mod error;
+#[cfg(feature = "full")]
+pub mod events;
+#[cfg(feature = "full")]
+pub mod fees;
+#[cfg(feature = "full")]
+pub mod filters;
+pub mod instruction;
+#[cfg(feature = "full")]
+pub mod keys;
+#[cfg(feature = "full")]
+pub mod lookup_table;
+#[cfg(any(feature = "mock", all(test, feature = "full")))]
+pub mod mock;
+#[cfg(feature = "full")]
+pub mod nonce;
+#[cfg(feature = "full")]
+pub mod partial_sign;
+#[cfg(feature = "full")]
+pub mod pool;
+#[cfg(feature = "full")]
+pub mod preflight;
+#[cfg(feature = "qr")]
+pub mod qr;
+#[cfg(feature = "full")]
+pub mod remote_signer;
+#[cfg(feature = "full")]
+pub mod retry;
+#[cfg(feature = "full")]
+pub mod rpc;
+#[cfg(feature = "serde")]
+mod serde_fmt;
+#[cfg(feature = "full")]
+pub mod simulate;
+#[cfg(feature = "full")]
+pub mod solana_pay;
+pub mod state;
+#[cfg(feature = "full")]
+pub mod subscribe;
+#[cfg(feature = "full")]
+pub mod transaction;
+pub mod uri;
+
+/// PDA/ATA derivations, shared with the on-chain program so seeds cannot drift.
+pub use ln_usdt_escrow::pda;
+use solana_sdk::pubkey::Pubkey;
+
+/// Program id of the production deployment.
+pub const PROGRAM_ID: Pubkey = ln_usdt_escrow::ID;
@@ -0,0 +1,140 @@
+//! Versioned (v0) transactions and the address lookup table (ALT) holding the escrow program's static accounts.
+//!
+//! Init alone touches 15 accounts, so batching several escrow operations into one legacy transaction quickly
+//! exceeds the 1232-byte packet limit. Static accounts moved into an ALT cost one byte each instead of 32.
+
+use solana_sdk::{
+    address_lookup_table::{self, state::AddressLookupTable, AddressLookupTableAccount},
+    clock::Slot,
+    compute_budget,
+    hash::Hash,
+    instruction::Instruction,
+    message::{v0, CompileError, VersionedMessage},
+    pubkey::Pubkey,
+    signer::{Signer, SignerError},
+    system_program, sysvar,
+    transaction::VersionedTransaction,
+};
+
+use crate::{
+    client::{EscrowClient, FetchError},
+    pda,
+    state::DecodeError,
+};
+
+/// Maximum addresses per ExtendLookupTable instruction that still fits a legacy transaction.
+pub const MAX_ADDRESSES_PER_EXTEND: usize = 20;
+
+/// Accounts shared by every escrow transaction, plus the fee vaults of `mints`.
+pub fn static_addresses(program_id: &Pubkey, mints: &[Pubkey]) -> Vec<Pubkey> {
+    let config = pda::find_config_pda(program_id).0;
+    let mut out = vec![
+        *program_id,
+        config,
+        spl_token::id(),
+        spl_associated_token_account::id(),
+        system_program::id(),
+        sysvar::rent::id(),
+        sysvar::clock::id(),
+        compute_budget::id(),
+    ];
+    for mint in mints {
+        out.push(*mint);
+        out.push(pda::fee_vault_ata_for(&config, mint));
+    }
+    out
+}
+
+/// Creates a lookup table owned by `authority` and fills it with `addresses`.
+///
+/// Returns the table address and one instruction batch per transaction to send (in order). `recent_slot`
+/// must be a recent finalized slot.
+pub fn create_lookup_table(
+    authority: &Pubkey,
+    payer: &Pubkey,
+    recent_slot: Slot,
+    addresses: &[Pubkey],
+) -> (Pubkey, Vec<Vec<Instruction>>) {
+    let (create_ix, table) = address_lookup_table::instruction::create_lookup_table(*authority, *payer, recent_slot);
+    let mut batches = vec![vec![create_ix]];
+    batches.extend(extend_lookup_table(&table, authority, payer, addresses));
+    (table, batches)
+}
+
+/// Instructions adding `addresses` to `table`, chunked to fit one transaction each.
+pub fn extend_lookup_table(
+    table: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    addresses: &[Pubkey],
+) -> Vec<Vec<Instruction>> {
+    addresses
+        .chunks(MAX_ADDRESSES_PER_EXTEND)
+        .map(|chunk| {
+            vec![address_lookup_table::instruction::extend_lookup_table(
+                *table,
+                *authority,
+                Some(*payer),
+                chunk.to_vec(),
+            )]
+        })
+        .collect()
+}
+
+/// Addresses from `wanted` that `table` does not contain yet (order preserved, duplicates removed).
+pub fn missing_addresses(table: &AddressLookupTableAccount, wanted: &[Pubkey]) -> Vec<Pubkey> {
+    let mut out: Vec<Pubkey> = Vec::new();
+    for address in wanted {
+        if !table.addresses.contains(address) && !out.contains(address) {
+            out.push(*address);
+        }
+    }
+    out
+}
+
+/// Compiles `ixs` into a v0 message, resolving accounts through `tables` where possible.
+pub fn v0_message(
+    payer: &Pubkey,
+    ixs: &[Instruction],
+    tables: &[AddressLookupTableAccount],
+    recent_blockhash: Hash,
+) -> Result<VersionedMessage, CompileError> {
+    Ok(VersionedMessage::V0(v0::Message::try_compile(payer, ixs, tables, recent_blockhash)?))
+}
+
+/// Signs a v0 message; `signers` must cover every required signature.
+pub fn sign_v0(message: VersionedMessage, signers: &[&dyn Signer]) -> Result<VersionedTransaction, SignerError> {
+    VersionedTransaction::try_new(message, signers)
+}
+
+impl EscrowClient {
+    pub async fn get_lookup_table(&self, address: &Pubkey) -> Result<Option<AddressLookupTableAccount>, FetchError> {
+        let Some(account) = self.rpc().get_account_with_commitment(address, self.commitment()).await?.value else {
+            return Ok(None);
+        };
+        let table = AddressLookupTable::deserialize(&account.data).map_err(|_| FetchError::Decode {
+            address: *address,
+            source: DecodeError::Invalid("address lookup table"),
+        })?;
+        Ok(Some(AddressLookupTableAccount {
+            key: *address,
+            addresses: table.addresses.to_vec(),
+        }))
+    }
+
+    /// Extension batches that bring `table` up to date with [`static_addresses`] for `mints`.
+    pub async fn lookup_table_updates(
+        &self,
+        table: &Pubkey,
+        authority: &Pubkey,
+        payer: &Pubkey,
+        mints: &[Pubkey],
+    ) -> Result<Vec<Vec<Instruction>>, FetchError> {
+        let wanted = static_addresses(self.program_id(), mints);
+        let missing = match self.get_lookup_table(table).await? {
+            Some(current) => missing_addresses(&current, &wanted),
+            None => wanted,
+        };
+        Ok(extend_lookup_table(table, authority, payer, &missing))
+    }
+}
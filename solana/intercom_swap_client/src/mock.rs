@@ -0,0 +1,753 @@
+//! In-memory [`EscrowRpc`] for unit-testing swap logic without a validator.
+//!
+//! [`MockRpc`] keeps escrow/config state and SPL token balances in memory and executes escrow instructions
+//! with the program's rules: signer and PDA checks, expected-fee matching, fee caps, status transitions and
+//! refund timeouts, returning the same custom error codes. Compute budget and ATA-creation instructions are
+//! accepted; anything else is rejected. Transactions are atomic and signatures are verified, but there are no
+//! lamports, rent or account-index PDAs.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+
+use intercom_swap_core::{EscrowAmounts, MAX_PLATFORM_FEE_BPS, MAX_TOTAL_FEE_BPS, MAX_TRADE_FEE_BPS};
+use solana_sdk::{
+    compute_budget,
+    hash::{hash, Hash},
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{Transaction, TransactionError},
+};
+use spl_associated_token_account::get_associated_token_address;
+
+use crate::{
+    client::FetchError,
+    error::EscrowError,
+    instruction::{EscrowInstruction, RefundAfter},
+    pda,
+    rpc::EscrowRpc,
+    state::{ConfigState, EscrowState, EscrowStatus, TradeConfigState, CONFIG_V1, ESCROW_V4},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MockTokenAccount {
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Ledger {
+    now: i64,
+    blockhash_seq: u64,
+    escrows: HashMap<Pubkey, EscrowState>,
+    config: Option<ConfigState>,
+    trade_configs: HashMap<Pubkey, TradeConfigState>,
+    tokens: HashMap<Pubkey, MockTokenAccount>,
+    processed: HashSet<Signature>,
+}
+
+pub struct MockRpc {
+    program_id: Pubkey,
+    ledger: Mutex<Ledger>,
+}
+
+fn custom(e: EscrowError) -> InstructionError {
+    InstructionError::Custom(e.code())
+}
+
+/// Account keys of one instruction, with signer flags, resolved against the message.
+struct Accounts<'a> {
+    tx: &'a Transaction,
+    indices: &'a [u8],
+}
+
+impl Accounts<'_> {
+    fn key(&self, i: usize) -> Result<Pubkey, InstructionError> {
+        self.indices
+            .get(i)
+            .and_then(|idx| self.tx.message.account_keys.get(*idx as usize))
+            .copied()
+            .ok_or(InstructionError::NotEnoughAccountKeys)
+    }
+
+    fn signer(&self, i: usize) -> Result<Pubkey, InstructionError> {
+        let key = self.key(i)?;
+        if !self.tx.message.is_signer(self.indices[i] as usize) {
+            return Err(custom(EscrowError::InvalidSigner));
+        }
+        Ok(key)
+    }
+}
+
+impl Ledger {
+    fn token(&self, address: &Pubkey, owner: &Pubkey, mint: &Pubkey) -> Result<MockTokenAccount, InstructionError> {
+        match self.tokens.get(address) {
+            Some(t) if t.owner == *owner && t.mint == *mint => Ok(*t),
+            _ => Err(custom(EscrowError::InvalidTokenAccount)),
+        }
+    }
+
+    fn credit(&mut self, owner: &Pubkey, mint: &Pubkey, amount: u64) -> Result<(), InstructionError> {
+        let account = self
+            .tokens
+            .entry(get_associated_token_address(owner, mint))
+            .or_insert(MockTokenAccount {
+                owner: *owner,
+                mint: *mint,
+                amount: 0,
+            });
+        account.amount = account.amount.checked_add(amount).ok_or(InstructionError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    fn debit(&mut self, address: &Pubkey, amount: u64) -> Result<(), InstructionError> {
+        let account = self.tokens.get_mut(address).ok_or(custom(EscrowError::InvalidTokenAccount))?;
+        account.amount = account.amount.checked_sub(amount).ok_or(InstructionError::InsufficientFunds)?;
+        Ok(())
+    }
+
+    fn execute(&mut self, program_id: &Pubkey, tx: &Transaction) -> Result<(), TransactionError> {
+        for (i, ix) in tx.message.instructions.iter().enumerate() {
+            let program = tx.message.account_keys[ix.program_id_index as usize];
+            let accounts = Accounts {
+                tx,
+                indices: &ix.accounts,
+            };
+            let result = if program == *program_id {
+                self.execute_escrow(program_id, &accounts, &ix.data)
+            } else if program == spl_associated_token_account::id() {
+                self.create_ata(&accounts)
+            } else if program == compute_budget::id() {
+                Ok(())
+            } else {
+                return Err(TransactionError::InvalidProgramForExecution);
+            };
+            result.map_err(|e| TransactionError::InstructionError(i as u8, e))?;
+        }
+        Ok(())
+    }
+
+    /// Create / CreateIdempotent: [payer, ata, owner, mint, ..].
+    fn create_ata(&mut self, accounts: &Accounts) -> Result<(), InstructionError> {
+        let (ata, owner, mint) = (accounts.key(1)?, accounts.key(2)?, accounts.key(3)?);
+        if ata != get_associated_token_address(&owner, &mint) {
+            return Err(InstructionError::InvalidSeeds);
+        }
+        self.credit(&owner, &mint, 0)
+    }
+
+    fn execute_escrow(&mut self, program_id: &Pubkey, accounts: &Accounts, data: &[u8]) -> Result<(), InstructionError> {
+        let ix = EscrowInstruction::unpack(data).map_err(custom)?;
+        match ix {
+            EscrowInstruction::Init {
+                payment_hash,
+                recipient,
+                refund,
+                refund_after,
+                amount,
+                expected_platform_fee_bps,
+                expected_trade_fee_bps,
+                trade_fee_collector,
+            } => {
+                let payer = accounts.signer(0)?;
+                let payer_token = accounts.key(1)?;
+                let mint = accounts.key(4)?;
+                let (escrow, bump) = pda::find_escrow_pda(program_id, &payment_hash);
+                if accounts.key(2)? != escrow {
+                    return Err(custom(EscrowError::InvalidEscrowPda));
+                }
+                let refund_after = match refund_after {
+                    RefundAfter::At(ts) => ts,
+                    RefundAfter::Delay(secs) if secs > 0 => {
+                        self.now.checked_add(secs).ok_or(custom(EscrowError::InvalidInstruction))?
+                    }
+                    RefundAfter::Delay(_) => return Err(custom(EscrowError::InvalidInstruction)),
+                };
+                let config = self.config.clone().ok_or(custom(EscrowError::InvalidConfigState))?;
+                if config.fee_bps != expected_platform_fee_bps {
+                    return Err(custom(EscrowError::FeeMismatch));
+                }
+                let trade_fee_collector = Pubkey::new_from_array(trade_fee_collector);
+                let trade_config = self
+                    .trade_configs
+                    .get(&trade_fee_collector)
+                    .cloned()
+                    .ok_or(custom(EscrowError::InvalidTradeConfigState))?;
+                if trade_config.fee_bps != expected_trade_fee_bps {
+                    return Err(custom(EscrowError::FeeMismatch));
+                }
+                if config.fee_bps as u32 + trade_config.fee_bps as u32 > MAX_TOTAL_FEE_BPS as u32 {
+                    return Err(custom(EscrowError::FeeTooHigh));
+                }
+                let amounts = EscrowAmounts::compute(amount, config.fee_bps, trade_config.fee_bps)
+                    .ok_or(custom(EscrowError::InvalidInstruction))?;
+                let total = amounts.total_amount;
+                if self.token(&payer_token, &payer, &mint)?.amount < total {
+                    return Err(custom(EscrowError::InvalidTokenAccount));
+                }
+                if self.escrows.contains_key(&escrow) {
+                    return Err(custom(EscrowError::AlreadyInitialized));
+                }
+                self.debit(&payer_token, total)?;
+                self.credit(&escrow, &mint, total)?;
+                self.escrows.insert(
+                    escrow,
+                    EscrowState {
+                        v: ESCROW_V4,
+                        status: EscrowStatus::Active,
+                        payment_hash,
+                        recipient: Pubkey::new_from_array(recipient),
+                        refund: Pubkey::new_from_array(refund),
+                        refund_after,
+                        mint,
+                        net_amount: amount,
+                        platform_fee_amount: amounts.platform_fee_amount,
+                        platform_fee_bps: config.fee_bps,
+                        platform_fee_collector: config.fee_collector,
+                        trade_fee_amount: amounts.trade_fee_amount,
+                        trade_fee_bps: trade_config.fee_bps,
+                        trade_fee_collector,
+                        vault: pda::vault_ata(&escrow, &mint),
+                        bump,
+                        creator: Some(payer),
+                    },
+                );
+                Ok(())
+            }
+            EscrowInstruction::Claim { preimage } => {
+                let recipient = accounts.signer(0)?;
+                let escrow = accounts.key(1)?;
+                let state = self.active_escrow(&escrow)?;
+                if state.recipient != recipient {
+                    return Err(custom(EscrowError::InvalidSigner));
+                }
+                if hash(&preimage).to_bytes() != state.payment_hash {
+                    return Err(custom(EscrowError::InvalidPreimage));
+                }
+                let recipient_token = accounts.key(3)?;
+                self.token(&recipient_token, &recipient, &state.mint)?;
+                let (config, _) = pda::find_config_pda(program_id);
+                let (trade_config, _) = pda::find_trade_config_pda(program_id, &state.trade_fee_collector);
+                if accounts.key(4)? != pda::fee_vault_ata_for(&config, &state.mint) {
+                    return Err(custom(EscrowError::InvalidFeeVaultAta));
+                }
+                if accounts.key(5)? != pda::fee_vault_ata_for(&trade_config, &state.mint) {
+                    return Err(custom(EscrowError::InvalidTradeFeeVaultAta));
+                }
+                self.debit(&state.vault, state.total_amount())?;
+                self.tokens
+                    .get_mut(&recipient_token)
+                    .ok_or(custom(EscrowError::InvalidTokenAccount))?
+                    .amount += state.net_amount;
+                self.credit(&config, &state.mint, state.platform_fee_amount)?;
+                self.credit(&trade_config, &state.mint, state.trade_fee_amount)?;
+                self.settle(&escrow, EscrowStatus::Claimed);
+                Ok(())
+            }
+            EscrowInstruction::Refund => {
+                let refund = accounts.signer(0)?;
+                let escrow = accounts.key(1)?;
+                let state = self.active_escrow(&escrow)?;
+                if state.refund != refund {
+                    return Err(custom(EscrowError::InvalidSigner));
+                }
+                if self.now < state.refund_after {
+                    return Err(custom(EscrowError::TooEarly));
+                }
+                let refund_token = accounts.key(3)?;
+                self.token(&refund_token, &refund, &state.mint)?;
+                self.debit(&state.vault, state.total_amount())?;
+                self.tokens
+                    .get_mut(&refund_token)
+                    .ok_or(custom(EscrowError::InvalidTokenAccount))?
+                    .amount += state.total_amount();
+                self.settle(&escrow, EscrowStatus::Refunded);
+                Ok(())
+            }
+            EscrowInstruction::InitConfig { fee_collector, fee_bps } => {
+                let payer = accounts.signer(0)?;
+                if fee_bps > MAX_PLATFORM_FEE_BPS {
+                    return Err(custom(EscrowError::FeeTooHigh));
+                }
+                if payer.to_bytes() != fee_collector {
+                    return Err(custom(EscrowError::InvalidSigner));
+                }
+                if self.config.is_some() {
+                    return Err(custom(EscrowError::AlreadyInitialized));
+                }
+                self.config = Some(ConfigState {
+                    v: CONFIG_V1,
+                    authority: payer,
+                    fee_collector: payer,
+                    fee_bps,
+                    bump: pda::find_config_pda(program_id).1,
+                });
+                Ok(())
+            }
+            EscrowInstruction::SetConfig { fee_collector, fee_bps } => {
+                let authority = accounts.signer(0)?;
+                if fee_bps > MAX_PLATFORM_FEE_BPS {
+                    return Err(custom(EscrowError::FeeTooHigh));
+                }
+                let config = self.config.as_mut().ok_or(custom(EscrowError::InvalidConfigState))?;
+                if authority.to_bytes() != fee_collector || config.authority != authority {
+                    return Err(custom(EscrowError::InvalidSigner));
+                }
+                config.fee_collector = authority;
+                config.fee_bps = fee_bps;
+                Ok(())
+            }
+            EscrowInstruction::WithdrawFees { amount } => {
+                let collector = accounts.signer(0)?;
+                let config = self.config.clone().ok_or(custom(EscrowError::InvalidConfigState))?;
+                if config.authority != collector || config.fee_collector != collector {
+                    return Err(custom(EscrowError::InvalidSigner));
+                }
+                let (config_pda, _) = pda::find_config_pda(program_id);
+                self.withdraw(&config_pda, &collector, accounts.key(2)?, accounts.key(3)?, amount)
+            }
+            EscrowInstruction::InitTradeConfig { fee_collector, fee_bps } => {
+                let payer = accounts.signer(0)?;
+                if fee_bps > MAX_TRADE_FEE_BPS {
+                    return Err(custom(EscrowError::FeeTooHigh));
+                }
+                if payer.to_bytes() != fee_collector {
+                    return Err(custom(EscrowError::InvalidSigner));
+                }
+                if self.trade_configs.contains_key(&payer) {
+                    return Err(custom(EscrowError::AlreadyInitialized));
+                }
+                let bump = pda::find_trade_config_pda(program_id, &payer).1;
+                self.trade_configs.insert(
+                    payer,
+                    TradeConfigState {
+                        v: CONFIG_V1,
+                        authority: payer,
+                        fee_collector: payer,
+                        fee_bps,
+                        bump,
+                    },
+                );
+                Ok(())
+            }
+            EscrowInstruction::SetTradeConfig { fee_collector, fee_bps } => {
+                let authority = accounts.signer(0)?;
+                if fee_bps > MAX_TRADE_FEE_BPS {
+                    return Err(custom(EscrowError::FeeTooHigh));
+                }
+                if authority.to_bytes() != fee_collector {
+                    return Err(custom(EscrowError::InvalidSigner));
+                }
+                let config = self
+                    .trade_configs
+                    .get_mut(&authority)
+                    .ok_or(custom(EscrowError::InvalidTradeConfigState))?;
+                config.fee_bps = fee_bps;
+                Ok(())
+            }
+            EscrowInstruction::WithdrawTradeFees { amount } => {
+                let collector = accounts.signer(0)?;
+                if !self.trade_configs.contains_key(&collector) {
+                    return Err(custom(EscrowError::InvalidTradeConfigState));
+                }
+                let (trade_config, _) = pda::find_trade_config_pda(program_id, &collector);
+                self.withdraw(&trade_config, &collector, accounts.key(2)?, accounts.key(3)?, amount)
+            }
+        }
+    }
+
+    fn active_escrow(&self, escrow: &Pubkey) -> Result<EscrowState, InstructionError> {
+        let state = self.escrows.get(escrow).ok_or(InstructionError::InvalidAccountData)?;
+        if state.status != EscrowStatus::Active {
+            return Err(custom(EscrowError::NotActive));
+        }
+        Ok(state.clone())
+    }
+
+    fn settle(&mut self, escrow: &Pubkey, status: EscrowStatus) {
+        if let Some(state) = self.escrows.get_mut(escrow) {
+            state.status = status;
+            state.net_amount = 0;
+            state.platform_fee_amount = 0;
+            state.trade_fee_amount = 0;
+        }
+    }
+
+    fn withdraw(
+        &mut self,
+        vault_owner: &Pubkey,
+        collector: &Pubkey,
+        fee_vault: Pubkey,
+        dest: Pubkey,
+        amount: u64,
+    ) -> Result<(), InstructionError> {
+        let vault = self
+            .tokens
+            .get(&fee_vault)
+            .copied()
+            .filter(|t| t.owner == *vault_owner)
+            .ok_or(custom(EscrowError::InvalidTokenAccount))?;
+        self.token(&dest, collector, &vault.mint)?;
+        let amount = if amount == 0 { vault.amount } else { amount };
+        if amount > vault.amount {
+            return Err(custom(EscrowError::InvalidInstruction));
+        }
+        self.debit(&fee_vault, amount)?;
+        self.tokens.get_mut(&dest).expect("checked above").amount += amount;
+        Ok(())
+    }
+}
+
+impl MockRpc {
+    pub fn new(program_id: Pubkey) -> Self {
+        Self {
+            program_id,
+            ledger: Mutex::new(Ledger::default()),
+        }
+    }
+
+    fn ledger(&self) -> std::sync::MutexGuard<'_, Ledger> {
+        self.ledger.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn program_id(&self) -> &Pubkey {
+        &self.program_id
+    }
+
+    pub fn now(&self) -> i64 {
+        self.ledger().now
+    }
+
+    pub fn set_time(&self, unix_timestamp: i64) {
+        self.ledger().now = unix_timestamp;
+    }
+
+    pub fn advance_time(&self, secs: i64) {
+        self.ledger().now += secs;
+    }
+
+    /// Credits `amount` of `mint` to `owner`'s ATA (creating it) and returns the ATA address.
+    pub fn mint_to(&self, owner: &Pubkey, mint: &Pubkey, amount: u64) -> Pubkey {
+        self.ledger().credit(owner, mint, amount).expect("mock token balance overflow");
+        get_associated_token_address(owner, mint)
+    }
+
+    pub fn token_account(&self, address: &Pubkey) -> Option<MockTokenAccount> {
+        self.ledger().tokens.get(address).copied()
+    }
+
+    /// Seeds the platform config without sending InitConfig.
+    pub fn set_config(&self, fee_collector: &Pubkey, fee_bps: u16) {
+        self.ledger().config = Some(ConfigState {
+            v: CONFIG_V1,
+            authority: *fee_collector,
+            fee_collector: *fee_collector,
+            fee_bps,
+            bump: pda::find_config_pda(&self.program_id).1,
+        });
+    }
+
+    /// Seeds a trade config without sending InitTradeConfig.
+    pub fn set_trade_config(&self, fee_collector: &Pubkey, fee_bps: u16) {
+        let bump = pda::find_trade_config_pda(&self.program_id, fee_collector).1;
+        self.ledger().trade_configs.insert(
+            *fee_collector,
+            TradeConfigState {
+                v: CONFIG_V1,
+                authority: *fee_collector,
+                fee_collector: *fee_collector,
+                fee_bps,
+                bump,
+            },
+        );
+    }
+
+    /// Executes `tx` atomically, as a validator would.
+    pub fn process_transaction(&self, tx: &Transaction) -> Result<Signature, TransactionError> {
+        tx.verify()?;
+        let signature = tx.signatures[0];
+        let mut ledger = self.ledger();
+        if ledger.processed.contains(&signature) {
+            return Err(TransactionError::AlreadyProcessed);
+        }
+        let mut next = ledger.clone();
+        next.execute(&self.program_id, tx)?;
+        next.processed.insert(signature);
+        *ledger = next;
+        Ok(signature)
+    }
+}
+
+impl EscrowRpc for MockRpc {
+    async fn get_escrow(&self, payment_hash: &[u8; 32]) -> Result<Option<EscrowState>, FetchError> {
+        let escrow = pda::find_escrow_pda(&self.program_id, payment_hash).0;
+        Ok(self.ledger().escrows.get(&escrow).cloned())
+    }
+
+    async fn get_config(&self) -> Result<Option<ConfigState>, FetchError> {
+        Ok(self.ledger().config.clone())
+    }
+
+    async fn get_trade_config(&self, fee_collector: &Pubkey) -> Result<Option<TradeConfigState>, FetchError> {
+        Ok(self.ledger().trade_configs.get(fee_collector).cloned())
+    }
+
+    async fn get_token_balance(&self, token_account: &Pubkey) -> Result<u64, FetchError> {
+        Ok(self.ledger().tokens.get(token_account).map_or(0, |t| t.amount))
+    }
+
+    async fn get_unix_timestamp(&self) -> Result<i64, FetchError> {
+        Ok(self.now())
+    }
+
+    /// A fresh hash per call; the mock does not expire blockhashes.
+    async fn get_latest_blockhash(&self) -> Result<Hash, FetchError> {
+        let mut ledger = self.ledger();
+        ledger.blockhash_seq += 1;
+        Ok(hash(&ledger.blockhash_seq.to_le_bytes()))
+    }
+
+    async fn send_transaction(&self, tx: &Transaction) -> Result<Signature, FetchError> {
+        self.process_transaction(tx).map_err(|e| FetchError::Rpc(e.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use super::*;
+    use crate::{
+        error::from_transaction_error,
+        instruction::InitArgs,
+        transaction::{claim_transaction, init_transaction, refund_transaction, TxOptions},
+    };
+
+    const NOW: i64 = 1_700_000_000;
+    const AMOUNT: u64 = 10_000;
+    /// AMOUNT plus 0.5% platform and 0.3% trade fees.
+    const TOTAL: u64 = 10_080;
+
+    /// A mock with 50 bps platform and 30 bps trade fees, both collected by `collector`.
+    struct Setup {
+        rpc: MockRpc,
+        collector: Keypair,
+        mint: Pubkey,
+        payer: Keypair,
+        recipient: Keypair,
+        refund: Keypair,
+    }
+
+    fn setup() -> Setup {
+        let rpc = MockRpc::new(Pubkey::new_unique());
+        rpc.set_time(NOW);
+        let collector = Keypair::new();
+        rpc.set_config(&collector.pubkey(), 50);
+        rpc.set_trade_config(&collector.pubkey(), 30);
+        let mint = Pubkey::new_unique();
+        let payer = Keypair::new();
+        rpc.mint_to(&payer.pubkey(), &mint, TOTAL);
+        Setup {
+            rpc,
+            collector,
+            mint,
+            payer,
+            recipient: Keypair::new(),
+            refund: Keypair::new(),
+        }
+    }
+
+    fn payment(n: u8) -> ([u8; 32], [u8; 32]) {
+        let preimage = [n; 32];
+        (preimage, hash(&preimage).to_bytes())
+    }
+
+    /// Signs `tx` on a fresh blockhash and sends it, as swap logic does against any [`EscrowRpc`].
+    async fn send<R: EscrowRpc>(rpc: &R, mut tx: Transaction, signers: &[&Keypair]) -> Result<Signature, FetchError> {
+        let blockhash = rpc.get_latest_blockhash().await?;
+        tx.sign(signers, blockhash);
+        rpc.send_transaction(&tx).await
+    }
+
+    fn escrow_error(result: Result<Signature, FetchError>) -> Option<EscrowError> {
+        match result.err()? {
+            FetchError::Rpc(e) => from_transaction_error(&e.get_transaction_error()?),
+            FetchError::Decode { .. } => None,
+        }
+    }
+
+    impl Setup {
+        async fn init(&self, payment_hash: [u8; 32], refund_after: RefundAfter) -> Result<Signature, FetchError> {
+            let args = InitArgs {
+                payment_hash,
+                recipient: self.recipient.pubkey(),
+                refund: self.refund.pubkey(),
+                refund_after,
+                amount: AMOUNT,
+                expected_platform_fee_bps: 50,
+                expected_trade_fee_bps: 30,
+                trade_fee_collector: self.collector.pubkey(),
+            };
+            let tx = init_transaction(
+                self.rpc.program_id(),
+                &self.payer.pubkey(),
+                &self.mint,
+                &args,
+                &TxOptions::default(),
+                Hash::default(),
+            );
+            send(&self.rpc, tx, &[&self.payer]).await
+        }
+
+        async fn escrow(&self, payment_hash: &[u8; 32]) -> EscrowState {
+            self.rpc.get_escrow(payment_hash).await.unwrap().expect("escrow")
+        }
+
+        async fn balance(&self, owner: &Pubkey) -> u64 {
+            let token_account = get_associated_token_address(owner, &self.mint);
+            self.rpc.get_token_balance(&token_account).await.unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn init_then_claim() {
+        let s = setup();
+        let (preimage, payment_hash) = payment(1);
+        s.init(payment_hash, RefundAfter::Delay(3_600)).await.unwrap();
+        let escrow = s.escrow(&payment_hash).await;
+        assert_eq!(escrow.status, EscrowStatus::Active);
+        assert_eq!(escrow.refund_after, NOW + 3_600);
+        assert_eq!(escrow.creator, Some(s.payer.pubkey()));
+        assert_eq!(
+            (escrow.net_amount, escrow.platform_fee_amount, escrow.trade_fee_amount),
+            (AMOUNT, 50, 30)
+        );
+        assert_eq!(s.balance(&s.payer.pubkey()).await, 0);
+        assert_eq!(s.rpc.get_token_balance(&escrow.vault).await.unwrap(), TOTAL);
+
+        // The recipient pays for its own claim.
+        let recipient = s.recipient.pubkey();
+        let claim = claim_transaction(
+            s.rpc.program_id(),
+            &escrow,
+            &recipient,
+            &preimage,
+            &TxOptions::default(),
+            Hash::default(),
+        );
+        send(&s.rpc, claim, &[&s.recipient]).await.unwrap();
+        assert_eq!(s.escrow(&payment_hash).await.status, EscrowStatus::Claimed);
+        assert_eq!(s.balance(&recipient).await, AMOUNT);
+        let config = pda::find_config_pda(s.rpc.program_id()).0;
+        let trade_config = pda::find_trade_config_pda(s.rpc.program_id(), &s.collector.pubkey()).0;
+        assert_eq!(s.balance(&config).await, 50);
+        assert_eq!(s.balance(&trade_config).await, 30);
+        assert_eq!(s.rpc.get_token_balance(&escrow.vault).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn failed_claim_changes_nothing() {
+        let s = setup();
+        let (_, payment_hash) = payment(1);
+        s.init(payment_hash, RefundAfter::At(NOW)).await.unwrap();
+        let escrow = s.escrow(&payment_hash).await;
+        let claim = claim_transaction(
+            s.rpc.program_id(),
+            &escrow,
+            &s.recipient.pubkey(),
+            &[2; 32],
+            &TxOptions::default(),
+            Hash::default(),
+        );
+        let result = send(&s.rpc, claim, &[&s.recipient]).await;
+        assert_eq!(escrow_error(result), Some(EscrowError::InvalidPreimage));
+        assert_eq!(s.escrow(&payment_hash).await, escrow);
+        // Not even the recipient's token account, created earlier in the same transaction.
+        let recipient_token = get_associated_token_address(&s.recipient.pubkey(), &s.mint);
+        assert_eq!(s.rpc.token_account(&recipient_token), None);
+    }
+
+    #[tokio::test]
+    async fn refund_waits_for_the_clock() {
+        let s = setup();
+        let (_, payment_hash) = payment(1);
+        s.init(payment_hash, RefundAfter::Delay(3_600)).await.unwrap();
+        let escrow = s.escrow(&payment_hash).await;
+        let refund_tx = || {
+            refund_transaction(
+                s.rpc.program_id(),
+                &escrow,
+                &s.refund.pubkey(),
+                &TxOptions::default(),
+                Hash::default(),
+            )
+        };
+
+        s.rpc.advance_time(3_599);
+        assert_eq!(s.rpc.get_unix_timestamp().await.unwrap(), NOW + 3_599);
+        let early = send(&s.rpc, refund_tx(), &[&s.refund]).await;
+        assert_eq!(escrow_error(early), Some(EscrowError::TooEarly));
+        s.rpc.advance_time(1);
+        send(&s.rpc, refund_tx(), &[&s.refund]).await.unwrap();
+        assert_eq!(s.escrow(&payment_hash).await.status, EscrowStatus::Refunded);
+        assert_eq!(s.balance(&s.refund.pubkey()).await, TOTAL);
+
+        let again = send(&s.rpc, refund_tx(), &[&s.refund]).await;
+        assert_eq!(escrow_error(again), Some(EscrowError::NotActive));
+    }
+
+    #[tokio::test]
+    async fn init_checks_fees_and_funds() {
+        let s = setup();
+        let (_, payment_hash) = payment(1);
+        s.rpc.set_trade_config(&s.collector.pubkey(), 40);
+        let result = s.init(payment_hash, RefundAfter::At(NOW)).await;
+        assert_eq!(escrow_error(result), Some(EscrowError::FeeMismatch));
+        s.rpc.set_trade_config(&s.collector.pubkey(), 30);
+        s.init(payment_hash, RefundAfter::At(NOW)).await.unwrap();
+
+        // The same payment hash again, with the funds for it.
+        s.rpc.mint_to(&s.payer.pubkey(), &s.mint, TOTAL);
+        let result = s.init(payment_hash, RefundAfter::At(NOW)).await;
+        assert_eq!(escrow_error(result), Some(EscrowError::AlreadyInitialized));
+        // Another with only one escrow's worth left.
+        s.init(payment(2).1, RefundAfter::At(NOW)).await.unwrap();
+        let result = s.init(payment(3).1, RefundAfter::At(NOW)).await;
+        assert_eq!(escrow_error(result), Some(EscrowError::InvalidTokenAccount));
+        assert!(s.rpc.get_escrow(&payment(3).1).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn replayed_and_unsigned_transactions_are_rejected() {
+        let s = setup();
+        let (_, payment_hash) = payment(1);
+        s.rpc.mint_to(&s.payer.pubkey(), &s.mint, TOTAL);
+        let args = InitArgs {
+            payment_hash,
+            recipient: s.recipient.pubkey(),
+            refund: s.refund.pubkey(),
+            refund_after: RefundAfter::At(NOW),
+            amount: AMOUNT,
+            expected_platform_fee_bps: 50,
+            expected_trade_fee_bps: 30,
+            trade_fee_collector: s.collector.pubkey(),
+        };
+        let blockhash = s.rpc.get_latest_blockhash().await.unwrap();
+        let mut tx = init_transaction(
+            s.rpc.program_id(),
+            &s.payer.pubkey(),
+            &s.mint,
+            &args,
+            &TxOptions::default(),
+            blockhash,
+        );
+        assert_eq!(s.rpc.process_transaction(&tx), Err(TransactionError::SignatureFailure));
+        tx.sign(&[&s.payer], blockhash);
+        s.rpc.process_transaction(&tx).unwrap();
+        assert_eq!(s.rpc.process_transaction(&tx), Err(TransactionError::AlreadyProcessed));
+        assert_eq!(s.balance(&s.payer.pubkey()).await, TOTAL);
+    }
+}
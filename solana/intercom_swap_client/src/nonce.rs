@@ -0,0 +1,126 @@
+//! Durable nonce transactions for offline (air-gapped) signing.
+//!
+//! A durable nonce replaces the recent blockhash so a transaction signed offline stays valid until it is
+//! submitted. The program requires `AdvanceNonceAccount` as the first instruction; once the transaction lands
+//! (or the nonce is advanced by anything else) every transaction signed against the old value is void and
+//! must be re-signed against the new one.
+
+use solana_sdk::{
+    account::Account,
+    account_utils::StateMut,
+    hash::Hash,
+    instruction::Instruction,
+    message::Message,
+    nonce::state::{State, Versions},
+    pubkey::Pubkey,
+    system_instruction,
+    transaction::Transaction,
+};
+
+use crate::{
+    client::{EscrowClient, FetchError},
+    instruction::InitArgs,
+    state::{DecodeError, EscrowState},
+    transaction::{self, TxOptions},
+};
+
+/// Current value of an initialized nonce account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DurableNonce {
+    pub account: Pubkey,
+    pub authority: Pubkey,
+    /// Stored nonce, used in place of the recent blockhash.
+    pub blockhash: Hash,
+}
+
+/// Decodes an initialized nonce account; `None` if it is uninitialized or not a nonce account.
+pub fn decode_nonce(address: &Pubkey, account: &Account) -> Option<DurableNonce> {
+    let versions: Versions = account.state().ok()?;
+    match versions.state() {
+        State::Initialized(data) => Some(DurableNonce {
+            account: *address,
+            authority: data.authority,
+            blockhash: data.blockhash(),
+        }),
+        State::Uninitialized => None,
+    }
+}
+
+/// Instructions creating and initializing a nonce account controlled by `authority`.
+pub fn create_nonce_account(payer: &Pubkey, nonce_account: &Pubkey, authority: &Pubkey, lamports: u64) -> Vec<Instruction> {
+    system_instruction::create_nonce_account(payer, nonce_account, authority, lamports)
+}
+
+/// Prepends `AdvanceNonceAccount` to `ixs` and builds an unsigned transaction against the stored nonce.
+///
+/// `nonce.authority` must sign in addition to the usual signers.
+pub fn nonce_transaction(ixs: &[Instruction], fee_payer: &Pubkey, nonce: &DurableNonce) -> Transaction {
+    let mut all = Vec::with_capacity(ixs.len() + 1);
+    all.push(system_instruction::advance_nonce_account(&nonce.account, &nonce.authority));
+    all.extend_from_slice(ixs);
+    Transaction::new_unsigned(Message::new_with_blockhash(&all, Some(fee_payer), &nonce.blockhash))
+}
+
+pub fn init_transaction(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    args: &InitArgs,
+    opts: &TxOptions,
+    nonce: &DurableNonce,
+) -> Transaction {
+    nonce_transaction(&transaction::init_instructions(program_id, payer, mint, args, opts), payer, nonce)
+}
+
+pub fn claim_transaction(
+    program_id: &Pubkey,
+    escrow: &EscrowState,
+    fee_payer: &Pubkey,
+    preimage: &[u8; 32],
+    opts: &TxOptions,
+    nonce: &DurableNonce,
+) -> Transaction {
+    nonce_transaction(
+        &transaction::claim_instructions(program_id, escrow, fee_payer, preimage, opts),
+        fee_payer,
+        nonce,
+    )
+}
+
+pub fn refund_transaction(
+    program_id: &Pubkey,
+    escrow: &EscrowState,
+    fee_payer: &Pubkey,
+    opts: &TxOptions,
+    nonce: &DurableNonce,
+) -> Transaction {
+    nonce_transaction(
+        &transaction::refund_instructions(program_id, escrow, fee_payer, opts),
+        fee_payer,
+        nonce,
+    )
+}
+
+impl EscrowClient {
+    /// Reads the current nonce value; `None` if the account does not exist or is not initialized.
+    pub async fn get_durable_nonce(&self, nonce_account: &Pubkey) -> Result<Option<DurableNonce>, FetchError> {
+        let account = self
+            .rpc()
+            .get_account_with_commitment(nonce_account, self.commitment())
+            .await?
+            .value;
+        Ok(account.and_then(|a| decode_nonce(nonce_account, &a)))
+    }
+
+    /// Whether `tx` (built with [`nonce_transaction`]) can still land: the on-chain nonce must equal its
+    /// blockhash. `false` means it already landed or the nonce was advanced, so it must be rebuilt and re-signed.
+    pub async fn nonce_still_valid(&self, tx: &Transaction, nonce_account: &Pubkey) -> Result<bool, FetchError> {
+        match self.get_durable_nonce(nonce_account).await? {
+            Some(current) => Ok(current.blockhash == tx.message.recent_blockhash),
+            None => Err(FetchError::Decode {
+                address: *nonce_account,
+                source: DecodeError::Invalid("nonce"),
+            }),
+        }
+    }
+}
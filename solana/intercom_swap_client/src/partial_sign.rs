@@ -0,0 +1,146 @@
+//! Multi-party and offline signing.
+//!
+//! Builders return unsigned transactions whose signature slots hold the default (all-zero) placeholder. Each
+//! party signs its own slots with [`partial_sign`] (or on an air-gapped machine from the base64 export) and hands
+//! back [`SignatureShare`]s, which the coordinator folds in with [`merge_signatures`] after verifying them
+//! against the message. Combine with [`crate::nonce`] when a signer may take longer than a blockhash lifetime.
+
+use std::fmt;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::{Signer, SignerError},
+    transaction::Transaction,
+};
+
+/// One signer's signature over a transaction message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignatureShare {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+    pub pubkey: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+    pub signature: Signature,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeError {
+    /// The key is not one of the transaction's required signers.
+    NotASigner(Pubkey),
+    /// The signature does not verify against the message.
+    InvalidSignature(Pubkey),
+    /// A different valid signature is already present for the key.
+    Conflict(Pubkey),
+    /// The transactions being merged do not sign the same message.
+    MessageMismatch,
+    Encoding(String),
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotASigner(pk) => write!(f, "{pk} is not a required signer"),
+            Self::InvalidSignature(pk) => write!(f, "signature for {pk} does not verify"),
+            Self::Conflict(pk) => write!(f, "conflicting signature already present for {pk}"),
+            Self::MessageMismatch => f.write_str("transactions sign different messages"),
+            Self::Encoding(e) => write!(f, "invalid transaction encoding: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+/// Keys that must sign `tx`, fee payer first.
+pub fn required_signers(tx: &Transaction) -> &[Pubkey] {
+    let n = tx.message.header.num_required_signatures as usize;
+    &tx.message.account_keys[..n]
+}
+
+/// Required signers whose slot still holds the placeholder.
+pub fn missing_signers(tx: &Transaction) -> Vec<Pubkey> {
+    required_signers(tx)
+        .iter()
+        .zip(&tx.signatures)
+        .filter(|(_, sig)| **sig == Signature::default())
+        .map(|(pk, _)| *pk)
+        .collect()
+}
+
+pub fn is_fully_signed(tx: &Transaction) -> bool {
+    missing_signers(tx).is_empty()
+}
+
+/// Signs the slots belonging to `signers`, leaving every other slot untouched.
+pub fn partial_sign(tx: &mut Transaction, signers: &[&dyn Signer]) -> Result<Vec<SignatureShare>, SignerError> {
+    let blockhash = tx.message.recent_blockhash;
+    tx.try_partial_sign(signers, blockhash)?;
+    let mut shares = Vec::with_capacity(signers.len());
+    for signer in signers {
+        let pubkey = signer.try_pubkey()?;
+        if let Some(signature) = signature_of(tx, &pubkey) {
+            shares.push(SignatureShare { pubkey, signature });
+        }
+    }
+    Ok(shares)
+}
+
+/// The signature currently in `pubkey`'s slot, if it is a required signer and has signed.
+pub fn signature_of(tx: &Transaction, pubkey: &Pubkey) -> Option<Signature> {
+    let idx = required_signers(tx).iter().position(|k| k == pubkey)?;
+    tx.signatures.get(idx).copied().filter(|s| *s != Signature::default())
+}
+
+/// Inserts verified `shares` into `tx`. Re-applying an identical signature is a no-op.
+pub fn merge_signatures(tx: &mut Transaction, shares: &[SignatureShare]) -> Result<(), MergeError> {
+    let message = tx.message_data();
+    for share in shares {
+        let idx = required_signers(tx)
+            .iter()
+            .position(|k| *k == share.pubkey)
+            .ok_or(MergeError::NotASigner(share.pubkey))?;
+        if !share.signature.verify(share.pubkey.as_ref(), &message) {
+            return Err(MergeError::InvalidSignature(share.pubkey));
+        }
+        let slot = &mut tx.signatures[idx];
+        if *slot != Signature::default() && *slot != share.signature {
+            return Err(MergeError::Conflict(share.pubkey));
+        }
+        *slot = share.signature;
+    }
+    Ok(())
+}
+
+/// Folds the signatures of independently signed copies of the same transaction into `base`.
+pub fn merge_transactions(base: &mut Transaction, others: &[Transaction]) -> Result<(), MergeError> {
+    for other in others {
+        if other.message != base.message {
+            return Err(MergeError::MessageMismatch);
+        }
+        let shares: Vec<SignatureShare> = required_signers(other)
+            .iter()
+            .zip(&other.signatures)
+            .filter(|(_, sig)| **sig != Signature::default())
+            .map(|(pubkey, signature)| SignatureShare {
+                pubkey: *pubkey,
+                signature: *signature,
+            })
+            .collect();
+        merge_signatures(base, &shares)?;
+    }
+    Ok(())
+}
+
+/// Wire encoding (bincode, base64) for moving a partially signed transaction between machines.
+pub fn encode_base64(tx: &Transaction) -> String {
+    // Serializing an in-memory Transaction cannot fail.
+    STANDARD.encode(bincode::serialize(tx).unwrap_or_default())
+}
+
+pub fn decode_base64(encoded: &str) -> Result<Transaction, MergeError> {
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| MergeError::Encoding(e.to_string()))?;
+    bincode::deserialize(&bytes).map_err(|e| MergeError::Encoding(e.to_string()))
+}
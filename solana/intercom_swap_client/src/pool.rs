@@ -0,0 +1,223 @@
+//! Failover across several RPC providers behind one [`RpcClient`], so everything built on it (fetches, sends,
+//! confirmation polling) survives a provider going down or falling behind.
+//!
+//! Each request goes to the healthiest endpoint first and moves on to the next when the endpoint, rather than
+//! the request, is at fault: a transport error or a node reporting itself unhealthy or behind. Endpoints are
+//! ranked by an exponentially weighted success rate, then latency, and one failing `eject_after` times in a row
+//! is skipped for `cooldown` (still tried if nothing else is left). With `fan_out_sends`, `sendTransaction` goes
+//! to every endpoint at once and the first acceptance wins; the runtime deduplicates the identical signed bytes.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use futures::future::{select_ok, BoxFuture, FutureExt};
+use serde_json::Value;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    http_sender::HttpSender,
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::RpcClientConfig,
+    rpc_request::{RpcError, RpcRequest},
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_sdk::commitment_config::CommitmentConfig;
+
+/// JSON-RPC errors that say the node, not the request, is the problem.
+const NODE_ERROR_CODES: [i64; 3] = [
+    -32004, // block not available
+    -32005, // node unhealthy
+    -32016, // minimum context slot not reached
+];
+
+/// Weight of the latest outcome in an endpoint's success rate.
+const SCORE_WEIGHT: f64 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// Send transactions to every endpoint at once instead of the healthiest one.
+    pub fan_out_sends: bool,
+    /// Consecutive failures after which an endpoint is skipped.
+    pub eject_after: u32,
+    /// How long an ejected endpoint is skipped before it is tried again.
+    pub cooldown: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            fan_out_sends: false,
+            eject_after: 3,
+            cooldown: Duration::from_secs(30),
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// An endpoint's standing, as [`RpcPool::health`] reports it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointHealth {
+    pub url: String,
+    /// Success rate, weighted towards recent requests; 1.0 until the first failure.
+    pub score: f64,
+    /// Weighted like `score`; `None` until a request succeeds.
+    pub latency: Option<Duration>,
+    pub consecutive_failures: u32,
+    /// Skipped until then.
+    pub ejected_until: Option<Instant>,
+}
+
+struct Endpoint {
+    sender: HttpSender,
+    health: Mutex<EndpointHealth>,
+}
+
+impl Endpoint {
+    fn record(&self, ok: bool, latency: Duration, cfg: &PoolConfig) {
+        let mut health = self.health.lock().expect("endpoint health lock poisoned");
+        health.score += SCORE_WEIGHT * (f64::from(u8::from(ok)) - health.score);
+        if ok {
+            health.latency = Some(match health.latency {
+                Some(average) => average.mul_f64(1.0 - SCORE_WEIGHT) + latency.mul_f64(SCORE_WEIGHT),
+                None => latency,
+            });
+            health.consecutive_failures = 0;
+            health.ejected_until = None;
+        } else {
+            health.consecutive_failures += 1;
+            if health.consecutive_failures >= cfg.eject_after {
+                health.ejected_until = Some(Instant::now() + cfg.cooldown);
+            }
+        }
+    }
+
+    async fn send(&self, request: RpcRequest, params: Value, cfg: &PoolConfig) -> ClientResult<Value> {
+        let started = Instant::now();
+        let result = self.sender.send(request, params).await;
+        let faulty = result.as_ref().err().is_some_and(endpoint_fault);
+        self.record(!faulty, started.elapsed(), cfg);
+        result
+    }
+}
+
+/// Whether `e` blames the endpoint, so the request is worth retrying elsewhere.
+fn endpoint_fault(e: &ClientError) -> bool {
+    match &e.kind {
+        ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) => true,
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => NODE_ERROR_CODES.contains(code),
+        ClientErrorKind::RpcError(RpcError::RpcRequestError(_) | RpcError::ParseError(_)) => true,
+        _ => false,
+    }
+}
+
+/// The [`RpcSender`] behind a pooled [`RpcClient`].
+pub struct RpcPool {
+    endpoints: Vec<Endpoint>,
+    cfg: PoolConfig,
+}
+
+impl RpcPool {
+    /// A pool over `urls`, tried in the given order until their health tells them apart.
+    ///
+    /// # Panics
+    ///
+    /// If `urls` is empty.
+    pub fn new(urls: impl IntoIterator<Item = String>, cfg: PoolConfig) -> Self {
+        let endpoints: Vec<_> = urls
+            .into_iter()
+            .map(|url| Endpoint {
+                sender: HttpSender::new_with_timeout(url.clone(), cfg.timeout),
+                health: Mutex::new(EndpointHealth {
+                    url,
+                    score: 1.0,
+                    latency: None,
+                    consecutive_failures: 0,
+                    ejected_until: None,
+                }),
+            })
+            .collect();
+        assert!(!endpoints.is_empty(), "an RPC pool needs at least one endpoint");
+        Self { endpoints, cfg }
+    }
+
+    /// An [`RpcClient`] sending through the pool.
+    pub fn into_client(self, commitment: CommitmentConfig) -> RpcClient {
+        RpcClient::new_sender(self, RpcClientConfig::with_commitment(commitment))
+    }
+
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        self.endpoints
+            .iter()
+            .map(|e| e.health.lock().expect("endpoint health lock poisoned").clone())
+            .collect()
+    }
+
+    /// Endpoints best first: those not ejected, by score, then latency (untried last); ties keep the configured
+    /// order.
+    fn ranked(&self) -> Vec<&Endpoint> {
+        let now = Instant::now();
+        let mut ranked: Vec<_> = self
+            .endpoints
+            .iter()
+            .map(|e| (e, e.health.lock().expect("endpoint health lock poisoned").clone()))
+            .collect();
+        ranked.sort_by(|(_, a), (_, b)| {
+            let ejected = |h: &EndpointHealth| h.ejected_until.is_some_and(|until| until > now);
+            ejected(a).cmp(&ejected(b)).then(b.score.total_cmp(&a.score)).then(
+                a.latency
+                    .unwrap_or(Duration::MAX)
+                    .cmp(&b.latency.unwrap_or(Duration::MAX)),
+            )
+        });
+        ranked.into_iter().map(|(e, _)| e).collect()
+    }
+
+    async fn fan_out(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        let sends: Vec<BoxFuture<'_, ClientResult<Value>>> = self
+            .ranked()
+            .into_iter()
+            .map(|endpoint| endpoint.send(request, params.clone(), &self.cfg).boxed())
+            .collect();
+        select_ok(sends).await.map(|(value, _)| value)
+    }
+}
+
+#[async_trait]
+impl RpcSender for RpcPool {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        if self.cfg.fan_out_sends && self.endpoints.len() > 1 && request == RpcRequest::SendTransaction {
+            return self.fan_out(request, params).await;
+        }
+        let mut ranked = self.ranked().into_iter().peekable();
+        loop {
+            let endpoint = ranked.next().expect("pool has endpoints");
+            match endpoint.send(request, params.clone(), &self.cfg).await {
+                Err(e) if endpoint_fault(&e) && ranked.peek().is_some() => continue,
+                result => return result,
+            }
+        }
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.endpoints.iter().map(|e| e.sender.get_transport_stats()).fold(
+            RpcTransportStats::default(),
+            |mut total, stats| {
+                total.request_count += stats.request_count;
+                total.elapsed_time += stats.elapsed_time;
+                total.rate_limited_time += stats.rate_limited_time;
+                total
+            },
+        )
+    }
+
+    fn url(&self) -> String {
+        self.endpoints
+            .iter()
+            .map(|e| e.sender.url())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
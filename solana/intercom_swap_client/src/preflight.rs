@@ -0,0 +1,440 @@
+//! Client-side replay of the program's Init/Claim/Refund checks, to report every problem before sending.
+//!
+//! The `validate_*` functions are pure and run against an account snapshot; the `EscrowClient` methods fetch
+//! that snapshot with batched `getMultipleAccounts` calls. They assume the transactions come from
+//! [`crate::transaction`], which creates missing ATAs idempotently, so an absent ATA is only a violation where
+//! the program needs an existing balance.
+
+use std::fmt;
+
+use intercom_swap_core::{EscrowAmounts, MAX_PLATFORM_FEE_BPS, MAX_TOTAL_FEE_BPS, MAX_TRADE_FEE_BPS};
+use solana_sdk::{
+    account::{from_account, Account},
+    clock::Clock,
+    hash::hash,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar,
+};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::state::Account as TokenAccount;
+
+use crate::{
+    client::{EscrowClient, FetchError},
+    error::EscrowError,
+    instruction::{InitArgs, RefundAfter},
+    pda,
+    state::{self, ConfigState, EscrowState, EscrowStatus, TradeConfigState, CONFIG_V1},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    EscrowExists,
+    EscrowMissing,
+    NotActive(EscrowStatus),
+    InvalidRefundDelay(i64),
+    ConfigMissing,
+    ConfigInvalid,
+    TradeConfigMissing,
+    TradeConfigInvalid,
+    FeeTooHigh {
+        platform_fee_bps: u16,
+        trade_fee_bps: u16,
+    },
+    PlatformFeeMismatch {
+        expected: u16,
+        actual: u16,
+    },
+    TradeFeeMismatch {
+        expected: u16,
+        actual: u16,
+    },
+    AmountOverflow,
+    TokenAccountMissing(Pubkey),
+    TokenAccountOwner {
+        address: Pubkey,
+        expected: Pubkey,
+        actual: Pubkey,
+    },
+    TokenAccountMint {
+        address: Pubkey,
+        expected: Pubkey,
+        actual: Pubkey,
+    },
+    InsufficientBalance {
+        address: Pubkey,
+        required: u64,
+        available: u64,
+    },
+    SignerMismatch {
+        expected: Pubkey,
+        actual: Pubkey,
+    },
+    InvalidPreimage,
+    VaultMismatch,
+    VaultUnderfunded {
+        required: u64,
+        available: u64,
+    },
+    TooEarly {
+        refund_after: i64,
+        now: i64,
+    },
+}
+
+impl Violation {
+    /// Custom error the program would fail with; `None` where the failure comes from the runtime or SPL Token.
+    pub fn escrow_error(&self) -> Option<EscrowError> {
+        Some(match self {
+            Self::EscrowExists => EscrowError::AlreadyInitialized,
+            Self::NotActive(_) => EscrowError::NotActive,
+            Self::InvalidRefundDelay(_) | Self::AmountOverflow => EscrowError::InvalidInstruction,
+            Self::ConfigMissing | Self::ConfigInvalid => EscrowError::InvalidConfigState,
+            Self::TradeConfigMissing | Self::TradeConfigInvalid => EscrowError::InvalidTradeConfigState,
+            Self::FeeTooHigh { .. } => EscrowError::FeeTooHigh,
+            Self::PlatformFeeMismatch { .. } | Self::TradeFeeMismatch { .. } => EscrowError::FeeMismatch,
+            Self::TokenAccountMissing(_)
+            | Self::TokenAccountOwner { .. }
+            | Self::TokenAccountMint { .. }
+            | Self::InsufficientBalance { .. } => EscrowError::InvalidTokenAccount,
+            Self::SignerMismatch { .. } => EscrowError::InvalidSigner,
+            Self::InvalidPreimage => EscrowError::InvalidPreimage,
+            Self::VaultMismatch => EscrowError::InvalidVaultAta,
+            Self::TooEarly { .. } => EscrowError::TooEarly,
+            Self::EscrowMissing | Self::VaultUnderfunded { .. } => return None,
+        })
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EscrowExists => write!(f, "an escrow for this payment hash already exists"),
+            Self::EscrowMissing => write!(f, "escrow account not found"),
+            Self::NotActive(status) => write!(f, "escrow is {status:?}, not active"),
+            Self::InvalidRefundDelay(secs) => write!(f, "refund delay must be positive (got {secs})"),
+            Self::ConfigMissing => write!(f, "platform config is not initialized"),
+            Self::ConfigInvalid => write!(f, "platform config version or bump mismatch"),
+            Self::TradeConfigMissing => write!(f, "trade config for the fee collector is not initialized"),
+            Self::TradeConfigInvalid => write!(f, "trade config version, bump or owner mismatch"),
+            Self::FeeTooHigh {
+                platform_fee_bps,
+                trade_fee_bps,
+            } => write!(
+                f,
+                "fees too high: platform {platform_fee_bps} bps, trade {trade_fee_bps} bps"
+            ),
+            Self::PlatformFeeMismatch { expected, actual } => {
+                write!(f, "platform fee is {actual} bps, expected {expected}")
+            }
+            Self::TradeFeeMismatch { expected, actual } => write!(f, "trade fee is {actual} bps, expected {expected}"),
+            Self::AmountOverflow => write!(f, "amount plus fees overflows u64"),
+            Self::TokenAccountMissing(address) => write!(f, "token account {address} does not exist"),
+            Self::TokenAccountOwner {
+                address,
+                expected,
+                actual,
+            } => write!(f, "token account {address} is owned by {actual}, expected {expected}"),
+            Self::TokenAccountMint {
+                address,
+                expected,
+                actual,
+            } => write!(f, "token account {address} holds mint {actual}, expected {expected}"),
+            Self::InsufficientBalance {
+                address,
+                required,
+                available,
+            } => write!(f, "token account {address} holds {available}, needs {required}"),
+            Self::SignerMismatch { expected, actual } => write!(f, "signer {actual} is not the expected {expected}"),
+            Self::InvalidPreimage => write!(f, "preimage does not hash to the payment hash"),
+            Self::VaultMismatch => write!(f, "escrow vault is not the expected ATA"),
+            Self::VaultUnderfunded { required, available } => {
+                write!(f, "vault holds {available}, escrow records {required}")
+            }
+            Self::TooEarly { refund_after, now } => {
+                write!(f, "refund opens at {refund_after}, cluster time is {now}")
+            }
+        }
+    }
+}
+
+/// Accounts an Init reads. `None` means the account does not exist.
+#[derive(Debug, Clone, Default)]
+pub struct InitSnapshot {
+    pub config: Option<ConfigState>,
+    pub trade_config: Option<TradeConfigState>,
+    pub escrow_exists: bool,
+    pub payer_token: Option<TokenAccount>,
+}
+
+/// Accounts a Claim or Refund reads.
+#[derive(Debug, Clone, Default)]
+pub struct SettleSnapshot {
+    pub escrow: Option<EscrowState>,
+    pub vault: Option<TokenAccount>,
+    /// The recipient's (claim) or refund key's (refund) ATA.
+    pub destination: Option<TokenAccount>,
+    pub now: i64,
+}
+
+fn check_token(out: &mut Vec<Violation>, address: Pubkey, account: &TokenAccount, owner: &Pubkey, mint: &Pubkey) {
+    if account.owner != *owner {
+        out.push(Violation::TokenAccountOwner {
+            address,
+            expected: *owner,
+            actual: account.owner,
+        });
+    }
+    if account.mint != *mint {
+        out.push(Violation::TokenAccountMint {
+            address,
+            expected: *mint,
+            actual: account.mint,
+        });
+    }
+}
+
+/// Init checks for `payer` escrowing `args` from its ATA of `mint`.
+pub fn validate_init(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    args: &InitArgs,
+    snapshot: &InitSnapshot,
+) -> Vec<Violation> {
+    let mut out = Vec::new();
+    if let RefundAfter::Delay(secs) = args.refund_after {
+        if secs <= 0 {
+            out.push(Violation::InvalidRefundDelay(secs));
+        }
+    }
+
+    let platform_fee_bps = match &snapshot.config {
+        None => {
+            out.push(Violation::ConfigMissing);
+            None
+        }
+        Some(c) if c.v != CONFIG_V1 || c.bump != pda::find_config_pda(program_id).1 => {
+            out.push(Violation::ConfigInvalid);
+            None
+        }
+        Some(c) => Some(c.fee_bps),
+    };
+    let trade_fee_bps = match &snapshot.trade_config {
+        None => {
+            out.push(Violation::TradeConfigMissing);
+            None
+        }
+        Some(c)
+            if c.v != CONFIG_V1
+                || c.bump != pda::find_trade_config_pda(program_id, &args.trade_fee_collector).1
+                || c.fee_collector != args.trade_fee_collector
+                || c.authority != args.trade_fee_collector =>
+        {
+            out.push(Violation::TradeConfigInvalid);
+            None
+        }
+        Some(c) => Some(c.fee_bps),
+    };
+    if let Some(actual) = platform_fee_bps.filter(|bps| *bps != args.expected_platform_fee_bps) {
+        out.push(Violation::PlatformFeeMismatch {
+            expected: args.expected_platform_fee_bps,
+            actual,
+        });
+    }
+    if let Some(actual) = trade_fee_bps.filter(|bps| *bps != args.expected_trade_fee_bps) {
+        out.push(Violation::TradeFeeMismatch {
+            expected: args.expected_trade_fee_bps,
+            actual,
+        });
+    }
+
+    if let (Some(platform), Some(trade)) = (platform_fee_bps, trade_fee_bps) {
+        if platform > MAX_PLATFORM_FEE_BPS
+            || trade > MAX_TRADE_FEE_BPS
+            || u32::from(platform) + u32::from(trade) > u32::from(MAX_TOTAL_FEE_BPS)
+        {
+            out.push(Violation::FeeTooHigh {
+                platform_fee_bps: platform,
+                trade_fee_bps: trade,
+            });
+        }
+        let payer_token = get_associated_token_address(payer, mint);
+        match (
+            EscrowAmounts::compute(args.amount, platform, trade),
+            &snapshot.payer_token,
+        ) {
+            (None, _) => out.push(Violation::AmountOverflow),
+            (Some(_), None) => out.push(Violation::TokenAccountMissing(payer_token)),
+            (Some(amounts), Some(account)) => {
+                check_token(&mut out, payer_token, account, payer, mint);
+                if account.amount < amounts.total_amount {
+                    out.push(Violation::InsufficientBalance {
+                        address: payer_token,
+                        required: amounts.total_amount,
+                        available: account.amount,
+                    });
+                }
+            }
+        }
+    }
+
+    if snapshot.escrow_exists {
+        out.push(Violation::EscrowExists);
+    }
+    out
+}
+
+fn validate_settle(program_id: &Pubkey, signer: &Pubkey, snapshot: &SettleSnapshot, claim: bool) -> Vec<Violation> {
+    let mut out = Vec::new();
+    let Some(escrow) = &snapshot.escrow else {
+        out.push(Violation::EscrowMissing);
+        return out;
+    };
+    if escrow.status != EscrowStatus::Active {
+        out.push(Violation::NotActive(escrow.status));
+        return out;
+    }
+    let expected_signer = if claim { escrow.recipient } else { escrow.refund };
+    if expected_signer != *signer {
+        out.push(Violation::SignerMismatch {
+            expected: expected_signer,
+            actual: *signer,
+        });
+    }
+    let escrow_pda = pda::find_escrow_pda(program_id, &escrow.payment_hash).0;
+    if escrow.vault != pda::vault_ata(&escrow_pda, &escrow.mint) {
+        out.push(Violation::VaultMismatch);
+    }
+    let available = snapshot.vault.as_ref().map_or(0, |v| v.amount);
+    if available < escrow.total_amount() {
+        out.push(Violation::VaultUnderfunded {
+            required: escrow.total_amount(),
+            available,
+        });
+    }
+    if let Some(destination) = &snapshot.destination {
+        let address = get_associated_token_address(&expected_signer, &escrow.mint);
+        check_token(&mut out, address, destination, &expected_signer, &escrow.mint);
+    }
+    out
+}
+
+/// Claim checks for `recipient` revealing `preimage`.
+pub fn validate_claim(
+    program_id: &Pubkey,
+    recipient: &Pubkey,
+    preimage: &[u8; 32],
+    snapshot: &SettleSnapshot,
+) -> Vec<Violation> {
+    let mut out = validate_settle(program_id, recipient, snapshot, true);
+    if let Some(escrow) = snapshot.escrow.as_ref().filter(|e| e.status == EscrowStatus::Active) {
+        if hash(preimage).to_bytes() != escrow.payment_hash {
+            out.push(Violation::InvalidPreimage);
+        }
+    }
+    out
+}
+
+/// Refund checks for `refund` at cluster time `snapshot.now`.
+pub fn validate_refund(program_id: &Pubkey, refund: &Pubkey, snapshot: &SettleSnapshot) -> Vec<Violation> {
+    let mut out = validate_settle(program_id, refund, snapshot, false);
+    if let Some(escrow) = snapshot.escrow.as_ref().filter(|e| e.status == EscrowStatus::Active) {
+        if snapshot.now < escrow.refund_after {
+            out.push(Violation::TooEarly {
+                refund_after: escrow.refund_after,
+                now: snapshot.now,
+            });
+        }
+    }
+    out
+}
+
+fn decode_token(address: &Pubkey, account: Option<Account>) -> Result<Option<TokenAccount>, FetchError> {
+    account
+        .map(|a| {
+            TokenAccount::unpack(&a.data).map_err(|_| FetchError::Decode {
+                address: *address,
+                source: state::DecodeError::Invalid("token account"),
+            })
+        })
+        .transpose()
+}
+
+impl EscrowClient {
+    async fn accounts(&self, keys: &[Pubkey]) -> Result<Vec<Option<Account>>, FetchError> {
+        Ok(self
+            .rpc()
+            .get_multiple_accounts_with_commitment(keys, self.commitment())
+            .await?
+            .value)
+    }
+
+    pub async fn validate_init(
+        &self,
+        payer: &Pubkey,
+        mint: &Pubkey,
+        args: &InitArgs,
+    ) -> Result<Vec<Violation>, FetchError> {
+        let program_id = self.program_id();
+        let keys = [
+            pda::find_config_pda(program_id).0,
+            pda::find_trade_config_pda(program_id, &args.trade_fee_collector).0,
+            pda::find_escrow_pda(program_id, &args.payment_hash).0,
+            get_associated_token_address(payer, mint),
+        ];
+        let [config, trade_config, escrow, payer_token]: [Option<Account>; 4] =
+            self.accounts(&keys).await?.try_into().unwrap_or_default();
+        let decode = |i: usize, data: &[u8]| {
+            state::decode_config(data).map_err(|source| FetchError::Decode {
+                address: keys[i],
+                source,
+            })
+        };
+        let snapshot = InitSnapshot {
+            config: config.map(|a| decode(0, &a.data)).transpose()?,
+            trade_config: trade_config.map(|a| decode(1, &a.data)).transpose()?,
+            escrow_exists: escrow.is_some(),
+            payer_token: decode_token(&keys[3], payer_token)?,
+        };
+        Ok(validate_init(program_id, payer, mint, args, &snapshot))
+    }
+
+    async fn settle_snapshot(&self, payment_hash: &[u8; 32], claim: bool) -> Result<SettleSnapshot, FetchError> {
+        let Some(escrow) = self.get_escrow(payment_hash).await? else {
+            return Ok(SettleSnapshot::default());
+        };
+        let destination_owner = if claim { escrow.recipient } else { escrow.refund };
+        let destination = get_associated_token_address(&destination_owner, &escrow.mint);
+        let keys = [escrow.vault, destination, sysvar::clock::id()];
+        let [vault, destination_account, clock]: [Option<Account>; 3] =
+            self.accounts(&keys).await?.try_into().unwrap_or_default();
+        Ok(SettleSnapshot {
+            vault: decode_token(&keys[0], vault)?,
+            destination: decode_token(&destination, destination_account)?,
+            now: clock
+                .and_then(|a| from_account::<Clock, _>(&a))
+                .map_or(0, |c| c.unix_timestamp),
+            escrow: Some(escrow),
+        })
+    }
+
+    pub async fn validate_claim(
+        &self,
+        payment_hash: &[u8; 32],
+        recipient: &Pubkey,
+        preimage: &[u8; 32],
+    ) -> Result<Vec<Violation>, FetchError> {
+        let snapshot = self.settle_snapshot(payment_hash, true).await?;
+        Ok(validate_claim(self.program_id(), recipient, preimage, &snapshot))
+    }
+
+    pub async fn validate_refund(
+        &self,
+        payment_hash: &[u8; 32],
+        refund: &Pubkey,
+    ) -> Result<Vec<Violation>, FetchError> {
+        let snapshot = self.settle_snapshot(payment_hash, false).await?;
+        Ok(validate_refund(self.program_id(), refund, &snapshot))
+    }
+}
@@ -0,0 +1,118 @@
+//! QR payloads for swap handoff (point-of-sale style LN→USDT flows).
+//!
+//! [`QrPayload::new`] picks the strongest error correction that keeps the symbol at or below
+//! [`MAX_MOBILE_VERSION`], so a phone camera can still read it off a screen or receipt. Rendering to SVG and
+//! PNG needs the `qr-render` feature.
+
+use std::fmt;
+
+use qrcode::{render::unicode, EcLevel, QrCode, Version};
+
+use crate::uri::SwapUri;
+
+/// Largest symbol version (97×97 modules) considered reliably scannable by phone cameras at handheld distance.
+pub const MAX_MOBILE_VERSION: i16 = 20;
+
+/// Error correction levels tried in order; higher levels survive glare and damage but need bigger symbols.
+const EC_PREFERENCE: [EcLevel; 3] = [EcLevel::Q, EcLevel::M, EcLevel::L];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QrError {
+    /// The data does not fit in [`MAX_MOBILE_VERSION`] even at the lowest error correction.
+    TooLarge { len: usize },
+    Encode(String),
+}
+
+impl fmt::Display for QrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge { len } => write!(f, "{len} bytes do not fit in a version {MAX_MOBILE_VERSION} QR code"),
+            Self::Encode(e) => write!(f, "qr encoding failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for QrError {}
+
+pub struct QrPayload {
+    data: String,
+    code: QrCode,
+}
+
+impl QrPayload {
+    /// Encodes `data` with the highest error correction that fits the mobile size limit.
+    pub fn new(data: impl Into<String>) -> Result<Self, QrError> {
+        let data = data.into();
+        for ec in EC_PREFERENCE {
+            match QrCode::with_error_correction_level(data.as_bytes(), ec) {
+                Ok(code) if matches!(code.version(), Version::Normal(v) if v <= MAX_MOBILE_VERSION) => {
+                    return Ok(Self { data, code });
+                }
+                Ok(_) | Err(qrcode::types::QrError::DataTooLong) => continue,
+                Err(e) => return Err(QrError::Encode(e.to_string())),
+            }
+        }
+        Err(QrError::TooLarge { len: data.len() })
+    }
+
+    /// Canonical `intercomswap:` URI for the swap.
+    pub fn for_swap(uri: &SwapUri) -> Result<Self, QrError> {
+        Self::new(uri.to_string())
+    }
+
+    /// Base64 transaction (e.g. an unsigned claim from [`crate::transaction::claim_transaction`]) for an
+    /// offline or mobile signer; see [`crate::partial_sign::decode_base64`].
+    #[cfg(feature = "full")]
+    pub fn for_transaction(tx: &solana_sdk::transaction::Transaction) -> Result<Self, QrError> {
+        Self::new(crate::partial_sign::encode_base64(tx))
+    }
+
+    pub fn data(&self) -> &str {
+        &self.data
+    }
+
+    pub fn ec_level(&self) -> EcLevel {
+        self.code.error_correction_level()
+    }
+
+    /// Symbol version (1–40); modules per side is `17 + 4 * version`.
+    pub fn version(&self) -> i16 {
+        match self.code.version() {
+            Version::Normal(v) | Version::Micro(v) => v,
+        }
+    }
+
+    /// Terminal rendering with half-block characters.
+    pub fn to_unicode(&self) -> String {
+        self.code
+            .render::<unicode::Dense1x2>()
+            .quiet_zone(true)
+            .build()
+    }
+
+    /// SVG at least `min_size` pixels wide, with the standard 4-module quiet zone.
+    #[cfg(feature = "qr-render")]
+    pub fn to_svg(&self, min_size: u32) -> String {
+        self.code
+            .render::<qrcode::render::svg::Color>()
+            .min_dimensions(min_size, min_size)
+            .quiet_zone(true)
+            .build()
+    }
+
+    /// Grayscale PNG at least `min_size` pixels wide.
+    #[cfg(feature = "qr-render")]
+    pub fn to_png(&self, min_size: u32) -> Result<Vec<u8>, QrError> {
+        let image = self
+            .code
+            .render::<image::Luma<u8>>()
+            .min_dimensions(min_size, min_size)
+            .quiet_zone(true)
+            .build();
+        let mut out = std::io::Cursor::new(Vec::new());
+        image
+            .write_to(&mut out, image::ImageFormat::Png)
+            .map_err(|e| QrError::Encode(e.to_string()))?;
+        Ok(out.into_inner())
+    }
+}
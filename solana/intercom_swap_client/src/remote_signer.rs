@@ -0,0 +1,174 @@
+//! Authority signing routed to keys the process does not hold in memory.
+//!
+//! [`RemoteSigner`] is the object-safe, `Send + Sync` signing interface the CLI and daemon depend on. Hot keys
+//! wrap in [`LocalSigner`]; treasury authorities (SetConfig, WithdrawFees) can live on a Ledger via
+//! [`LedgerSigner`] (feature `ledger`). [`AsSigner`] adapts any of them to `solana_sdk::signer::Signer` so they
+//! plug into the regular transaction and [`crate::partial_sign`] APIs.
+
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::{Signer, SignerError},
+    transaction::Transaction,
+};
+
+use crate::partial_sign::{self, MergeError, SignatureShare};
+
+/// A signing key that may live outside this process.
+pub trait RemoteSigner: Send + Sync {
+    fn pubkey(&self) -> Pubkey;
+
+    /// Signs raw message bytes. May block on user confirmation (hardware wallets).
+    fn sign_message(&self, message: &[u8]) -> Result<Signature, SignerError>;
+
+    /// Short human label for prompts and logs, e.g. "ledger usb://ledger?key=0".
+    fn description(&self) -> String;
+
+    /// Whether signing needs someone to press a button; callers should surface a prompt first.
+    fn needs_confirmation(&self) -> bool {
+        false
+    }
+}
+
+/// Signs `tx` with `signer` and merges the signature into its slot.
+pub fn sign_transaction(tx: &mut Transaction, signer: &dyn RemoteSigner) -> Result<SignatureShare, MergeError> {
+    let pubkey = signer.pubkey();
+    if !partial_sign::required_signers(tx).contains(&pubkey) {
+        return Err(MergeError::NotASigner(pubkey));
+    }
+    let signature = signer
+        .sign_message(&tx.message_data())
+        .map_err(|_| MergeError::InvalidSignature(pubkey))?;
+    let share = SignatureShare { pubkey, signature };
+    partial_sign::merge_signatures(tx, &[share])?;
+    Ok(share)
+}
+
+/// Any in-memory `Signer` (e.g. a `Keypair`).
+pub struct LocalSigner<S>(pub S);
+
+impl<S: Signer + Send + Sync> RemoteSigner for LocalSigner<S> {
+    fn pubkey(&self) -> Pubkey {
+        self.0.pubkey()
+    }
+
+    fn sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        self.0.try_sign_message(message)
+    }
+
+    fn description(&self) -> String {
+        format!("local {}", self.0.pubkey())
+    }
+}
+
+/// Borrows a [`RemoteSigner`] as a `Signer`.
+pub struct AsSigner<'a>(pub &'a dyn RemoteSigner);
+
+impl Signer for AsSigner<'_> {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.0.pubkey())
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        self.0.sign_message(message)
+    }
+
+    fn is_interactive(&self) -> bool {
+        self.0.needs_confirmation()
+    }
+}
+
+#[cfg(feature = "ledger")]
+pub use ledger::LedgerSigner;
+
+#[cfg(feature = "ledger")]
+mod ledger {
+    use std::{sync::mpsc, thread};
+
+    use solana_remote_wallet::{
+        locator::Locator, remote_keypair::generate_remote_keypair, remote_wallet::maybe_wallet_manager,
+    };
+    use solana_sdk::{derivation_path::DerivationPath, pubkey::Pubkey, signature::Signature, signer::Signer};
+
+    use super::{RemoteSigner, SignerError};
+
+    type Reply = mpsc::Sender<Result<Signature, SignerError>>;
+
+    /// Ledger-backed signer. The USB handle is not `Send`, so it lives on a dedicated thread and requests are
+    /// forwarded over a channel; each signature requires approval on the device.
+    pub struct LedgerSigner {
+        pubkey: Pubkey,
+        locator: String,
+        requests: mpsc::Sender<(Vec<u8>, Reply)>,
+    }
+
+    impl LedgerSigner {
+        /// Connects to the device at `locator` (e.g. `usb://ledger`) and reads the key at `derivation_path`.
+        /// With `confirm_key` the device shows the address for the operator to check before it is used.
+        pub fn connect(locator: &str, derivation_path: DerivationPath, confirm_key: bool) -> Result<Self, SignerError> {
+            let (requests, inbox) = mpsc::channel::<(Vec<u8>, Reply)>();
+            let (ready_tx, ready_rx) = mpsc::channel::<Result<Pubkey, SignerError>>();
+            let locator_str = locator.to_string();
+            thread::Builder::new()
+                .name("ledger-signer".into())
+                .spawn(move || {
+                    let keypair = (|| {
+                        let manager = maybe_wallet_manager()
+                            .map_err(|e| SignerError::Connection(e.to_string()))?
+                            .ok_or_else(|| SignerError::Connection("no hardware wallet found".into()))?;
+                        let locator = Locator::new_from_path(&locator_str)
+                            .map_err(|e| SignerError::Connection(e.to_string()))?;
+                        generate_remote_keypair(locator, derivation_path, &manager, confirm_key, "authority")
+                            .map_err(|e| SignerError::Connection(e.to_string()))
+                    })();
+                    let keypair = match keypair {
+                        Ok(k) => {
+                            let _ = ready_tx.send(Ok(k.pubkey()));
+                            k
+                        }
+                        Err(e) => {
+                            let _ = ready_tx.send(Err(e));
+                            return;
+                        }
+                    };
+                    // Exits once every LedgerSigner handle (and thus the request sender) is dropped.
+                    for (message, reply) in inbox {
+                        let _ = reply.send(keypair.try_sign_message(&message));
+                    }
+                })
+                .map_err(|e| SignerError::Custom(e.to_string()))?;
+            let pubkey = ready_rx
+                .recv()
+                .map_err(|_| SignerError::Connection("ledger thread exited".into()))??;
+            Ok(Self {
+                pubkey,
+                locator: locator.to_string(),
+                requests,
+            })
+        }
+    }
+
+    impl RemoteSigner for LedgerSigner {
+        fn pubkey(&self) -> Pubkey {
+            self.pubkey
+        }
+
+        fn sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+            let (reply, response) = mpsc::channel();
+            self.requests
+                .send((message.to_vec(), reply))
+                .map_err(|_| SignerError::Connection("ledger thread exited".into()))?;
+            response
+                .recv()
+                .map_err(|_| SignerError::Connection("ledger thread exited".into()))?
+        }
+
+        fn description(&self) -> String {
+            format!("ledger {} ({})", self.locator, self.pubkey)
+        }
+
+        fn needs_confirmation(&self) -> bool {
+            true
+        }
+    }
+}
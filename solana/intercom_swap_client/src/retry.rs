@@ -0,0 +1,226 @@
+//! Send pipeline with transient-failure retries and safe re-signing.
+//!
+//! The rule that keeps this free of double-spends: a transaction is only re-signed with a fresh blockhash once
+//! the previous signature provably cannot land (its blockhash expired and the signature was never seen).
+//! Until then every retry rebroadcasts the identical signed bytes, which the runtime deduplicates.
+
+use std::{fmt, time::Duration};
+
+use solana_client::client_error::ClientError;
+use solana_sdk::{
+    clock::Slot,
+    commitment_config::CommitmentConfig,
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::{Signer, SignerError},
+    transaction::{uses_durable_nonce, Transaction, TransactionError},
+};
+
+use crate::{
+    client::{EscrowClient, FetchError},
+    confirm::{ConfirmOptions, ConfirmOutcome, ConfirmProgress},
+    error::SwapClientError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Consecutive transient RPC failures tolerated before giving up.
+    pub max_rpc_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Fresh-blockhash re-signs allowed after expiry. Ignored for durable-nonce transactions, which cannot be
+    /// refreshed without advancing the nonce.
+    pub max_resigns: u32,
+    pub confirm: ConfirmOptions,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_rpc_retries: 5,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(5),
+            max_resigns: 3,
+            confirm: ConfirmOptions::default(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, failures: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(1u32 << failures.saturating_sub(1).min(16))
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RetryEvent {
+    Confirm(ConfirmProgress),
+    RpcError { failures: u32, message: String },
+    /// The previous signature expired unseen; the transaction was re-signed with a new blockhash.
+    Resigned { previous: Signature, signature: Signature },
+}
+
+/// Final result of [`EscrowClient::send_with_retry`]. Every variant is settled: nothing is left in flight
+/// except after `Pending`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendOutcome {
+    Confirmed {
+        signature: Signature,
+        slot: Slot,
+        /// Earlier signatures of this transaction that expired without landing.
+        expired: Vec<Signature>,
+    },
+    /// Landed and failed; fees were charged.
+    Failed {
+        signature: Signature,
+        slot: Slot,
+        error: TransactionError,
+    },
+    /// Rejected in preflight and never broadcast.
+    Rejected { error: TransactionError },
+    /// Every signature expired unseen and the re-sign budget is spent. Safe to rebuild from scratch.
+    Expired { signatures: Vec<Signature> },
+    /// Durable-nonce transaction still valid when the confirm timeout elapsed; it may still land.
+    Pending { signature: Signature },
+}
+
+impl SendOutcome {
+    /// The failure of a `Failed` or `Rejected` outcome, with the failing account and a suggested fix. `tx` is
+    /// the transaction that was sent (any of its re-signed versions).
+    pub fn error(&self, program_id: &Pubkey, tx: &Transaction) -> Option<SwapClientError> {
+        match self {
+            Self::Failed { error, .. } | Self::Rejected { error } => {
+                Some(SwapClientError::from_transaction(program_id, tx, error.clone()))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RetryError {
+    Fetch(FetchError),
+    Sign(SignerError),
+}
+
+impl fmt::Display for RetryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fetch(e) => write!(f, "{e}"),
+            Self::Sign(e) => write!(f, "signing failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RetryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Fetch(e) => Some(e),
+            Self::Sign(e) => Some(e),
+        }
+    }
+}
+
+impl From<FetchError> for RetryError {
+    fn from(e: FetchError) -> Self {
+        Self::Fetch(e)
+    }
+}
+
+impl From<ClientError> for RetryError {
+    fn from(e: ClientError) -> Self {
+        Self::Fetch(e.into())
+    }
+}
+
+impl From<SignerError> for RetryError {
+    fn from(e: SignerError) -> Self {
+        Self::Sign(e)
+    }
+}
+
+impl EscrowClient {
+    /// Sends a signed `tx` until it settles, retrying transient RPC failures and re-signing with `signers` on
+    /// a fresh blockhash when the previous signature expired unseen.
+    pub async fn send_with_retry(
+        &self,
+        mut tx: Transaction,
+        signers: &[&dyn Signer],
+        policy: &RetryPolicy,
+        mut progress: impl FnMut(&RetryEvent),
+    ) -> Result<SendOutcome, RetryError> {
+        let durable = uses_durable_nonce(&tx).is_some();
+        let mut expired = Vec::new();
+        let mut failures = 0u32;
+        loop {
+            let outcome = self
+                .send_and_confirm(&tx, &policy.confirm, |p| progress(&RetryEvent::Confirm(p.clone())))
+                .await;
+            let previous = tx.signatures[0];
+            match outcome {
+                Ok(ConfirmOutcome::Confirmed { signature, slot }) => {
+                    return Ok(SendOutcome::Confirmed { signature, slot, expired });
+                }
+                Ok(ConfirmOutcome::Failed { signature, slot, error }) => {
+                    return Ok(SendOutcome::Failed { signature, slot, error });
+                }
+                Ok(ConfirmOutcome::TimedOut { signature, .. }) => {
+                    if durable {
+                        return Ok(SendOutcome::Pending { signature });
+                    }
+                    // Blockhash transactions expire within ~150 slots; keep rebroadcasting the same bytes until
+                    // the confirm loop reports Dropped.
+                    failures = 0;
+                    continue;
+                }
+                Ok(ConfirmOutcome::Dropped { signature }) => expired.push(signature),
+                Err(FetchError::Rpc(e)) => match e.get_transaction_error() {
+                    Some(TransactionError::BlockhashNotFound) if !durable && !self.may_still_land(&tx).await? => {
+                        expired.push(previous);
+                    }
+                    Some(TransactionError::BlockhashNotFound) | None => {
+                        failures += 1;
+                        progress(&RetryEvent::RpcError {
+                            failures,
+                            message: e.to_string(),
+                        });
+                        if failures > policy.max_rpc_retries {
+                            return Err(RetryError::Fetch(FetchError::Rpc(e)));
+                        }
+                        tokio::time::sleep(policy.backoff(failures)).await;
+                        continue;
+                    }
+                    Some(error) => return Ok(SendOutcome::Rejected { error }),
+                },
+                Err(e) => return Err(e.into()),
+            }
+
+            // Only reached once `previous` is known to be unlandable.
+            if durable || expired.len() as u32 > policy.max_resigns {
+                return Ok(SendOutcome::Expired { signatures: expired });
+            }
+            let blockhash = self.rpc().get_latest_blockhash().await?;
+            tx.try_sign(signers, blockhash)?;
+            failures = 0;
+            progress(&RetryEvent::Resigned {
+                previous,
+                signature: tx.signatures[0],
+            });
+        }
+    }
+
+    /// Blockhash still valid, or the signature already has a status (so it must not be re-signed).
+    async fn may_still_land(&self, tx: &Transaction) -> Result<bool, FetchError> {
+        let rpc = self.rpc();
+        if rpc
+            .is_blockhash_valid(&tx.message.recent_blockhash, CommitmentConfig::processed())
+            .await?
+        {
+            return Ok(true);
+        }
+        let status = rpc.get_signature_statuses(&tx.signatures[..1]).await?.value;
+        Ok(status.into_iter().next().flatten().is_some())
+    }
+}
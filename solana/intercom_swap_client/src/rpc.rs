@@ -0,0 +1,89 @@
+//! The RPC surface swap logic needs, as a trait so services can swap in [`crate::mock::MockRpc`] in tests.
+
+use std::future::Future;
+
+use solana_sdk::{
+    account::from_account, clock::Clock, hash::Hash, program_pack::Pack, pubkey::Pubkey, signature::Signature,
+    sysvar, transaction::Transaction,
+};
+
+use crate::{
+    client::{EscrowClient, FetchError},
+    state::{ConfigState, DecodeError, EscrowState, TradeConfigState},
+};
+
+pub trait EscrowRpc: Send + Sync {
+    fn get_escrow(&self, payment_hash: &[u8; 32]) -> impl Future<Output = Result<Option<EscrowState>, FetchError>> + Send;
+
+    fn get_config(&self) -> impl Future<Output = Result<Option<ConfigState>, FetchError>> + Send;
+
+    fn get_trade_config(
+        &self,
+        fee_collector: &Pubkey,
+    ) -> impl Future<Output = Result<Option<TradeConfigState>, FetchError>> + Send;
+
+    /// SPL token balance of `token_account`; 0 if it does not exist.
+    fn get_token_balance(&self, token_account: &Pubkey) -> impl Future<Output = Result<u64, FetchError>> + Send;
+
+    /// Cluster time as seen by the program (the Clock sysvar), which is what refund timeouts compare against.
+    fn get_unix_timestamp(&self) -> impl Future<Output = Result<i64, FetchError>> + Send;
+
+    fn get_latest_blockhash(&self) -> impl Future<Output = Result<Hash, FetchError>> + Send;
+
+    /// Submits a signed transaction and waits until it is confirmed or rejected.
+    fn send_transaction(&self, tx: &Transaction) -> impl Future<Output = Result<Signature, FetchError>> + Send;
+}
+
+impl EscrowRpc for EscrowClient {
+    async fn get_escrow(&self, payment_hash: &[u8; 32]) -> Result<Option<EscrowState>, FetchError> {
+        EscrowClient::get_escrow(self, payment_hash).await
+    }
+
+    async fn get_config(&self) -> Result<Option<ConfigState>, FetchError> {
+        EscrowClient::get_config(self).await
+    }
+
+    async fn get_trade_config(&self, fee_collector: &Pubkey) -> Result<Option<TradeConfigState>, FetchError> {
+        EscrowClient::get_trade_config(self, fee_collector).await
+    }
+
+    async fn get_token_balance(&self, token_account: &Pubkey) -> Result<u64, FetchError> {
+        let account = self
+            .rpc()
+            .get_account_with_commitment(token_account, self.commitment())
+            .await?
+            .value;
+        match account {
+            Some(account) => spl_token::state::Account::unpack(&account.data)
+                .map(|a| a.amount)
+                .map_err(|_| FetchError::Decode {
+                    address: *token_account,
+                    source: DecodeError::Invalid("token account"),
+                }),
+            None => Ok(0),
+        }
+    }
+
+    async fn get_unix_timestamp(&self) -> Result<i64, FetchError> {
+        let account = self
+            .rpc()
+            .get_account_with_commitment(&sysvar::clock::id(), self.commitment())
+            .await?
+            .value;
+        account
+            .and_then(|a| from_account::<Clock, _>(&a))
+            .map(|c| c.unix_timestamp)
+            .ok_or(FetchError::Decode {
+                address: sysvar::clock::id(),
+                source: DecodeError::Invalid("clock"),
+            })
+    }
+
+    async fn get_latest_blockhash(&self) -> Result<Hash, FetchError> {
+        Ok(self.rpc().get_latest_blockhash().await?)
+    }
+
+    async fn send_transaction(&self, tx: &Transaction) -> Result<Signature, FetchError> {
+        Ok(self.rpc().send_and_confirm_transaction(tx).await?)
+    }
+}
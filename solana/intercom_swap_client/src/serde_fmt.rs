@@ -0,0 +1,95 @@
+//! Human-readable serde encodings: keys and signatures as base58 strings, hashes as lowercase hex.
+//!
+//! Used through `#[serde(with = "...")]`; `solana-sdk`'s own impls encode these as byte arrays.
+
+use std::{fmt::Display, str::FromStr};
+
+use serde::{de::Error as _, Deserialize, Deserializer, Serializer};
+
+/// Any `Display + FromStr` type (`Pubkey`, `Signature`) as its string form.
+pub mod string {
+    use super::*;
+
+    pub fn serialize<T: Display, S: Serializer>(value: &T, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(value)
+    }
+
+    pub fn deserialize<'de, T, D>(d: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        String::deserialize(d)?.parse().map_err(D::Error::custom)
+    }
+}
+
+pub mod option_string {
+    use super::*;
+
+    pub fn serialize<T: Display, S: Serializer>(value: &Option<T>, s: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(v) => s.collect_str(v),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(d: D) -> Result<Option<T>, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(d)?
+            .map(|v| v.parse().map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+pub mod vec_string {
+    use serde::ser::SerializeSeq;
+
+    use super::*;
+
+    pub fn serialize<T: Display, S: Serializer>(values: &[T], s: S) -> Result<S::Ok, S::Error> {
+        let mut seq = s.serialize_seq(Some(values.len()))?;
+        for v in values {
+            seq.serialize_element(&v.to_string())?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, T, D>(d: D) -> Result<Vec<T>, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        Vec::<String>::deserialize(d)?
+            .into_iter()
+            .map(|v| v.parse().map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+/// 32-byte hashes (payment hash, preimage) as 64 hex characters.
+pub mod hex32 {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &[u8; 32], s: S) -> Result<S::Ok, S::Error> {
+        let hex: String = value.iter().map(|b| format!("{b:02x}")).collect();
+        s.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[u8; 32], D::Error> {
+        let hex = String::deserialize(d)?;
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(D::Error::custom("expected 32 bytes of hex"));
+        }
+        let mut out = [0u8; 32];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(D::Error::custom)?;
+        }
+        Ok(out)
+    }
+}
@@ -0,0 +1,165 @@
+//! Pre-send simulation with a structured diagnosis of failures.
+
+use solana_client::rpc_config::RpcSimulateTransactionConfig;
+use solana_sdk::{
+    account::from_account,
+    clock::Clock,
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    sysvar,
+    transaction::{Transaction, TransactionError},
+};
+
+use crate::{
+    client::{EscrowClient, FetchError},
+    error::{EscrowError, FailedAccount, SwapClientError},
+    state,
+};
+
+const REFUND_TAG: u8 = 2;
+
+/// Outcome of [`EscrowClient::simulate_and_explain`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Diagnosis {
+    pub ok: bool,
+    pub transaction_error: Option<TransactionError>,
+    /// Set when the escrow program failed with one of its custom codes.
+    pub escrow_error: Option<EscrowError>,
+    /// Index of the failing instruction within the transaction.
+    pub failing_instruction: Option<u8>,
+    /// Last `msg!` line logged before the failure (e.g. "vault mismatch").
+    pub program_message: Option<String>,
+    /// One-line human readable summary.
+    pub explanation: String,
+    /// Suggested fix (see [`SwapClientError::hint`]).
+    pub hint: Option<String>,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+}
+
+impl EscrowClient {
+    /// Simulates `tx` (signatures not verified, blockhash replaced) and explains any failure.
+    pub async fn simulate_and_explain(&self, tx: &Transaction) -> Result<Diagnosis, FetchError> {
+        let config = RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: true,
+            commitment: Some(self.commitment()),
+            ..RpcSimulateTransactionConfig::default()
+        };
+        let result = self.rpc().simulate_transaction_with_config(tx, config).await?.value;
+        let logs = result.logs.unwrap_or_default();
+        let program_message = last_program_log(&logs);
+
+        let Some(err) = result.err else {
+            return Ok(Diagnosis {
+                ok: true,
+                transaction_error: None,
+                escrow_error: None,
+                failing_instruction: None,
+                program_message,
+                explanation: "simulation succeeded".to_string(),
+                hint: None,
+                logs,
+                units_consumed: result.units_consumed,
+            });
+        };
+
+        let failing_instruction = match &err {
+            TransactionError::InstructionError(idx, _) => Some(*idx),
+            _ => None,
+        };
+        let failing_ix = failing_instruction.and_then(|idx| tx.message.instructions.get(idx as usize));
+        let classified = SwapClientError::from_transaction(self.program_id(), tx, err.clone());
+        let escrow_error = classified.escrow_error();
+        let hint = classified.hint().map(str::to_string);
+
+        let explanation = match escrow_error {
+            Some(EscrowError::TooEarly) => {
+                let escrow = failing_ix
+                    .filter(|ix| ix.data.first() == Some(&REFUND_TAG))
+                    .and_then(|ix| ix.accounts.get(1))
+                    .and_then(|idx| tx.message.account_keys.get(*idx as usize));
+                match escrow {
+                    Some(escrow) => self.explain_too_early(escrow).await?,
+                    None => EscrowError::TooEarly.to_string(),
+                }
+            }
+            Some(e) => {
+                let mut out = e.to_string();
+                if let SwapClientError::Program {
+                    account: Some(FailedAccount { role, address }),
+                    ..
+                } = &classified
+                {
+                    out.push_str(&format!(": {role} {address}"));
+                }
+                match &program_message {
+                    Some(m) => format!("{out} ({m})"),
+                    None => out,
+                }
+            }
+            None => explain_transaction_error(&err, program_message.as_deref()),
+        };
+
+        Ok(Diagnosis {
+            ok: false,
+            transaction_error: Some(err),
+            escrow_error,
+            failing_instruction,
+            program_message,
+            explanation,
+            hint,
+            logs,
+            units_consumed: result.units_consumed,
+        })
+    }
+
+    async fn explain_too_early(&self, escrow: &Pubkey) -> Result<String, FetchError> {
+        let rpc = self.rpc();
+        let escrow_account = rpc.get_account_with_commitment(escrow, self.commitment()).await?.value;
+        let clock_account = rpc
+            .get_account_with_commitment(&sysvar::clock::id(), self.commitment())
+            .await?
+            .value;
+        let refund_after = escrow_account.and_then(|a| state::decode_escrow(&a.data).ok().map(|s| s.refund_after));
+        let now = clock_account.and_then(|a| from_account::<Clock, _>(&a)).map(|c| c.unix_timestamp);
+        Ok(match (refund_after, now) {
+            (Some(refund_after), Some(now)) => format!(
+                "refund too early: {} remaining",
+                format_hms(refund_after.saturating_sub(now).max(0))
+            ),
+            _ => EscrowError::TooEarly.to_string(),
+        })
+    }
+}
+
+fn last_program_log(logs: &[String]) -> Option<String> {
+    logs.iter()
+        .rev()
+        .find_map(|l| l.strip_prefix("Program log: "))
+        .map(str::to_string)
+}
+
+fn explain_transaction_error(err: &TransactionError, program_message: Option<&str>) -> String {
+    let base = match err {
+        TransactionError::BlockhashNotFound => "blockhash expired or not found; rebuild and re-sign".to_string(),
+        TransactionError::InsufficientFundsForFee => "fee payer cannot cover the transaction fee".to_string(),
+        TransactionError::AccountNotFound => "fee payer account does not exist".to_string(),
+        TransactionError::InstructionError(idx, InstructionError::ComputationalBudgetExceeded) => {
+            format!("instruction {idx} exceeded the compute budget; raise the compute unit limit")
+        }
+        TransactionError::InstructionError(idx, e) => format!("instruction {idx} failed: {e}"),
+        other => other.to_string(),
+    };
+    match program_message {
+        Some(m) => format!("{base} ({m})"),
+        None => base,
+    }
+}
+
+/// Formats seconds as `HH:MM:SS` (hours may exceed 24).
+pub fn format_hms(secs: i64) -> String {
+    let secs = secs.max(0);
+    format!("{:02}:{:02}:{:02}", secs / 3600, (secs % 3600) / 60, secs % 60)
+}
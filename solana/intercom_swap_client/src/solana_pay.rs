@@ -0,0 +1,163 @@
+//! Server side of the Solana Pay transaction-request spec for Init and Claim.
+//!
+//! A wallet scans `solana:<link>` ([`transaction_request_url`]), GETs the link for a [`MetadataResponse`],
+//! then POSTs a [`TransactionRequest`] with its account and receives a [`TransactionResponse`] carrying an
+//! unsigned transaction with that account as fee payer. The HTTP layer is left to the caller; these types
+//! serialize to the spec's JSON with the `serde` feature. Transactions are preflighted first, so a wallet is
+//! never handed one the program would reject.
+
+use std::fmt;
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    client::{EscrowClient, FetchError},
+    instruction::InitArgs,
+    partial_sign,
+    preflight::Violation,
+    transaction::{self, TxOptions},
+};
+
+/// Body of the GET response: what the wallet shows before the user approves the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MetadataResponse {
+    pub label: String,
+    /// Absolute URL of an SVG, PNG or WebP icon.
+    pub icon: String,
+}
+
+/// Body of the POST request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransactionRequest {
+    /// Wallet account that will sign the returned transaction.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+    pub account: Pubkey,
+}
+
+/// Body of the POST response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransactionResponse {
+    /// Base64 wire-format transaction.
+    pub transaction: String,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub message: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum SolanaPayError {
+    Fetch(FetchError),
+    EscrowNotFound,
+    /// The transaction would fail on-chain; report these to the wallet instead of a transaction.
+    Rejected(Vec<Violation>),
+}
+
+impl fmt::Display for SolanaPayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fetch(e) => write!(f, "{e}"),
+            Self::EscrowNotFound => write!(f, "escrow not found"),
+            Self::Rejected(violations) => {
+                let reasons: Vec<String> = violations.iter().map(ToString::to_string).collect();
+                write!(f, "transaction would fail: {}", reasons.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for SolanaPayError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Fetch(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<FetchError> for SolanaPayError {
+    fn from(e: FetchError) -> Self {
+        Self::Fetch(e)
+    }
+}
+
+impl From<solana_client::client_error::ClientError> for SolanaPayError {
+    fn from(e: solana_client::client_error::ClientError) -> Self {
+        Self::Fetch(e.into())
+    }
+}
+
+fn check(violations: Vec<Violation>) -> Result<(), SolanaPayError> {
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(SolanaPayError::Rejected(violations))
+    }
+}
+
+/// Whether `b` is left as-is by JavaScript's `encodeURIComponent`, which wallets use to decode the link.
+fn is_unreserved(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"-_.!~*'()".contains(&b)
+}
+
+/// `solana:` URL for the transaction-request endpoint at `link` (an absolute `https` URL). Per the spec the
+/// link is percent-encoded only when it carries a query string.
+pub fn transaction_request_url(link: &str) -> String {
+    if !link.contains('?') {
+        return format!("solana:{link}");
+    }
+    let mut out = String::from("solana:");
+    for &b in link.as_bytes() {
+        if is_unreserved(b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{b:02X}"));
+        }
+    }
+    out
+}
+
+impl EscrowClient {
+    /// Init transaction for the requesting wallet to fund the escrow described by `args` from its ATA.
+    pub async fn solana_pay_init(
+        &self,
+        request: &TransactionRequest,
+        mint: &Pubkey,
+        args: &InitArgs,
+        opts: &TxOptions,
+        message: Option<String>,
+    ) -> Result<TransactionResponse, SolanaPayError> {
+        check(self.validate_init(&request.account, mint, args).await?)?;
+        let blockhash = self.rpc().get_latest_blockhash().await?;
+        let tx = transaction::init_transaction(self.program_id(), &request.account, mint, args, opts, blockhash);
+        Ok(TransactionResponse {
+            transaction: partial_sign::encode_base64(&tx),
+            message,
+        })
+    }
+
+    /// Claim transaction revealing `preimage`; the requesting wallet must be the escrow recipient and pays the
+    /// fees.
+    pub async fn solana_pay_claim(
+        &self,
+        request: &TransactionRequest,
+        payment_hash: &[u8; 32],
+        preimage: &[u8; 32],
+        opts: &TxOptions,
+        message: Option<String>,
+    ) -> Result<TransactionResponse, SolanaPayError> {
+        check(self.validate_claim(payment_hash, &request.account, preimage).await?)?;
+        let escrow = self
+            .get_escrow(payment_hash)
+            .await?
+            .ok_or(SolanaPayError::EscrowNotFound)?;
+        let blockhash = self.rpc().get_latest_blockhash().await?;
+        let tx =
+            transaction::claim_transaction(self.program_id(), &escrow, &request.account, preimage, opts, blockhash);
+        Ok(TransactionResponse {
+            transaction: partial_sign::encode_base64(&tx),
+            message,
+        })
+    }
+}
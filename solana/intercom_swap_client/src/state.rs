@@ -0,0 +1,179 @@
+//! Account decoders for escrow, config, trade config and index PDAs.
+//!
+//! Layouts come from `intercom-swap-core`; this module converts raw keys to [`Pubkey`] for client code.
+
+use std::fmt;
+
+use intercom_swap_core::state::{self as wire, StateError, CONFIG_V1, ESCROW_INDEX_V1};
+pub use intercom_swap_core::state::{
+    EscrowStatus, CONFIG_LEN, ESCROW_INDEX_CAPACITY, ESCROW_INDEX_LEN, ESCROW_V3_LEN, ESCROW_V4_LEN,
+};
+use solana_sdk::pubkey::Pubkey;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct EscrowState {
+    pub v: u8,
+    pub status: EscrowStatus,
+    #[cfg_attr(feature = "serde", serde(rename = "paymentHashHex", with = "crate::serde_fmt::hex32"))]
+    pub payment_hash: [u8; 32],
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+    pub recipient: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+    pub refund: Pubkey,
+    pub refund_after: i64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+    pub mint: Pubkey,
+    pub net_amount: u64,
+    pub platform_fee_amount: u64,
+    pub platform_fee_bps: u16,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+    pub platform_fee_collector: Pubkey,
+    pub trade_fee_amount: u64,
+    pub trade_fee_bps: u16,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+    pub trade_fee_collector: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+    pub vault: Pubkey,
+    pub bump: u8,
+    /// Payer that funded the escrow; `None` for v3 accounts.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::option_string"))]
+    pub creator: Option<Pubkey>,
+}
+
+impl EscrowState {
+    /// Amount currently locked in the vault (zero once claimed or refunded).
+    pub fn total_amount(&self) -> u64 {
+        self.net_amount
+            .saturating_add(self.platform_fee_amount)
+            .saturating_add(self.trade_fee_amount)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct ConfigState {
+    pub v: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+    pub authority: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+    pub fee_collector: Pubkey,
+    pub fee_bps: u16,
+    pub bump: u8,
+}
+
+/// Trade config accounts share the platform config layout.
+pub type TradeConfigState = ConfigState;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct EscrowIndexState {
+    pub v: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::string"))]
+    pub owner: Pubkey,
+    pub total: u64,
+    /// Recorded escrow addresses, newest first.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fmt::vec_string"))]
+    pub escrows: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    TooSmall { expected: usize, actual: usize },
+    UnsupportedVersion(u8),
+    InvalidStatus(u8),
+    /// Not an account of the expected kind (token account, lookup table, nonce).
+    Invalid(&'static str),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooSmall { expected, actual } => {
+                write!(f, "account too small: expected {expected} bytes, got {actual}")
+            }
+            Self::UnsupportedVersion(v) => write!(f, "unsupported account version v={v}"),
+            Self::InvalidStatus(s) => write!(f, "invalid escrow status {s}"),
+            Self::Invalid(kind) => write!(f, "not a valid {kind} account"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<StateError> for DecodeError {
+    fn from(e: StateError) -> Self {
+        match e {
+            StateError::TooSmall { expected, actual } => Self::TooSmall { expected, actual },
+            StateError::UnsupportedVersion(v) => Self::UnsupportedVersion(v),
+            StateError::InvalidStatus(s) => Self::InvalidStatus(s),
+        }
+    }
+}
+
+fn pubkey(bytes: [u8; 32]) -> Pubkey {
+    Pubkey::new_from_array(bytes)
+}
+
+/// Decodes a v3 or v4 escrow account.
+pub fn decode_escrow(data: &[u8]) -> Result<EscrowState, DecodeError> {
+    let s = wire::EscrowState::unpack(data)?;
+    Ok(EscrowState {
+        v: s.v,
+        status: s.status,
+        payment_hash: s.payment_hash,
+        recipient: pubkey(s.recipient),
+        refund: pubkey(s.refund),
+        refund_after: s.refund_after,
+        mint: pubkey(s.mint),
+        net_amount: s.net_amount,
+        platform_fee_amount: s.platform_fee_amount,
+        platform_fee_bps: s.platform_fee_bps,
+        platform_fee_collector: pubkey(s.platform_fee_collector),
+        trade_fee_amount: s.trade_fee_amount,
+        trade_fee_bps: s.trade_fee_bps,
+        trade_fee_collector: pubkey(s.trade_fee_collector),
+        vault: pubkey(s.vault),
+        bump: s.bump,
+        creator: s.creator.map(pubkey),
+    })
+}
+
+/// Decodes the platform config account.
+pub fn decode_config(data: &[u8]) -> Result<ConfigState, DecodeError> {
+    let s = wire::ConfigState::unpack(data)?;
+    if s.v != CONFIG_V1 {
+        return Err(DecodeError::UnsupportedVersion(s.v));
+    }
+    Ok(ConfigState {
+        v: s.v,
+        authority: pubkey(s.authority),
+        fee_collector: pubkey(s.fee_collector),
+        fee_bps: s.fee_bps,
+        bump: s.bump,
+    })
+}
+
+/// Decodes a trade config account.
+pub fn decode_trade_config(data: &[u8]) -> Result<TradeConfigState, DecodeError> {
+    decode_config(data)
+}
+
+/// Decodes a recipient or refund index account.
+pub fn decode_escrow_index(data: &[u8]) -> Result<EscrowIndexState, DecodeError> {
+    let s = wire::EscrowIndexState::unpack(data)?;
+    if s.v != ESCROW_INDEX_V1 {
+        return Err(DecodeError::UnsupportedVersion(s.v));
+    }
+    Ok(EscrowIndexState {
+        v: s.v,
+        owner: pubkey(s.owner),
+        total: s.total,
+        escrows: s.newest_first().copied().map(pubkey).collect(),
+        bump: s.bump,
+    })
+}
@@ -0,0 +1,247 @@
+//! Push-based escrow status tracking over the RPC WebSocket, replacing polling.
+//!
+//! Each subscription runs on a background task that reconnects with exponential backoff and, after every
+//! (re)subscribe, re-reads the current state over HTTP so a transition that happened while disconnected is
+//! still reported. Only status changes are yielded.
+
+use std::{collections::HashMap, time::Duration};
+
+use futures::stream::{select_all, StreamExt};
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_client::{nonblocking::pubsub_client::PubsubClient, rpc_config::RpcAccountInfoConfig};
+use solana_sdk::{account::Account, clock::Slot, pubkey::Pubkey};
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use crate::{
+    client::EscrowClient,
+    filters::EscrowQuery,
+    pda,
+    state::{self, EscrowState, EscrowStatus},
+};
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusTransition {
+    pub address: Pubkey,
+    pub slot: Slot,
+    /// `None` for the first observation of an escrow.
+    pub from: Option<EscrowStatus>,
+    pub state: EscrowState,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubscriptionEvent {
+    Transition(StatusTransition),
+    /// The WebSocket dropped or could not be opened; the task retries after a backoff.
+    Disconnected { error: String },
+}
+
+/// Handle to a running subscription; dropping it stops the background task.
+pub struct EscrowSubscription {
+    rx: mpsc::Receiver<SubscriptionEvent>,
+    task: JoinHandle<()>,
+}
+
+impl EscrowSubscription {
+    /// Next event; `None` once the subscription has ended (single-escrow subscriptions end after
+    /// Claimed/Refunded).
+    pub async fn next(&mut self) -> Option<SubscriptionEvent> {
+        self.rx.recv().await
+    }
+}
+
+impl Drop for EscrowSubscription {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// WebSocket URL the CLI would use for `rpc_url`: same host, `ws(s)` scheme, and port 8899 mapped to 8900.
+pub fn websocket_url(rpc_url: &str) -> String {
+    let url = if let Some(rest) = rpc_url.strip_prefix("https://") {
+        format!("wss://{rest}")
+    } else if let Some(rest) = rpc_url.strip_prefix("http://") {
+        format!("ws://{rest}")
+    } else {
+        rpc_url.to_string()
+    };
+    url.replacen(":8899", ":8900", 1)
+}
+
+fn account_config(client: &EscrowClient) -> RpcAccountInfoConfig {
+    RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(client.commitment()),
+        ..RpcAccountInfoConfig::default()
+    }
+}
+
+/// Tracks the last status per escrow and forwards changes. `Err` means the receiver is gone.
+struct Tracker {
+    last: HashMap<Pubkey, EscrowStatus>,
+    tx: mpsc::Sender<SubscriptionEvent>,
+}
+
+impl Tracker {
+    async fn observe(&mut self, address: Pubkey, slot: Slot, state: EscrowState) -> Result<(), ()> {
+        let from = self.last.insert(address, state.status);
+        if from == Some(state.status) {
+            return Ok(());
+        }
+        let event = SubscriptionEvent::Transition(StatusTransition {
+            address,
+            slot,
+            from,
+            state,
+        });
+        self.tx.send(event).await.map_err(|_| ())
+    }
+
+    async fn observe_ui(&mut self, address: Pubkey, slot: Slot, account: &UiAccount) -> Result<(), ()> {
+        match account.decode::<Account>().and_then(|a| state::decode_escrow(&a.data).ok()) {
+            Some(state) => self.observe(address, slot, state).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn disconnected(&self, error: String, backoff: &mut Duration) -> Result<(), ()> {
+        self.tx
+            .send(SubscriptionEvent::Disconnected { error })
+            .await
+            .map_err(|_| ())?;
+        tokio::time::sleep(*backoff).await;
+        *backoff = (*backoff * 2).min(MAX_BACKOFF);
+        Ok(())
+    }
+
+    fn settled(&self, address: &Pubkey) -> bool {
+        matches!(
+            self.last.get(address),
+            Some(EscrowStatus::Claimed | EscrowStatus::Refunded)
+        )
+    }
+}
+
+/// One connection's worth of `accountSubscribe` on `address`. `Ok` once the escrow settled or the receiver
+/// went away; `Err` with a reason when the connection needs to be re-established.
+async fn watch_account(
+    client: &EscrowClient,
+    ws_url: &str,
+    address: Pubkey,
+    tracker: &mut Tracker,
+) -> Result<(), String> {
+    let pubsub = PubsubClient::new(ws_url).await.map_err(|e| e.to_string())?;
+    let (mut updates, _unsubscribe) = pubsub
+        .account_subscribe(&address, Some(account_config(client)))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let current = client
+        .rpc()
+        .get_account_with_commitment(&address, client.commitment())
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(state) = current.value.and_then(|a| state::decode_escrow(&a.data).ok()) {
+        if tracker.observe(address, current.context.slot, state).await.is_err() || tracker.settled(&address) {
+            return Ok(());
+        }
+    }
+
+    while let Some(update) = updates.next().await {
+        if tracker
+            .observe_ui(address, update.context.slot, &update.value)
+            .await
+            .is_err()
+            || tracker.settled(&address)
+        {
+            return Ok(());
+        }
+    }
+    Err("account subscription closed".into())
+}
+
+/// One connection's worth of `programSubscribe` with `query`'s filters (one subscription per escrow layout).
+async fn watch_program(
+    client: &EscrowClient,
+    ws_url: &str,
+    query: &EscrowQuery,
+    tracker: &mut Tracker,
+) -> Result<(), String> {
+    let pubsub = PubsubClient::new(ws_url).await.map_err(|e| e.to_string())?;
+    let mut streams = Vec::new();
+    for config in query.configs(client.commitment()) {
+        let (updates, _unsubscribe) = pubsub
+            .program_subscribe(client.program_id(), Some(config))
+            .await
+            .map_err(|e| e.to_string())?;
+        streams.push(updates);
+    }
+    let mut updates = select_all(streams);
+
+    let slot = client.rpc().get_slot().await.map_err(|e| e.to_string())?;
+    for (address, state) in client.list_escrows(query).await.map_err(|e| e.to_string())? {
+        if tracker.observe(address, slot, state).await.is_err() {
+            return Ok(());
+        }
+    }
+
+    while let Some(update) = updates.next().await {
+        let Ok(address) = update.value.pubkey.parse::<Pubkey>() else {
+            continue;
+        };
+        if tracker
+            .observe_ui(address, update.context.slot, &update.value.account)
+            .await
+            .is_err()
+        {
+            return Ok(());
+        }
+    }
+    Err("program subscription closed".into())
+}
+
+impl EscrowClient {
+    /// Status transitions of the escrow for `payment_hash` (Active → Claimed/Refunded), ending once it settles.
+    /// `ws_url` defaults to [`websocket_url`] of the RPC URL.
+    pub fn subscribe_escrow(&self, payment_hash: &[u8; 32], ws_url: Option<String>) -> EscrowSubscription {
+        let address = pda::find_escrow_pda(self.program_id(), payment_hash).0;
+        let client = self.clone();
+        let ws_url = ws_url.unwrap_or_else(|| websocket_url(&self.rpc().url()));
+        let (tx, rx) = mpsc::channel(16);
+        let task = tokio::spawn(async move {
+            let mut tracker = Tracker {
+                last: HashMap::new(),
+                tx,
+            };
+            let mut backoff = INITIAL_BACKOFF;
+            while let Err(error) = watch_account(&client, &ws_url, address, &mut tracker).await {
+                if tracker.disconnected(error, &mut backoff).await.is_err() {
+                    return;
+                }
+            }
+        });
+        EscrowSubscription { rx, task }
+    }
+
+    /// Status transitions of every escrow matching `query`'s server-side filters. Runs until dropped.
+    pub fn subscribe_escrows(&self, query: EscrowQuery, ws_url: Option<String>) -> EscrowSubscription {
+        let client = self.clone();
+        let ws_url = ws_url.unwrap_or_else(|| websocket_url(&self.rpc().url()));
+        let (tx, rx) = mpsc::channel(256);
+        let task = tokio::spawn(async move {
+            let mut tracker = Tracker {
+                last: HashMap::new(),
+                tx,
+            };
+            let mut backoff = INITIAL_BACKOFF;
+            while let Err(error) = watch_program(&client, &ws_url, &query, &mut tracker).await {
+                if tracker.disconnected(error, &mut backoff).await.is_err() {
+                    return;
+                }
+            }
+        });
+        EscrowSubscription { rx, task }
+    }
+}
@@ -0,0 +1,150 @@
+//! Ready-to-sign transactions for init/claim/refund.
+//!
+//! Each builder prepends ComputeBudget instructions and idempotent ATA creation for the token accounts the
+//! program expects to exist, so a wallet only has to sign and send.
+
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, hash::Hash, instruction::Instruction, message::Message,
+    pubkey::Pubkey, transaction::Transaction,
+};
+use spl_associated_token_account::{get_associated_token_address, instruction::create_associated_token_account_idempotent};
+
+use crate::{
+    instruction::{self, InitArgs},
+    pda,
+    state::EscrowState,
+};
+
+/// Compute unit limits with headroom over measured usage (init creates up to 6 accounts via CPI).
+pub const INIT_COMPUTE_UNITS: u32 = 300_000;
+pub const CLAIM_COMPUTE_UNITS: u32 = 150_000;
+pub const REFUND_COMPUTE_UNITS: u32 = 100_000;
+
+/// Compute budget overrides; `None` keeps the per-instruction default limit and no priority fee.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TxOptions {
+    pub compute_unit_limit: Option<u32>,
+    pub compute_unit_price_micro_lamports: Option<u64>,
+}
+
+impl TxOptions {
+    fn compute_budget(&self, default_limit: u32) -> Vec<Instruction> {
+        let mut out = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+            self.compute_unit_limit.unwrap_or(default_limit),
+        )];
+        if let Some(price) = self.compute_unit_price_micro_lamports.filter(|p| *p > 0) {
+            out.push(ComputeBudgetInstruction::set_compute_unit_price(price));
+        }
+        out
+    }
+}
+
+fn create_ata(payer: &Pubkey, owner: &Pubkey, mint: &Pubkey) -> Instruction {
+    create_associated_token_account_idempotent(payer, owner, mint, &spl_token::id())
+}
+
+/// Instructions for an escrow Init (the program itself creates the escrow, vault and fee vault accounts).
+pub fn init_instructions(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    args: &InitArgs,
+    opts: &TxOptions,
+) -> Vec<Instruction> {
+    let payer_token = get_associated_token_address(payer, mint);
+    let mut ixs = opts.compute_budget(INIT_COMPUTE_UNITS);
+    ixs.push(instruction::init(program_id, payer, &payer_token, mint, args));
+    ixs
+}
+
+/// Instructions claiming `escrow` into the recipient's ATA, creating it and both fee vault ATAs if missing.
+pub fn claim_instructions(
+    program_id: &Pubkey,
+    escrow: &EscrowState,
+    fee_payer: &Pubkey,
+    preimage: &[u8; 32],
+    opts: &TxOptions,
+) -> Vec<Instruction> {
+    let config = pda::find_config_pda(program_id).0;
+    let trade_config = pda::find_trade_config_pda(program_id, &escrow.trade_fee_collector).0;
+    let recipient_token = get_associated_token_address(&escrow.recipient, &escrow.mint);
+    let mut ixs = opts.compute_budget(CLAIM_COMPUTE_UNITS);
+    ixs.push(create_ata(fee_payer, &escrow.recipient, &escrow.mint));
+    ixs.push(create_ata(fee_payer, &config, &escrow.mint));
+    ixs.push(create_ata(fee_payer, &trade_config, &escrow.mint));
+    ixs.push(instruction::claim(
+        program_id,
+        &escrow.recipient,
+        &recipient_token,
+        &escrow.mint,
+        &escrow.payment_hash,
+        preimage,
+        &escrow.trade_fee_collector,
+        escrow.creator.as_ref(),
+    ));
+    ixs
+}
+
+/// Instructions refunding `escrow` into the refund key's ATA, creating it if missing.
+pub fn refund_instructions(
+    program_id: &Pubkey,
+    escrow: &EscrowState,
+    fee_payer: &Pubkey,
+    opts: &TxOptions,
+) -> Vec<Instruction> {
+    let refund_token = get_associated_token_address(&escrow.refund, &escrow.mint);
+    let mut ixs = opts.compute_budget(REFUND_COMPUTE_UNITS);
+    ixs.push(create_ata(fee_payer, &escrow.refund, &escrow.mint));
+    ixs.push(instruction::refund(
+        program_id,
+        &escrow.refund,
+        &refund_token,
+        &escrow.mint,
+        &escrow.payment_hash,
+        escrow.creator.as_ref(),
+    ));
+    ixs
+}
+
+fn unsigned(ixs: &[Instruction], fee_payer: &Pubkey, recent_blockhash: Hash) -> Transaction {
+    Transaction::new_unsigned(Message::new_with_blockhash(ixs, Some(fee_payer), &recent_blockhash))
+}
+
+/// Unsigned Init transaction; `payer` pays fees and must sign.
+pub fn init_transaction(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    args: &InitArgs,
+    opts: &TxOptions,
+    recent_blockhash: Hash,
+) -> Transaction {
+    unsigned(&init_instructions(program_id, payer, mint, args, opts), payer, recent_blockhash)
+}
+
+/// Unsigned Claim transaction; the escrow recipient and `fee_payer` must sign.
+pub fn claim_transaction(
+    program_id: &Pubkey,
+    escrow: &EscrowState,
+    fee_payer: &Pubkey,
+    preimage: &[u8; 32],
+    opts: &TxOptions,
+    recent_blockhash: Hash,
+) -> Transaction {
+    unsigned(
+        &claim_instructions(program_id, escrow, fee_payer, preimage, opts),
+        fee_payer,
+        recent_blockhash,
+    )
+}
+
+/// Unsigned Refund transaction; the escrow refund key and `fee_payer` must sign.
+pub fn refund_transaction(
+    program_id: &Pubkey,
+    escrow: &EscrowState,
+    fee_payer: &Pubkey,
+    opts: &TxOptions,
+    recent_blockhash: Hash,
+) -> Transaction {
+    unsigned(&refund_instructions(program_id, escrow, fee_payer, opts), fee_payer, recent_blockhash)
+}
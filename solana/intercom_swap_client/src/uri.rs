@@ -0,0 +1,305 @@
+//! `intercomswap:` URIs for handing swap parameters between wallets via links and QR codes.
+//!
+//! ```text
+//! intercomswap:<payment_hash_hex>?mint=<base58>&amount=<u64>&recipient=<base58>&refund=<base58>&expiry=<unix>[&bolt11=<invoice>]
+//! ```
+//!
+//! `amount` is the net token amount in base units and `expiry` the escrow's `refund_after`. Encoding is
+//! canonical (fixed parameter order, lowercase hex and invoice) so equal swaps produce byte-identical URIs.
+//! Parsing is strict: unknown, duplicate, missing or malformed parameters are errors. Only the scheme is
+//! matched case-insensitively, and the invoice is lowercased (QR alphanumeric mode uppercases it).
+
+use std::{fmt, str::FromStr};
+
+use solana_sdk::pubkey::Pubkey;
+
+pub const SCHEME: &str = "intercomswap";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapUri {
+    pub payment_hash: [u8; 32],
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub recipient: Pubkey,
+    pub refund: Pubkey,
+    pub expiry: i64,
+    pub bolt11: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UriError {
+    Scheme,
+    InvalidPaymentHash,
+    MissingParam(&'static str),
+    DuplicateParam(String),
+    UnknownParam(String),
+    InvalidValue(&'static str),
+}
+
+impl fmt::Display for UriError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Scheme => write!(f, "not an {SCHEME}: URI"),
+            Self::InvalidPaymentHash => write!(f, "payment hash must be 64 lowercase hex characters"),
+            Self::MissingParam(p) => write!(f, "missing parameter {p}"),
+            Self::DuplicateParam(p) => write!(f, "duplicate parameter {p}"),
+            Self::UnknownParam(p) => write!(f, "unknown parameter {p}"),
+            Self::InvalidValue(p) => write!(f, "invalid value for {p}"),
+        }
+    }
+}
+
+impl std::error::Error for UriError {}
+
+impl fmt::Display for SwapUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{SCHEME}:")?;
+        for b in &self.payment_hash {
+            write!(f, "{b:02x}")?;
+        }
+        write!(
+            f,
+            "?mint={}&amount={}&recipient={}&refund={}&expiry={}",
+            self.mint, self.amount, self.recipient, self.refund, self.expiry
+        )?;
+        if let Some(bolt11) = &self.bolt11 {
+            write!(f, "&bolt11={}", bolt11.to_ascii_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_hash(s: &str) -> Result<[u8; 32], UriError> {
+    if s.len() != 64 || !s.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)) {
+        return Err(UriError::InvalidPaymentHash);
+    }
+    let mut out = [0u8; 32];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).map_err(|_| UriError::InvalidPaymentHash)?;
+    }
+    Ok(out)
+}
+
+fn parse_param<T: FromStr>(name: &'static str, value: Option<&str>) -> Result<T, UriError> {
+    let value = value.ok_or(UriError::MissingParam(name))?;
+    // Reject forms FromStr tolerates but the canonical encoding never produces ("+5", "007").
+    if value.starts_with('+') || (value.len() > 1 && value.starts_with('0')) || value.starts_with("-0") {
+        return Err(UriError::InvalidValue(name));
+    }
+    value.parse().map_err(|_| UriError::InvalidValue(name))
+}
+
+impl FromStr for SwapUri {
+    type Err = UriError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scheme, rest) = s.split_once(':').ok_or(UriError::Scheme)?;
+        if !scheme.eq_ignore_ascii_case(SCHEME) {
+            return Err(UriError::Scheme);
+        }
+        let (hash, query) = rest.split_once('?').ok_or(UriError::MissingParam("mint"))?;
+        let payment_hash = parse_hash(hash)?;
+
+        const NAMES: [&str; 6] = ["mint", "amount", "recipient", "refund", "expiry", "bolt11"];
+        let mut values: [Option<&str>; 6] = [None; 6];
+        for pair in query.split('&') {
+            let (key, value) = pair.split_once('=').ok_or_else(|| UriError::UnknownParam(pair.to_string()))?;
+            let slot = NAMES
+                .iter()
+                .position(|n| *n == key)
+                .ok_or_else(|| UriError::UnknownParam(key.to_string()))?;
+            if values[slot].replace(value).is_some() {
+                return Err(UriError::DuplicateParam(key.to_string()));
+            }
+        }
+        let [mint, amount, recipient, refund, expiry, bolt11] = values;
+
+        let bolt11 = match bolt11 {
+            Some(v) if !v.is_empty() && v.bytes().all(|b| b.is_ascii_alphanumeric()) => Some(v.to_ascii_lowercase()),
+            Some(_) => return Err(UriError::InvalidValue("bolt11")),
+            None => None,
+        };
+        Ok(Self {
+            payment_hash,
+            mint: parse_param("mint", mint)?,
+            amount: parse_param("amount", amount)?,
+            recipient: parse_param("recipient", recipient)?,
+            refund: parse_param("refund", refund)?,
+            expiry: parse_param("expiry", expiry)?,
+            bolt11,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn swap(bolt11: Option<&str>) -> SwapUri {
+        SwapUri {
+            payment_hash: [0xab; 32],
+            mint: Pubkey::new_unique(),
+            amount: 64_665_000,
+            recipient: Pubkey::new_unique(),
+            refund: Pubkey::new_unique(),
+            expiry: 1_700_000_000,
+            bolt11: bolt11.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        for bolt11 in [None, Some("lnbcrt1u1pjq0x5mpp5")] {
+            let uri = swap(bolt11);
+            let encoded = uri.to_string();
+            assert_eq!(encoded.parse::<SwapUri>().unwrap(), uri);
+            assert_eq!(encoded.parse::<SwapUri>().unwrap().to_string(), encoded);
+        }
+    }
+
+    #[test]
+    fn encoding_is_canonical() {
+        let uri = swap(Some("LNBCRT1U1PJQ0X5MPP5"));
+        let encoded = uri.to_string();
+        assert_eq!(
+            encoded,
+            format!(
+                concat!(
+                    "intercomswap:{}?mint={}&amount=64665000&recipient={}&refund={}",
+                    "&expiry=1700000000&bolt11=lnbcrt1u1pjq0x5mpp5"
+                ),
+                "ab".repeat(32),
+                uri.mint,
+                uri.recipient,
+                uri.refund
+            )
+        );
+    }
+
+    #[test]
+    fn scheme_is_matched_case_insensitively_and_invoice_lowercased() {
+        let uri = swap(Some("lnbcrt1u1pjq0x5mpp5"));
+        // As a QR code in alphanumeric mode would carry the scheme and invoice.
+        let shouted = uri
+            .to_string()
+            .replacen(SCHEME, "INTERCOMSWAP", 1)
+            .replace("lnbcrt1u1pjq0x5mpp5", "LNBCRT1U1PJQ0X5MPP5");
+        assert_eq!(shouted.parse::<SwapUri>().unwrap(), uri);
+    }
+
+    #[test]
+    fn params_may_come_in_any_order() {
+        let uri = swap(None);
+        let reordered = format!(
+            "{SCHEME}:{}?expiry={}&refund={}&recipient={}&amount={}&mint={}",
+            "ab".repeat(32),
+            uri.expiry,
+            uri.refund,
+            uri.recipient,
+            uri.amount,
+            uri.mint
+        );
+        assert_eq!(reordered.parse::<SwapUri>().unwrap(), uri);
+    }
+
+    #[test]
+    fn wrong_scheme_is_rejected() {
+        let encoded = swap(None).to_string();
+        for other in ["bitcoin", "solana", "intercom", ""] {
+            let uri = encoded.replacen(SCHEME, other, 1);
+            assert_eq!(uri.parse::<SwapUri>(), Err(UriError::Scheme), "{other}");
+        }
+        assert_eq!("intercomswap".parse::<SwapUri>(), Err(UriError::Scheme));
+    }
+
+    #[test]
+    fn bad_payment_hash_is_rejected() {
+        let encoded = swap(None).to_string();
+        let hash = "ab".repeat(32);
+        for bad in [
+            "AB".repeat(32),
+            "ab".repeat(31),
+            "ab".repeat(33),
+            format!("{}g", &hash[1..]),
+        ] {
+            let uri = encoded.replace(&hash, &bad);
+            assert_eq!(uri.parse::<SwapUri>(), Err(UriError::InvalidPaymentHash), "{bad}");
+        }
+    }
+
+    #[test]
+    fn unknown_and_duplicate_params_are_rejected() {
+        let encoded = swap(None).to_string();
+        assert_eq!(
+            format!("{encoded}&memo=hi").parse::<SwapUri>(),
+            Err(UriError::UnknownParam("memo".into()))
+        );
+        assert_eq!(
+            format!("{encoded}&flag").parse::<SwapUri>(),
+            Err(UriError::UnknownParam("flag".into()))
+        );
+        assert_eq!(
+            format!("{encoded}&Amount=1").parse::<SwapUri>(),
+            Err(UriError::UnknownParam("Amount".into()))
+        );
+        assert_eq!(
+            format!("{encoded}&amount=1").parse::<SwapUri>(),
+            Err(UriError::DuplicateParam("amount".into()))
+        );
+        assert_eq!(
+            format!("{encoded}&bolt11=lnbc1&bolt11=lnbc2").parse::<SwapUri>(),
+            Err(UriError::DuplicateParam("bolt11".into()))
+        );
+    }
+
+    #[test]
+    fn missing_params_are_rejected() {
+        let uri = swap(None);
+        let without_amount = format!(
+            "{SCHEME}:{}?mint={}&recipient={}&refund={}&expiry={}",
+            "ab".repeat(32),
+            uri.mint,
+            uri.recipient,
+            uri.refund,
+            uri.expiry
+        );
+        assert_eq!(without_amount.parse::<SwapUri>(), Err(UriError::MissingParam("amount")));
+        let no_query = format!("{SCHEME}:{}", "ab".repeat(32));
+        assert_eq!(no_query.parse::<SwapUri>(), Err(UriError::MissingParam("mint")));
+    }
+
+    #[test]
+    fn bad_amounts_are_rejected() {
+        let encoded = swap(None).to_string();
+        for bad in ["", "+5", "007", "-1", "1.5", "1e6", "18446744073709551616", "0x10"] {
+            let uri = encoded.replace("amount=64665000", &format!("amount={bad}"));
+            assert_eq!(uri.parse::<SwapUri>(), Err(UriError::InvalidValue("amount")), "{bad:?}");
+        }
+        let zero = encoded.replace("amount=64665000", "amount=0");
+        assert_eq!(zero.parse::<SwapUri>().unwrap().amount, 0);
+    }
+
+    #[test]
+    fn bad_values_are_rejected() {
+        let uri = swap(None);
+        let encoded = uri.to_string();
+        for bad in ["-0", "01", "soon"] {
+            let changed = encoded.replace("expiry=1700000000", &format!("expiry={bad}"));
+            assert_eq!(
+                changed.parse::<SwapUri>(),
+                Err(UriError::InvalidValue("expiry")),
+                "{bad}"
+            );
+        }
+        let changed = encoded.replace(&uri.mint.to_string(), "not-a-key");
+        assert_eq!(changed.parse::<SwapUri>(), Err(UriError::InvalidValue("mint")));
+        for bad in ["", "lnbc%201", "lnbc 1"] {
+            let changed = format!("{encoded}&bolt11={bad}");
+            assert_eq!(
+                changed.parse::<SwapUri>(),
+                Err(UriError::InvalidValue("bolt11")),
+                "{bad:?}"
+            );
+        }
+    }
+}
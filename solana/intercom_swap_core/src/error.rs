@@ -0,0 +1,100 @@
+//! Custom program error codes (`ProgramError::Custom(n)`).
+
+use core::fmt;
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EscrowError {
+    InvalidInstruction = 1,
+    InvalidEscrowPda = 2,
+    InvalidVaultAta = 3,
+    InvalidTokenAccount = 4,
+    InvalidSigner = 5,
+    InvalidPreimage = 6,
+    NotActive = 7,
+    TooEarly = 8,
+    InvalidConfigPda = 9,
+    InvalidConfigState = 10,
+    FeeTooHigh = 11,
+    AlreadyInitialized = 12,
+    InvalidFeeVaultAta = 13,
+    InvalidTradeConfigPda = 14,
+    InvalidTradeConfigState = 15,
+    InvalidTradeFeeVaultAta = 16,
+    FeeMismatch = 17,
+    InvalidIndexPda = 18,
+    InvalidIndexState = 19,
+    InvalidCreator = 20,
+}
+
+impl EscrowError {
+    pub const fn code(self) -> u32 {
+        self as u32
+    }
+
+    /// `None` for codes this version does not know.
+    pub const fn from_code(code: u32) -> Option<Self> {
+        Some(match code {
+            1 => Self::InvalidInstruction,
+            2 => Self::InvalidEscrowPda,
+            3 => Self::InvalidVaultAta,
+            4 => Self::InvalidTokenAccount,
+            5 => Self::InvalidSigner,
+            6 => Self::InvalidPreimage,
+            7 => Self::NotActive,
+            8 => Self::TooEarly,
+            9 => Self::InvalidConfigPda,
+            10 => Self::InvalidConfigState,
+            11 => Self::FeeTooHigh,
+            12 => Self::AlreadyInitialized,
+            13 => Self::InvalidFeeVaultAta,
+            14 => Self::InvalidTradeConfigPda,
+            15 => Self::InvalidTradeConfigState,
+            16 => Self::InvalidTradeFeeVaultAta,
+            17 => Self::FeeMismatch,
+            18 => Self::InvalidIndexPda,
+            19 => Self::InvalidIndexState,
+            20 => Self::InvalidCreator,
+            _ => return None,
+        })
+    }
+
+    pub const fn message(self) -> &'static str {
+        match self {
+            Self::InvalidInstruction => "invalid instruction data or arithmetic overflow",
+            Self::InvalidEscrowPda => "escrow PDA does not match payment hash",
+            Self::InvalidVaultAta => "vault is not the escrow's associated token account",
+            Self::InvalidTokenAccount => "token account has the wrong mint, owner or balance",
+            Self::InvalidSigner => "missing or unexpected signer",
+            Self::InvalidPreimage => "preimage does not hash to payment_hash",
+            Self::NotActive => "escrow not active",
+            Self::TooEarly => "refund_after has not passed yet",
+            Self::InvalidConfigPda => "config PDA mismatch",
+            Self::InvalidConfigState => "config not initialized or has an unexpected layout",
+            Self::FeeTooHigh => "fee bps above the on-chain cap",
+            Self::AlreadyInitialized => "account already initialized",
+            Self::InvalidFeeVaultAta => "platform fee vault ATA mismatch",
+            Self::InvalidTradeConfigPda => "trade config PDA mismatch",
+            Self::InvalidTradeConfigState => "trade config not initialized or has an unexpected layout",
+            Self::InvalidTradeFeeVaultAta => "trade fee vault ATA mismatch",
+            Self::FeeMismatch => "on-chain fee bps differs from the expected value",
+            Self::InvalidIndexPda => "escrow index PDA mismatch",
+            Self::InvalidIndexState => "escrow index has an unexpected layout",
+            Self::InvalidCreator => "creator account does not match the escrow",
+        }
+    }
+}
+
+impl fmt::Display for EscrowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+#[cfg(feature = "program")]
+impl From<EscrowError> for solana_program::program_error::ProgramError {
+    fn from(e: EscrowError) -> Self {
+        solana_program::program_error::ProgramError::Custom(e.code())
+    }
+}
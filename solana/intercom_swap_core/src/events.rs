@@ -0,0 +1,298 @@
+//! Events the program emits with `sol_log_data` (shown as `Program data: <base64>` in transaction logs).
+//!
+//! Each payload is [`EVENT_MAGIC`], a tag byte and little-endian fields, so indexers can tell them apart from
+//! data logged by other programs in the same transaction.
+
+pub const EVENT_MAGIC: [u8; 4] = *b"iswp";
+
+/// Longest encoded event (EscrowInitialized).
+pub const MAX_EVENT_LEN: usize = 4 + 1 + 32 * 5 + 8 * 4;
+
+/// Which fee vault a withdrawal drained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum FeeSource {
+    Platform,
+    Trade,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EscrowEvent {
+    EscrowInitialized {
+        payment_hash: [u8; 32],
+        recipient: [u8; 32],
+        refund: [u8; 32],
+        mint: [u8; 32],
+        trade_fee_collector: [u8; 32],
+        refund_after: i64,
+        net_amount: u64,
+        platform_fee_amount: u64,
+        trade_fee_amount: u64,
+    },
+    Claimed {
+        payment_hash: [u8; 32],
+        preimage: [u8; 32],
+        recipient: [u8; 32],
+        net_amount: u64,
+        platform_fee_amount: u64,
+        trade_fee_amount: u64,
+    },
+    Refunded {
+        payment_hash: [u8; 32],
+        refund: [u8; 32],
+        amount: u64,
+    },
+    FeesWithdrawn {
+        source: FeeSource,
+        fee_collector: [u8; 32],
+        mint: [u8; 32],
+        amount: u64,
+    },
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        if self.data.len() < N {
+            return None;
+        }
+        let (head, tail) = self.data.split_at(N);
+        self.data = tail;
+        let mut out = [0u8; N];
+        out.copy_from_slice(head);
+        Some(out)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn i64(&mut self) -> Option<i64> {
+        Some(i64::from_le_bytes(self.bytes()?))
+    }
+}
+
+struct Writer<'a> {
+    out: &'a mut [u8; MAX_EVENT_LEN],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn put(&mut self, bytes: &[u8]) {
+        self.out[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+}
+
+impl EscrowEvent {
+    /// Parses one `sol_log_data` payload; `None` if it is not an escrow event.
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        let rest = data.strip_prefix(&EVENT_MAGIC)?;
+        let (&tag, rest) = rest.split_first()?;
+        let mut r = Reader { data: rest };
+        Some(match tag {
+            0 => Self::EscrowInitialized {
+                payment_hash: r.bytes()?,
+                recipient: r.bytes()?,
+                refund: r.bytes()?,
+                mint: r.bytes()?,
+                trade_fee_collector: r.bytes()?,
+                refund_after: r.i64()?,
+                net_amount: r.u64()?,
+                platform_fee_amount: r.u64()?,
+                trade_fee_amount: r.u64()?,
+            },
+            1 => Self::Claimed {
+                payment_hash: r.bytes()?,
+                preimage: r.bytes()?,
+                recipient: r.bytes()?,
+                net_amount: r.u64()?,
+                platform_fee_amount: r.u64()?,
+                trade_fee_amount: r.u64()?,
+            },
+            2 => Self::Refunded {
+                payment_hash: r.bytes()?,
+                refund: r.bytes()?,
+                amount: r.u64()?,
+            },
+            3 | 4 => Self::FeesWithdrawn {
+                source: if tag == 3 {
+                    FeeSource::Platform
+                } else {
+                    FeeSource::Trade
+                },
+                fee_collector: r.bytes()?,
+                mint: r.bytes()?,
+                amount: r.u64()?,
+            },
+            _ => return None,
+        })
+    }
+
+    /// Encodes into `out` and returns the encoded length.
+    pub fn pack_into(&self, out: &mut [u8; MAX_EVENT_LEN]) -> usize {
+        let mut w = Writer { out, len: 0 };
+        w.put(&EVENT_MAGIC);
+        match self {
+            Self::EscrowInitialized {
+                payment_hash,
+                recipient,
+                refund,
+                mint,
+                trade_fee_collector,
+                refund_after,
+                net_amount,
+                platform_fee_amount,
+                trade_fee_amount,
+            } => {
+                w.put(&[0]);
+                w.put(payment_hash);
+                w.put(recipient);
+                w.put(refund);
+                w.put(mint);
+                w.put(trade_fee_collector);
+                w.put(&refund_after.to_le_bytes());
+                w.put(&net_amount.to_le_bytes());
+                w.put(&platform_fee_amount.to_le_bytes());
+                w.put(&trade_fee_amount.to_le_bytes());
+            }
+            Self::Claimed {
+                payment_hash,
+                preimage,
+                recipient,
+                net_amount,
+                platform_fee_amount,
+                trade_fee_amount,
+            } => {
+                w.put(&[1]);
+                w.put(payment_hash);
+                w.put(preimage);
+                w.put(recipient);
+                w.put(&net_amount.to_le_bytes());
+                w.put(&platform_fee_amount.to_le_bytes());
+                w.put(&trade_fee_amount.to_le_bytes());
+            }
+            Self::Refunded {
+                payment_hash,
+                refund,
+                amount,
+            } => {
+                w.put(&[2]);
+                w.put(payment_hash);
+                w.put(refund);
+                w.put(&amount.to_le_bytes());
+            }
+            Self::FeesWithdrawn {
+                source,
+                fee_collector,
+                mint,
+                amount,
+            } => {
+                w.put(&[match source {
+                    FeeSource::Platform => 3,
+                    FeeSource::Trade => 4,
+                }]);
+                w.put(fee_collector);
+                w.put(mint);
+                w.put(&amount.to_le_bytes());
+            }
+        }
+        w.len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events() -> [EscrowEvent; 5] {
+        [
+            EscrowEvent::EscrowInitialized {
+                payment_hash: [1; 32],
+                recipient: [2; 32],
+                refund: [3; 32],
+                mint: [4; 32],
+                trade_fee_collector: [5; 32],
+                refund_after: -7,
+                net_amount: 1_000,
+                platform_fee_amount: 5,
+                trade_fee_amount: u64::MAX,
+            },
+            EscrowEvent::Claimed {
+                payment_hash: [1; 32],
+                preimage: [6; 32],
+                recipient: [2; 32],
+                net_amount: 1_000,
+                platform_fee_amount: 5,
+                trade_fee_amount: 3,
+            },
+            EscrowEvent::Refunded {
+                payment_hash: [1; 32],
+                refund: [3; 32],
+                amount: 1_008,
+            },
+            EscrowEvent::FeesWithdrawn {
+                source: FeeSource::Platform,
+                fee_collector: [7; 32],
+                mint: [4; 32],
+                amount: 5,
+            },
+            EscrowEvent::FeesWithdrawn {
+                source: FeeSource::Trade,
+                fee_collector: [8; 32],
+                mint: [4; 32],
+                amount: 3,
+            },
+        ]
+    }
+
+    fn pack(event: &EscrowEvent) -> Vec<u8> {
+        let mut out = [0u8; MAX_EVENT_LEN];
+        let len = event.pack_into(&mut out);
+        out[..len].to_vec()
+    }
+
+    #[test]
+    fn round_trips() {
+        for event in events() {
+            assert_eq!(EscrowEvent::unpack(&pack(&event)), Some(event));
+        }
+    }
+
+    #[test]
+    fn longest_event_fills_the_buffer() {
+        assert_eq!(pack(&events()[0]).len(), MAX_EVENT_LEN);
+    }
+
+    #[test]
+    fn truncated_payloads_are_not_events() {
+        for event in events() {
+            let data = pack(&event);
+            for len in 0..data.len() {
+                assert_eq!(EscrowEvent::unpack(&data[..len]), None, "{event:?} cut at {len}");
+            }
+        }
+    }
+
+    #[test]
+    fn foreign_payloads_are_not_events() {
+        let mut data = pack(&events()[2]);
+        for tag in [5, 0xff] {
+            data[4] = tag;
+            assert_eq!(EscrowEvent::unpack(&data), None, "tag {tag}");
+        }
+        let mut data = pack(&events()[2]);
+        data[0] ^= 0x20;
+        assert_eq!(EscrowEvent::unpack(&data), None);
+        assert_eq!(EscrowEvent::unpack(&data[4..]), None);
+        assert_eq!(EscrowEvent::unpack(b""), None);
+        assert_eq!(EscrowEvent::unpack(&[0u8; MAX_EVENT_LEN]), None);
+    }
+}
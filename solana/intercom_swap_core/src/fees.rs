@@ -0,0 +1,129 @@
+//! Fee arithmetic exactly as the program performs it during Init.
+
+use crate::BPS_DENOMINATOR;
+
+/// `amount * bps / 10_000`, rounded down. `None` only if the result does not fit in a u64.
+pub fn compute_fee(amount: u64, bps: u16) -> Option<u64> {
+    let fee = (amount as u128).checked_mul(bps as u128)? / BPS_DENOMINATOR as u128;
+    u64::try_from(fee).ok()
+}
+
+/// Amounts recorded in an escrow for a given net amount and fee rates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EscrowAmounts {
+    pub net_amount: u64,
+    pub platform_fee_amount: u64,
+    pub trade_fee_amount: u64,
+    /// What the payer transfers into the vault: net plus both fees.
+    pub total_amount: u64,
+}
+
+impl EscrowAmounts {
+    /// Each fee is computed on `net_amount` independently and rounded down; `None` on overflow.
+    pub fn compute(net_amount: u64, platform_fee_bps: u16, trade_fee_bps: u16) -> Option<Self> {
+        let platform_fee_amount = compute_fee(net_amount, platform_fee_bps)?;
+        let trade_fee_amount = compute_fee(net_amount, trade_fee_bps)?;
+        let total_amount = net_amount
+            .checked_add(platform_fee_amount)?
+            .checked_add(trade_fee_amount)?;
+        Some(Self {
+            net_amount,
+            platform_fee_amount,
+            trade_fee_amount,
+            total_amount,
+        })
+    }
+}
+
+/// What the payer is debited for an escrow of `net_amount`: `net + fee(platform) + fee(trade)`, as
+/// [`EscrowAmounts::total_amount`]. `None` where Init would fail with an overflow.
+pub fn total_with_fee(net_amount: u64, platform_fee_bps: u16, trade_fee_bps: u16) -> Option<u64> {
+    EscrowAmounts::compute(net_amount, platform_fee_bps, trade_fee_bps).map(|a| a.total_amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{MAX_PLATFORM_FEE_BPS, MAX_TRADE_FEE_BPS};
+
+    /// Init's fee math as the program wrote it before it moved here: `(platform fee, trade fee, total)`, or `None`
+    /// where Init fails with `InvalidInstruction`.
+    fn program_init(amount: u64, platform_fee_bps: u16, trade_fee_bps: u16) -> Option<(u64, u64, u64)> {
+        let platform_fee_amount: u64 = ((amount as u128).checked_mul(platform_fee_bps as u128)? / 10_000u128)
+            .try_into()
+            .ok()?;
+        let trade_fee_amount: u64 = ((amount as u128).checked_mul(trade_fee_bps as u128)? / 10_000u128)
+            .try_into()
+            .ok()?;
+        let total_amount = amount.checked_add(platform_fee_amount)?.checked_add(trade_fee_amount)?;
+        Some((platform_fee_amount, trade_fee_amount, total_amount))
+    }
+
+    /// What Claim pays out of a vault holding `total_amount`: the fees to their vaults, the rest to the recipient.
+    fn program_claim_net(total_amount: u64, platform_fee_amount: u64, trade_fee_amount: u64) -> u64 {
+        total_amount - platform_fee_amount - trade_fee_amount
+    }
+
+    fn check(amount: u64, platform_fee_bps: u16, trade_fee_bps: u16) {
+        let expected = program_init(amount, platform_fee_bps, trade_fee_bps);
+        let computed = EscrowAmounts::compute(amount, platform_fee_bps, trade_fee_bps);
+        assert_eq!(
+            computed.map(|a| (a.platform_fee_amount, a.trade_fee_amount, a.total_amount)),
+            expected,
+            "amount {amount}, bps {platform_fee_bps}/{trade_fee_bps}"
+        );
+        assert_eq!(
+            total_with_fee(amount, platform_fee_bps, trade_fee_bps),
+            expected.map(|e| e.2)
+        );
+        assert_eq!(
+            compute_fee(amount, platform_fee_bps),
+            Some(amount as u128 * platform_fee_bps as u128 / 10_000).and_then(|f| u64::try_from(f).ok())
+        );
+    }
+
+    #[test]
+    fn bps_sweep_matches_program() {
+        let amounts = [
+            0,
+            1,
+            9_999,
+            10_000,
+            10_001,
+            1_000_000,
+            u64::MAX / 10_000,
+            u64::MAX / 2,
+            u64::MAX - 1,
+            u64::MAX,
+        ];
+        for bps in 0..=u16::MAX {
+            for amount in amounts {
+                check(amount, bps, 0);
+                check(amount, 0, bps);
+                check(amount, bps, bps);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn matches_program(amount in any::<u64>(), platform_fee_bps in any::<u16>(), trade_fee_bps in any::<u16>()) {
+            check(amount, platform_fee_bps, trade_fee_bps);
+        }
+
+        #[test]
+        fn total_round_trips_to_net(
+            net_amount in 0..=u64::MAX / 2,
+            platform_fee_bps in 0..=MAX_PLATFORM_FEE_BPS,
+            trade_fee_bps in 0..=MAX_TRADE_FEE_BPS,
+        ) {
+            let total = total_with_fee(net_amount, platform_fee_bps, trade_fee_bps).unwrap();
+            let (platform_fee_amount, trade_fee_amount, debited) =
+                program_init(net_amount, platform_fee_bps, trade_fee_bps).unwrap();
+            prop_assert_eq!(total, debited);
+            prop_assert_eq!(program_claim_net(total, platform_fee_amount, trade_fee_amount), net_amount);
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Instruction data: a tag byte followed by little-endian fields.
+
+use crate::error::EscrowError;
+
+/// Longest encoded instruction (Init).
+pub const MAX_INSTRUCTION_LEN: usize = 1 + 32 + 32 + 32 + 8 + 8 + 2 + 2 + 32;
+
+/// How an Init expresses the refund timelock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefundAfter {
+    /// Absolute unix timestamp (tag 0).
+    At(i64),
+    /// Seconds after the on-chain clock at init time (tag 9); the program stores the absolute deadline.
+    Delay(i64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EscrowInstruction {
+    Init {
+        payment_hash: [u8; 32],
+        recipient: [u8; 32],
+        refund: [u8; 32],
+        refund_after: RefundAfter,
+        amount: u64,
+        expected_platform_fee_bps: u16,
+        expected_trade_fee_bps: u16,
+        trade_fee_collector: [u8; 32],
+    },
+    Claim {
+        preimage: [u8; 32],
+    },
+    Refund,
+    InitConfig {
+        fee_collector: [u8; 32],
+        fee_bps: u16,
+    },
+    SetConfig {
+        fee_collector: [u8; 32],
+        fee_bps: u16,
+    },
+    WithdrawFees {
+        amount: u64,
+    },
+    InitTradeConfig {
+        fee_collector: [u8; 32],
+        fee_bps: u16,
+    },
+    SetTradeConfig {
+        fee_collector: [u8; 32],
+        fee_bps: u16,
+    },
+    WithdrawTradeFees {
+        amount: u64,
+    },
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], EscrowError> {
+        if self.data.len() < N {
+            return Err(EscrowError::InvalidInstruction);
+        }
+        let (head, tail) = self.data.split_at(N);
+        self.data = tail;
+        let mut out = [0u8; N];
+        out.copy_from_slice(head);
+        Ok(out)
+    }
+
+    fn u16(&mut self) -> Result<u16, EscrowError> {
+        Ok(u16::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, EscrowError> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn i64(&mut self) -> Result<i64, EscrowError> {
+        Ok(i64::from_le_bytes(self.bytes()?))
+    }
+}
+
+struct Writer<'a> {
+    out: &'a mut [u8; MAX_INSTRUCTION_LEN],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn put(&mut self, bytes: &[u8]) {
+        self.out[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+}
+
+impl EscrowInstruction {
+    /// Parses instruction data; trailing bytes are ignored.
+    pub fn unpack(input: &[u8]) -> Result<Self, EscrowError> {
+        let (&tag, rest) = input.split_first().ok_or(EscrowError::InvalidInstruction)?;
+        let mut r = Reader { data: rest };
+        Ok(match tag {
+            0 | 9 => {
+                let payment_hash = r.bytes()?;
+                let recipient = r.bytes()?;
+                let refund = r.bytes()?;
+                let refund_after = match (tag, r.i64()?) {
+                    (0, ts) => RefundAfter::At(ts),
+                    (_, secs) => RefundAfter::Delay(secs),
+                };
+                Self::Init {
+                    payment_hash,
+                    recipient,
+                    refund,
+                    refund_after,
+                    amount: r.u64()?,
+                    expected_platform_fee_bps: r.u16()?,
+                    expected_trade_fee_bps: r.u16()?,
+                    trade_fee_collector: r.bytes()?,
+                }
+            }
+            1 => Self::Claim { preimage: r.bytes()? },
+            2 => Self::Refund,
+            3 => Self::InitConfig {
+                fee_collector: r.bytes()?,
+                fee_bps: r.u16()?,
+            },
+            4 => Self::SetConfig {
+                fee_collector: r.bytes()?,
+                fee_bps: r.u16()?,
+            },
+            5 => Self::WithdrawFees { amount: r.u64()? },
+            6 => Self::InitTradeConfig {
+                fee_collector: r.bytes()?,
+                fee_bps: r.u16()?,
+            },
+            7 => Self::SetTradeConfig {
+                fee_collector: r.bytes()?,
+                fee_bps: r.u16()?,
+            },
+            8 => Self::WithdrawTradeFees { amount: r.u64()? },
+            _ => return Err(EscrowError::InvalidInstruction),
+        })
+    }
+
+    /// Encodes into `out` and returns the encoded length.
+    pub fn pack_into(&self, out: &mut [u8; MAX_INSTRUCTION_LEN]) -> usize {
+        let mut w = Writer { out, len: 0 };
+        match self {
+            Self::Init {
+                payment_hash,
+                recipient,
+                refund,
+                refund_after,
+                amount,
+                expected_platform_fee_bps,
+                expected_trade_fee_bps,
+                trade_fee_collector,
+            } => {
+                let (tag, refund_after) = match refund_after {
+                    RefundAfter::At(ts) => (0u8, *ts),
+                    RefundAfter::Delay(secs) => (9u8, *secs),
+                };
+                w.put(&[tag]);
+                w.put(payment_hash);
+                w.put(recipient);
+                w.put(refund);
+                w.put(&refund_after.to_le_bytes());
+                w.put(&amount.to_le_bytes());
+                w.put(&expected_platform_fee_bps.to_le_bytes());
+                w.put(&expected_trade_fee_bps.to_le_bytes());
+                w.put(trade_fee_collector);
+            }
+            Self::Claim { preimage } => {
+                w.put(&[1]);
+                w.put(preimage);
+            }
+            Self::Refund => w.put(&[2]),
+            Self::InitConfig { fee_collector, fee_bps } => put_fee_config(&mut w, 3, fee_collector, *fee_bps),
+            Self::SetConfig { fee_collector, fee_bps } => put_fee_config(&mut w, 4, fee_collector, *fee_bps),
+            Self::WithdrawFees { amount } => {
+                w.put(&[5]);
+                w.put(&amount.to_le_bytes());
+            }
+            Self::InitTradeConfig { fee_collector, fee_bps } => put_fee_config(&mut w, 6, fee_collector, *fee_bps),
+            Self::SetTradeConfig { fee_collector, fee_bps } => put_fee_config(&mut w, 7, fee_collector, *fee_bps),
+            Self::WithdrawTradeFees { amount } => {
+                w.put(&[8]);
+                w.put(&amount.to_le_bytes());
+            }
+        }
+        w.len
+    }
+}
+
+fn put_fee_config(w: &mut Writer, tag: u8, fee_collector: &[u8; 32], fee_bps: u16) {
+    w.put(&[tag]);
+    w.put(fee_collector);
+    w.put(&fee_bps.to_le_bytes());
+}
@@ -0,0 +1,26 @@
+//! Wire format of the `ln_usdt_escrow` program: account layouts, instruction encoding, events and error
+//! codes.
+//!
+//! This is the single definition shared by the program, the client SDK, its bindings and embedded signers, so
+//! it is `no_std`, allocation-free and works on raw 32-byte keys rather than `Pubkey`.
+
+#![cfg_attr(not(test), no_std)]
+
+pub mod error;
+pub mod events;
+pub mod fees;
+pub mod instruction;
+pub mod state;
+
+pub use error::EscrowError;
+pub use events::{EscrowEvent, FeeSource};
+pub use fees::{compute_fee, total_with_fee, EscrowAmounts};
+pub use instruction::{EscrowInstruction, RefundAfter};
+pub use state::{ConfigState, EscrowIndexState, EscrowState, EscrowStatus, StateError, TradeConfigState};
+
+/// Basis points: 10_000 = 100%.
+pub const BPS_DENOMINATOR: u64 = 10_000;
+// Fee caps are enforced on-chain (and re-validated during escrow init).
+pub const MAX_PLATFORM_FEE_BPS: u16 = 500; // 5%
+pub const MAX_TRADE_FEE_BPS: u16 = 1000; // 10%
+pub const MAX_TOTAL_FEE_BPS: u16 = 1500; // 15% (platform + trade)
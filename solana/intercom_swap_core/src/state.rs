@@ -0,0 +1,330 @@
+//! Account layouts. All integers are little-endian; keys are raw 32-byte arrays.
+
+use core::fmt;
+
+pub const ESCROW_V3: u8 = 3;
+/// v3 + trailing `creator`.
+pub const ESCROW_V4: u8 = 4;
+pub const ESCROW_V3_LEN: usize = 1 + 1 + 32 + 32 + 32 + 8 + 32 + 8 + 8 + 2 + 32 + 8 + 2 + 32 + 32 + 1;
+pub const ESCROW_V4_LEN: usize = ESCROW_V3_LEN + 32;
+
+pub const CONFIG_V1: u8 = 1;
+/// Config and trade config accounts share this layout.
+pub const CONFIG_LEN: usize = 1 + 32 + 32 + 2 + 1;
+
+pub const ESCROW_INDEX_V1: u8 = 1;
+/// Number of most recent escrow addresses kept per index PDA (ring buffer).
+pub const ESCROW_INDEX_CAPACITY: usize = 16;
+pub const ESCROW_INDEX_LEN: usize = 1 + 32 + 8 + 32 * ESCROW_INDEX_CAPACITY + 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    TooSmall { expected: usize, actual: usize },
+    UnsupportedVersion(u8),
+    InvalidStatus(u8),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooSmall { expected, actual } => {
+                write!(f, "account too small: expected {expected} bytes, got {actual}")
+            }
+            Self::UnsupportedVersion(v) => write!(f, "unsupported account version v={v}"),
+            Self::InvalidStatus(s) => write!(f, "invalid escrow status {s}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum EscrowStatus {
+    Active,
+    Claimed,
+    Refunded,
+}
+
+impl EscrowStatus {
+    pub const fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(Self::Active),
+            1 => Some(Self::Claimed),
+            2 => Some(Self::Refunded),
+            _ => None,
+        }
+    }
+
+    pub const fn as_u8(self) -> u8 {
+        match self {
+            Self::Active => 0,
+            Self::Claimed => 1,
+            Self::Refunded => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EscrowState {
+    pub v: u8,
+    pub status: EscrowStatus,
+    pub payment_hash: [u8; 32],
+    pub recipient: [u8; 32],
+    pub refund: [u8; 32],
+    pub refund_after: i64,
+    pub mint: [u8; 32],
+    pub net_amount: u64,
+    pub platform_fee_amount: u64,
+    pub platform_fee_bps: u16,
+    pub platform_fee_collector: [u8; 32],
+    pub trade_fee_amount: u64,
+    pub trade_fee_bps: u16,
+    pub trade_fee_collector: [u8; 32],
+    pub vault: [u8; 32],
+    pub bump: u8,
+    /// v4+: payer that funded the escrow (vault rent destination); `None` for v3.
+    pub creator: Option<[u8; 32]>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigState {
+    pub v: u8,
+    pub authority: [u8; 32],
+    pub fee_collector: [u8; 32],
+    pub fee_bps: u16,
+    pub bump: u8,
+}
+
+/// Trade config accounts share the platform config layout.
+pub type TradeConfigState = ConfigState;
+
+/// The most recent escrows naming a key as recipient or refund. Best-effort, not authoritative: anyone can create
+/// escrows naming any key and so push a wallet's real entries out of the ring buffer. Treat entries as hints and
+/// read each escrow account before relying on it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EscrowIndexState {
+    pub v: u8,
+    pub owner: [u8; 32],
+    /// Total escrows ever recorded; the next write goes to `total % ESCROW_INDEX_CAPACITY`.
+    pub total: u64,
+    pub escrows: [[u8; 32]; ESCROW_INDEX_CAPACITY],
+    pub bump: u8,
+}
+
+fn ensure_len(data: &[u8], expected: usize) -> Result<(), StateError> {
+    if data.len() < expected {
+        return Err(StateError::TooSmall {
+            expected,
+            actual: data.len(),
+        });
+    }
+    Ok(())
+}
+
+// Callers check the total length up front, so reads and writes cannot run past the end.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        let (head, tail) = self.data.split_at(N);
+        self.data = tail;
+        let mut out = [0u8; N];
+        out.copy_from_slice(head);
+        out
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.bytes::<1>()[0]
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.bytes())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.bytes())
+    }
+
+    fn i64(&mut self) -> i64 {
+        i64::from_le_bytes(self.bytes())
+    }
+}
+
+struct Writer<'a> {
+    data: &'a mut [u8],
+}
+
+impl<'a> Writer<'a> {
+    fn put(&mut self, bytes: &[u8]) {
+        let data = core::mem::take(&mut self.data);
+        let (head, tail) = data.split_at_mut(bytes.len());
+        head.copy_from_slice(bytes);
+        self.data = tail;
+    }
+}
+
+impl EscrowState {
+    /// Serialized length in this state's own version.
+    pub fn packed_len(&self) -> usize {
+        if self.v >= ESCROW_V4 {
+            ESCROW_V4_LEN
+        } else {
+            ESCROW_V3_LEN
+        }
+    }
+
+    /// Amount locked in the vault while active.
+    pub fn total_amount(&self) -> u64 {
+        self.net_amount
+            .saturating_add(self.platform_fee_amount)
+            .saturating_add(self.trade_fee_amount)
+    }
+
+    /// Decodes a v3 or v4 escrow account.
+    pub fn unpack(data: &[u8]) -> Result<Self, StateError> {
+        ensure_len(data, 1)?;
+        let v = data[0];
+        match v {
+            ESCROW_V3 => ensure_len(data, ESCROW_V3_LEN)?,
+            ESCROW_V4 => ensure_len(data, ESCROW_V4_LEN)?,
+            _ => return Err(StateError::UnsupportedVersion(v)),
+        }
+        let mut r = Reader { data: &data[1..] };
+        let status_raw = r.u8();
+        let status = EscrowStatus::from_u8(status_raw).ok_or(StateError::InvalidStatus(status_raw))?;
+        Ok(Self {
+            v,
+            status,
+            payment_hash: r.bytes(),
+            recipient: r.bytes(),
+            refund: r.bytes(),
+            refund_after: r.i64(),
+            mint: r.bytes(),
+            net_amount: r.u64(),
+            platform_fee_amount: r.u64(),
+            platform_fee_bps: r.u16(),
+            platform_fee_collector: r.bytes(),
+            trade_fee_amount: r.u64(),
+            trade_fee_bps: r.u16(),
+            trade_fee_collector: r.bytes(),
+            vault: r.bytes(),
+            bump: r.u8(),
+            creator: if v >= ESCROW_V4 { Some(r.bytes()) } else { None },
+        })
+    }
+
+    /// Writes the state in the layout of its own version (v3 accounts are never resized).
+    pub fn pack_into(&self, data: &mut [u8]) -> Result<(), StateError> {
+        ensure_len(data, self.packed_len())?;
+        let mut w = Writer { data };
+        w.put(&[self.v, self.status.as_u8()]);
+        w.put(&self.payment_hash);
+        w.put(&self.recipient);
+        w.put(&self.refund);
+        w.put(&self.refund_after.to_le_bytes());
+        w.put(&self.mint);
+        w.put(&self.net_amount.to_le_bytes());
+        w.put(&self.platform_fee_amount.to_le_bytes());
+        w.put(&self.platform_fee_bps.to_le_bytes());
+        w.put(&self.platform_fee_collector);
+        w.put(&self.trade_fee_amount.to_le_bytes());
+        w.put(&self.trade_fee_bps.to_le_bytes());
+        w.put(&self.trade_fee_collector);
+        w.put(&self.vault);
+        w.put(&[self.bump]);
+        if self.v >= ESCROW_V4 {
+            w.put(&self.creator.unwrap_or_default());
+        }
+        Ok(())
+    }
+}
+
+impl ConfigState {
+    /// Decodes a config or trade config account. The version is returned as-is for the caller to check.
+    pub fn unpack(data: &[u8]) -> Result<Self, StateError> {
+        ensure_len(data, CONFIG_LEN)?;
+        let mut r = Reader { data };
+        Ok(Self {
+            v: r.u8(),
+            authority: r.bytes(),
+            fee_collector: r.bytes(),
+            fee_bps: r.u16(),
+            bump: r.u8(),
+        })
+    }
+
+    pub fn pack_into(&self, data: &mut [u8]) -> Result<(), StateError> {
+        ensure_len(data, CONFIG_LEN)?;
+        let mut w = Writer { data };
+        w.put(&[self.v]);
+        w.put(&self.authority);
+        w.put(&self.fee_collector);
+        w.put(&self.fee_bps.to_le_bytes());
+        w.put(&[self.bump]);
+        Ok(())
+    }
+}
+
+impl EscrowIndexState {
+    pub fn new(owner: [u8; 32], bump: u8) -> Self {
+        Self {
+            v: ESCROW_INDEX_V1,
+            owner,
+            total: 0,
+            escrows: [[0u8; 32]; ESCROW_INDEX_CAPACITY],
+            bump,
+        }
+    }
+
+    /// Decodes a recipient or refund index account. The version is returned as-is for the caller to check.
+    pub fn unpack(data: &[u8]) -> Result<Self, StateError> {
+        ensure_len(data, ESCROW_INDEX_LEN)?;
+        let mut r = Reader { data };
+        let v = r.u8();
+        let owner = r.bytes();
+        let total = r.u64();
+        let mut escrows = [[0u8; 32]; ESCROW_INDEX_CAPACITY];
+        for slot in escrows.iter_mut() {
+            *slot = r.bytes();
+        }
+        Ok(Self {
+            v,
+            owner,
+            total,
+            escrows,
+            bump: r.u8(),
+        })
+    }
+
+    pub fn pack_into(&self, data: &mut [u8]) -> Result<(), StateError> {
+        ensure_len(data, ESCROW_INDEX_LEN)?;
+        let mut w = Writer { data };
+        w.put(&[self.v]);
+        w.put(&self.owner);
+        w.put(&self.total.to_le_bytes());
+        for escrow in &self.escrows {
+            w.put(escrow);
+        }
+        w.put(&[self.bump]);
+        Ok(())
+    }
+
+    /// Records `escrow` in the next ring slot; `None` if `total` would overflow.
+    pub fn push(&mut self, escrow: [u8; 32]) -> Option<()> {
+        let slot = (self.total % ESCROW_INDEX_CAPACITY as u64) as usize;
+        self.escrows[slot] = escrow;
+        self.total = self.total.checked_add(1)?;
+        Some(())
+    }
+
+    /// Recorded escrows, newest first.
+    pub fn newest_first(&self) -> impl Iterator<Item = &[u8; 32]> + '_ {
+        let cap = ESCROW_INDEX_CAPACITY as u64;
+        (0..self.total.min(cap)).map(move |i| &self.escrows[((self.total - 1 - i) % cap) as usize])
+    }
+}
@@ -0,0 +1,15 @@
+use std::{env, path::PathBuf};
+
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).expect("cbindgen.toml");
+    // Header generation is best-effort so a cbindgen parse hiccup never breaks the library build itself.
+    match cbindgen::generate_with_config(&crate_dir, config) {
+        Ok(bindings) => {
+            bindings.write_to_file(crate_dir.join("include/intercom_swap.h"));
+        }
+        Err(e) => println!("cargo:warning=cbindgen failed: {e}"),
+    }
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
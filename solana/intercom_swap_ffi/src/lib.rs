@@ -0,0 +1,430 @@
+//! Stable C ABI over instruction encoding, account decoding and PDA derivation.
+//!
+//! Conventions (the generated `include/intercom_swap.h` documents the same):
+//! - Every function returns an [`IsStatus`]; `IS_STATUS_OK` (0) is success and errors are negative. The message
+//!   for the last error on the calling thread is available via [`is_last_error`].
+//! - Pubkeys, hashes and preimages are fixed 32-byte values passed by pointer; a null `program_id` selects the
+//!   production deployment.
+//! - Output structs are caller-owned and written only on success. The one exception is [`IsInstruction`], whose
+//!   account and data buffers are allocated by this library and must be released with [`is_instruction_free`].
+//! - No function panics across the boundary; null pointers yield `IS_STATUS_NULL_POINTER`.
+
+use std::{cell::RefCell, ffi::c_char, ptr, slice};
+
+use intercom_swap_client::{
+    instruction::{self, InitArgs, RefundAfter},
+    pda,
+    state::{self, EscrowState},
+};
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsStatus {
+    Ok = 0,
+    NullPointer = -1,
+    InvalidArgument = -2,
+    Decode = -3,
+    BufferTooSmall = -4,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IsPubkey {
+    pub bytes: [u8; 32],
+}
+
+impl From<Pubkey> for IsPubkey {
+    fn from(p: Pubkey) -> Self {
+        Self { bytes: p.to_bytes() }
+    }
+}
+
+impl From<IsPubkey> for Pubkey {
+    fn from(p: IsPubkey) -> Self {
+        Pubkey::new_from_array(p.bytes)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct IsAccountMeta {
+    pub pubkey: IsPubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+/// Library-owned instruction; release with [`is_instruction_free`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct IsInstruction {
+    pub program_id: IsPubkey,
+    pub accounts: *mut IsAccountMeta,
+    pub accounts_len: usize,
+    pub data: *mut u8,
+    pub data_len: usize,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct IsInitArgs {
+    pub payment_hash: [u8; 32],
+    pub recipient: IsPubkey,
+    pub refund: IsPubkey,
+    /// 0 = `refund_after` is an absolute unix timestamp, 1 = seconds after init.
+    pub refund_after_is_delay: bool,
+    pub refund_after: i64,
+    pub amount: u64,
+    pub expected_platform_fee_bps: u16,
+    pub expected_trade_fee_bps: u16,
+    pub trade_fee_collector: IsPubkey,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IsEscrowState {
+    pub v: u8,
+    /// 0 = active, 1 = claimed, 2 = refunded.
+    pub status: u8,
+    pub payment_hash: [u8; 32],
+    pub recipient: IsPubkey,
+    pub refund: IsPubkey,
+    pub refund_after: i64,
+    pub mint: IsPubkey,
+    pub net_amount: u64,
+    pub platform_fee_amount: u64,
+    pub platform_fee_bps: u16,
+    pub platform_fee_collector: IsPubkey,
+    pub trade_fee_amount: u64,
+    pub trade_fee_bps: u16,
+    pub trade_fee_collector: IsPubkey,
+    pub vault: IsPubkey,
+    pub bump: u8,
+    /// False for v3 escrows, in which case `creator` is all zeroes.
+    pub has_creator: bool,
+    pub creator: IsPubkey,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IsConfigState {
+    pub v: u8,
+    pub authority: IsPubkey,
+    pub fee_collector: IsPubkey,
+    pub fee_bps: u16,
+    pub bump: u8,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+fn fail(status: IsStatus, msg: impl Into<String>) -> IsStatus {
+    LAST_ERROR.with(|e| *e.borrow_mut() = msg.into());
+    status
+}
+
+unsafe fn read<T: Copy>(p: *const T, label: &str) -> Result<T, IsStatus> {
+    if p.is_null() {
+        return Err(fail(IsStatus::NullPointer, format!("{label} is null")));
+    }
+    Ok(*p)
+}
+
+unsafe fn program_id(p: *const IsPubkey) -> Pubkey {
+    if p.is_null() {
+        intercom_swap_client::PROGRAM_ID
+    } else {
+        (*p).into()
+    }
+}
+
+unsafe fn write<T>(out: *mut T, value: T, label: &str) -> IsStatus {
+    if out.is_null() {
+        return fail(IsStatus::NullPointer, format!("{label} is null"));
+    }
+    out.write(value);
+    IsStatus::Ok
+}
+
+unsafe fn write_instruction(out: *mut IsInstruction, ix: Instruction) -> IsStatus {
+    if out.is_null() {
+        return fail(IsStatus::NullPointer, "out is null");
+    }
+    let accounts: Box<[IsAccountMeta]> = ix
+        .accounts
+        .iter()
+        .map(|m| IsAccountMeta {
+            pubkey: m.pubkey.into(),
+            is_signer: m.is_signer,
+            is_writable: m.is_writable,
+        })
+        .collect();
+    let data: Box<[u8]> = ix.data.into_boxed_slice();
+    let (accounts_len, data_len) = (accounts.len(), data.len());
+    out.write(IsInstruction {
+        program_id: ix.program_id.into(),
+        accounts: Box::into_raw(accounts) as *mut IsAccountMeta,
+        accounts_len,
+        data: Box::into_raw(data) as *mut u8,
+        data_len,
+    });
+    IsStatus::Ok
+}
+
+macro_rules! tri {
+    ($e:expr) => {
+        match $e {
+            Ok(v) => v,
+            Err(status) => return status,
+        }
+    };
+}
+
+/// Copies the last error message of the calling thread into `buf` (NUL-terminated).
+/// Returns `IS_STATUS_BUFFER_TOO_SMALL` if `buf_len` cannot hold the message and terminator.
+///
+/// # Safety
+/// `buf` must be valid for `buf_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn is_last_error(buf: *mut c_char, buf_len: usize) -> IsStatus {
+    if buf.is_null() {
+        return IsStatus::NullPointer;
+    }
+    LAST_ERROR.with(|e| {
+        let msg = e.borrow();
+        if msg.len() + 1 > buf_len {
+            return IsStatus::BufferTooSmall;
+        }
+        ptr::copy_nonoverlapping(msg.as_ptr(), buf as *mut u8, msg.len());
+        *buf.add(msg.len()) = 0;
+        IsStatus::Ok
+    })
+}
+
+/// # Safety
+/// `payment_hash` must point to 32 bytes; `out` and `out_bump` must be valid (`out_bump` may be null).
+#[no_mangle]
+pub unsafe extern "C" fn is_derive_escrow_pda(
+    program_id_ptr: *const IsPubkey,
+    payment_hash: *const [u8; 32],
+    out: *mut IsPubkey,
+    out_bump: *mut u8,
+) -> IsStatus {
+    let hash = tri!(read(payment_hash, "payment_hash"));
+    let (escrow, bump) = pda::find_escrow_pda(&program_id(program_id_ptr), &hash);
+    if !out_bump.is_null() {
+        *out_bump = bump;
+    }
+    write(out, escrow.into(), "out")
+}
+
+/// # Safety
+/// `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn is_derive_config_pda(program_id_ptr: *const IsPubkey, out: *mut IsPubkey) -> IsStatus {
+    write(out, pda::find_config_pda(&program_id(program_id_ptr)).0.into(), "out")
+}
+
+/// # Safety
+/// `fee_collector` must be valid for reads and `out` for writes.
+#[no_mangle]
+pub unsafe extern "C" fn is_derive_trade_config_pda(
+    program_id_ptr: *const IsPubkey,
+    fee_collector: *const IsPubkey,
+    out: *mut IsPubkey,
+) -> IsStatus {
+    let fee_collector: Pubkey = tri!(read(fee_collector, "fee_collector")).into();
+    write(out, pda::find_trade_config_pda(&program_id(program_id_ptr), &fee_collector).0.into(), "out")
+}
+
+/// Vault ATA of an escrow, or the fee vault ATA of a (trade) config PDA.
+///
+/// # Safety
+/// `owner_pda` and `mint` must be valid for reads and `out` for writes.
+#[no_mangle]
+pub unsafe extern "C" fn is_derive_vault_ata(
+    owner_pda: *const IsPubkey,
+    mint: *const IsPubkey,
+    out: *mut IsPubkey,
+) -> IsStatus {
+    let owner: Pubkey = tri!(read(owner_pda, "owner_pda")).into();
+    let mint: Pubkey = tri!(read(mint, "mint")).into();
+    write(out, pda::vault_ata(&owner, &mint).into(), "out")
+}
+
+/// # Safety
+/// All pointers except `program_id_ptr` must be non-null and valid; `out` receives a library-owned instruction.
+#[no_mangle]
+pub unsafe extern "C" fn is_build_init(
+    program_id_ptr: *const IsPubkey,
+    payer: *const IsPubkey,
+    payer_token: *const IsPubkey,
+    mint: *const IsPubkey,
+    args: *const IsInitArgs,
+    out: *mut IsInstruction,
+) -> IsStatus {
+    let a = tri!(read(args, "args"));
+    let refund_after = match (a.refund_after_is_delay, a.refund_after) {
+        (true, secs) if secs <= 0 => return fail(IsStatus::InvalidArgument, "refund delay must be positive"),
+        (true, secs) => RefundAfter::Delay(secs),
+        (false, ts) => RefundAfter::At(ts),
+    };
+    let init = InitArgs {
+        payment_hash: a.payment_hash,
+        recipient: a.recipient.into(),
+        refund: a.refund.into(),
+        refund_after,
+        amount: a.amount,
+        expected_platform_fee_bps: a.expected_platform_fee_bps,
+        expected_trade_fee_bps: a.expected_trade_fee_bps,
+        trade_fee_collector: a.trade_fee_collector.into(),
+    };
+    let ix = instruction::init(
+        &program_id(program_id_ptr),
+        &tri!(read(payer, "payer")).into(),
+        &tri!(read(payer_token, "payer_token")).into(),
+        &tri!(read(mint, "mint")).into(),
+        &init,
+    );
+    write_instruction(out, ix)
+}
+
+/// `creator` must be null for v3 escrows and the escrow's creator for v4+.
+///
+/// # Safety
+/// All pointers except `program_id_ptr` and `creator` must be non-null and valid.
+#[no_mangle]
+pub unsafe extern "C" fn is_build_claim(
+    program_id_ptr: *const IsPubkey,
+    recipient: *const IsPubkey,
+    recipient_token: *const IsPubkey,
+    mint: *const IsPubkey,
+    payment_hash: *const [u8; 32],
+    preimage: *const [u8; 32],
+    trade_fee_collector: *const IsPubkey,
+    creator: *const IsPubkey,
+    out: *mut IsInstruction,
+) -> IsStatus {
+    let creator: Option<Pubkey> = (!creator.is_null()).then(|| (*creator).into());
+    let ix = instruction::claim(
+        &program_id(program_id_ptr),
+        &tri!(read(recipient, "recipient")).into(),
+        &tri!(read(recipient_token, "recipient_token")).into(),
+        &tri!(read(mint, "mint")).into(),
+        &tri!(read(payment_hash, "payment_hash")),
+        &tri!(read(preimage, "preimage")),
+        &tri!(read(trade_fee_collector, "trade_fee_collector")).into(),
+        creator.as_ref(),
+    );
+    write_instruction(out, ix)
+}
+
+/// `creator` must be null for v3 escrows and the escrow's creator for v4+.
+///
+/// # Safety
+/// All pointers except `program_id_ptr` and `creator` must be non-null and valid.
+#[no_mangle]
+pub unsafe extern "C" fn is_build_refund(
+    program_id_ptr: *const IsPubkey,
+    refund: *const IsPubkey,
+    refund_token: *const IsPubkey,
+    mint: *const IsPubkey,
+    payment_hash: *const [u8; 32],
+    creator: *const IsPubkey,
+    out: *mut IsInstruction,
+) -> IsStatus {
+    let creator: Option<Pubkey> = (!creator.is_null()).then(|| (*creator).into());
+    let ix = instruction::refund(
+        &program_id(program_id_ptr),
+        &tri!(read(refund, "refund")).into(),
+        &tri!(read(refund_token, "refund_token")).into(),
+        &tri!(read(mint, "mint")).into(),
+        &tri!(read(payment_hash, "payment_hash")),
+        creator.as_ref(),
+    );
+    write_instruction(out, ix)
+}
+
+/// Releases the buffers of an instruction produced by an `is_build_*` call and zeroes it. Null is a no-op.
+///
+/// # Safety
+/// `ix` must have been filled by this library and not freed already.
+#[no_mangle]
+pub unsafe extern "C" fn is_instruction_free(ix: *mut IsInstruction) {
+    if ix.is_null() {
+        return;
+    }
+    let ix = &mut *ix;
+    if !ix.accounts.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(ix.accounts, ix.accounts_len)));
+    }
+    if !ix.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(ix.data, ix.data_len)));
+    }
+    ix.accounts = ptr::null_mut();
+    ix.accounts_len = 0;
+    ix.data = ptr::null_mut();
+    ix.data_len = 0;
+}
+
+fn escrow_to_c(s: &EscrowState) -> IsEscrowState {
+    IsEscrowState {
+        v: s.v,
+        status: s.status.as_u8(),
+        payment_hash: s.payment_hash,
+        recipient: s.recipient.into(),
+        refund: s.refund.into(),
+        refund_after: s.refund_after,
+        mint: s.mint.into(),
+        net_amount: s.net_amount,
+        platform_fee_amount: s.platform_fee_amount,
+        platform_fee_bps: s.platform_fee_bps,
+        platform_fee_collector: s.platform_fee_collector.into(),
+        trade_fee_amount: s.trade_fee_amount,
+        trade_fee_bps: s.trade_fee_bps,
+        trade_fee_collector: s.trade_fee_collector.into(),
+        vault: s.vault.into(),
+        bump: s.bump,
+        has_creator: s.creator.is_some(),
+        creator: s.creator.map(Into::into).unwrap_or_default(),
+    }
+}
+
+/// # Safety
+/// `data` must be valid for `len` bytes and `out` for writes.
+#[no_mangle]
+pub unsafe extern "C" fn is_decode_escrow(data: *const u8, len: usize, out: *mut IsEscrowState) -> IsStatus {
+    if data.is_null() {
+        return fail(IsStatus::NullPointer, "data is null");
+    }
+    match state::decode_escrow(slice::from_raw_parts(data, len)) {
+        Ok(s) => write(out, escrow_to_c(&s), "out"),
+        Err(e) => fail(IsStatus::Decode, e.to_string()),
+    }
+}
+
+/// Decodes a platform or trade config account (same layout).
+///
+/// # Safety
+/// `data` must be valid for `len` bytes and `out` for writes.
+#[no_mangle]
+pub unsafe extern "C" fn is_decode_config(data: *const u8, len: usize, out: *mut IsConfigState) -> IsStatus {
+    if data.is_null() {
+        return fail(IsStatus::NullPointer, "data is null");
+    }
+    match state::decode_config(slice::from_raw_parts(data, len)) {
+        Ok(s) => write(
+            out,
+            IsConfigState {
+                v: s.v,
+                authority: s.authority.into(),
+                fee_collector: s.fee_collector.into(),
+                fee_bps: s.fee_bps,
+                bump: s.bump,
+            },
+            "out",
+        ),
+        Err(e) => fail(IsStatus::Decode, e.to_string()),
+    }
+}
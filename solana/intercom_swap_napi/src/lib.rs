@@ -0,0 +1,271 @@
+//! napi-rs bindings so Node services reuse the canonical Rust encoder instead of a parallel implementation.
+//!
+//! Build with `npm run build` in this directory. Pubkeys are base58 strings, hashes hex, amounts `bigint`, and
+//! instruction data a `Buffer`; field names match `src/solana/lnUsdtEscrowClient.js`.
+
+use std::str::FromStr;
+
+use intercom_swap_client::{
+    instruction::{self, InitArgs, RefundAfter},
+    pda,
+    state,
+};
+use napi::bindgen_prelude::{BigInt, Buffer};
+use napi::{Error, Result};
+use napi_derive::napi;
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+fn invalid(msg: impl Into<String>) -> Error {
+    Error::from_reason(msg.into())
+}
+
+fn pubkey(label: &str, s: &str) -> Result<Pubkey> {
+    Pubkey::from_str(s.trim()).map_err(|_| invalid(format!("invalid {label} pubkey")))
+}
+
+fn program_id(s: Option<String>) -> Result<Pubkey> {
+    s.map_or(Ok(intercom_swap_client::PROGRAM_ID), |s| pubkey("programId", &s))
+}
+
+fn hex32(label: &str, s: &str) -> Result<[u8; 32]> {
+    let s = s.trim();
+    if s.len() != 64 || !s.is_ascii() {
+        return Err(invalid(format!("{label} must be 32 bytes hex")));
+    }
+    let mut out = [0u8; 32];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).map_err(|_| invalid(format!("{label} must be hex")))?;
+    }
+    Ok(out)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn u64_of(label: &str, v: &BigInt) -> Result<u64> {
+    match v.get_u64() {
+        (false, n, true) => Ok(n),
+        _ => Err(invalid(format!("{label} must fit in u64"))),
+    }
+}
+
+#[napi(object)]
+pub struct AccountMetaJs {
+    pub pubkey: String,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+#[napi(object)]
+pub struct InstructionJs {
+    pub program_id: String,
+    pub keys: Vec<AccountMetaJs>,
+    pub data: Buffer,
+}
+
+impl From<Instruction> for InstructionJs {
+    fn from(ix: Instruction) -> Self {
+        Self {
+            program_id: ix.program_id.to_string(),
+            keys: ix
+                .accounts
+                .into_iter()
+                .map(|m| AccountMetaJs {
+                    pubkey: m.pubkey.to_string(),
+                    is_signer: m.is_signer,
+                    is_writable: m.is_writable,
+                })
+                .collect(),
+            data: ix.data.into(),
+        }
+    }
+}
+
+#[napi(object)]
+pub struct EscrowPdas {
+    pub escrow: String,
+    pub vault: String,
+    pub config: String,
+    pub platform_fee_vault: String,
+    pub trade_config: String,
+    pub trade_fee_vault: String,
+    pub recipient_index: Option<String>,
+    pub refund_index: Option<String>,
+}
+
+/// Every PDA/ATA an escrow for `paymentHashHex` touches.
+#[napi]
+pub fn derive_pdas(
+    payment_hash_hex: String,
+    mint: String,
+    trade_fee_collector: String,
+    recipient: Option<String>,
+    refund: Option<String>,
+    program_id_b58: Option<String>,
+) -> Result<EscrowPdas> {
+    let program_id = program_id(program_id_b58)?;
+    let mint = pubkey("mint", &mint)?;
+    let escrow = pda::find_escrow_pda(&program_id, &hex32("paymentHashHex", &payment_hash_hex)?).0;
+    let config = pda::find_config_pda(&program_id).0;
+    let trade_config = pda::find_trade_config_pda(&program_id, &pubkey("tradeFeeCollector", &trade_fee_collector)?).0;
+    let index = |seed: &[u8], label: &str, owner: Option<String>| -> Result<Option<String>> {
+        owner
+            .map(|o| Ok(pda::find_index_pda(&program_id, seed, &pubkey(label, &o)?).0.to_string()))
+            .transpose()
+    };
+    Ok(EscrowPdas {
+        escrow: escrow.to_string(),
+        vault: pda::vault_ata(&escrow, &mint).to_string(),
+        config: config.to_string(),
+        platform_fee_vault: pda::fee_vault_ata_for(&config, &mint).to_string(),
+        trade_config: trade_config.to_string(),
+        trade_fee_vault: pda::fee_vault_ata_for(&trade_config, &mint).to_string(),
+        recipient_index: index(pda::RECIPIENT_INDEX_SEED, "recipient", recipient)?,
+        refund_index: index(pda::REFUND_INDEX_SEED, "refund", refund)?,
+    })
+}
+
+#[napi(object)]
+pub struct InitIxArgs {
+    pub payer: String,
+    pub payer_token: String,
+    pub mint: String,
+    pub payment_hash_hex: String,
+    pub recipient: String,
+    pub refund: String,
+    /// Exactly one of `refundAfterUnix` / `refundAfterSecs`.
+    pub refund_after_unix: Option<i64>,
+    pub refund_after_secs: Option<i64>,
+    pub amount: BigInt,
+    pub expected_platform_fee_bps: u16,
+    pub expected_trade_fee_bps: u16,
+    pub trade_fee_collector: String,
+    pub program_id: Option<String>,
+}
+
+#[napi(js_name = "buildInitIx")]
+pub fn build_init_ix(args: InitIxArgs) -> Result<InstructionJs> {
+    let refund_after = match (args.refund_after_unix, args.refund_after_secs) {
+        (Some(ts), None) => RefundAfter::At(ts),
+        (None, Some(secs)) => RefundAfter::Delay(secs),
+        _ => return Err(invalid("exactly one of refundAfterUnix or refundAfterSecs is required")),
+    };
+    let init = InitArgs {
+        payment_hash: hex32("paymentHashHex", &args.payment_hash_hex)?,
+        recipient: pubkey("recipient", &args.recipient)?,
+        refund: pubkey("refund", &args.refund)?,
+        refund_after,
+        amount: u64_of("amount", &args.amount)?,
+        expected_platform_fee_bps: args.expected_platform_fee_bps,
+        expected_trade_fee_bps: args.expected_trade_fee_bps,
+        trade_fee_collector: pubkey("tradeFeeCollector", &args.trade_fee_collector)?,
+    };
+    Ok(instruction::init(
+        &program_id(args.program_id)?,
+        &pubkey("payer", &args.payer)?,
+        &pubkey("payerToken", &args.payer_token)?,
+        &pubkey("mint", &args.mint)?,
+        &init,
+    )
+    .into())
+}
+
+#[napi(object)]
+pub struct ClaimIxArgs {
+    pub recipient: String,
+    pub recipient_token: String,
+    pub mint: String,
+    pub payment_hash_hex: String,
+    pub preimage_hex: String,
+    pub trade_fee_collector: String,
+    /// Required for v4+ escrows, omitted for v3.
+    pub creator: Option<String>,
+    pub program_id: Option<String>,
+}
+
+#[napi(js_name = "buildClaimIx")]
+pub fn build_claim_ix(args: ClaimIxArgs) -> Result<InstructionJs> {
+    let creator = args.creator.map(|c| pubkey("creator", &c)).transpose()?;
+    Ok(instruction::claim(
+        &program_id(args.program_id)?,
+        &pubkey("recipient", &args.recipient)?,
+        &pubkey("recipientToken", &args.recipient_token)?,
+        &pubkey("mint", &args.mint)?,
+        &hex32("paymentHashHex", &args.payment_hash_hex)?,
+        &hex32("preimageHex", &args.preimage_hex)?,
+        &pubkey("tradeFeeCollector", &args.trade_fee_collector)?,
+        creator.as_ref(),
+    )
+    .into())
+}
+
+#[napi(object)]
+pub struct RefundIxArgs {
+    pub refund: String,
+    pub refund_token: String,
+    pub mint: String,
+    pub payment_hash_hex: String,
+    /// Required for v4+ escrows, omitted for v3.
+    pub creator: Option<String>,
+    pub program_id: Option<String>,
+}
+
+#[napi(js_name = "buildRefundIx")]
+pub fn build_refund_ix(args: RefundIxArgs) -> Result<InstructionJs> {
+    let creator = args.creator.map(|c| pubkey("creator", &c)).transpose()?;
+    Ok(instruction::refund(
+        &program_id(args.program_id)?,
+        &pubkey("refund", &args.refund)?,
+        &pubkey("refundToken", &args.refund_token)?,
+        &pubkey("mint", &args.mint)?,
+        &hex32("paymentHashHex", &args.payment_hash_hex)?,
+        creator.as_ref(),
+    )
+    .into())
+}
+
+#[napi(object)]
+pub struct EscrowJs {
+    pub v: u8,
+    pub status: u8,
+    pub payment_hash_hex: String,
+    pub recipient: String,
+    pub refund: String,
+    pub refund_after: i64,
+    pub mint: String,
+    pub net_amount: BigInt,
+    pub platform_fee_amount: BigInt,
+    pub platform_fee_bps: u16,
+    pub platform_fee_collector: String,
+    pub trade_fee_amount: BigInt,
+    pub trade_fee_bps: u16,
+    pub trade_fee_collector: String,
+    pub vault: String,
+    pub bump: u8,
+    pub creator: Option<String>,
+}
+
+#[napi]
+pub fn decode_escrow(data: Buffer) -> Result<EscrowJs> {
+    let s = state::decode_escrow(&data).map_err(|e| invalid(e.to_string()))?;
+    Ok(EscrowJs {
+        v: s.v,
+        status: s.status.as_u8(),
+        payment_hash_hex: to_hex(&s.payment_hash),
+        recipient: s.recipient.to_string(),
+        refund: s.refund.to_string(),
+        refund_after: s.refund_after,
+        mint: s.mint.to_string(),
+        net_amount: BigInt::from(s.net_amount),
+        platform_fee_amount: BigInt::from(s.platform_fee_amount),
+        platform_fee_bps: s.platform_fee_bps,
+        platform_fee_collector: s.platform_fee_collector.to_string(),
+        trade_fee_amount: BigInt::from(s.trade_fee_amount),
+        trade_fee_bps: s.trade_fee_bps,
+        trade_fee_collector: s.trade_fee_collector.to_string(),
+        vault: s.vault.to_string(),
+        bump: s.bump,
+        creator: s.creator.map(|c| c.to_string()),
+    })
+}
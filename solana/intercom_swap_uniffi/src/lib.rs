@@ -0,0 +1,304 @@
+//! UniFFI bindings so iOS/Android wallets can offer USDT swaps without reimplementing the byte layouts.
+//!
+//! Generate bindings from the built library:
+//! `cargo run --bin uniffi-bindgen -- generate --library <libintercom_swap_uniffi> --language swift|kotlin`.
+//! Pubkeys are base58 strings and hashes/preimages lowercase hex, matching the JS client.
+
+use std::{fmt, str::FromStr};
+
+use intercom_swap_client::{
+    instruction::{self, InitArgs, RefundAfter},
+    pda,
+    state::{self, EscrowStatus},
+};
+use solana_program::{hash::hashv, instruction::Instruction, pubkey::Pubkey};
+
+uniffi::setup_scaffolding!();
+
+#[derive(Debug, uniffi::Error)]
+pub enum SwapError {
+    InvalidInput { message: String },
+    Decode { message: String },
+    Random { message: String },
+}
+
+impl fmt::Display for SwapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidInput { message } => write!(f, "invalid input: {message}"),
+            Self::Decode { message } => write!(f, "decode failed: {message}"),
+            Self::Random { message } => write!(f, "random source failed: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for SwapError {}
+
+fn invalid(message: impl Into<String>) -> SwapError {
+    SwapError::InvalidInput { message: message.into() }
+}
+
+fn pubkey(label: &str, s: &str) -> Result<Pubkey, SwapError> {
+    Pubkey::from_str(s.trim()).map_err(|_| invalid(format!("invalid {label} pubkey")))
+}
+
+fn program_id(s: Option<String>) -> Result<Pubkey, SwapError> {
+    s.map_or(Ok(intercom_swap_client::PROGRAM_ID), |s| pubkey("program id", &s))
+}
+
+fn hex32(label: &str, s: &str) -> Result<[u8; 32], SwapError> {
+    let s = s.trim();
+    if s.len() != 64 || !s.is_ascii() {
+        return Err(invalid(format!("{label} must be 32 bytes hex")));
+    }
+    let mut out = [0u8; 32];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).map_err(|_| invalid(format!("{label} must be hex")))?;
+    }
+    Ok(out)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct PreimagePair {
+    pub preimage_hex: String,
+    pub payment_hash_hex: String,
+}
+
+/// Fresh random preimage and its sha256 payment hash, for wallets that create the invoice themselves.
+#[uniffi::export]
+pub fn generate_preimage() -> Result<PreimagePair, SwapError> {
+    let mut preimage = [0u8; 32];
+    getrandom::getrandom(&mut preimage).map_err(|e| SwapError::Random { message: e.to_string() })?;
+    Ok(PreimagePair {
+        preimage_hex: to_hex(&preimage),
+        payment_hash_hex: to_hex(&hashv(&[&preimage]).to_bytes()),
+    })
+}
+
+#[uniffi::export]
+pub fn payment_hash_for(preimage_hex: String) -> Result<String, SwapError> {
+    let preimage = hex32("preimage", &preimage_hex)?;
+    Ok(to_hex(&hashv(&[&preimage]).to_bytes()))
+}
+
+/// Same check the program's Claim performs: sha256(preimage) == payment_hash.
+#[uniffi::export]
+pub fn preimage_matches(preimage_hex: String, payment_hash_hex: String) -> Result<bool, SwapError> {
+    let preimage = hex32("preimage", &preimage_hex)?;
+    let hash = hex32("payment hash", &payment_hash_hex)?;
+    Ok(hashv(&[&preimage]).to_bytes() == hash)
+}
+
+#[uniffi::export]
+pub fn derive_escrow_pda(payment_hash_hex: String, program_id_b58: Option<String>) -> Result<String, SwapError> {
+    let hash = hex32("payment hash", &payment_hash_hex)?;
+    Ok(pda::find_escrow_pda(&program_id(program_id_b58)?, &hash).0.to_string())
+}
+
+#[uniffi::export]
+pub fn derive_vault_ata(escrow_pda: String, mint: String) -> Result<String, SwapError> {
+    Ok(pda::vault_ata(&pubkey("escrow", &escrow_pda)?, &pubkey("mint", &mint)?).to_string())
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct AccountMetaRecord {
+    pub pubkey: String,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct InstructionRecord {
+    pub program_id: String,
+    pub accounts: Vec<AccountMetaRecord>,
+    pub data: Vec<u8>,
+}
+
+impl From<Instruction> for InstructionRecord {
+    fn from(ix: Instruction) -> Self {
+        Self {
+            program_id: ix.program_id.to_string(),
+            accounts: ix
+                .accounts
+                .into_iter()
+                .map(|m| AccountMetaRecord {
+                    pubkey: m.pubkey.to_string(),
+                    is_signer: m.is_signer,
+                    is_writable: m.is_writable,
+                })
+                .collect(),
+            data: ix.data,
+        }
+    }
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct InitRequest {
+    pub payer: String,
+    pub payer_token: String,
+    pub mint: String,
+    pub payment_hash_hex: String,
+    pub recipient: String,
+    pub refund: String,
+    /// Exactly one of `refund_after_unix` / `refund_after_secs`.
+    pub refund_after_unix: Option<i64>,
+    pub refund_after_secs: Option<i64>,
+    pub amount: u64,
+    pub expected_platform_fee_bps: u16,
+    pub expected_trade_fee_bps: u16,
+    pub trade_fee_collector: String,
+}
+
+#[uniffi::export]
+pub fn build_init_instruction(req: InitRequest, program_id_b58: Option<String>) -> Result<InstructionRecord, SwapError> {
+    let refund_after = match (req.refund_after_unix, req.refund_after_secs) {
+        (Some(ts), None) => RefundAfter::At(ts),
+        (None, Some(secs)) => RefundAfter::Delay(secs),
+        _ => return Err(invalid("exactly one of refund_after_unix or refund_after_secs is required")),
+    };
+    let args = InitArgs {
+        payment_hash: hex32("payment hash", &req.payment_hash_hex)?,
+        recipient: pubkey("recipient", &req.recipient)?,
+        refund: pubkey("refund", &req.refund)?,
+        refund_after,
+        amount: req.amount,
+        expected_platform_fee_bps: req.expected_platform_fee_bps,
+        expected_trade_fee_bps: req.expected_trade_fee_bps,
+        trade_fee_collector: pubkey("trade fee collector", &req.trade_fee_collector)?,
+    };
+    Ok(instruction::init(
+        &program_id(program_id_b58)?,
+        &pubkey("payer", &req.payer)?,
+        &pubkey("payer token", &req.payer_token)?,
+        &pubkey("mint", &req.mint)?,
+        &args,
+    )
+    .into())
+}
+
+/// `creator` is required for v4+ escrows (see [`EscrowRecord::creator`]) and must be `None` for v3.
+#[uniffi::export]
+pub fn build_claim_instruction(
+    recipient: String,
+    recipient_token: String,
+    mint: String,
+    preimage_hex: String,
+    trade_fee_collector: String,
+    creator: Option<String>,
+    program_id_b58: Option<String>,
+) -> Result<InstructionRecord, SwapError> {
+    let preimage = hex32("preimage", &preimage_hex)?;
+    let payment_hash = hashv(&[&preimage]).to_bytes();
+    let creator = creator.map(|c| pubkey("creator", &c)).transpose()?;
+    Ok(instruction::claim(
+        &program_id(program_id_b58)?,
+        &pubkey("recipient", &recipient)?,
+        &pubkey("recipient token", &recipient_token)?,
+        &pubkey("mint", &mint)?,
+        &payment_hash,
+        &preimage,
+        &pubkey("trade fee collector", &trade_fee_collector)?,
+        creator.as_ref(),
+    )
+    .into())
+}
+
+#[uniffi::export]
+pub fn build_refund_instruction(
+    refund: String,
+    refund_token: String,
+    mint: String,
+    payment_hash_hex: String,
+    creator: Option<String>,
+    program_id_b58: Option<String>,
+) -> Result<InstructionRecord, SwapError> {
+    let creator = creator.map(|c| pubkey("creator", &c)).transpose()?;
+    Ok(instruction::refund(
+        &program_id(program_id_b58)?,
+        &pubkey("refund", &refund)?,
+        &pubkey("refund token", &refund_token)?,
+        &pubkey("mint", &mint)?,
+        &hex32("payment hash", &payment_hash_hex)?,
+        creator.as_ref(),
+    )
+    .into())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum EscrowStatusRecord {
+    Active,
+    Claimed,
+    Refunded,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct EscrowRecord {
+    pub v: u8,
+    pub status: EscrowStatusRecord,
+    pub payment_hash_hex: String,
+    pub recipient: String,
+    pub refund: String,
+    pub refund_after: i64,
+    pub mint: String,
+    pub net_amount: u64,
+    pub platform_fee_amount: u64,
+    pub platform_fee_bps: u16,
+    pub platform_fee_collector: String,
+    pub trade_fee_amount: u64,
+    pub trade_fee_bps: u16,
+    pub trade_fee_collector: String,
+    pub vault: String,
+    /// `None` for v3 escrows.
+    pub creator: Option<String>,
+}
+
+#[uniffi::export]
+pub fn decode_escrow(data: Vec<u8>) -> Result<EscrowRecord, SwapError> {
+    let s = state::decode_escrow(&data).map_err(|e| SwapError::Decode { message: e.to_string() })?;
+    Ok(EscrowRecord {
+        v: s.v,
+        status: match s.status {
+            EscrowStatus::Active => EscrowStatusRecord::Active,
+            EscrowStatus::Claimed => EscrowStatusRecord::Claimed,
+            EscrowStatus::Refunded => EscrowStatusRecord::Refunded,
+        },
+        payment_hash_hex: to_hex(&s.payment_hash),
+        recipient: s.recipient.to_string(),
+        refund: s.refund.to_string(),
+        refund_after: s.refund_after,
+        mint: s.mint.to_string(),
+        net_amount: s.net_amount,
+        platform_fee_amount: s.platform_fee_amount,
+        platform_fee_bps: s.platform_fee_bps,
+        platform_fee_collector: s.platform_fee_collector.to_string(),
+        trade_fee_amount: s.trade_fee_amount,
+        trade_fee_bps: s.trade_fee_bps,
+        trade_fee_collector: s.trade_fee_collector.to_string(),
+        vault: s.vault.to_string(),
+        creator: s.creator.map(|c| c.to_string()),
+    })
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ConfigRecord {
+    pub v: u8,
+    pub authority: String,
+    pub fee_collector: String,
+    pub fee_bps: u16,
+}
+
+/// Decodes a platform or trade config account (same layout).
+#[uniffi::export]
+pub fn decode_config(data: Vec<u8>) -> Result<ConfigRecord, SwapError> {
+    let s = state::decode_config(&data).map_err(|e| SwapError::Decode { message: e.to_string() })?;
+    Ok(ConfigRecord {
+        v: s.v,
+        authority: s.authority.to_string(),
+        fee_collector: s.fee_collector.to_string(),
+        fee_bps: s.fee_bps,
+    })
+}
@@ -0,0 +1,287 @@
+//! wasm-bindgen exports of the client SDK for browser wallets and the swap frontend.
+//!
+//! Build with `wasm-pack build --target web`. Pubkeys cross the boundary as base58 strings, hashes and
+//! preimages as hex, and u64 amounts as `bigint`. Instructions are returned as plain
+//! `{ programId, keys: [{ pubkey, isSigner, isWritable }], data }` objects, which is the shape
+//! `new TransactionInstruction(...)` from `@solana/web3.js` accepts once the strings are wrapped in `PublicKey`.
+//! Field names follow `src/solana/lnUsdtEscrowClient.js`.
+
+use std::str::FromStr;
+
+use intercom_swap_client::{
+    instruction::{self, InitArgs, RefundAfter},
+    pda,
+    state::{self, ConfigState, EscrowIndexState, EscrowState},
+};
+use js_sys::{Array, BigInt, Object, Reflect, Uint8Array};
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use wasm_bindgen::prelude::*;
+
+fn err(msg: impl std::fmt::Display) -> JsError {
+    JsError::new(&msg.to_string())
+}
+
+fn pubkey(label: &str, s: &str) -> Result<Pubkey, JsError> {
+    Pubkey::from_str(s.trim()).map_err(|_| err(format!("invalid {label} pubkey")))
+}
+
+fn program_id(s: Option<String>) -> Result<Pubkey, JsError> {
+    match s {
+        Some(s) => pubkey("program id", &s),
+        None => Ok(intercom_swap_client::PROGRAM_ID),
+    }
+}
+
+fn hex32(label: &str, s: &str) -> Result<[u8; 32], JsError> {
+    let s = s.trim();
+    if s.len() != 64 || !s.is_ascii() {
+        return Err(err(format!("{label} must be 32 bytes hex")));
+    }
+    let mut out = [0u8; 32];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).map_err(|_| err(format!("{label} must be hex")))?;
+    }
+    Ok(out)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn set(obj: &Object, key: &str, value: impl Into<JsValue>) {
+    // Reflect::set only fails on frozen/proxy targets, never on a fresh Object.
+    let _ = Reflect::set(obj, &JsValue::from_str(key), &value.into());
+}
+
+fn instruction_to_js(ix: &Instruction) -> Object {
+    let keys = Array::new();
+    for meta in &ix.accounts {
+        let key = Object::new();
+        set(&key, "pubkey", meta.pubkey.to_string());
+        set(&key, "isSigner", meta.is_signer);
+        set(&key, "isWritable", meta.is_writable);
+        keys.push(&key);
+    }
+    let out = Object::new();
+    set(&out, "programId", ix.program_id.to_string());
+    set(&out, "keys", keys);
+    set(&out, "data", Uint8Array::from(ix.data.as_slice()));
+    out
+}
+
+#[wasm_bindgen(js_name = deriveEscrowPda)]
+pub fn derive_escrow_pda(payment_hash_hex: &str, program_id_b58: Option<String>) -> Result<String, JsError> {
+    let hash = hex32("payment hash", payment_hash_hex)?;
+    Ok(pda::find_escrow_pda(&program_id(program_id_b58)?, &hash).0.to_string())
+}
+
+#[wasm_bindgen(js_name = deriveConfigPda)]
+pub fn derive_config_pda(program_id_b58: Option<String>) -> Result<String, JsError> {
+    Ok(pda::find_config_pda(&program_id(program_id_b58)?).0.to_string())
+}
+
+#[wasm_bindgen(js_name = deriveTradeConfigPda)]
+pub fn derive_trade_config_pda(fee_collector: &str, program_id_b58: Option<String>) -> Result<String, JsError> {
+    let fee_collector = pubkey("fee collector", fee_collector)?;
+    Ok(pda::find_trade_config_pda(&program_id(program_id_b58)?, &fee_collector).0.to_string())
+}
+
+#[wasm_bindgen(js_name = deriveRecipientIndexPda)]
+pub fn derive_recipient_index_pda(recipient: &str, program_id_b58: Option<String>) -> Result<String, JsError> {
+    let recipient = pubkey("recipient", recipient)?;
+    let program_id = program_id(program_id_b58)?;
+    Ok(pda::find_index_pda(&program_id, pda::RECIPIENT_INDEX_SEED, &recipient).0.to_string())
+}
+
+#[wasm_bindgen(js_name = deriveRefundIndexPda)]
+pub fn derive_refund_index_pda(refund: &str, program_id_b58: Option<String>) -> Result<String, JsError> {
+    let refund = pubkey("refund", refund)?;
+    let program_id = program_id(program_id_b58)?;
+    Ok(pda::find_index_pda(&program_id, pda::REFUND_INDEX_SEED, &refund).0.to_string())
+}
+
+#[wasm_bindgen(js_name = deriveVaultAta)]
+pub fn derive_vault_ata(escrow_pda: &str, mint: &str) -> Result<String, JsError> {
+    Ok(pda::vault_ata(&pubkey("escrow", escrow_pda)?, &pubkey("mint", mint)?).to_string())
+}
+
+/// Fee vault ATA of a platform or trade config PDA.
+#[wasm_bindgen(js_name = deriveFeeVaultAta)]
+pub fn derive_fee_vault_ata(config_pda: &str, mint: &str) -> Result<String, JsError> {
+    Ok(pda::fee_vault_ata_for(&pubkey("config", config_pda)?, &pubkey("mint", mint)?).to_string())
+}
+
+/// `amount * feeBps / 10000` rounded down, exactly as Init computes each fee.
+#[wasm_bindgen(js_name = computeFee)]
+pub fn compute_fee(amount: u64, fee_bps: u16) -> Result<u64, JsError> {
+    intercom_swap_client::amount::compute_fee(amount, fee_bps).ok_or_else(|| err("fee overflows u64"))
+}
+
+/// Total Init debits from the payer for `netAmount`; display this rather than summing fees in JS.
+#[wasm_bindgen(js_name = totalWithFee)]
+pub fn total_with_fee(net_amount: u64, platform_fee_bps: u16, trade_fee_bps: u16) -> Result<u64, JsError> {
+    intercom_swap_client::amount::total_with_fee(net_amount, platform_fee_bps, trade_fee_bps)
+        .ok_or_else(|| err("total overflows u64"))
+}
+
+/// Exactly one of `refund_after_unix` / `refund_after_secs` must be set, as in `buildInitInstruction`.
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen(js_name = buildInitInstruction)]
+pub fn build_init_instruction(
+    payer: &str,
+    payer_token: &str,
+    mint: &str,
+    payment_hash_hex: &str,
+    recipient: &str,
+    refund: &str,
+    refund_after_unix: Option<i64>,
+    refund_after_secs: Option<i64>,
+    amount: u64,
+    expected_platform_fee_bps: u16,
+    expected_trade_fee_bps: u16,
+    trade_fee_collector: &str,
+    program_id_b58: Option<String>,
+) -> Result<Object, JsError> {
+    let refund_after = match (refund_after_unix, refund_after_secs) {
+        (Some(ts), None) => RefundAfter::At(ts),
+        (None, Some(secs)) => RefundAfter::Delay(secs),
+        _ => return Err(err("exactly one of refundAfterUnix or refundAfterSecs is required")),
+    };
+    let args = InitArgs {
+        payment_hash: hex32("payment hash", payment_hash_hex)?,
+        recipient: pubkey("recipient", recipient)?,
+        refund: pubkey("refund", refund)?,
+        refund_after,
+        amount,
+        expected_platform_fee_bps,
+        expected_trade_fee_bps,
+        trade_fee_collector: pubkey("trade fee collector", trade_fee_collector)?,
+    };
+    let ix = instruction::init(
+        &program_id(program_id_b58)?,
+        &pubkey("payer", payer)?,
+        &pubkey("payer token", payer_token)?,
+        &pubkey("mint", mint)?,
+        &args,
+    );
+    Ok(instruction_to_js(&ix))
+}
+
+/// `creator` is required for v4+ escrows and must be omitted for v3 (see `decodeEscrowState().creator`).
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen(js_name = buildClaimInstruction)]
+pub fn build_claim_instruction(
+    recipient: &str,
+    recipient_token: &str,
+    mint: &str,
+    payment_hash_hex: &str,
+    preimage_hex: &str,
+    trade_fee_collector: &str,
+    creator: Option<String>,
+    program_id_b58: Option<String>,
+) -> Result<Object, JsError> {
+    let creator = creator.map(|c| pubkey("creator", &c)).transpose()?;
+    let ix = instruction::claim(
+        &program_id(program_id_b58)?,
+        &pubkey("recipient", recipient)?,
+        &pubkey("recipient token", recipient_token)?,
+        &pubkey("mint", mint)?,
+        &hex32("payment hash", payment_hash_hex)?,
+        &hex32("preimage", preimage_hex)?,
+        &pubkey("trade fee collector", trade_fee_collector)?,
+        creator.as_ref(),
+    );
+    Ok(instruction_to_js(&ix))
+}
+
+#[wasm_bindgen(js_name = buildRefundInstruction)]
+pub fn build_refund_instruction(
+    refund: &str,
+    refund_token: &str,
+    mint: &str,
+    payment_hash_hex: &str,
+    creator: Option<String>,
+    program_id_b58: Option<String>,
+) -> Result<Object, JsError> {
+    let creator = creator.map(|c| pubkey("creator", &c)).transpose()?;
+    let ix = instruction::refund(
+        &program_id(program_id_b58)?,
+        &pubkey("refund", refund)?,
+        &pubkey("refund token", refund_token)?,
+        &pubkey("mint", mint)?,
+        &hex32("payment hash", payment_hash_hex)?,
+        creator.as_ref(),
+    );
+    Ok(instruction_to_js(&ix))
+}
+
+fn escrow_to_js(s: &EscrowState) -> Object {
+    let out = Object::new();
+    set(&out, "v", s.v);
+    set(&out, "status", s.status.as_u8());
+    set(&out, "paymentHashHex", to_hex(&s.payment_hash));
+    set(&out, "recipient", s.recipient.to_string());
+    set(&out, "refund", s.refund.to_string());
+    set(&out, "refundAfter", BigInt::from(s.refund_after));
+    set(&out, "mint", s.mint.to_string());
+    set(&out, "amount", BigInt::from(s.net_amount));
+    set(&out, "netAmount", BigInt::from(s.net_amount));
+    set(&out, "platformFeeAmount", BigInt::from(s.platform_fee_amount));
+    set(&out, "platformFeeBps", s.platform_fee_bps);
+    set(&out, "platformFeeCollector", s.platform_fee_collector.to_string());
+    set(&out, "tradeFeeAmount", BigInt::from(s.trade_fee_amount));
+    set(&out, "tradeFeeBps", s.trade_fee_bps);
+    set(&out, "tradeFeeCollector", s.trade_fee_collector.to_string());
+    set(&out, "vault", s.vault.to_string());
+    set(&out, "bump", s.bump);
+    set(
+        &out,
+        "creator",
+        s.creator.map_or(JsValue::NULL, |c| JsValue::from_str(&c.to_string())),
+    );
+    out
+}
+
+fn config_to_js(s: &ConfigState) -> Object {
+    let out = Object::new();
+    set(&out, "v", s.v);
+    set(&out, "authority", s.authority.to_string());
+    set(&out, "feeCollector", s.fee_collector.to_string());
+    set(&out, "feeBps", s.fee_bps);
+    set(&out, "bump", s.bump);
+    out
+}
+
+fn index_to_js(s: &EscrowIndexState) -> Object {
+    let escrows = Array::new();
+    for escrow in &s.escrows {
+        escrows.push(&JsValue::from_str(&escrow.to_string()));
+    }
+    let out = Object::new();
+    set(&out, "v", s.v);
+    set(&out, "owner", s.owner.to_string());
+    set(&out, "total", BigInt::from(s.total));
+    set(&out, "escrows", escrows);
+    set(&out, "bump", s.bump);
+    out
+}
+
+#[wasm_bindgen(js_name = decodeEscrowState)]
+pub fn decode_escrow_state(data: &[u8]) -> Result<Object, JsError> {
+    Ok(escrow_to_js(&state::decode_escrow(data).map_err(err)?))
+}
+
+#[wasm_bindgen(js_name = decodeConfigState)]
+pub fn decode_config_state(data: &[u8]) -> Result<Object, JsError> {
+    Ok(config_to_js(&state::decode_config(data).map_err(err)?))
+}
+
+#[wasm_bindgen(js_name = decodeTradeConfigState)]
+pub fn decode_trade_config_state(data: &[u8]) -> Result<Object, JsError> {
+    Ok(config_to_js(&state::decode_trade_config(data).map_err(err)?))
+}
+
+#[wasm_bindgen(js_name = decodeEscrowIndexState)]
+pub fn decode_escrow_index_state(data: &[u8]) -> Result<Object, JsError> {
+    Ok(index_to_js(&state::decode_escrow_index(data).map_err(err)?))
+}
@@ -1,9 +1,10 @@
-use borsh::{BorshDeserialize, BorshSerialize};
+pub mod pda;
+
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
-    entrypoint,
     entrypoint::ProgramResult,
     hash::hash,
+    log::sol_log_data,
     msg,
     program::{invoke, invoke_signed},
     program_error::ProgramError,
@@ -13,206 +14,38 @@ use solana_program::{
     sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
 
+use intercom_swap_core::{
+    events::MAX_EVENT_LEN,
+    state::{CONFIG_LEN, CONFIG_V1, ESCROW_INDEX_LEN, ESCROW_INDEX_V1, ESCROW_V4, ESCROW_V4_LEN},
+    ConfigState, EscrowAmounts, EscrowError, EscrowEvent, EscrowIndexState, EscrowInstruction, EscrowState,
+    EscrowStatus, FeeSource, RefundAfter, TradeConfigState, MAX_PLATFORM_FEE_BPS, MAX_TOTAL_FEE_BPS, MAX_TRADE_FEE_BPS,
+};
+use pda::{
+    fee_vault_ata_for, find_config_pda, find_escrow_pda, find_index_pda, find_trade_config_pda, vault_ata, CONFIG_SEED,
+    ESCROW_SEED, RECIPIENT_INDEX_SEED, REFUND_INDEX_SEED, TRADE_CONFIG_SEED,
+};
+
 // Program id for this fork's production deployment.
 // Keep this in sync with `src/solana/lnUsdtEscrowClient.js` (`LN_USDT_ESCROW_PROGRAM_ID`).
 solana_program::declare_id!("4RS6xpspM1V2K7FKSqeSH6VVaZbtzHzhJqacwrz8gJrF");
 
-const ESCROW_SEED: &[u8] = b"escrow";
-const CONFIG_SEED: &[u8] = b"config";
-const TRADE_CONFIG_SEED: &[u8] = b"trade_config";
-// Fee caps are enforced on-chain (and re-validated during escrow init).
-// Basis points: 10_000 = 100%.
-const MAX_PLATFORM_FEE_BPS: u16 = 500; // 5%
-const MAX_TRADE_FEE_BPS: u16 = 1000; // 10%
-const MAX_TOTAL_FEE_BPS: u16 = 1500; // 15% (platform + trade)
-
-#[repr(u32)]
-enum EscrowError {
-    InvalidInstruction = 1,
-    InvalidEscrowPda = 2,
-    InvalidVaultAta = 3,
-    InvalidTokenAccount = 4,
-    InvalidSigner = 5,
-    InvalidPreimage = 6,
-    NotActive = 7,
-    TooEarly = 8,
-    InvalidConfigPda = 9,
-    InvalidConfigState = 10,
-    FeeTooHigh = 11,
-    AlreadyInitialized = 12,
-    InvalidFeeVaultAta = 13,
-    InvalidTradeConfigPda = 14,
-    InvalidTradeConfigState = 15,
-    InvalidTradeFeeVaultAta = 16,
-    FeeMismatch = 17,
-}
-
-impl From<EscrowError> for ProgramError {
-    fn from(e: EscrowError) -> Self {
-        ProgramError::Custom(e as u32)
-    }
-}
-
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
-struct EscrowState {
-    v: u8,
-    status: u8, // 0=active, 1=claimed, 2=refunded
-    payment_hash: [u8; 32],
-    recipient: [u8; 32],
-    refund: [u8; 32],
-    refund_after: i64,
-    mint: [u8; 32],
-    net_amount: u64,
-    platform_fee_amount: u64,
-    platform_fee_bps: u16,
-    platform_fee_collector: [u8; 32],
-    trade_fee_amount: u64,
-    trade_fee_bps: u16,
-    trade_fee_collector: [u8; 32],
-    vault: [u8; 32],
-    bump: u8,
-}
-
-impl EscrowState {
-    const V3: u8 = 3;
-    const STATUS_ACTIVE: u8 = 0;
-    const STATUS_CLAIMED: u8 = 1;
-    const STATUS_REFUNDED: u8 = 2;
-}
-
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
-struct ConfigState {
-    v: u8,
-    authority: [u8; 32],
-    fee_collector: [u8; 32],
-    fee_bps: u16,
-    bump: u8,
-}
-
-impl ConfigState {
-    const V1: u8 = 1;
-}
-
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
-struct TradeConfigState {
-    v: u8,
-    authority: [u8; 32],
-    fee_collector: [u8; 32],
-    fee_bps: u16,
-    bump: u8,
-}
+// Account layouts, instruction encoding and error codes live in `intercom-swap-core`, shared with the clients.
 
-impl TradeConfigState {
-    const V1: u8 = 1;
+// v3 and v4 escrows are both accepted and written back in their own layout (v3 accounts are never resized).
+fn load_escrow(data: &[u8]) -> Result<EscrowState, ProgramError> {
+    EscrowState::unpack(data).map_err(|_| ProgramError::InvalidAccountData)
 }
 
-enum EscrowIx {
-    Init {
-        payment_hash: [u8; 32],
-        recipient: Pubkey,
-        refund: Pubkey,
-        refund_after: i64,
-        amount: u64,
-        expected_platform_fee_bps: u16,
-        expected_trade_fee_bps: u16,
-        trade_fee_collector: Pubkey,
-    },
-    Claim { preimage: [u8; 32] },
-    Refund,
-    InitConfig { fee_collector: Pubkey, fee_bps: u16 },
-    SetConfig { fee_collector: Pubkey, fee_bps: u16 },
-    WithdrawFees { amount: u64 },
-    InitTradeConfig { fee_collector: Pubkey, fee_bps: u16 },
-    SetTradeConfig { fee_collector: Pubkey, fee_bps: u16 },
-    WithdrawTradeFees { amount: u64 },
+fn store_escrow(state: &EscrowState, data: &mut [u8]) -> ProgramResult {
+    state.pack_into(data).map_err(|_| ProgramError::InvalidAccountData)
 }
 
-fn read_bytes<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], ProgramError> {
-    if data.len() < N {
-        return Err(EscrowError::InvalidInstruction.into());
-    }
-    let (head, tail) = data.split_at(N);
-    *data = tail;
-    let mut out = [0u8; N];
-    out.copy_from_slice(head);
-    Ok(out)
+fn load_config(data: &[u8], err: EscrowError) -> Result<ConfigState, ProgramError> {
+    ConfigState::unpack(data).map_err(|_| err.into())
 }
 
-fn read_u64_le(data: &mut &[u8]) -> Result<u64, ProgramError> {
-    Ok(u64::from_le_bytes(read_bytes::<8>(data)?))
-}
-
-fn read_i64_le(data: &mut &[u8]) -> Result<i64, ProgramError> {
-    Ok(i64::from_le_bytes(read_bytes::<8>(data)?))
-}
-
-fn read_u16_le(data: &mut &[u8]) -> Result<u16, ProgramError> {
-    Ok(u16::from_le_bytes(read_bytes::<2>(data)?))
-}
-
-fn parse_ix(input: &[u8]) -> Result<EscrowIx, ProgramError> {
-    let mut data = input;
-    if data.is_empty() {
-        return Err(EscrowError::InvalidInstruction.into());
-    }
-    let tag = data[0];
-    data = &data[1..];
-    match tag {
-        0 => {
-            let payment_hash = read_bytes::<32>(&mut data)?;
-            let recipient = Pubkey::new_from_array(read_bytes::<32>(&mut data)?);
-            let refund = Pubkey::new_from_array(read_bytes::<32>(&mut data)?);
-            let refund_after = read_i64_le(&mut data)?;
-            let amount = read_u64_le(&mut data)?;
-            let expected_platform_fee_bps = read_u16_le(&mut data)?;
-            let expected_trade_fee_bps = read_u16_le(&mut data)?;
-            let trade_fee_collector = Pubkey::new_from_array(read_bytes::<32>(&mut data)?);
-            Ok(EscrowIx::Init {
-                payment_hash,
-                recipient,
-                refund,
-                refund_after,
-                amount,
-                expected_platform_fee_bps,
-                expected_trade_fee_bps,
-                trade_fee_collector,
-            })
-        }
-        1 => {
-            let preimage = read_bytes::<32>(&mut data)?;
-            Ok(EscrowIx::Claim { preimage })
-        }
-        2 => Ok(EscrowIx::Refund),
-        3 => {
-            let fee_collector = Pubkey::new_from_array(read_bytes::<32>(&mut data)?);
-            let fee_bps = read_u16_le(&mut data)?;
-            Ok(EscrowIx::InitConfig { fee_collector, fee_bps })
-        }
-        4 => {
-            let fee_collector = Pubkey::new_from_array(read_bytes::<32>(&mut data)?);
-            let fee_bps = read_u16_le(&mut data)?;
-            Ok(EscrowIx::SetConfig { fee_collector, fee_bps })
-        }
-        5 => {
-            let amount = read_u64_le(&mut data)?;
-            Ok(EscrowIx::WithdrawFees { amount })
-        }
-        6 => {
-            let fee_collector = Pubkey::new_from_array(read_bytes::<32>(&mut data)?);
-            let fee_bps = read_u16_le(&mut data)?;
-            Ok(EscrowIx::InitTradeConfig { fee_collector, fee_bps })
-        }
-        7 => {
-            let fee_collector = Pubkey::new_from_array(read_bytes::<32>(&mut data)?);
-            let fee_bps = read_u16_le(&mut data)?;
-            Ok(EscrowIx::SetTradeConfig { fee_collector, fee_bps })
-        }
-        8 => {
-            let amount = read_u64_le(&mut data)?;
-            Ok(EscrowIx::WithdrawTradeFees { amount })
-        }
-        _ => Err(EscrowError::InvalidInstruction.into()),
-    }
+fn store_config(state: &ConfigState, data: &mut [u8]) -> ProgramResult {
+    state.pack_into(data).map_err(|_| ProgramError::InvalidAccountData)
 }
 
 fn assert_signer(ai: &AccountInfo) -> Result<(), ProgramError> {
@@ -229,31 +62,144 @@ fn assert_writable(ai: &AccountInfo) -> Result<(), ProgramError> {
     Ok(())
 }
 
-fn pda_for_hash(program_id: &Pubkey, payment_hash: &[u8; 32]) -> (Pubkey, u8) {
-    Pubkey::find_program_address(&[ESCROW_SEED, payment_hash], program_id)
+// An index PDA to record an escrow in, with the seed and key it is derived from.
+struct IndexTarget<'a, 'b> {
+    index: &'b AccountInfo<'a>,
+    seed: &'static [u8],
+    owner: &'b Pubkey,
 }
 
-fn config_pda(program_id: &Pubkey) -> (Pubkey, u8) {
-    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+// Appends `escrow` to the index PDA seeded by (`seed`, `owner`), creating the account on first use.
+fn record_in_index<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    rent: &Rent,
+    target: IndexTarget<'a, '_>,
+    escrow: &Pubkey,
+) -> ProgramResult {
+    let IndexTarget { index, seed, owner } = target;
+    assert_writable(index)?;
+    let (expected_index, bump) = find_index_pda(program_id, seed, owner);
+    if expected_index != *index.key {
+        msg!("index PDA mismatch");
+        return Err(EscrowError::InvalidIndexPda.into());
+    }
+
+    let mut state = if index.data_is_empty() {
+        // Index addresses are predictable, so tolerate a pre-funded PDA instead of failing in create_account.
+        let signer_seeds: &[&[u8]] = &[seed, owner.as_ref(), &[bump]];
+        let lamports = rent.minimum_balance(ESCROW_INDEX_LEN);
+        if index.lamports() == 0 {
+            invoke_signed(
+                &system_instruction::create_account(
+                    payer.key,
+                    index.key,
+                    lamports,
+                    ESCROW_INDEX_LEN as u64,
+                    program_id,
+                ),
+                &[payer.clone(), index.clone(), system_program.clone()],
+                &[signer_seeds],
+            )?;
+        } else {
+            let top_up = lamports.saturating_sub(index.lamports());
+            if top_up > 0 {
+                invoke(
+                    &system_instruction::transfer(payer.key, index.key, top_up),
+                    &[payer.clone(), index.clone(), system_program.clone()],
+                )?;
+            }
+            invoke_signed(
+                &system_instruction::allocate(index.key, ESCROW_INDEX_LEN as u64),
+                &[index.clone(), system_program.clone()],
+                &[signer_seeds],
+            )?;
+            invoke_signed(
+                &system_instruction::assign(index.key, program_id),
+                &[index.clone(), system_program.clone()],
+                &[signer_seeds],
+            )?;
+        }
+        EscrowIndexState::new(owner.to_bytes(), bump)
+    } else {
+        let state =
+            EscrowIndexState::unpack(&index.try_borrow_data()?).map_err(|_| EscrowError::InvalidIndexState)?;
+        if state.v != ESCROW_INDEX_V1 || state.bump != bump || state.owner != owner.to_bytes() {
+            msg!("index state version/bump/owner mismatch");
+            return Err(EscrowError::InvalidIndexState.into());
+        }
+        state
+    };
+
+    state.push(escrow.to_bytes()).ok_or(EscrowError::InvalidIndexState)?;
+    state
+        .pack_into(&mut index.try_borrow_mut_data()?)
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    Ok(())
+}
+
+// v4+ escrows take the creator as a trailing account so the vault rent can be returned to it.
+fn next_creator_account<'a, 'b>(
+    acc_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+    state: &EscrowState,
+) -> Result<Option<&'b AccountInfo<'a>>, ProgramError> {
+    let Some(creator_pk) = state.creator.map(Pubkey::new_from_array) else {
+        return Ok(None);
+    };
+    let creator = next_account_info(acc_iter)?;
+    assert_writable(creator)?;
+    if *creator.key != creator_pk {
+        msg!("creator mismatch");
+        return Err(EscrowError::InvalidCreator.into());
+    }
+    Ok(Some(creator))
+}
+
+// Closes the vault ATA (rent to creator) once all escrowed tokens have left it.
+// Tokens sent to the vault out-of-band keep it open rather than blocking settlement.
+fn close_vault_if_drained<'a>(
+    token_program: &AccountInfo<'a>,
+    vault: &AccountInfo<'a>,
+    escrow: &AccountInfo<'a>,
+    creator: &AccountInfo<'a>,
+    vault_balance_before: u64,
+    transferred: u64,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    if vault_balance_before != transferred {
+        msg!("vault holds extra tokens; leaving it open");
+        return Ok(());
+    }
+    let close_ix =
+        spl_token::instruction::close_account(token_program.key, vault.key, creator.key, escrow.key, &[])?;
+    invoke_signed(
+        &close_ix,
+        &[vault.clone(), creator.clone(), escrow.clone(), token_program.clone()],
+        &[seeds],
+    )
 }
 
-fn trade_config_pda(program_id: &Pubkey, fee_collector: &Pubkey) -> (Pubkey, u8) {
-    Pubkey::find_program_address(&[TRADE_CONFIG_SEED, fee_collector.as_ref()], program_id)
+fn emit(event: &EscrowEvent) {
+    let mut buf = [0u8; MAX_EVENT_LEN];
+    let len = event.pack_into(&mut buf);
+    sol_log_data(&[&buf[..len]]);
 }
 
 fn require_active(state: &EscrowState) -> Result<(), ProgramError> {
-    if state.status != EscrowState::STATUS_ACTIVE {
+    if state.status != EscrowStatus::Active {
         return Err(EscrowError::NotActive.into());
     }
     Ok(())
 }
 
-entrypoint!(process_instruction);
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
 
-fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], instruction_data: &[u8]) -> ProgramResult {
-    let ix = parse_ix(instruction_data)?;
+pub fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], instruction_data: &[u8]) -> ProgramResult {
+    let ix = EscrowInstruction::unpack(instruction_data)?;
     match ix {
-        EscrowIx::Init {
+        EscrowInstruction::Init {
             payment_hash,
             recipient,
             refund,
@@ -266,34 +212,34 @@ fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], instructio
             program_id,
             accounts,
             payment_hash,
-            recipient,
-            refund,
+            Pubkey::new_from_array(recipient),
+            Pubkey::new_from_array(refund),
             refund_after,
             amount,
             expected_platform_fee_bps,
             expected_trade_fee_bps,
-            trade_fee_collector,
+            Pubkey::new_from_array(trade_fee_collector),
         ),
-        EscrowIx::Claim { preimage } => process_claim(program_id, accounts, preimage),
-        EscrowIx::Refund => process_refund(program_id, accounts),
-        EscrowIx::InitConfig {
+        EscrowInstruction::Claim { preimage } => process_claim(program_id, accounts, preimage),
+        EscrowInstruction::Refund => process_refund(program_id, accounts),
+        EscrowInstruction::InitConfig {
             fee_collector,
             fee_bps,
-        } => process_init_config(program_id, accounts, fee_collector, fee_bps),
-        EscrowIx::SetConfig {
+        } => process_init_config(program_id, accounts, Pubkey::new_from_array(fee_collector), fee_bps),
+        EscrowInstruction::SetConfig {
             fee_collector,
             fee_bps,
-        } => process_set_config(program_id, accounts, fee_collector, fee_bps),
-        EscrowIx::WithdrawFees { amount } => process_withdraw_fees(program_id, accounts, amount),
-        EscrowIx::InitTradeConfig {
+        } => process_set_config(program_id, accounts, Pubkey::new_from_array(fee_collector), fee_bps),
+        EscrowInstruction::WithdrawFees { amount } => process_withdraw_fees(program_id, accounts, amount),
+        EscrowInstruction::InitTradeConfig {
             fee_collector,
             fee_bps,
-        } => process_init_trade_config(program_id, accounts, fee_collector, fee_bps),
-        EscrowIx::SetTradeConfig {
+        } => process_init_trade_config(program_id, accounts, Pubkey::new_from_array(fee_collector), fee_bps),
+        EscrowInstruction::SetTradeConfig {
             fee_collector,
             fee_bps,
-        } => process_set_trade_config(program_id, accounts, fee_collector, fee_bps),
-        EscrowIx::WithdrawTradeFees { amount } => process_withdraw_trade_fees(program_id, accounts, amount),
+        } => process_set_trade_config(program_id, accounts, Pubkey::new_from_array(fee_collector), fee_bps),
+        EscrowInstruction::WithdrawTradeFees { amount } => process_withdraw_trade_fees(program_id, accounts, amount),
     }
 }
 
@@ -327,7 +273,7 @@ fn process_init_trade_config(
         return Err(EscrowError::InvalidSigner.into());
     }
 
-    let (expected_trade_cfg, bump) = trade_config_pda(program_id, &fee_collector);
+    let (expected_trade_cfg, bump) = find_trade_config_pda(program_id, &fee_collector);
     if expected_trade_cfg != *trade_config.key {
         msg!("trade config PDA mismatch");
         return Err(EscrowError::InvalidTradeConfigPda.into());
@@ -339,7 +285,7 @@ fn process_init_trade_config(
     }
 
     let rent = Rent::from_account_info(rent_sysvar)?;
-    let space = 1usize + 32 + 32 + 2 + 1; // TradeConfigState layout
+    let space = CONFIG_LEN;
     let lamports = rent.minimum_balance(space);
     invoke_signed(
         &system_instruction::create_account(
@@ -354,15 +300,13 @@ fn process_init_trade_config(
     )?;
 
     let state = TradeConfigState {
-        v: TradeConfigState::V1,
+        v: CONFIG_V1,
         authority: payer.key.to_bytes(),
         fee_collector: fee_collector.to_bytes(),
         fee_bps,
         bump,
     };
-    state
-        .serialize(&mut &mut trade_config.try_borrow_mut_data()?[..])
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    store_config(&state, &mut trade_config.try_borrow_mut_data()?)?;
     Ok(())
 }
 
@@ -391,15 +335,14 @@ fn process_set_trade_config(
         return Err(EscrowError::InvalidSigner.into());
     }
 
-    let (expected_trade_cfg, bump) = trade_config_pda(program_id, &fee_collector);
+    let (expected_trade_cfg, bump) = find_trade_config_pda(program_id, &fee_collector);
     if expected_trade_cfg != *trade_config.key {
         msg!("trade config PDA mismatch");
         return Err(EscrowError::InvalidTradeConfigPda.into());
     }
 
-    let mut state = TradeConfigState::try_from_slice(&trade_config.try_borrow_data()?)
-        .map_err(|_| EscrowError::InvalidTradeConfigState)?;
-    if state.v != TradeConfigState::V1 || state.bump != bump {
+    let mut state = load_config(&trade_config.try_borrow_data()?, EscrowError::InvalidTradeConfigState)?;
+    if state.v != CONFIG_V1 || state.bump != bump {
         msg!("trade config state version/bump mismatch");
         return Err(EscrowError::InvalidTradeConfigState.into());
     }
@@ -410,9 +353,7 @@ fn process_set_trade_config(
 
     state.fee_collector = fee_collector.to_bytes();
     state.fee_bps = fee_bps;
-    state
-        .serialize(&mut &mut trade_config.try_borrow_mut_data()?[..])
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    store_config(&state, &mut trade_config.try_borrow_mut_data()?)?;
     Ok(())
 }
 
@@ -435,15 +376,14 @@ fn process_withdraw_trade_fees(program_id: &Pubkey, accounts: &[AccountInfo], am
     assert_writable(dest_token)?;
 
     // Validate trade config PDA from signer.
-    let (expected_trade_cfg, bump) = trade_config_pda(program_id, fee_collector.key);
+    let (expected_trade_cfg, bump) = find_trade_config_pda(program_id, fee_collector.key);
     if expected_trade_cfg != *trade_config.key {
         msg!("trade config PDA mismatch");
         return Err(EscrowError::InvalidTradeConfigPda.into());
     }
 
-    let state = TradeConfigState::try_from_slice(&trade_config.try_borrow_data()?)
-        .map_err(|_| EscrowError::InvalidTradeConfigState)?;
-    if state.v != TradeConfigState::V1 || state.bump != bump {
+    let state = load_config(&trade_config.try_borrow_data()?, EscrowError::InvalidTradeConfigState)?;
+    if state.v != CONFIG_V1 || state.bump != bump {
         msg!("trade config state version/bump mismatch");
         return Err(EscrowError::InvalidTradeConfigState.into());
     }
@@ -467,8 +407,7 @@ fn process_withdraw_trade_fees(program_id: &Pubkey, accounts: &[AccountInfo], am
         return Err(EscrowError::InvalidTokenAccount.into());
     }
     let mint_pk = fee_vault_state.mint;
-    let expected_fee_vault =
-        spl_associated_token_account::get_associated_token_address(trade_config.key, &mint_pk);
+    let expected_fee_vault = fee_vault_ata_for(trade_config.key, &mint_pk);
     if expected_fee_vault != *fee_vault.key {
         msg!("fee vault ATA mismatch");
         return Err(EscrowError::InvalidTradeFeeVaultAta.into());
@@ -514,6 +453,12 @@ fn process_withdraw_trade_fees(program_id: &Pubkey, accounts: &[AccountInfo], am
         ],
         &[&[TRADE_CONFIG_SEED, fee_collector.key.as_ref(), &[bump]]],
     )?;
+    emit(&EscrowEvent::FeesWithdrawn {
+        source: FeeSource::Trade,
+        fee_collector: collector_pk.to_bytes(),
+        mint: mint_pk.to_bytes(),
+        amount: withdraw_amount,
+    });
 
     Ok(())
 }
@@ -548,7 +493,7 @@ fn process_init_config(
         return Err(EscrowError::InvalidSigner.into());
     }
 
-    let (expected_config, bump) = config_pda(program_id);
+    let (expected_config, bump) = find_config_pda(program_id);
     if expected_config != *config.key {
         msg!("config PDA mismatch");
         return Err(EscrowError::InvalidConfigPda.into());
@@ -560,7 +505,7 @@ fn process_init_config(
     }
 
     let rent = Rent::from_account_info(rent_sysvar)?;
-    let space = 1usize + 32 + 32 + 2 + 1; // ConfigState layout
+    let space = CONFIG_LEN;
     let lamports = rent.minimum_balance(space);
     invoke_signed(
         &system_instruction::create_account(payer.key, config.key, lamports, space as u64, program_id),
@@ -569,15 +514,13 @@ fn process_init_config(
     )?;
 
     let state = ConfigState {
-        v: ConfigState::V1,
+        v: CONFIG_V1,
         authority: payer.key.to_bytes(),
         fee_collector: fee_collector.to_bytes(),
         fee_bps,
         bump,
     };
-    state
-        .serialize(&mut &mut config.try_borrow_mut_data()?[..])
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    store_config(&state, &mut config.try_borrow_mut_data()?)?;
     Ok(())
 }
 
@@ -606,15 +549,14 @@ fn process_set_config(
         return Err(EscrowError::InvalidSigner.into());
     }
 
-    let (expected_config, bump) = config_pda(program_id);
+    let (expected_config, bump) = find_config_pda(program_id);
     if expected_config != *config.key {
         msg!("config PDA mismatch");
         return Err(EscrowError::InvalidConfigPda.into());
     }
 
-    let mut state =
-        ConfigState::try_from_slice(&config.try_borrow_data()?).map_err(|_| EscrowError::InvalidConfigState)?;
-    if state.v != ConfigState::V1 || state.bump != bump {
+    let mut state = load_config(&config.try_borrow_data()?, EscrowError::InvalidConfigState)?;
+    if state.v != CONFIG_V1 || state.bump != bump {
         msg!("config state version/bump mismatch");
         return Err(EscrowError::InvalidConfigState.into());
     }
@@ -625,9 +567,7 @@ fn process_set_config(
 
     state.fee_collector = fee_collector.to_bytes();
     state.fee_bps = fee_bps;
-    state
-        .serialize(&mut &mut config.try_borrow_mut_data()?[..])
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    store_config(&state, &mut config.try_borrow_mut_data()?)?;
     Ok(())
 }
 
@@ -649,15 +589,14 @@ fn process_withdraw_fees(program_id: &Pubkey, accounts: &[AccountInfo], amount:
     assert_writable(fee_vault)?;
     assert_writable(dest_token)?;
 
-    let (expected_config, bump) = config_pda(program_id);
+    let (expected_config, bump) = find_config_pda(program_id);
     if expected_config != *config.key {
         msg!("config PDA mismatch");
         return Err(EscrowError::InvalidConfigPda.into());
     }
 
-    let state =
-        ConfigState::try_from_slice(&config.try_borrow_data()?).map_err(|_| EscrowError::InvalidConfigState)?;
-    if state.v != ConfigState::V1 || state.bump != bump {
+    let state = load_config(&config.try_borrow_data()?, EscrowError::InvalidConfigState)?;
+    if state.v != CONFIG_V1 || state.bump != bump {
         msg!("config state version/bump mismatch");
         return Err(EscrowError::InvalidConfigState.into());
     }
@@ -681,8 +620,7 @@ fn process_withdraw_fees(program_id: &Pubkey, accounts: &[AccountInfo], amount:
         return Err(EscrowError::InvalidTokenAccount.into());
     }
     let mint_pk = fee_vault_state.mint;
-    let expected_fee_vault =
-        spl_associated_token_account::get_associated_token_address(config.key, &mint_pk);
+    let expected_fee_vault = fee_vault_ata_for(config.key, &mint_pk);
     if expected_fee_vault != *fee_vault.key {
         msg!("fee vault ATA mismatch");
         return Err(EscrowError::InvalidFeeVaultAta.into());
@@ -723,6 +661,12 @@ fn process_withdraw_fees(program_id: &Pubkey, accounts: &[AccountInfo], amount:
         &[fee_vault.clone(), dest_token.clone(), config.clone(), token_program.clone()],
         &[&[CONFIG_SEED, &[bump]]],
     )?;
+    emit(&EscrowEvent::FeesWithdrawn {
+        source: FeeSource::Platform,
+        fee_collector: collector_pk.to_bytes(),
+        mint: mint_pk.to_bytes(),
+        amount: withdraw_amount,
+    });
 
     Ok(())
 }
@@ -733,7 +677,7 @@ fn process_init(
     payment_hash: [u8; 32],
     recipient: Pubkey,
     refund: Pubkey,
-    refund_after: i64,
+    refund_after: RefundAfter,
     amount: u64,
     expected_platform_fee_bps: u16,
     expected_trade_fee_bps: u16,
@@ -753,6 +697,8 @@ fn process_init(
     // 10 [writable] platform fee vault ATA (ATA(owner=config PDA, mint))
     // 11 [] trade config PDA (seeded by trade_fee_collector)
     // 12 [writable] trade fee vault ATA (ATA(owner=trade config PDA, mint))
+    // 13 [writable] recipient index PDA (seeded by recipient)
+    // 14 [writable] refund index PDA (seeded by refund)
     let acc_iter = &mut accounts.iter();
     let payer = next_account_info(acc_iter)?;
     let payer_token = next_account_info(acc_iter)?;
@@ -767,6 +713,8 @@ fn process_init(
     let platform_fee_vault = next_account_info(acc_iter)?;
     let trade_config = next_account_info(acc_iter)?;
     let trade_fee_vault = next_account_info(acc_iter)?;
+    let recipient_index = next_account_info(acc_iter)?;
+    let refund_index = next_account_info(acc_iter)?;
 
     assert_signer(payer)?;
     assert_writable(payer)?;
@@ -774,13 +722,27 @@ fn process_init(
     assert_writable(escrow)?;
     assert_writable(vault)?;
 
-    let (expected_escrow, bump) = pda_for_hash(program_id, &payment_hash);
+    let (expected_escrow, bump) = find_escrow_pda(program_id, &payment_hash);
     if expected_escrow != *escrow.key {
         msg!("escrow PDA mismatch");
         return Err(EscrowError::InvalidEscrowPda.into());
     }
 
-    let (expected_config, config_bump) = config_pda(program_id);
+    let refund_after = match refund_after {
+        RefundAfter::At(ts) => ts,
+        RefundAfter::Delay(secs) => {
+            if secs <= 0 {
+                msg!("refund delay must be positive");
+                return Err(EscrowError::InvalidInstruction.into());
+            }
+            Clock::get()?
+                .unix_timestamp
+                .checked_add(secs)
+                .ok_or(EscrowError::InvalidInstruction)?
+        }
+    };
+
+    let (expected_config, config_bump) = find_config_pda(program_id);
     if expected_config != *config.key {
         msg!("config PDA mismatch");
         return Err(EscrowError::InvalidConfigPda.into());
@@ -789,9 +751,8 @@ fn process_init(
         msg!("config not initialized");
         return Err(EscrowError::InvalidConfigState.into());
     }
-    let config_state =
-        ConfigState::try_from_slice(&config.try_borrow_data()?).map_err(|_| EscrowError::InvalidConfigState)?;
-    if config_state.v != ConfigState::V1 || config_state.bump != config_bump {
+    let config_state = load_config(&config.try_borrow_data()?, EscrowError::InvalidConfigState)?;
+    if config_state.v != CONFIG_V1 || config_state.bump != config_bump {
         msg!("config state version/bump mismatch");
         return Err(EscrowError::InvalidConfigState.into());
     }
@@ -805,14 +766,14 @@ fn process_init(
         return Err(EscrowError::FeeMismatch.into());
     }
 
-    let expected_vault = spl_associated_token_account::get_associated_token_address(escrow.key, mint.key);
+    let expected_vault = vault_ata(escrow.key, mint.key);
     if expected_vault != *vault.key {
         msg!("vault ATA mismatch");
         return Err(EscrowError::InvalidVaultAta.into());
     }
 
     // Validate trade config PDA + state.
-    let (expected_trade_cfg, trade_cfg_bump) = trade_config_pda(program_id, &trade_fee_collector);
+    let (expected_trade_cfg, trade_cfg_bump) = find_trade_config_pda(program_id, &trade_fee_collector);
     if expected_trade_cfg != *trade_config.key {
         msg!("trade config PDA mismatch");
         return Err(EscrowError::InvalidTradeConfigPda.into());
@@ -821,9 +782,8 @@ fn process_init(
         msg!("trade config not initialized");
         return Err(EscrowError::InvalidTradeConfigState.into());
     }
-    let trade_cfg_state = TradeConfigState::try_from_slice(&trade_config.try_borrow_data()?)
-        .map_err(|_| EscrowError::InvalidTradeConfigState)?;
-    if trade_cfg_state.v != TradeConfigState::V1 || trade_cfg_state.bump != trade_cfg_bump {
+    let trade_cfg_state = load_config(&trade_config.try_borrow_data()?, EscrowError::InvalidTradeConfigState)?;
+    if trade_cfg_state.v != CONFIG_V1 || trade_cfg_state.bump != trade_cfg_bump {
         msg!("trade config state version/bump mismatch");
         return Err(EscrowError::InvalidTradeConfigState.into());
     }
@@ -852,8 +812,7 @@ fn process_init(
 
     // Ensure platform fee vault ATA exists (ATA(owner=config PDA, mint)).
     assert_writable(platform_fee_vault)?;
-    let expected_fee_vault =
-        spl_associated_token_account::get_associated_token_address(config.key, mint.key);
+    let expected_fee_vault = fee_vault_ata_for(config.key, mint.key);
     if expected_fee_vault != *platform_fee_vault.key {
         msg!("platform fee vault ATA mismatch");
         return Err(EscrowError::InvalidFeeVaultAta.into());
@@ -882,8 +841,7 @@ fn process_init(
 
     // Ensure trade fee vault ATA exists (ATA(owner=trade config PDA, mint)).
     assert_writable(trade_fee_vault)?;
-    let expected_trade_fee_vault =
-        spl_associated_token_account::get_associated_token_address(trade_config.key, mint.key);
+    let expected_trade_fee_vault = fee_vault_ata_for(trade_config.key, mint.key);
     if expected_trade_fee_vault != *trade_fee_vault.key {
         msg!("trade fee vault ATA mismatch");
         return Err(EscrowError::InvalidTradeFeeVaultAta.into());
@@ -922,26 +880,12 @@ fn process_init(
         return Err(EscrowError::InvalidTokenAccount.into());
     }
 
-    let platform_fee_amount_u128 = (amount as u128)
-        .checked_mul(config_state.fee_bps as u128)
-        .ok_or(EscrowError::InvalidInstruction)?
-        / 10_000u128;
-    let platform_fee_amount: u64 = platform_fee_amount_u128
-        .try_into()
-        .map_err(|_| EscrowError::InvalidInstruction)?;
-
-    let trade_fee_amount_u128 = (amount as u128)
-        .checked_mul(trade_cfg_state.fee_bps as u128)
-        .ok_or(EscrowError::InvalidInstruction)?
-        / 10_000u128;
-    let trade_fee_amount: u64 = trade_fee_amount_u128
-        .try_into()
-        .map_err(|_| EscrowError::InvalidInstruction)?;
-
-    let total_amount: u64 = amount
-        .checked_add(platform_fee_amount)
-        .ok_or(EscrowError::InvalidInstruction)?
-        .checked_add(trade_fee_amount)
+    let EscrowAmounts {
+        platform_fee_amount,
+        trade_fee_amount,
+        total_amount,
+        ..
+    } = EscrowAmounts::compute(amount, config_state.fee_bps, trade_cfg_state.fee_bps)
         .ok_or(EscrowError::InvalidInstruction)?;
 
     if payer_token_state.amount < total_amount {
@@ -956,22 +900,7 @@ fn process_init(
     }
     {
         let rent = Rent::from_account_info(rent_sysvar)?;
-        let space = 1usize
-            + 1usize
-            + 32
-            + 32
-            + 32
-            + 8
-            + 32
-            + 8
-            + 8
-            + 2
-            + 32
-            + 8
-            + 2
-            + 32
-            + 32
-            + 1; // EscrowState layout (v3)
+        let space = ESCROW_V4_LEN;
         let lamports = rent.minimum_balance(space);
         invoke_signed(
             &system_instruction::create_account(payer.key, escrow.key, lamports, space as u64, program_id),
@@ -1016,8 +945,8 @@ fn process_init(
 
     // Persist state.
     let state = EscrowState {
-        v: EscrowState::V3,
-        status: EscrowState::STATUS_ACTIVE,
+        v: ESCROW_V4,
+        status: EscrowStatus::Active,
         payment_hash,
         recipient: recipient.to_bytes(),
         refund: refund.to_bytes(),
@@ -1032,10 +961,48 @@ fn process_init(
         trade_fee_collector: trade_fee_collector.to_bytes(),
         vault: vault.key.to_bytes(),
         bump,
+        creator: Some(payer.key.to_bytes()),
     };
-    state
-        .serialize(&mut &mut escrow.try_borrow_mut_data()?[..])
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    store_escrow(&state, &mut escrow.try_borrow_mut_data()?)?;
+    emit(&EscrowEvent::EscrowInitialized {
+        payment_hash,
+        recipient: state.recipient,
+        refund: state.refund,
+        mint: state.mint,
+        trade_fee_collector: state.trade_fee_collector,
+        refund_after,
+        net_amount: amount,
+        platform_fee_amount,
+        trade_fee_amount,
+    });
+
+    // Record the escrow in the per-recipient and per-refund indexes (lookups without getProgramAccounts). Anyone
+    // can name any recipient or refund key, so these are best-effort hints, not a record of a wallet's escrows.
+    let rent = Rent::from_account_info(rent_sysvar)?;
+    record_in_index(
+        program_id,
+        payer,
+        system_program,
+        &rent,
+        IndexTarget {
+            index: recipient_index,
+            seed: RECIPIENT_INDEX_SEED,
+            owner: &recipient,
+        },
+        escrow.key,
+    )?;
+    record_in_index(
+        program_id,
+        payer,
+        system_program,
+        &rent,
+        IndexTarget {
+            index: refund_index,
+            seed: REFUND_INDEX_SEED,
+            owner: &refund,
+        },
+        escrow.key,
+    )?;
     Ok(())
 }
 
@@ -1048,6 +1015,7 @@ fn process_claim(program_id: &Pubkey, accounts: &[AccountInfo], preimage: [u8; 3
     // 4 [writable] platform fee vault ATA (ATA(owner=config PDA, mint))
     // 5 [writable] trade fee vault ATA (ATA(owner=trade config PDA, mint))
     // 6 [] token program
+    // 7 [writable] escrow creator (v4+ only; receives the vault ATA rent when it is closed)
     let acc_iter = &mut accounts.iter();
     let recipient = next_account_info(acc_iter)?;
     let escrow = next_account_info(acc_iter)?;
@@ -1064,8 +1032,7 @@ fn process_claim(program_id: &Pubkey, accounts: &[AccountInfo], preimage: [u8; 3
     assert_writable(platform_fee_vault)?;
     assert_writable(trade_fee_vault)?;
 
-    let mut state = EscrowState::try_from_slice(&escrow.try_borrow_data()?)
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    let mut state = load_escrow(&escrow.try_borrow_data()?)?;
     require_active(&state)?;
 
     let recipient_pk = Pubkey::new_from_array(state.recipient);
@@ -1100,7 +1067,7 @@ fn process_claim(program_id: &Pubkey, accounts: &[AccountInfo], preimage: [u8; 3
         return Err(EscrowError::InvalidTokenAccount.into());
     }
 
-    let (expected_escrow, bump) = pda_for_hash(program_id, &state.payment_hash);
+    let (expected_escrow, bump) = find_escrow_pda(program_id, &state.payment_hash);
     if expected_escrow != *escrow.key || bump != state.bump {
         msg!("escrow PDA mismatch");
         return Err(EscrowError::InvalidEscrowPda.into());
@@ -1111,9 +1078,8 @@ fn process_claim(program_id: &Pubkey, accounts: &[AccountInfo], preimage: [u8; 3
     }
 
     // Validate platform fee vault ATA (ATA(owner=config PDA, mint)).
-    let (cfg_pda, _cfg_bump) = config_pda(program_id);
-    let expected_fee_vault =
-        spl_associated_token_account::get_associated_token_address(&cfg_pda, &mint_pk);
+    let (cfg_pda, _cfg_bump) = find_config_pda(program_id);
+    let expected_fee_vault = fee_vault_ata_for(&cfg_pda, &mint_pk);
     if expected_fee_vault != *platform_fee_vault.key {
         msg!("platform fee vault ATA mismatch");
         return Err(EscrowError::InvalidFeeVaultAta.into());
@@ -1131,9 +1097,8 @@ fn process_claim(program_id: &Pubkey, accounts: &[AccountInfo], preimage: [u8; 3
 
     // Validate trade fee vault ATA (ATA(owner=trade config PDA, mint)).
     let trade_collector_pk = Pubkey::new_from_array(state.trade_fee_collector);
-    let (trade_cfg_pda, _trade_cfg_bump) = trade_config_pda(program_id, &trade_collector_pk);
-    let expected_trade_fee_vault =
-        spl_associated_token_account::get_associated_token_address(&trade_cfg_pda, &mint_pk);
+    let (trade_cfg_pda, _trade_cfg_bump) = find_trade_config_pda(program_id, &trade_collector_pk);
+    let expected_trade_fee_vault = fee_vault_ata_for(&trade_cfg_pda, &mint_pk);
     if expected_trade_fee_vault != *trade_fee_vault.key {
         msg!("trade fee vault ATA mismatch");
         return Err(EscrowError::InvalidTradeFeeVaultAta.into());
@@ -1149,6 +1114,8 @@ fn process_claim(program_id: &Pubkey, accounts: &[AccountInfo], preimage: [u8; 3
         return Err(EscrowError::InvalidTokenAccount.into());
     }
 
+    let creator = next_creator_account(acc_iter, &state)?;
+
     // Transfer net amount to recipient, then fees to their respective fee vaults.
     let net_amount = state.net_amount;
     let platform_fee_amount = state.platform_fee_amount;
@@ -1205,13 +1172,28 @@ fn process_claim(program_id: &Pubkey, accounts: &[AccountInfo], preimage: [u8; 3
         )?;
     }
 
-    state.status = EscrowState::STATUS_CLAIMED;
+    let total_amount = net_amount
+        .checked_add(platform_fee_amount)
+        .ok_or(EscrowError::InvalidInstruction)?
+        .checked_add(trade_fee_amount)
+        .ok_or(EscrowError::InvalidInstruction)?;
+    if let Some(creator) = creator {
+        close_vault_if_drained(token_program, vault, escrow, creator, vault_state.amount, total_amount, seeds)?;
+    }
+
+    state.status = EscrowStatus::Claimed;
     state.net_amount = 0;
     state.platform_fee_amount = 0;
     state.trade_fee_amount = 0;
-    state
-        .serialize(&mut &mut escrow.try_borrow_mut_data()?[..])
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    store_escrow(&state, &mut escrow.try_borrow_mut_data()?)?;
+    emit(&EscrowEvent::Claimed {
+        payment_hash: state.payment_hash,
+        preimage,
+        recipient: state.recipient,
+        net_amount,
+        platform_fee_amount,
+        trade_fee_amount,
+    });
     Ok(())
 }
 
@@ -1223,6 +1205,7 @@ fn process_refund(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResul
     // 3 [writable] refund token account
     // 4 [] token program
     // 5 [] clock sysvar
+    // 6 [writable] escrow creator (v4+ only; receives the vault ATA rent when it is closed)
     let acc_iter = &mut accounts.iter();
     let refund = next_account_info(acc_iter)?;
     let escrow = next_account_info(acc_iter)?;
@@ -1236,8 +1219,7 @@ fn process_refund(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResul
     assert_writable(vault)?;
     assert_writable(refund_token)?;
 
-    let mut state = EscrowState::try_from_slice(&escrow.try_borrow_data()?)
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    let mut state = load_escrow(&escrow.try_borrow_data()?)?;
     require_active(&state)?;
 
     let refund_pk = Pubkey::new_from_array(state.refund);
@@ -1271,7 +1253,7 @@ fn process_refund(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResul
         return Err(EscrowError::InvalidTokenAccount.into());
     }
 
-    let (expected_escrow, bump) = pda_for_hash(program_id, &state.payment_hash);
+    let (expected_escrow, bump) = find_escrow_pda(program_id, &state.payment_hash);
     if expected_escrow != *escrow.key || bump != state.bump {
         msg!("escrow PDA mismatch");
         return Err(EscrowError::InvalidEscrowPda.into());
@@ -1281,12 +1263,16 @@ fn process_refund(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResul
         return Err(EscrowError::InvalidTokenAccount.into());
     }
 
+    let creator = next_creator_account(acc_iter, &state)?;
+
     let total_amount = state
         .net_amount
         .checked_add(state.platform_fee_amount)
         .ok_or(EscrowError::InvalidInstruction)?
         .checked_add(state.trade_fee_amount)
         .ok_or(EscrowError::InvalidInstruction)?;
+    let bump_seed = [state.bump];
+    let seeds: &[&[u8]] = &[ESCROW_SEED, &state.payment_hash, &bump_seed];
     let transfer_ix = spl_token::instruction::transfer(
         token_program.key,
         vault.key,
@@ -1298,15 +1284,21 @@ fn process_refund(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResul
     invoke_signed(
         &transfer_ix,
         &[vault.clone(), refund_token.clone(), escrow.clone(), token_program.clone()],
-        &[&[ESCROW_SEED, &state.payment_hash, &[state.bump]]],
+        &[seeds],
     )?;
+    if let Some(creator) = creator {
+        close_vault_if_drained(token_program, vault, escrow, creator, vault_state.amount, total_amount, seeds)?;
+    }
 
-    state.status = EscrowState::STATUS_REFUNDED;
+    state.status = EscrowStatus::Refunded;
     state.net_amount = 0;
     state.platform_fee_amount = 0;
     state.trade_fee_amount = 0;
-    state
-        .serialize(&mut &mut escrow.try_borrow_mut_data()?[..])
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    store_escrow(&state, &mut escrow.try_borrow_mut_data()?)?;
+    emit(&EscrowEvent::Refunded {
+        payment_hash: state.payment_hash,
+        refund: state.refund,
+        amount: total_amount,
+    });
     Ok(())
 }
@@ -0,0 +1,72 @@
+//! PDA and ATA derivations shared by the program and off-chain clients.
+//!
+//! The `find_*` functions take an explicit program id (the program itself uses the id it was invoked with);
+//! the short forms derive against the production [`crate::ID`].
+
+use solana_program::pubkey::Pubkey;
+
+pub const ESCROW_SEED: &[u8] = b"escrow";
+pub const CONFIG_SEED: &[u8] = b"config";
+pub const TRADE_CONFIG_SEED: &[u8] = b"trade_config";
+pub const RECIPIENT_INDEX_SEED: &[u8] = b"recipient_index";
+pub const REFUND_INDEX_SEED: &[u8] = b"refund_index";
+
+pub fn find_escrow_pda(program_id: &Pubkey, payment_hash: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ESCROW_SEED, payment_hash], program_id)
+}
+
+pub fn find_config_pda(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+pub fn find_trade_config_pda(program_id: &Pubkey, fee_collector: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TRADE_CONFIG_SEED, fee_collector.as_ref()], program_id)
+}
+
+// `seed` is RECIPIENT_INDEX_SEED or REFUND_INDEX_SEED.
+pub fn find_index_pda(program_id: &Pubkey, seed: &[u8], owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[seed, owner.as_ref()], program_id)
+}
+
+/// Escrow PDA for `payment_hash`.
+pub fn escrow_pda(payment_hash: &[u8; 32]) -> Pubkey {
+    find_escrow_pda(&crate::ID, payment_hash).0
+}
+
+/// Platform config PDA.
+pub fn config_pda() -> Pubkey {
+    find_config_pda(&crate::ID).0
+}
+
+/// Trade config PDA owned by `fee_collector`.
+pub fn trade_config_pda(fee_collector: &Pubkey) -> Pubkey {
+    find_trade_config_pda(&crate::ID, fee_collector).0
+}
+
+pub fn recipient_index_pda(recipient: &Pubkey) -> Pubkey {
+    find_index_pda(&crate::ID, RECIPIENT_INDEX_SEED, recipient).0
+}
+
+pub fn refund_index_pda(refund: &Pubkey) -> Pubkey {
+    find_index_pda(&crate::ID, REFUND_INDEX_SEED, refund).0
+}
+
+/// Token vault ATA holding the escrowed funds of `escrow`.
+pub fn vault_ata(escrow: &Pubkey, mint: &Pubkey) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address(escrow, mint)
+}
+
+/// Platform fee vault ATA (owned by the config PDA) for `mint`.
+pub fn fee_vault_ata(mint: &Pubkey) -> Pubkey {
+    fee_vault_ata_for(&config_pda(), mint)
+}
+
+/// Trade fee vault ATA (owned by `fee_collector`'s trade config PDA) for `mint`.
+pub fn trade_fee_vault_ata(fee_collector: &Pubkey, mint: &Pubkey) -> Pubkey {
+    fee_vault_ata_for(&trade_config_pda(fee_collector), mint)
+}
+
+/// Fee vault ATA owned by an already-derived config or trade config PDA.
+pub fn fee_vault_ata_for(config: &Pubkey, mint: &Pubkey) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address(config, mint)
+}
@@ -0,0 +1,267 @@
+//! A bank running the program natively, with a mint and fee-free platform and trade configs (both collected by
+//! the bank's payer) whose fee vaults already exist.
+
+// Each test file uses part of this.
+#![allow(dead_code)]
+
+use intercom_swap_core::{
+    instruction::MAX_INSTRUCTION_LEN, EscrowError, EscrowIndexState, EscrowInstruction, EscrowState, RefundAfter,
+};
+use ln_usdt_escrow::pda::{
+    config_pda, escrow_pda, fee_vault_ata, recipient_index_pda, refund_index_pda, trade_config_pda,
+    trade_fee_vault_ata, vault_ata,
+};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    hash::hash,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction, system_program, sysvar,
+    transaction::{Transaction, TransactionError},
+};
+use spl_associated_token_account::{get_associated_token_address, instruction::create_associated_token_account};
+
+/// Lamports each [`Harness::wallet`] starts with.
+pub const WALLET_LAMPORTS: u64 = 1_000_000_000;
+
+pub struct Harness {
+    pub ctx: ProgramTestContext,
+    pub mint: Pubkey,
+    /// Platform and trade fee collector: the bank's payer.
+    pub collector: Pubkey,
+}
+
+impl Harness {
+    pub async fn start() -> Self {
+        let program = ProgramTest::new(
+            "ln_usdt_escrow",
+            ln_usdt_escrow::ID,
+            processor!(ln_usdt_escrow::process_instruction),
+        );
+        let ctx = program.start_with_context().await;
+        let collector = ctx.payer.pubkey();
+        let mut h = Self {
+            ctx,
+            mint: Pubkey::default(),
+            collector,
+        };
+
+        let mint = Keypair::new();
+        let rent = h.ctx.banks_client.get_rent().await.unwrap();
+        let len = spl_token::state::Mint::LEN;
+        h.send(
+            &[
+                system_instruction::create_account(
+                    &collector,
+                    &mint.pubkey(),
+                    rent.minimum_balance(len),
+                    len as u64,
+                    &spl_token::ID,
+                ),
+                spl_token::instruction::initialize_mint(&spl_token::ID, &mint.pubkey(), &collector, None, 6).unwrap(),
+            ],
+            &[&mint],
+        )
+        .await
+        .unwrap();
+        h.mint = mint.pubkey();
+
+        let init_config = EscrowInstruction::InitConfig {
+            fee_collector: collector.to_bytes(),
+            fee_bps: 0,
+        };
+        let init_trade_config = EscrowInstruction::InitTradeConfig {
+            fee_collector: collector.to_bytes(),
+            fee_bps: 0,
+        };
+        let config_accounts = |config: Pubkey| {
+            vec![
+                AccountMeta::new(collector, true),
+                AccountMeta::new(config, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(sysvar::rent::ID, false),
+            ]
+        };
+        h.send(
+            &[
+                instruction(&init_config, config_accounts(config_pda())),
+                instruction(&init_trade_config, config_accounts(trade_config_pda(&collector))),
+                create_associated_token_account(&collector, &config_pda(), &h.mint, &spl_token::ID),
+                create_associated_token_account(&collector, &trade_config_pda(&collector), &h.mint, &spl_token::ID),
+            ],
+            &[],
+        )
+        .await
+        .unwrap();
+        h
+    }
+
+    /// Sends `instructions` paid for by the bank's payer and signed by `signers` besides, on a fresh blockhash so
+    /// that repeating a transaction is not mistaken for a duplicate.
+    pub async fn send(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<(), BanksClientError> {
+        let blockhash = self.ctx.get_new_latest_blockhash().await?;
+        let mut all = vec![&self.ctx.payer];
+        all.extend_from_slice(signers);
+        let tx = Transaction::new_signed_with_payer(instructions, Some(&self.ctx.payer.pubkey()), &all, blockhash);
+        self.ctx.banks_client.process_transaction(tx).await
+    }
+
+    /// A new wallet holding [`WALLET_LAMPORTS`], and `tokens` in its token account for the mint.
+    pub async fn wallet(&mut self, tokens: u64) -> Keypair {
+        let wallet = Keypair::new();
+        let token_account = self.token_account(&wallet.pubkey());
+        let mut instructions = vec![
+            system_instruction::transfer(&self.collector, &wallet.pubkey(), WALLET_LAMPORTS),
+            create_associated_token_account(&self.collector, &wallet.pubkey(), &self.mint, &spl_token::ID),
+        ];
+        if tokens > 0 {
+            instructions.push(self.mint_to(&token_account, tokens));
+        }
+        self.send(&instructions, &[]).await.unwrap();
+        wallet
+    }
+
+    /// The token account for the mint that `owner` holds it in.
+    pub fn token_account(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address(owner, &self.mint)
+    }
+
+    pub fn mint_to(&self, token_account: &Pubkey, tokens: u64) -> Instruction {
+        spl_token::instruction::mint_to(&spl_token::ID, &self.mint, token_account, &self.collector, &[], tokens)
+            .unwrap()
+    }
+
+    pub async fn account(&mut self, key: &Pubkey) -> Option<Account> {
+        self.ctx.banks_client.get_account(*key).await.unwrap()
+    }
+
+    pub async fn lamports(&mut self, key: &Pubkey) -> u64 {
+        self.account(key).await.map_or(0, |a| a.lamports)
+    }
+
+    pub async fn tokens(&mut self, owner: &Pubkey) -> u64 {
+        let account = self.account(&self.token_account(owner)).await.expect("token account");
+        spl_token::state::Account::unpack(&account.data).unwrap().amount
+    }
+
+    pub async fn escrow(&mut self, payment_hash: &[u8; 32]) -> EscrowState {
+        let account = self.account(&escrow_pda(payment_hash)).await.expect("escrow account");
+        EscrowState::unpack(&account.data).unwrap()
+    }
+
+    pub async fn index(&mut self, index: &Pubkey) -> EscrowIndexState {
+        let account = self.account(index).await.expect("index account");
+        EscrowIndexState::unpack(&account.data).unwrap()
+    }
+
+    pub async fn clock(&mut self) -> Clock {
+        self.ctx.banks_client.get_sysvar::<Clock>().await.unwrap()
+    }
+
+    /// Moves the bank's clock to `unix_timestamp`.
+    pub async fn set_time(&mut self, unix_timestamp: i64) {
+        let clock = Clock {
+            unix_timestamp,
+            ..self.clock().await
+        };
+        self.ctx.set_sysvar(&clock);
+    }
+
+    /// Init of an escrow of `amount` funded by `creator`, with the fee vaults and indexes it derives.
+    pub fn init_ix(
+        &self,
+        creator: &Pubkey,
+        payment_hash: &[u8; 32],
+        recipient: &Pubkey,
+        refund: &Pubkey,
+        refund_after: RefundAfter,
+        amount: u64,
+    ) -> Instruction {
+        let escrow = escrow_pda(payment_hash);
+        let init = EscrowInstruction::Init {
+            payment_hash: *payment_hash,
+            recipient: recipient.to_bytes(),
+            refund: refund.to_bytes(),
+            refund_after,
+            amount,
+            expected_platform_fee_bps: 0,
+            expected_trade_fee_bps: 0,
+            trade_fee_collector: self.collector.to_bytes(),
+        };
+        instruction(
+            &init,
+            vec![
+                AccountMeta::new(*creator, true),
+                AccountMeta::new(self.token_account(creator), false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new(vault_ata(&escrow, &self.mint), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(spl_token::ID, false),
+                AccountMeta::new_readonly(spl_associated_token_account::ID, false),
+                AccountMeta::new_readonly(sysvar::rent::ID, false),
+                AccountMeta::new_readonly(config_pda(), false),
+                AccountMeta::new(fee_vault_ata(&self.mint), false),
+                AccountMeta::new_readonly(trade_config_pda(&self.collector), false),
+                AccountMeta::new(trade_fee_vault_ata(&self.collector, &self.mint), false),
+                AccountMeta::new(recipient_index_pda(recipient), false),
+                AccountMeta::new(refund_index_pda(refund), false),
+            ],
+        )
+    }
+
+    /// Claim by `recipient`; `creator` is the trailing account v4 escrows take.
+    pub fn claim_ix(&self, recipient: &Pubkey, preimage: &[u8; 32], creator: Option<&Pubkey>) -> Instruction {
+        let escrow = escrow_pda(&hash(preimage).to_bytes());
+        let mut accounts = vec![
+            AccountMeta::new_readonly(*recipient, true),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new(vault_ata(&escrow, &self.mint), false),
+            AccountMeta::new(self.token_account(recipient), false),
+            AccountMeta::new(fee_vault_ata(&self.mint), false),
+            AccountMeta::new(trade_fee_vault_ata(&self.collector, &self.mint), false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+        ];
+        accounts.extend(creator.map(|c| AccountMeta::new(*c, false)));
+        instruction(&EscrowInstruction::Claim { preimage: *preimage }, accounts)
+    }
+
+    /// Refund by `refund`; `creator` is the trailing account v4 escrows take.
+    pub fn refund_ix(&self, refund: &Pubkey, payment_hash: &[u8; 32], creator: Option<&Pubkey>) -> Instruction {
+        let escrow = escrow_pda(payment_hash);
+        let mut accounts = vec![
+            AccountMeta::new_readonly(*refund, true),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new(vault_ata(&escrow, &self.mint), false),
+            AccountMeta::new(self.token_account(refund), false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(sysvar::clock::ID, false),
+        ];
+        accounts.extend(creator.map(|c| AccountMeta::new(*c, false)));
+        instruction(&EscrowInstruction::Refund, accounts)
+    }
+}
+
+pub fn instruction(ix: &EscrowInstruction, accounts: Vec<AccountMeta>) -> Instruction {
+    let mut data = [0u8; MAX_INSTRUCTION_LEN];
+    let len = ix.pack_into(&mut data);
+    Instruction::new_with_bytes(ln_usdt_escrow::ID, &data[..len], accounts)
+}
+
+/// Preimage `[n; 32]` and its payment hash.
+pub fn payment(n: u8) -> ([u8; 32], [u8; 32]) {
+    let preimage = [n; 32];
+    (preimage, hash(&preimage).to_bytes())
+}
+
+/// The program error a transaction failed with, if it failed with one.
+pub fn escrow_error(result: Result<(), BanksClientError>) -> Option<EscrowError> {
+    match result.err()?.unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => EscrowError::from_code(code),
+        _ => None,
+    }
+}
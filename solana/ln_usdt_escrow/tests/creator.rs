@@ -0,0 +1,171 @@
+//! Escrow layouts v3 and v4, and the vault rent v4 escrows return to the creator stored at Init.
+
+mod common;
+
+use common::{escrow_error, payment, Harness};
+use intercom_swap_core::{
+    state::{ESCROW_V3, ESCROW_V3_LEN, ESCROW_V4, ESCROW_V4_LEN},
+    EscrowError, EscrowState, EscrowStatus, RefundAfter,
+};
+use ln_usdt_escrow::pda::{escrow_pda, find_escrow_pda, vault_ata};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+};
+use spl_associated_token_account::instruction::create_associated_token_account;
+
+const AMOUNT: u64 = 1_000;
+
+/// An escrow of `AMOUNT` funded by a `creator` that is neither its `recipient` nor its `refund` key, refundable
+/// at once, for `preimage`.
+struct Funded {
+    creator: Keypair,
+    recipient: Keypair,
+    refund: Keypair,
+    preimage: [u8; 32],
+    payment_hash: [u8; 32],
+}
+
+async fn funded(h: &mut Harness) -> Funded {
+    let creator = h.wallet(AMOUNT).await;
+    let recipient = h.wallet(0).await;
+    let refund = h.wallet(0).await;
+    let (preimage, payment_hash) = payment(1);
+    let init = h.init_ix(
+        &creator.pubkey(),
+        &payment_hash,
+        &recipient.pubkey(),
+        &refund.pubkey(),
+        RefundAfter::At(0),
+        AMOUNT,
+    );
+    h.send(&[init], &[&creator]).await.unwrap();
+    Funded {
+        creator,
+        recipient,
+        refund,
+        preimage,
+        payment_hash,
+    }
+}
+
+#[tokio::test]
+async fn v4_escrow_stores_its_creator() {
+    let mut h = Harness::start().await;
+    let f = funded(&mut h).await;
+    let escrow = h.account(&escrow_pda(&f.payment_hash)).await.expect("escrow account");
+    assert_eq!(escrow.data.len(), ESCROW_V4_LEN);
+    let state = h.escrow(&f.payment_hash).await;
+    assert_eq!(state.v, ESCROW_V4);
+    assert_eq!(state.creator, Some(f.creator.pubkey().to_bytes()));
+}
+
+#[tokio::test]
+async fn v3_escrow_is_still_claimed_without_a_creator() {
+    let mut h = Harness::start().await;
+    let recipient = h.wallet(0).await;
+    let refund = h.wallet(0).await;
+    let (preimage, payment_hash) = payment(1);
+    let (escrow, bump) = find_escrow_pda(&ln_usdt_escrow::ID, &payment_hash);
+    let vault = vault_ata(&escrow, &h.mint);
+    let fund_vault = [
+        create_associated_token_account(&h.collector, &escrow, &h.mint, &spl_token::ID),
+        h.mint_to(&vault, AMOUNT),
+    ];
+    h.send(&fund_vault, &[]).await.unwrap();
+    let state = EscrowState {
+        v: ESCROW_V3,
+        status: EscrowStatus::Active,
+        payment_hash,
+        recipient: recipient.pubkey().to_bytes(),
+        refund: refund.pubkey().to_bytes(),
+        refund_after: 0,
+        mint: h.mint.to_bytes(),
+        net_amount: AMOUNT,
+        platform_fee_amount: 0,
+        platform_fee_bps: 0,
+        platform_fee_collector: h.collector.to_bytes(),
+        trade_fee_amount: 0,
+        trade_fee_bps: 0,
+        trade_fee_collector: h.collector.to_bytes(),
+        vault: vault.to_bytes(),
+        bump,
+        creator: None,
+    };
+    let mut data = vec![0; ESCROW_V3_LEN];
+    state.pack_into(&mut data).unwrap();
+    let rent = h.ctx.banks_client.get_rent().await.unwrap();
+    let account = Account {
+        lamports: rent.minimum_balance(ESCROW_V3_LEN),
+        data,
+        owner: ln_usdt_escrow::ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    h.ctx.set_account(&escrow, &account.into());
+
+    let claim = h.claim_ix(&recipient.pubkey(), &preimage, None);
+    h.send(&[claim], &[&recipient]).await.unwrap();
+    let account = h.account(&escrow).await.expect("escrow account");
+    assert_eq!(account.data.len(), ESCROW_V3_LEN);
+    let state = h.escrow(&payment_hash).await;
+    assert_eq!(state.v, ESCROW_V3);
+    assert_eq!(state.status, EscrowStatus::Claimed);
+    assert_eq!(state.creator, None);
+    assert_eq!(h.tokens(&recipient.pubkey()).await, AMOUNT);
+    // Nobody is on record to return its rent to.
+    assert!(h.account(&vault).await.is_some());
+}
+
+#[tokio::test]
+async fn claim_returns_the_vault_rent_to_the_creator() {
+    let mut h = Harness::start().await;
+    let f = funded(&mut h).await;
+    let vault = vault_ata(&escrow_pda(&f.payment_hash), &h.mint);
+    let vault_rent = h.lamports(&vault).await;
+    let creator_before = h.lamports(&f.creator.pubkey()).await;
+    let recipient_before = h.lamports(&f.recipient.pubkey()).await;
+
+    let claim = h.claim_ix(&f.recipient.pubkey(), &f.preimage, Some(&f.creator.pubkey()));
+    h.send(&[claim], &[&f.recipient]).await.unwrap();
+    assert_eq!(h.escrow(&f.payment_hash).await.status, EscrowStatus::Claimed);
+    assert_eq!(h.tokens(&f.recipient.pubkey()).await, AMOUNT);
+    assert!(h.account(&vault).await.is_none());
+    assert_eq!(h.lamports(&f.creator.pubkey()).await, creator_before + vault_rent);
+    assert_eq!(h.lamports(&f.recipient.pubkey()).await, recipient_before);
+}
+
+#[tokio::test]
+async fn refund_returns_the_vault_rent_to_the_creator() {
+    let mut h = Harness::start().await;
+    let f = funded(&mut h).await;
+    let vault = vault_ata(&escrow_pda(&f.payment_hash), &h.mint);
+    let vault_rent = h.lamports(&vault).await;
+    let creator_before = h.lamports(&f.creator.pubkey()).await;
+    let refund_before = h.lamports(&f.refund.pubkey()).await;
+
+    let refund = h.refund_ix(&f.refund.pubkey(), &f.payment_hash, Some(&f.creator.pubkey()));
+    h.send(&[refund], &[&f.refund]).await.unwrap();
+    assert_eq!(h.escrow(&f.payment_hash).await.status, EscrowStatus::Refunded);
+    assert_eq!(h.tokens(&f.refund.pubkey()).await, AMOUNT);
+    assert!(h.account(&vault).await.is_none());
+    assert_eq!(h.lamports(&f.creator.pubkey()).await, creator_before + vault_rent);
+    assert_eq!(h.lamports(&f.refund.pubkey()).await, refund_before);
+}
+
+#[tokio::test]
+async fn wrong_creator_is_rejected() {
+    let mut h = Harness::start().await;
+    let f = funded(&mut h).await;
+
+    let claim = h.claim_ix(&f.recipient.pubkey(), &f.preimage, Some(&f.recipient.pubkey()));
+    let result = h.send(&[claim], &[&f.recipient]).await;
+    assert_eq!(escrow_error(result), Some(EscrowError::InvalidCreator));
+    let refund = h.refund_ix(&f.refund.pubkey(), &f.payment_hash, Some(&f.refund.pubkey()));
+    let result = h.send(&[refund], &[&f.refund]).await;
+    assert_eq!(escrow_error(result), Some(EscrowError::InvalidCreator));
+
+    assert_eq!(h.escrow(&f.payment_hash).await.status, EscrowStatus::Active);
+    let vault = vault_ata(&escrow_pda(&f.payment_hash), &h.mint);
+    assert!(h.account(&vault).await.is_some());
+}
@@ -0,0 +1,132 @@
+//! The per-recipient and per-refund indexes Init records each escrow in.
+
+mod common;
+
+use common::{escrow_error, payment, Harness};
+use intercom_swap_core::{
+    state::{ESCROW_INDEX_CAPACITY, ESCROW_INDEX_LEN, ESCROW_INDEX_V1},
+    EscrowError, RefundAfter,
+};
+use ln_usdt_escrow::pda::{
+    escrow_pda, find_index_pda, recipient_index_pda, refund_index_pda, RECIPIENT_INDEX_SEED, REFUND_INDEX_SEED,
+};
+use solana_sdk::{pubkey::Pubkey, signature::Signer, system_instruction};
+
+#[tokio::test]
+async fn first_escrow_creates_both_indexes() {
+    let mut h = Harness::start().await;
+    let creator = h.wallet(1_000).await;
+    let (recipient, refund) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (_, payment_hash) = payment(1);
+    let init = h.init_ix(
+        &creator.pubkey(),
+        &payment_hash,
+        &recipient,
+        &refund,
+        RefundAfter::At(0),
+        1_000,
+    );
+    h.send(&[init], &[&creator]).await.unwrap();
+
+    let rent = h.ctx.banks_client.get_rent().await.unwrap();
+    for (seed, owner) in [(RECIPIENT_INDEX_SEED, recipient), (REFUND_INDEX_SEED, refund)] {
+        let (index, bump) = find_index_pda(&ln_usdt_escrow::ID, seed, &owner);
+        let account = h.account(&index).await.expect("index account");
+        assert_eq!(account.owner, ln_usdt_escrow::ID);
+        assert_eq!(account.data.len(), ESCROW_INDEX_LEN);
+        assert!(rent.is_exempt(account.lamports, ESCROW_INDEX_LEN));
+        let state = h.index(&index).await;
+        assert_eq!(state.v, ESCROW_INDEX_V1);
+        assert_eq!(state.owner, owner.to_bytes());
+        assert_eq!(state.bump, bump);
+        assert_eq!(state.total, 1);
+        assert_eq!(
+            state.newest_first().collect::<Vec<_>>(),
+            [&escrow_pda(&payment_hash).to_bytes()]
+        );
+    }
+}
+
+#[tokio::test]
+async fn prefunded_index_is_taken_over() {
+    let mut h = Harness::start().await;
+    let creator = h.wallet(1_000).await;
+    let recipient = Pubkey::new_unique();
+    let index = recipient_index_pda(&recipient);
+    // Index addresses are predictable, so anyone can send lamports to one before its first escrow.
+    h.send(&[system_instruction::transfer(&h.collector, &index, 1)], &[])
+        .await
+        .unwrap();
+
+    let (_, payment_hash) = payment(1);
+    let init = h.init_ix(
+        &creator.pubkey(),
+        &payment_hash,
+        &recipient,
+        &recipient,
+        RefundAfter::At(0),
+        1_000,
+    );
+    h.send(&[init], &[&creator]).await.unwrap();
+    let account = h.account(&index).await.expect("index account");
+    assert_eq!(account.owner, ln_usdt_escrow::ID);
+    let state = h.index(&index).await;
+    assert_eq!(state.total, 1);
+    assert_eq!(state.escrows[0], escrow_pda(&payment_hash).to_bytes());
+}
+
+#[tokio::test]
+async fn index_keeps_the_newest_escrows_once_full() {
+    let mut h = Harness::start().await;
+    let escrows = ESCROW_INDEX_CAPACITY as u8 + 1;
+    let creator = h.wallet(escrows as u64).await;
+    let (recipient, refund) = (Pubkey::new_unique(), Pubkey::new_unique());
+    for n in 1..=escrows {
+        let (_, payment_hash) = payment(n);
+        let init = h.init_ix(
+            &creator.pubkey(),
+            &payment_hash,
+            &recipient,
+            &refund,
+            RefundAfter::At(0),
+            1,
+        );
+        h.send(&[init], &[&creator]).await.unwrap();
+    }
+
+    let expected: Vec<[u8; 32]> = (2..=escrows)
+        .rev()
+        .map(|n| escrow_pda(&payment(n).1).to_bytes())
+        .collect();
+    for index in [recipient_index_pda(&recipient), refund_index_pda(&refund)] {
+        let state = h.index(&index).await;
+        assert_eq!(state.total, escrows as u64);
+        // The newest took the oldest's slot.
+        assert_eq!(state.escrows[0], escrow_pda(&payment(escrows).1).to_bytes());
+        assert_eq!(state.newest_first().copied().collect::<Vec<_>>(), expected);
+    }
+}
+
+#[tokio::test]
+async fn wrong_index_pda_is_rejected() {
+    let mut h = Harness::start().await;
+    let creator = h.wallet(1_000).await;
+    let (recipient, refund) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (_, payment_hash) = payment(1);
+    let mut init = h.init_ix(
+        &creator.pubkey(),
+        &payment_hash,
+        &recipient,
+        &refund,
+        RefundAfter::At(0),
+        1_000,
+    );
+    // The refund key's index in the recipient index's place.
+    init.accounts[13].pubkey = refund_index_pda(&refund);
+
+    let result = h.send(&[init], &[&creator]).await;
+    assert_eq!(escrow_error(result), Some(EscrowError::InvalidIndexPda));
+    assert!(h.account(&escrow_pda(&payment_hash)).await.is_none());
+    assert!(h.account(&recipient_index_pda(&recipient)).await.is_none());
+    assert_eq!(h.tokens(&creator.pubkey()).await, 1_000);
+}
@@ -0,0 +1,103 @@
+//! Init with a refund delay: the deadline is taken from the on-chain clock.
+
+mod common;
+
+use common::{escrow_error, payment, Harness};
+use intercom_swap_core::{EscrowError, EscrowStatus, RefundAfter};
+use ln_usdt_escrow::pda::escrow_pda;
+use solana_sdk::signature::Signer;
+
+const NOW: i64 = 1_700_000_000;
+const DELAY: i64 = 3_600;
+
+#[tokio::test]
+async fn deadline_is_the_clock_plus_the_delay() {
+    let mut h = Harness::start().await;
+    h.set_time(NOW).await;
+    let creator = h.wallet(1_000).await;
+    let refund = h.wallet(0).await;
+    let (_, payment_hash) = payment(1);
+    let init = h.init_ix(
+        &creator.pubkey(),
+        &payment_hash,
+        &creator.pubkey(),
+        &refund.pubkey(),
+        RefundAfter::Delay(DELAY),
+        1_000,
+    );
+    h.send(&[init], &[&creator]).await.unwrap();
+    assert_eq!(h.escrow(&payment_hash).await.refund_after, NOW + DELAY);
+}
+
+#[tokio::test]
+async fn refund_waits_for_the_delay() {
+    let mut h = Harness::start().await;
+    h.set_time(NOW).await;
+    let creator = h.wallet(1_000).await;
+    let refund = h.wallet(0).await;
+    let (_, payment_hash) = payment(1);
+    let init = h.init_ix(
+        &creator.pubkey(),
+        &payment_hash,
+        &creator.pubkey(),
+        &refund.pubkey(),
+        RefundAfter::Delay(DELAY),
+        1_000,
+    );
+    h.send(&[init], &[&creator]).await.unwrap();
+
+    h.set_time(NOW + DELAY - 1).await;
+    let early = h.refund_ix(&refund.pubkey(), &payment_hash, Some(&creator.pubkey()));
+    let result = h.send(&[early], &[&refund]).await;
+    assert_eq!(escrow_error(result), Some(EscrowError::TooEarly));
+    assert_eq!(h.escrow(&payment_hash).await.status, EscrowStatus::Active);
+
+    h.set_time(NOW + DELAY).await;
+    let on_time = h.refund_ix(&refund.pubkey(), &payment_hash, Some(&creator.pubkey()));
+    h.send(&[on_time], &[&refund]).await.unwrap();
+    assert_eq!(h.escrow(&payment_hash).await.status, EscrowStatus::Refunded);
+    assert_eq!(h.tokens(&refund.pubkey()).await, 1_000);
+}
+
+#[tokio::test]
+async fn delay_must_be_positive() {
+    let mut h = Harness::start().await;
+    let creator = h.wallet(1_000).await;
+    for (n, delay) in [(1, 0), (2, -1), (3, i64::MIN)] {
+        let (_, payment_hash) = payment(n);
+        let init = h.init_ix(
+            &creator.pubkey(),
+            &payment_hash,
+            &creator.pubkey(),
+            &creator.pubkey(),
+            RefundAfter::Delay(delay),
+            1_000,
+        );
+        let result = h.send(&[init], &[&creator]).await;
+        assert_eq!(
+            escrow_error(result),
+            Some(EscrowError::InvalidInstruction),
+            "delay {delay}"
+        );
+        assert!(h.account(&escrow_pda(&payment_hash)).await.is_none());
+    }
+    assert_eq!(h.tokens(&creator.pubkey()).await, 1_000);
+}
+
+#[tokio::test]
+async fn delay_past_the_end_of_time_is_rejected() {
+    let mut h = Harness::start().await;
+    h.set_time(NOW).await;
+    let creator = h.wallet(1_000).await;
+    let (_, payment_hash) = payment(1);
+    let init = h.init_ix(
+        &creator.pubkey(),
+        &payment_hash,
+        &creator.pubkey(),
+        &creator.pubkey(),
+        RefundAfter::Delay(i64::MAX),
+        1_000,
+    );
+    let result = h.send(&[init], &[&creator]).await;
+    assert_eq!(escrow_error(result), Some(EscrowError::InvalidInstruction));
+}
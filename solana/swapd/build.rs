@@ -0,0 +1,9 @@
+fn main() {
+    // The control API's server code is generated from the shipped proto; needs `protoc` on the PATH.
+    #[cfg(feature = "grpc-api")]
+    tonic_build::configure()
+        .build_client(false)
+        .compile(&["proto/swapd/v1/swapd.proto"], &["proto"])
+        .expect("compile proto/swapd/v1/swapd.proto");
+    println!("cargo:rerun-if-changed=proto");
+}
@@ -0,0 +1,406 @@
+//! On-chain platform config management with the program's authority key: InitConfig, SetConfig and
+//! WithdrawFees, each in two steps. [`Admin::preview`] builds the transaction, simulates it and decodes its
+//! [`Effect`] against the current config and fee vault, then holds the [`Preview`] under a random id for a few
+//! minutes. [`Admin::confirm`] re-plans the same operation, refuses if the effect is no longer what was
+//! previewed (someone changed the config, or the vault was drained), and only then signs and sends. A preview
+//! is confirmed at most once.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use intercom_swap_client::{
+    client::{EscrowClient, FetchError},
+    instruction,
+    retry::{RetryError, RetryPolicy, SendOutcome},
+    rpc::EscrowRpc,
+    simulate::Diagnosis,
+};
+use rand::RngCore;
+use solana_sdk::{
+    instruction::Instruction,
+    message::Message,
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::{Signer, SignerError},
+    transaction::Transaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
+use tracing::{debug, info, warn};
+
+use crate::{kms::Operator, metrics::metrics, swap::unix_now};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminOp {
+    /// Create the platform config; the signing key becomes its authority and fee collector.
+    InitConfig { fee_bps: u16 },
+    /// Change the fee collector and/or rate; unset fields keep their current value.
+    SetConfig {
+        fee_collector: Option<Pubkey>,
+        fee_bps: Option<u16>,
+    },
+    /// Move platform fees for `mint` out of the vault; `amount` 0 takes the whole balance at preview time.
+    /// `destination` is a token account, by default the signer's associated one (created if missing).
+    WithdrawFees {
+        mint: Pubkey,
+        amount: u64,
+        destination: Option<Pubkey>,
+    },
+}
+
+impl AdminOp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::InitConfig { .. } => "init_config",
+            Self::SetConfig { .. } => "set_config",
+            Self::WithdrawFees { .. } => "withdraw_fees",
+        }
+    }
+}
+
+/// What an operation changes on chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Effect {
+    ConfigCreated {
+        authority: Pubkey,
+        fee_bps: u16,
+    },
+    ConfigChanged {
+        fee_collector: (Pubkey, Pubkey),
+        fee_bps: (u16, u16),
+    },
+    FeesWithdrawn {
+        mint: Pubkey,
+        amount: u64,
+        /// Vault balance before the withdrawal; informational, not checked on confirm.
+        vault_balance: u64,
+        destination: Pubkey,
+    },
+}
+
+impl Effect {
+    /// Whether confirming `self` still does what `previewed` showed.
+    fn matches(&self, previewed: &Effect) -> bool {
+        match (self, previewed) {
+            (
+                Self::FeesWithdrawn {
+                    mint,
+                    amount,
+                    destination,
+                    ..
+                },
+                Self::FeesWithdrawn {
+                    mint: m,
+                    amount: a,
+                    destination: d,
+                    ..
+                },
+            ) => (mint, amount, destination) == (m, a, d),
+            _ => self == previewed,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Self::ConfigCreated { authority, fee_bps } => serde_json::json!({
+                "effect": "config_created",
+                "authority": authority.to_string(),
+                "feeCollector": authority.to_string(),
+                "feeBps": fee_bps,
+            }),
+            Self::ConfigChanged { fee_collector, fee_bps } => serde_json::json!({
+                "effect": "config_changed",
+                "feeCollector": { "from": fee_collector.0.to_string(), "to": fee_collector.1.to_string() },
+                "feeBps": { "from": fee_bps.0, "to": fee_bps.1 },
+            }),
+            Self::FeesWithdrawn {
+                mint,
+                amount,
+                vault_balance,
+                destination,
+            } => serde_json::json!({
+                "effect": "fees_withdrawn",
+                "mint": mint.to_string(),
+                "amount": amount,
+                "vaultBalance": vault_balance,
+                "destination": destination.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Preview {
+    /// Hex; confirms the operation.
+    pub id: String,
+    /// With defaults filled in and "all" resolved to an amount.
+    pub op: AdminOp,
+    pub signer: Pubkey,
+    pub effect: Effect,
+    pub simulation: Diagnosis,
+    pub expires_at: i64,
+}
+
+impl Preview {
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "id": self.id,
+            "operation": self.op.name(),
+            "signer": self.signer.to_string(),
+            "effect": self.effect.to_json(),
+            "simulation": {
+                "ok": self.simulation.ok,
+                "explanation": self.simulation.explanation,
+                "hint": self.simulation.hint,
+                "logs": self.simulation.logs,
+            },
+            "expiresAt": self.expires_at,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum AdminError {
+    Fetch(FetchError),
+    Send(RetryError),
+    Signer(SignerError),
+    /// The operation cannot apply to the current config, e.g. SetConfig by a key that is not the authority.
+    Invalid(String),
+    /// No such preview, or it was already confirmed.
+    UnknownPreview(String),
+    Expired,
+    /// The chain changed since the preview; preview again.
+    Stale(String),
+    /// Simulation or the cluster refused the transaction.
+    Rejected(String),
+    /// Sent but not confirmed before the retry budget ran out; check the signer's recent transactions.
+    NotLanded,
+}
+
+impl fmt::Display for AdminError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fetch(e) => write!(f, "{e}"),
+            Self::Send(e) => write!(f, "{e}"),
+            Self::Signer(e) => write!(f, "signing failed: {e}"),
+            Self::Invalid(e) => f.write_str(e),
+            Self::UnknownPreview(id) => write!(f, "no pending preview {id}"),
+            Self::Expired => f.write_str("preview expired; preview again"),
+            Self::Stale(e) => write!(f, "chain changed since the preview ({e}); preview again"),
+            Self::Rejected(e) => write!(f, "transaction rejected: {e}"),
+            Self::NotLanded => f.write_str("transaction did not confirm in time"),
+        }
+    }
+}
+
+impl std::error::Error for AdminError {}
+
+impl From<FetchError> for AdminError {
+    fn from(e: FetchError) -> Self {
+        Self::Fetch(e)
+    }
+}
+
+impl From<RetryError> for AdminError {
+    fn from(e: RetryError) -> Self {
+        Self::Send(e)
+    }
+}
+
+impl From<SignerError> for AdminError {
+    fn from(e: SignerError) -> Self {
+        Self::Signer(e)
+    }
+}
+
+pub struct Admin {
+    client: EscrowClient,
+    authority: Arc<Operator>,
+    retry: RetryPolicy,
+    /// How long a preview can be confirmed.
+    ttl: Duration,
+    previews: Mutex<HashMap<String, Preview>>,
+}
+
+impl Admin {
+    pub fn new(client: EscrowClient, authority: Arc<Operator>, retry: RetryPolicy, ttl: Duration) -> Self {
+        Self {
+            client,
+            authority,
+            retry,
+            ttl,
+            previews: Mutex::default(),
+        }
+    }
+
+    pub fn signer(&self) -> Pubkey {
+        self.authority.pubkey()
+    }
+
+    /// Plans, simulates and holds `op` for [`Self::confirm`].
+    pub async fn preview(&self, op: AdminOp) -> Result<Preview, AdminError> {
+        let (op, instructions, effect) = self.plan(op).await?;
+        let tx = Transaction::new_unsigned(Message::new(&instructions, Some(&self.signer())));
+        let simulation = self.client.simulate_and_explain(&tx).await?;
+        let mut id = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut id);
+        let preview = Preview {
+            id: hex::encode(id),
+            op,
+            signer: self.signer(),
+            effect,
+            simulation,
+            expires_at: unix_now() + self.ttl.as_secs() as i64,
+        };
+        let mut previews = self.previews.lock().unwrap_or_else(|e| e.into_inner());
+        let now = unix_now();
+        previews.retain(|_, p| p.expires_at >= now);
+        previews.insert(preview.id.clone(), preview.clone());
+        let (operation, ok) = (preview.op.name(), preview.simulation.ok);
+        info!(preview = %preview.id, operation, ok, "admin operation previewed");
+        Ok(preview)
+    }
+
+    /// Sends the previewed operation once it is checked to still do what the preview showed.
+    pub async fn confirm(&self, id: &str) -> Result<Signature, AdminError> {
+        let preview = self
+            .previews
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id)
+            .ok_or_else(|| AdminError::UnknownPreview(id.to_string()))?;
+        if unix_now() > preview.expires_at {
+            return Err(AdminError::Expired);
+        }
+        if !preview.simulation.ok {
+            return Err(AdminError::Rejected(preview.simulation.explanation));
+        }
+        let (_, instructions, effect) = self.plan(preview.op.clone()).await?;
+        if !effect.matches(&preview.effect) {
+            return Err(AdminError::Stale(format!("{:?} is now {:?}", preview.effect, effect)));
+        }
+        let blockhash = self.client.get_latest_blockhash().await?;
+        let mut tx = Transaction::new_unsigned(Message::new(&instructions, Some(&self.signer())));
+        tx.try_sign(&[&*self.authority], blockhash)?;
+        let outcome = self
+            .client
+            .send_with_retry(tx.clone(), &[&*self.authority], &self.retry, |event| {
+                metrics().observe_send_event(event);
+                debug!(preview = %id, ?event, "admin send progress");
+            })
+            .await?;
+        metrics().observe_send_outcome(&outcome);
+        if let Some(e) = outcome.error(self.client.program_id(), &tx) {
+            warn!(preview = %id, error = %e, "admin operation rejected");
+            return Err(AdminError::Rejected(e.to_string()));
+        }
+        match outcome {
+            SendOutcome::Confirmed { signature, .. } => {
+                info!(preview = %id, operation = preview.op.name(), %signature, "admin operation confirmed");
+                Ok(signature)
+            }
+            _ => Err(AdminError::NotLanded),
+        }
+    }
+
+    /// `op` resolved against the chain: its instructions and effect.
+    async fn plan(&self, op: AdminOp) -> Result<(AdminOp, Vec<Instruction>, Effect), AdminError> {
+        let program_id = *self.client.program_id();
+        let signer = self.signer();
+        let config = self.client.get_config().await?;
+        match op {
+            AdminOp::InitConfig { fee_bps } => {
+                if config.is_some() {
+                    return Err(AdminError::Invalid("the platform config already exists".into()));
+                }
+                let ix = instruction::init_config(&program_id, &signer, &signer, fee_bps);
+                let effect = Effect::ConfigCreated {
+                    authority: signer,
+                    fee_bps,
+                };
+                Ok((op, vec![ix], effect))
+            }
+            AdminOp::SetConfig { fee_collector, fee_bps } => {
+                let config = config.ok_or_else(|| AdminError::Invalid("the platform config does not exist".into()))?;
+                if config.authority != signer {
+                    return Err(AdminError::Invalid(format!(
+                        "{signer} is not the config authority {}",
+                        config.authority
+                    )));
+                }
+                let to = (
+                    fee_collector.unwrap_or(config.fee_collector),
+                    fee_bps.unwrap_or(config.fee_bps),
+                );
+                let ix = instruction::set_config(&program_id, &signer, &to.0, to.1);
+                let effect = Effect::ConfigChanged {
+                    fee_collector: (config.fee_collector, to.0),
+                    fee_bps: (config.fee_bps, to.1),
+                };
+                let op = AdminOp::SetConfig {
+                    fee_collector: Some(to.0),
+                    fee_bps: Some(to.1),
+                };
+                Ok((op, vec![ix], effect))
+            }
+            AdminOp::WithdrawFees {
+                mint,
+                amount,
+                destination,
+            } => {
+                let config = config.ok_or_else(|| AdminError::Invalid("the platform config does not exist".into()))?;
+                if config.fee_collector != signer {
+                    return Err(AdminError::Invalid(format!(
+                        "{signer} is not the fee collector {}",
+                        config.fee_collector
+                    )));
+                }
+                let vault_balance = self.client.get_fee_vault_balance(&mint).await?;
+                let amount = if amount == 0 { vault_balance } else { amount };
+                if amount == 0 || amount > vault_balance {
+                    return Err(AdminError::Invalid(format!(
+                        "cannot withdraw {amount} from a fee vault holding {vault_balance}"
+                    )));
+                }
+                let mut instructions = Vec::new();
+                let resolved = match destination {
+                    Some(account) => account,
+                    None => {
+                        instructions.push(create_associated_token_account_idempotent(
+                            &signer,
+                            &signer,
+                            &mint,
+                            &spl_token::id(),
+                        ));
+                        get_associated_token_address(&signer, &mint)
+                    }
+                };
+                instructions.push(instruction::withdraw_fees(
+                    &program_id,
+                    &signer,
+                    &resolved,
+                    &mint,
+                    amount,
+                ));
+                let effect = Effect::FeesWithdrawn {
+                    mint,
+                    amount,
+                    vault_balance,
+                    destination: resolved,
+                };
+                // The default destination stays unresolved so confirming creates it again if missing.
+                let op = AdminOp::WithdrawFees {
+                    mint,
+                    amount,
+                    destination,
+                };
+                Ok((op, instructions, effect))
+            }
+        }
+    }
+}
@@ -0,0 +1,490 @@
+//! Authentication of control API callers. Each request carries an API key or, when a signing secret is
+//! configured, a JWT, as `Authorization: Bearer <credential>` (or the key alone in `X-Api-Key`). Both grant
+//! one [`Scope`]: `read` to follow swaps and the daemon's status, `create` to also quote, queue and cancel
+//! swaps, `admin` for everything, including operator actions that move funds.
+//!
+//! API keys are `swapd_<id>_<secret>`, shown once when created with `swapd create-api-key`; the store keeps
+//! only the id and a SHA-256 of the key, which is enough for 256-bit random secrets. JWTs are HS256 with
+//! `scope` and `exp` claims, and `sub` naming the caller in logs.
+
+use std::{fmt, str::FromStr, sync::Arc};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    store::{Store, StoreError},
+    swap::unix_now,
+};
+
+/// Header carrying an API key on its own, for clients that cannot set `Authorization`.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+const KEY_PREFIX: &str = "swapd_";
+
+/// What a caller may do; each scope includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Scope {
+    /// Daemon info and swaps, including the WebSocket feed.
+    Read,
+    /// Quote, queue and cancel swaps.
+    Create,
+    /// Operator reports, such as the accounting export.
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Create => "create",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "read" => Self::Read,
+            "create" => Self::Create,
+            "admin" => Self::Admin,
+            other => return Err(format!("unknown scope {other:?}")),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    /// The request carries no credential.
+    Missing,
+    /// Unknown, malformed, revoked or badly signed.
+    Invalid(String),
+    Expired,
+    Forbidden {
+        scope: Scope,
+        needed: Scope,
+    },
+    Store(StoreError),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => f.write_str("missing credentials: send `Authorization: Bearer <api key or token>`"),
+            Self::Invalid(reason) => write!(f, "invalid credentials: {reason}"),
+            Self::Expired => f.write_str("token expired"),
+            Self::Forbidden { scope, needed } => write!(f, "{needed} scope needed, credentials grant {scope}"),
+            Self::Store(e) => write!(f, "cannot check credentials: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Store(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<StoreError> for AuthError {
+    fn from(e: StoreError) -> Self {
+        Self::Store(e)
+    }
+}
+
+/// An API key as stored: everything but the secret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKey {
+    /// Hex; the public part of the key.
+    pub id: String,
+    pub name: String,
+    /// SHA-256 of the whole key.
+    pub key_hash: [u8; 32],
+    pub scope: Scope,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+}
+
+impl ApiKey {
+    /// A new random key named `name`, with the key itself, which is not kept anywhere.
+    pub fn generate(name: &str, scope: Scope) -> (Self, String) {
+        let id = hex::encode(rand::random::<[u8; 8]>());
+        let key = format!("{KEY_PREFIX}{id}_{}", hex::encode(rand::random::<[u8; 32]>()));
+        let record = Self {
+            id,
+            name: name.to_string(),
+            key_hash: Sha256::digest(key.as_bytes()).into(),
+            scope,
+            created_at: unix_now(),
+            last_used_at: None,
+            revoked_at: None,
+        };
+        (record, key)
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "name": self.name,
+            "scope": self.scope.as_str(),
+            "createdAt": self.created_at,
+            "lastUsedAt": self.last_used_at,
+            "revokedAt": self.revoked_at,
+        })
+    }
+}
+
+/// Who made a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    /// The API key's name or the token's `sub`.
+    pub name: String,
+    pub scope: Scope,
+}
+
+impl Principal {
+    pub fn require(&self, needed: Scope) -> Result<(), AuthError> {
+        if self.scope < needed {
+            return Err(AuthError::Forbidden {
+                scope: self.scope,
+                needed,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The credential of a request, from its `Authorization` header or, failing that, its API key header.
+pub fn credential<'a>(authorization: Option<&'a str>, api_key: Option<&'a str>) -> Option<&'a str> {
+    authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or(api_key)
+        .map(str::trim)
+}
+
+#[derive(Clone)]
+pub struct Authenticator {
+    store: Arc<Store>,
+    /// Accept HS256 tokens signed with this.
+    jwt_secret: Option<Arc<[u8]>>,
+    /// Let every request through as `admin`.
+    open: bool,
+}
+
+impl Authenticator {
+    pub fn new(store: Arc<Store>, jwt_secret: Option<Vec<u8>>) -> Self {
+        Self {
+            store,
+            jwt_secret: jwt_secret.map(Arc::from),
+            open: false,
+        }
+    }
+
+    /// Lets everyone in, e.g. for an API bound to loopback during development.
+    pub fn open(store: Arc<Store>) -> Self {
+        Self {
+            store,
+            jwt_secret: None,
+            open: true,
+        }
+    }
+
+    pub fn authenticate(&self, credential: Option<&str>) -> Result<Principal, AuthError> {
+        if self.open {
+            return Ok(Principal {
+                name: "anonymous".into(),
+                scope: Scope::Admin,
+            });
+        }
+        let credential = credential.filter(|c| !c.is_empty()).ok_or(AuthError::Missing)?;
+        if credential.starts_with(KEY_PREFIX) {
+            return self.api_key(credential);
+        }
+        match &self.jwt_secret {
+            Some(secret) => verify_jwt(secret, credential, unix_now()),
+            None => Err(AuthError::Invalid("not an API key".into())),
+        }
+    }
+
+    fn api_key(&self, key: &str) -> Result<Principal, AuthError> {
+        let unknown = || AuthError::Invalid("unknown API key".into());
+        let id = key[KEY_PREFIX.len()..].split('_').next().ok_or_else(unknown)?;
+        let record = self.store.api_key(id)?.ok_or_else(unknown)?;
+        let hash: [u8; 32] = Sha256::digest(key.as_bytes()).into();
+        if !constant_time_eq(&hash, &record.key_hash) {
+            return Err(unknown());
+        }
+        if record.revoked_at.is_some() {
+            return Err(AuthError::Invalid("API key revoked".into()));
+        }
+        self.store.touch_api_key(&record.id, unix_now())?;
+        Ok(Principal {
+            name: record.name,
+            scope: record.scope,
+        })
+    }
+}
+
+fn constant_time_eq(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Checks an HS256 JWT's signature and expiry, and reads its scope.
+fn verify_jwt(secret: &[u8], token: &str, now: i64) -> Result<Principal, AuthError> {
+    let invalid = |reason: &str| AuthError::Invalid(reason.to_string());
+    let mut parts = token.split('.');
+    let (Some(header), Some(claims), Some(signature), None) = (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid("not a JWT"));
+    };
+    let decode = |part: &str| -> Result<Value, AuthError> {
+        let bytes = URL_SAFE_NO_PAD.decode(part).map_err(|_| invalid("malformed JWT"))?;
+        serde_json::from_slice(&bytes).map_err(|_| invalid("malformed JWT"))
+    };
+    if decode(header)?["alg"] != "HS256" {
+        return Err(invalid("JWT must be signed with HS256"));
+    }
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| invalid("malformed JWT"))?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(header.as_bytes());
+    mac.update(b".");
+    mac.update(claims.as_bytes());
+    mac.verify_slice(&signature).map_err(|_| invalid("bad JWT signature"))?;
+    let claims = decode(claims)?;
+    let exp = claims["exp"].as_i64().ok_or_else(|| invalid("JWT has no exp"))?;
+    if exp <= now {
+        return Err(AuthError::Expired);
+    }
+    if claims["nbf"].as_i64().is_some_and(|nbf| nbf > now) {
+        return Err(invalid("JWT not yet valid"));
+    }
+    let scope = claims["scope"]
+        .as_str()
+        .ok_or_else(|| invalid("JWT has no scope"))?
+        .parse()
+        .map_err(AuthError::Invalid)?;
+    Ok(Principal {
+        name: claims["sub"].as_str().unwrap_or("jwt").to_string(),
+        scope,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::Vault;
+
+    const SECRET: &[u8] = b"jwt signing secret";
+    const NOW: i64 = 1_700_000_000;
+
+    fn encode(value: &Value) -> String {
+        URL_SAFE_NO_PAD.encode(value.to_string())
+    }
+
+    fn sign(secret: &[u8], header: &str, claims: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
+        mac.update(format!("{header}.{claims}").as_bytes());
+        URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+    }
+
+    fn jwt(secret: &[u8], alg: &str, claims: Value) -> String {
+        let header = encode(&json!({ "alg": alg, "typ": "JWT" }));
+        let claims = encode(&claims);
+        let signature = sign(secret, &header, &claims);
+        format!("{header}.{claims}.{signature}")
+    }
+
+    fn claims(exp: i64) -> Value {
+        json!({ "sub": "merchant", "scope": "create", "exp": exp })
+    }
+
+    fn invalid(result: Result<Principal, AuthError>) -> String {
+        match result {
+            Err(AuthError::Invalid(reason)) => reason,
+            other => panic!("expected invalid credentials, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn valid_jwt_grants_its_scope() {
+        let token = jwt(SECRET, "HS256", claims(NOW + 60));
+        let principal = verify_jwt(SECRET, &token, NOW).unwrap();
+        assert_eq!(principal.name, "merchant");
+        assert_eq!(principal.scope, Scope::Create);
+    }
+
+    #[test]
+    fn expired_jwt_is_refused() {
+        let token = jwt(SECRET, "HS256", claims(NOW));
+        assert!(matches!(verify_jwt(SECRET, &token, NOW), Err(AuthError::Expired)));
+        let token = jwt(SECRET, "HS256", claims(NOW - 1));
+        assert!(matches!(verify_jwt(SECRET, &token, NOW), Err(AuthError::Expired)));
+    }
+
+    #[test]
+    fn jwt_not_yet_valid_is_refused() {
+        let mut future = claims(NOW + 60);
+        future["nbf"] = json!(NOW + 1);
+        let token = jwt(SECRET, "HS256", future);
+        assert_eq!(invalid(verify_jwt(SECRET, &token, NOW)), "JWT not yet valid");
+    }
+
+    #[test]
+    fn jwt_with_another_alg_is_refused() {
+        for alg in ["none", "HS512", "RS256", "hs256"] {
+            let token = jwt(SECRET, alg, claims(NOW + 60));
+            assert_eq!(
+                invalid(verify_jwt(SECRET, &token, NOW)),
+                "JWT must be signed with HS256",
+                "{alg}"
+            );
+        }
+        // An unsigned token, as `alg: none` would have it.
+        let header = encode(&json!({ "alg": "none" }));
+        let token = format!("{header}.{}.", encode(&claims(NOW + 60)));
+        assert_eq!(
+            invalid(verify_jwt(SECRET, &token, NOW)),
+            "JWT must be signed with HS256"
+        );
+    }
+
+    #[test]
+    fn tampered_jwt_is_refused() {
+        let token = jwt(SECRET, "HS256", claims(NOW + 60));
+        let (signed, signature) = token.rsplit_once('.').unwrap();
+        let header = signed.split('.').next().unwrap();
+        let mut admin = claims(NOW + 60);
+        admin["scope"] = json!("admin");
+        let escalated = format!("{header}.{}.{signature}", encode(&admin));
+        assert_eq!(invalid(verify_jwt(SECRET, &escalated, NOW)), "bad JWT signature");
+
+        let mut flipped = URL_SAFE_NO_PAD.decode(signature).unwrap();
+        flipped[0] ^= 1;
+        let token = format!("{signed}.{}", URL_SAFE_NO_PAD.encode(flipped));
+        assert_eq!(invalid(verify_jwt(SECRET, &token, NOW)), "bad JWT signature");
+    }
+
+    #[test]
+    fn jwt_signed_with_an_unknown_key_is_refused() {
+        let token = jwt(b"someone else's secret", "HS256", claims(NOW + 60));
+        assert_eq!(invalid(verify_jwt(SECRET, &token, NOW)), "bad JWT signature");
+    }
+
+    #[test]
+    fn malformed_jwt_is_refused() {
+        let token = jwt(SECRET, "HS256", claims(NOW + 60));
+        assert_eq!(invalid(verify_jwt(SECRET, "a.b", NOW)), "not a JWT");
+        assert_eq!(invalid(verify_jwt(SECRET, &format!("{token}.x"), NOW)), "not a JWT");
+        assert_eq!(invalid(verify_jwt(SECRET, "!!.b.c", NOW)), "malformed JWT");
+        let no_scope = jwt(SECRET, "HS256", json!({ "exp": NOW + 60 }));
+        assert_eq!(invalid(verify_jwt(SECRET, &no_scope, NOW)), "JWT has no scope");
+        let no_exp = jwt(SECRET, "HS256", json!({ "scope": "read" }));
+        assert_eq!(invalid(verify_jwt(SECRET, &no_exp, NOW)), "JWT has no exp");
+    }
+
+    fn authenticator() -> Authenticator {
+        let store = Store::open(":memory:", Vault::new(&[3; 32])).unwrap();
+        Authenticator::new(Arc::new(store), Some(SECRET.to_vec()))
+    }
+
+    #[test]
+    fn api_key_is_stored_as_its_hash() {
+        let (record, key) = ApiKey::generate("shop", Scope::Read);
+        let (id, secret) = key[KEY_PREFIX.len()..].split_once('_').unwrap();
+        assert_eq!(id, record.id);
+        assert_eq!(secret.len(), 64);
+        assert_eq!(record.key_hash, <[u8; 32]>::from(Sha256::digest(key.as_bytes())));
+        assert!(!record.to_json().to_string().contains(secret));
+    }
+
+    #[test]
+    fn api_key_authenticates_until_revoked() {
+        let auth = authenticator();
+        let (record, key) = ApiKey::generate("shop", Scope::Create);
+        auth.store.insert_api_key(&record).unwrap();
+        let principal = auth.authenticate(Some(&key)).unwrap();
+        assert_eq!(principal.name, "shop");
+        assert_eq!(principal.scope, Scope::Create);
+        assert!(auth.store.api_key(&record.id).unwrap().unwrap().last_used_at.is_some());
+
+        auth.store.revoke_api_key("shop", unix_now()).unwrap();
+        assert_eq!(invalid(auth.authenticate(Some(&key))), "API key revoked");
+    }
+
+    #[test]
+    fn api_key_with_a_wrong_secret_is_unknown() {
+        let auth = authenticator();
+        let (record, key) = ApiKey::generate("shop", Scope::Admin);
+        auth.store.insert_api_key(&record).unwrap();
+        // Right id, last secret character changed.
+        let mut forged = key.clone();
+        let last = forged.pop().unwrap();
+        forged.push(if last == '0' { '1' } else { '0' });
+        assert_eq!(invalid(auth.authenticate(Some(&forged))), "unknown API key");
+        let (_, unknown) = ApiKey::generate("elsewhere", Scope::Admin);
+        assert_eq!(invalid(auth.authenticate(Some(&unknown))), "unknown API key");
+    }
+
+    #[test]
+    fn constant_time_eq_compares_every_byte() {
+        let a = [7; 32];
+        assert!(constant_time_eq(&a, &a));
+        for i in [0, 15, 31] {
+            let mut b = a;
+            b[i] ^= 0x80;
+            assert!(!constant_time_eq(&a, &b));
+        }
+    }
+
+    #[test]
+    fn authenticate_routes_credentials() {
+        let auth = authenticator();
+        assert!(matches!(auth.authenticate(None), Err(AuthError::Missing)));
+        assert!(matches!(auth.authenticate(Some("")), Err(AuthError::Missing)));
+        let token = jwt(SECRET, "HS256", claims(unix_now() + 60));
+        assert_eq!(auth.authenticate(Some(&token)).unwrap().scope, Scope::Create);
+
+        let without_jwt = Authenticator::new(auth.store.clone(), None);
+        assert_eq!(invalid(without_jwt.authenticate(Some(&token))), "not an API key");
+    }
+
+    #[test]
+    fn scopes_include_the_ones_below() {
+        let create = Principal {
+            name: "shop".into(),
+            scope: Scope::Create,
+        };
+        assert!(create.require(Scope::Read).is_ok());
+        assert!(create.require(Scope::Create).is_ok());
+        assert!(matches!(
+            create.require(Scope::Admin),
+            Err(AuthError::Forbidden {
+                scope: Scope::Create,
+                needed: Scope::Admin
+            })
+        ));
+    }
+
+    #[test]
+    fn credential_prefers_the_bearer_token() {
+        assert_eq!(credential(Some("Bearer abc "), Some("key")), Some("abc"));
+        assert_eq!(credential(Some("Basic abc"), Some(" key")), Some("key"));
+        assert_eq!(credential(None, None), None);
+    }
+}
@@ -0,0 +1,481 @@
+//! Online backups of the swap database. [`Backups`] snapshots the store every interval with `VACUUM INTO`,
+//! which is consistent while the engine keeps writing, seals the snapshot under the preimage
+//! [`vault`](crate::vault) key and stores it in a local directory or an S3-compatible bucket (`s3` feature),
+//! keeping the newest few. The key itself never goes into a backup: keep one copy of it apart. [`restore`]
+//! fetches a backup, opens it with the key, checks the file and that every swap's preimage opens, and only then
+//! moves it into place, so a rebuilt host resumes in-flight swaps from where the backup left them.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use tracing::{error, info, warn};
+use zeroize::Zeroizing;
+
+use crate::{
+    export,
+    metrics::metrics,
+    store::{Store, StoreError},
+    swap::unix_now,
+    vault::{Vault, VaultError},
+};
+
+/// Leads every backup; also the associated data of its sealed body.
+const MAGIC: &[u8] = b"swapd-backup-1\n";
+const PREFIX: &str = "swapd-";
+const SUFFIX: &str = ".backup";
+
+/// Where backups are kept, written as `s3://BUCKET/PREFIX` or a directory (`file:` optional).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackupTarget {
+    Dir(PathBuf),
+    S3 { bucket: String, prefix: String },
+}
+
+impl FromStr for BackupTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(rest) = s.strip_prefix("s3://") {
+            let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+            if bucket.is_empty() {
+                return Err(format!("backup target {s:?} names no bucket"));
+            }
+            let prefix = match prefix.trim_end_matches('/') {
+                "" => String::new(),
+                p => format!("{p}/"),
+            };
+            return Ok(Self::S3 {
+                bucket: bucket.to_string(),
+                prefix,
+            });
+        }
+        let path = s.strip_prefix("file:").unwrap_or(s);
+        if path.is_empty() || path.contains("://") {
+            return Err(format!(
+                "unrecognized backup target {s:?}: expected a directory or s3://BUCKET/PREFIX"
+            ));
+        }
+        Ok(Self::Dir(path.into()))
+    }
+}
+
+impl BackupTarget {
+    /// The target under this one for a single tenant's backups.
+    pub fn join(&self, tenant: &str) -> Self {
+        match self {
+            Self::Dir(dir) => Self::Dir(dir.join(tenant)),
+            Self::S3 { bucket, prefix } => Self::S3 {
+                bucket: bucket.clone(),
+                prefix: format!("{prefix}{tenant}/"),
+            },
+        }
+    }
+}
+
+impl fmt::Display for BackupTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dir(path) => write!(f, "file:{}", path.display()),
+            Self::S3 { bucket, prefix } => write!(f, "s3://{bucket}/{prefix}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    pub target: BackupTarget,
+    /// Endpoint of an S3-compatible service other than AWS, e.g. `http://127.0.0.1:9000`; path-style requests.
+    pub s3_endpoint: Option<String>,
+    pub interval: Duration,
+    /// Backups kept at the target; older ones are deleted. 0 keeps all.
+    pub keep: usize,
+}
+
+#[derive(Debug)]
+pub enum BackupError {
+    Store(StoreError),
+    Vault(VaultError),
+    Io(io::Error),
+    /// Built without the feature the target needs.
+    Unsupported(&'static str),
+    Remote(String),
+    NotFound(String),
+    /// Not a backup, or one that fails its checks.
+    Invalid(String),
+    /// Restoring would overwrite this database.
+    Exists(PathBuf),
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Store(e) => write!(f, "{e}"),
+            Self::Vault(VaultError::Unsealable) => {
+                f.write_str("cannot open the backup: wrong preimage key, or the backup is damaged")
+            }
+            Self::Vault(e) => write!(f, "{e}"),
+            Self::Io(e) => write!(f, "backup: {e}"),
+            Self::Unsupported(feature) => {
+                write!(f, "this backup target needs swapd built with the `{feature}` feature")
+            }
+            Self::Remote(e) => write!(f, "backup storage: {e}"),
+            Self::NotFound(name) => write!(f, "no backup {name}"),
+            Self::Invalid(e) => write!(f, "invalid backup: {e}"),
+            Self::Exists(path) => write!(f, "{} exists; restore with --force to replace it", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+impl From<StoreError> for BackupError {
+    fn from(e: StoreError) -> Self {
+        Self::Store(e)
+    }
+}
+
+impl From<VaultError> for BackupError {
+    fn from(e: VaultError) -> Self {
+        Self::Vault(e)
+    }
+}
+
+impl From<io::Error> for BackupError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// A connected [`BackupTarget`].
+pub struct Storage {
+    target: BackupTarget,
+    #[cfg(feature = "s3")]
+    s3: Option<aws_sdk_s3::Client>,
+}
+
+impl Storage {
+    pub async fn connect(target: BackupTarget, s3_endpoint: Option<String>) -> Result<Self, BackupError> {
+        match &target {
+            BackupTarget::Dir(dir) => {
+                fs::create_dir_all(dir)?;
+                Ok(Self {
+                    target,
+                    #[cfg(feature = "s3")]
+                    s3: None,
+                })
+            }
+            #[cfg(feature = "s3")]
+            BackupTarget::S3 { .. } => {
+                let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+                if let Some(endpoint) = &s3_endpoint {
+                    loader = loader.endpoint_url(endpoint);
+                }
+                let config = aws_sdk_s3::config::Builder::from(&loader.load().await)
+                    .force_path_style(s3_endpoint.is_some())
+                    .build();
+                Ok(Self {
+                    target,
+                    s3: Some(aws_sdk_s3::Client::from_conf(config)),
+                })
+            }
+            #[cfg(not(feature = "s3"))]
+            BackupTarget::S3 { .. } => {
+                let _ = s3_endpoint;
+                Err(BackupError::Unsupported("s3"))
+            }
+        }
+    }
+
+    pub fn target(&self) -> &BackupTarget {
+        &self.target
+    }
+
+    /// Backup names, oldest first.
+    pub async fn list(&self) -> Result<Vec<String>, BackupError> {
+        let mut names: Vec<String> = match &self.target {
+            BackupTarget::Dir(dir) => fs::read_dir(dir)?
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                .collect(),
+            #[cfg(feature = "s3")]
+            BackupTarget::S3 { bucket, prefix } => {
+                let mut keys = Vec::new();
+                let mut pages = self
+                    .s3()
+                    .list_objects_v2()
+                    .bucket(bucket)
+                    .prefix(prefix)
+                    .into_paginator()
+                    .send();
+                while let Some(page) = pages.next().await {
+                    let page = page.map_err(|e| BackupError::Remote(e.to_string()))?;
+                    let found = page
+                        .contents()
+                        .iter()
+                        .filter_map(|o| o.key()?.strip_prefix(prefix.as_str()));
+                    keys.extend(found.map(str::to_string));
+                }
+                keys
+            }
+            #[cfg(not(feature = "s3"))]
+            BackupTarget::S3 { .. } => Vec::new(),
+        };
+        names.retain(|n| n.starts_with(PREFIX) && n.ends_with(SUFFIX));
+        names.sort();
+        Ok(names)
+    }
+
+    async fn put(&self, name: &str, bytes: Vec<u8>) -> Result<(), BackupError> {
+        match &self.target {
+            BackupTarget::Dir(dir) => {
+                // Written aside first so a crash never leaves a truncated backup under a backup's name.
+                let partial = dir.join(format!(".{name}.partial"));
+                fs::write(&partial, bytes)?;
+                fs::rename(partial, dir.join(name))?;
+            }
+            #[cfg(feature = "s3")]
+            BackupTarget::S3 { bucket, prefix } => {
+                self.s3()
+                    .put_object()
+                    .bucket(bucket)
+                    .key(format!("{prefix}{name}"))
+                    .body(bytes.into())
+                    .send()
+                    .await
+                    .map_err(|e| BackupError::Remote(e.to_string()))?;
+            }
+            #[cfg(not(feature = "s3"))]
+            BackupTarget::S3 { .. } => return Err(BackupError::Unsupported("s3")),
+        }
+        Ok(())
+    }
+
+    async fn get(&self, name: &str) -> Result<Vec<u8>, BackupError> {
+        match &self.target {
+            BackupTarget::Dir(dir) => match fs::read(dir.join(name)) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Err(BackupError::NotFound(name.to_string())),
+                read => Ok(read?),
+            },
+            #[cfg(feature = "s3")]
+            BackupTarget::S3 { bucket, prefix } => {
+                let object = self
+                    .s3()
+                    .get_object()
+                    .bucket(bucket)
+                    .key(format!("{prefix}{name}"))
+                    .send()
+                    .await
+                    .map_err(|e| match e.as_service_error() {
+                        Some(e) if e.is_no_such_key() => BackupError::NotFound(name.to_string()),
+                        _ => BackupError::Remote(e.to_string()),
+                    })?;
+                let body = object
+                    .body
+                    .collect()
+                    .await
+                    .map_err(|e| BackupError::Remote(e.to_string()))?;
+                Ok(body.into_bytes().to_vec())
+            }
+            #[cfg(not(feature = "s3"))]
+            BackupTarget::S3 { .. } => Err(BackupError::Unsupported("s3")),
+        }
+    }
+
+    async fn delete(&self, name: &str) -> Result<(), BackupError> {
+        match &self.target {
+            BackupTarget::Dir(dir) => fs::remove_file(dir.join(name))?,
+            #[cfg(feature = "s3")]
+            BackupTarget::S3 { bucket, prefix } => {
+                self.s3()
+                    .delete_object()
+                    .bucket(bucket)
+                    .key(format!("{prefix}{name}"))
+                    .send()
+                    .await
+                    .map_err(|e| BackupError::Remote(e.to_string()))?;
+            }
+            #[cfg(not(feature = "s3"))]
+            BackupTarget::S3 { .. } => return Err(BackupError::Unsupported("s3")),
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "s3")]
+    fn s3(&self) -> &aws_sdk_s3::Client {
+        self.s3.as_ref().expect("s3 targets connect a client")
+    }
+}
+
+pub struct Backups {
+    store: Arc<Store>,
+    storage: Storage,
+    /// Where the snapshot is written before it is sealed.
+    scratch: PathBuf,
+    interval: Duration,
+    keep: usize,
+}
+
+impl Backups {
+    /// Connects to `cfg.target`, failing early on a target that cannot be used. `db` is the store's path.
+    pub async fn connect(store: Arc<Store>, db: &Path, cfg: BackupConfig) -> Result<Self, BackupError> {
+        let mut scratch = db.as_os_str().to_owned();
+        scratch.push(".snapshot");
+        Ok(Self {
+            store,
+            storage: Storage::connect(cfg.target, cfg.s3_endpoint).await?,
+            scratch: scratch.into(),
+            interval: cfg.interval,
+            keep: cfg.keep,
+        })
+    }
+
+    /// Backs up every `interval` until `shutdown` resolves.
+    pub async fn run(&self, shutdown: impl std::future::Future<Output = ()>) {
+        tokio::pin!(shutdown);
+        loop {
+            match self.backup().await {
+                Ok(name) => {
+                    metrics().backups.with_label_values(&["stored"]).inc();
+                    metrics().last_backup_timestamp.set(unix_now());
+                    info!(%name, target = %self.storage.target(), "database backed up");
+                    if let Err(e) = self.prune().await {
+                        warn!(error = %e, "cannot delete old backups");
+                    }
+                }
+                Err(e) => {
+                    metrics().backups.with_label_values(&["failed"]).inc();
+                    error!(alert = "backup_failed", error = %e, "database backup failed");
+                }
+            }
+            tokio::select! {
+                _ = &mut shutdown => return,
+                _ = tokio::time::sleep(self.interval) => {}
+            }
+        }
+    }
+
+    /// Snapshots, seals and stores the database now; the backup's name.
+    pub async fn backup(&self) -> Result<String, BackupError> {
+        remove_if_exists(&self.scratch)?;
+        self.store.snapshot(&self.scratch)?;
+        let snapshot = fs::read(&self.scratch).map(Zeroizing::new);
+        remove_if_exists(&self.scratch)?;
+        let sealed = self.store.vault().seal_blob(MAGIC, &snapshot?)?;
+        let name = format!(
+            "{PREFIX}{}{SUFFIX}",
+            export::timestamp(unix_now()).replace(['-', ':'], "")
+        );
+        self.storage.put(&name, [MAGIC, &sealed].concat()).await?;
+        Ok(name)
+    }
+
+    /// Deletes all but the newest `keep` backups.
+    async fn prune(&self) -> Result<(), BackupError> {
+        if self.keep == 0 {
+            return Ok(());
+        }
+        let names = self.storage.list().await?;
+        for name in names.iter().take(names.len().saturating_sub(self.keep)) {
+            self.storage.delete(name).await?;
+        }
+        Ok(())
+    }
+}
+
+/// What [`restore`] put in place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Restored {
+    pub name: String,
+    pub swaps: usize,
+    /// Swaps the engine will resume.
+    pub active: usize,
+    /// Preimages checked to open with the key.
+    pub preimages: usize,
+}
+
+impl Restored {
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "backup": self.name,
+            "swaps": self.swaps,
+            "activeSwaps": self.active,
+            "preimagesVerified": self.preimages,
+        })
+    }
+}
+
+/// Rebuilds the database at `db` from the backup `name` (the newest when `None`) after checking it. Run with the
+/// daemon stopped; an existing database is only replaced with `force`.
+pub async fn restore(
+    storage: &Storage,
+    name: Option<&str>,
+    db: &Path,
+    vault: Vault,
+    force: bool,
+) -> Result<Restored, BackupError> {
+    if db.exists() && !force {
+        return Err(BackupError::Exists(db.to_path_buf()));
+    }
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => storage
+            .list()
+            .await?
+            .pop()
+            .ok_or_else(|| BackupError::NotFound(format!("at {}", storage.target())))?,
+    };
+    let bytes = storage.get(&name).await?;
+    let sealed = bytes
+        .strip_prefix(MAGIC)
+        .ok_or_else(|| BackupError::Invalid(format!("{name} is not a swapd backup")))?;
+    let snapshot = vault.open_blob(MAGIC, sealed)?;
+    let mut staged = db.as_os_str().to_owned();
+    staged.push(".restoring");
+    let staged = PathBuf::from(staged);
+    remove_if_exists(&staged)?;
+    fs::write(&staged, snapshot.as_slice())?;
+    let checked = check(&staged, vault, &name);
+    let restored = match checked {
+        Ok(restored) => restored,
+        Err(e) => {
+            remove_if_exists(&staged)?;
+            return Err(e);
+        }
+    };
+    for suffix in ["-wal", "-shm"] {
+        let mut path = db.as_os_str().to_owned();
+        path.push(suffix);
+        remove_if_exists(Path::new(&path))?;
+    }
+    fs::rename(&staged, db)?;
+    info!(backup = %name, db = %db.display(), swaps = restored.swaps, "database restored");
+    Ok(restored)
+}
+
+/// Opens the staged copy as the daemon would and checks it through.
+fn check(staged: &Path, vault: Vault, name: &str) -> Result<Restored, BackupError> {
+    let store = Store::open(staged, vault)?;
+    let integrity = store.integrity_check()?;
+    if integrity != "ok" {
+        return Err(BackupError::Invalid(format!(
+            "{name} fails the integrity check: {integrity}"
+        )));
+    }
+    Ok(Restored {
+        name: name.to_string(),
+        swaps: store.all()?.len(),
+        active: store.active()?.len(),
+        preimages: store.verify_preimages()?,
+    })
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
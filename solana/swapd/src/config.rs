@@ -0,0 +1,527 @@
+//! Typed TOML configuration for `swapd run --config`. Sections group what the flags of the same names set:
+//!
+//! ```toml
+//! network = "signet"  # profile binding the Lightning network to a Solana cluster (`swapd::network`)
+//! [solana]      # rpc-url, rpc-fallback-urls, rpc-fan-out-sends, program-id, keypair, signer,
+//!               # threshold-signer, mint, trade-fee-collector, compute-unit-price-micro-lamports
+//! [lightning]   # impl, network, bin, lnd-*, cln-rpc-socket, eclair-url, eclair-password, ldk-*
+//! [fees]        # fee-bps, flat-fee, spread-bps, max-routing-fee-bps, claim/refund-priority-fee-micro-lamports,
+//!               # claim-max-priority-fee-micro-lamports
+//! [ln-to-usdt]  # fee-bps, flat-fee, spread-bps, min-amount-msat, max-amount-msat: overrides for one direction
+//! [usdt-to-ln]  # the same
+//! [fee-policy]  # size-tiers ("MIN_AMOUNT_MSAT:BPS"), skew-bps, routing-fees, token-per-sol, min-fee-bps, max-fee-bps
+//! [limits]      # min-amount-msat, max-amount-msat, max-active-swaps, max-locked-token-amount,
+//!               # max-outstanding-msat, max-counterparty-msat, max-pending-htlc-msat
+//! [reputation]  # enabled, min-history, new-max-amount-msat, min-completion-bps, max-disputes
+//! [screening]   # allow, flag, deny (Solana addresses), large-amount-msat, large-amount-action, on-error
+//! [price]       # token-per-btc, sources, pyth-url, max-age-secs, min-sources, token-decimals
+//! [listen]      # grpc, rest, lnurl, metrics
+//! [tls]         # cert, key, self-signed
+//! [jito]        # url, normal-tip-lamports, elevated-tip-lamports, critical-tip-lamports
+//! [rate-limits] # quote-per-minute, quote-burst, create-per-minute, create-burst
+//! ```
+//!
+//! Flags and environment variables win over the file, which wins over defaults. Unknown keys, malformed
+//! values and out-of-range settings are refused before anything starts, with the line and column they are at.
+//!
+//! The offer [`Terms`] (fees and fee policy, spread, amount and exposure limits, per-direction overrides,
+//! reputation) are re-read by [`Reloader`] on SIGHUP and whenever the file changes; the rest takes a restart,
+//! which a reload that changes it warns about.
+
+use std::{
+    fmt,
+    future::Future,
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+use intercom_swap_client::keys::KeySource;
+use serde::{de, Deserialize, Deserializer};
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::watch;
+use toml::Spanned;
+use tracing::{info, warn};
+
+use crate::{feepolicy::SizeTier, kms::SignerSource, negotiate::Terms, network::Profile, screening::Action};
+
+/// How often the file's modification time is checked for a reload.
+const RELOAD_POLL: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    /// Not TOML, or a key or value the schema does not allow; the error names the line and column.
+    Parse {
+        path: PathBuf,
+        error: toml::de::Error,
+    },
+    /// Well-formed but out of range.
+    Invalid {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        key: &'static str,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read { path, error } => write!(f, "cannot read {}: {error}", path.display()),
+            Self::Parse { path, error } => write!(f, "{}: {error}", path.display()),
+            Self::Invalid {
+                path,
+                line,
+                column,
+                key,
+                reason,
+            } => write!(f, "{}:{line}:{column}: {key}: {reason}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Read { error, .. } => Some(error),
+            Self::Parse { error, .. } => Some(error),
+            Self::Invalid { .. } => None,
+        }
+    }
+}
+
+/// A value read through its `FromStr`, e.g. a base58 key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parsed<T>(pub T);
+
+impl<'de, T> Deserialize<'de> for Parsed<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map(Parsed).map_err(de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SolanaSection {
+    pub rpc_url: Option<String>,
+    pub rpc_fallback_urls: Option<Vec<String>>,
+    pub rpc_fan_out_sends: Option<bool>,
+    pub program_id: Option<Parsed<Pubkey>>,
+    /// A [`KeySource`]: `file:`, `env:`, `mnemonic-env:` or `prompt`.
+    pub keypair: Option<Parsed<KeySource>>,
+    /// A [`SignerSource`], instead of `keypair`: `aws-kms:`, `gcp-kms:` or a signing service URL.
+    pub signer: Option<Parsed<SignerSource>>,
+    /// FROST setup file, instead of `keypair` (`frost` feature).
+    pub threshold_signer: Option<PathBuf>,
+    pub mint: Option<Parsed<Pubkey>>,
+    pub trade_fee_collector: Option<Parsed<Pubkey>>,
+    pub compute_unit_price_micro_lamports: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LightningSection {
+    /// `--ln-impl` value, checked by the caller against what it was built with.
+    #[serde(rename = "impl")]
+    pub implementation: Option<Spanned<String>>,
+    pub network: Option<String>,
+    pub bin: Option<String>,
+    pub lnd_rpcserver: Option<String>,
+    pub lnd_tlscertpath: Option<PathBuf>,
+    pub lnd_macaroonpath: Option<PathBuf>,
+    pub lnd_lnddir: Option<PathBuf>,
+    pub lnd_grpc_address: Option<String>,
+    pub cln_rpc_socket: Option<PathBuf>,
+    pub eclair_url: Option<String>,
+    pub eclair_password: Option<String>,
+    pub ldk_storage_dir: Option<PathBuf>,
+    pub ldk_esplora_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FeeSection {
+    /// Operator fee on offers and quotes. Reloadable.
+    pub fee_bps: Option<Spanned<u16>>,
+    /// Token base units per swap, on top of `fee-bps`. Reloadable.
+    pub flat_fee: Option<u64>,
+    /// Oracle spread. Reloadable.
+    pub spread_bps: Option<Spanned<u16>>,
+    pub max_routing_fee_bps: Option<Spanned<u16>>,
+    pub claim_priority_fee_micro_lamports: Option<u64>,
+    pub claim_max_priority_fee_micro_lamports: Option<u64>,
+    pub refund_priority_fee_micro_lamports: Option<u64>,
+}
+
+/// Amount limits of offers and quotes, and caps on what is outstanding at once. Reloadable.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LimitSection {
+    pub min_amount_msat: Option<Spanned<u64>>,
+    pub max_amount_msat: Option<Spanned<u64>>,
+    pub max_active_swaps: Option<u64>,
+    pub max_locked_token_amount: Option<u64>,
+    pub max_outstanding_msat: Option<u64>,
+    pub max_counterparty_msat: Option<u64>,
+    pub max_pending_htlc_msat: Option<u64>,
+}
+
+/// Fees and amount limits of one direction, over the shared ones. Reloadable.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DirectionSection {
+    pub fee_bps: Option<Spanned<u16>>,
+    pub flat_fee: Option<u64>,
+    pub spread_bps: Option<Spanned<u16>>,
+    pub min_amount_msat: Option<Spanned<u64>>,
+    pub max_amount_msat: Option<Spanned<u64>>,
+}
+
+/// How each quote's fee moves off `fees.fee-bps` ([`crate::feepolicy`]). Reloadable.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FeePolicySection {
+    pub size_tiers: Option<Vec<Parsed<SizeTier>>>,
+    pub skew_bps: Option<Spanned<u16>>,
+    pub routing_fees: Option<bool>,
+    pub token_per_sol: Option<u64>,
+    pub min_fee_bps: Option<Spanned<u16>>,
+    pub max_fee_bps: Option<Spanned<u16>>,
+}
+
+/// Counterparty policy. Reloadable.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ReputationSection {
+    /// `false` quotes any counterparty.
+    pub enabled: Option<bool>,
+    pub min_history: Option<u64>,
+    pub new_max_amount_msat: Option<u64>,
+    pub min_completion_bps: Option<Spanned<u16>>,
+    pub max_disputes: Option<u64>,
+}
+
+/// Compliance screening of counterparties ([`crate::screening`]); actions are `allow`, `flag` or `deny`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ScreeningSection {
+    pub allow: Option<Vec<Parsed<Pubkey>>>,
+    pub flag: Option<Vec<Parsed<Pubkey>>>,
+    pub deny: Option<Vec<Parsed<Pubkey>>>,
+    pub large_amount_msat: Option<u64>,
+    pub large_amount_action: Option<Parsed<Action>>,
+    /// What a screener that cannot answer counts as.
+    pub on_error: Option<Parsed<Action>>,
+}
+
+/// How far an escrow may fall short of its swap's token amount ([`crate::tolerance`]).
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ToleranceSection {
+    /// Token base units.
+    pub tokens: Option<u64>,
+    pub bps: Option<Spanned<u16>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PriceSection {
+    /// Fixed price instead of the oracle.
+    pub token_per_btc: Option<u64>,
+    /// `--price-source` values, checked by the caller.
+    pub sources: Option<Spanned<Vec<String>>>,
+    pub pyth_url: Option<String>,
+    pub max_age_secs: Option<u64>,
+    pub min_sources: Option<Spanned<usize>>,
+    pub token_decimals: Option<u8>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ListenSection {
+    pub grpc: Option<Parsed<std::net::SocketAddr>>,
+    pub rest: Option<Parsed<std::net::SocketAddr>>,
+    pub lnurl: Option<Parsed<std::net::SocketAddr>>,
+    pub metrics: Option<Parsed<std::net::SocketAddr>>,
+}
+
+/// Certificate of the control APIs.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct TlsSection {
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+    /// Hostnames to generate a self-signed certificate for.
+    pub self_signed: Option<Vec<String>>,
+}
+
+/// Jito bundles for claims.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct JitoSection {
+    pub url: Option<String>,
+    pub normal_tip_lamports: Option<u64>,
+    pub elevated_tip_lamports: Option<u64>,
+    pub critical_tip_lamports: Option<u64>,
+}
+
+/// Control API limits per caller and per client address; a rate of 0 lifts the limit.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RateLimitSection {
+    pub quote_per_minute: Option<u32>,
+    pub quote_burst: Option<u32>,
+    pub create_per_minute: Option<u32>,
+    pub create_burst: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// A [`Profile`]: regtest, signet, devnet, testnet or mainnet.
+    pub network: Option<Parsed<Profile>>,
+    pub solana: SolanaSection,
+    pub lightning: LightningSection,
+    pub fees: FeeSection,
+    #[serde(rename = "ln-to-usdt")]
+    pub ln_to_usdt: DirectionSection,
+    #[serde(rename = "usdt-to-ln")]
+    pub usdt_to_ln: DirectionSection,
+    #[serde(rename = "fee-policy")]
+    pub fee_policy: FeePolicySection,
+    pub limits: LimitSection,
+    pub reputation: ReputationSection,
+    pub screening: ScreeningSection,
+    pub tolerance: ToleranceSection,
+    pub price: PriceSection,
+    pub listen: ListenSection,
+    pub tls: TlsSection,
+    pub jito: JitoSection,
+    #[serde(rename = "rate-limits")]
+    pub rate_limits: RateLimitSection,
+    /// Where it was read from, and the text, to point errors at.
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(skip)]
+    source: String,
+}
+
+impl Config {
+    /// Reads and validates `path`.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let source = std::fs::read_to_string(path).map_err(|error| ConfigError::Read {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let mut cfg: Self = toml::from_str(&source).map_err(|error| ConfigError::Parse {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        cfg.path = path.to_path_buf();
+        cfg.source = source;
+        cfg.validate()?;
+        Ok(cfg)
+    }
+
+    /// A [`ConfigError::Invalid`] at `span` of the file, for checks made by the caller.
+    pub fn error_at(&self, span: Range<usize>, key: &'static str, reason: impl Into<String>) -> ConfigError {
+        let before = &self.source[..span.start.min(self.source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        ConfigError::Invalid {
+            path: self.path.clone(),
+            line,
+            column,
+            key,
+            reason: reason.into(),
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let bps = [
+            ("fees.fee-bps", &self.fees.fee_bps),
+            ("fees.spread-bps", &self.fees.spread_bps),
+            ("fees.max-routing-fee-bps", &self.fees.max_routing_fee_bps),
+            ("ln-to-usdt.fee-bps", &self.ln_to_usdt.fee_bps),
+            ("ln-to-usdt.spread-bps", &self.ln_to_usdt.spread_bps),
+            ("usdt-to-ln.fee-bps", &self.usdt_to_ln.fee_bps),
+            ("usdt-to-ln.spread-bps", &self.usdt_to_ln.spread_bps),
+            ("fee-policy.skew-bps", &self.fee_policy.skew_bps),
+            ("fee-policy.min-fee-bps", &self.fee_policy.min_fee_bps),
+            ("fee-policy.max-fee-bps", &self.fee_policy.max_fee_bps),
+            ("reputation.min-completion-bps", &self.reputation.min_completion_bps),
+            ("tolerance.bps", &self.tolerance.bps),
+        ];
+        for (key, value) in bps {
+            if let Some(value) = value.as_ref().filter(|v| *v.get_ref() > 10_000) {
+                return Err(self.error_at(value.span(), key, "must be at most 10000"));
+            }
+        }
+        let amounts = [
+            (
+                "limits.min-amount-msat",
+                "limits.max-amount-msat",
+                &self.limits.min_amount_msat,
+                &self.limits.max_amount_msat,
+            ),
+            (
+                "ln-to-usdt.min-amount-msat",
+                "ln-to-usdt.max-amount-msat",
+                &self.ln_to_usdt.min_amount_msat,
+                &self.ln_to_usdt.max_amount_msat,
+            ),
+            (
+                "usdt-to-ln.min-amount-msat",
+                "usdt-to-ln.max-amount-msat",
+                &self.usdt_to_ln.min_amount_msat,
+                &self.usdt_to_ln.max_amount_msat,
+            ),
+        ];
+        for (min_key, max_key, min, max) in amounts {
+            if let (Some(min), Some(max)) = (min, max) {
+                if min.get_ref() > max.get_ref() {
+                    return Err(self.error_at(min.span(), min_key, format!("exceeds {max_key}")));
+                }
+            }
+        }
+        if let (Some(min), Some(max)) = (&self.fee_policy.min_fee_bps, &self.fee_policy.max_fee_bps) {
+            if min.get_ref() > max.get_ref() {
+                return Err(self.error_at(min.span(), "fee-policy.min-fee-bps", "exceeds fee-policy.max-fee-bps"));
+            }
+        }
+        if let Some(min) = self.price.min_sources.as_ref().filter(|m| *m.get_ref() == 0) {
+            return Err(self.error_at(min.span(), "price.min-sources", "must be at least 1"));
+        }
+        Ok(())
+    }
+
+    /// Whether `other` differs from this in something only a restart applies.
+    pub fn needs_restart(&self, other: &Self) -> bool {
+        self.network != other.network
+            || self.solana != other.solana
+            || self.lightning != other.lightning
+            || self.screening != other.screening
+            || self.tolerance != other.tolerance
+            || self.price != other.price
+            || self.listen != other.listen
+            || self.tls != other.tls
+            || self.jito != other.jito
+            || self.rate_limits != other.rate_limits
+            || self.fees.max_routing_fee_bps != other.fees.max_routing_fee_bps
+            || self.fees.claim_priority_fee_micro_lamports != other.fees.claim_priority_fee_micro_lamports
+            || self.fees.claim_max_priority_fee_micro_lamports != other.fees.claim_max_priority_fee_micro_lamports
+            || self.fees.refund_priority_fee_micro_lamports != other.fees.refund_priority_fee_micro_lamports
+    }
+}
+
+/// The [`Terms`] a loaded file stands for, with whatever the caller layers over it (e.g. explicit flags).
+pub type Layer = Box<dyn Fn(&Config) -> Result<Terms, ConfigError> + Send + Sync>;
+
+/// Re-reads the file on SIGHUP and when it changes, publishing the [`Terms`] it yields. A file that no
+/// longer loads is logged and the terms in force are kept.
+pub struct Reloader {
+    cfg: Config,
+    terms: watch::Sender<Terms>,
+    layer: Layer,
+}
+
+impl Reloader {
+    /// Follows `cfg`'s file from the terms `layer` gives for it.
+    pub fn new(cfg: Config, layer: Layer) -> Result<(Self, watch::Receiver<Terms>), ConfigError> {
+        let (terms, rx) = watch::channel(layer(&cfg)?);
+        Ok((Self { cfg, terms, layer }, rx))
+    }
+
+    pub async fn run(&self, shutdown: impl Future<Output = ()>) {
+        tokio::pin!(shutdown);
+        let mut hangup = hangup();
+        let mut current = self.cfg.clone();
+        let mut modified = modified(&current.path);
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => return,
+                _ = hangup.recv() => self.reload(&mut current),
+                _ = tokio::time::sleep(RELOAD_POLL) => {
+                    let now = modified(&current.path);
+                    if now != modified {
+                        modified = now;
+                        self.reload(&mut current);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Re-reads `current`'s file, replacing it once it loads.
+    fn reload(&self, current: &mut Config) {
+        let loaded = Config::load(&current.path).and_then(|cfg| Ok(((self.layer)(&cfg)?, cfg)));
+        let (terms, cfg) = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => return warn!(error = %e, "config not reloaded; keeping the current terms"),
+        };
+        if current.needs_restart(&cfg) {
+            warn!(
+                path = %cfg.path.display(),
+                "config changes outside fees, fee policy, limits, direction terms and reputation take effect on restart"
+            );
+        }
+        if self
+            .terms
+            .send_if_modified(|sent| std::mem::replace(sent, terms.clone()) != terms)
+        {
+            info!(?terms, "config reloaded");
+        }
+        *current = cfg;
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(unix)]
+fn hangup() -> Hangup {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::hangup()) {
+        Ok(signal) => Hangup(Some(signal)),
+        Err(e) => {
+            warn!(error = %e, "cannot listen for SIGHUP; reloading on file changes only");
+            Hangup(None)
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn hangup() -> Hangup {
+    Hangup(None)
+}
+
+#[cfg(unix)]
+struct Hangup(Option<tokio::signal::unix::Signal>);
+
+#[cfg(not(unix))]
+struct Hangup(Option<std::convert::Infallible>);
+
+impl Hangup {
+    /// Resolves on each SIGHUP; never where there is none.
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = &mut self.0 {
+            signal.recv().await;
+            return;
+        }
+        std::future::pending::<()>().await
+    }
+}
@@ -0,0 +1,590 @@
+//! Swap lifecycle operations behind the control APIs, independent of how requests arrive: quote, queue, look
+//! up, list, export and cancel swaps, register a taker's key for the on-chain fallback, report profit and loss,
+//! and describe the daemon. Quotes use the same
+//! terms, pricing and liquidity sizing as the offer transports. API servers cannot hold the Lightning node, so
+//! calls that need it are handed to [`LnCalls`], which runs next to the server with the node in reach. Quotes
+//! and new swaps take an optional [`IdempotencyKey`], so a client retrying after a lost response gets the first
+//! result back instead of a second quote or swap.
+
+use std::{fmt, future::Future, sync::Arc};
+
+use bitcoin::secp256k1::XOnlyPublicKey;
+use sha2::{Digest, Sha256};
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
+use tokio::sync::{broadcast, mpsc, oneshot, Notify};
+use tracing::info;
+
+use crate::{
+    admin::AdminError,
+    auth::AuthError,
+    export::{self, Period},
+    exposure::Exposure,
+    journal::Cause,
+    ln::{ChannelBalance, LnBackend, LnError},
+    negotiate::{Maker, NegotiateError},
+    onchain::{HtlcRecord, Registration},
+    pnl::{self, Report},
+    quote::{self, Quote, QuoteError, QuoteRequest},
+    ratelimit::RateLimited,
+    retries::DeadLetter,
+    store::{Store, StoreError},
+    swap::{unix_now, Direction, Swap},
+};
+
+/// Funding timeout of usdt-to-ln swaps created without one, as on the command line.
+const DEFAULT_FUNDING_TIMEOUT_SECS: i64 = 1800;
+/// How long an idempotency key keeps answering with its first result. Retries come within minutes; a day also
+/// covers a client that retries after its own restart.
+pub const IDEMPOTENCY_RETENTION_SECS: i64 = 24 * 3600;
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// Why a control call failed, in classes each API maps to its own status codes.
+#[derive(Debug)]
+pub enum ControlError {
+    InvalidArgument(String),
+    NotFound(String),
+    AlreadyExists(String),
+    /// The swap or quote is past the point where the call applies.
+    FailedPrecondition(String),
+    /// Liquidity or the exposure limits cannot fill the amount.
+    InsufficientLiquidity(String),
+    /// The counterparty's record rules the quote out.
+    Rejected(String),
+    /// No price right now, or the daemon is stopping.
+    Unavailable(String),
+    /// No valid credentials.
+    Unauthenticated(String),
+    /// The credentials' scope does not cover the call.
+    PermissionDenied(String),
+    /// The caller or its address made too many such calls lately.
+    RateLimited(RateLimited),
+    Internal(String),
+}
+
+impl fmt::Display for ControlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidArgument(e)
+            | Self::NotFound(e)
+            | Self::AlreadyExists(e)
+            | Self::FailedPrecondition(e)
+            | Self::InsufficientLiquidity(e)
+            | Self::Rejected(e)
+            | Self::Unavailable(e)
+            | Self::Unauthenticated(e)
+            | Self::PermissionDenied(e)
+            | Self::Internal(e) => f.write_str(e),
+            Self::RateLimited(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ControlError {}
+
+impl From<StoreError> for ControlError {
+    fn from(e: StoreError) -> Self {
+        Self::Internal(e.to_string())
+    }
+}
+
+impl From<RateLimited> for ControlError {
+    fn from(e: RateLimited) -> Self {
+        Self::RateLimited(e)
+    }
+}
+
+impl From<AuthError> for ControlError {
+    fn from(e: AuthError) -> Self {
+        let message = e.to_string();
+        match e {
+            AuthError::Missing | AuthError::Invalid(_) | AuthError::Expired => Self::Unauthenticated(message),
+            AuthError::Forbidden { .. } => Self::PermissionDenied(message),
+            AuthError::Store(_) => Self::Internal(message),
+        }
+    }
+}
+
+impl From<QuoteError> for ControlError {
+    fn from(e: QuoteError) -> Self {
+        let message = e.to_string();
+        match e {
+            QuoteError::AmountOutOfRange { .. }
+            | QuoteError::TooSmall
+            | QuoteError::Malformed(_)
+            | QuoteError::BadSignature
+            | QuoteError::Invoice(_) => Self::InvalidArgument(message),
+            QuoteError::InsufficientLiquidity { .. } | QuoteError::OverLimit(_) => Self::InsufficientLiquidity(message),
+            QuoteError::Rejected(_) => Self::Rejected(message),
+            QuoteError::Expired { .. } | QuoteError::NoRoute(_) => Self::FailedPrecondition(message),
+            QuoteError::Unknown => Self::NotFound(message),
+            QuoteError::AlreadyAccepted { .. } => Self::AlreadyExists(message),
+            QuoteError::Store(_) => Self::Internal(message),
+        }
+    }
+}
+
+impl From<NegotiateError> for ControlError {
+    fn from(e: NegotiateError) -> Self {
+        match e {
+            NegotiateError::Quote(e) => e.into(),
+            NegotiateError::Store(e) => e.into(),
+            NegotiateError::NoPrice(_) => Self::Unavailable(e.to_string()),
+            NegotiateError::Malformed(_) => Self::InvalidArgument(e.to_string()),
+            NegotiateError::UnknownSwap(_) => Self::NotFound(e.to_string()),
+        }
+    }
+}
+
+impl From<AdminError> for ControlError {
+    fn from(e: AdminError) -> Self {
+        let message = e.to_string();
+        match e {
+            AdminError::Invalid(_) | AdminError::Expired | AdminError::Stale(_) => Self::FailedPrecondition(message),
+            AdminError::UnknownPreview(_) => Self::NotFound(message),
+            AdminError::Rejected(_) => Self::Rejected(message),
+            AdminError::Fetch(_) | AdminError::Send(_) | AdminError::NotLanded => Self::Unavailable(message),
+            AdminError::Signer(_) => Self::Internal(message),
+        }
+    }
+}
+
+pub fn parse_pubkey(s: &str, field: &str) -> Result<Pubkey, ControlError> {
+    s.parse()
+        .map_err(|_| ControlError::InvalidArgument(format!("{field} is not a Solana key")))
+}
+
+/// A Bitcoin x-only public key: 32 bytes of hex.
+pub fn parse_claim_key(s: &str, field: &str) -> Result<XOnlyPublicKey, ControlError> {
+    s.parse()
+        .map_err(|_| ControlError::InvalidArgument(format!("{field} is not an x-only public key")))
+}
+
+/// A Lightning node id: 33 bytes of hex, compressed.
+pub fn parse_node_id(s: &str, field: &str) -> Result<[u8; 33], ControlError> {
+    hex::decode(s)
+        .ok()
+        .and_then(|b| <[u8; 33]>::try_from(b).ok())
+        .filter(|b| matches!(b[0], 2 | 3))
+        .ok_or_else(|| ControlError::InvalidArgument(format!("{field} is not a Lightning node id")))
+}
+
+pub fn parse_quote_id(s: &str) -> Result<[u8; 16], ControlError> {
+    hex::decode(s)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| ControlError::InvalidArgument("quote id must be 16 bytes of hex".into()))
+}
+
+/// A caller's key for one quote or swap creation. Keys are scoped to the caller, so two clients picking the
+/// same key do not collide.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotencyKey {
+    caller: String,
+    key: String,
+}
+
+/// `key` as sent by `caller`; an empty key means the call has none.
+pub fn idempotency_key(caller: &str, key: &str) -> Result<Option<IdempotencyKey>, ControlError> {
+    if key.is_empty() {
+        return Ok(None);
+    }
+    if key.len() > MAX_IDEMPOTENCY_KEY_LEN || !key.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(ControlError::InvalidArgument(format!(
+            "idempotency key must be at most {MAX_IDEMPOTENCY_KEY_LEN} printable ASCII characters"
+        )));
+    }
+    Ok(Some(IdempotencyKey {
+        caller: caller.to_string(),
+        key: key.to_string(),
+    }))
+}
+
+/// The request an idempotency key was first used for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotentRequest {
+    /// Hash of the operation and its arguments, so reusing a key for a different request is caught.
+    pub fingerprint: String,
+    /// Id of the quote or swap it produced; `None` while it is still running.
+    pub result: Option<String>,
+}
+
+/// A swap to queue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NewSwap {
+    /// The swap of a quote this daemon issued.
+    AcceptQuote {
+        quote_id: [u8; 16],
+        /// The taker's invoice for exactly the quoted amount; usdt-to-ln only.
+        bolt11: Option<String>,
+        hold: bool,
+        funding_timeout_secs: Option<i64>,
+    },
+    LnToUsdt {
+        recipient: Pubkey,
+        amount_msat: u64,
+        token_amount: u64,
+        hold: bool,
+    },
+    UsdtToLn {
+        bolt11: String,
+        refund: Pubkey,
+        token_amount: u64,
+        funding_timeout_secs: Option<i64>,
+    },
+}
+
+/// What the daemon is and offers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Info {
+    pub version: &'static str,
+    pub operator: Pubkey,
+    pub mint: Pubkey,
+    pub directions: Vec<Direction>,
+    /// Shared terms; `direction_terms` has what each direction is actually quoted at.
+    pub fee_bps: u16,
+    pub min_amount_msat: u64,
+    pub max_amount_msat: u64,
+    pub direction_terms: Vec<DirectionInfo>,
+    pub active_swaps: u64,
+    /// `None` when the node cannot be reached.
+    pub channel_balance: Option<ChannelBalance>,
+}
+
+/// The fee and size limits quotes in one direction get.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirectionInfo {
+    pub direction: Direction,
+    pub fee_bps: u16,
+    /// Token base units per swap, on top of `fee_bps`.
+    pub flat_fee: u64,
+    pub min_amount_msat: u64,
+    pub max_amount_msat: u64,
+}
+
+/// A call that needs the Lightning node.
+enum LnCall {
+    Quote(QuoteRequest, oneshot::Sender<Result<Quote, NegotiateError>>),
+    Balance(oneshot::Sender<Result<ChannelBalance, LnError>>),
+}
+
+/// Answers the calls a [`Control`] hands over.
+pub struct LnCalls {
+    maker: Arc<Maker>,
+    pending: mpsc::Receiver<LnCall>,
+}
+
+impl LnCalls {
+    /// Answers calls with `ln` until `server` finishes, and returns its output.
+    pub async fn answer_until<L: LnBackend, T>(mut self, ln: &L, server: impl Future<Output = T>) -> T {
+        tokio::pin!(server);
+        loop {
+            tokio::select! {
+                output = &mut server => return output,
+                Some(call) = self.pending.recv() => match call {
+                    LnCall::Quote(request, reply) => {
+                        let _ = reply.send(self.maker.quote(ln, &request).await);
+                    }
+                    LnCall::Balance(reply) => {
+                        let _ = reply.send(ln.channel_balance().await);
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// The operations, cheap to clone into request handlers. Swaps it queues or cancels are picked up by the
+/// engine that owns `wake`.
+#[derive(Clone)]
+pub struct Control {
+    maker: Arc<Maker>,
+    wake: Arc<Notify>,
+    calls: mpsc::Sender<LnCall>,
+}
+
+impl Control {
+    pub fn new(maker: Arc<Maker>, wake: Arc<Notify>) -> (Self, LnCalls) {
+        let (calls, pending) = mpsc::channel(16);
+        let control = Self {
+            maker: maker.clone(),
+            wake,
+            calls,
+        };
+        (control, LnCalls { maker, pending })
+    }
+
+    fn store(&self) -> &Store {
+        &self.maker.negotiator.store
+    }
+
+    async fn call<T>(&self, call: impl FnOnce(oneshot::Sender<T>) -> LnCall) -> Result<T, ControlError> {
+        let (reply, answer) = oneshot::channel();
+        let stopping = || ControlError::Unavailable("swapd is shutting down".into());
+        self.calls.send(call(reply)).await.map_err(|_| stopping())?;
+        answer.await.map_err(|_| stopping())
+    }
+
+    pub async fn info(&self) -> Result<Info, ControlError> {
+        let cfg = &self.maker.cfg;
+        let terms = self.maker.terms();
+        Ok(Info {
+            version: env!("CARGO_PKG_VERSION"),
+            operator: self.maker.negotiator.operator.pubkey(),
+            mint: cfg.quote.mint,
+            directions: cfg.directions.clone(),
+            fee_bps: terms.fee_bps,
+            min_amount_msat: terms.min_amount_msat,
+            max_amount_msat: terms.max_amount_msat,
+            direction_terms: cfg
+                .directions
+                .iter()
+                .map(|&direction| {
+                    let terms = terms.for_direction(direction);
+                    DirectionInfo {
+                        direction,
+                        fee_bps: terms.fee_bps,
+                        flat_fee: terms.flat_fee,
+                        min_amount_msat: terms.min_amount_msat,
+                        max_amount_msat: terms.max_amount_msat,
+                    }
+                })
+                .collect(),
+            active_swaps: self.store().active()?.len() as u64,
+            channel_balance: self.call(LnCall::Balance).await?.ok(),
+        })
+    }
+
+    /// A repeat under `key` returns the first quote, even once it has expired, rather than quoting anew.
+    pub async fn quote(&self, request: QuoteRequest, key: Option<&IdempotencyKey>) -> Result<Quote, ControlError> {
+        if let Some(id) = self.claim(key, "quote", &request)? {
+            let record = self.store().quote(&parse_quote_id(&id)?)?;
+            return record
+                .map(|r| r.quote)
+                .ok_or_else(|| ControlError::NotFound(format!("no quote {id}")));
+        }
+        let quote = self.call(|reply| LnCall::Quote(request, reply)).await;
+        let quote = quote.and_then(|quote| Ok(quote?));
+        self.settle(key, quote.as_ref().ok().map(|q| hex::encode(q.id)))?;
+        quote
+    }
+
+    /// A repeat under `key` returns the swap the first call queued, as it is now.
+    pub fn create(&self, new: NewSwap, key: Option<&IdempotencyKey>) -> Result<Swap, ControlError> {
+        if let Some(id) = self.claim(key, "create_swap", &new)? {
+            return self.get(&id);
+        }
+        let swap = self.queue_new(new);
+        self.settle(key, swap.as_ref().ok().map(|s| s.id.clone()))?;
+        let swap = swap?;
+        info!(swap = %swap.id, direction = %swap.direction, "swap queued over the control api");
+        self.wake.notify_one();
+        Ok(swap)
+    }
+
+    /// Claims `key` for `operation` with `request`: `Some` with the id of the first call's result when this
+    /// repeats a finished call, `None` when the call should go ahead.
+    fn claim(
+        &self,
+        key: Option<&IdempotencyKey>,
+        operation: &str,
+        request: &impl fmt::Debug,
+    ) -> Result<Option<String>, ControlError> {
+        let Some(key) = key else {
+            return Ok(None);
+        };
+        let fingerprint = hex::encode(Sha256::digest(format!("{operation} {request:?}")));
+        let now = unix_now();
+        let earlier = self.store().claim_idempotency_key(
+            &key.caller,
+            &key.key,
+            &fingerprint,
+            now,
+            now - IDEMPOTENCY_RETENTION_SECS,
+        )?;
+        match earlier {
+            None => Ok(None),
+            Some(earlier) if earlier.fingerprint != fingerprint => Err(ControlError::InvalidArgument(format!(
+                "idempotency key {} was used for a different request",
+                key.key
+            ))),
+            Some(IdempotentRequest { result: Some(id), .. }) => Ok(Some(id)),
+            Some(_) => Err(ControlError::Unavailable(format!(
+                "a request with idempotency key {} is still in progress",
+                key.key
+            ))),
+        }
+    }
+
+    /// Records the id of what the call holding `key` produced, or releases the key when it failed so that a
+    /// retry runs again.
+    fn settle(&self, key: Option<&IdempotencyKey>, result: Option<String>) -> Result<(), ControlError> {
+        let Some(key) = key else {
+            return Ok(());
+        };
+        match result {
+            Some(id) => self.store().finish_idempotency_key(&key.caller, &key.key, &id)?,
+            None => self.store().release_idempotency_key(&key.caller, &key.key)?,
+        }
+        Ok(())
+    }
+
+    fn queue_new(&self, new: NewSwap) -> Result<Swap, ControlError> {
+        let funding_timeout = |secs: Option<i64>| secs.filter(|&s| s > 0).unwrap_or(DEFAULT_FUNDING_TIMEOUT_SECS);
+        Ok(match new {
+            NewSwap::AcceptQuote {
+                quote_id,
+                bolt11,
+                hold,
+                funding_timeout_secs,
+            } => {
+                let record = self
+                    .store()
+                    .quote(&quote_id)?
+                    .ok_or_else(|| ControlError::NotFound(format!("no quote {}", hex::encode(quote_id))))?;
+                quote::accept(
+                    self.store(),
+                    &self.maker.negotiator.operator.pubkey(),
+                    &record.quote,
+                    bolt11.as_deref(),
+                    hold,
+                    funding_timeout(funding_timeout_secs),
+                    &self.maker.negotiator.screening,
+                    self.maker.negotiator.transport,
+                )?
+            }
+            NewSwap::LnToUsdt {
+                recipient,
+                amount_msat,
+                token_amount,
+                hold,
+            } => self.queue(Swap::ln_to_usdt(recipient, amount_msat, token_amount, hold))?,
+            NewSwap::UsdtToLn {
+                bolt11,
+                refund,
+                token_amount,
+                funding_timeout_secs,
+            } => {
+                let timeout = funding_timeout(funding_timeout_secs);
+                let swap = Swap::usdt_to_ln(&bolt11, refund, token_amount, timeout)
+                    .map_err(|e| ControlError::InvalidArgument(e.to_string()))?;
+                self.queue(swap)?
+            }
+        })
+    }
+
+    /// Queues a swap made without a quote, once it fits the exposure limits a quote would have been sized to and
+    /// passes screening.
+    fn queue(&self, swap: Swap) -> Result<Swap, ControlError> {
+        let limits = self.maker.terms().exposure;
+        if !limits.is_unlimited() {
+            let exposure = Exposure::load(self.store(), &swap.counterparty)?;
+            limits
+                .check(&exposure, swap.direction, swap.amount_msat, swap.token_amount)
+                .map_err(QuoteError::OverLimit)?;
+        }
+        let negotiator = &self.maker.negotiator;
+        negotiator
+            .screening
+            .check(self.store(), &swap, negotiator.transport)
+            .map_err(QuoteError::from)?;
+        if !self.store().insert(&swap, &Cause::new(negotiator.transport))? {
+            return Err(ControlError::AlreadyExists(format!("swap {} already exists", swap.id)));
+        }
+        Ok(swap)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Swap, ControlError> {
+        self.store()
+            .get(id)?
+            .ok_or_else(|| ControlError::NotFound(format!("no swap {id}")))
+    }
+
+    /// Newest first; `limit` 0 lists all.
+    pub fn list(&self, active: bool, limit: usize) -> Result<Vec<Swap>, ControlError> {
+        let mut swaps = if active {
+            let mut active = self.store().active()?;
+            active.reverse();
+            active
+        } else {
+            self.store().all()?
+        };
+        if limit > 0 {
+            swaps.truncate(limit);
+        }
+        Ok(swaps)
+    }
+
+    /// Every swap with `account` as its counterparty, newest first.
+    pub fn swaps_of(&self, account: &Pubkey) -> Result<Vec<Swap>, ControlError> {
+        Ok(self.store().swaps_of(account)?)
+    }
+
+    /// Accounting CSV of the swaps created in `period`; see [`export`](crate::export).
+    pub fn export(&self, period: &Period) -> Result<String, ControlError> {
+        Ok(export::csv(self.store(), period)?)
+    }
+
+    /// Profit and loss of the swaps created in `period`, with the inventory marked at the current price; see
+    /// [`pnl`](crate::pnl).
+    pub async fn pnl(&self, period: &Period) -> Result<Report, ControlError> {
+        let mark = self.maker.mark_token_per_btc().await;
+        Ok(pnl::report(self.store(), period, mark)?)
+    }
+
+    /// Swap steps given up on, newest first; resolved ones too if `resolved`. See [`retries`](crate::retries).
+    pub fn dead_letters(&self, resolved: bool) -> Result<Vec<DeadLetter>, ControlError> {
+        Ok(self.store().dead_letters(resolved)?)
+    }
+
+    /// Resolves dead letter `id` and wakes the engine to step its swap again.
+    pub fn retry_dead_letter(&self, id: i64) -> Result<Swap, ControlError> {
+        let swap_id = self
+            .store()
+            .resolve_dead_letter(id, unix_now())?
+            .ok_or_else(|| ControlError::NotFound(format!("no open dead letter {id}")))?;
+        info!(dead_letter = id, swap = %swap_id, "dead letter resolved; retrying the swap");
+        self.wake.notify_one();
+        self.get(&swap_id)
+    }
+
+    /// Registers `claim_key` to be paid through an on-chain HTLC should the Lightning payment of usdt-to-ln
+    /// swap `id` keep failing; see [`onchain`](crate::onchain). Only until an HTLC is opened.
+    pub fn register_onchain_key(&self, id: &str, claim_key: &XOnlyPublicKey) -> Result<Swap, ControlError> {
+        let swap = self.get(id)?;
+        if let Some(refusal) = Registration::refusal(&swap, self.store().htlc(id)?.as_ref()) {
+            return Err(ControlError::FailedPrecondition(refusal));
+        }
+        self.store().register_onchain_claim_key(id, claim_key, unix_now())?;
+        info!(swap = %id, claim_key = %claim_key, "on-chain claim key registered");
+        Ok(swap)
+    }
+
+    /// The on-chain HTLC opened for swap `id`.
+    pub fn htlc(&self, id: &str) -> Result<HtlcRecord, ControlError> {
+        self.store()
+            .htlc(id)?
+            .ok_or_else(|| ControlError::NotFound(format!("swap {id} has no on-chain HTLC")))
+    }
+
+    /// Swaps as they are written; see [`Store::subscribe`].
+    pub fn subscribe(&self) -> broadcast::Receiver<Swap> {
+        self.store().subscribe()
+    }
+
+    /// Flags the swap for the engine to cancel while it still can; the engine re-checks when it acts, in
+    /// case the swap moved on since.
+    pub fn cancel(&self, id: &str) -> Result<Swap, ControlError> {
+        let mut swap = self.get(id)?;
+        let cause = Cause::new(self.maker.negotiator.transport).because("cancel requested over the control api");
+        if !swap.is_cancellable() || !self.store().request_cancel(id, &cause)? {
+            return Err(ControlError::FailedPrecondition(format!(
+                "swap {id} can no longer be cancelled in state {}",
+                swap.state
+            )));
+        }
+        swap.cancel_requested = true;
+        info!(swap = %id, "cancel requested over the control api");
+        self.wake.notify_one();
+        Ok(swap)
+    }
+}
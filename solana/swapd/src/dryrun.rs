@@ -0,0 +1,129 @@
+//! Dry runs: the engine drives swaps through their state machine as usual, reading the cluster and the Lightning
+//! node, but every step that would move funds or change the node is recorded here instead. Transactions are
+//! simulated (`simulateTransaction`, signatures not checked) and invoices decoded, so the report shows what each
+//! swap would do next and whether it would work, before the daemon is trusted with real money.
+//!
+//! A dry run works on a scratch copy of the database, so the transitions it writes never reach the real one.
+
+use std::{
+    collections::HashSet,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+use crate::{
+    journal::Cause,
+    store::{Store, StoreError},
+    swap::{unix_now, Direction, Swap, SwapState},
+    vault::Vault,
+};
+
+/// One thing the engine would have done.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Action {
+    pub swap_id: String,
+    pub direction: Direction,
+    /// State the swap was in when the engine got to the action.
+    pub state: SwapState,
+    /// E.g. `create_invoice`, `pay_invoice` or `send_init`.
+    pub kind: &'static str,
+    /// The action's inputs, and for transactions the simulation's outcome.
+    pub detail: Value,
+    pub at: i64,
+}
+
+impl Action {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "swapId": self.swap_id,
+            "direction": self.direction.as_str(),
+            "state": self.state.as_str(),
+            "action": self.kind,
+            "detail": self.detail,
+            "at": self.at,
+        })
+    }
+}
+
+/// Collects the actions of a dry run, once per swap, state and kind however often the engine retries them, and
+/// appends each to the report file as a JSON line.
+pub struct DryRun {
+    report: Option<Mutex<File>>,
+    seen: Mutex<HashSet<(String, SwapState, &'static str)>>,
+    actions: Mutex<Vec<Action>>,
+}
+
+impl DryRun {
+    /// A dry run reporting to `report`, appended to if it exists; `None` keeps the actions in memory only.
+    pub fn new(report: Option<&Path>) -> std::io::Result<Self> {
+        let report = report
+            .map(|path| OpenOptions::new().create(true).append(true).open(path))
+            .transpose()?;
+        Ok(Self {
+            report: report.map(Mutex::new),
+            seen: Mutex::default(),
+            actions: Mutex::default(),
+        })
+    }
+
+    /// Records that `swap` would take action `kind` now.
+    pub fn record(&self, swap: &Swap, kind: &'static str, detail: Value) {
+        let key = (swap.id.clone(), swap.state, kind);
+        if !self.seen.lock().unwrap_or_else(|e| e.into_inner()).insert(key) {
+            return;
+        }
+        let action = Action {
+            swap_id: swap.id.clone(),
+            direction: swap.direction,
+            state: swap.state,
+            kind,
+            detail,
+            at: unix_now(),
+        };
+        info!(swap = %swap.id, state = %swap.state, action = kind, detail = %action.detail, "dry run: would act");
+        if let Some(report) = &self.report {
+            let mut file = report.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(e) = writeln!(file, "{}", action.to_json()) {
+                warn!(error = %e, "cannot write the dry-run report");
+            }
+        }
+        self.actions.lock().unwrap_or_else(|e| e.into_inner()).push(action);
+    }
+
+    /// Every action recorded so far, oldest first.
+    pub fn actions(&self) -> Vec<Action> {
+        self.actions.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// Where the scratch copy of `db` goes.
+pub fn scratch_path(db: &Path) -> PathBuf {
+    db.with_extension("dry-run.db")
+}
+
+/// Copies `store`, swaps included wherever they are kept, to a fresh scratch database beside `db` and opens it
+/// with `vault`, replacing any copy left by an earlier dry run.
+pub fn scratch(store: &Store, db: &Path, vault: Vault) -> Result<Store, StoreError> {
+    let path = scratch_path(db);
+    for stale in [
+        path.clone(),
+        path.with_extension("db-wal"),
+        path.with_extension("db-shm"),
+    ] {
+        let _ = std::fs::remove_file(stale);
+    }
+    store.snapshot(&path)?;
+    let scratch = Store::open(&path, vault)?;
+    // Swaps kept in a shared database are not in the file; the copy gets them too.
+    if store.swap_store() != scratch.swap_store() {
+        for swap in store.all()? {
+            scratch.insert(&swap, &Cause::new("dry-run").because("copied from the swap database"))?;
+        }
+    }
+    Ok(scratch)
+}
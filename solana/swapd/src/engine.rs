@@ -0,0 +1,1408 @@
+//! Drives every active swap through its state machine.
+//!
+//! Each tick re-reads the active swaps from the store and advances each one until it blocks on something
+//! external (a Lightning payment, the user's claim, a timeout). Every step first looks at the external
+//! state it is about to change: the escrow account before Init/Claim/Refund, the invoice before creating it,
+//! the payment before paying. So a step interrupted by a crash is finished rather than repeated on restart,
+//! and the escrow PDA (one per payment hash) makes a second Init for the same swap impossible anyway.
+
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use intercom_swap_client::{
+    bolt11::{check_invoice, InvoiceCheck},
+    client::EscrowClient,
+    error::{EscrowError, SwapClientError},
+    instruction::{InitArgs, RefundAfter},
+    retry::{RetryPolicy, SendOutcome},
+    rpc::EscrowRpc,
+    state::{EscrowState, EscrowStatus},
+    transaction::{self, TxOptions},
+};
+use serde_json::json;
+use solana_sdk::{hash::hash, pubkey::Pubkey, signer::Signer, transaction::Transaction};
+use tokio::{
+    sync::{mpsc, Notify},
+    time::Instant,
+};
+use tracing::{debug, error, info, warn, Instrument, Span};
+use zeroize::Zeroizing;
+
+use crate::{
+    dryrun::DryRun,
+    error::SwapError,
+    jito::{Jito, JitoConfig, Urgency},
+    journal::Cause,
+    kms::Operator,
+    ledger::{self, Entry},
+    ln::{decode_invoice, Description, InvoiceStatus, Keysend, LnBackend, PaymentStatus},
+    metrics::metrics,
+    onchain::{Fallback, Progress},
+    priority::{FeeControl, FeeController},
+    reputation::Outcome,
+    retries::{StepRetry, StepRetryPolicy},
+    safety::{CltvSafety, SafetyError},
+    store::{Store, StoreError},
+    swap::{unix_now, Direction, Swap, SwapState},
+    tolerance::{AmountCheck, AmountTolerance},
+    vault::Preimage,
+};
+
+/// A signature recorded less than this long ago may still land, so the swap waits instead of giving up.
+const LANDING_WINDOW_SECS: i64 = 90;
+/// Claim rounds per step when every signature expires unseen (each round re-signs per the retry policy).
+const CLAIM_ROUNDS: u32 = 3;
+/// Store cursor: highest node invoice index already scanned for keysends.
+const KEYSEND_CURSOR: &str = "keysend_index";
+/// Alert once a swap's claim has failed this many times in a row.
+const CLAIM_FAILURE_ALERT: u32 = 3;
+/// Failure reason of swaps stopped by [`Store::request_cancel`].
+const CANCELLED: &str = "cancelled on request";
+
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    pub mint: Pubkey,
+    /// Trade fee collector written into escrows the daemon funds.
+    pub trade_fee_collector: Pubkey,
+    pub poll_interval: Duration,
+    /// LnToUsdt: lifetime of our invoice.
+    pub invoice_expiry_secs: u64,
+    /// LnToUsdt: refund_after of our escrow, relative to cluster time at Init.
+    pub refund_delay_secs: i64,
+    /// Minimum gap between invoice expiry and refund_after, in both directions: time for whoever learns the
+    /// preimage to claim before the escrow can be refunded.
+    pub claim_margin_secs: i64,
+    /// UsdtToLn: routing fee budget per payment, in basis points of the invoice amount.
+    pub max_routing_fee_bps: u16,
+    /// UsdtToLn: least compute unit price of claims, which have to land before refund_after. The higher of this
+    /// and `tx`'s price is used; recent fees, landing rates and the deadline raise it from there.
+    pub claim_priority_fee_micro_lamports: u64,
+    /// UsdtToLn: most a claim pays per compute unit.
+    pub claim_max_priority_fee_micro_lamports: u64,
+    /// UsdtToLn: alert, and pay the top percentile of recent fees for claims, once less than this remains
+    /// before refund_after.
+    pub claim_alert_margin_secs: i64,
+    /// On shutdown, how long to keep stepping swaps whose claim is due within `claim_alert_margin_secs`.
+    pub shutdown_grace: Duration,
+    /// Names this instance's leases on the swaps it drives, among instances sharing a swap store.
+    pub instance_id: String,
+    /// How long a lease holds without being renewed; the swaps of an instance that died go to another after it.
+    pub lease: Duration,
+    /// UsdtToLn: bounds the payment's route CLTV so it resolves `claim_margin_secs` before refund_after.
+    pub cltv: CltvSafety,
+    /// Match settled keysends against keysend quotes each tick.
+    pub keysend: bool,
+    /// UsdtToLn: also submit claims as Jito bundles, tipped by how close refund_after is.
+    pub jito: Option<JitoConfig>,
+    pub tx: TxOptions,
+    pub retry: RetryPolicy,
+    /// How a step that failed is retried before its swap is dead-lettered.
+    pub step_retry: StepRetryPolicy,
+    /// How far an escrow may fall short of its swap's token amount.
+    pub amount_tolerance: AmountTolerance,
+}
+
+impl EngineConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        self.cltv.validate()?;
+        if self.claim_max_priority_fee_micro_lamports < self.claim_fee_control().min_micro_lamports {
+            return Err("claim max priority fee must be at least the claim priority fee".into());
+        }
+        let min_delay = self.invoice_expiry_secs as i64 + self.claim_margin_secs;
+        if self.refund_delay_secs < min_delay {
+            return Err(format!(
+                "refund delay {}s must be at least invoice expiry plus claim margin ({min_delay}s)",
+                self.refund_delay_secs
+            ));
+        }
+        Ok(())
+    }
+
+    fn claim_fee_control(&self) -> FeeControl {
+        FeeControl {
+            min_micro_lamports: self
+                .tx
+                .compute_unit_price_micro_lamports
+                .unwrap_or(0)
+                .max(self.claim_priority_fee_micro_lamports),
+            max_micro_lamports: self.claim_max_priority_fee_micro_lamports,
+        }
+    }
+}
+
+/// Where an active swap stood when the daemon shut down. Its state is in the swap row already; the rest only
+/// lived in memory.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub swap_id: String,
+    pub state: SwapState,
+    /// A `pay_invoice` call was still running. Its outcome is read back from the node.
+    pub payment_in_flight: bool,
+    /// Failed claims in a row, so the repeated-failure alert keeps counting across the restart.
+    pub claim_failures: u32,
+    pub taken_at: i64,
+}
+
+/// Result of sending one of the daemon's transactions.
+enum Sent {
+    Confirmed,
+    Failed(SwapClientError),
+    /// Never landed; safe to rebuild on the next tick.
+    Expired,
+    /// Dry run: simulated and recorded, never sent.
+    Simulated,
+}
+
+pub struct Engine<L> {
+    store: Arc<Store>,
+    client: EscrowClient,
+    operator: Arc<Operator>,
+    ln: Arc<L>,
+    cfg: EngineConfig,
+    jito: Option<Jito>,
+    fees: FeeController,
+    /// Payment hashes with a `pay_invoice` call running in this process.
+    paying: Arc<Mutex<HashSet<[u8; 32]>>>,
+    /// Failed claims in a row per swap id, since the daemon started.
+    claim_failures: Mutex<HashMap<String, u32>>,
+    /// Cuts the poll sleep short, e.g. when a payment settles and its claim should go out immediately.
+    wake: Arc<Notify>,
+    /// Records what would be sent or changed on the node instead of doing it.
+    dry_run: Option<Arc<DryRun>>,
+    /// Pays registered usdt-to-ln takers through an on-chain HTLC once Lightning keeps failing.
+    onchain: Option<Arc<Fallback>>,
+}
+
+impl<L: LnBackend> Engine<L> {
+    pub fn new(store: Arc<Store>, client: EscrowClient, operator: Arc<Operator>, ln: L, cfg: EngineConfig) -> Self {
+        Self {
+            store,
+            client,
+            operator,
+            ln: Arc::new(ln),
+            jito: cfg.jito.clone().map(Jito::new),
+            fees: FeeController::new(cfg.claim_fee_control()),
+            cfg,
+            paying: Arc::default(),
+            claim_failures: Mutex::default(),
+            wake: Arc::default(),
+            dry_run: None,
+            onchain: None,
+        }
+    }
+
+    /// Records every transaction and node change in `dry_run` instead of making it; reads still go to the
+    /// cluster and the node. Give it a scratch store (see [`crate::dryrun::scratch`]).
+    pub fn with_dry_run(mut self, dry_run: Arc<DryRun>) -> Self {
+        self.dry_run = Some(dry_run);
+        self
+    }
+
+    /// Falls back to an on-chain HTLC for swaps whose taker registered a claim key; see [`crate::onchain`].
+    pub fn with_onchain(mut self, onchain: Arc<Fallback>) -> Self {
+        self.onchain = Some(onchain);
+        self
+    }
+
+    /// In a dry run, records `kind` for `swap` and returns `true`: the caller skips the action.
+    fn dry(&self, swap: &Swap, kind: &'static str, detail: impl FnOnce() -> serde_json::Value) -> bool {
+        let Some(dry_run) = &self.dry_run else {
+            return false;
+        };
+        dry_run.record(swap, kind, detail());
+        true
+    }
+
+    /// Wakes [`Self::run`] for an immediate tick, e.g. after queueing a swap the caller is waiting on.
+    pub fn waker(&self) -> Arc<Notify> {
+        self.wake.clone()
+    }
+
+    pub fn operator(&self) -> Pubkey {
+        self.operator.pubkey()
+    }
+
+    pub fn ln(&self) -> &L {
+        &self.ln
+    }
+
+    /// Ticks every `poll_interval` until `shutdown` resolves, and right away whenever the node reports a
+    /// settled invoice. Then hands over to the next instance (see [`Self::hand_over`]).
+    pub async fn run(&self, shutdown: impl Future<Output = ()>) {
+        self.resume();
+        tokio::pin!(shutdown);
+        let (settled_tx, mut settled) = mpsc::channel(64);
+        let watch = self.ln.watch_settlements(settled_tx);
+        tokio::pin!(watch);
+        let mut watching = true;
+        loop {
+            self.tick().await;
+            tokio::select! {
+                _ = &mut shutdown => break,
+                _ = tokio::time::sleep(self.cfg.poll_interval) => {}
+                _ = self.wake.notified() => {}
+                Some(hash) = settled.recv() => debug!(payment_hash = %hex::encode(hash), "invoice settled"),
+                _ = &mut watch, if watching => watching = false,
+            }
+        }
+        self.hand_over().await;
+    }
+
+    /// One pass over the active swaps. Those waiting out a failed step's backoff or dead-lettered are skipped,
+    /// unless their claim is due.
+    pub async fn tick(&self) {
+        if self.cfg.keysend {
+            if let Err(e) = self.scan_keysends().await {
+                warn!(error = %e, "keysend scan failed; retrying next tick");
+            }
+        }
+        let swaps = match self.store.lease_active(&self.cfg.instance_id, self.cfg.lease) {
+            Ok(swaps) => swaps,
+            Err(e) => {
+                error!(error = %e, "cannot load active swaps");
+                return;
+            }
+        };
+        let retries = self.step_retries();
+        let parked: HashSet<String> = match self.store.dead_letters(false) {
+            Ok(letters) => letters.into_iter().map(|l| l.swap_id).collect(),
+            Err(e) => {
+                warn!(error = %e, "cannot read dead letters");
+                HashSet::new()
+            }
+        };
+        let now = unix_now();
+        for mut swap in swaps {
+            let retry = retries.get(&swap.id);
+            let held = parked.contains(&swap.id) || retry.is_some_and(|r| r.waiting(swap.state, now));
+            if held && !self.claim_due(&swap) {
+                continue;
+            }
+            self.step_in_span(&mut swap, retry).await;
+        }
+    }
+
+    /// The scheduled retries by swap id; none if they cannot be read, so every swap is stepped.
+    fn step_retries(&self) -> HashMap<String, StepRetry> {
+        match self.store.step_retries() {
+            Ok(retries) => retries.into_iter().map(|r| (r.swap_id.clone(), r)).collect(),
+            Err(e) => {
+                warn!(error = %e, "cannot read step retries");
+                HashMap::new()
+            }
+        }
+    }
+
+    /// Steps `swap`, whose last step failed as `retry` says if at all, and records the outcome in the retry queue.
+    async fn step_in_span(&self, swap: &mut Swap, retry: Option<&StepRetry>) {
+        let span = swap.span();
+        async {
+            let result = self.step(swap).await;
+            let recorded = match result {
+                Err(SwapError::Store(StoreError::Conflict { .. })) => {
+                    info!(swap = %swap.id, "swap written elsewhere since it was read; reading it again next tick");
+                    Ok(())
+                }
+                Err(SwapError::Store(e @ StoreError::Fenced { .. })) => {
+                    warn!(swap = %swap.id, error = %e, "not stepping a handed-off swap");
+                    Ok(())
+                }
+                Ok(()) => match retry {
+                    Some(retry) => {
+                        info!(swap = %swap.id, attempts = retry.attempts, "step went through after failing");
+                        self.store.clear_step_retry(&swap.id)
+                    }
+                    None => Ok(()),
+                },
+                Err(e) => self.schedule_retry(swap, retry, e),
+            };
+            if let Err(e) = recorded {
+                warn!(swap = %swap.id, error = %e, "cannot record the step in the retry queue");
+            }
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Schedules the next attempt of `swap`'s failed step, or dead-letters the swap after the last one.
+    fn schedule_retry(&self, swap: &Swap, previous: Option<&StepRetry>, e: SwapError) -> Result<(), StoreError> {
+        let now = unix_now();
+        let mut retry = StepRetry::failed(previous, &swap.id, swap.state, e.to_string(), now);
+        match self.cfg.step_retry.next_attempt_at(retry.attempts, now) {
+            Some(at) => {
+                retry.next_attempt_at = at;
+                warn!(
+                    swap = %swap.id,
+                    state = %swap.state,
+                    attempts = retry.attempts,
+                    retry_in_secs = at - now,
+                    error = %e,
+                    "step failed; retrying"
+                );
+                self.store.schedule_step_retry(&retry)
+            }
+            None => {
+                let id = self.store.dead_letter(&retry, now)?;
+                error!(
+                    alert = "swap_dead_lettered",
+                    swap = %swap.id,
+                    state = %swap.state,
+                    attempts = retry.attempts,
+                    dead_letter = id,
+                    error = %e,
+                    "giving up on the swap's step; look into it and retry the dead letter"
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Takes on nothing new, but keeps stepping swaps whose claim is due soon, which a replacement might not
+    /// start in time to land, for up to `shutdown_grace`. Then checkpoints every active swap.
+    async fn hand_over(&self) {
+        let deadline = Instant::now() + self.cfg.shutdown_grace;
+        loop {
+            let due: Vec<Swap> = match self.store.lease_active(&self.cfg.instance_id, self.cfg.lease) {
+                Ok(swaps) => swaps.into_iter().filter(|s| self.claim_due(s)).collect(),
+                Err(e) => {
+                    error!(error = %e, "cannot load active swaps");
+                    break;
+                }
+            };
+            if due.is_empty() {
+                break;
+            }
+            if Instant::now() >= deadline {
+                error!(
+                    alert = "shutdown_claims_pending",
+                    swaps = due.len(),
+                    "stopping with claims close to refund_after unfinished; start the next instance now"
+                );
+                break;
+            }
+            info!(
+                swaps = due.len(),
+                "finishing claims close to refund_after before stopping"
+            );
+            let retries = self.step_retries();
+            for mut swap in due {
+                let retry = retries.get(&swap.id);
+                self.step_in_span(&mut swap, retry).await;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline.min(Instant::now() + self.cfg.poll_interval)) => {}
+                _ = self.wake.notified() => {}
+            }
+        }
+        self.checkpoint();
+        if let Err(e) = self.store.release_leases(&self.cfg.instance_id) {
+            warn!(error = %e, "cannot release swap leases; other instances take the swaps on once they run out");
+        }
+    }
+
+    /// UsdtToLn swaps paying or claiming with less than `claim_alert_margin_secs` left before refund_after.
+    fn claim_due(&self, swap: &Swap) -> bool {
+        matches!(swap.state, SwapState::Paying | SwapState::Claiming)
+            && swap
+                .refund_after
+                .is_some_and(|t| t - unix_now() < self.cfg.claim_alert_margin_secs)
+    }
+
+    /// Records the active swaps and what this process knew about them beyond their rows.
+    fn checkpoint(&self) {
+        let taken_at = unix_now();
+        let paying = self.paying.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let failures = self.claim_failures.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let saved = self.store.active().and_then(|swaps| {
+            let entries: Vec<Checkpoint> = swaps
+                .into_iter()
+                .map(|swap| Checkpoint {
+                    payment_in_flight: paying.contains(&swap.payment_hash),
+                    claim_failures: failures.get(&swap.id).copied().unwrap_or(0),
+                    swap_id: swap.id,
+                    state: swap.state,
+                    taken_at,
+                })
+                .collect();
+            self.store.save_checkpoint(&entries).map(|()| entries.len())
+        });
+        match saved {
+            Ok(swaps) => info!(swaps, "checkpointed active swaps"),
+            Err(e) => error!(error = %e, "cannot checkpoint active swaps; the next instance resumes from their rows"),
+        }
+    }
+
+    /// Takes the checkpoint the previous instance left, if it shut down cleanly, and restores its claim failure
+    /// counts. A swap that moved on since means something else ran against this database in between.
+    fn resume(&self) {
+        let checkpoint = match self.store.take_checkpoint() {
+            Ok(checkpoint) => checkpoint,
+            Err(e) => {
+                warn!(error = %e, "cannot read the previous instance's checkpoint");
+                return;
+            }
+        };
+        let Some(taken_at) = checkpoint.first().map(|c| c.taken_at) else {
+            return;
+        };
+        info!(
+            swaps = checkpoint.len(),
+            taken_at, "resuming from the previous instance's checkpoint"
+        );
+        let mut failures = self.claim_failures.lock().unwrap_or_else(|e| e.into_inner());
+        for entry in checkpoint {
+            match self.store.get(&entry.swap_id) {
+                Ok(Some(swap)) if swap.state != entry.state => warn!(
+                    swap = %entry.swap_id,
+                    checkpointed = %entry.state,
+                    state = %swap.state,
+                    "swap moved on after the checkpoint; was another instance running?"
+                ),
+                Ok(Some(_)) => {}
+                Ok(None) => warn!(swap = %entry.swap_id, "checkpointed swap is missing from the database"),
+                Err(e) => warn!(swap = %entry.swap_id, error = %e, "cannot load checkpointed swap"),
+            }
+            if entry.payment_in_flight {
+                info!(swap = %entry.swap_id, "payment was in flight at shutdown; its outcome comes from the node");
+            }
+            if entry.claim_failures > 0 {
+                failures.insert(entry.swap_id, entry.claim_failures);
+            }
+        }
+    }
+
+    /// Turns settled keysends that pay an open quote into swaps.
+    async fn scan_keysends(&self) -> Result<(), SwapError> {
+        let after = self.store.cursor(KEYSEND_CURSOR)?;
+        for keysend in self.ln.keysends(after).await? {
+            if let Err(reason) = self.match_keysend(&keysend) {
+                error!(
+                    alert = "keysend_unmatched",
+                    payment_hash = %hex::encode(keysend.payment_hash),
+                    amount_msat = keysend.amount_msat,
+                    reason,
+                    "keysend received but not swapped; handle manually"
+                );
+            }
+            self.store.set_cursor(KEYSEND_CURSOR, keysend.index)?;
+        }
+        Ok(())
+    }
+
+    fn match_keysend(&self, keysend: &Keysend) -> Result<(), String> {
+        let id = <[u8; 16]>::try_from(keysend.swap_id.as_slice()).map_err(|_| "malformed swap id record")?;
+        let quote = self
+            .store
+            .keysend_quote(&id)
+            .map_err(|e| e.to_string())?
+            .ok_or("no quote with this id")?;
+        if quote.payment_hash == Some(keysend.payment_hash) {
+            return Ok(());
+        }
+        if quote.payment_hash.is_some() {
+            return Err("quote was already paid by another keysend".into());
+        }
+        if keysend.amount_msat < quote.amount_msat {
+            return Err(format!(
+                "paid {} msat, quote asks {}",
+                keysend.amount_msat, quote.amount_msat
+            ));
+        }
+        if unix_now() > quote.expires_at {
+            return Err("quote expired".into());
+        }
+        let swap = Swap::from_keysend(&quote, keysend);
+        let cause = Cause::new("keysend").because(format!("keysend matched quote {}", hex::encode(id)));
+        self.store.insert(&swap, &cause).map_err(|e| e.to_string())?;
+        self.store
+            .post_ledger_entry(&Entry::ln_received(&swap, keysend.amount_msat))
+            .map_err(|e| e.to_string())?;
+        self.store
+            .mark_keysend_quote_paid(&id, &keysend.payment_hash)
+            .map_err(|e| e.to_string())?;
+        info!(swap = %swap.id, quote = %hex::encode(id), "keysend matched its quote");
+        Ok(())
+    }
+
+    /// Advances `swap` until it waits on something external.
+    pub async fn step(&self, swap: &mut Swap) -> Result<(), SwapError> {
+        loop {
+            let before = swap.state;
+            match swap.direction {
+                Direction::LnToUsdt => self.step_ln_to_usdt(swap).await?,
+                Direction::UsdtToLn => self.step_usdt_to_ln(swap).await?,
+            }
+            if swap.state == before || swap.state.is_terminal() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn transition(&self, swap: &mut Swap, state: SwapState) -> Result<(), SwapError> {
+        self.transition_because(swap, state, Cause::engine())
+    }
+
+    /// [`Self::transition`], journaling why when the new state alone does not say.
+    fn transition_because(&self, swap: &mut Swap, state: SwapState, cause: Cause) -> Result<(), SwapError> {
+        info!(swap = %swap.id, direction = %swap.direction, from = %swap.state, to = %state, "transition");
+        let from = swap.state;
+        swap.state = state;
+        swap.error = None;
+        self.store.update(swap, from, &cause)?;
+        self.record_outcome(swap);
+        self.record_ledger(swap);
+        self.observe(swap);
+        Ok(())
+    }
+
+    fn fail(&self, swap: &mut Swap, reason: impl Into<String>) -> Result<(), SwapError> {
+        let reason = reason.into();
+        warn!(swap = %swap.id, state = %swap.state, reason, "swap failed");
+        let from = swap.state;
+        swap.state = SwapState::Failed;
+        swap.error = Some(reason.clone());
+        self.store.update(swap, from, &Cause::engine().because(reason))?;
+        self.record_outcome(swap);
+        self.record_ledger(swap);
+        self.observe(swap);
+        Ok(())
+    }
+
+    /// Counts the transition in the metrics, with the quote's fee once a quoted swap completes.
+    fn observe(&self, swap: &Swap) {
+        let quote = match swap.state {
+            SwapState::Completed => self.store.quote_of_swap(&swap.id).unwrap_or_else(|e| {
+                warn!(swap = %swap.id, error = %e, "cannot read the swap's quote for fee metrics");
+                None
+            }),
+            _ => None,
+        };
+        metrics().observe_transition(swap, quote.as_ref());
+    }
+
+    /// Adds an ended swap to its counterparty's reputation. Losing the record must not hold up the swap.
+    fn record_outcome(&self, swap: &Swap) {
+        let Some(outcome) = Outcome::of(swap) else {
+            return;
+        };
+        if let Err(e) = self.store.record_outcome(swap, outcome, None) {
+            warn!(swap = %swap.id, error = %e, "cannot record counterparty outcome");
+        }
+    }
+
+    /// Posts the ledger entries `swap`'s new state implies. Like outcomes, a lost entry must not hold up the
+    /// swap; the audit shows it as drift.
+    fn record_ledger(&self, swap: &Swap) {
+        let posted = ledger::entries_for(&self.store, swap).and_then(|entries| {
+            entries
+                .iter()
+                .try_for_each(|e| self.store.post_ledger_entry(e).map(drop))
+        });
+        if let Err(e) = posted {
+            warn!(swap = %swap.id, error = %e, "cannot post ledger entries");
+        }
+    }
+
+    /// Records a retryable problem without changing state.
+    fn note(&self, swap: &mut Swap, reason: impl Into<String>) -> Result<(), SwapError> {
+        let reason = reason.into();
+        swap.error = Some(reason.clone());
+        let state = swap.state;
+        self.store.update(swap, state, &Cause::engine().because(reason))?;
+        Ok(())
+    }
+
+    /// Counts a failed claim of `swap`, or clears the count once one lands; the failures in a row.
+    fn count_claim_failure(&self, swap: &Swap, failed: bool) -> u32 {
+        let mut failures = self.claim_failures.lock().unwrap_or_else(|e| e.into_inner());
+        if !failed {
+            failures.remove(&swap.id);
+            return 0;
+        }
+        let count = failures.entry(swap.id.clone()).or_default();
+        *count += 1;
+        *count
+    }
+
+    /// A transaction was signed and recorded but its outcome never was (the daemon stopped mid-send).
+    fn may_still_land(swap: &Swap) -> bool {
+        swap.signature.is_some() && swap.error.is_none() && unix_now() - swap.updated_at < LANDING_WINDOW_SECS
+    }
+
+    async fn escrow(&self, swap: &Swap) -> Result<Option<EscrowState>, SwapError> {
+        Ok(self.client.get_escrow(&swap.payment_hash).await?)
+    }
+
+    /// Signs `tx` with the operator key, records its signature before broadcasting, and sends it to a final
+    /// outcome.
+    async fn send(&self, swap: &mut Swap, kind: &'static str, tx: Transaction) -> Result<Sent, SwapError> {
+        self.send_tipped(swap, kind, tx, None).await
+    }
+
+    /// [`Self::send`], first submitting the signed `tx` as a Jito bundle with `tip` lamports if one is given.
+    /// The RPC send goes ahead either way; both carry the same signature, so the transaction lands at most once.
+    /// A dry run only simulates `tx`, recorded as `kind`.
+    async fn send_tipped(
+        &self,
+        swap: &mut Swap,
+        kind: &'static str,
+        mut tx: Transaction,
+        tip: Option<u64>,
+    ) -> Result<Sent, SwapError> {
+        if let Some(dry_run) = &self.dry_run {
+            let simulation = self.client.simulate_and_explain(&tx).await?;
+            dry_run.record(
+                swap,
+                kind,
+                json!({
+                    "ok": simulation.ok,
+                    "explanation": simulation.explanation,
+                    "hint": simulation.hint,
+                    "unitsConsumed": simulation.units_consumed,
+                    "computeUnitPrice": tx_price(&tx),
+                    "jitoTipLamports": tip,
+                    "logs": simulation.logs,
+                }),
+            );
+            return Ok(Sent::Simulated);
+        }
+        let blockhash = tx.message.recent_blockhash;
+        tx.try_sign(&[&*self.operator], blockhash)?;
+        swap.signature = Some(tx.signatures[0].to_string());
+        let state = swap.state;
+        self.store
+            .update(swap, state, &Cause::engine().because(format!("{kind} signed")))?;
+        if let (Some(jito), Some(tip)) = (&self.jito, tip) {
+            match jito.send_bundle(&tx, &*self.operator, tip).await {
+                Ok(bundle) => {
+                    metrics().jito_bundles.with_label_values(&["submitted"]).inc();
+                    info!(swap = %swap.id, bundle, tip_lamports = tip, "submitted jito bundle");
+                }
+                Err(e) => {
+                    metrics().jito_bundles.with_label_values(&["rejected"]).inc();
+                    warn!(swap = %swap.id, error = %e, "jito bundle not submitted; sending over rpc only");
+                }
+            }
+        }
+        let outcome = self
+            .client
+            .send_with_retry(tx.clone(), &[&*self.operator], &self.cfg.retry, |event| {
+                metrics().observe_send_event(event);
+                debug!(swap = %swap.id, ?event, "send progress");
+            })
+            .await?;
+        metrics().observe_send_outcome(&outcome);
+        self.observe_landing(&outcome);
+        if let Some(e) = outcome.error(self.client.program_id(), &tx) {
+            return Ok(Sent::Failed(e));
+        }
+        match outcome {
+            SendOutcome::Confirmed { signature, .. } => {
+                swap.signature = Some(signature.to_string());
+                Ok(Sent::Confirmed)
+            }
+            _ => {
+                swap.signature = None;
+                let state = swap.state;
+                self.store
+                    .update(swap, state, &Cause::engine().because(format!("{kind} expired")))?;
+                Ok(Sent::Expired)
+            }
+        }
+    }
+
+    /// Feeds each signature of `outcome` into the landing rate claims are priced by.
+    fn observe_landing(&self, outcome: &SendOutcome) {
+        match outcome {
+            SendOutcome::Confirmed { expired, .. } => {
+                expired.iter().for_each(|_| self.fees.observe(false));
+                self.fees.observe(true);
+            }
+            SendOutcome::Failed { .. } => self.fees.observe(true),
+            SendOutcome::Expired { signatures } => signatures.iter().for_each(|_| self.fees.observe(false)),
+            SendOutcome::Rejected { .. } | SendOutcome::Pending { .. } => {}
+        }
+    }
+
+    // LnToUsdt: we issue the invoice and fund the escrow; the user pays, learns the preimage and claims. With a
+    // hold invoice the order flips: the user pays first, we fund the escrow while holding the HTLCs, and only
+    // then settle, so the preimage is revealed when the USDT is already claimable.
+
+    async fn step_ln_to_usdt(&self, swap: &mut Swap) -> Result<(), SwapError> {
+        let before = swap.state;
+        match swap.state {
+            _ if swap.cancel_requested && swap.is_cancellable() => self.fail(swap, CANCELLED),
+            SwapState::Created => self.create_invoice(swap).await,
+            SwapState::InvoiceCreated if swap.hold => self.await_htlcs(swap).await,
+            SwapState::InvoiceCreated | SwapState::InvoiceAccepted | SwapState::KeysendReceived => {
+                self.fund_escrow(swap).await
+            }
+            SwapState::EscrowFunded if swap.hold => self.settle_hold(swap).await,
+            SwapState::EscrowFunded | SwapState::InvoiceSettled => self.await_claim(swap).await,
+            SwapState::Refunding => self.refund(swap).await,
+            other => self.fail(swap, format!("state {other} is not valid for {}", swap.direction)),
+        }?;
+        if swap.hold && swap.state == SwapState::Failed && before != SwapState::Failed {
+            // Fail the held HTLCs back now rather than letting them sit until they time out.
+            if self.dry(swap, "cancel_hold_invoice", || json!({ "reason": swap.error })) {
+                return Ok(());
+            }
+            if let Err(e) = self.ln.cancel_hold_invoice(&swap.payment_hash).await {
+                warn!(swap = %swap.id, error = %e, "cannot cancel hold invoice");
+            }
+        }
+        Ok(())
+    }
+
+    async fn create_invoice(&self, swap: &mut Swap) -> Result<(), SwapError> {
+        let Some(preimage) = self.open_preimage(swap)? else {
+            return self.fail(swap, "missing preimage");
+        };
+        if swap.hold && !self.ln.supports_hold_invoices() {
+            return self.fail(swap, "the lightning backend does not support hold invoices");
+        }
+        let bolt11 = match self.ln.lookup_invoice(&swap.payment_hash).await? {
+            Some(existing) => existing.bolt11,
+            None => {
+                let default = format!("intercom-swap {}", swap.id);
+                let description = swap
+                    .description
+                    .as_deref()
+                    .map_or(Description::Text(&default), Description::Hashed);
+                let expiry = self.cfg.invoice_expiry_secs;
+                let kind = if swap.hold {
+                    "create_hold_invoice"
+                } else {
+                    "create_invoice"
+                };
+                let detail = || {
+                    json!({
+                        "amountMsat": swap.amount_msat,
+                        "description": description.text(),
+                        "descriptionHashed": description.hash().is_some(),
+                        "expirySecs": expiry,
+                    })
+                };
+                if self.dry(swap, kind, detail) {
+                    return Ok(());
+                }
+                let invoice = if swap.hold {
+                    self.ln
+                        .create_hold_invoice(swap.amount_msat, &swap.payment_hash, description, expiry)
+                        .await?
+                } else {
+                    self.ln
+                        .create_invoice(swap.amount_msat, &preimage, description, expiry)
+                        .await?
+                };
+                invoice.bolt11
+            }
+        };
+        swap.deadline = crate::ln::decode_invoice(&bolt11)?.expires_at;
+        swap.bolt11 = Some(bolt11);
+        self.transition(swap, SwapState::InvoiceCreated)
+    }
+
+    async fn fund_escrow(&self, swap: &mut Swap) -> Result<(), SwapError> {
+        if let Some(escrow) = self.escrow(swap).await? {
+            if escrow.refund != self.operator() || escrow.recipient != swap.counterparty {
+                return self.fail(
+                    swap,
+                    "an escrow for this payment hash exists but was not funded by this daemon",
+                );
+            }
+            swap.refund_after = Some(escrow.refund_after);
+            self.store.post_ledger_entry(&Entry::escrow_funded(swap, &escrow))?;
+            return self.transition(swap, SwapState::EscrowFunded);
+        }
+        if Self::may_still_land(swap) {
+            return Ok(());
+        }
+        // A plain invoice is only handed out once funded; don't fund one the user can no longer pay. Held and
+        // keysend payments are already in.
+        if swap.state == SwapState::InvoiceCreated && unix_now() >= swap.deadline {
+            return self.fail(swap, "invoice expired before the escrow was funded");
+        }
+
+        let program_id = self.client.program_id();
+        let platform_fee_bps = self
+            .client
+            .get_config()
+            .await?
+            .ok_or_else(|| SwapError::Setup("platform config is not initialized".into()))?
+            .fee_bps;
+        let trade_fee_bps = self
+            .client
+            .get_trade_config(&self.cfg.trade_fee_collector)
+            .await?
+            .ok_or_else(|| SwapError::Setup("trade config is not initialized for the trade fee collector".into()))?
+            .fee_bps;
+        let args = InitArgs {
+            payment_hash: swap.payment_hash,
+            recipient: swap.counterparty,
+            refund: self.operator(),
+            refund_after: RefundAfter::Delay(self.cfg.refund_delay_secs),
+            amount: swap.token_amount,
+            expected_platform_fee_bps: platform_fee_bps,
+            expected_trade_fee_bps: trade_fee_bps,
+            trade_fee_collector: self.cfg.trade_fee_collector,
+        };
+        let violations = self
+            .client
+            .validate_init(&self.operator(), &self.cfg.mint, &args)
+            .await?;
+        if !violations.is_empty() {
+            // Usually an unfunded operator wallet; retry rather than fail so topping it up resumes the swap.
+            return self.note(swap, SwapClientError::Preflight(violations).to_string());
+        }
+        let blockhash = self.client.get_latest_blockhash().await?;
+        let tx = transaction::init_transaction(
+            program_id,
+            &self.operator(),
+            &self.cfg.mint,
+            &args,
+            &self.cfg.tx,
+            blockhash,
+        );
+        match self.send(swap, "send_init", tx).await? {
+            Sent::Confirmed => match self.escrow(swap).await? {
+                Some(escrow) => {
+                    swap.refund_after = Some(escrow.refund_after);
+                    self.store.post_ledger_entry(&Entry::escrow_funded(swap, &escrow))?;
+                    self.transition(swap, SwapState::EscrowFunded)
+                }
+                None => Ok(()),
+            },
+            Sent::Expired | Sent::Simulated => Ok(()),
+            // Re-read on the next pass, which checks the existing escrow is ours.
+            Sent::Failed(e) if e.escrow_error() == Some(EscrowError::AlreadyInitialized) => Ok(()),
+            Sent::Failed(e) => self.note(swap, e.to_string()),
+        }
+    }
+
+    /// The swap invoice's status with its HTLC parts counted against the swap amount.
+    async fn invoice_status(&self, swap: &Swap) -> Result<Option<InvoiceStatus>, SwapError> {
+        let Some(lookup) = self.ln.lookup_invoice(&swap.payment_hash).await? else {
+            return Ok(None);
+        };
+        let status = lookup.status_for(swap.amount_msat);
+        if status == InvoiceStatus::Partial {
+            debug!(
+                swap = %swap.id,
+                received_msat = lookup.received_msat,
+                amount_msat = swap.amount_msat,
+                "payment parts still arriving"
+            );
+        }
+        Ok(Some(status))
+    }
+
+    /// Hold invoice: waits until the user's HTLCs are held, all parts of them, before committing any USDT. A
+    /// payment still incomplete at the deadline fails the swap, which fails the held parts back.
+    async fn await_htlcs(&self, swap: &mut Swap) -> Result<(), SwapError> {
+        let expired = unix_now() >= swap.deadline;
+        match self.invoice_status(swap).await? {
+            Some(InvoiceStatus::Accepted | InvoiceStatus::Settled) => self.transition(swap, SwapState::InvoiceAccepted),
+            Some(InvoiceStatus::Canceled) | None => self.fail(swap, "hold invoice was canceled"),
+            Some(InvoiceStatus::Partial) if expired => {
+                self.fail(swap, "only part of the payment arrived before the invoice expired")
+            }
+            Some(InvoiceStatus::Open) if expired => self.fail(swap, "hold invoice expired unpaid"),
+            Some(InvoiceStatus::Open | InvoiceStatus::Partial) => Ok(()),
+        }
+    }
+
+    /// Hold invoice: settles once the escrow is funded with the agreed terms and far enough from refund_after
+    /// for the user to claim. Otherwise the HTLCs are failed back and the escrow refunded when it can be.
+    async fn settle_hold(&self, swap: &mut Swap) -> Result<(), SwapError> {
+        let Some(preimage) = self.open_preimage(swap)? else {
+            return self.fail(swap, "missing preimage");
+        };
+        let Some(escrow) = self.escrow(swap).await? else {
+            return self.fail(swap, "funded escrow disappeared");
+        };
+        let status = self.invoice_status(swap).await?;
+        if status == Some(InvoiceStatus::Settled) {
+            return self.transition(swap, SwapState::InvoiceSettled);
+        }
+        let now = self.client.get_unix_timestamp().await?;
+        let amounts = self.check_amount(swap, escrow.net_amount);
+        let claimable = escrow.status == EscrowStatus::Active
+            && escrow.recipient == swap.counterparty
+            && amounts.accepted
+            && now + self.cfg.claim_margin_secs < escrow.refund_after;
+        if status == Some(InvoiceStatus::Accepted) && claimable {
+            self.store.record_amount_check(&amounts)?;
+            if self.dry(
+                swap,
+                "settle_hold_invoice",
+                || json!({ "refundAfter": escrow.refund_after }),
+            ) {
+                return Ok(());
+            }
+            self.ln.settle_hold_invoice(&preimage).await?;
+            return self.transition(swap, SwapState::InvoiceSettled);
+        }
+        // Never settle short: if parts went missing, fail the rest back rather than take part of the payment.
+        if matches!(status, Some(InvoiceStatus::Accepted | InvoiceStatus::Partial))
+            && !self.dry(swap, "cancel_hold_invoice", || json!({ "claimable": claimable }))
+        {
+            self.ln.cancel_hold_invoice(&swap.payment_hash).await?;
+        }
+        if now >= escrow.refund_after {
+            let cause = Cause::engine().because("hold invoice not settled by refund_after");
+            return self.transition_because(swap, SwapState::Refunding, cause);
+        }
+        self.note(
+            swap,
+            "hold invoice was not settled; the escrow is refunded after refund_after",
+        )
+    }
+
+    /// Waits for the user's claim, noting the Lightning settlement on the way, until refund_after.
+    async fn await_claim(&self, swap: &mut Swap) -> Result<(), SwapError> {
+        let Some(escrow) = self.escrow(swap).await? else {
+            return self.fail(swap, "funded escrow disappeared");
+        };
+        match escrow.status {
+            EscrowStatus::Claimed => return self.transition(swap, SwapState::Completed),
+            EscrowStatus::Refunded => {
+                let cause = Cause::engine().because("escrow found refunded on chain");
+                return self.transition_because(swap, SwapState::Refunded, cause);
+            }
+            EscrowStatus::Active => {}
+        }
+        if swap.state == SwapState::EscrowFunded {
+            if self.invoice_status(swap).await? == Some(InvoiceStatus::Settled) {
+                return self.transition(swap, SwapState::InvoiceSettled);
+            }
+        }
+        if self.client.get_unix_timestamp().await? >= escrow.refund_after {
+            let reason = if swap.state == SwapState::InvoiceSettled {
+                warn!(swap = %swap.id, "invoice was paid but the user never claimed; refunding");
+                "invoice paid but the escrow not claimed by refund_after"
+            } else {
+                "escrow not claimed by refund_after"
+            };
+            return self.transition_because(swap, SwapState::Refunding, Cause::engine().because(reason));
+        }
+        Ok(())
+    }
+
+    async fn refund(&self, swap: &mut Swap) -> Result<(), SwapError> {
+        let Some(escrow) = self.escrow(swap).await? else {
+            return self.fail(swap, "funded escrow disappeared");
+        };
+        match escrow.status {
+            EscrowStatus::Claimed => return self.transition(swap, SwapState::Completed),
+            EscrowStatus::Refunded => {
+                let cause = Cause::engine().because("escrow found refunded on chain");
+                return self.transition_because(swap, SwapState::Refunded, cause);
+            }
+            EscrowStatus::Active => {}
+        }
+        if Self::may_still_land(swap) {
+            return Ok(());
+        }
+        let blockhash = self.client.get_latest_blockhash().await?;
+        let tx = transaction::refund_transaction(
+            self.client.program_id(),
+            &escrow,
+            &self.operator(),
+            &self.cfg.tx,
+            blockhash,
+        );
+        match self.send(swap, "send_refund", tx).await? {
+            Sent::Confirmed => self.transition(swap, SwapState::Refunded),
+            // NotActive: the user claimed in the meantime; the next read picks that up.
+            Sent::Expired | Sent::Simulated => Ok(()),
+            Sent::Failed(e) => self.note(swap, e.to_string()),
+        }
+    }
+
+    // UsdtToLn: the user funds an escrow payable to us; we pay their invoice and claim with the preimage.
+
+    async fn step_usdt_to_ln(&self, swap: &mut Swap) -> Result<(), SwapError> {
+        match swap.state {
+            _ if swap.cancel_requested && swap.is_cancellable() => self.fail(swap, CANCELLED),
+            SwapState::Created => self.verify_escrow(swap).await,
+            SwapState::EscrowVerified => self.start_payment(swap).await,
+            SwapState::Paying => self.await_payment(swap).await,
+            SwapState::Claiming => self.claim(swap).await,
+            other => self.fail(swap, format!("state {other} is not valid for {}", swap.direction)),
+        }
+    }
+
+    async fn verify_escrow(&self, swap: &mut Swap) -> Result<(), SwapError> {
+        let Some(escrow) = self.escrow(swap).await? else {
+            if unix_now() > swap.deadline {
+                return self.fail(swap, "escrow was not funded in time");
+            }
+            return Ok(());
+        };
+        let amounts = self.check_amount(swap, escrow.net_amount);
+        self.store.record_amount_check(&amounts)?;
+        if let Err(reason) = self.check_escrow(swap, &escrow, &amounts) {
+            return self.fail(swap, reason);
+        }
+        swap.refund_after = Some(escrow.refund_after);
+        if let Err(e) = self.cltv_limit(swap).await? {
+            return self.fail(swap, e.to_string());
+        }
+        self.store.post_ledger_entry(&Entry::escrow_verified(swap, &escrow))?;
+        self.transition(swap, SwapState::EscrowVerified)
+    }
+
+    /// Checks an escrow holding `net_amount` against `swap` within the configured tolerance, noting a shortfall
+    /// it lets through.
+    fn check_amount(&self, swap: &Swap, net_amount: u64) -> AmountCheck {
+        let check = self
+            .cfg
+            .amount_tolerance
+            .check(&swap.id, swap.token_amount, net_amount, unix_now());
+        if check.accepted && check.shortfall() > 0 {
+            info!(
+                swap = %swap.id,
+                expected = check.expected,
+                actual = check.actual,
+                allowed = check.allowed,
+                "escrow short within the amount tolerance"
+            );
+        }
+        check
+    }
+
+    fn check_escrow(&self, swap: &Swap, escrow: &EscrowState, amounts: &AmountCheck) -> Result<(), String> {
+        if escrow.recipient != self.operator() {
+            return Err(format!("escrow recipient is {}, not the operator", escrow.recipient));
+        }
+        if escrow.refund != swap.counterparty {
+            return Err(format!(
+                "escrow refund key is {}, expected {}",
+                escrow.refund, swap.counterparty
+            ));
+        }
+        if escrow.mint != self.cfg.mint {
+            return Err(format!("escrow mint is {}, expected {}", escrow.mint, self.cfg.mint));
+        }
+        if !amounts.accepted {
+            return Err(format!(
+                "escrow holds {} net, expected at least {}",
+                amounts.actual,
+                amounts.expected - amounts.allowed
+            ));
+        }
+        let bolt11 = swap.bolt11.as_deref().ok_or("missing invoice")?;
+        let check = InvoiceCheck {
+            refund_margin_secs: self.cfg.claim_margin_secs,
+            rate: None,
+        };
+        check_invoice(bolt11, escrow, &check)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn start_payment(&self, swap: &mut Swap) -> Result<(), SwapError> {
+        match self.ln.payment_status(&swap.payment_hash).await? {
+            Some(PaymentStatus::Succeeded { preimage, fee_msat }) => {
+                return self.payment_succeeded(swap, preimage, fee_msat)
+            }
+            Some(PaymentStatus::InFlight) => return self.transition(swap, SwapState::Paying),
+            Some(PaymentStatus::Failed { .. }) | None => {}
+        }
+        let cltv_limit = match self.cltv_limit(swap).await? {
+            Ok(limit) => limit,
+            Err(e) => {
+                return self.fail(
+                    swap,
+                    format!("too close to the escrow's refund_after to pay safely: {e}"),
+                )
+            }
+        };
+        self.spawn_payment(swap, cltv_limit);
+        self.transition(swap, SwapState::Paying)
+    }
+
+    /// Route CLTV a payment for `swap` made now may use, or why it cannot be paid in time.
+    async fn cltv_limit(&self, swap: &Swap) -> Result<Result<u32, SafetyError>, SwapError> {
+        let invoice = decode_invoice(swap.bolt11.as_deref().unwrap_or_default())?;
+        let claim_by = swap
+            .refund_after
+            .unwrap_or(i64::MIN)
+            .saturating_sub(self.cfg.claim_margin_secs);
+        let now = self.client.get_unix_timestamp().await?;
+        Ok(self.cfg.cltv.check_payment(now, &invoice, claim_by))
+    }
+
+    /// Pays in the background, in the current swap's span; [`Self::await_payment`] follows the node's record of
+    /// the payment.
+    fn spawn_payment(&self, swap: &Swap, cltv_limit: u32) {
+        let Some(bolt11) = swap.bolt11.clone() else {
+            return;
+        };
+        let max_fee_msat = swap.amount_msat.saturating_mul(u64::from(self.cfg.max_routing_fee_bps)) / 10_000;
+        let detail = || {
+            let invoice = decode_invoice(&bolt11).ok();
+            json!({
+                "amountMsat": swap.amount_msat,
+                "maxFeeMsat": max_fee_msat,
+                "cltvLimit": cltv_limit,
+                "invoiceExpiresAt": invoice.as_ref().map(|i| i.expires_at),
+                "minFinalCltvExpiryDelta": invoice.as_ref().map(|i| i.min_final_cltv_expiry_delta),
+            })
+        };
+        if self.dry(swap, "pay_invoice", detail) {
+            return;
+        }
+        if !self
+            .paying
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(swap.payment_hash)
+        {
+            return;
+        }
+        let (ln, paying, wake) = (self.ln.clone(), self.paying.clone(), self.wake.clone());
+        let (hash, id) = (swap.payment_hash, swap.id.clone());
+        tokio::spawn(
+            async move {
+                if let Err(e) = ln.pay_invoice(&bolt11, max_fee_msat, cltv_limit).await {
+                    metrics().ln_payment_failures.inc();
+                    warn!(swap = %id, error = %e, "lightning payment returned an error");
+                }
+                paying.lock().unwrap_or_else(|e| e.into_inner()).remove(&hash);
+                // The preimage is known now; claim without waiting for the next poll.
+                wake.notify_one();
+            }
+            .instrument(Span::current()),
+        );
+    }
+
+    async fn await_payment(&self, swap: &mut Swap) -> Result<(), SwapError> {
+        if let Some(onchain) = &self.onchain {
+            if self.store.htlc(&swap.id)?.is_some() {
+                return self.await_htlc(onchain, swap).await;
+            }
+        }
+        match self.ln.payment_status(&swap.payment_hash).await? {
+            Some(PaymentStatus::Succeeded { preimage, fee_msat }) => self.payment_succeeded(swap, preimage, fee_msat),
+            Some(PaymentStatus::Failed { reason }) => {
+                let retrying = self
+                    .paying
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .contains(&swap.payment_hash);
+                if retrying {
+                    // Failed before and issued again; the new attempt is not recorded yet.
+                    return Ok(());
+                }
+                metrics().ln_payment_failures.inc();
+                self.payment_failed(swap, reason).await
+            }
+            Some(PaymentStatus::InFlight) => Ok(()),
+            // Restarted before the node recorded the payment: issue it again (the node dedupes by hash), if it can
+            // still resolve in time.
+            None => match self.cltv_limit(swap).await? {
+                Ok(limit) => {
+                    self.spawn_payment(swap, limit);
+                    Ok(())
+                }
+                Err(e) => self.fail(
+                    swap,
+                    format!("payment never started and can no longer resolve in time: {e}"),
+                ),
+            },
+        }
+    }
+
+    /// Fails `swap` after a failed payment, unless its taker registered a claim key: then the payment is
+    /// retried until it has failed `after_ln_failures` times, or cannot resolve in time, and the BTC is locked
+    /// in an on-chain HTLC instead.
+    async fn payment_failed(&self, swap: &mut Swap, reason: String) -> Result<(), SwapError> {
+        let registration = match &self.onchain {
+            Some(_) => self.store.onchain_registration(&swap.id)?,
+            None => None,
+        };
+        let (Some(onchain), Some(registration)) = (&self.onchain, registration) else {
+            return self.fail(swap, format!("lightning payment failed: {reason}"));
+        };
+        let failures = self.store.count_ln_failure(&swap.id)?;
+        let after = onchain.config().after_ln_failures;
+        if failures < after {
+            if let Ok(limit) = self.cltv_limit(swap).await? {
+                self.note(
+                    swap,
+                    format!("lightning payment failed ({failures} of {after}): {reason}; retrying"),
+                )?;
+                self.spawn_payment(swap, limit);
+                return Ok(());
+            }
+        }
+        let claim_by = swap
+            .refund_after
+            .unwrap_or(i64::MIN)
+            .saturating_sub(self.cfg.claim_margin_secs);
+        let now = self.client.get_unix_timestamp().await?;
+        let blocks = self.cfg.cltv.cltv_limit(now, claim_by);
+        let detail = || json!({ "amountMsat": swap.amount_msat, "timeoutBlocks": blocks, "lnFailures": failures });
+        if self.dry(swap, "onchain_htlc", detail) {
+            return Ok(());
+        }
+        let Some(record) = onchain
+            .open(self.ln.as_ref(), swap, &registration.claim_key, blocks)
+            .await?
+        else {
+            return self.fail(
+                swap,
+                format!("lightning payment failed: {reason}; too close to refund_after for an on-chain HTLC"),
+            );
+        };
+        warn!(
+            alert = "onchain_fallback",
+            swap = %swap.id,
+            address = %record.address,
+            amount_sat = record.amount_sat,
+            timeout_height = record.htlc.timeout_height,
+            "lightning payment failed {failures} times; paying through an on-chain HTLC"
+        );
+        self.note(swap, format!("paying on-chain to {} after: {reason}", record.address))
+    }
+
+    async fn await_htlc(&self, onchain: &Fallback, swap: &mut Swap) -> Result<(), SwapError> {
+        match onchain.advance(self.ln.as_ref(), swap).await? {
+            Progress::Pending => Ok(()),
+            Progress::Claimed(preimage) => self.learn_preimage(swap, preimage),
+            Progress::Refunded => self.fail(swap, "on-chain HTLC was refunded unclaimed"),
+        }
+    }
+
+    /// Books a settled Lightning payment, however the engine came to see it, and claims with its preimage.
+    fn payment_succeeded(&self, swap: &mut Swap, preimage: [u8; 32], fee_msat: u64) -> Result<(), SwapError> {
+        info!(swap = %swap.id, fee_msat, "lightning payment succeeded");
+        metrics().ln_routing_fees_msat.inc_by(fee_msat);
+        self.store.post_ledger_entry(&Entry::ln_paid(swap, fee_msat))?;
+        self.learn_preimage(swap, preimage)
+    }
+
+    fn learn_preimage(&self, swap: &mut Swap, preimage: [u8; 32]) -> Result<(), SwapError> {
+        if hash(&preimage).to_bytes() != swap.payment_hash {
+            return self.fail(swap, "node reported a preimage that does not match the payment hash");
+        }
+        // Sealed by the store as the transition is written.
+        swap.preimage = Some(Preimage::plain(preimage));
+        self.transition(swap, SwapState::Claiming)
+    }
+
+    /// The swap's preimage in the clear, to settle with; zeroed when dropped.
+    fn open_preimage(&self, swap: &Swap) -> Result<Option<Zeroizing<[u8; 32]>>, SwapError> {
+        let Some(preimage) = &swap.preimage else {
+            return Ok(None);
+        };
+        let opened = self
+            .store
+            .vault()
+            .open(&swap.payment_hash, preimage)
+            .map_err(StoreError::from)?;
+        Ok(Some(opened))
+    }
+
+    /// Options of the `attempt`th claim of `escrow` this step, priced by the fee controller.
+    async fn claim_options(&self, escrow: &EscrowState, urgency: Urgency, attempt: u32) -> TxOptions {
+        let price = self
+            .fees
+            .claim_price(&self.client, escrow, &self.operator(), urgency, attempt)
+            .await;
+        TxOptions {
+            compute_unit_price_micro_lamports: Some(price),
+            ..self.cfg.tx
+        }
+    }
+
+    /// Claims as soon as the preimage is known, re-sending until confirmed. Each attempt re-reads the escrow,
+    /// so a claim that landed unseen is picked up instead of repeated.
+    async fn claim(&self, swap: &mut Swap) -> Result<(), SwapError> {
+        let Some(preimage) = self.open_preimage(swap)? else {
+            return self.fail(swap, "missing preimage");
+        };
+        for attempt in 0..CLAIM_ROUNDS {
+            let Some(escrow) = self.escrow(swap).await? else {
+                return self.fail(swap, "verified escrow disappeared");
+            };
+            match escrow.status {
+                EscrowStatus::Claimed => return self.transition(swap, SwapState::Completed),
+                EscrowStatus::Refunded => {
+                    error!(swap = %swap.id, "escrow was refunded after we paid the invoice");
+                    return self.fail(
+                        swap,
+                        "escrow refunded before the claim landed; the lightning payment is lost",
+                    );
+                }
+                EscrowStatus::Active => {}
+            }
+            if Self::may_still_land(swap) {
+                return Ok(());
+            }
+            let remaining = escrow.refund_after - self.client.get_unix_timestamp().await?;
+            if remaining < self.cfg.claim_alert_margin_secs {
+                error!(
+                    swap = %swap.id,
+                    alert = "claim_margin",
+                    remaining_secs = remaining,
+                    "claim not confirmed and refund_after is close; the payer can refund soon"
+                );
+            }
+            let urgency = Urgency::of(remaining, self.cfg.claim_alert_margin_secs);
+            let tip = self.jito.as_ref().and_then(|jito| jito.tip(urgency));
+            let options = self.claim_options(&escrow, urgency, attempt).await;
+            let blockhash = self.client.get_latest_blockhash().await?;
+            let tx = transaction::claim_transaction(
+                self.client.program_id(),
+                &escrow,
+                &self.operator(),
+                &preimage,
+                &options,
+                blockhash,
+            );
+            match self.send_tipped(swap, "send_claim", tx, tip).await? {
+                Sent::Confirmed => {
+                    self.count_claim_failure(swap, false);
+                    return self.transition(swap, SwapState::Completed);
+                }
+                Sent::Expired => continue,
+                Sent::Simulated => return Ok(()),
+                Sent::Failed(e) => {
+                    let failures = self.count_claim_failure(swap, true);
+                    if failures >= CLAIM_FAILURE_ALERT {
+                        error!(
+                            swap = %swap.id,
+                            alert = "claim_failing",
+                            failures,
+                            remaining_secs = remaining,
+                            error = %e,
+                            "claim transaction keeps failing"
+                        );
+                    }
+                    return self.note(swap, e.to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compute unit price a transaction sets, if any, for dry-run reports.
+fn tx_price(tx: &Transaction) -> Option<u64> {
+    let budget = solana_sdk::compute_budget::id();
+    tx.message.instructions.iter().find_map(|ix| {
+        let program = tx.message.account_keys.get(usize::from(ix.program_id_index))?;
+        match (program == &budget, ix.data.split_first()) {
+            // SetComputeUnitPrice: tag 3, then the price as a little-endian u64.
+            (true, Some((3, price))) => Some(u64::from_le_bytes(price.get(..8)?.try_into().ok()?)),
+            _ => None,
+        }
+    })
+}
@@ -0,0 +1,85 @@
+use std::fmt;
+
+use intercom_swap_client::{client::FetchError, retry::RetryError};
+use solana_sdk::signer::SignerError;
+
+use crate::{ln::LnError, onchain::OnchainError, store::StoreError};
+
+/// A step that could not complete this round. The engine logs it and retries the same step on the next tick;
+/// failures that retrying cannot fix move the swap to `Failed` instead.
+#[derive(Debug)]
+pub enum SwapError {
+    Store(StoreError),
+    Ln(LnError),
+    Fetch(FetchError),
+    Send(RetryError),
+    Sign(SignerError),
+    /// The on-chain fallback of the Lightning payment.
+    Onchain(OnchainError),
+    /// Cluster or configuration state the daemon cannot work with (e.g. missing fee config).
+    Setup(String),
+}
+
+impl fmt::Display for SwapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Store(e) => write!(f, "{e}"),
+            Self::Ln(e) => write!(f, "{e}"),
+            Self::Fetch(e) => write!(f, "{e}"),
+            Self::Send(e) => write!(f, "{e}"),
+            Self::Sign(e) => write!(f, "signing failed: {e}"),
+            Self::Onchain(e) => write!(f, "{e}"),
+            Self::Setup(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for SwapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Store(e) => Some(e),
+            Self::Ln(e) => Some(e),
+            Self::Fetch(e) => Some(e),
+            Self::Send(e) => Some(e),
+            Self::Sign(e) => Some(e),
+            Self::Onchain(e) => Some(e),
+            Self::Setup(_) => None,
+        }
+    }
+}
+
+impl From<StoreError> for SwapError {
+    fn from(e: StoreError) -> Self {
+        Self::Store(e)
+    }
+}
+
+impl From<LnError> for SwapError {
+    fn from(e: LnError) -> Self {
+        Self::Ln(e)
+    }
+}
+
+impl From<FetchError> for SwapError {
+    fn from(e: FetchError) -> Self {
+        Self::Fetch(e)
+    }
+}
+
+impl From<RetryError> for SwapError {
+    fn from(e: RetryError) -> Self {
+        Self::Send(e)
+    }
+}
+
+impl From<SignerError> for SwapError {
+    fn from(e: SignerError) -> Self {
+        Self::Sign(e)
+    }
+}
+
+impl From<OnchainError> for SwapError {
+    fn from(e: OnchainError) -> Self {
+        Self::Onchain(e)
+    }
+}
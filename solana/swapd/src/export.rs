@@ -0,0 +1,147 @@
+//! Accounting export: one CSV row per swap created in a [`Period`], with the amounts, the operator fee, the
+//! Lightning payment hash and every Solana signature the daemon recorded for it. Times are UTC.
+
+use std::{fmt, str::FromStr};
+
+use crate::{
+    store::{Store, StoreError},
+    swap::Swap,
+};
+
+const HEADER: &str = "swap_id,direction,state,created_at,updated_at,amount_sat,token_amount,fee_tokens,\
+                      payment_hash,counterparty,signatures\n";
+
+pub(crate) const SECS_PER_DAY: i64 = 86_400;
+
+/// Unix seconds `[from, to)`, written as a calendar year (`2026`), month (`2026-09`), day (`2026-09-30`), or
+/// two dates joined by `..` with the end date included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Period {
+    pub from: i64,
+    pub to: i64,
+}
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((from, to)) = s.split_once("..") {
+            let (from, to) = (Self::from_str(from)?, Self::from_str(to)?);
+            if to.to <= from.from {
+                return Err(format!("period {s:?} ends before it starts"));
+            }
+            return Ok(Self {
+                from: from.from,
+                to: to.to,
+            });
+        }
+        let invalid = || format!("invalid period {s:?}: expected YYYY, YYYY-MM, YYYY-MM-DD or FROM..TO");
+        let parts: Vec<i64> = s
+            .split('-')
+            .map(|p| p.parse().map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+        let (from, to) = match parts[..] {
+            [y] => (days_from_civil(y, 1, 1), days_from_civil(y + 1, 1, 1)),
+            [y, m] if (1..=12).contains(&m) => {
+                let next = if m == 12 { (y + 1, 1) } else { (y, m + 1) };
+                (days_from_civil(y, m, 1), days_from_civil(next.0, next.1, 1))
+            }
+            [y, m, d] if (1..=12).contains(&m) && (1..=31).contains(&d) => {
+                let day = days_from_civil(y, m, d);
+                if civil_from_days(day) != (y, m, d) {
+                    return Err(invalid());
+                }
+                (day, day + 1)
+            }
+            _ => return Err(invalid()),
+        };
+        Ok(Self {
+            from: from * SECS_PER_DAY,
+            to: to * SECS_PER_DAY,
+        })
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", date(self.from), date(self.to - 1))
+    }
+}
+
+/// The CSV for swaps created in `period`, oldest first.
+pub fn csv(store: &Store, period: &Period) -> Result<String, StoreError> {
+    let mut out = String::from(HEADER);
+    for swap in store.swaps_created_between(period.from, period.to)? {
+        let fee_tokens = store.quote_of_swap(&swap.id)?.map(|q| q.fee_tokens());
+        let signatures = store.signatures_of(&swap.id)?;
+        out.push_str(&row(&swap, fee_tokens, &signatures));
+    }
+    Ok(out)
+}
+
+fn row(swap: &Swap, fee_tokens: Option<u64>, signatures: &[String]) -> String {
+    let amount_sat = match swap.amount_msat % 1000 {
+        0 => (swap.amount_msat / 1000).to_string(),
+        msat => format!("{}.{msat:03}", swap.amount_msat / 1000),
+    };
+    let fields = [
+        swap.id.clone(),
+        swap.direction.to_string(),
+        swap.state.to_string(),
+        timestamp(swap.created_at),
+        timestamp(swap.updated_at),
+        amount_sat,
+        swap.token_amount.to_string(),
+        fee_tokens.map(|f| f.to_string()).unwrap_or_default(),
+        hex::encode(swap.payment_hash),
+        swap.counterparty.to_string(),
+        signatures.join(" "),
+    ];
+    let mut line = fields.map(|f| escape(&f)).join(",");
+    line.push('\n');
+    line
+}
+
+/// Quotes a field holding a separator, quote or line break.
+pub(crate) fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// RFC 3339 UTC, e.g. `2026-09-30T17:04:05Z`.
+pub(crate) fn timestamp(secs: i64) -> String {
+    let t = secs.rem_euclid(SECS_PER_DAY);
+    format!("{}T{:02}:{:02}:{:02}Z", date(secs), t / 3600, t / 60 % 60, t % 60)
+}
+
+/// The UTC date of `secs`, e.g. `2026-09-30`.
+pub(crate) fn date(secs: i64) -> String {
+    let (y, m, d) = civil_from_days(secs.div_euclid(SECS_PER_DAY));
+    format!("{y:04}-{m:02}-{d:02}")
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date (Howard Hinnant's algorithm).
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (m + if m > 2 { -3 } else { 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Inverse of [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(m <= 2), m, d)
+}
@@ -0,0 +1,173 @@
+//! Caps on what the daemon has outstanding at once: swaps in flight, USDT locked in the operator's escrows,
+//! Lightning value of those swaps, and how much of that any one counterparty holds. Active swaps and open
+//! quotes both count, as for [`liquidity`](crate::liquidity), so a peer cannot take the whole inventory by
+//! collecting quotes. Quotes are sized to fit and swaps created without one are refused when they do not.
+//!
+//! Separately, the Lightning value locked in pending HTLCs (held hold-invoice payments and payments in flight)
+//! bounds what a mass timeout could cost: once it reaches its ceiling nothing more is quoted or created until
+//! some of those HTLCs resolve, whatever the amount.
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    quote::QuoteConfig,
+    store::{Store, StoreError},
+    swap::{unix_now, Direction, Swap, SwapState},
+};
+
+/// What is outstanding now, overall and with one counterparty.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Exposure {
+    /// Active swaps and open quotes.
+    pub swaps: u64,
+    /// LnToUsdt: tokens the operator has in escrows, or owes to ones it has yet to fund.
+    pub token_amount: u64,
+    /// Lightning amount of every active swap and open quote.
+    pub amount_msat: u64,
+    /// Lightning amount of the counterparty's active swaps and open quotes.
+    pub counterparty_msat: u64,
+    /// Lightning amount of the swaps with HTLCs pending on the operator's node.
+    pub htlc_msat: u64,
+}
+
+impl Exposure {
+    pub fn load(store: &Store, counterparty: &Pubkey) -> Result<Self, StoreError> {
+        let mut exposure = Self::default();
+        let mut add = |direction: Direction, amount_msat: u64, token_amount: u64, with: &Pubkey| {
+            exposure.swaps += 1;
+            exposure.amount_msat = exposure.amount_msat.saturating_add(amount_msat);
+            if direction == Direction::LnToUsdt {
+                exposure.token_amount = exposure.token_amount.saturating_add(token_amount);
+            }
+            if with == counterparty {
+                exposure.counterparty_msat = exposure.counterparty_msat.saturating_add(amount_msat);
+            }
+        };
+        for swap in store.active()? {
+            // Once refunding, the escrow's tokens are on their way back and no Lightning is outstanding.
+            if swap.state != SwapState::Refunding {
+                add(swap.direction, swap.amount_msat, swap.token_amount, &swap.counterparty);
+            }
+            if holds_htlcs(&swap) {
+                exposure.htlc_msat = exposure.htlc_msat.saturating_add(swap.amount_msat);
+            }
+        }
+        let now = unix_now();
+        for quote in store.open_quotes(now)? {
+            add(
+                quote.direction,
+                quote.amount_msat,
+                quote.token_amount,
+                &quote.counterparty,
+            );
+        }
+        for quote in store.open_keysend_quotes(now)? {
+            add(
+                Direction::LnToUsdt,
+                quote.amount_msat,
+                quote.token_amount,
+                &quote.recipient,
+            );
+        }
+        Ok(exposure)
+    }
+}
+
+/// Lightning amount of `swaps` with HTLCs pending on the operator's node.
+pub fn pending_htlc_msat<'a>(swaps: impl IntoIterator<Item = &'a Swap>) -> u64 {
+    swaps
+        .into_iter()
+        .filter(|swap| holds_htlcs(swap))
+        .fold(0, |sum, swap| sum.saturating_add(swap.amount_msat))
+}
+
+/// Whether `swap` has HTLCs pending on the operator's node, which fail or force a channel closed if they time
+/// out: the payer's, held by a hold invoice until the escrow is funded and the invoice settled, or the
+/// operator's own payment in flight.
+pub fn holds_htlcs(swap: &Swap) -> bool {
+    match swap.state {
+        SwapState::InvoiceAccepted | SwapState::Paying => true,
+        SwapState::EscrowFunded => swap.hold,
+        _ => false,
+    }
+}
+
+/// `None` leaves a limit off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExposureLimits {
+    /// Active swaps and open quotes.
+    pub max_swaps: Option<u64>,
+    /// Tokens in or owed to the operator's escrows.
+    pub max_token_amount: Option<u64>,
+    /// Lightning amount across all swaps.
+    pub max_amount_msat: Option<u64>,
+    /// Lightning amount across any one counterparty's swaps.
+    pub max_counterparty_msat: Option<u64>,
+    /// Lightning amount in pending HTLCs, at which quoting stops.
+    pub max_htlc_msat: Option<u64>,
+}
+
+impl ExposureLimits {
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The largest swap in `direction` that still fits next to `exposure` at `cfg`'s price, or why none does.
+    pub fn max_amount_msat(&self, exposure: &Exposure, direction: Direction, cfg: &QuoteConfig) -> Result<u64, String> {
+        self.check_swaps(exposure)?;
+        let left = |limit: Option<u64>, used: u64| limit.map_or(u64::MAX, |limit| limit.saturating_sub(used));
+        let mut max = left(self.max_amount_msat, exposure.amount_msat)
+            .min(left(self.max_counterparty_msat, exposure.counterparty_msat));
+        if direction == Direction::LnToUsdt && self.max_token_amount.is_some() {
+            max = max.min(cfg.max_amount_for_tokens(left(self.max_token_amount, exposure.token_amount)));
+        }
+        Ok(max)
+    }
+
+    /// Whether a new swap of `amount_msat` for `token_amount` fits next to `exposure`.
+    pub fn check(
+        &self,
+        exposure: &Exposure,
+        direction: Direction,
+        amount_msat: u64,
+        token_amount: u64,
+    ) -> Result<(), String> {
+        self.check_swaps(exposure)?;
+        let over = |limit: Option<u64>, used: u64, adding: u64| limit.filter(|&l| used.saturating_add(adding) > l);
+        if let Some(limit) = over(self.max_amount_msat, exposure.amount_msat, amount_msat) {
+            return Err(format!(
+                "{} msat is outstanding and at most {limit} msat may be",
+                exposure.amount_msat
+            ));
+        }
+        if let Some(limit) = over(self.max_counterparty_msat, exposure.counterparty_msat, amount_msat) {
+            return Err(format!(
+                "the counterparty has {} msat outstanding and at most {limit} msat may be",
+                exposure.counterparty_msat
+            ));
+        }
+        if direction == Direction::LnToUsdt {
+            if let Some(limit) = over(self.max_token_amount, exposure.token_amount, token_amount) {
+                return Err(format!(
+                    "{} tokens are locked in escrows and at most {limit} may be",
+                    exposure.token_amount
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The limits that refuse any new swap, whatever its amount.
+    fn check_swaps(&self, exposure: &Exposure) -> Result<(), String> {
+        if self.max_swaps.is_some_and(|max| exposure.swaps >= max) {
+            return Err(format!("{} swaps are in flight, the most allowed", exposure.swaps));
+        }
+        if let Some(max) = self.max_htlc_msat.filter(|&max| exposure.htlc_msat >= max) {
+            return Err(format!(
+                "{} msat is in pending Lightning HTLCs and at most {max} msat may be",
+                exposure.htlc_msat
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,372 @@
+//! Per-quote fees. A [`FeePolicy`] starts from the configured fee rate and moves it with what the swap costs the
+//! operator when it is quoted: its size, how it shifts the channel inventory, what Lightning routing has cost
+//! lately and the Solana fee of the transaction the operator sends for it. The result is signed into the quote
+//! like a static fee, so the taker sees exactly what it pays. Offers advertise the most any amount in their range
+//! would be charged.
+
+use std::str::FromStr;
+
+use intercom_swap_client::{
+    client::{EscrowClient, FetchError},
+    fees::{FeeBounds, PriorityLevel},
+    transaction::{CLAIM_COMPUTE_UNITS, INIT_COMPUTE_UNITS},
+};
+
+use crate::{liquidity::Liquidity, quote::QuoteConfig, swap::Direction};
+
+/// Lightning payments the recent routing fee rate is taken over.
+pub const ROUTING_FEE_SAMPLE: u32 = 20;
+/// Base fee of a transaction with one signature.
+const SIGNATURE_LAMPORTS: u128 = 5_000;
+const LAMPORTS_PER_SOL: u128 = 1_000_000_000;
+
+/// A fee change for swaps of at least `min_amount_msat`, written `MIN_AMOUNT_MSAT:BPS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeTier {
+    pub min_amount_msat: u64,
+    /// Added to the fee rate, or taken off it when negative.
+    pub bps: i16,
+}
+
+impl FromStr for SizeTier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (amount, bps) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected MIN_AMOUNT_MSAT:BPS, got {s:?}"))?;
+        Ok(Self {
+            min_amount_msat: amount
+                .trim()
+                .parse()
+                .map_err(|e| format!("invalid amount {amount:?}: {e}"))?,
+            bps: bps.trim().parse().map_err(|e| format!("invalid bps {bps:?}: {e}"))?,
+        })
+    }
+}
+
+/// How quotes move off the configured fee. The default charges it as it is.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeePolicy {
+    /// The tier with the highest `min_amount_msat` the amount reaches applies.
+    pub size_tiers: Vec<SizeTier>,
+    /// Added at full imbalance to swaps that deepen the channel imbalance and taken off swaps that ease it,
+    /// in proportion to the imbalance.
+    pub skew_bps: u16,
+    /// usdt-to-ln: add the routing fee rate of recent payments, up to the routing fee budget. A fee probed for the
+    /// quote's own route is added whether or not this is set.
+    pub routing_fees: bool,
+    /// Token base units per SOL, to charge the Solana fee of the transaction the operator sends for the swap
+    /// (funding its escrow, or claiming the taker's) on top of the flat fee. `None` leaves it out.
+    pub token_per_sol: Option<u64>,
+    /// Bounds of the resulting fee rate; no ceiling but 100% when `max_fee_bps` is unset.
+    pub min_fee_bps: u16,
+    pub max_fee_bps: Option<u16>,
+}
+
+/// What the policy is evaluated against, gathered when quoting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeInputs {
+    /// Free liquidity; `None` leaves inventory out.
+    pub liquidity: Option<Liquidity>,
+    /// Routing fees of recent payments in bps of what they delivered; `None` before any.
+    pub routing_fee_bps: Option<u64>,
+    /// Routing fee of the route probed to the payee, in bps of the amount; replaces `routing_fee_bps`.
+    pub probed_routing_fee_bps: Option<u64>,
+    /// What Solana transactions pay per compute unit now.
+    pub compute_unit_price_micro_lamports: u64,
+}
+
+impl FeePolicy {
+    /// Whether quotes keep the configured fee as it is.
+    pub fn is_static(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether evaluating the policy needs the current compute unit price.
+    pub fn needs_compute_unit_price(&self) -> bool {
+        self.token_per_sol.is_some()
+    }
+
+    /// `cfg` with the fee rate and flat fee this policy charges for `amount_msat` in `direction`.
+    pub fn apply(&self, cfg: &QuoteConfig, direction: Direction, amount_msat: u64, inputs: &FeeInputs) -> QuoteConfig {
+        let mut bps = i64::from(cfg.fee_bps);
+        let tier = self
+            .size_tiers
+            .iter()
+            .filter(|t| amount_msat >= t.min_amount_msat)
+            .max_by_key(|t| t.min_amount_msat);
+        if let Some(tier) = tier {
+            bps += i64::from(tier.bps);
+        }
+        if let Some(liquidity) = &inputs.liquidity {
+            bps += self.skew_adjustment(direction, liquidity);
+        }
+        if direction == Direction::UsdtToLn {
+            let routing = match inputs.probed_routing_fee_bps {
+                Some(probed) => probed,
+                None if self.routing_fees => inputs.routing_fee_bps.unwrap_or(0),
+                None => 0,
+            };
+            bps += routing.min(u64::from(cfg.max_routing_fee_bps)) as i64;
+        }
+        let max = self.max_fee_bps.unwrap_or(10_000).min(10_000);
+        let fee_bps = bps.clamp(i64::from(self.min_fee_bps.min(max)), i64::from(max)) as u16;
+        QuoteConfig {
+            fee_bps,
+            flat_fee: cfg.flat_fee.saturating_add(self.solana_fee_tokens(direction, inputs)),
+            ..*cfg
+        }
+    }
+
+    /// The highest fee any amount within `cfg`'s range is charged in `direction`, for offers.
+    pub fn ceiling(&self, cfg: &QuoteConfig, direction: Direction, inputs: &FeeInputs) -> QuoteConfig {
+        let tiers = self
+            .size_tiers
+            .iter()
+            .map(|t| t.min_amount_msat)
+            .filter(|&amount| amount > cfg.min_amount_msat && amount <= cfg.max_amount_msat);
+        std::iter::once(cfg.min_amount_msat)
+            .chain(tiers)
+            .map(|amount| self.apply(cfg, direction, amount, inputs))
+            .max_by_key(|priced| priced.fee_bps)
+            .unwrap_or(*cfg)
+    }
+
+    /// Between `-skew_bps` and `skew_bps`: positive when `direction` moves Lightning toward the side that already
+    /// has more of it.
+    fn skew_adjustment(&self, direction: Direction, liquidity: &Liquidity) -> i64 {
+        let (local, remote) = (i128::from(liquidity.outbound_msat), i128::from(liquidity.inbound_msat));
+        if self.skew_bps == 0 || local + remote == 0 {
+            return 0;
+        }
+        let skew = (i128::from(self.skew_bps) * (local - remote) / (local + remote)) as i64;
+        match direction {
+            // Lightning received adds to the local side, Lightning paid to the remote one.
+            Direction::LnToUsdt => skew,
+            Direction::UsdtToLn => -skew,
+        }
+    }
+
+    fn solana_fee_tokens(&self, direction: Direction, inputs: &FeeInputs) -> u64 {
+        let Some(token_per_sol) = self.token_per_sol else {
+            return 0;
+        };
+        let compute_units = match direction {
+            Direction::LnToUsdt => INIT_COMPUTE_UNITS,
+            Direction::UsdtToLn => CLAIM_COMPUTE_UNITS,
+        };
+        let lamports = SIGNATURE_LAMPORTS
+            + u128::from(compute_units) * u128::from(inputs.compute_unit_price_micro_lamports) / 1_000_000;
+        u64::try_from(lamports * u128::from(token_per_sol) / LAMPORTS_PER_SOL).unwrap_or(u64::MAX)
+    }
+}
+
+/// The median compute unit price Solana transactions paid lately, for [`FeeInputs`].
+pub async fn compute_unit_price(client: &EscrowClient) -> Result<u64, FetchError> {
+    // No accounts: the fees recently paid across the cluster.
+    let fee = client
+        .recommend_fee(&[], CLAIM_COMPUTE_UNITS, PriorityLevel::Medium, FeeBounds::default())
+        .await?;
+    Ok(fee.compute_unit_price_micro_lamports)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+
+    const M: u64 = 1_000_000;
+
+    fn cfg() -> QuoteConfig {
+        QuoteConfig {
+            mint: Pubkey::new_unique(),
+            token_per_btc: 65_000 * M,
+            fee_bps: 30,
+            flat_fee: 0,
+            ttl_secs: 60,
+            min_amount_msat: 10 * M,
+            max_amount_msat: 1_000 * M,
+            max_routing_fee_bps: 100,
+        }
+    }
+
+    fn tiers(tiers: &[(u64, i16)]) -> FeePolicy {
+        FeePolicy {
+            size_tiers: tiers
+                .iter()
+                .map(|&(min_amount_msat, bps)| SizeTier { min_amount_msat, bps })
+                .collect(),
+            ..FeePolicy::default()
+        }
+    }
+
+    fn fee_bps(policy: &FeePolicy, direction: Direction, amount_msat: u64, inputs: &FeeInputs) -> u16 {
+        policy.apply(&cfg(), direction, amount_msat, inputs).fee_bps
+    }
+
+    #[test]
+    fn default_policy_keeps_the_configured_fee() {
+        let policy = FeePolicy::default();
+        assert!(policy.is_static());
+        assert!(!policy.needs_compute_unit_price());
+        let cfg = cfg();
+        for direction in [Direction::LnToUsdt, Direction::UsdtToLn] {
+            assert_eq!(policy.apply(&cfg, direction, 50 * M, &FeeInputs::default()), cfg);
+        }
+    }
+
+    #[test]
+    fn size_tier_applies_from_its_minimum() {
+        // Listed out of order: the highest minimum reached wins, not the last listed.
+        let policy = tiers(&[(500 * M, -20), (100 * M, -10)]);
+        let fee = |amount| fee_bps(&policy, Direction::LnToUsdt, amount, &FeeInputs::default());
+        assert_eq!(fee(100 * M - 1), 30);
+        assert_eq!(fee(100 * M), 20);
+        assert_eq!(fee(500 * M - 1), 20);
+        assert_eq!(fee(500 * M), 10);
+        assert_eq!(fee(u64::MAX), 10);
+    }
+
+    #[test]
+    fn tier_fee_is_clamped_to_the_bounds() {
+        let mut policy = tiers(&[(0, -50), (100 * M, 20_000)]);
+        policy.min_fee_bps = 5;
+        assert_eq!(fee_bps(&policy, Direction::LnToUsdt, M, &FeeInputs::default()), 5);
+        assert_eq!(
+            fee_bps(&policy, Direction::LnToUsdt, 100 * M, &FeeInputs::default()),
+            10_000
+        );
+        policy.max_fee_bps = Some(80);
+        assert_eq!(
+            fee_bps(&policy, Direction::LnToUsdt, 100 * M, &FeeInputs::default()),
+            80
+        );
+        // A floor above the ceiling gives way to it.
+        policy.min_fee_bps = 100;
+        assert_eq!(fee_bps(&policy, Direction::LnToUsdt, M, &FeeInputs::default()), 80);
+    }
+
+    #[test]
+    fn skew_charges_the_side_that_deepens_the_imbalance() {
+        let policy = FeePolicy {
+            skew_bps: 20,
+            ..FeePolicy::default()
+        };
+        // Three quarters of the channel balance is local: half way to full imbalance.
+        let inputs = FeeInputs {
+            liquidity: Some(Liquidity {
+                inbound_msat: 250 * M,
+                outbound_msat: 750 * M,
+                token_available: 0,
+            }),
+            ..FeeInputs::default()
+        };
+        assert_eq!(fee_bps(&policy, Direction::LnToUsdt, 50 * M, &inputs), 40);
+        assert_eq!(fee_bps(&policy, Direction::UsdtToLn, 50 * M, &inputs), 20);
+        let empty = FeeInputs {
+            liquidity: Some(Liquidity::default()),
+            ..FeeInputs::default()
+        };
+        assert_eq!(fee_bps(&policy, Direction::LnToUsdt, 50 * M, &empty), 30);
+    }
+
+    #[test]
+    fn routing_fees_apply_to_usdt_to_ln_up_to_the_budget() {
+        let policy = FeePolicy {
+            routing_fees: true,
+            ..FeePolicy::default()
+        };
+        let recent = |bps| FeeInputs {
+            routing_fee_bps: Some(bps),
+            ..FeeInputs::default()
+        };
+        assert_eq!(fee_bps(&policy, Direction::UsdtToLn, 50 * M, &recent(40)), 70);
+        assert_eq!(fee_bps(&policy, Direction::UsdtToLn, 50 * M, &recent(500)), 130);
+        assert_eq!(fee_bps(&policy, Direction::LnToUsdt, 50 * M, &recent(40)), 30);
+        // A probed route replaces the recent rate, and counts even when recent fees are left out.
+        let probed = FeeInputs {
+            routing_fee_bps: Some(40),
+            probed_routing_fee_bps: Some(7),
+            ..FeeInputs::default()
+        };
+        assert_eq!(fee_bps(&policy, Direction::UsdtToLn, 50 * M, &probed), 37);
+        assert_eq!(fee_bps(&FeePolicy::default(), Direction::UsdtToLn, 50 * M, &probed), 37);
+        assert_eq!(
+            fee_bps(&FeePolicy::default(), Direction::UsdtToLn, 50 * M, &recent(40)),
+            30
+        );
+    }
+
+    #[test]
+    fn solana_fee_is_added_to_the_flat_fee() {
+        // 150 tokens of 6 decimals per SOL, 1 lamport per compute unit.
+        let policy = FeePolicy {
+            token_per_sol: Some(150 * M),
+            ..FeePolicy::default()
+        };
+        assert!(policy.needs_compute_unit_price());
+        let inputs = FeeInputs {
+            compute_unit_price_micro_lamports: M,
+            ..FeeInputs::default()
+        };
+        let cfg = QuoteConfig {
+            flat_fee: 1_000,
+            ..cfg()
+        };
+        // 5_000 + 300_000 lamports to fund the escrow, 5_000 + 150_000 to claim one.
+        assert_eq!(
+            policy.apply(&cfg, Direction::LnToUsdt, 50 * M, &inputs).flat_fee,
+            1_000 + 45_750
+        );
+        assert_eq!(
+            policy.apply(&cfg, Direction::UsdtToLn, 50 * M, &inputs).flat_fee,
+            1_000 + 23_250
+        );
+        let free = FeeInputs::default();
+        assert_eq!(
+            policy.apply(&cfg, Direction::UsdtToLn, 50 * M, &free).flat_fee,
+            1_000 + 750
+        );
+    }
+
+    #[test]
+    fn ceiling_is_the_dearest_tier_within_the_range() {
+        // Below the range (reached by its minimum), within it, and above it.
+        let policy = tiers(&[(5 * M, 5), (100 * M, 20), (500 * M, -10), (2_000 * M, 50)]);
+        let offer = policy.ceiling(&cfg(), Direction::LnToUsdt, &FeeInputs::default());
+        assert_eq!(offer.fee_bps, 50);
+        let cheaper = tiers(&[(500 * M, -10)]);
+        assert_eq!(
+            cheaper
+                .ceiling(&cfg(), Direction::LnToUsdt, &FeeInputs::default())
+                .fee_bps,
+            30
+        );
+        let past_max = tiers(&[(cfg().max_amount_msat + 1, 50)]);
+        assert_eq!(
+            past_max
+                .ceiling(&cfg(), Direction::LnToUsdt, &FeeInputs::default())
+                .fee_bps,
+            30
+        );
+        let at_max = tiers(&[(cfg().max_amount_msat, 50)]);
+        assert_eq!(
+            at_max
+                .ceiling(&cfg(), Direction::LnToUsdt, &FeeInputs::default())
+                .fee_bps,
+            80
+        );
+    }
+
+    #[test]
+    fn parses_size_tiers() {
+        let tier = |min_amount_msat, bps| SizeTier { min_amount_msat, bps };
+        assert_eq!("100000000:-5".parse(), Ok(tier(100_000_000, -5)));
+        assert_eq!(" 0 : 12 ".parse(), Ok(tier(0, 12)));
+        for bad in ["100", "x:1", "1:x", "1:40000", "-1:5", "1:2:3", ""] {
+            assert!(bad.parse::<SizeTier>().is_err(), "{bad:?}");
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! gRPC control API (`swapd.v1.Swapd`, defined in `proto/swapd/v1/swapd.proto`), so exchanges and bots can
+//! quote, queue, follow and cancel swaps with generated clients. A thin mapping onto [`control`](crate::control).
+//! Each call needs credentials with the scope it names (see [`auth`](crate::auth)) in its `authorization` or
+//! `x-api-key` metadata. Quotes and new swaps count against the [`ratelimit`](crate::ratelimit) limits.
+
+use std::{fmt, future::Future, net::SocketAddr, sync::Arc};
+
+use tokio::sync::Notify;
+use tonic::{transport::Server, Request, Response, Status};
+use tracing::info;
+
+use crate::{
+    auth::{credential, Authenticator, Principal, Scope, API_KEY_HEADER},
+    control::{idempotency_key, parse_node_id, parse_pubkey, parse_quote_id, Control, ControlError, NewSwap},
+    ln::LnBackend,
+    negotiate::Maker,
+    quote::{Quote, QuoteRequest},
+    ratelimit::{Action, RateLimiter},
+    swap::{Direction, Swap, SwapState},
+};
+
+pub mod proto {
+    tonic::include_proto!("swapd.v1");
+}
+
+use proto::swapd_server::{Swapd, SwapdServer};
+
+/// Negotiator transport name of the API's maker.
+pub const TRANSPORT: &str = "grpc";
+
+#[derive(Debug)]
+pub enum GrpcError {
+    Transport(tonic::transport::Error),
+    /// Cannot bind the TLS listener.
+    Io(std::io::Error),
+}
+
+impl fmt::Display for GrpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "grpc: {e}"),
+            Self::Io(e) => write!(f, "grpc: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for GrpcError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            Self::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for GrpcError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<tonic::transport::Error> for GrpcError {
+    fn from(e: tonic::transport::Error) -> Self {
+        Self::Transport(e)
+    }
+}
+
+pub struct GrpcConfig {
+    pub listen: SocketAddr,
+    /// Serve over TLS with this certificate instead of plaintext HTTP/2.
+    #[cfg(feature = "tls")]
+    pub tls: Option<Arc<crate::tls::Tls>>,
+}
+
+pub struct GrpcApi {
+    cfg: GrpcConfig,
+    maker: Arc<Maker>,
+    auth: Authenticator,
+    limiter: Arc<RateLimiter>,
+}
+
+impl GrpcApi {
+    /// `limiter` may be shared with the REST API, so its limits hold across both.
+    pub fn new(cfg: GrpcConfig, maker: Arc<Maker>, auth: Authenticator, limiter: Arc<RateLimiter>) -> Self {
+        Self {
+            cfg,
+            maker,
+            auth,
+            limiter,
+        }
+    }
+
+    /// Serves the API until `shutdown` resolves. Swaps it queues are picked up by the engine that owns `wake`.
+    pub async fn run<L: LnBackend>(
+        &self,
+        ln: &L,
+        wake: Arc<Notify>,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(), GrpcError> {
+        let (control, calls) = Control::new(self.maker.clone(), wake);
+        let router = Server::builder().add_service(SwapdServer::new(Service {
+            control,
+            auth: self.auth.clone(),
+            limiter: self.limiter.clone(),
+        }));
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.cfg.tls {
+            let listener = tokio::net::TcpListener::bind(self.cfg.listen).await?;
+            let incoming = crate::tls::incoming(listener, tls.server_config(&[b"h2"]));
+            info!(listen = %self.cfg.listen, "grpc api started over tls");
+            return Ok(calls
+                .answer_until(ln, router.serve_with_incoming_shutdown(incoming, shutdown))
+                .await?);
+        }
+        let server = router.serve_with_shutdown(self.cfg.listen, shutdown);
+        info!(listen = %self.cfg.listen, "grpc api started");
+        Ok(calls.answer_until(ln, server).await?)
+    }
+}
+
+struct Service {
+    control: Control,
+    auth: Authenticator,
+    limiter: Arc<RateLimiter>,
+}
+
+impl Service {
+    fn authorize<T>(&self, request: &Request<T>, scope: Scope) -> Result<Principal, Status> {
+        let header = |name: &str| request.metadata().get(name).and_then(|v| v.to_str().ok());
+        let principal = self
+            .auth
+            .authenticate(credential(header("authorization"), header(API_KEY_HEADER)))
+            .map_err(|e| status(e.into()))?;
+        principal.require(scope).map_err(|e| status(e.into()))?;
+        Ok(principal)
+    }
+
+    /// Counts a call to `action` against the caller's and its address's rate limits.
+    fn limit<T>(&self, request: &Request<T>, principal: &Principal, action: Action) -> Result<(), Status> {
+        let ip = request.remote_addr().map(|peer| peer.ip());
+        self.limiter
+            .check(action, &principal.name, ip)
+            .map_err(|e| status(e.into()))
+    }
+}
+
+#[tonic::async_trait]
+impl Swapd for Service {
+    async fn get_info(
+        &self,
+        request: Request<proto::GetInfoRequest>,
+    ) -> Result<Response<proto::GetInfoResponse>, Status> {
+        self.authorize(&request, Scope::Read)?;
+        let info = self.control.info().await.map_err(status)?;
+        Ok(Response::new(proto::GetInfoResponse {
+            version: info.version.to_string(),
+            operator: info.operator.to_string(),
+            mint: info.mint.to_string(),
+            directions: info.directions.iter().map(|&d| direction_to_proto(d) as i32).collect(),
+            fee_bps: info.fee_bps.into(),
+            min_amount_msat: info.min_amount_msat,
+            max_amount_msat: info.max_amount_msat,
+            direction_terms: info
+                .direction_terms
+                .iter()
+                .map(|t| proto::DirectionTerms {
+                    direction: direction_to_proto(t.direction) as i32,
+                    fee_bps: t.fee_bps.into(),
+                    flat_fee: t.flat_fee,
+                    min_amount_msat: t.min_amount_msat,
+                    max_amount_msat: t.max_amount_msat,
+                })
+                .collect(),
+            active_swaps: info.active_swaps,
+            channel_balance: info.channel_balance.map(|b| proto::ChannelBalance {
+                local_msat: b.local_msat,
+                remote_msat: b.remote_msat,
+            }),
+        }))
+    }
+
+    async fn quote(&self, request: Request<proto::QuoteRequest>) -> Result<Response<proto::Quote>, Status> {
+        let principal = self.authorize(&request, Scope::Create)?;
+        self.limit(&request, &principal, Action::Quote)?;
+        let request = request.into_inner();
+        let key = idempotency_key(&principal.name, &request.idempotency_key).map_err(status)?;
+        let request = QuoteRequest {
+            direction: direction_from_proto(request.direction)?,
+            amount_msat: request.amount_msat,
+            counterparty: parse_pubkey(&request.counterparty, "counterparty").map_err(status)?,
+            allow_partial: request.allow_partial,
+            destination: match request.destination.as_str() {
+                "" => None,
+                id => Some(parse_node_id(id, "destination").map_err(status)?),
+            },
+        };
+        let quote = self.control.quote(request, key.as_ref()).await.map_err(status)?;
+        Ok(Response::new(quote_to_proto(&quote)))
+    }
+
+    async fn create_swap(&self, request: Request<proto::CreateSwapRequest>) -> Result<Response<proto::Swap>, Status> {
+        use proto::create_swap_request::Kind;
+
+        let principal = self.authorize(&request, Scope::Create)?;
+        self.limit(&request, &principal, Action::Create)?;
+        let timeout = |secs: i64| (secs != 0).then_some(secs);
+        let request = request.into_inner();
+        let key = idempotency_key(&principal.name, &request.idempotency_key).map_err(status)?;
+        let new = match request.kind {
+            Some(Kind::AcceptQuote(accept)) => NewSwap::AcceptQuote {
+                quote_id: parse_quote_id(&accept.quote_id).map_err(status)?,
+                bolt11: accept.bolt11,
+                hold: accept.hold,
+                funding_timeout_secs: timeout(accept.funding_timeout_secs),
+            },
+            Some(Kind::LnToUsdt(swap)) => NewSwap::LnToUsdt {
+                recipient: parse_pubkey(&swap.recipient, "recipient").map_err(status)?,
+                amount_msat: swap.amount_msat,
+                token_amount: swap.token_amount,
+                hold: swap.hold,
+            },
+            Some(Kind::UsdtToLn(swap)) => NewSwap::UsdtToLn {
+                refund: parse_pubkey(&swap.refund, "refund").map_err(status)?,
+                bolt11: swap.bolt11,
+                token_amount: swap.token_amount,
+                funding_timeout_secs: timeout(swap.funding_timeout_secs),
+            },
+            None => return Err(Status::invalid_argument("missing swap kind")),
+        };
+        let swap = self.control.create(new, key.as_ref()).map_err(status)?;
+        Ok(Response::new(swap_to_proto(&swap)))
+    }
+
+    async fn get_swap(&self, request: Request<proto::GetSwapRequest>) -> Result<Response<proto::Swap>, Status> {
+        self.authorize(&request, Scope::Read)?;
+        let swap = self.control.get(&request.into_inner().id).map_err(status)?;
+        Ok(Response::new(swap_to_proto(&swap)))
+    }
+
+    async fn list_swaps(
+        &self,
+        request: Request<proto::ListSwapsRequest>,
+    ) -> Result<Response<proto::ListSwapsResponse>, Status> {
+        self.authorize(&request, Scope::Read)?;
+        let request = request.into_inner();
+        let swaps = self
+            .control
+            .list(request.active, request.limit as usize)
+            .map_err(status)?;
+        Ok(Response::new(proto::ListSwapsResponse {
+            swaps: swaps.iter().map(swap_to_proto).collect(),
+        }))
+    }
+
+    async fn cancel_swap(&self, request: Request<proto::CancelSwapRequest>) -> Result<Response<proto::Swap>, Status> {
+        self.authorize(&request, Scope::Create)?;
+        let swap = self.control.cancel(&request.into_inner().id).map_err(status)?;
+        Ok(Response::new(swap_to_proto(&swap)))
+    }
+}
+
+fn direction_from_proto(direction: i32) -> Result<Direction, Status> {
+    match proto::Direction::try_from(direction) {
+        Ok(proto::Direction::LnToUsdt) => Ok(Direction::LnToUsdt),
+        Ok(proto::Direction::UsdtToLn) => Ok(Direction::UsdtToLn),
+        _ => Err(Status::invalid_argument("direction is required")),
+    }
+}
+
+fn direction_to_proto(direction: Direction) -> proto::Direction {
+    match direction {
+        Direction::LnToUsdt => proto::Direction::LnToUsdt,
+        Direction::UsdtToLn => proto::Direction::UsdtToLn,
+    }
+}
+
+fn state_to_proto(state: SwapState) -> proto::SwapState {
+    match state {
+        SwapState::Created => proto::SwapState::Created,
+        SwapState::InvoiceCreated => proto::SwapState::InvoiceCreated,
+        SwapState::InvoiceAccepted => proto::SwapState::InvoiceAccepted,
+        SwapState::KeysendReceived => proto::SwapState::KeysendReceived,
+        SwapState::EscrowFunded => proto::SwapState::EscrowFunded,
+        SwapState::InvoiceSettled => proto::SwapState::InvoiceSettled,
+        SwapState::Refunding => proto::SwapState::Refunding,
+        SwapState::EscrowVerified => proto::SwapState::EscrowVerified,
+        SwapState::Paying => proto::SwapState::Paying,
+        SwapState::Claiming => proto::SwapState::Claiming,
+        SwapState::Completed => proto::SwapState::Completed,
+        SwapState::Refunded => proto::SwapState::Refunded,
+        SwapState::Failed => proto::SwapState::Failed,
+    }
+}
+
+/// Built from [`Swap::to_json`], so the API withholds exactly what the JSON view does.
+fn swap_to_proto(swap: &Swap) -> proto::Swap {
+    let json = swap.to_json();
+    proto::Swap {
+        id: swap.id.clone(),
+        direction: direction_to_proto(swap.direction) as i32,
+        state: state_to_proto(swap.state) as i32,
+        bolt11: json["bolt11"].as_str().map(str::to_string),
+        amount_msat: swap.amount_msat,
+        hold: swap.hold,
+        token_amount: swap.token_amount,
+        counterparty: swap.counterparty.to_string(),
+        refund_after: swap.refund_after,
+        deadline: swap.deadline,
+        signature: swap.signature.clone(),
+        error: swap.error.clone(),
+        cancel_requested: swap.cancel_requested,
+        created_at: swap.created_at,
+        updated_at: swap.updated_at,
+    }
+}
+
+fn quote_to_proto(quote: &Quote) -> proto::Quote {
+    proto::Quote {
+        id: hex::encode(quote.id),
+        direction: direction_to_proto(quote.direction) as i32,
+        amount_msat: quote.amount_msat,
+        token_per_btc: quote.token_per_btc,
+        fee_bps: quote.fee_bps.into(),
+        flat_fee: quote.flat_fee,
+        token_amount: quote.token_amount,
+        counterparty: quote.counterparty.to_string(),
+        mint: quote.mint.to_string(),
+        payment_hash: quote.payment_hash.map(hex::encode),
+        expires_at: quote.expires_at,
+        signature: quote.signature.to_string(),
+    }
+}
+
+fn status(e: ControlError) -> Status {
+    let message = e.to_string();
+    match e {
+        ControlError::InvalidArgument(_) => Status::invalid_argument(message),
+        ControlError::NotFound(_) => Status::not_found(message),
+        ControlError::AlreadyExists(_) => Status::already_exists(message),
+        ControlError::FailedPrecondition(_) => Status::failed_precondition(message),
+        ControlError::InsufficientLiquidity(_) => Status::resource_exhausted(message),
+        ControlError::Rejected(_) => Status::permission_denied(message),
+        ControlError::Unavailable(_) => Status::unavailable(message),
+        ControlError::Unauthenticated(_) => Status::unauthenticated(message),
+        ControlError::PermissionDenied(_) => Status::permission_denied(message),
+        ControlError::RateLimited(limited) => {
+            let mut status = Status::resource_exhausted(message);
+            let secs = limited.retry_after.as_secs_f64().ceil() as u64;
+            status.metadata_mut().insert("retry-after", secs.into());
+            status
+        }
+        ControlError::Internal(_) => Status::internal(message),
+    }
+}
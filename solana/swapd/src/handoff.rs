@@ -0,0 +1,327 @@
+//! Moving in-flight swaps to another host. [`export`] writes every active swap into a bundle sealed under a
+//! transfer key: the full swap row (invoice, preimage, deadlines), what the stopped engine's checkpoint knew about
+//! it, and the operator key its escrows name. It then fences the swaps in the exporting swap store, so no engine
+//! sharing it steps or writes them from then on, even one started again by mistake. [`import`] opens the bundle on
+//! the new host, checks that it runs as the same operator, seals the preimages under its own preimage key and
+//! inserts the swaps with their checkpoint, so a payment that was in flight is read back rather than sent again.
+//!
+//! Swaps only move while nothing acts on them: export refuses unless each swap still matches the checkpoint its
+//! engine wrote on a clean shutdown, and none is being paid through an on-chain HTLC, whose refund key stays
+//! with the host that opened it. [`release`] lifts the fence of a bundle that was never imported.
+
+use std::{fmt, io};
+
+use rand::RngCore;
+use serde_json::{json, Value};
+use solana_sdk::{hash::hash, pubkey::Pubkey};
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+use crate::{
+    engine::Checkpoint,
+    journal::Cause,
+    store::{Store, StoreError},
+    swap::{unix_now, Swap},
+    vault::{Preimage, Vault, VaultError},
+};
+
+/// Leads every bundle; also the associated data of its sealed body.
+const MAGIC: &[u8] = b"swapd-handoff-1\n";
+/// Journal actor of swaps written by an import.
+const ACTOR: &str = "handoff";
+
+#[derive(Debug)]
+pub enum HandoffError {
+    Store(StoreError),
+    Vault(VaultError),
+    Io(io::Error),
+    Malformed(String),
+    /// A swap moved on since the engine's last clean shutdown, or it never shut down: swapd is still running.
+    Running(String),
+    /// A swap is being paid through an on-chain HTLC (see [`crate::onchain`]).
+    Onchain(String),
+    /// The bundle's escrows name another operator key than the importing host's.
+    WrongOperator {
+        bundle: Pubkey,
+        host: Pubkey,
+    },
+}
+
+impl fmt::Display for HandoffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Store(e) => write!(f, "{e}"),
+            Self::Vault(VaultError::Unsealable) => {
+                f.write_str("cannot open the bundle: wrong transfer key, or the bundle is damaged")
+            }
+            Self::Vault(e) => write!(f, "transfer key: {e}"),
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Malformed(e) => write!(f, "malformed bundle: {e}"),
+            Self::Running(e) => write!(f, "{e}; stop swapd (and let it finish its shutdown) before exporting"),
+            Self::Onchain(id) => write!(
+                f,
+                "swap {id} is being paid through an on-chain HTLC refundable only from this host; let it finish \
+                 before exporting"
+            ),
+            Self::WrongOperator { bundle, host } => write!(
+                f,
+                "the bundle's escrows belong to operator {bundle}, but this host runs as {host}; import with the \
+                 operator key the swaps were made with"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HandoffError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Store(e) => Some(e),
+            Self::Vault(e) => Some(e),
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<StoreError> for HandoffError {
+    fn from(e: StoreError) -> Self {
+        Self::Store(e)
+    }
+}
+
+impl From<VaultError> for HandoffError {
+    fn from(e: VaultError) -> Self {
+        Self::Vault(e)
+    }
+}
+
+impl From<io::Error> for HandoffError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// What an export or import moved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Handoff {
+    pub bundle_id: String,
+    pub operator: Pubkey,
+    pub swaps: Vec<String>,
+    /// Import only: swaps the importing database already had, left as they were.
+    pub skipped: Vec<String>,
+}
+
+impl Handoff {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "bundleId": self.bundle_id,
+            "operator": self.operator.to_string(),
+            "swaps": self.swaps,
+            "skipped": self.skipped,
+        })
+    }
+}
+
+/// Seals every active swap of `store` under `transfer` into a bundle and fences them; the bundle and what it
+/// holds. `force` skips the check that swapd is stopped, for a host that died without a clean shutdown.
+pub fn export(
+    store: &Store,
+    transfer: &Vault,
+    operator: Pubkey,
+    force: bool,
+) -> Result<(Vec<u8>, Handoff), HandoffError> {
+    let swaps = store.active()?;
+    let checkpoint = store.checkpoint()?;
+    let mut entries = Vec::with_capacity(swaps.len());
+    for swap in &swaps {
+        let saved = checkpoint.iter().find(|c| c.swap_id == swap.id);
+        match saved {
+            Some(c) if c.state == swap.state => {}
+            _ if force => warn!(swap = %swap.id, "exporting a swap without a matching shutdown checkpoint"),
+            Some(c) => {
+                return Err(HandoffError::Running(format!(
+                    "swap {} moved from {} to {} since the last shutdown",
+                    swap.id, c.state, swap.state
+                )))
+            }
+            None => {
+                return Err(HandoffError::Running(format!(
+                    "swap {} is not in a shutdown checkpoint",
+                    swap.id
+                )))
+            }
+        }
+        if store.htlc(&swap.id)?.is_some() {
+            return Err(HandoffError::Onchain(swap.id.clone()));
+        }
+        entries.push(swap_to_json(store.vault(), swap, saved)?);
+    }
+    let mut nonce = [0u8; 8];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+    let bundle_id = hex::encode(nonce);
+    let body = json!({
+        "bundleId": bundle_id,
+        "createdAt": unix_now(),
+        "operator": operator.to_string(),
+        "swaps": entries,
+    });
+    let body = Zeroizing::new(serde_json::to_vec(&body).map_err(|e| HandoffError::Malformed(e.to_string()))?);
+    let bundle = [MAGIC, &transfer.seal_blob(MAGIC, &body)?].concat();
+    let ids: Vec<String> = swaps.iter().map(|s| s.id.clone()).collect();
+    store.fence_swaps(&ids, &bundle_id, unix_now())?;
+    info!(bundle = %bundle_id, swaps = ids.len(), "swaps exported and fenced");
+    Ok((
+        bundle,
+        Handoff {
+            bundle_id,
+            operator,
+            swaps: ids,
+            skipped: Vec::new(),
+        },
+    ))
+}
+
+/// Opens `bundle` with `transfer` and inserts its swaps into `store`, whose host runs as `operator`.
+pub fn import(store: &Store, transfer: &Vault, bundle: &[u8], operator: Pubkey) -> Result<Handoff, HandoffError> {
+    let sealed = bundle
+        .strip_prefix(MAGIC)
+        .ok_or_else(|| HandoffError::Malformed("not a swapd handoff bundle".into()))?;
+    let body = transfer.open_blob(MAGIC, sealed)?;
+    let body: Value = serde_json::from_slice(&body).map_err(|e| HandoffError::Malformed(e.to_string()))?;
+    let bundle_id = str_field(&body, "bundleId")?.to_string();
+    let bundle_operator: Pubkey = str_field(&body, "operator")?
+        .parse()
+        .map_err(|_| HandoffError::Malformed("invalid operator".into()))?;
+    if bundle_operator != operator {
+        return Err(HandoffError::WrongOperator {
+            bundle: bundle_operator,
+            host: operator,
+        });
+    }
+    let entries = body["swaps"]
+        .as_array()
+        .ok_or_else(|| HandoffError::Malformed("no swaps".into()))?;
+    let cause = Cause::new(ACTOR).because(format!("imported from bundle {bundle_id}"));
+    let (mut swaps, mut skipped, mut checkpoint) = (Vec::new(), Vec::new(), Vec::new());
+    for entry in entries {
+        let (swap, saved) = swap_from_json(entry)?;
+        if !store.insert(&swap, &cause)? {
+            warn!(swap = %swap.id, bundle = %bundle_id, "swap already in this database; left as it is");
+            skipped.push(swap.id);
+            continue;
+        }
+        checkpoint.extend(saved);
+        swaps.push(swap.id);
+    }
+    store.extend_checkpoint(&checkpoint)?;
+    info!(bundle = %bundle_id, swaps = swaps.len(), skipped = skipped.len(), "swaps imported");
+    Ok(Handoff {
+        bundle_id,
+        operator,
+        swaps,
+        skipped,
+    })
+}
+
+/// Lifts the fence of bundle `bundle_id`, handing its swaps back to this host's engine. Only for a bundle that
+/// was never imported: otherwise both hosts drive the same swaps.
+pub fn release(store: &Store, bundle_id: &str) -> Result<usize, HandoffError> {
+    let released = store.release_fences(bundle_id)?;
+    info!(bundle = %bundle_id, swaps = released, "handoff fence released");
+    Ok(released)
+}
+
+/// Every field of `swap`, its preimage opened: the bundle is sealed as a whole.
+fn swap_to_json(vault: &Vault, swap: &Swap, saved: Option<&Checkpoint>) -> Result<Value, HandoffError> {
+    let preimage = swap
+        .preimage
+        .as_ref()
+        .map(|p| {
+            vault
+                .open(&swap.payment_hash, p)
+                .map(|plain| hex::encode(plain.as_slice()))
+        })
+        .transpose()?;
+    Ok(json!({
+        "id": swap.id,
+        "direction": swap.direction.as_str(),
+        "state": swap.state.as_str(),
+        "paymentHash": hex::encode(swap.payment_hash),
+        "preimage": preimage,
+        "bolt11": swap.bolt11,
+        "amountMsat": swap.amount_msat,
+        "hold": swap.hold,
+        "description": swap.description,
+        "tokenAmount": swap.token_amount,
+        "counterparty": swap.counterparty.to_string(),
+        "refundAfter": swap.refund_after,
+        "deadline": swap.deadline,
+        "signature": swap.signature,
+        "error": swap.error,
+        "cancelRequested": swap.cancel_requested,
+        "createdAt": swap.created_at,
+        "updatedAt": swap.updated_at,
+        "paymentInFlight": saved.map(|c| c.payment_in_flight),
+        "claimFailures": saved.map(|c| c.claim_failures),
+    }))
+}
+
+fn swap_from_json(v: &Value) -> Result<(Swap, Option<Checkpoint>), HandoffError> {
+    let malformed = |field: &str| HandoffError::Malformed(format!("swap without a valid {field}"));
+    let hash32 = |field: &str| -> Result<[u8; 32], HandoffError> {
+        hex::decode(str_field(v, field)?)
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| malformed(field))
+    };
+    let int = |field: &str| v[field].as_i64().ok_or_else(|| malformed(field));
+    let opt_str = |field: &str| v[field].as_str().map(str::to_string);
+    let preimage = match v["preimage"].as_str() {
+        Some(_) => Some(Preimage::plain(hash32("preimage")?)),
+        None => None,
+    };
+    let swap = Swap {
+        id: str_field(v, "id")?.to_string(),
+        direction: str_field(v, "direction")?.parse().map_err(HandoffError::Malformed)?,
+        state: str_field(v, "state")?.parse().map_err(HandoffError::Malformed)?,
+        payment_hash: hash32("paymentHash")?,
+        preimage,
+        bolt11: opt_str("bolt11"),
+        amount_msat: v["amountMsat"].as_u64().ok_or_else(|| malformed("amountMsat"))?,
+        hold: v["hold"].as_bool().unwrap_or(false),
+        description: opt_str("description"),
+        token_amount: v["tokenAmount"].as_u64().ok_or_else(|| malformed("tokenAmount"))?,
+        counterparty: str_field(v, "counterparty")?
+            .parse()
+            .map_err(|_| malformed("counterparty"))?,
+        refund_after: v["refundAfter"].as_i64(),
+        deadline: int("deadline")?,
+        signature: opt_str("signature"),
+        error: opt_str("error"),
+        cancel_requested: v["cancelRequested"].as_bool().unwrap_or(false),
+        created_at: int("createdAt")?,
+        updated_at: int("updatedAt")?,
+    };
+    if swap.id != hex::encode(swap.payment_hash) {
+        return Err(malformed("id"));
+    }
+    if let Some(Preimage::Plain(preimage)) = &swap.preimage {
+        if hash(preimage.as_slice()).to_bytes() != swap.payment_hash {
+            return Err(malformed("preimage"));
+        }
+    }
+    let saved = v["paymentInFlight"].as_bool().map(|payment_in_flight| Checkpoint {
+        swap_id: swap.id.clone(),
+        state: swap.state,
+        payment_in_flight,
+        claim_failures: v["claimFailures"].as_u64().unwrap_or(0) as u32,
+        taken_at: unix_now(),
+    });
+    Ok((swap, saved))
+}
+
+fn str_field<'a>(v: &'a Value, field: &str) -> Result<&'a str, HandoffError> {
+    v[field]
+        .as_str()
+        .ok_or_else(|| HandoffError::Malformed(format!("missing {field}")))
+}
@@ -0,0 +1,150 @@
+//! Binance spot (`/api/v3/order`), signed with HMAC-SHA256 over the query string.
+
+use reqwest::{Client, Method, StatusCode};
+use serde_json::Value;
+
+use super::{
+    fee_micro_usd, field, hmac_sha256_hex, parse_sat, placed, unix_millis, Credentials, Fill, HedgeError, Order, Side,
+    Venue,
+};
+use crate::rates::parse_micro;
+
+pub const URL: &str = "https://api.binance.com";
+const SYMBOL: &str = "BTCUSDT";
+/// Error code of an order lookup that matched nothing.
+const NO_SUCH_ORDER: i64 = -2013;
+
+#[derive(Debug, Clone)]
+pub struct Binance {
+    http: Client,
+    url: String,
+    credentials: Credentials,
+}
+
+impl Binance {
+    /// `url` is [`URL`], or the testnet's.
+    pub fn new(url: impl Into<String>, credentials: Credentials) -> Self {
+        Self {
+            http: Client::new(),
+            url: url.into(),
+            credentials,
+        }
+    }
+
+    /// Sends a signed request with `params` and returns the response body, or Binance's error code and message.
+    async fn signed(&self, method: Method, path: &str, params: &[(&str, &str)]) -> Result<Value, (i64, String)> {
+        let mut query = params
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join("&");
+        query.push_str(&format!("&recvWindow=5000&timestamp={}", unix_millis()));
+        let signature = hmac_sha256_hex(&self.credentials.api_secret, &query);
+        let response = self
+            .http
+            .request(method, format!("{}{path}?{query}&signature={signature}", self.url))
+            .header("X-MBX-APIKEY", &self.credentials.api_key)
+            .send()
+            .await
+            .map_err(|e| (0, e.to_string()))?;
+        let status = response.status();
+        // Binance does not know whether a request it answered with 5xx was executed.
+        if status.is_server_error() {
+            return Err((0, status.to_string()));
+        }
+        let body: Value = response.json().await.map_err(|e| (0, e.to_string()))?;
+        if status == StatusCode::OK {
+            return Ok(body);
+        }
+        let code = body["code"].as_i64().unwrap_or_default();
+        Err((code, body["msg"].as_str().unwrap_or(status.as_str()).to_string()))
+    }
+
+    /// What `order` executed. A placed market order lists its trades; a looked-up one does not, so they are read
+    /// for the commission.
+    async fn fill(&self, order: &Value) -> Result<Fill, HedgeError> {
+        let venue_order_id = order_id(order)?;
+        let filled_sat = field(order, "executedQty", parse_sat)?;
+        let cost_micro_usd = field(order, "cummulativeQuoteQty", parse_micro)?;
+        let trades = match &order["fills"] {
+            Value::Array(trades) => trades.clone(),
+            _ if filled_sat == 0 => Vec::new(),
+            _ => {
+                let params = [("symbol", SYMBOL), ("orderId", venue_order_id.as_str())];
+                let trades = self
+                    .signed(Method::GET, "/api/v3/myTrades", &params)
+                    .await
+                    .map_err(classify)?;
+                trades.as_array().cloned().unwrap_or_default()
+            }
+        };
+        let mut fee = 0;
+        for trade in &trades {
+            let price = field(trade, "price", parse_micro)?;
+            let asset = trade["commissionAsset"].as_str().unwrap_or_default();
+            let commission = trade["commission"].as_str().unwrap_or("0");
+            fee += fee_micro_usd(asset, commission, price)?.unwrap_or(0);
+        }
+        Ok(Fill {
+            venue_order_id,
+            open: matches!(
+                order["status"].as_str(),
+                Some("NEW" | "PARTIALLY_FILLED" | "PENDING_NEW")
+            ),
+            filled_sat,
+            cost_micro_usd,
+            fee_micro_usd: fee,
+        })
+    }
+}
+
+/// Binance answers a refused request with a negative code; without one the request did not get through.
+fn classify((code, message): (i64, String)) -> HedgeError {
+    match code {
+        0 => HedgeError::Http(message),
+        code => HedgeError::Rejected(format!("{message} (code {code})")),
+    }
+}
+
+fn order_id(body: &Value) -> Result<String, HedgeError> {
+    body["orderId"]
+        .as_u64()
+        .map(|id| id.to_string())
+        .ok_or_else(|| HedgeError::Parse("order without orderId".into()))
+}
+
+impl Venue for Binance {
+    fn name(&self) -> &'static str {
+        "binance"
+    }
+
+    async fn place(&self, order: &Order) -> Result<Fill, HedgeError> {
+        let side = match order.side {
+            Side::Buy => "BUY",
+            Side::Sell => "SELL",
+        };
+        let quantity = order.quantity();
+        let params = [
+            ("symbol", SYMBOL),
+            ("side", side),
+            ("type", "MARKET"),
+            ("quantity", quantity.as_str()),
+            ("newClientOrderId", order.client_order_id.as_str()),
+            ("newOrderRespType", "FULL"),
+        ];
+        let body = self
+            .signed(Method::POST, "/api/v3/order", &params)
+            .await
+            .map_err(classify)?;
+        self.fill(&body).await.map_err(placed)
+    }
+
+    async fn find(&self, client_order_id: &str) -> Result<Option<Fill>, HedgeError> {
+        let params = [("symbol", SYMBOL), ("origClientOrderId", client_order_id)];
+        match self.signed(Method::GET, "/api/v3/order", &params).await {
+            Ok(body) => self.fill(&body).await.map(Some),
+            Err((NO_SUCH_ORDER, _)) => Ok(None),
+            Err(e) => Err(classify(e)),
+        }
+    }
+}
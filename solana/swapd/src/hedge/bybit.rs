@@ -0,0 +1,148 @@
+//! Bybit spot over the v5 API (`/v5/order/create`), signed with HMAC-SHA256 over the timestamp, key, receive
+//! window and the JSON body or query string.
+
+use reqwest::{Client, RequestBuilder};
+use serde_json::{json, Value};
+
+use super::{
+    fee_micro_usd, field, hmac_sha256_hex, parse_sat, placed, price, unix_millis, Credentials, Fill, HedgeError, Order,
+    Side, Venue,
+};
+use crate::rates::parse_micro;
+
+pub const URL: &str = "https://api.bybit.com";
+const SYMBOL: &str = "BTCUSDT";
+const RECV_WINDOW: &str = "5000";
+
+#[derive(Debug, Clone)]
+pub struct Bybit {
+    http: Client,
+    url: String,
+    credentials: Credentials,
+}
+
+impl Bybit {
+    /// `url` is [`URL`], or the testnet's.
+    pub fn new(url: impl Into<String>, credentials: Credentials) -> Self {
+        Self {
+            http: Client::new(),
+            url: url.into(),
+            credentials,
+        }
+    }
+
+    /// `request` with the auth headers over `payload`, sent; returns its `result`.
+    async fn send(&self, request: RequestBuilder, payload: &str) -> Result<Value, HedgeError> {
+        let timestamp = unix_millis().to_string();
+        let key = &self.credentials.api_key;
+        let signature = hmac_sha256_hex(
+            &self.credentials.api_secret,
+            &format!("{timestamp}{key}{RECV_WINDOW}{payload}"),
+        );
+        let response: Value = request
+            .header("X-BAPI-API-KEY", key)
+            .header("X-BAPI-TIMESTAMP", timestamp)
+            .header("X-BAPI-RECV-WINDOW", RECV_WINDOW)
+            .header("X-BAPI-SIGN", signature)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| HedgeError::Http(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| HedgeError::Parse(e.to_string()))?;
+        match response["retCode"].as_i64() {
+            Some(0) => Ok(response["result"].clone()),
+            code => Err(HedgeError::Rejected(format!(
+                "{} (code {})",
+                response["retMsg"].as_str().unwrap_or_default(),
+                code.unwrap_or_default()
+            ))),
+        }
+    }
+
+    async fn get(&self, path: &str, query: &str) -> Result<Value, HedgeError> {
+        let request = self.http.get(format!("{}{path}?{query}", self.url));
+        self.send(request, query).await
+    }
+}
+
+/// What `order` executed. Spot commission is charged in what the order receives: BTC on a buy, USDT on a sell.
+fn fill(order: &Value) -> Result<Fill, HedgeError> {
+    let venue_order_id = order["orderId"]
+        .as_str()
+        .ok_or_else(|| HedgeError::Parse("order without orderId".into()))?;
+    let filled_sat = field(order, "cumExecQty", parse_sat)?;
+    let cost_micro_usd = field(order, "cumExecValue", parse_micro)?;
+    let asset = match order["side"].as_str() {
+        Some("Buy") => "BTC",
+        _ => "USDT",
+    };
+    let commission = order["cumExecFee"].as_str().unwrap_or("0");
+    let fee = fee_micro_usd(asset, commission, price(cost_micro_usd, filled_sat))?;
+    Ok(Fill {
+        venue_order_id: venue_order_id.to_string(),
+        open: matches!(
+            order["orderStatus"].as_str(),
+            Some("New" | "PartiallyFilled" | "Untriggered")
+        ),
+        filled_sat,
+        cost_micro_usd,
+        fee_micro_usd: fee.unwrap_or(0),
+    })
+}
+
+impl Venue for Bybit {
+    fn name(&self) -> &'static str {
+        "bybit"
+    }
+
+    async fn place(&self, order: &Order) -> Result<Fill, HedgeError> {
+        let side = match order.side {
+            Side::Buy => "Buy",
+            Side::Sell => "Sell",
+        };
+        // Market buys are sized in USDT unless the quantity is marked as BTC.
+        let body = json!({
+            "category": "spot",
+            "symbol": SYMBOL,
+            "side": side,
+            "orderType": "Market",
+            "qty": order.quantity(),
+            "marketUnit": "baseCoin",
+            "orderLinkId": order.client_order_id,
+        })
+        .to_string();
+        let request = self
+            .http
+            .post(format!("{}/v5/order/create", self.url))
+            .header("Content-Type", "application/json")
+            .body(body.clone());
+        let result = self.send(request, &body).await?;
+        let order_id = result["orderId"]
+            .as_str()
+            .ok_or_else(|| HedgeError::Parse("order without orderId".into()))?;
+        // The order is only acknowledged; what it executed is queried.
+        let read = async {
+            let query = format!("category=spot&orderId={order_id}");
+            let orders = self.get("/v5/order/realtime", &query).await?;
+            match &orders["list"][0] {
+                Value::Null => Err(HedgeError::Parse(format!("order {order_id} not listed yet"))),
+                order => fill(order),
+            }
+        };
+        read.await.map_err(placed)
+    }
+
+    async fn find(&self, client_order_id: &str) -> Result<Option<Fill>, HedgeError> {
+        let query = format!("category=spot&orderLinkId={client_order_id}");
+        // Open and just-filled orders are in realtime, older ones only in history.
+        for path in ["/v5/order/realtime", "/v5/order/history"] {
+            let result = self.get(path, &query).await?;
+            if let order @ Value::Object(_) = &result["list"][0] {
+                return fill(order).map(Some);
+            }
+        }
+        Ok(None)
+    }
+}
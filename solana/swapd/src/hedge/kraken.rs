@@ -0,0 +1,117 @@
+//! Kraken spot (`/0/private/AddOrder`), signed with HMAC-SHA512 over the path and the SHA-256 of the nonce
+//! and form body, under the base64-decoded secret.
+
+use base64::Engine as _;
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde_json::Value;
+use sha2::{Digest, Sha256, Sha512};
+
+use super::{field, parse_sat, placed, unix_millis, Credentials, Fill, HedgeError, Order, Venue};
+use crate::rates::parse_micro;
+
+pub const URL: &str = "https://api.kraken.com";
+const PAIR: &str = "XBTUSDT";
+
+#[derive(Debug, Clone)]
+pub struct Kraken {
+    http: Client,
+    url: String,
+    credentials: Credentials,
+}
+
+impl Kraken {
+    pub fn new(url: impl Into<String>, credentials: Credentials) -> Self {
+        Self {
+            http: Client::new(),
+            url: url.into(),
+            credentials,
+        }
+    }
+
+    /// Posts `params` to private endpoint `path` and returns its `result`.
+    async fn private(&self, path: &str, params: &[(&str, &str)]) -> Result<Value, HedgeError> {
+        let nonce = unix_millis().to_string();
+        let mut body = format!("nonce={nonce}");
+        for (k, v) in params {
+            body.push_str(&format!("&{k}={v}"));
+        }
+        let secret = base64::engine::general_purpose::STANDARD
+            .decode(&self.credentials.api_secret)
+            .map_err(|e| HedgeError::Rejected(format!("api secret is not base64: {e}")))?;
+        let mut mac = Hmac::<Sha512>::new_from_slice(&secret).expect("HMAC takes keys of any length");
+        mac.update(path.as_bytes());
+        mac.update(&Sha256::digest(format!("{nonce}{body}").as_bytes()));
+        let signature = base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes());
+        let response: Value = self
+            .http
+            .post(format!("{}{path}", self.url))
+            .header("API-Key", &self.credentials.api_key)
+            .header("API-Sign", signature)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| HedgeError::Http(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| HedgeError::Parse(e.to_string()))?;
+        // Errors come back with HTTP 200, listed in `error`.
+        match response["error"].as_array().filter(|errors| !errors.is_empty()) {
+            Some(errors) => Err(HedgeError::Rejected(
+                errors.iter().filter_map(Value::as_str).collect::<Vec<_>>().join("; "),
+            )),
+            None => Ok(response["result"].clone()),
+        }
+    }
+}
+
+/// What order `txid`, as Kraken describes it in `info`, executed; its fee is charged in USDT.
+fn fill(txid: &str, info: &Value) -> Result<Fill, HedgeError> {
+    Ok(Fill {
+        venue_order_id: txid.to_string(),
+        open: matches!(info["status"].as_str(), Some("pending" | "open")),
+        filled_sat: field(info, "vol_exec", parse_sat)?,
+        cost_micro_usd: field(info, "cost", parse_micro)?,
+        fee_micro_usd: field(info, "fee", parse_micro)?,
+    })
+}
+
+impl Venue for Kraken {
+    fn name(&self) -> &'static str {
+        "kraken"
+    }
+
+    async fn place(&self, order: &Order) -> Result<Fill, HedgeError> {
+        let quantity = order.quantity();
+        let params = [
+            ("ordertype", "market"),
+            ("type", order.side.as_str()),
+            ("volume", quantity.as_str()),
+            ("pair", PAIR),
+            ("cl_ord_id", order.client_order_id.as_str()),
+        ];
+        let result = self.private("/0/private/AddOrder", &params).await?;
+        let txid = result["txid"][0]
+            .as_str()
+            .ok_or_else(|| HedgeError::Parse("AddOrder without txid".into()))?;
+        // AddOrder only acknowledges the order; what it executed is queried.
+        let read = async {
+            let orders = self.private("/0/private/QueryOrders", &[("txid", txid)]).await?;
+            fill(txid, &orders[txid])
+        };
+        read.await.map_err(placed)
+    }
+
+    async fn find(&self, client_order_id: &str) -> Result<Option<Fill>, HedgeError> {
+        // A market order is closed within moments, but may still be listed as open.
+        for (path, key) in [("/0/private/ClosedOrders", "closed"), ("/0/private/OpenOrders", "open")] {
+            let result = self.private(path, &[("cl_ord_id", client_order_id)]).await?;
+            if let Some((txid, info)) = result[key].as_object().and_then(|orders| orders.iter().next()) {
+                return fill(txid, info).map(Some);
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,601 @@
+//! Hedging of filled swaps on a spot exchange, for operators who make markets rather than hold BTC. A completed
+//! ln-to-usdt swap leaves the operator long the BTC it received, a completed usdt-to-ln swap short the BTC it
+//! paid. The [`Hedger`] records each as a fill and, once the net unhedged position reaches the smallest order
+//! worth placing, sends an offsetting BTC/USDT market order to the configured [`Venue`] (Binance, Kraken or
+//! Bybit), keeping the inventory delta-neutral. Orders are written before they are sent under a client order id
+//! the venue echoes back, so an order that was in flight when the daemon stopped is looked up rather than sent
+//! twice. A venue that refuses an order raises a `hedge_failed` alert and the position is retried next interval.
+//! What an order executed, at what average price and for what commission, is read back from the venue until it
+//! closes; a closed order counts for what it filled, so a partial or cancelled fill leaves the rest to hedge.
+
+pub mod binance;
+pub mod bybit;
+pub mod kraken;
+
+use std::{fmt, future::Future, str::FromStr, sync::Arc, time::Duration};
+
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tracing::{error, info, warn};
+
+use crate::{
+    rates::{self, parse_micro},
+    store::{Store, StoreError},
+    swap::{unix_now, Direction, Swap, SwapState},
+};
+
+/// Store cursor: `updated_at` of the newest swap already recorded as a fill.
+const SCAN_CURSOR: &str = "hedge_scan_at";
+const SAT_PER_BTC: u64 = 100_000_000;
+
+#[derive(Debug)]
+pub enum HedgeError {
+    Http(String),
+    /// The venue answered and refused: bad credentials, balance too low, order too small.
+    Rejected(String),
+    Parse(String),
+    Store(StoreError),
+}
+
+impl fmt::Display for HedgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(e) => write!(f, "venue request failed: {e}"),
+            Self::Rejected(e) => write!(f, "venue refused the request: {e}"),
+            Self::Parse(e) => write!(f, "unexpected venue response: {e}"),
+            Self::Store(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for HedgeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Store(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<StoreError> for HedgeError {
+    fn from(e: StoreError) -> Self {
+        Self::Store(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Buy => "buy",
+            Self::Sell => "sell",
+        }
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Side {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "buy" => Ok(Self::Buy),
+            "sell" => Ok(Self::Sell),
+            other => Err(format!("unknown order side {other:?}")),
+        }
+    }
+}
+
+/// A BTC/USDT market order as sent to a venue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Order {
+    pub side: Side,
+    pub amount_sat: u64,
+    /// Chosen by the daemon and stored before sending, so the order can be found again after a restart.
+    pub client_order_id: String,
+}
+
+impl Order {
+    /// The amount in BTC with 8 decimals, as venues take it.
+    pub fn quantity(&self) -> String {
+        format!("{}.{:08}", self.amount_sat / SAT_PER_BTC, self.amount_sat % SAT_PER_BTC)
+    }
+}
+
+/// What a venue executed of an order so far, in the micro-dollars it quotes USDT in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fill {
+    pub venue_order_id: String,
+    /// Still on the book, so more of it may execute.
+    pub open: bool,
+    pub filled_sat: u64,
+    /// USDT paid for a buy, received for a sell.
+    pub cost_micro_usd: u64,
+    /// Commission; one charged in BTC is converted at the fill's price, one in another asset is not counted.
+    pub fee_micro_usd: u64,
+}
+
+impl Fill {
+    /// Micro-dollars per BTC, 0 with nothing filled.
+    pub fn avg_price_micro_usd(&self) -> u64 {
+        price(self.cost_micro_usd, self.filled_sat)
+    }
+
+    /// The fill in base units of the swapped token, for the store.
+    pub fn execution(&self, token_decimals: u8) -> Execution {
+        let tokens = |micro_usd| rates::micro_usd_to_tokens(u128::from(micro_usd), token_decimals);
+        Execution {
+            filled_sat: self.filled_sat,
+            cost_tokens: tokens(self.cost_micro_usd),
+            fee_tokens: tokens(self.fee_micro_usd),
+        }
+    }
+
+    fn status(&self) -> OrderStatus {
+        match self.open {
+            true => OrderStatus::Placed,
+            false => OrderStatus::Closed,
+        }
+    }
+}
+
+/// What an order executed, as the daemon keeps it. Token amounts are base units.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Execution {
+    pub filled_sat: u64,
+    pub cost_tokens: u64,
+    pub fee_tokens: u64,
+}
+
+impl Execution {
+    /// Token base units per BTC, 0 with nothing filled.
+    pub fn avg_price_token_per_btc(&self) -> u64 {
+        price(self.cost_tokens, self.filled_sat)
+    }
+}
+
+/// `cost` per BTC for `sat`.
+pub(crate) fn price(cost: u64, sat: u64) -> u64 {
+    match sat {
+        0 => 0,
+        sat => u64::try_from(u128::from(cost) * u128::from(SAT_PER_BTC) / u128::from(sat)).unwrap_or(u64::MAX),
+    }
+}
+
+/// API key and secret of a venue account allowed to trade spot.
+#[derive(Clone)]
+pub struct Credentials {
+    pub api_key: String,
+    pub api_secret: String,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("api_key", &self.api_key)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An exchange the operator hedges on.
+pub trait Venue: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Places `order` at market and returns what it executed. An error once the venue took the order is
+    /// [`HedgeError::Http`], so that it is looked up again rather than failed.
+    fn place(&self, order: &Order) -> impl Future<Output = Result<Fill, HedgeError>> + Send;
+
+    /// What the order placed under `client_order_id` executed, or `None` if it never reached the venue.
+    fn find(&self, client_order_id: &str) -> impl Future<Output = Result<Option<Fill>, HedgeError>> + Send;
+}
+
+/// The venues swapd ships with.
+#[derive(Debug, Clone)]
+pub enum Exchange {
+    Binance(binance::Binance),
+    Kraken(kraken::Kraken),
+    Bybit(bybit::Bybit),
+}
+
+impl Venue for Exchange {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Binance(v) => v.name(),
+            Self::Kraken(v) => v.name(),
+            Self::Bybit(v) => v.name(),
+        }
+    }
+
+    async fn place(&self, order: &Order) -> Result<Fill, HedgeError> {
+        match self {
+            Self::Binance(v) => v.place(order).await,
+            Self::Kraken(v) => v.place(order).await,
+            Self::Bybit(v) => v.place(order).await,
+        }
+    }
+
+    async fn find(&self, client_order_id: &str) -> Result<Option<Fill>, HedgeError> {
+        match self {
+            Self::Binance(v) => v.find(client_order_id).await,
+            Self::Kraken(v) => v.find(client_order_id).await,
+            Self::Bybit(v) => v.find(client_order_id).await,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    /// Written, and sent or about to be; counts as hedged in full until resolved.
+    Pending,
+    /// On the venue's book and may execute further; counts as hedged in full until closed.
+    Placed,
+    /// Done on the venue; counts for what it filled, which may be nothing.
+    Closed,
+    /// Refused by the venue or never sent; does not count.
+    Failed,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Placed => "placed",
+            Self::Closed => "closed",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OrderStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(Self::Pending),
+            "placed" => Ok(Self::Placed),
+            "closed" => Ok(Self::Closed),
+            "failed" => Ok(Self::Failed),
+            other => Err(format!("unknown hedge order status {other:?}")),
+        }
+    }
+}
+
+/// A hedge order as the daemon keeps it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HedgeOrder {
+    pub id: i64,
+    pub venue: String,
+    pub side: Side,
+    pub amount_sat: u64,
+    pub client_order_id: String,
+    pub venue_order_id: Option<String>,
+    pub status: OrderStatus,
+    /// As last read from the venue.
+    pub execution: Execution,
+    pub error: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl HedgeOrder {
+    /// BTC the order takes off a long position, negative for a buy: what it filled once closed, all of it before.
+    pub fn hedged_msat(&self) -> i64 {
+        let sat = match self.status {
+            OrderStatus::Pending | OrderStatus::Placed => self.amount_sat,
+            OrderStatus::Closed => self.execution.filled_sat,
+            OrderStatus::Failed => 0,
+        };
+        let msat = i64::try_from(sat.saturating_mul(1000)).unwrap_or(i64::MAX);
+        match self.side {
+            Side::Sell => msat,
+            Side::Buy => -msat,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "venue": self.venue,
+            "side": self.side.as_str(),
+            "amountSat": self.amount_sat,
+            "clientOrderId": self.client_order_id,
+            "venueOrderId": self.venue_order_id,
+            "status": self.status.as_str(),
+            "filledSat": self.execution.filled_sat,
+            "costTokens": self.execution.cost_tokens,
+            "avgPriceTokenPerBtc": self.execution.avg_price_token_per_btc(),
+            "feeTokens": self.execution.fee_tokens,
+            "error": self.error,
+            "createdAt": self.created_at,
+            "updatedAt": self.updated_at,
+        })
+    }
+}
+
+/// BTC the operator holds because of filled swaps, against what its orders took off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Position {
+    /// Net of every fill: long after ln-to-usdt swaps, short after usdt-to-ln ones.
+    pub filled_msat: i64,
+    /// Net of every order not failed, sells less buys: what closed ones filled, all of the others.
+    pub hedged_msat: i64,
+}
+
+impl Position {
+    /// What is still to sell, or to buy when negative.
+    pub fn unhedged_msat(&self) -> i64 {
+        self.filled_msat.saturating_sub(self.hedged_msat)
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "filledMsat": self.filled_msat,
+            "hedgedMsat": self.hedged_msat,
+            "unhedgedMsat": self.unhedged_msat(),
+        })
+    }
+}
+
+/// BTC `swap` leaves the operator long (short when negative) once completed; `None` before.
+pub fn fill_msat(swap: &Swap) -> Option<i64> {
+    if swap.state != SwapState::Completed {
+        return None;
+    }
+    let msat = i64::try_from(swap.amount_msat).unwrap_or(i64::MAX);
+    Some(match swap.direction {
+        Direction::LnToUsdt => msat,
+        Direction::UsdtToLn => -msat,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HedgeConfig {
+    pub interval: Duration,
+    /// No order while the unhedged position is smaller than this.
+    pub min_order_sat: u64,
+    /// Larger positions are hedged over several intervals.
+    pub max_order_sat: u64,
+    /// Orders are a multiple of this, the venue's step size.
+    pub lot_sat: u64,
+    /// Decimals of the swapped token, which fills are stored in.
+    pub token_decimals: u8,
+}
+
+impl HedgeConfig {
+    /// The order that takes `position` back toward flat, if one is worth placing.
+    fn plan(&self, position: &Position) -> Option<(Side, u64)> {
+        let unhedged = position.unhedged_msat();
+        let side = if unhedged > 0 { Side::Sell } else { Side::Buy };
+        let sat = (unhedged.unsigned_abs() / 1000).min(self.max_order_sat);
+        let sat = sat - sat % self.lot_sat.max(1);
+        (sat > 0 && sat >= self.min_order_sat).then_some((side, sat))
+    }
+}
+
+pub struct Hedger {
+    store: Arc<Store>,
+    venue: Exchange,
+    cfg: HedgeConfig,
+}
+
+impl Hedger {
+    pub fn new(store: Arc<Store>, venue: Exchange, cfg: HedgeConfig) -> Self {
+        Self { store, venue, cfg }
+    }
+
+    /// Records fills and hedges them every `interval` until `shutdown` resolves.
+    pub async fn run(&self, shutdown: impl Future<Output = ()>) {
+        info!(venue = self.venue.name(), "hedging filled swaps");
+        tokio::pin!(shutdown);
+        loop {
+            if let Err(e) = self.tick().await {
+                warn!(venue = self.venue.name(), error = %e, "hedge check failed; retrying next interval");
+            }
+            tokio::select! {
+                _ = &mut shutdown => return,
+                _ = tokio::time::sleep(self.cfg.interval) => {}
+            }
+        }
+    }
+
+    async fn tick(&self) -> Result<(), HedgeError> {
+        self.scan()?;
+        self.settle().await?;
+        let position = self.store.hedge_position()?;
+        let Some((side, amount_sat)) = self.cfg.plan(&position) else {
+            return Ok(());
+        };
+        self.place(side, amount_sat, &position).await
+    }
+
+    /// Records swaps completed since the last scan as fills.
+    fn scan(&self) -> Result<(), StoreError> {
+        let since = self.store.cursor(SCAN_CURSOR)?;
+        if since == 0 {
+            // First run: only swaps completed from now on are hedged, not the whole history.
+            return self.store.set_cursor(SCAN_CURSOR, unix_now() as u64);
+        }
+        let swaps = self.store.swaps_updated_since(since as i64)?;
+        let Some(newest) = swaps.iter().map(|s| s.updated_at).max() else {
+            return Ok(());
+        };
+        for swap in &swaps {
+            if let Some(fill_msat) = fill_msat(swap) {
+                self.store.record_hedge_fill(&swap.id, fill_msat, unix_now())?;
+            }
+        }
+        // Swaps written in the same second as the newest are scanned again next time; fills are recorded once.
+        self.store.set_cursor(SCAN_CURSOR, newest.max(0) as u64)
+    }
+
+    /// Reads back the orders the venue is not done with: those left pending by a restart are failed if the venue
+    /// never got them, and each one found takes its latest fill, closing once the venue has closed it.
+    async fn settle(&self) -> Result<(), HedgeError> {
+        let mut orders = self.store.hedge_orders(Some(OrderStatus::Pending))?;
+        orders.extend(self.store.hedge_orders(Some(OrderStatus::Placed))?);
+        for order in orders {
+            let Some(fill) = self.venue.find(&order.client_order_id).await? else {
+                if order.status == OrderStatus::Placed {
+                    warn!(
+                        order = order.id,
+                        client_order_id = order.client_order_id,
+                        "placed hedge order not found"
+                    );
+                    continue;
+                }
+                info!(
+                    order = order.id,
+                    client_order_id = order.client_order_id,
+                    "pending hedge order never placed"
+                );
+                self.store.update_hedge_order(
+                    order.id,
+                    OrderStatus::Failed,
+                    None,
+                    None,
+                    Some("never reached the venue"),
+                    unix_now(),
+                )?;
+                continue;
+            };
+            let status = fill.status();
+            if status != order.status {
+                info!(
+                    order = order.id,
+                    client_order_id = order.client_order_id,
+                    %status,
+                    filled_sat = fill.filled_sat,
+                    amount_sat = order.amount_sat,
+                    "hedge order updated"
+                );
+            }
+            self.record(order.id, &fill)?;
+        }
+        Ok(())
+    }
+
+    fn record(&self, id: i64, fill: &Fill) -> Result<(), StoreError> {
+        self.store.update_hedge_order(
+            id,
+            fill.status(),
+            Some(&fill.venue_order_id),
+            Some(&fill.execution(self.cfg.token_decimals)),
+            None,
+            unix_now(),
+        )
+    }
+
+    async fn place(&self, side: Side, amount_sat: u64, position: &Position) -> Result<(), HedgeError> {
+        let mut nonce = [0u8; 8];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        // At most 18 characters, Kraken's limit for free-text ids.
+        let order = Order {
+            side,
+            amount_sat,
+            client_order_id: format!("sh{}", hex::encode(nonce)),
+        };
+        let id = self.store.insert_hedge_order(self.venue.name(), &order, unix_now())?;
+        match self.venue.place(&order).await {
+            Ok(fill) => {
+                info!(
+                    venue = self.venue.name(),
+                    %side,
+                    amount_sat,
+                    venue_order_id = fill.venue_order_id,
+                    filled_sat = fill.filled_sat,
+                    avg_price_micro_usd = fill.avg_price_micro_usd(),
+                    unhedged_msat = position.unhedged_msat(),
+                    "hedge order placed"
+                );
+                self.record(id, &fill)?;
+            }
+            // Unknown outcome: left pending for the next interval to look up.
+            Err(HedgeError::Http(e)) => {
+                warn!(venue = self.venue.name(), order = id, error = %e, "hedge order outcome unknown");
+            }
+            Err(e) => {
+                error!(
+                    alert = "hedge_failed",
+                    venue = self.venue.name(),
+                    %side,
+                    amount_sat,
+                    error = %e,
+                    "hedge order refused; the position stays open"
+                );
+                let reason = e.to_string();
+                self.store
+                    .update_hedge_order(id, OrderStatus::Failed, None, None, Some(&reason), unix_now())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Hex HMAC-SHA256 of `message` under `secret`, as Binance and Bybit sign requests.
+pub(crate) fn hmac_sha256_hex(secret: &str, message: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(message.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Milliseconds since the epoch, for request timestamps and nonces.
+pub(crate) fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// A commission of `amount` `asset` in micro-dollars, BTC converted at `price_micro_usd`; `None` for other assets,
+/// such as a venue's own token.
+pub(crate) fn fee_micro_usd(asset: &str, amount: &str, price_micro_usd: u64) -> Result<Option<u64>, HedgeError> {
+    let parse = |units: fn(&str) -> Option<u64>| {
+        units(amount).ok_or_else(|| HedgeError::Parse(format!("commission {amount:?} is not a number")))
+    };
+    Ok(match asset {
+        "USDT" => Some(parse(parse_micro)?),
+        "BTC" | "XBT" => {
+            let sat = parse(parse_sat)?;
+            Some((u128::from(sat) * u128::from(price_micro_usd) / u128::from(SAT_PER_BTC)) as u64)
+        }
+        _ => None,
+    })
+}
+
+/// A BTC amount (e.g. `"0.00120000"`) in satoshis, truncating digits past the eighth decimal.
+pub(crate) fn parse_sat(s: &str) -> Option<u64> {
+    let (int, frac) = s.trim().split_once('.').unwrap_or((s.trim(), ""));
+    let int: u64 = int.parse().ok()?;
+    let frac = format!("{:0<8}", &frac[..frac.len().min(8)]);
+    int.checked_mul(SAT_PER_BTC)?.checked_add(frac.parse().ok()?)
+}
+
+/// An error reading back an order the venue took: the order is left pending, to be looked up next interval.
+pub(crate) fn placed(e: HedgeError) -> HedgeError {
+    HedgeError::Http(format!("order placed, but reading it back failed: {e}"))
+}
+
+/// Parses `field` of a venue order with `parse`.
+pub(crate) fn field(order: &Value, field: &str, parse: fn(&str) -> Option<u64>) -> Result<u64, HedgeError> {
+    order[field]
+        .as_str()
+        .and_then(parse)
+        .ok_or_else(|| HedgeError::Parse(format!("order without a numeric {field}")))
+}
@@ -0,0 +1,192 @@
+//! Jito block-engine bundles for claims that have to land before refund_after. The claim is bundled with a
+//! tip transfer to one of the block engine's tip accounts, so a Jito leader includes both or neither. The same
+//! signed claim still goes out over RPC: if the bundle is dropped or the block engine is down the claim lands
+//! the normal way, and the tip, which only travels in the bundle, is not paid. Each [`Urgency`] has its own
+//! tip, or none to use RPC alone.
+
+use std::{fmt, time::Duration};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use rand::seq::SliceRandom;
+use serde_json::{json, Value};
+use solana_sdk::{pubkey::Pubkey, signer::Signer, system_instruction, transaction::Transaction};
+use tokio::sync::OnceCell;
+
+/// Path of the bundle JSON-RPC API under the block engine URL.
+const BUNDLES_PATH: &str = "/api/v1/bundles";
+
+/// How close a claim is to its escrow's refund_after.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Urgency {
+    Normal,
+    /// Within twice the claim alert margin.
+    Elevated,
+    /// Within the claim alert margin: the payer can refund soon.
+    Critical,
+}
+
+impl Urgency {
+    pub fn of(remaining_secs: i64, alert_margin_secs: i64) -> Self {
+        if remaining_secs < alert_margin_secs {
+            Self::Critical
+        } else if remaining_secs < alert_margin_secs.saturating_mul(2) {
+            Self::Elevated
+        } else {
+            Self::Normal
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Elevated => "elevated",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+impl fmt::Display for Urgency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JitoConfig {
+    /// Block engine, e.g. `https://mainnet.block-engine.jito.wtf`.
+    pub url: String,
+    /// Tip in lamports per urgency; `None` sends over RPC only.
+    pub normal_tip: Option<u64>,
+    pub elevated_tip: Option<u64>,
+    pub critical_tip: Option<u64>,
+    pub timeout: Duration,
+}
+
+impl JitoConfig {
+    pub fn tip(&self, urgency: Urgency) -> Option<u64> {
+        match urgency {
+            Urgency::Normal => self.normal_tip,
+            Urgency::Elevated => self.elevated_tip,
+            Urgency::Critical => self.critical_tip,
+        }
+        .filter(|&tip| tip > 0)
+    }
+}
+
+#[derive(Debug)]
+pub enum JitoError {
+    Http(reqwest::Error),
+    Rpc { code: i64, message: String },
+    NoTipAccounts,
+    Encode(String),
+}
+
+impl fmt::Display for JitoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(e) => write!(f, "block engine: {e}"),
+            Self::Rpc { code, message } => write!(f, "block engine error {code}: {message}"),
+            Self::NoTipAccounts => f.write_str("block engine returned no tip accounts"),
+            Self::Encode(e) => write!(f, "cannot encode bundle: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for JitoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Http(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for JitoError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Http(e)
+    }
+}
+
+pub struct Jito {
+    cfg: JitoConfig,
+    http: reqwest::Client,
+    /// Fetched on the first bundle.
+    tip_accounts: OnceCell<Vec<Pubkey>>,
+}
+
+impl Jito {
+    pub fn new(cfg: JitoConfig) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(cfg.timeout)
+            .build()
+            .unwrap_or_default();
+        Self {
+            cfg,
+            http,
+            tip_accounts: OnceCell::new(),
+        }
+    }
+
+    pub fn tip(&self, urgency: Urgency) -> Option<u64> {
+        self.cfg.tip(urgency)
+    }
+
+    /// Submits `tx`, already signed, in a bundle with a `tip` lamport transfer from `payer`, and returns the
+    /// bundle id. Acceptance says nothing about landing; the caller confirms `tx` as usual.
+    pub async fn send_bundle(&self, tx: &Transaction, payer: &impl Signer, tip: u64) -> Result<String, JitoError> {
+        let tip_account = self.tip_account().await?;
+        let transfer = system_instruction::transfer(&payer.pubkey(), &tip_account, tip);
+        let tip_tx = Transaction::new_signed_with_payer(
+            &[transfer],
+            Some(&payer.pubkey()),
+            &[payer],
+            tx.message.recent_blockhash,
+        );
+        let encode = |tx: &Transaction| {
+            bincode::serialize(tx)
+                .map(|bytes| STANDARD.encode(bytes))
+                .map_err(|e| JitoError::Encode(e.to_string()))
+        };
+        let bundle = [encode(tx)?, encode(&tip_tx)?];
+        let id = self
+            .call("sendBundle", json!([bundle, { "encoding": "base64" }]))
+            .await?;
+        Ok(id.as_str().unwrap_or_default().to_string())
+    }
+
+    async fn tip_account(&self) -> Result<Pubkey, JitoError> {
+        let accounts = self
+            .tip_accounts
+            .get_or_try_init(|| async {
+                let accounts = self.call("getTipAccounts", json!([])).await?;
+                let accounts: Vec<Pubkey> = accounts
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|a| a.as_str()?.parse().ok())
+                    .collect();
+                if accounts.is_empty() {
+                    return Err(JitoError::NoTipAccounts);
+                }
+                Ok(accounts)
+            })
+            .await?;
+        // Spreading tips over the accounts avoids write-lock contention between bundles.
+        Ok(*accounts
+            .choose(&mut rand::thread_rng())
+            .expect("tip accounts are not empty"))
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value, JitoError> {
+        let url = format!("{}{BUNDLES_PATH}", self.cfg.url.trim_end_matches('/'));
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let response: Value = self.http.post(url).json(&body).send().await?.json().await?;
+        if let Some(error) = response.get("error") {
+            return Err(JitoError::Rpc {
+                code: error["code"].as_i64().unwrap_or_default(),
+                message: error["message"].as_str().unwrap_or("unknown error").to_string(),
+            });
+        }
+        Ok(response["result"].clone())
+    }
+}
@@ -0,0 +1,242 @@
+//! Append-only journal of swap writes. Every write of a swap, from intake through each transition to a cancel
+//! request, appends a [`SwapEvent`] in the same transaction as the write: the swap as written, the state it came
+//! from, who wrote it and why, and the event it follows. The swaps table is the journal's projection; at startup
+//! [`Store::rebuild_from_journal`](crate::store::Store::rebuild_from_journal) restores any swap whose row no
+//! longer matches its last event, so questions like "why did this swap refund?" are answered from the database
+//! alone (`swapd journal ID`).
+
+use std::{fmt, str::FromStr};
+
+use serde_json::{json, Value};
+
+use crate::{
+    store::{stored_preimage, RawSwap, StoreError},
+    swap::{Swap, SwapState},
+};
+
+/// What a journaled write did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// The swap was accepted.
+    Created,
+    /// The swap moved to another state.
+    Transitioned,
+    /// The swap was written without changing state: a signature recorded, a retryable problem noted.
+    Updated,
+    CancelRequested,
+    /// The swap predates the journal; its first event is the row as found.
+    Imported,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Transitioned => "transitioned",
+            Self::Updated => "updated",
+            Self::CancelRequested => "cancel_requested",
+            Self::Imported => "imported",
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for EventKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "created" => Ok(Self::Created),
+            "transitioned" => Ok(Self::Transitioned),
+            "updated" => Ok(Self::Updated),
+            "cancel_requested" => Ok(Self::CancelRequested),
+            "imported" => Ok(Self::Imported),
+            other => Err(format!("unknown journal event kind {other:?}")),
+        }
+    }
+}
+
+/// Who wrote a swap, and why when there is more to say than the new state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cause {
+    /// `engine`, `keysend`, or where the swap came in or was cancelled from: the transport (`grpc`, `rest`,
+    /// `nostr`, `libp2p`), `lnurl` or `cli`.
+    pub actor: String,
+    pub reason: Option<String>,
+}
+
+impl Cause {
+    pub fn new(actor: impl Into<String>) -> Self {
+        Self {
+            actor: actor.into(),
+            reason: None,
+        }
+    }
+
+    pub fn engine() -> Self {
+        Self::new("engine")
+    }
+
+    pub fn because(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+}
+
+/// One journaled write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapEvent {
+    /// Position in the journal, across all swaps.
+    pub seq: i64,
+    pub swap_id: String,
+    pub kind: EventKind,
+    /// State before the write; `None` for the first event of a swap.
+    pub from: Option<SwapState>,
+    pub to: SwapState,
+    pub actor: String,
+    pub reason: Option<String>,
+    /// The swap's previous event.
+    pub follows: Option<i64>,
+    /// The swap as stored after the write, preimage sealed.
+    pub swap: Swap,
+    pub recorded_at: i64,
+}
+
+impl SwapEvent {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "seq": self.seq,
+            "swapId": self.swap_id,
+            "kind": self.kind.as_str(),
+            "from": self.from.map(|s| s.as_str()),
+            "to": self.to.as_str(),
+            "actor": self.actor,
+            "reason": self.reason,
+            "follows": self.follows,
+            "swap": self.swap.to_json(),
+            "recordedAt": self.recorded_at,
+        })
+    }
+}
+
+/// A journal row as read from either backend.
+pub(crate) struct RawEvent {
+    pub(crate) seq: i64,
+    pub(crate) swap_id: String,
+    pub(crate) kind: String,
+    pub(crate) from_state: Option<String>,
+    pub(crate) to_state: String,
+    pub(crate) actor: String,
+    pub(crate) reason: Option<String>,
+    pub(crate) follows: Option<i64>,
+    pub(crate) snapshot: String,
+    pub(crate) recorded_at: i64,
+}
+
+impl RawEvent {
+    pub(crate) fn decode(self) -> Result<SwapEvent, StoreError> {
+        let corrupt = |reason: String| StoreError::Corrupt {
+            id: format!("{} event {}", self.swap_id, self.seq),
+            reason,
+        };
+        Ok(SwapEvent {
+            kind: self.kind.parse().map_err(corrupt)?,
+            from: self
+                .from_state
+                .as_deref()
+                .map(str::parse)
+                .transpose()
+                .map_err(corrupt)?,
+            to: self.to_state.parse().map_err(corrupt)?,
+            swap: restore(&self.snapshot).map_err(|e| corrupt(format!("snapshot: {e}")))?,
+            seq: self.seq,
+            swap_id: self.swap_id,
+            actor: self.actor,
+            reason: self.reason,
+            follows: self.follows,
+            recorded_at: self.recorded_at,
+        })
+    }
+}
+
+/// The kind of an update that leaves the swap in `to`: a transition unless it is still in `from`, the state of
+/// its previous event.
+pub(crate) fn update_kind(from: Option<&str>, to: SwapState) -> EventKind {
+    match from {
+        Some(from) if from != to.as_str() => EventKind::Transitioned,
+        _ => EventKind::Updated,
+    }
+}
+
+/// `swap` as journaled: every stored column, the sealed preimage in hex.
+pub(crate) fn snapshot(swap: &Swap) -> String {
+    json!({
+        "id": swap.id,
+        "direction": swap.direction.as_str(),
+        "state": swap.state.as_str(),
+        "paymentHash": hex::encode(swap.payment_hash),
+        "preimage": stored_preimage(swap).map(hex::encode),
+        "bolt11": swap.bolt11,
+        "amountMsat": swap.amount_msat,
+        "hold": swap.hold,
+        "description": swap.description,
+        "tokenAmount": swap.token_amount,
+        "counterparty": swap.counterparty.to_string(),
+        "refundAfter": swap.refund_after,
+        "deadline": swap.deadline,
+        "signature": swap.signature,
+        "error": swap.error,
+        "cancelRequested": swap.cancel_requested,
+        "createdAt": swap.created_at,
+        "updatedAt": swap.updated_at,
+    })
+    .to_string()
+}
+
+/// The swap a [`snapshot`] was taken of.
+fn restore(snapshot: &str) -> Result<Swap, String> {
+    let v: Value = serde_json::from_str(snapshot).map_err(|e| e.to_string())?;
+    let text = |field: &str| -> Result<String, String> {
+        v[field]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| format!("{field} missing"))
+    };
+    let int = |field: &str| v[field].as_i64().ok_or_else(|| format!("{field} missing"));
+    let bytes = |hex: String| hex::decode(hex).map_err(|e| e.to_string());
+    let raw = RawSwap {
+        id: text("id")?,
+        direction: text("direction")?,
+        state: text("state")?,
+        payment_hash: bytes(text("paymentHash")?)?,
+        preimage: v["preimage"].as_str().map(|p| bytes(p.to_string())).transpose()?,
+        bolt11: v["bolt11"].as_str().map(str::to_string),
+        amount_msat: int("amountMsat")?,
+        token_amount: int("tokenAmount")?,
+        counterparty: text("counterparty")?,
+        refund_after: v["refundAfter"].as_i64(),
+        deadline: int("deadline")?,
+        signature: v["signature"].as_str().map(str::to_string),
+        error: v["error"].as_str().map(str::to_string),
+        created_at: int("createdAt")?,
+        updated_at: int("updatedAt")?,
+        hold: v["hold"].as_bool().unwrap_or_default(),
+        description: v["description"].as_str().map(str::to_string),
+        cancel_requested: v["cancelRequested"].as_bool().unwrap_or_default(),
+    };
+    raw.decode().map_err(|e| e.to_string())
+}
+
+/// What [`Store::rebuild_from_journal`](crate::store::Store::rebuild_from_journal) did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rebuilt {
+    /// Swaps from before the journal, now journaled as found.
+    pub imported: usize,
+    /// Swaps whose row was restored from their last event.
+    pub restored: usize,
+}
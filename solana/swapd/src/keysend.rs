@@ -0,0 +1,57 @@
+//! Keysend swaps: the user pays a quote with a spontaneous payment instead of an invoice, naming the quote in
+//! the [`SWAP_ID_RECORD`](crate::ln::SWAP_ID_RECORD) TLV record. The engine scans settled keysends, matches
+//! each to its quote and funds the escrow for the keysend's payment hash; the user already knows the preimage
+//! (they chose it) and claims as usual.
+//!
+//! Unlike an invoice swap, the BTC arrives before any USDT is committed. A keysend that matches no open quote
+//! (unknown id, underpaid, expired or already paid) is not swapped and raises an alert for manual handling.
+
+use rand::RngCore;
+use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::swap::unix_now;
+
+/// An offer to pay `token_amount` to `recipient` for a keysend of at least `amount_msat`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeysendQuote {
+    /// The value the payer puts in the swap-id record.
+    pub id: [u8; 16],
+    pub recipient: Pubkey,
+    pub amount_msat: u64,
+    pub token_amount: u64,
+    pub expires_at: i64,
+    /// Set once a keysend paid this quote.
+    pub payment_hash: Option<[u8; 32]>,
+    pub created_at: i64,
+}
+
+impl KeysendQuote {
+    pub fn new(recipient: Pubkey, amount_msat: u64, token_amount: u64, expires_in_secs: i64) -> Self {
+        let mut id = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut id);
+        let now = unix_now();
+        Self {
+            id,
+            recipient,
+            amount_msat,
+            token_amount,
+            expires_at: now.saturating_add(expires_in_secs),
+            payment_hash: None,
+            created_at: now,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": hex::encode(self.id),
+            "recordType": crate::ln::SWAP_ID_RECORD,
+            "recipient": self.recipient.to_string(),
+            "amountMsat": self.amount_msat,
+            "tokenAmount": self.token_amount,
+            "expiresAt": self.expires_at,
+            "paymentHash": self.payment_hash.map(hex::encode),
+            "createdAt": self.created_at,
+        })
+    }
+}
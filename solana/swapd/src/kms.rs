@@ -0,0 +1,593 @@
+//! Operator key held by a KMS or remote signing service instead of in the daemon's memory, so a compromised
+//! host can ask for signatures while it is compromised but cannot walk away with the key.
+//!
+//! A [`SignerSource`] is written as one string:
+//!
+//! - `aws-kms:KEY_ID`: an `ECC_NIST_EDWARDS25519` key in AWS KMS (`aws-kms` feature), credentials from the
+//!   usual AWS environment
+//! - `gcp-kms:projects/P/locations/L/keyRings/R/cryptoKeys/K/cryptoKeyVersions/V`: an `EC_SIGN_ED25519` key in
+//!   Cloud KMS, with an access token from `GOOGLE_OAUTH_ACCESS_TOKEN` or the instance metadata server
+//! - `https://...`: a signing service answering `GET /pubkey` with `{"pubkey": base58}` and
+//!   `POST /sign {"messages": [base64]}` with `{"signatures": [base58]}`
+//!
+//! [`KmsSigner`] implements `Signer`, so it goes wherever the keypair did. Signing requests that arrive while
+//! one is being gathered go out together: in one call to a signing service, concurrently to a KMS. The wait
+//! for company is a fraction of the backend's recent round trip, so a fast backend is barely delayed and a slow
+//! one gets fewer, fuller calls. Every signature is checked against the key before it is used.
+//!
+//! [`KeyWrap`] puts the same two KMS sources to another use: encrypting a data key (the preimage key, see
+//! [`crate::vault`]) so that only the KMS can open it. There `aws-kms:` names a symmetric key and `gcp-kms:` a
+//! `cryptoKeys/K` key without version, as Cloud KMS picks the version itself.
+
+use std::{
+    fmt,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use futures_util::future::try_join_all;
+use serde_json::{json, Value};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::{Signer, SignerError},
+};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+use zeroize::Zeroizing;
+
+use crate::metrics::metrics;
+#[cfg(feature = "frost")]
+use crate::threshold::ThresholdSigner;
+
+/// Longest a signing request waits for others to join its batch.
+const MAX_BATCH_WAIT: Duration = Duration::from_millis(10);
+/// Share of the backend's average round trip a request waits for company.
+const BATCH_WAIT_SHARE: f64 = 0.125;
+const MAX_BATCH: usize = 32;
+/// Weight of the latest round trip in the average.
+const LATENCY_WEIGHT: f64 = 0.2;
+/// DER prefix of an Ed25519 SubjectPublicKeyInfo; the key follows.
+const ED25519_SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+const GCP_KMS_URL: &str = "https://cloudkms.googleapis.com/v1";
+const GCP_METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// Where the operator key lives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignerSource {
+    AwsKms { key_id: String },
+    GcpKms { key_version: String },
+    Remote { url: String },
+}
+
+impl FromStr for SignerSource {
+    type Err = KmsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(key_id) = s.strip_prefix("aws-kms:") {
+            return Ok(Self::AwsKms {
+                key_id: key_id.to_string(),
+            });
+        }
+        if let Some(key_version) = s.strip_prefix("gcp-kms:") {
+            return Ok(Self::GcpKms {
+                key_version: key_version.to_string(),
+            });
+        }
+        if s.starts_with("https://") || s.starts_with("http://") {
+            return Ok(Self::Remote {
+                url: s.trim_end_matches('/').to_string(),
+            });
+        }
+        Err(KmsError::InvalidSource(s.to_string()))
+    }
+}
+
+impl fmt::Display for SignerSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AwsKms { key_id } => write!(f, "aws-kms:{key_id}"),
+            Self::GcpKms { key_version } => write!(f, "gcp-kms:{key_version}"),
+            Self::Remote { url } => f.write_str(url),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum KmsError {
+    InvalidSource(String),
+    /// Built without the feature the source needs.
+    Unsupported(&'static str),
+    Http(reqwest::Error),
+    Backend(String),
+    /// The backend returned something that is not an Ed25519 key or signature.
+    Malformed(String),
+    /// A signature that does not verify against the key.
+    BadSignature,
+}
+
+impl fmt::Display for KmsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSource(s) => write!(f, "unrecognized signer: {s}"),
+            Self::Unsupported(feature) => write!(f, "this signer needs swapd built with the `{feature}` feature"),
+            Self::Http(e) => write!(f, "signer: {e}"),
+            Self::Backend(e) => write!(f, "signer: {e}"),
+            Self::Malformed(e) => write!(f, "signer returned {e}"),
+            Self::BadSignature => f.write_str("signer returned a signature that does not verify"),
+        }
+    }
+}
+
+impl std::error::Error for KmsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Http(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for KmsError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Http(e)
+    }
+}
+
+/// The key that funds, claims and refunds escrows, and signs quotes.
+pub enum Operator {
+    Local(Keypair),
+    Kms(KmsSigner),
+    #[cfg(feature = "frost")]
+    Threshold(ThresholdSigner),
+}
+
+impl Signer for Operator {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        match self {
+            Self::Local(keypair) => keypair.try_pubkey(),
+            Self::Kms(signer) => signer.try_pubkey(),
+            #[cfg(feature = "frost")]
+            Self::Threshold(signer) => signer.try_pubkey(),
+        }
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        match self {
+            Self::Local(keypair) => keypair.try_sign_message(message),
+            Self::Kms(signer) => signer.try_sign_message(message),
+            #[cfg(feature = "frost")]
+            Self::Threshold(signer) => signer.try_sign_message(message),
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+enum Backend {
+    #[cfg(feature = "aws-kms")]
+    Aws {
+        client: aws_sdk_kms::Client,
+        key_id: String,
+    },
+    Gcp {
+        http: reqwest::Client,
+        key_version: String,
+        /// Access token and when it expires.
+        token: tokio::sync::Mutex<Option<(String, Instant)>>,
+    },
+    Remote {
+        http: reqwest::Client,
+        url: String,
+        token: Option<String>,
+    },
+}
+
+impl Backend {
+    async fn connect(source: SignerSource, token: Option<String>, timeout: Duration) -> Result<Self, KmsError> {
+        let http = reqwest::Client::builder().timeout(timeout).build().unwrap_or_default();
+        match source {
+            #[cfg(feature = "aws-kms")]
+            SignerSource::AwsKms { key_id } => {
+                let config = aws_config::defaults(aws_config::BehaviorVersion::latest())
+                    .timeout_config(
+                        aws_config::timeout::TimeoutConfig::builder()
+                            .operation_timeout(timeout)
+                            .build(),
+                    )
+                    .load()
+                    .await;
+                Ok(Self::Aws {
+                    client: aws_sdk_kms::Client::new(&config),
+                    key_id,
+                })
+            }
+            #[cfg(not(feature = "aws-kms"))]
+            SignerSource::AwsKms { .. } => Err(KmsError::Unsupported("aws-kms")),
+            SignerSource::GcpKms { key_version } => Ok(Self::Gcp {
+                http,
+                key_version,
+                token: tokio::sync::Mutex::new(None),
+            }),
+            SignerSource::Remote { url } => Ok(Self::Remote { http, url, token }),
+        }
+    }
+
+    async fn pubkey(&self) -> Result<Pubkey, KmsError> {
+        match self {
+            #[cfg(feature = "aws-kms")]
+            Self::Aws { client, key_id } => {
+                let out = client
+                    .get_public_key()
+                    .key_id(key_id)
+                    .send()
+                    .await
+                    .map_err(|e| KmsError::Backend(e.to_string()))?;
+                let der = out
+                    .public_key()
+                    .ok_or_else(|| KmsError::Malformed("no public key".into()))?;
+                spki_pubkey(der.as_ref())
+            }
+            Self::Gcp { http, key_version, .. } => {
+                let response: Value = http
+                    .get(format!("{GCP_KMS_URL}/{key_version}/publicKey"))
+                    .bearer_auth(self.gcp_token().await?)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                let pem = response["pem"]
+                    .as_str()
+                    .ok_or_else(|| KmsError::Malformed("no public key".into()))?;
+                let body: String = pem.lines().filter(|line| !line.starts_with("-----")).collect();
+                let der = STANDARD
+                    .decode(body)
+                    .map_err(|e| KmsError::Malformed(format!("a public key that is not base64: {e}")))?;
+                spki_pubkey(&der)
+            }
+            Self::Remote { http, url, token } => {
+                let mut request = http.get(format!("{url}/pubkey"));
+                if let Some(token) = token {
+                    request = request.bearer_auth(token);
+                }
+                let response: Value = request.send().await?.error_for_status()?.json().await?;
+                response["pubkey"]
+                    .as_str()
+                    .and_then(|p| p.parse().ok())
+                    .ok_or_else(|| KmsError::Malformed("no base58 pubkey".into()))
+            }
+        }
+    }
+
+    async fn sign(&self, messages: &[Vec<u8>]) -> Result<Vec<Signature>, KmsError> {
+        match self {
+            #[cfg(feature = "aws-kms")]
+            Self::Aws { client, key_id } => {
+                use aws_sdk_kms::{
+                    primitives::Blob,
+                    types::{MessageType, SigningAlgorithmSpec},
+                };
+                try_join_all(messages.iter().map(|message| async move {
+                    let out = client
+                        .sign()
+                        .key_id(key_id)
+                        .message(Blob::new(message.clone()))
+                        .message_type(MessageType::Raw)
+                        .signing_algorithm(SigningAlgorithmSpec::from("ED25519_SHA_512"))
+                        .send()
+                        .await
+                        .map_err(|e| KmsError::Backend(e.to_string()))?;
+                    let signature = out
+                        .signature()
+                        .ok_or_else(|| KmsError::Malformed("no signature".into()))?;
+                    to_signature(signature.as_ref())
+                }))
+                .await
+            }
+            Self::Gcp { http, key_version, .. } => {
+                let token = self.gcp_token().await?;
+                try_join_all(messages.iter().map(|message| {
+                    let token = &token;
+                    async move {
+                        let response: Value = http
+                            .post(format!("{GCP_KMS_URL}/{key_version}:asymmetricSign"))
+                            .bearer_auth(token)
+                            .json(&json!({ "data": STANDARD.encode(message) }))
+                            .send()
+                            .await?
+                            .error_for_status()?
+                            .json()
+                            .await?;
+                        let signature = response["signature"]
+                            .as_str()
+                            .and_then(|s| STANDARD.decode(s).ok())
+                            .ok_or_else(|| KmsError::Malformed("no base64 signature".into()))?;
+                        to_signature(&signature)
+                    }
+                }))
+                .await
+            }
+            Self::Remote { http, url, token } => {
+                let body = json!({ "messages": messages.iter().map(|m| STANDARD.encode(m)).collect::<Vec<_>>() });
+                let mut request = http.post(format!("{url}/sign")).json(&body);
+                if let Some(token) = token {
+                    request = request.bearer_auth(token);
+                }
+                let response: Value = request.send().await?.error_for_status()?.json().await?;
+                let signatures: Vec<Signature> = response["signatures"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|s| s.as_str().and_then(|s| s.parse().ok()))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| KmsError::Malformed("signatures that are not base58".into()))?;
+                if signatures.len() != messages.len() {
+                    return Err(KmsError::Malformed(format!(
+                        "{} signatures for {} messages",
+                        signatures.len(),
+                        messages.len()
+                    )));
+                }
+                Ok(signatures)
+            }
+        }
+    }
+
+    async fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, KmsError> {
+        match self {
+            #[cfg(feature = "aws-kms")]
+            Self::Aws { client, key_id } => {
+                let out = client
+                    .encrypt()
+                    .key_id(key_id)
+                    .plaintext(aws_sdk_kms::primitives::Blob::new(plaintext))
+                    .send()
+                    .await
+                    .map_err(|e| KmsError::Backend(e.to_string()))?;
+                out.ciphertext_blob()
+                    .map(|blob| blob.as_ref().to_vec())
+                    .ok_or_else(|| KmsError::Malformed("no ciphertext".into()))
+            }
+            Self::Gcp { http, key_version, .. } => {
+                let response: Value = http
+                    .post(format!("{GCP_KMS_URL}/{key_version}:encrypt"))
+                    .bearer_auth(self.gcp_token().await?)
+                    .json(&json!({ "plaintext": STANDARD.encode(plaintext) }))
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                response["ciphertext"]
+                    .as_str()
+                    .and_then(|c| STANDARD.decode(c).ok())
+                    .ok_or_else(|| KmsError::Malformed("no base64 ciphertext".into()))
+            }
+            Self::Remote { .. } => Err(KmsError::InvalidSource("a signing service cannot wrap keys".into())),
+        }
+    }
+
+    async fn decrypt(&self, ciphertext: &[u8]) -> Result<Zeroizing<Vec<u8>>, KmsError> {
+        match self {
+            #[cfg(feature = "aws-kms")]
+            Self::Aws { client, key_id } => {
+                let out = client
+                    .decrypt()
+                    .key_id(key_id)
+                    .ciphertext_blob(aws_sdk_kms::primitives::Blob::new(ciphertext))
+                    .send()
+                    .await
+                    .map_err(|e| KmsError::Backend(e.to_string()))?;
+                out.plaintext()
+                    .map(|blob| Zeroizing::new(blob.as_ref().to_vec()))
+                    .ok_or_else(|| KmsError::Malformed("no plaintext".into()))
+            }
+            Self::Gcp { http, key_version, .. } => {
+                let response: Value = http
+                    .post(format!("{GCP_KMS_URL}/{key_version}:decrypt"))
+                    .bearer_auth(self.gcp_token().await?)
+                    .json(&json!({ "ciphertext": STANDARD.encode(ciphertext) }))
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                let plaintext = Zeroizing::new(response["plaintext"].as_str().unwrap_or_default().to_string());
+                STANDARD
+                    .decode(plaintext.as_bytes())
+                    .map(Zeroizing::new)
+                    .map_err(|_| KmsError::Malformed("no base64 plaintext".into()))
+            }
+            Self::Remote { .. } => Err(KmsError::InvalidSource("a signing service cannot unwrap keys".into())),
+        }
+    }
+
+    /// A Cloud KMS access token: `GOOGLE_OAUTH_ACCESS_TOKEN`, or one from the metadata server, cached until
+    /// shortly before it expires.
+    async fn gcp_token(&self) -> Result<String, KmsError> {
+        let Self::Gcp { http, token, .. } = self else {
+            unreachable!("only Cloud KMS takes an access token");
+        };
+        if let Ok(token) = std::env::var("GOOGLE_OAUTH_ACCESS_TOKEN") {
+            return Ok(token);
+        }
+        let mut cached = token.lock().await;
+        if let Some((token, expires)) = cached.as_ref() {
+            if Instant::now() < *expires {
+                return Ok(token.clone());
+            }
+        }
+        let response: Value = http
+            .get(GCP_METADATA_TOKEN_URL)
+            .header("Metadata-Flavor", "Google")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let access_token = response["access_token"]
+            .as_str()
+            .ok_or_else(|| KmsError::Malformed("no access token".into()))?
+            .to_string();
+        let lifetime = response["expires_in"].as_u64().unwrap_or(60).saturating_sub(30);
+        *cached = Some((access_token.clone(), Instant::now() + Duration::from_secs(lifetime)));
+        Ok(access_token)
+    }
+}
+
+fn spki_pubkey(der: &[u8]) -> Result<Pubkey, KmsError> {
+    der.strip_prefix(&ED25519_SPKI_PREFIX)
+        .and_then(|key| <[u8; 32]>::try_from(key).ok())
+        .map(Pubkey::new_from_array)
+        .ok_or_else(|| KmsError::Malformed("a public key that is not Ed25519".into()))
+}
+
+fn to_signature(bytes: &[u8]) -> Result<Signature, KmsError> {
+    Signature::try_from(bytes).map_err(|_| KmsError::Malformed(format!("a {}-byte signature", bytes.len())))
+}
+
+type Request = (Vec<u8>, oneshot::Sender<Result<Signature, KmsError>>);
+
+/// Signs with a key held by a [`SignerSource`].
+pub struct KmsSigner {
+    pubkey: Pubkey,
+    source: SignerSource,
+    requests: mpsc::UnboundedSender<Request>,
+}
+
+impl KmsSigner {
+    /// Connects to `source` and reads its public key. `token` authenticates to a signing service; calls time
+    /// out after `timeout`.
+    pub async fn connect(source: SignerSource, token: Option<String>, timeout: Duration) -> Result<Self, KmsError> {
+        let backend = Arc::new(Backend::connect(source.clone(), token, timeout).await?);
+        let pubkey = backend.pubkey().await?;
+        let (requests, inbox) = mpsc::unbounded_channel();
+        tokio::spawn(batch(backend, pubkey, inbox));
+        Ok(Self {
+            pubkey,
+            source,
+            requests,
+        })
+    }
+
+    pub fn source(&self) -> &SignerSource {
+        &self.source
+    }
+}
+
+impl Signer for KmsSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    /// Blocks the calling thread (not the runtime's other tasks) until the backend answers.
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let (reply, response) = oneshot::channel();
+        self.requests
+            .send((message.to_vec(), reply))
+            .map_err(|_| SignerError::Connection("signer task exited".into()))?;
+        let signature = tokio::task::block_in_place(|| response.blocking_recv())
+            .map_err(|_| SignerError::Connection("signer task exited".into()))?;
+        signature.map_err(|e| SignerError::Custom(e.to_string()))
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// Encrypts and decrypts data keys with a KMS key, which never leaves the KMS.
+pub struct KeyWrap {
+    backend: Backend,
+}
+
+impl KeyWrap {
+    /// Connects to the KMS of `source`; a signing service is refused, as it has no encryption to offer.
+    pub async fn connect(source: SignerSource, timeout: Duration) -> Result<Self, KmsError> {
+        if let SignerSource::Remote { url } = source {
+            return Err(KmsError::InvalidSource(format!(
+                "{url} cannot wrap keys; name an AWS or Cloud KMS key"
+            )));
+        }
+        Ok(Self {
+            backend: Backend::connect(source, None, timeout).await?,
+        })
+    }
+
+    pub async fn wrap(&self, key: &[u8]) -> Result<Vec<u8>, KmsError> {
+        self.backend.encrypt(key).await
+    }
+
+    pub async fn unwrap(&self, wrapped: &[u8]) -> Result<Zeroizing<Vec<u8>>, KmsError> {
+        self.backend.decrypt(wrapped).await
+    }
+}
+
+/// Gathers signing requests into batches and sends each to `backend` on its own task.
+async fn batch(backend: Arc<Backend>, pubkey: Pubkey, mut inbox: mpsc::UnboundedReceiver<Request>) {
+    let latency = Arc::new(Mutex::new(None::<Duration>));
+    while let Some(first) = inbox.recv().await {
+        let wait = latency
+            .lock()
+            .expect("signer latency lock poisoned")
+            .map_or(Duration::ZERO, |average| average.mul_f64(BATCH_WAIT_SHARE))
+            .min(MAX_BATCH_WAIT);
+        let mut requests = vec![first];
+        let deadline = tokio::time::Instant::now() + wait;
+        while requests.len() < MAX_BATCH {
+            match tokio::time::timeout_at(deadline, inbox.recv()).await {
+                Ok(Some(request)) => requests.push(request),
+                Ok(None) | Err(_) => break,
+            }
+        }
+        let (backend, latency) = (backend.clone(), latency.clone());
+        tokio::spawn(async move {
+            let (messages, replies): (Vec<_>, Vec<_>) = requests.into_iter().unzip();
+            let started = Instant::now();
+            let result = backend.sign(&messages).await;
+            let elapsed = started.elapsed();
+            {
+                let mut average = latency.lock().expect("signer latency lock poisoned");
+                *average = Some(match *average {
+                    Some(average) => average.mul_f64(1.0 - LATENCY_WEIGHT) + elapsed.mul_f64(LATENCY_WEIGHT),
+                    None => elapsed,
+                });
+            }
+            debug!(
+                batch = messages.len(),
+                elapsed_ms = elapsed.as_millis() as u64,
+                "remote signatures"
+            );
+            match result {
+                Ok(signatures) => {
+                    for ((message, signature), reply) in messages.iter().zip(signatures).zip(replies) {
+                        let checked = if signature.verify(pubkey.as_ref(), message) {
+                            metrics().remote_signatures.with_label_values(&["signed"]).inc();
+                            Ok(signature)
+                        } else {
+                            metrics().remote_signatures.with_label_values(&["invalid"]).inc();
+                            Err(KmsError::BadSignature)
+                        };
+                        let _ = reply.send(checked);
+                    }
+                }
+                Err(e) => {
+                    warn!(error = %e, batch = messages.len(), "remote signing failed");
+                    metrics()
+                        .remote_signatures
+                        .with_label_values(&["failed"])
+                        .inc_by(messages.len() as u64);
+                    for reply in replies {
+                        let _ = reply.send(Err(KmsError::Backend(e.to_string())));
+                    }
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,564 @@
+//! Double-entry ledger of every value movement the daemon makes: escrows funded, verified, claimed, refunded
+//! or released, Lightning received and paid, on-chain HTLCs funded, claimed and refunded, fees accrued and
+//! rebalancing swaps started. Each [`Entry`] posts
+//! signed amounts (debits positive) that sum to zero per [`Asset`]; the store refuses unbalanced ones. Swap
+//! entries are keyed by swap and [`EntryKind`], so a step re-run after a crash never posts twice.
+//!
+//! The [`audit`] compares the ledger's operator wallet and channel balances with the real ones. The first audit
+//! opens each against [`Account::Equity`]; after that any difference beyond the tolerance is drift worth
+//! explaining: a manual deposit, on-chain channel activity, forwarding income, or a swap the daemon lost track
+//! of. Payments in flight and rebalancing fees show as drift until they settle. Explained drift is booked with
+//! an [`EntryKind::Adjustment`].
+
+use std::{fmt, str::FromStr};
+
+use intercom_swap_client::state::EscrowState;
+use tracing::{error, info};
+
+use crate::{
+    metrics::metrics,
+    quote::Quote,
+    store::{Store, StoreError},
+    swap::{Direction, Swap, SwapState},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Asset {
+    Msat,
+    /// Token base units.
+    Token,
+}
+
+impl Asset {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Msat => "msat",
+            Self::Token => "token",
+        }
+    }
+}
+
+impl fmt::Display for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Asset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "msat" => Ok(Self::Msat),
+            "token" => Ok(Self::Token),
+            other => Err(format!("unknown asset {other:?}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Account {
+    /// The operator's token account.
+    Wallet,
+    /// Our side of the node's channels.
+    Channels,
+    /// Tokens in escrows that come to the operator: its own until the user claims, or the user's once verified.
+    Escrow,
+    /// What swap users paid in (credits) and got out (debits).
+    Counterparties,
+    /// Platform and trade fees paid out of the operator's escrows on claim.
+    EscrowFees,
+    /// Lightning routing fees.
+    RoutingFees,
+    /// Operator fees earned on quoted swaps.
+    FeeIncome,
+    /// Sats moved off (debits) or onto (credits) the channels by Loop or PeerSwap.
+    Rebalancing,
+    /// The node's on-chain wallet, as far as HTLCs draw on it and refunds go back to it.
+    OnchainWallet,
+    /// Sats locked in on-chain HTLCs, until claimed or refunded.
+    Htlc,
+    /// Bitcoin miner fees of HTLC fundings and refunds.
+    MinerFees,
+    /// Opening balances and adjustments.
+    Equity,
+}
+
+impl Account {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Wallet => "wallet",
+            Self::Channels => "channels",
+            Self::Escrow => "escrow",
+            Self::Counterparties => "counterparties",
+            Self::EscrowFees => "escrow_fees",
+            Self::RoutingFees => "routing_fees",
+            Self::FeeIncome => "fee_income",
+            Self::Rebalancing => "rebalancing",
+            Self::OnchainWallet => "onchain_wallet",
+            Self::Htlc => "htlc",
+            Self::MinerFees => "miner_fees",
+            Self::Equity => "equity",
+        }
+    }
+
+    /// The asset of an account that holds one.
+    pub fn asset(&self) -> Option<Asset> {
+        match self {
+            Self::Wallet | Self::Escrow | Self::EscrowFees | Self::FeeIncome => Some(Asset::Token),
+            Self::Channels
+            | Self::RoutingFees
+            | Self::Rebalancing
+            | Self::OnchainWallet
+            | Self::Htlc
+            | Self::MinerFees => Some(Asset::Msat),
+            Self::Counterparties | Self::Equity => None,
+        }
+    }
+}
+
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Account {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "wallet" => Self::Wallet,
+            "channels" => Self::Channels,
+            "escrow" => Self::Escrow,
+            "counterparties" => Self::Counterparties,
+            "escrow_fees" => Self::EscrowFees,
+            "routing_fees" => Self::RoutingFees,
+            "fee_income" => Self::FeeIncome,
+            "rebalancing" => Self::Rebalancing,
+            "onchain_wallet" => Self::OnchainWallet,
+            "htlc" => Self::Htlc,
+            "miner_fees" => Self::MinerFees,
+            "equity" => Self::Equity,
+            other => return Err(format!("unknown ledger account {other:?}")),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EntryKind {
+    /// LnToUsdt: the operator locked tokens in the escrow.
+    EscrowFunded,
+    /// UsdtToLn: the user's escrow, payable to the operator, checks out.
+    EscrowVerified,
+    /// LnToUsdt: the user's Lightning payment arrived.
+    LnReceived,
+    /// UsdtToLn: the operator paid the user's invoice.
+    LnPaid,
+    /// The escrow was claimed: by the user (LnToUsdt) or the operator (UsdtToLn).
+    EscrowClaimed,
+    /// LnToUsdt: the escrow went back to the operator.
+    EscrowRefunded,
+    /// UsdtToLn: the swap failed, leaving the escrow to its payer.
+    EscrowReleased,
+    /// UsdtToLn: the operator locked the BTC in an on-chain HTLC instead of paying over Lightning.
+    HtlcFunded,
+    /// UsdtToLn: the user claimed the on-chain HTLC.
+    HtlcClaimed,
+    /// UsdtToLn: the on-chain HTLC timed out back to the operator.
+    HtlcRefunded,
+    /// The operator's fee on a completed quoted swap.
+    FeeAccrued,
+    /// A Loop or PeerSwap swap was started.
+    Rebalance,
+    /// Balance found at the first audit.
+    Opening,
+    /// Booked by the operator, e.g. a deposit.
+    Adjustment,
+}
+
+impl EntryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::EscrowFunded => "escrow_funded",
+            Self::EscrowVerified => "escrow_verified",
+            Self::LnReceived => "ln_received",
+            Self::LnPaid => "ln_paid",
+            Self::EscrowClaimed => "escrow_claimed",
+            Self::EscrowRefunded => "escrow_refunded",
+            Self::EscrowReleased => "escrow_released",
+            Self::HtlcFunded => "htlc_funded",
+            Self::HtlcClaimed => "htlc_claimed",
+            Self::HtlcRefunded => "htlc_refunded",
+            Self::FeeAccrued => "fee_accrued",
+            Self::Rebalance => "rebalance",
+            Self::Opening => "opening",
+            Self::Adjustment => "adjustment",
+        }
+    }
+}
+
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for EntryKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "escrow_funded" => Self::EscrowFunded,
+            "escrow_verified" => Self::EscrowVerified,
+            "ln_received" => Self::LnReceived,
+            "ln_paid" => Self::LnPaid,
+            "escrow_claimed" => Self::EscrowClaimed,
+            "escrow_refunded" => Self::EscrowRefunded,
+            "escrow_released" => Self::EscrowReleased,
+            "htlc_funded" => Self::HtlcFunded,
+            "htlc_claimed" => Self::HtlcClaimed,
+            "htlc_refunded" => Self::HtlcRefunded,
+            "fee_accrued" => Self::FeeAccrued,
+            "rebalance" => Self::Rebalance,
+            "opening" => Self::Opening,
+            "adjustment" => Self::Adjustment,
+            other => return Err(format!("unknown ledger entry kind {other:?}")),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Posting {
+    pub account: Account,
+    pub asset: Asset,
+    /// Debit when positive, credit when negative.
+    pub amount: i64,
+}
+
+/// `amount` moved from `from` to `to`.
+fn transfer(from: Account, to: Account, asset: Asset, amount: u64) -> [Posting; 2] {
+    let amount = i64::try_from(amount).unwrap_or(i64::MAX);
+    [
+        Posting {
+            account: to,
+            asset,
+            amount,
+        },
+        Posting {
+            account: from,
+            asset,
+            amount: -amount,
+        },
+    ]
+}
+
+fn sat_to_msat(sat: u64) -> u64 {
+    sat.saturating_mul(1000)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub kind: EntryKind,
+    /// Set on swap entries, which are posted once per swap and kind.
+    pub swap_id: Option<String>,
+    pub note: Option<String>,
+    pub postings: Vec<Posting>,
+}
+
+impl Entry {
+    fn of_swap(kind: EntryKind, swap: &Swap, postings: Vec<Posting>) -> Self {
+        Self {
+            kind,
+            swap_id: Some(swap.id.clone()),
+            note: None,
+            postings,
+        }
+    }
+
+    /// Whether the postings sum to zero in each asset.
+    pub fn is_balanced(&self) -> bool {
+        [Asset::Msat, Asset::Token].iter().all(|asset| {
+            self.postings
+                .iter()
+                .filter(|p| p.asset == *asset)
+                .map(|p| i128::from(p.amount))
+                .sum::<i128>()
+                == 0
+        })
+    }
+
+    /// LnToUsdt: the operator's tokens into `escrow`, fees included.
+    pub fn escrow_funded(swap: &Swap, escrow: &EscrowState) -> Self {
+        let postings = transfer(Account::Wallet, Account::Escrow, Asset::Token, escrow.total_amount());
+        Self::of_swap(EntryKind::EscrowFunded, swap, postings.to_vec())
+    }
+
+    /// UsdtToLn: the user's net tokens in `escrow`, now due to the operator.
+    pub fn escrow_verified(swap: &Swap, escrow: &EscrowState) -> Self {
+        let postings = transfer(
+            Account::Counterparties,
+            Account::Escrow,
+            Asset::Token,
+            escrow.net_amount,
+        );
+        Self::of_swap(EntryKind::EscrowVerified, swap, postings.to_vec())
+    }
+
+    /// LnToUsdt: `amount_msat` paid in by the user.
+    pub fn ln_received(swap: &Swap, amount_msat: u64) -> Self {
+        let postings = transfer(Account::Counterparties, Account::Channels, Asset::Msat, amount_msat);
+        Self::of_swap(EntryKind::LnReceived, swap, postings.to_vec())
+    }
+
+    /// UsdtToLn: the invoice amount to the user and `fee_msat` to the route.
+    pub fn ln_paid(swap: &Swap, fee_msat: u64) -> Self {
+        let mut postings = transfer(
+            Account::Channels,
+            Account::Counterparties,
+            Asset::Msat,
+            swap.amount_msat,
+        )
+        .to_vec();
+        postings.extend(transfer(Account::Channels, Account::RoutingFees, Asset::Msat, fee_msat));
+        Self::of_swap(EntryKind::LnPaid, swap, postings)
+    }
+
+    /// LnToUsdt: `escrowed` (the escrow's total) paid out, the net to the user and the rest in fees.
+    pub fn claimed_by_user(swap: &Swap, escrowed: u64) -> Self {
+        let net = swap.token_amount.min(escrowed);
+        let mut postings = transfer(Account::Escrow, Account::Counterparties, Asset::Token, net).to_vec();
+        postings.extend(transfer(
+            Account::Escrow,
+            Account::EscrowFees,
+            Asset::Token,
+            escrowed - net,
+        ));
+        Self::of_swap(EntryKind::EscrowClaimed, swap, postings)
+    }
+
+    /// UsdtToLn: `escrowed` into the operator's wallet.
+    pub fn claimed_by_operator(swap: &Swap, escrowed: u64) -> Self {
+        let postings = transfer(Account::Escrow, Account::Wallet, Asset::Token, escrowed);
+        Self::of_swap(EntryKind::EscrowClaimed, swap, postings.to_vec())
+    }
+
+    pub fn escrow_refunded(swap: &Swap, escrowed: u64) -> Self {
+        let postings = transfer(Account::Escrow, Account::Wallet, Asset::Token, escrowed);
+        Self::of_swap(EntryKind::EscrowRefunded, swap, postings.to_vec())
+    }
+
+    pub fn escrow_released(swap: &Swap, escrowed: u64) -> Self {
+        let postings = transfer(Account::Escrow, Account::Counterparties, Asset::Token, escrowed);
+        Self::of_swap(EntryKind::EscrowReleased, swap, postings.to_vec())
+    }
+
+    /// UsdtToLn: `amount_sat` from the node's on-chain wallet into the HTLC, and `fee_sat` to the miners.
+    pub fn htlc_funded(swap: &Swap, amount_sat: u64, fee_sat: u64) -> Self {
+        let mut postings = transfer(
+            Account::OnchainWallet,
+            Account::Htlc,
+            Asset::Msat,
+            sat_to_msat(amount_sat),
+        )
+        .to_vec();
+        postings.extend(transfer(
+            Account::OnchainWallet,
+            Account::MinerFees,
+            Asset::Msat,
+            sat_to_msat(fee_sat),
+        ));
+        Self::of_swap(EntryKind::HtlcFunded, swap, postings)
+    }
+
+    /// UsdtToLn: the HTLC's `amount_sat` claimed by the user.
+    pub fn htlc_claimed(swap: &Swap, amount_sat: u64) -> Self {
+        let postings = transfer(
+            Account::Htlc,
+            Account::Counterparties,
+            Asset::Msat,
+            sat_to_msat(amount_sat),
+        );
+        Self::of_swap(EntryKind::HtlcClaimed, swap, postings.to_vec())
+    }
+
+    /// UsdtToLn: the HTLC's `amount_sat` back to the on-chain wallet, `fee_sat` of it to the miners.
+    pub fn htlc_refunded(swap: &Swap, amount_sat: u64, fee_sat: u64) -> Self {
+        let fee_sat = fee_sat.min(amount_sat);
+        let mut postings = transfer(
+            Account::Htlc,
+            Account::OnchainWallet,
+            Asset::Msat,
+            sat_to_msat(amount_sat - fee_sat),
+        )
+        .to_vec();
+        postings.extend(transfer(
+            Account::Htlc,
+            Account::MinerFees,
+            Asset::Msat,
+            sat_to_msat(fee_sat),
+        ));
+        Self::of_swap(EntryKind::HtlcRefunded, swap, postings)
+    }
+
+    /// The part of what the user paid that the operator keeps, per the swap's quote.
+    pub fn fee_accrued(swap: &Swap, quote: &Quote) -> Self {
+        let postings = transfer(
+            Account::FeeIncome,
+            Account::Counterparties,
+            Asset::Token,
+            quote.fee_tokens(),
+        );
+        Self::of_swap(EntryKind::FeeAccrued, swap, postings.to_vec())
+    }
+
+    /// `amount_msat` off the channels (`out`) or onto them.
+    pub fn rebalance(out: bool, amount_msat: u64, note: String) -> Self {
+        let postings = if out {
+            transfer(Account::Channels, Account::Rebalancing, Asset::Msat, amount_msat)
+        } else {
+            transfer(Account::Rebalancing, Account::Channels, Asset::Msat, amount_msat)
+        };
+        Self {
+            kind: EntryKind::Rebalance,
+            swap_id: None,
+            note: Some(note),
+            postings: postings.to_vec(),
+        }
+    }
+
+    /// Moves `amount` of `account`'s asset into it (out when negative), against equity.
+    pub fn against_equity(kind: EntryKind, account: Account, asset: Asset, amount: i64, note: Option<String>) -> Self {
+        Self {
+            kind,
+            swap_id: None,
+            note,
+            postings: vec![
+                Posting { account, asset, amount },
+                Posting {
+                    account: Account::Equity,
+                    asset,
+                    amount: -amount,
+                },
+            ],
+        }
+    }
+}
+
+/// The entries `swap`'s current state implies once written, beyond those posted where on-chain or Lightning
+/// amounts are read. Terminal states settle what the swap still has in [`Account::Escrow`].
+pub fn entries_for(store: &Store, swap: &Swap) -> Result<Vec<Entry>, StoreError> {
+    let escrowed = || -> Result<u64, StoreError> {
+        Ok(u64::try_from(store.ledger_swap_balance(&swap.id, Account::Escrow)?).unwrap_or(0))
+    };
+    let fee = || -> Result<Option<Entry>, StoreError> {
+        Ok(store
+            .quote_of_swap(&swap.id)?
+            .map(|quote| Entry::fee_accrued(swap, &quote)))
+    };
+    let mut entries = Vec::new();
+    match (swap.direction, swap.state) {
+        (Direction::LnToUsdt, SwapState::InvoiceSettled) => entries.push(Entry::ln_received(swap, swap.amount_msat)),
+        (Direction::LnToUsdt, SwapState::Completed) => {
+            // A plain invoice can be paid and claimed between two looks; completing implies it was paid.
+            entries.push(Entry::ln_received(swap, swap.amount_msat));
+            match escrowed()? {
+                0 => {}
+                escrowed => entries.push(Entry::claimed_by_user(swap, escrowed)),
+            }
+            entries.extend(fee()?);
+        }
+        (Direction::LnToUsdt, SwapState::Refunded) => match escrowed()? {
+            0 => {}
+            escrowed => entries.push(Entry::escrow_refunded(swap, escrowed)),
+        },
+        (Direction::UsdtToLn, SwapState::Completed) => {
+            match escrowed()? {
+                0 => {}
+                escrowed => entries.push(Entry::claimed_by_operator(swap, escrowed)),
+            }
+            entries.extend(fee()?);
+        }
+        (Direction::UsdtToLn, SwapState::Failed) => match escrowed()? {
+            0 => {}
+            escrowed => entries.push(Entry::escrow_released(swap, escrowed)),
+        },
+        _ => {}
+    }
+    Ok(entries)
+}
+
+/// How far the ledger may be off from the real balances before the audit alerts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tolerance {
+    pub msat: u64,
+    pub tokens: u64,
+}
+
+/// An audited account: the balance the ledger expects against the one read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Drift {
+    pub account: Account,
+    pub asset: Asset,
+    pub expected: i64,
+    pub actual: i64,
+}
+
+impl Drift {
+    /// Actual less expected.
+    pub fn amount(&self) -> i64 {
+        self.actual.saturating_sub(self.expected)
+    }
+}
+
+/// Checks the ledger against the channels' local balance and the wallet's token balance: opens accounts not
+/// audited before, alerts on drift beyond `tolerance` and on unbalanced entries, and sets the drift gauges.
+pub fn audit(
+    store: &Store,
+    channels_msat: u64,
+    wallet_tokens: u64,
+    tolerance: Tolerance,
+) -> Result<Vec<Drift>, StoreError> {
+    let mut drifts = Vec::new();
+    for (account, asset, actual, allowed) in [
+        (Account::Channels, Asset::Msat, channels_msat, tolerance.msat),
+        (Account::Wallet, Asset::Token, wallet_tokens, tolerance.tokens),
+    ] {
+        let actual = i64::try_from(actual).unwrap_or(i64::MAX);
+        let expected = store.ledger_balance(account, asset)?;
+        if !store.ledger_opened(account)? {
+            let opening = actual.saturating_sub(expected);
+            let entry = Entry::against_equity(EntryKind::Opening, account, asset, opening, Some(account.to_string()));
+            store.post_ledger_entry(&entry)?;
+            info!(%account, balance = actual, "ledger account opened");
+            continue;
+        }
+        let drift = Drift {
+            account,
+            asset,
+            expected,
+            actual,
+        };
+        metrics()
+            .ledger_drift
+            .with_label_values(&[account.as_str()])
+            .set(drift.amount());
+        if drift.amount().unsigned_abs() > allowed {
+            error!(
+                alert = "ledger_drift",
+                %account,
+                %asset,
+                expected,
+                actual,
+                drift = drift.amount(),
+                "balance does not match the ledger; find the cause and book an adjustment"
+            );
+        }
+        drifts.push(drift);
+    }
+    let unbalanced = store.unbalanced_ledger_entries()?;
+    if !unbalanced.is_empty() {
+        error!(alert = "ledger_unbalanced", entries = ?unbalanced, "ledger entries do not balance");
+    }
+    Ok(drifts)
+}
@@ -0,0 +1,98 @@
+//! Swap daemon: runs LN <-> USDT swaps end to end against the `ln_usdt_escrow` program.
+//!
+//! Swaps are accepted into the [`store`], a SQLite file or a PostgreSQL database instances share (`postgres`
+//! module, `postgres` feature), with preimages sealed by the [`vault`], and the [`engine`] drives each through its
+//! [`swap::SwapState`] machine using a Lightning node ([`ln::LnBackend`]) and an operator key, in memory, behind a
+//! [`kms`] or split t-of-n (`threshold` module, `frost` feature), that funds, claims and refunds escrows. Every
+//! value movement is booked in a double-entry [`ledger`] audited against the real balances. A [`dryrun`] walks the
+//! same state machine on a scratch copy of the database, simulating what it would send. Databases are upgraded at
+//! startup by embedded, versioned [`migrate`] steps. A step that keeps failing is retried with backoff, then parked
+//! as a dead letter for the operator ([`retries`]). State is persisted after every transition, and each write is
+//! kept in an append-only swap [`journal`], so the daemon can be stopped and restarted at any point, and sealed
+//! [`backup`]s of it can rebuild a lost host, while a [`handoff`] moves in-flight swaps to a new one, fenced so
+//! only one host drives them; claims racing refund_after can also go out as tipped [`jito`] bundles. The [`refund`]
+//! watcher reclaims any other expired escrow the operator can refund, and [`tower`] watches escrows for third
+//! parties. [`lnurl`] serves LNURL-pay links that swap sats into USDT, and [`keysend`] quotes can be paid without
+//! an invoice. Takers get signed, expiring prices from [`quote`], priced off the [`rates`] oracle with a fee set
+//! per quote by the [`feepolicy`] (usdt-to-ln quotes can charge the fee of a route probed to the payee, refusing
+//! unroutable ones), and sized to the operator's [`liquidity`], which the [`rebalance`]r keeps on both sides;
+//! operators who would rather not hold the BTC their swaps leave them can [`hedge`] it with offsetting spot orders
+//! on an exchange. A usdt-to-ln payment that keeps failing can instead go out through an [`onchain`] Taproot HTLC,
+//! for takers who registered a claim key. Takers can also [`negotiate`] swaps peer to peer, over Nostr (`nostr`
+//! feature) or a libp2p gossip network (`p2p` feature). Escrows may fall short of their swap's amount by a
+//! configured [`tolerance`], recorded per swap. Quotes are limited by each counterparty's [`reputation`] and by
+//! caps on the daemon's overall and per-counterparty [`exposure`], including the Lightning value it has in pending
+//! HTLCs, and counterparties pass compliance [`screening`] before a swap is accepted. Exchanges and bots drive the
+//! daemon through the [`control`] operations, over gRPC (`grpc` module, `grpc-api` feature) or REST with an OpenAPI
+//! document (`rest` module, `rest-api` feature), which also pushes swap progress over a WebSocket (`ws` module),
+//! authenticating callers with scoped keys ([`auth`]). Merchant backends can instead receive signed [`webhook`]s,
+//! and operators scrape Prometheus [`metrics`], get paged by [`alert`]s over Telegram, Slack or email, pull an
+//! accounting [`export`] and a profit-and-loss report ([`pnl`]) for finance and manage the program's platform
+//! config and fee withdrawals through previewed [`admin`] operations. The daemon reads its settings from a TOML
+//! [`config`] file, reloading offer terms while it runs, and a [`network`] profile binds its Lightning network to a
+//! Solana cluster, refusing to start against mismatched ones. One daemon can host several isolated [`tenant`]s,
+//! each with its own config, keys, node and database. The APIs can be served over TLS (`tls` module, `tls` feature)
+//! with certificates reloaded as they are renewed, and [`ratelimit`] throttles quoting and swap creation per caller
+//! and address.
+
+pub mod admin;
+pub mod alert;
+pub mod auth;
+pub mod backup;
+pub mod config;
+pub mod control;
+pub mod dryrun;
+pub mod engine;
+pub mod error;
+pub mod export;
+pub mod exposure;
+pub mod feepolicy;
+#[cfg(feature = "grpc-api")]
+pub mod grpc;
+pub mod handoff;
+pub mod hedge;
+pub mod jito;
+pub mod journal;
+pub mod keysend;
+pub mod kms;
+pub mod ledger;
+pub mod liquidity;
+pub mod ln;
+pub mod lnurl;
+pub mod metrics;
+pub mod migrate;
+pub mod negotiate;
+pub mod network;
+#[cfg(feature = "nostr")]
+pub mod nostr;
+pub mod onchain;
+#[cfg(feature = "p2p")]
+pub mod p2p;
+pub mod pnl;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod priority;
+pub mod quote;
+pub mod ratelimit;
+pub mod rates;
+pub mod rebalance;
+pub mod refund;
+pub mod reputation;
+#[cfg(feature = "rest-api")]
+pub mod rest;
+pub mod retries;
+pub mod safety;
+pub mod screening;
+pub mod store;
+pub mod swap;
+pub mod tenant;
+#[cfg(feature = "frost")]
+pub mod threshold;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod tolerance;
+pub mod tower;
+pub mod vault;
+pub mod webhook;
+#[cfg(feature = "rest-api")]
+pub mod ws;
@@ -0,0 +1,133 @@
+//! What the operator can still fill on each leg: Lightning capacity from the node's usable channels and the
+//! operator's USDT, less what active swaps and open quotes already claim. Quotes are sized against this so a
+//! swap does not fail halfway for want of liquidity.
+
+use std::fmt;
+
+use intercom_swap_client::client::{EscrowClient, FetchError};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    ln::{LnBackend, LnError},
+    metrics::metrics,
+    quote::QuoteConfig,
+    store::{Store, StoreError},
+    swap::{unix_now, Direction, SwapState},
+};
+
+#[derive(Debug)]
+pub enum LiquidityError {
+    Ln(LnError),
+    Fetch(FetchError),
+    Store(StoreError),
+}
+
+impl fmt::Display for LiquidityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ln(e) => write!(f, "cannot read channel balance: {e}"),
+            Self::Fetch(e) => write!(f, "cannot read token balance: {e}"),
+            Self::Store(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for LiquidityError {}
+
+impl From<LnError> for LiquidityError {
+    fn from(e: LnError) -> Self {
+        Self::Ln(e)
+    }
+}
+
+impl From<FetchError> for LiquidityError {
+    fn from(e: FetchError) -> Self {
+        Self::Fetch(e)
+    }
+}
+
+impl From<StoreError> for LiquidityError {
+    fn from(e: StoreError) -> Self {
+        Self::Store(e)
+    }
+}
+
+/// Free liquidity, net of commitments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Liquidity {
+    /// Lightning the operator can still receive (LnToUsdt).
+    pub inbound_msat: u64,
+    /// Lightning the operator can still send, routing fees included (UsdtToLn).
+    pub outbound_msat: u64,
+    /// USDT base units the operator can still put into escrows (LnToUsdt).
+    pub token_available: u64,
+}
+
+impl Liquidity {
+    /// Reads both legs and subtracts what active swaps and open quotes will use. `routing_fee_bps` is the fee
+    /// budget UsdtToLn payments reserve on top of their amount. Every snapshot updates the inventory metrics.
+    pub async fn snapshot<L: LnBackend>(
+        ln: &L,
+        client: &EscrowClient,
+        store: &Store,
+        operator: &Pubkey,
+        mint: &Pubkey,
+        routing_fee_bps: u16,
+    ) -> Result<Self, LiquidityError> {
+        let balance = ln.channel_balance().await?;
+        let tokens = client.get_token_balance(operator, mint).await?;
+        let with_fees = |msat: u64| msat.saturating_add(msat.saturating_mul(u64::from(routing_fee_bps)) / 10_000);
+
+        let (mut inbound, mut outbound, mut token) = (0u64, 0u64, 0u64);
+        for swap in store.active()? {
+            match (swap.direction, swap.state) {
+                // Escrow not funded yet: the tokens and, unless already received, the inbound are still owed.
+                (Direction::LnToUsdt, SwapState::Created | SwapState::InvoiceCreated) => {
+                    inbound = inbound.saturating_add(swap.amount_msat);
+                    token = token.saturating_add(swap.token_amount);
+                }
+                (Direction::LnToUsdt, SwapState::InvoiceAccepted | SwapState::KeysendReceived) => {
+                    token = token.saturating_add(swap.token_amount);
+                }
+                // Not paying yet; a payment in flight is already off the local balance.
+                (Direction::UsdtToLn, SwapState::Created | SwapState::EscrowVerified) => {
+                    outbound = outbound.saturating_add(with_fees(swap.amount_msat));
+                }
+                _ => {}
+            }
+        }
+        let now = unix_now();
+        for quote in store.open_quotes(now)? {
+            match quote.direction {
+                Direction::LnToUsdt => {
+                    inbound = inbound.saturating_add(quote.amount_msat);
+                    token = token.saturating_add(quote.token_amount);
+                }
+                Direction::UsdtToLn => outbound = outbound.saturating_add(with_fees(quote.amount_msat)),
+            }
+        }
+        for quote in store.open_keysend_quotes(now)? {
+            inbound = inbound.saturating_add(quote.amount_msat);
+            token = token.saturating_add(quote.token_amount);
+        }
+        let liquidity = Self {
+            inbound_msat: balance.remote_msat.saturating_sub(inbound),
+            outbound_msat: balance.local_msat.saturating_sub(outbound),
+            token_available: tokens.saturating_sub(token),
+        };
+        metrics().observe_balances(balance.local_msat, balance.remote_msat, tokens);
+        metrics().observe_liquidity(&liquidity);
+        Ok(liquidity)
+    }
+
+    /// Largest amount a quote in `direction` can be filled for at `cfg`'s price.
+    pub fn max_amount_msat(&self, direction: Direction, cfg: &QuoteConfig) -> u64 {
+        match direction {
+            Direction::LnToUsdt => self.inbound_msat.min(cfg.max_amount_for_tokens(self.token_available)),
+            Direction::UsdtToLn => {
+                let scaled = u128::from(self.outbound_msat) * 10_000 / (10_000 + u128::from(cfg.max_routing_fee_bps));
+                scaled as u64
+            }
+        }
+    }
+}
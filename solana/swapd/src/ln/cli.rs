@@ -0,0 +1,631 @@
+//! [`LnBackend`] over `lncli` or `lightning-cli`, optionally inside a docker compose service. Mirrors the JS
+//! tooling in `src/ln/client.js`, so the same node setups work for both.
+
+use std::path::PathBuf;
+
+use base64::Engine as _;
+use serde_json::Value;
+use tokio::process::Command;
+
+use super::{
+    decode_invoice, parse_network, ChannelBalance, Description, Invoice, InvoiceLookup, InvoiceStatus, Keysend,
+    LnBackend, LnError, PaymentStatus, Route, WalletTxStatus, SWAP_ID_RECORD,
+};
+use crate::network::LnNetwork;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeImpl {
+    Lnd,
+    Cln,
+}
+
+/// Run the CLI through `docker compose -f <compose_file> exec -T <service>` instead of directly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DockerTarget {
+    pub compose_file: PathBuf,
+    pub service: String,
+}
+
+/// Explicit `lncli` connection flags; ignored for CLN and when running in docker.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LndConnection {
+    pub rpcserver: Option<String>,
+    pub tlscertpath: Option<PathBuf>,
+    pub macaroonpath: Option<PathBuf>,
+    pub lnddir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CliBackend {
+    pub node: NodeImpl,
+    pub network: String,
+    /// Defaults to `lncli` / `lightning-cli`.
+    pub bin: Option<String>,
+    pub docker: Option<DockerTarget>,
+    pub lnd: LndConnection,
+}
+
+impl CliBackend {
+    fn command(&self, args: &[String]) -> Command {
+        let bin = self.bin.clone().unwrap_or_else(|| {
+            match self.node {
+                NodeImpl::Lnd => "lncli",
+                NodeImpl::Cln => "lightning-cli",
+            }
+            .to_string()
+        });
+        let mut base = vec![format!("--network={}", self.network)];
+        if self.node == NodeImpl::Lnd && self.docker.is_none() {
+            let lnd = &self.lnd;
+            base.extend(lnd.rpcserver.iter().map(|v| format!("--rpcserver={v}")));
+            base.extend(lnd.tlscertpath.iter().map(|v| format!("--tlscertpath={}", v.display())));
+            base.extend(
+                lnd.macaroonpath
+                    .iter()
+                    .map(|v| format!("--macaroonpath={}", v.display())),
+            );
+            base.extend(lnd.lnddir.iter().map(|v| format!("--lnddir={}", v.display())));
+        }
+        let mut cmd = match &self.docker {
+            Some(docker) => {
+                let mut cmd = Command::new("docker");
+                cmd.arg("compose")
+                    .arg("-f")
+                    .arg(&docker.compose_file)
+                    .args(["exec", "-T", &docker.service, &bin]);
+                cmd
+            }
+            None => Command::new(bin),
+        };
+        cmd.args(base).args(args).kill_on_drop(true);
+        cmd
+    }
+
+    async fn run(&self, args: &[String]) -> Result<Value, LnError> {
+        let out = self
+            .command(args)
+            .output()
+            .await
+            .map_err(|e| LnError::Node(e.to_string()))?;
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        if !out.status.success() {
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            let msg = [stderr.trim(), stdout.trim()]
+                .into_iter()
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+                .join(" | ");
+            return Err(LnError::Node(msg));
+        }
+        parse_json_or_lines(&stdout)
+    }
+}
+
+/// `lncli` flag and value for an invoice description.
+fn lnd_description(description: Description<'_>) -> (&'static str, String) {
+    match description.hash() {
+        Some(hash) => ("--description_hash", hex::encode(hash)),
+        None => ("--memo", description.text().to_string()),
+    }
+}
+
+/// Some `lncli` commands stream several JSON objects; the last one is the final state.
+fn parse_json_or_lines(text: &str) -> Result<Value, LnError> {
+    if let Ok(v) = serde_json::from_str(text) {
+        return Ok(v);
+    }
+    let stream = serde_json::Deserializer::from_str(text).into_iter::<Value>();
+    stream
+        .filter_map(Result::ok)
+        .last()
+        .ok_or_else(|| LnError::Parse(format!("not JSON: {}", text.trim())))
+}
+
+/// 32 bytes given as hex or base64, as `lncli` uses both depending on the command.
+pub(super) fn decode_bytes32(v: &Value) -> Option<[u8; 32]> {
+    let s = v.as_str()?.trim();
+    let bytes = if s.len() == 64 {
+        hex::decode(s).ok()?
+    } else {
+        base64::engine::general_purpose::STANDARD.decode(s).ok()?
+    };
+    bytes.try_into().ok()
+}
+
+/// Integers arrive as JSON numbers, decimal strings, or CLN's `"123msat"`.
+pub(super) fn as_u64(v: &Value) -> Option<u64> {
+    v.as_u64()
+        .or_else(|| v.as_str().and_then(|s| s.trim_end_matches("msat").parse().ok()))
+}
+
+/// Sum of the `htlcs` parts in one of `states`, as listed by `lncli lookupinvoice` and the CLN hold plugin.
+pub(super) fn htlc_parts_msat(invoice: &Value, states: &[&str]) -> u64 {
+    let htlcs = invoice["htlcs"].as_array().into_iter().flatten();
+    htlcs
+        .filter(|h| h["state"].as_str().is_some_and(|s| states.contains(&s)))
+        .filter_map(|h| as_u64(&h["amt_msat"]).or_else(|| as_u64(&h["amount_msat"])))
+        .sum()
+}
+
+/// Parts the CLN hold plugin holds for `inv`; older plugin versions list no HTLCs, only the invoice amount once
+/// the whole set is accepted.
+pub(super) fn cln_hold_received_msat(inv: &Value, status: InvoiceStatus) -> u64 {
+    match htlc_parts_msat(inv, &["accepted", "paid"]) {
+        0 if matches!(status, InvoiceStatus::Accepted | InvoiceStatus::Settled) => {
+            as_u64(&inv["amount_msat"]).unwrap_or(0)
+        }
+        held => held,
+    }
+}
+
+/// CLN `getroute` output as a [`Route`]: the first hop carries the amount plus every fee after it.
+pub(super) fn cln_route(r: &Value, amount_msat: u64) -> Option<Route> {
+    let hops = r["route"].as_array()?;
+    let sent = hops.first().and_then(|h| as_u64(&h["amount_msat"]))?;
+    Some(Route {
+        fee_msat: sent.saturating_sub(amount_msat),
+        hops: hops.len() as u32,
+    })
+}
+
+/// Both nodes' answer when the graph has no path, as opposed to a failed call.
+fn is_no_route(err: &LnError) -> bool {
+    matches!(err, LnError::Node(msg) if msg.contains("unable to find a path") || msg.contains("Could not find a route"))
+}
+
+fn is_not_found(err: &LnError) -> bool {
+    matches!(err, LnError::Node(msg) if msg.contains("unable to locate invoice") || msg.contains("not found"))
+}
+
+impl CliBackend {
+    async fn lnd_payment(&self, hash: &str) -> Result<Option<PaymentStatus>, LnError> {
+        // No lookup-by-hash command for outgoing payments; scan the recent window like the JS tooling.
+        let r = self
+            .run(&[
+                "listpayments".into(),
+                "--include_incomplete".into(),
+                "--max_payments".into(),
+                "200".into(),
+            ])
+            .await?;
+        let payment = r["payments"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|p| decode_bytes32(&p["payment_hash"]).map(hex::encode).as_deref() == Some(hash));
+        let Some(p) = payment else {
+            return Ok(None);
+        };
+        Ok(Some(match p["status"].as_str().unwrap_or_default() {
+            "SUCCEEDED" => PaymentStatus::Succeeded {
+                preimage: decode_bytes32(&p["payment_preimage"])
+                    .ok_or_else(|| LnError::Parse("succeeded payment without preimage".into()))?,
+                fee_msat: as_u64(&p["fee_msat"]).unwrap_or(0),
+            },
+            "FAILED" => PaymentStatus::Failed {
+                reason: p["failure_reason"].as_str().unwrap_or("FAILED").to_string(),
+            },
+            _ => PaymentStatus::InFlight,
+        }))
+    }
+
+    async fn cln_payment(&self, hash: &str) -> Result<Option<PaymentStatus>, LnError> {
+        let r = self
+            .run(&["listpays".into(), "-k".into(), format!("payment_hash={hash}")])
+            .await?;
+        let pays = r["pays"].as_array().cloned().unwrap_or_default();
+        if pays.is_empty() {
+            return Ok(None);
+        }
+        if let Some(p) = pays.iter().find(|p| p["status"] == "complete") {
+            let sent = as_u64(&p["amount_sent_msat"]).unwrap_or(0);
+            let amount = as_u64(&p["amount_msat"]).unwrap_or(sent);
+            return Ok(Some(PaymentStatus::Succeeded {
+                preimage: decode_bytes32(&p["preimage"])
+                    .ok_or_else(|| LnError::Parse("complete payment without preimage".into()))?,
+                fee_msat: sent.saturating_sub(amount),
+            }));
+        }
+        if pays.iter().any(|p| p["status"] == "pending") {
+            return Ok(Some(PaymentStatus::InFlight));
+        }
+        Ok(Some(PaymentStatus::Failed {
+            reason: "all payment attempts failed".into(),
+        }))
+    }
+}
+
+impl CliBackend {
+    /// The hold plugin's record of `hash`; `None` if it has none or is not loaded.
+    async fn cln_hold_invoice(&self, hash: &str) -> Result<Option<InvoiceLookup>, LnError> {
+        let r = match self
+            .run(&["listholdinvoices".into(), "-k".into(), format!("payment_hash={hash}")])
+            .await
+        {
+            Ok(r) => r,
+            Err(LnError::Node(msg)) if msg.contains("Unknown command") => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let Some(inv) = r["holdinvoices"].as_array().and_then(|a| a.first()) else {
+            return Ok(None);
+        };
+        let status = match inv["state"].as_str().unwrap_or_default() {
+            "paid" => InvoiceStatus::Settled,
+            "accepted" => InvoiceStatus::Accepted,
+            "cancelled" => InvoiceStatus::Canceled,
+            _ => InvoiceStatus::Open,
+        };
+        let bolt11 = inv["bolt11"]
+            .as_str()
+            .ok_or_else(|| LnError::Parse("hold invoice without bolt11".into()))?;
+        Ok(Some(InvoiceLookup {
+            bolt11: bolt11.to_string(),
+            status,
+            received_msat: cln_hold_received_msat(inv, status),
+        }))
+    }
+}
+
+impl LnBackend for CliBackend {
+    async fn create_invoice(
+        &self,
+        amount_msat: u64,
+        preimage: &[u8; 32],
+        description: Description<'_>,
+        expiry_secs: u64,
+    ) -> Result<Invoice, LnError> {
+        let (desc_flag, desc_value) = lnd_description(description);
+        let bolt11 = match self.node {
+            NodeImpl::Lnd => {
+                let r = self
+                    .run(&[
+                        "addinvoice".into(),
+                        "--amt_msat".into(),
+                        amount_msat.to_string(),
+                        desc_flag.into(),
+                        desc_value.clone(),
+                        "--expiry".into(),
+                        expiry_secs.to_string(),
+                        "--preimage".into(),
+                        hex::encode(preimage),
+                    ])
+                    .await?;
+                r["payment_request"].as_str().map(str::to_string)
+            }
+            NodeImpl::Cln => {
+                let hash = hex::encode(solana_sdk::hash::hash(preimage).to_bytes());
+                let mut args = vec![
+                    "invoice".into(),
+                    "-k".into(),
+                    format!("amount_msat={amount_msat}"),
+                    format!("label=swapd-{hash}"),
+                    format!("description={}", description.text()),
+                    format!("expiry={expiry_secs}"),
+                    format!("preimage={}", hex::encode(preimage)),
+                ];
+                if description.hash().is_some() {
+                    args.push("deschashonly=true".into());
+                }
+                let r = self.run(&args).await?;
+                r["bolt11"].as_str().map(str::to_string)
+            }
+        }
+        .ok_or_else(|| LnError::Parse("invoice response without bolt11".into()))?;
+        let decoded = decode_invoice(&bolt11)?;
+        Ok(Invoice {
+            bolt11,
+            payment_hash: decoded.payment_hash,
+            expires_at: decoded.expires_at,
+        })
+    }
+
+    async fn lookup_invoice(&self, payment_hash: &[u8; 32]) -> Result<Option<InvoiceLookup>, LnError> {
+        let hash = hex::encode(payment_hash);
+        let (bolt11, status, received_msat) = match self.node {
+            NodeImpl::Lnd => {
+                let r = match self.run(&["lookupinvoice".into(), hash]).await {
+                    Ok(r) => r,
+                    Err(e) if is_not_found(&e) => return Ok(None),
+                    Err(e) => return Err(e),
+                };
+                let status = match r["state"].as_str().unwrap_or_default() {
+                    "SETTLED" => InvoiceStatus::Settled,
+                    "ACCEPTED" => InvoiceStatus::Accepted,
+                    "CANCELED" => InvoiceStatus::Canceled,
+                    _ => InvoiceStatus::Open,
+                };
+                let received = htlc_parts_msat(&r, &["ACCEPTED", "SETTLED"]);
+                (r["payment_request"].as_str().map(str::to_string), status, received)
+            }
+            NodeImpl::Cln => {
+                let r = self
+                    .run(&["listinvoices".into(), "-k".into(), format!("payment_hash={hash}")])
+                    .await?;
+                let Some(inv) = r["invoices"].as_array().and_then(|a| a.first()) else {
+                    return self.cln_hold_invoice(&hash).await;
+                };
+                let status = match inv["status"].as_str().unwrap_or_default() {
+                    "paid" => InvoiceStatus::Settled,
+                    "expired" => InvoiceStatus::Canceled,
+                    _ => InvoiceStatus::Open,
+                };
+                let received = as_u64(&inv["amount_received_msat"]).unwrap_or(0);
+                (inv["bolt11"].as_str().map(str::to_string), status, received)
+            }
+        };
+        let bolt11 = bolt11.ok_or_else(|| LnError::Parse("invoice lookup without bolt11".into()))?;
+        Ok(Some(InvoiceLookup {
+            bolt11,
+            status,
+            received_msat,
+        }))
+    }
+
+    async fn pay_invoice(&self, bolt11: &str, max_fee_msat: u64, max_cltv_blocks: u32) -> Result<(), LnError> {
+        let args = match self.node {
+            NodeImpl::Lnd => vec![
+                "payinvoice".into(),
+                "--force".into(),
+                "--json".into(),
+                "--fee_limit".into(),
+                (max_fee_msat / 1000).to_string(),
+                "--cltv_limit".into(),
+                max_cltv_blocks.to_string(),
+                bolt11.to_string(),
+            ],
+            NodeImpl::Cln => vec![
+                "pay".into(),
+                "-k".into(),
+                format!("bolt11={bolt11}"),
+                format!("maxfee={max_fee_msat}"),
+                format!("maxdelay={max_cltv_blocks}"),
+            ],
+        };
+        self.run(&args).await.map(|_| ())
+    }
+
+    async fn payment_status(&self, payment_hash: &[u8; 32]) -> Result<Option<PaymentStatus>, LnError> {
+        let hash = hex::encode(payment_hash);
+        match self.node {
+            NodeImpl::Lnd => self.lnd_payment(&hash).await,
+            NodeImpl::Cln => self.cln_payment(&hash).await,
+        }
+    }
+
+    async fn channel_balance(&self) -> Result<ChannelBalance, LnError> {
+        match self.node {
+            NodeImpl::Lnd => {
+                let r = self.run(&["channelbalance".into()]).await?;
+                Ok(ChannelBalance {
+                    local_msat: as_u64(&r["local_balance"]["msat"]).unwrap_or(0),
+                    remote_msat: as_u64(&r["remote_balance"]["msat"]).unwrap_or(0),
+                })
+            }
+            NodeImpl::Cln => {
+                let r = self.run(&["listfunds".into()]).await?;
+                let channels = r["channels"].as_array().into_iter().flatten();
+                let mut balance = ChannelBalance::default();
+                for c in channels.filter(|c| c["state"] == "CHANNELD_NORMAL") {
+                    let ours = as_u64(&c["our_amount_msat"]).unwrap_or(0);
+                    let total = as_u64(&c["amount_msat"]).unwrap_or(ours);
+                    balance.local_msat += ours;
+                    balance.remote_msat += total.saturating_sub(ours);
+                }
+                Ok(balance)
+            }
+        }
+    }
+
+    async fn network(&self) -> Result<LnNetwork, LnError> {
+        let info = self.run(&["getinfo".into()]).await?;
+        let network = match self.node {
+            NodeImpl::Lnd => &info["chains"][0]["network"],
+            NodeImpl::Cln => &info["network"],
+        };
+        parse_network(network.as_str())
+    }
+
+    async fn probe_route(
+        &self,
+        destination: &[u8; 33],
+        amount_msat: u64,
+        max_fee_msat: u64,
+    ) -> Result<Option<Route>, LnError> {
+        let destination = hex::encode(destination);
+        let args = match self.node {
+            NodeImpl::Lnd => vec![
+                "queryroutes".into(),
+                "--dest".into(),
+                destination,
+                "--amt".into(),
+                amount_msat.div_ceil(1000).to_string(),
+                "--fee_limit".into(),
+                (max_fee_msat / 1000).to_string(),
+            ],
+            NodeImpl::Cln => vec![
+                "getroute".into(),
+                "-k".into(),
+                format!("id={destination}"),
+                format!("amount_msat={amount_msat}"),
+                "riskfactor=1".into(),
+            ],
+        };
+        let r = match self.run(&args).await {
+            Ok(r) => r,
+            Err(e) if is_no_route(&e) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let route = match self.node {
+            NodeImpl::Lnd => r["routes"].as_array().and_then(|a| a.first()).map(|route| Route {
+                fee_msat: as_u64(&route["total_fees_msat"]).unwrap_or(0),
+                hops: route["hops"].as_array().map_or(0, |h| h.len() as u32),
+            }),
+            NodeImpl::Cln => cln_route(&r, amount_msat),
+        };
+        // getroute takes no fee limit.
+        Ok(route.filter(|route| route.fee_msat <= max_fee_msat))
+    }
+
+    /// LND natively; CLN through the hold plugin.
+    fn supports_hold_invoices(&self) -> bool {
+        true
+    }
+
+    async fn create_hold_invoice(
+        &self,
+        amount_msat: u64,
+        payment_hash: &[u8; 32],
+        description: Description<'_>,
+        expiry_secs: u64,
+    ) -> Result<Invoice, LnError> {
+        let hash = hex::encode(payment_hash);
+        let (desc_flag, desc_value) = lnd_description(description);
+        let bolt11 = match self.node {
+            NodeImpl::Lnd => {
+                let r = self
+                    .run(&[
+                        "addholdinvoice".into(),
+                        hash,
+                        "--amt_msat".into(),
+                        amount_msat.to_string(),
+                        desc_flag.into(),
+                        desc_value.clone(),
+                        "--expiry".into(),
+                        expiry_secs.to_string(),
+                    ])
+                    .await?;
+                r["payment_request"].as_str().map(str::to_string)
+            }
+            NodeImpl::Cln => {
+                let r = self
+                    .run(&[
+                        "holdinvoice".into(),
+                        "-k".into(),
+                        format!("payment_hash={hash}"),
+                        format!("amount={amount_msat}"),
+                        // The hold plugin always commits to the full text.
+                        format!("description={}", description.text()),
+                        format!("expiry={expiry_secs}"),
+                    ])
+                    .await?;
+                r["bolt11"].as_str().map(str::to_string)
+            }
+        }
+        .ok_or_else(|| LnError::Parse("hold invoice response without bolt11".into()))?;
+        let decoded = decode_invoice(&bolt11)?;
+        Ok(Invoice {
+            bolt11,
+            payment_hash: decoded.payment_hash,
+            expires_at: decoded.expires_at,
+        })
+    }
+
+    async fn settle_hold_invoice(&self, preimage: &[u8; 32]) -> Result<(), LnError> {
+        let args = match self.node {
+            NodeImpl::Lnd => vec!["settleinvoice".into(), hex::encode(preimage)],
+            NodeImpl::Cln => vec![
+                "settleholdinvoice".into(),
+                "-k".into(),
+                format!("preimage={}", hex::encode(preimage)),
+            ],
+        };
+        self.run(&args).await.map(|_| ())
+    }
+
+    async fn cancel_hold_invoice(&self, payment_hash: &[u8; 32]) -> Result<(), LnError> {
+        let args = match self.node {
+            NodeImpl::Lnd => vec!["cancelinvoice".into(), hex::encode(payment_hash)],
+            NodeImpl::Cln => vec![
+                "cancelholdinvoice".into(),
+                "-k".into(),
+                format!("payment_hash={}", hex::encode(payment_hash)),
+            ],
+        };
+        self.run(&args).await.map(|_| ())
+    }
+
+    async fn keysends(&self, after: u64) -> Result<Vec<Keysend>, LnError> {
+        if self.node == NodeImpl::Cln {
+            // Core Lightning does not keep extra TLV records with the invoice.
+            return Err(LnError::Unsupported("keysend swaps on Core Lightning"));
+        }
+        let r = self
+            .run(&[
+                "listinvoices".into(),
+                "--index_offset".into(),
+                after.to_string(),
+                "--max_invoices".into(),
+                "500".into(),
+            ])
+            .await?;
+        let record = SWAP_ID_RECORD.to_string();
+        let mut keysends = Vec::new();
+        for inv in r["invoices"].as_array().into_iter().flatten() {
+            if inv["is_keysend"] != true || inv["state"] != "SETTLED" {
+                continue;
+            }
+            let swap_id = inv["htlcs"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find_map(|h| h["custom_records"][&record].as_str())
+                .and_then(|v| hex::decode(v).ok());
+            let (Some(swap_id), Some(payment_hash), Some(preimage), Some(index)) = (
+                swap_id,
+                decode_bytes32(&inv["r_hash"]),
+                decode_bytes32(&inv["r_preimage"]),
+                as_u64(&inv["add_index"]),
+            ) else {
+                continue;
+            };
+            keysends.push(Keysend {
+                payment_hash,
+                preimage,
+                amount_msat: as_u64(&inv["amt_paid_msat"]).unwrap_or(0),
+                swap_id,
+                index,
+            });
+        }
+        Ok(keysends)
+    }
+
+    async fn send_onchain(&self, address: &str, amount_sat: u64) -> Result<String, LnError> {
+        let args = match self.node {
+            NodeImpl::Lnd => vec![
+                "sendcoins".into(),
+                "--addr".into(),
+                address.to_string(),
+                "--amt".into(),
+                amount_sat.to_string(),
+                // Skips the confirmation prompt.
+                "--force".into(),
+            ],
+            NodeImpl::Cln => vec![
+                "withdraw".into(),
+                "-k".into(),
+                format!("destination={address}"),
+                format!("satoshi={amount_sat}"),
+            ],
+        };
+        let r = self.run(&args).await?;
+        r["txid"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| LnError::Parse("on-chain send without txid".into()))
+    }
+
+    async fn wallet_tx(&self, txid: &str) -> Result<WalletTxStatus, LnError> {
+        let (command, hash, confirmations) = match self.node {
+            NodeImpl::Lnd => ("listchaintxns", "tx_hash", "num_confirmations"),
+            // `blockheight` is 0 until it confirms.
+            NodeImpl::Cln => ("listtransactions", "hash", "blockheight"),
+        };
+        let r = self.run(&[command.into()]).await?;
+        let tx = r["transactions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|tx| tx[hash] == txid);
+        Ok(WalletTxStatus::of(tx.map(|tx| as_u64(&tx[confirmations]).unwrap_or(0))))
+    }
+}
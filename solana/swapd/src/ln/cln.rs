@@ -0,0 +1,342 @@
+//! [`LnBackend`] over Core Lightning's JSON-RPC socket (`lightning-rpc` in the node's network directory).
+//! Settlements stream in through `waitanyinvoice`. Hold invoices go through the `hold` plugin
+//! (`holdinvoice`, `listholdinvoices`, `settleholdinvoice`, `cancelholdinvoice`); without the plugin loaded,
+//! the hold-invoice calls fail and plain invoices keep working.
+
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+    sync::mpsc,
+};
+use tracing::{debug, warn};
+
+use super::{
+    cli::{as_u64, cln_hold_received_msat, cln_route, decode_bytes32},
+    decode_invoice, parse_network, ChannelBalance, Description, Invoice, InvoiceLookup, InvoiceStatus, LnBackend,
+    LnError, PaymentStatus, Route, WalletTxStatus,
+};
+use crate::network::LnNetwork;
+
+/// JSON-RPC code CLN returns for a command no plugin provides.
+const METHOD_NOT_FOUND: i64 = -32601;
+/// JSON-RPC code of `getroute` finding no path.
+const ROUTE_NOT_FOUND: i64 = 205;
+/// Wait before calling `waitanyinvoice` again after it errors.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub struct ClnRpc {
+    socket: PathBuf,
+    next_id: AtomicU64,
+}
+
+#[derive(Debug)]
+enum RpcError {
+    Node(LnError),
+    Rpc { code: i64, message: String },
+}
+
+impl From<RpcError> for LnError {
+    fn from(e: RpcError) -> Self {
+        match e {
+            RpcError::Node(e) => e,
+            RpcError::Rpc { code, message } => LnError::Node(format!("{message} (code {code})")),
+        }
+    }
+}
+
+impl ClnRpc {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// One request per connection, which keeps the long-running `waitanyinvoice` off the other calls' path.
+    async fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        let io = |e: std::io::Error| RpcError::Node(LnError::Node(format!("{}: {e}", self.socket.display())));
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let mut stream = UnixStream::connect(&self.socket).await.map_err(io)?;
+        stream.write_all(request.to_string().as_bytes()).await.map_err(io)?;
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 8192];
+        let response = loop {
+            let n = stream.read(&mut chunk).await.map_err(io)?;
+            if n == 0 {
+                return Err(RpcError::Node(LnError::Parse(format!("{method}: connection closed"))));
+            }
+            buf.extend_from_slice(&chunk[..n]);
+            match serde_json::from_slice::<Value>(&buf) {
+                Ok(v) => break v,
+                Err(e) if e.is_eof() => continue,
+                Err(e) => return Err(RpcError::Node(LnError::Parse(format!("{method}: {e}")))),
+            }
+        };
+        if let Some(err) = response.get("error") {
+            return Err(RpcError::Rpc {
+                code: err["code"].as_i64().unwrap_or_default(),
+                message: err["message"].as_str().unwrap_or("unknown error").to_string(),
+            });
+        }
+        Ok(response["result"].clone())
+    }
+
+    /// The hold plugin's record of `hash`; `None` if it has none or is not loaded.
+    async fn hold_invoice(&self, hash: &str) -> Result<Option<InvoiceLookup>, LnError> {
+        let r = match self.call("listholdinvoices", json!({ "payment_hash": hash })).await {
+            Ok(r) => r,
+            Err(RpcError::Rpc { code, .. }) if code == METHOD_NOT_FOUND => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let Some(inv) = r["holdinvoices"].as_array().and_then(|a| a.first()) else {
+            return Ok(None);
+        };
+        let status = match inv["state"].as_str().unwrap_or_default() {
+            "paid" => InvoiceStatus::Settled,
+            "accepted" => InvoiceStatus::Accepted,
+            "cancelled" => InvoiceStatus::Canceled,
+            _ => InvoiceStatus::Open,
+        };
+        let bolt11 = inv["bolt11"]
+            .as_str()
+            .ok_or_else(|| LnError::Parse("hold invoice without bolt11".into()))?;
+        Ok(Some(InvoiceLookup {
+            bolt11: bolt11.to_string(),
+            status,
+            received_msat: cln_hold_received_msat(inv, status),
+        }))
+    }
+
+    /// Highest pay_index so far, so `waitanyinvoice` starts with the next settlement rather than replaying
+    /// every invoice the node ever settled.
+    async fn last_pay_index(&self) -> Result<u64, LnError> {
+        let r = self.call("listinvoices", json!({})).await?;
+        let invoices = r["invoices"].as_array().into_iter().flatten();
+        Ok(invoices.filter_map(|i| i["pay_index"].as_u64()).max().unwrap_or(0))
+    }
+}
+
+impl LnBackend for ClnRpc {
+    async fn create_invoice(
+        &self,
+        amount_msat: u64,
+        preimage: &[u8; 32],
+        description: Description<'_>,
+        expiry_secs: u64,
+    ) -> Result<Invoice, LnError> {
+        let hash = hex::encode(solana_sdk::hash::hash(preimage).to_bytes());
+        let params = json!({
+            "amount_msat": amount_msat,
+            "label": format!("swapd-{hash}"),
+            "description": description.text(),
+            "deschashonly": description.hash().is_some(),
+            "expiry": expiry_secs,
+            "preimage": hex::encode(preimage),
+        });
+        let r = self.call("invoice", params).await?;
+        let bolt11 = r["bolt11"]
+            .as_str()
+            .ok_or_else(|| LnError::Parse("invoice response without bolt11".into()))?;
+        let decoded = decode_invoice(bolt11)?;
+        Ok(Invoice {
+            bolt11: bolt11.to_string(),
+            payment_hash: decoded.payment_hash,
+            expires_at: decoded.expires_at,
+        })
+    }
+
+    async fn lookup_invoice(&self, payment_hash: &[u8; 32]) -> Result<Option<InvoiceLookup>, LnError> {
+        let hash = hex::encode(payment_hash);
+        let r = self.call("listinvoices", json!({ "payment_hash": hash })).await?;
+        let Some(inv) = r["invoices"].as_array().and_then(|a| a.first()) else {
+            return self.hold_invoice(&hash).await;
+        };
+        let status = match inv["status"].as_str().unwrap_or_default() {
+            "paid" => InvoiceStatus::Settled,
+            "expired" => InvoiceStatus::Canceled,
+            _ => InvoiceStatus::Open,
+        };
+        let bolt11 = inv["bolt11"]
+            .as_str()
+            .ok_or_else(|| LnError::Parse("invoice lookup without bolt11".into()))?;
+        Ok(Some(InvoiceLookup {
+            bolt11: bolt11.to_string(),
+            status,
+            received_msat: as_u64(&inv["amount_received_msat"]).unwrap_or(0),
+        }))
+    }
+
+    async fn pay_invoice(&self, bolt11: &str, max_fee_msat: u64, max_cltv_blocks: u32) -> Result<(), LnError> {
+        let params = json!({ "bolt11": bolt11, "maxfee": max_fee_msat, "maxdelay": max_cltv_blocks });
+        self.call("pay", params).await?;
+        Ok(())
+    }
+
+    async fn payment_status(&self, payment_hash: &[u8; 32]) -> Result<Option<PaymentStatus>, LnError> {
+        let r = self
+            .call("listpays", json!({ "payment_hash": hex::encode(payment_hash) }))
+            .await?;
+        let pays = r["pays"].as_array().cloned().unwrap_or_default();
+        if pays.is_empty() {
+            return Ok(None);
+        }
+        if let Some(p) = pays.iter().find(|p| p["status"] == "complete") {
+            let sent = as_u64(&p["amount_sent_msat"]).unwrap_or(0);
+            let amount = as_u64(&p["amount_msat"]).unwrap_or(sent);
+            return Ok(Some(PaymentStatus::Succeeded {
+                preimage: decode_bytes32(&p["preimage"])
+                    .ok_or_else(|| LnError::Parse("complete payment without preimage".into()))?,
+                fee_msat: sent.saturating_sub(amount),
+            }));
+        }
+        if pays.iter().any(|p| p["status"] == "pending") {
+            return Ok(Some(PaymentStatus::InFlight));
+        }
+        Ok(Some(PaymentStatus::Failed {
+            reason: "all payment attempts failed".into(),
+        }))
+    }
+
+    async fn channel_balance(&self) -> Result<ChannelBalance, LnError> {
+        let r = self.call("listfunds", json!({})).await?;
+        let channels = r["channels"].as_array().into_iter().flatten();
+        let mut balance = ChannelBalance::default();
+        for c in channels.filter(|c| c["state"] == "CHANNELD_NORMAL") {
+            let ours = as_u64(&c["our_amount_msat"]).unwrap_or(0);
+            let total = as_u64(&c["amount_msat"]).unwrap_or(ours);
+            balance.local_msat += ours;
+            balance.remote_msat += total.saturating_sub(ours);
+        }
+        Ok(balance)
+    }
+
+    async fn network(&self) -> Result<LnNetwork, LnError> {
+        let info = self.call("getinfo", json!({})).await?;
+        parse_network(info["network"].as_str())
+    }
+
+    async fn probe_route(
+        &self,
+        destination: &[u8; 33],
+        amount_msat: u64,
+        max_fee_msat: u64,
+    ) -> Result<Option<Route>, LnError> {
+        let params = json!({ "id": hex::encode(destination), "amount_msat": amount_msat, "riskfactor": 1 });
+        let r = match self.call("getroute", params).await {
+            Ok(r) => r,
+            Err(RpcError::Rpc { code, .. }) if code == ROUTE_NOT_FOUND => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        // getroute takes no fee limit.
+        Ok(cln_route(&r, amount_msat).filter(|route| route.fee_msat <= max_fee_msat))
+    }
+
+    async fn watch_settlements(&self, settled: mpsc::Sender<[u8; 32]>) {
+        let mut last = loop {
+            match self.last_pay_index().await {
+                Ok(index) => break index,
+                Err(e) => {
+                    warn!(error = %e, "cannot read invoice pay index; retrying");
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+        };
+        while !settled.is_closed() {
+            let inv = match self.call("waitanyinvoice", json!({ "lastpay_index": last })).await {
+                Ok(inv) => inv,
+                Err(e) => {
+                    warn!(error = %LnError::from(e), "waitanyinvoice failed; retrying");
+                    tokio::time::sleep(RETRY_DELAY).await;
+                    continue;
+                }
+            };
+            last = inv["pay_index"].as_u64().unwrap_or(last + 1);
+            let Some(hash) = inv["payment_hash"]
+                .as_str()
+                .and_then(|h| hex::decode(h).ok())
+                .and_then(|b| <[u8; 32]>::try_from(b).ok())
+            else {
+                continue;
+            };
+            debug!(payment_hash = %hex::encode(hash), "cln reported settlement");
+            if settled.send(hash).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    fn supports_hold_invoices(&self) -> bool {
+        true
+    }
+
+    async fn create_hold_invoice(
+        &self,
+        amount_msat: u64,
+        payment_hash: &[u8; 32],
+        description: Description<'_>,
+        expiry_secs: u64,
+    ) -> Result<Invoice, LnError> {
+        let params = json!({
+            "payment_hash": hex::encode(payment_hash),
+            "amount": amount_msat,
+            // The hold plugin always commits to the full text.
+            "description": description.text(),
+            "expiry": expiry_secs,
+        });
+        let r = self.call("holdinvoice", params).await?;
+        let bolt11 = r["bolt11"]
+            .as_str()
+            .ok_or_else(|| LnError::Parse("holdinvoice response without bolt11".into()))?;
+        let decoded = decode_invoice(bolt11)?;
+        Ok(Invoice {
+            bolt11: bolt11.to_string(),
+            payment_hash: decoded.payment_hash,
+            expires_at: decoded.expires_at,
+        })
+    }
+
+    async fn settle_hold_invoice(&self, preimage: &[u8; 32]) -> Result<(), LnError> {
+        self.call("settleholdinvoice", json!({ "preimage": hex::encode(preimage) }))
+            .await?;
+        Ok(())
+    }
+
+    async fn cancel_hold_invoice(&self, payment_hash: &[u8; 32]) -> Result<(), LnError> {
+        self.call(
+            "cancelholdinvoice",
+            json!({ "payment_hash": hex::encode(payment_hash) }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn send_onchain(&self, address: &str, amount_sat: u64) -> Result<String, LnError> {
+        let r = self
+            .call("withdraw", json!({ "destination": address, "satoshi": amount_sat }))
+            .await?;
+        r["txid"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| LnError::Parse("withdraw response without txid".into()))
+    }
+
+    async fn wallet_tx(&self, txid: &str) -> Result<WalletTxStatus, LnError> {
+        let r = self.call("listtransactions", json!({})).await?;
+        let tx = r["transactions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|tx| tx["hash"] == txid);
+        // `blockheight` is 0 until it confirms.
+        Ok(WalletTxStatus::of(tx.map(|tx| tx["blockheight"].as_u64().unwrap_or(0))))
+    }
+}
@@ -0,0 +1,264 @@
+//! [`LnBackend`] over Eclair's REST API (HTTP basic auth with the API password). Settlements arrive as
+//! `payment-received` events on the `/ws` websocket.
+
+use std::time::Duration;
+
+use base64::Engine as _;
+use futures_util::StreamExt;
+use reqwest::Client;
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, http::HeaderValue, Message};
+use tracing::{debug, warn};
+
+use super::{
+    cli::{as_u64, decode_bytes32},
+    decode_invoice, parse_network, ChannelBalance, Description, Invoice, InvoiceLookup, InvoiceStatus, LnBackend,
+    LnError, PaymentStatus,
+};
+use crate::network::LnNetwork;
+
+/// Eclair's default `eclair.router.path-finding.max-cltv`, the longest route it builds unless configured lower.
+const ECLAIR_DEFAULT_MAX_CLTV: u32 = 1008;
+/// Wait before reconnecting after the websocket drops.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EclairConfig {
+    /// API base URL, e.g. `http://127.0.0.1:8080`.
+    pub url: String,
+    /// `eclair.api.password`.
+    pub password: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Eclair {
+    http: Client,
+    cfg: EclairConfig,
+}
+
+impl Eclair {
+    pub fn new(cfg: EclairConfig) -> Self {
+        Self {
+            http: Client::new(),
+            cfg,
+        }
+    }
+
+    async fn post(&self, path: &str, form: &[(&str, String)]) -> Result<Value, LnError> {
+        let url = format!("{}/{path}", self.cfg.url.trim_end_matches('/'));
+        let response = self
+            .http
+            .post(&url)
+            .basic_auth("", Some(&self.cfg.password))
+            .form(form)
+            .send()
+            .await
+            .map_err(|e| LnError::Node(format!("{path}: {e}")))?;
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| LnError::Node(format!("{path}: {e}")))?;
+        if !status.is_success() {
+            let message = serde_json::from_str::<Value>(&body)
+                .ok()
+                .and_then(|v| v["error"].as_str().map(str::to_string))
+                .unwrap_or(body);
+            return Err(LnError::Node(format!("{path}: {status}: {message}")));
+        }
+        serde_json::from_str(&body).map_err(|e| LnError::Parse(format!("{path}: {e}")))
+    }
+
+    fn ws_url(&self) -> String {
+        let base = self.cfg.url.trim_end_matches('/');
+        let base = base
+            .strip_prefix("https://")
+            .map(|rest| format!("wss://{rest}"))
+            .or_else(|| base.strip_prefix("http://").map(|rest| format!("ws://{rest}")))
+            .unwrap_or_else(|| base.to_string());
+        format!("{base}/ws")
+    }
+
+    /// Reads one websocket session, forwarding settled hashes; `false` once the receiver is gone.
+    async fn watch_once(&self, settled: &mpsc::Sender<[u8; 32]>) -> Result<bool, String> {
+        let mut request = self.ws_url().into_client_request().map_err(|e| e.to_string())?;
+        let auth = base64::engine::general_purpose::STANDARD.encode(format!(":{}", self.cfg.password));
+        request.headers_mut().insert(
+            "Authorization",
+            HeaderValue::from_str(&format!("Basic {auth}")).map_err(|e| e.to_string())?,
+        );
+        let (mut ws, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .map_err(|e| e.to_string())?;
+        while let Some(message) = ws.next().await {
+            let Message::Text(text) = message.map_err(|e| e.to_string())? else {
+                continue;
+            };
+            let Ok(event) = serde_json::from_str::<Value>(&text) else {
+                continue;
+            };
+            if event["type"] != "payment-received" {
+                continue;
+            }
+            let Some(hash) = decode_bytes32(&event["paymentHash"]) else {
+                continue;
+            };
+            debug!(payment_hash = %hex::encode(hash), "eclair reported settlement");
+            if settled.send(hash).await.is_err() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl LnBackend for Eclair {
+    async fn create_invoice(
+        &self,
+        amount_msat: u64,
+        preimage: &[u8; 32],
+        description: Description<'_>,
+        expiry_secs: u64,
+    ) -> Result<Invoice, LnError> {
+        let description = match description.hash() {
+            Some(hash) => ("descriptionHash", hex::encode(hash)),
+            None => ("description", description.text().to_string()),
+        };
+        let r = self
+            .post(
+                "createinvoice",
+                &[
+                    ("amountMsat", amount_msat.to_string()),
+                    description,
+                    ("expireIn", expiry_secs.to_string()),
+                    ("paymentPreimage", hex::encode(preimage)),
+                ],
+            )
+            .await?;
+        let bolt11 = r["serialized"]
+            .as_str()
+            .ok_or_else(|| LnError::Parse("invoice response without serialized invoice".into()))?;
+        let decoded = decode_invoice(bolt11)?;
+        Ok(Invoice {
+            bolt11: bolt11.to_string(),
+            payment_hash: decoded.payment_hash,
+            expires_at: decoded.expires_at,
+        })
+    }
+
+    async fn lookup_invoice(&self, payment_hash: &[u8; 32]) -> Result<Option<InvoiceLookup>, LnError> {
+        let hash = hex::encode(payment_hash);
+        let invoice = match self.post("getinvoice", &[("paymentHash", hash.clone())]).await {
+            Ok(invoice) => invoice,
+            Err(LnError::Node(msg)) if msg.contains("404") || msg.contains("not found") => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let bolt11 = invoice["serialized"]
+            .as_str()
+            .ok_or_else(|| LnError::Parse("invoice without serialized invoice".into()))?
+            .to_string();
+        let received = self.post("getreceivedinfo", &[("paymentHash", hash)]).await.ok();
+        let status = match received.as_ref().map(|r| &r["status"]["type"]) {
+            Some(t) if t == "received" => InvoiceStatus::Settled,
+            Some(t) if t == "expired" => InvoiceStatus::Canceled,
+            _ if decode_invoice(&bolt11)?.expires_at <= crate::swap::unix_now() => InvoiceStatus::Canceled,
+            _ => InvoiceStatus::Open,
+        };
+        // Eclair only reports a payment once all its parts arrived.
+        let received_msat = match status {
+            InvoiceStatus::Settled => received
+                .as_ref()
+                .and_then(|r| as_u64(&r["status"]["amount"]))
+                .unwrap_or(0),
+            _ => 0,
+        };
+        Ok(Some(InvoiceLookup {
+            bolt11,
+            status,
+            received_msat,
+        }))
+    }
+
+    async fn pay_invoice(&self, bolt11: &str, max_fee_msat: u64, max_cltv_blocks: u32) -> Result<(), LnError> {
+        // payinvoice takes no per-payment CLTV limit; Eclair caps routes at `eclair.router.path-finding.max-cltv`
+        // instead, which the operator must keep within the escrow window.
+        if max_cltv_blocks < ECLAIR_DEFAULT_MAX_CLTV {
+            warn!(
+                max_cltv_blocks,
+                "eclair cannot enforce the cltv limit per payment; relying on max-cltv"
+            );
+        }
+        // Non-blocking: Eclair returns the payment id at once and payment_status follows the attempts.
+        self.post(
+            "payinvoice",
+            &[
+                ("invoice", bolt11.to_string()),
+                ("maxFeeFlatSat", (max_fee_msat / 1000).to_string()),
+                ("maxFeePct", "0".to_string()),
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn payment_status(&self, payment_hash: &[u8; 32]) -> Result<Option<PaymentStatus>, LnError> {
+        let r = self
+            .post("getsentinfo", &[("paymentHash", hex::encode(payment_hash))])
+            .await?;
+        let attempts = r.as_array().cloned().unwrap_or_default();
+        if attempts.is_empty() {
+            return Ok(None);
+        }
+        if let Some(sent) = attempts.iter().find(|a| a["status"]["type"] == "sent") {
+            return Ok(Some(PaymentStatus::Succeeded {
+                preimage: decode_bytes32(&sent["status"]["paymentPreimage"])
+                    .ok_or_else(|| LnError::Parse("sent payment without preimage".into()))?,
+                fee_msat: as_u64(&sent["status"]["feesPaid"]).unwrap_or(0),
+            }));
+        }
+        if attempts.iter().any(|a| a["status"]["type"] == "pending") {
+            return Ok(Some(PaymentStatus::InFlight));
+        }
+        let reason = attempts
+            .iter()
+            .find_map(|a| a["status"]["failures"][0]["failureMessage"].as_str())
+            .unwrap_or("all payment attempts failed");
+        Ok(Some(PaymentStatus::Failed {
+            reason: reason.to_string(),
+        }))
+    }
+
+    async fn channel_balance(&self) -> Result<ChannelBalance, LnError> {
+        let r = self.post("usablebalances", &[]).await?;
+        let mut balance = ChannelBalance::default();
+        for channel in r.as_array().into_iter().flatten() {
+            balance.local_msat += as_u64(&channel["canSend"]).unwrap_or(0);
+            balance.remote_msat += as_u64(&channel["canReceive"]).unwrap_or(0);
+        }
+        Ok(balance)
+    }
+
+    /// Older releases name only the chain hash.
+    async fn network(&self) -> Result<LnNetwork, LnError> {
+        let info = self.post("getinfo", &[]).await?;
+        match info["network"].as_str() {
+            Some(network) => parse_network(Some(network)),
+            None => info["chainHash"]
+                .as_str()
+                .and_then(LnNetwork::from_chain_hash)
+                .ok_or_else(|| LnError::Parse(format!("getinfo names no known chain: {}", info["chainHash"]))),
+        }
+    }
+
+    async fn watch_settlements(&self, settled: mpsc::Sender<[u8; 32]>) {
+        while !settled.is_closed() {
+            match self.watch_once(&settled).await {
+                Ok(false) => return,
+                Ok(true) => warn!("eclair websocket closed; reconnecting"),
+                Err(e) => warn!(error = %e, "eclair websocket failed; reconnecting"),
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+}
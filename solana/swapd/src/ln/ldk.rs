@@ -0,0 +1,423 @@
+//! Embedded Lightning node ([`ldk_node`]) for operators without a separate node. swapd owns the node: it
+//! keeps LDK's state in its own directory next to the swap database, syncs the chain from Esplora and opens
+//! channels to the configured peers at startup.
+//!
+//! Invoices are created with `receive_for_hash`, so LDK holds every incoming payment until we claim it. For
+//! plain invoices the preimage is recorded when the invoice is created and the payment is claimed as soon as
+//! it arrives; hold invoices wait for [`LnBackend::settle_hold_invoice`]. LDK does not keep the
+//! bolt11 string or the claimable amount, so both are recorded per payment hash under `swapd-invoices/`.
+//! Descriptions are always committed as text, so LNURL-pay wallets that check the description hash reject
+//! these invoices.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use ldk_node::{
+    bitcoin::{secp256k1::PublicKey, Network},
+    lightning::ln::{msgs::SocketAddress, PaymentHash, PaymentPreimage},
+    payment::{PaymentDirection, PaymentKind, PaymentStatus as LdkPaymentStatus, SendingParameters},
+    Builder, Event, Node,
+};
+use lightning_invoice::Bolt11Invoice;
+use serde_json::json;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
+
+use super::{
+    decode_invoice, ChannelBalance, Description, Invoice, InvoiceLookup, InvoiceStatus, LnBackend, LnError,
+    PaymentStatus,
+};
+use crate::network::LnNetwork;
+
+/// A channel swapd opens at startup unless one to `node_id` already exists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LdkPeer {
+    pub node_id: PublicKey,
+    pub address: String,
+    pub channel_sats: u64,
+}
+
+impl FromStr for LdkPeer {
+    type Err = String;
+
+    /// `<node_id>@<host:port>=<sats>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (peer, sats) = s.rsplit_once('=').ok_or("expected <node_id>@<host:port>=<sats>")?;
+        let (node_id, address) = peer.split_once('@').ok_or("expected <node_id>@<host:port>=<sats>")?;
+        Ok(Self {
+            node_id: node_id.parse().map_err(|e| format!("node id: {e}"))?,
+            address: address.to_string(),
+            channel_sats: sats.parse().map_err(|e| format!("channel size: {e}"))?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LdkConfig {
+    pub network: Network,
+    pub storage_dir: PathBuf,
+    pub esplora_url: String,
+    pub listen: Vec<String>,
+    pub peers: Vec<LdkPeer>,
+}
+
+/// What swapd records about an invoice LDK holds for it.
+#[derive(Debug, Clone, Default)]
+struct Record {
+    bolt11: String,
+    /// Known for plain invoices; hold invoices get it on settle.
+    preimage: Option<[u8; 32]>,
+    /// Set once the payment is claimable.
+    accepted_msat: Option<u64>,
+}
+
+pub struct LdkNode {
+    node: Arc<Node>,
+    network: Network,
+    dir: PathBuf,
+    records: Mutex<HashMap<[u8; 32], Record>>,
+    settled: broadcast::Sender<[u8; 32]>,
+}
+
+fn node_err(e: impl std::fmt::Display) -> LnError {
+    LnError::Node(e.to_string())
+}
+
+impl LdkNode {
+    /// Builds and starts the node, reloads the invoice records and opens missing channels.
+    pub fn start(cfg: &LdkConfig) -> Result<Arc<Self>, LnError> {
+        let mut builder = Builder::new();
+        builder.set_network(cfg.network);
+        builder.set_storage_dir_path(cfg.storage_dir.display().to_string());
+        builder.set_chain_source_esplora(cfg.esplora_url.clone(), None);
+        if !cfg.listen.is_empty() {
+            let addresses = cfg
+                .listen
+                .iter()
+                .map(|a| SocketAddress::from_str(a).map_err(|_| LnError::Parse(format!("listen address {a}"))))
+                .collect::<Result<_, _>>()?;
+            builder.set_listening_addresses(addresses).map_err(node_err)?;
+        }
+        let node = Arc::new(builder.build().map_err(node_err)?);
+        node.start().map_err(node_err)?;
+        info!(node_id = %node.node_id(), "embedded lightning node started");
+
+        let dir = cfg.storage_dir.join("swapd-invoices");
+        fs::create_dir_all(&dir).map_err(node_err)?;
+        let this = Arc::new(Self {
+            records: Mutex::new(load_records(&dir)?),
+            node,
+            network: cfg.network,
+            dir,
+            settled: broadcast::channel(64).0,
+        });
+        for peer in &cfg.peers {
+            this.ensure_channel(peer)?;
+        }
+        tokio::spawn(this.clone().handle_events());
+        Ok(this)
+    }
+
+    pub fn node(&self) -> &Node {
+        &self.node
+    }
+
+    fn ensure_channel(&self, peer: &LdkPeer) -> Result<(), LnError> {
+        if self
+            .node
+            .list_channels()
+            .iter()
+            .any(|c| c.counterparty_node_id == peer.node_id)
+        {
+            return Ok(());
+        }
+        let address = SocketAddress::from_str(&peer.address)
+            .map_err(|_| LnError::Parse(format!("peer address {}", peer.address)))?;
+        self.node
+            .open_channel(peer.node_id, address, peer.channel_sats, None, None)
+            .map_err(node_err)?;
+        info!(peer = %peer.node_id, sats = peer.channel_sats, "opening channel");
+        Ok(())
+    }
+
+    fn records(&self) -> std::sync::MutexGuard<'_, HashMap<[u8; 32], Record>> {
+        self.records.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn save(&self, hash: &[u8; 32], record: &Record) -> Result<(), LnError> {
+        let body = json!({
+            "bolt11": record.bolt11,
+            "preimage": record.preimage.map(hex::encode),
+            "acceptedMsat": record.accepted_msat,
+        });
+        fs::write(self.dir.join(hex::encode(hash)), body.to_string()).map_err(node_err)?;
+        self.records().insert(*hash, record.clone());
+        Ok(())
+    }
+
+    /// Claims plain invoices as their payments become claimable and reports settlements.
+    async fn handle_events(self: Arc<Self>) {
+        loop {
+            let event = self.node.next_event_async().await;
+            match &event {
+                Event::PaymentClaimable {
+                    payment_hash,
+                    claimable_amount_msat,
+                    ..
+                } => {
+                    if let Err(e) = self.on_claimable(&payment_hash.0, *claimable_amount_msat) {
+                        warn!(payment_hash = %hex::encode(payment_hash.0), error = %e, "cannot claim payment");
+                    }
+                }
+                Event::PaymentReceived { payment_hash, .. } => {
+                    let _ = self.settled.send(payment_hash.0);
+                }
+                _ => {}
+            }
+            self.node.event_handled();
+        }
+    }
+
+    fn on_claimable(&self, hash: &[u8; 32], amount_msat: u64) -> Result<(), LnError> {
+        let Some(mut record) = self.records().get(hash).cloned() else {
+            // Not ours; LDK fails it back once the claim deadline passes.
+            return Ok(());
+        };
+        record.accepted_msat = Some(amount_msat);
+        self.save(hash, &record)?;
+        if let Some(preimage) = record.preimage {
+            self.claim(hash, amount_msat, &preimage)?;
+        }
+        Ok(())
+    }
+
+    fn claim(&self, hash: &[u8; 32], amount_msat: u64, preimage: &[u8; 32]) -> Result<(), LnError> {
+        self.node
+            .bolt11_payment()
+            .claim_for_hash(PaymentHash(*hash), amount_msat, PaymentPreimage(*preimage))
+            .map_err(node_err)
+    }
+
+    fn receive(
+        &self,
+        amount_msat: u64,
+        payment_hash: &[u8; 32],
+        preimage: Option<[u8; 32]>,
+        description: Description<'_>,
+        expiry_secs: u64,
+    ) -> Result<Invoice, LnError> {
+        let invoice = self
+            .node
+            .bolt11_payment()
+            .receive_for_hash(
+                amount_msat,
+                description.text(),
+                expiry_secs as u32,
+                PaymentHash(*payment_hash),
+            )
+            .map_err(node_err)?;
+        let bolt11 = invoice.to_string();
+        let decoded = decode_invoice(&bolt11)?;
+        self.save(
+            payment_hash,
+            &Record {
+                bolt11: bolt11.clone(),
+                preimage,
+                accepted_msat: None,
+            },
+        )?;
+        Ok(Invoice {
+            bolt11,
+            payment_hash: decoded.payment_hash,
+            expires_at: decoded.expires_at,
+        })
+    }
+}
+
+fn load_records(dir: &Path) -> Result<HashMap<[u8; 32], Record>, LnError> {
+    let mut records = HashMap::new();
+    for entry in fs::read_dir(dir).map_err(node_err)? {
+        let path = entry.map_err(node_err)?.path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let Some(hash) = hex::decode(name).ok().and_then(|b| <[u8; 32]>::try_from(b).ok()) else {
+            continue;
+        };
+        let body: serde_json::Value = fs::read(&path)
+            .ok()
+            .and_then(|b| serde_json::from_slice(&b).ok())
+            .ok_or_else(|| LnError::Parse(format!("invoice record {}", path.display())))?;
+        records.insert(
+            hash,
+            Record {
+                bolt11: body["bolt11"].as_str().unwrap_or_default().to_string(),
+                preimage: body["preimage"]
+                    .as_str()
+                    .and_then(|p| hex::decode(p).ok())
+                    .and_then(|b| b.try_into().ok()),
+                accepted_msat: body["acceptedMsat"].as_u64(),
+            },
+        );
+    }
+    Ok(records)
+}
+
+impl LnBackend for Arc<LdkNode> {
+    async fn create_invoice(
+        &self,
+        amount_msat: u64,
+        preimage: &[u8; 32],
+        description: Description<'_>,
+        expiry_secs: u64,
+    ) -> Result<Invoice, LnError> {
+        let hash = solana_sdk::hash::hash(preimage).to_bytes();
+        self.receive(amount_msat, &hash, Some(*preimage), description, expiry_secs)
+    }
+
+    async fn lookup_invoice(&self, payment_hash: &[u8; 32]) -> Result<Option<InvoiceLookup>, LnError> {
+        let Some(record) = self.records().get(payment_hash).cloned() else {
+            return Ok(None);
+        };
+        let details = self
+            .node
+            .list_payments_with_filter(|p| {
+                p.direction == PaymentDirection::Inbound
+                    && matches!(&p.kind, PaymentKind::Bolt11 { hash, .. } if hash.0 == *payment_hash)
+            })
+            .into_iter()
+            .next();
+        let expired = decode_invoice(&record.bolt11)?.expires_at <= crate::swap::unix_now();
+        // LDK raises PaymentClaimable only once every MPP part is in, with their total.
+        let received_msat = details
+            .as_ref()
+            .filter(|d| d.status == LdkPaymentStatus::Succeeded)
+            .and_then(|d| d.amount_msat)
+            .or(record.accepted_msat)
+            .unwrap_or(0);
+        let status = match details.map(|d| d.status) {
+            Some(LdkPaymentStatus::Succeeded) => InvoiceStatus::Settled,
+            Some(LdkPaymentStatus::Failed) => InvoiceStatus::Canceled,
+            _ if record.accepted_msat.is_some() => InvoiceStatus::Accepted,
+            _ if expired => InvoiceStatus::Canceled,
+            _ => InvoiceStatus::Open,
+        };
+        Ok(Some(InvoiceLookup {
+            bolt11: record.bolt11,
+            status,
+            received_msat,
+        }))
+    }
+
+    async fn pay_invoice(&self, bolt11: &str, max_fee_msat: u64, max_cltv_blocks: u32) -> Result<(), LnError> {
+        let invoice = Bolt11Invoice::from_str(bolt11.trim()).map_err(|e| LnError::Parse(e.to_string()))?;
+        let params = SendingParameters {
+            max_total_routing_fee_msat: Some(Some(max_fee_msat)),
+            max_total_cltv_expiry_delta: Some(max_cltv_blocks),
+            ..Default::default()
+        };
+        // Returns once the first attempt is underway; payment_status follows it.
+        self.node
+            .bolt11_payment()
+            .send(&invoice, Some(params))
+            .map_err(node_err)?;
+        Ok(())
+    }
+
+    async fn payment_status(&self, payment_hash: &[u8; 32]) -> Result<Option<PaymentStatus>, LnError> {
+        let outbound = self.node.list_payments_with_filter(|p| {
+            p.direction == PaymentDirection::Outbound
+                && matches!(&p.kind, PaymentKind::Bolt11 { hash, .. } if hash.0 == *payment_hash)
+        });
+        let Some(details) = outbound.into_iter().next() else {
+            return Ok(None);
+        };
+        Ok(Some(match details.status {
+            LdkPaymentStatus::Pending => PaymentStatus::InFlight,
+            LdkPaymentStatus::Failed => PaymentStatus::Failed {
+                reason: "payment failed".into(),
+            },
+            LdkPaymentStatus::Succeeded => {
+                let PaymentKind::Bolt11 {
+                    preimage: Some(preimage),
+                    ..
+                } = details.kind
+                else {
+                    return Err(LnError::Parse("succeeded payment without preimage".into()));
+                };
+                PaymentStatus::Succeeded {
+                    preimage: preimage.0,
+                    // ldk-node does not report the routing fee paid.
+                    fee_msat: 0,
+                }
+            }
+        }))
+    }
+
+    async fn channel_balance(&self) -> Result<ChannelBalance, LnError> {
+        let mut balance = ChannelBalance::default();
+        for c in self.node.list_channels().iter().filter(|c| c.is_usable) {
+            balance.local_msat += c.outbound_capacity_msat;
+            balance.remote_msat += c.inbound_capacity_msat;
+        }
+        Ok(balance)
+    }
+
+    async fn network(&self) -> Result<LnNetwork, LnError> {
+        LnNetwork::from_bitcoin(self.network)
+            .ok_or_else(|| LnError::Parse(format!("unsupported network {}", self.network)))
+    }
+
+    async fn watch_settlements(&self, settled: mpsc::Sender<[u8; 32]>) {
+        let mut events = self.settled.subscribe();
+        loop {
+            let hash = match events.recv().await {
+                Ok(hash) => hash,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            if settled.send(hash).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    fn supports_hold_invoices(&self) -> bool {
+        true
+    }
+
+    async fn create_hold_invoice(
+        &self,
+        amount_msat: u64,
+        payment_hash: &[u8; 32],
+        description: Description<'_>,
+        expiry_secs: u64,
+    ) -> Result<Invoice, LnError> {
+        self.receive(amount_msat, payment_hash, None, description, expiry_secs)
+    }
+
+    async fn settle_hold_invoice(&self, preimage: &[u8; 32]) -> Result<(), LnError> {
+        let hash = solana_sdk::hash::hash(preimage).to_bytes();
+        let mut record = self
+            .records()
+            .get(&hash)
+            .cloned()
+            .ok_or_else(|| LnError::Node("unknown hold invoice".into()))?;
+        let amount_msat = record
+            .accepted_msat
+            .ok_or_else(|| LnError::Node("hold invoice has not been paid".into()))?;
+        record.preimage = Some(*preimage);
+        self.save(&hash, &record)?;
+        self.claim(&hash, amount_msat, preimage)
+    }
+
+    async fn cancel_hold_invoice(&self, payment_hash: &[u8; 32]) -> Result<(), LnError> {
+        self.node
+            .bolt11_payment()
+            .fail_for_hash(PaymentHash(*payment_hash))
+            .map_err(node_err)
+    }
+}
@@ -0,0 +1,389 @@
+//! [`LnBackend`] over LND's gRPC API (`lnrpc`, `routerrpc` and `invoicesrpc` for hold invoices), authenticated with a macaroon over TLS. Unlike
+//! [`super::cli::CliBackend`] it streams invoice settlements, so the engine reacts without waiting for a poll.
+
+use std::{path::PathBuf, time::Duration};
+
+use tokio::sync::mpsc;
+use tonic_lnd::{
+    invoicesrpc,
+    lnrpc::{self, invoice::InvoiceState, payment::PaymentStatus as LndPaymentStatus, InvoiceHtlcState},
+    routerrpc, Client, InvoicesClient, LightningClient, RouterClient,
+};
+use tracing::{debug, warn};
+
+use super::{
+    decode_invoice, parse_network, ChannelBalance, Description, Invoice, InvoiceLookup, InvoiceStatus, Keysend,
+    LnBackend, LnError, PaymentStatus, Route, WalletTxStatus, SWAP_ID_RECORD,
+};
+use crate::network::LnNetwork;
+
+/// Wait before re-subscribing after the invoice stream drops.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+/// How long LND keeps trying routes for one payment.
+const PAYMENT_TIMEOUT_SECS: i32 = 60;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LndGrpcConfig {
+    /// `https://host:port` of LND's gRPC listener (`rpclisten`, 10009 by default).
+    pub address: String,
+    pub tls_cert: PathBuf,
+    pub macaroon: PathBuf,
+}
+
+#[derive(Clone)]
+pub struct LndGrpc {
+    lightning: LightningClient,
+    router: RouterClient,
+    invoices: InvoicesClient,
+}
+
+impl LndGrpc {
+    pub async fn connect(cfg: &LndGrpcConfig) -> Result<Self, LnError> {
+        let mut client: Client = tonic_lnd::connect(cfg.address.clone(), &cfg.tls_cert, &cfg.macaroon)
+            .await
+            .map_err(|e| LnError::Node(format!("connect to {}: {e}", cfg.address)))?;
+        Ok(Self {
+            lightning: client.lightning().clone(),
+            router: client.router().clone(),
+            invoices: client.invoices().clone(),
+        })
+    }
+}
+
+fn node_err(status: tonic_lnd::tonic::Status) -> LnError {
+    LnError::Node(status.message().to_string())
+}
+
+fn is_not_found(status: &tonic_lnd::tonic::Status) -> bool {
+    status.code() == tonic_lnd::tonic::Code::NotFound || status.message().contains("unable to locate invoice")
+}
+
+fn hash32(bytes: &[u8]) -> Result<[u8; 32], LnError> {
+    bytes.try_into().map_err(|_| LnError::Parse("expected 32 bytes".into()))
+}
+
+impl LnBackend for LndGrpc {
+    async fn create_invoice(
+        &self,
+        amount_msat: u64,
+        preimage: &[u8; 32],
+        description: Description<'_>,
+        expiry_secs: u64,
+    ) -> Result<Invoice, LnError> {
+        let request = lnrpc::Invoice {
+            memo: description
+                .hash()
+                .map_or_else(|| description.text().to_string(), |_| String::new()),
+            description_hash: description.hash().map(|h| h.to_vec()).unwrap_or_default(),
+            r_preimage: preimage.to_vec(),
+            value_msat: amount_msat as i64,
+            expiry: expiry_secs as i64,
+            ..Default::default()
+        };
+        let added = self
+            .lightning
+            .clone()
+            .add_invoice(request)
+            .await
+            .map_err(node_err)?
+            .into_inner();
+        let decoded = decode_invoice(&added.payment_request)?;
+        Ok(Invoice {
+            payment_hash: hash32(&added.r_hash)?,
+            expires_at: decoded.expires_at,
+            bolt11: added.payment_request,
+        })
+    }
+
+    async fn lookup_invoice(&self, payment_hash: &[u8; 32]) -> Result<Option<InvoiceLookup>, LnError> {
+        let request = lnrpc::PaymentHash {
+            r_hash: payment_hash.to_vec(),
+            ..Default::default()
+        };
+        let invoice = match self.lightning.clone().lookup_invoice(request).await {
+            Ok(response) => response.into_inner(),
+            Err(status) if is_not_found(&status) => return Ok(None),
+            Err(status) => return Err(node_err(status)),
+        };
+        let status = match InvoiceState::try_from(invoice.state) {
+            Ok(InvoiceState::Settled) => InvoiceStatus::Settled,
+            Ok(InvoiceState::Canceled) => InvoiceStatus::Canceled,
+            Ok(InvoiceState::Accepted) => InvoiceStatus::Accepted,
+            Ok(InvoiceState::Open) => InvoiceStatus::Open,
+            Err(_) => return Err(LnError::Parse(format!("invoice state {}", invoice.state))),
+        };
+        let received_msat = invoice
+            .htlcs
+            .iter()
+            .filter(|h| {
+                matches!(
+                    InvoiceHtlcState::try_from(h.state),
+                    Ok(InvoiceHtlcState::Accepted | InvoiceHtlcState::Settled)
+                )
+            })
+            .map(|h| h.amt_msat)
+            .sum();
+        Ok(Some(InvoiceLookup {
+            bolt11: invoice.payment_request,
+            status,
+            received_msat,
+        }))
+    }
+
+    async fn pay_invoice(&self, bolt11: &str, max_fee_msat: u64, max_cltv_blocks: u32) -> Result<(), LnError> {
+        let request = routerrpc::SendPaymentRequest {
+            payment_request: bolt11.to_string(),
+            fee_limit_msat: max_fee_msat as i64,
+            cltv_limit: max_cltv_blocks as i32,
+            timeout_seconds: PAYMENT_TIMEOUT_SECS,
+            no_inflight_updates: true,
+            ..Default::default()
+        };
+        let mut updates = self
+            .router
+            .clone()
+            .send_payment_v2(request)
+            .await
+            .map_err(node_err)?
+            .into_inner();
+        // The final update (succeeded or failed) closes the stream; payment_status reads the outcome.
+        while updates.message().await.map_err(node_err)?.is_some() {}
+        Ok(())
+    }
+
+    async fn payment_status(&self, payment_hash: &[u8; 32]) -> Result<Option<PaymentStatus>, LnError> {
+        let request = routerrpc::TrackPaymentRequest {
+            payment_hash: payment_hash.to_vec(),
+            no_inflight_updates: false,
+        };
+        let mut updates = match self.router.clone().track_payment_v2(request).await {
+            Ok(response) => response.into_inner(),
+            Err(status) if is_not_found(&status) => return Ok(None),
+            Err(status) => return Err(node_err(status)),
+        };
+        // The first update is the current state; later ones would block until it changes.
+        let payment = match updates.message().await {
+            Ok(Some(payment)) => payment,
+            Ok(None) => return Ok(None),
+            Err(status) if is_not_found(&status) => return Ok(None),
+            Err(status) => return Err(node_err(status)),
+        };
+        Ok(Some(match LndPaymentStatus::try_from(payment.status) {
+            Ok(LndPaymentStatus::Succeeded) => {
+                let preimage = hex::decode(&payment.payment_preimage)
+                    .map_err(|e| LnError::Parse(format!("payment preimage: {e}")))?;
+                PaymentStatus::Succeeded {
+                    preimage: hash32(&preimage)?,
+                    fee_msat: payment.fee_msat.max(0) as u64,
+                }
+            }
+            Ok(LndPaymentStatus::Failed) => PaymentStatus::Failed {
+                reason: format!("{:?}", payment.failure_reason()),
+            },
+            _ => PaymentStatus::InFlight,
+        }))
+    }
+
+    async fn channel_balance(&self) -> Result<ChannelBalance, LnError> {
+        let balance = self
+            .lightning
+            .clone()
+            .channel_balance(lnrpc::ChannelBalanceRequest {})
+            .await
+            .map_err(node_err)?
+            .into_inner();
+        Ok(ChannelBalance {
+            local_msat: balance.local_balance.map_or(0, |a| a.msat),
+            remote_msat: balance.remote_balance.map_or(0, |a| a.msat),
+        })
+    }
+
+    async fn network(&self) -> Result<LnNetwork, LnError> {
+        let info = self
+            .lightning
+            .clone()
+            .get_info(lnrpc::GetInfoRequest {})
+            .await
+            .map_err(node_err)?
+            .into_inner();
+        parse_network(info.chains.first().map(|chain| chain.network.as_str()))
+    }
+
+    async fn probe_route(
+        &self,
+        destination: &[u8; 33],
+        amount_msat: u64,
+        max_fee_msat: u64,
+    ) -> Result<Option<Route>, LnError> {
+        let request = lnrpc::QueryRoutesRequest {
+            pub_key: hex::encode(destination),
+            amt_msat: amount_msat as i64,
+            fee_limit: Some(lnrpc::FeeLimit {
+                limit: Some(lnrpc::fee_limit::Limit::FixedMsat(max_fee_msat as i64)),
+            }),
+            ..Default::default()
+        };
+        let routes = match self.lightning.clone().query_routes(request).await {
+            Ok(response) => response.into_inner().routes,
+            Err(status) if status.message().contains("unable to find a path") => return Ok(None),
+            Err(status) => return Err(node_err(status)),
+        };
+        Ok(routes.first().map(|route| Route {
+            fee_msat: route.total_fees_msat.max(0) as u64,
+            hops: route.hops.len() as u32,
+        }))
+    }
+
+    async fn watch_settlements(&self, settled: mpsc::Sender<[u8; 32]>) {
+        // Resuming from the last settle index replays settlements missed while disconnected.
+        let mut settle_index = 0;
+        while !settled.is_closed() {
+            let request = lnrpc::InvoiceSubscription {
+                add_index: 0,
+                settle_index,
+            };
+            let mut stream = match self.lightning.clone().subscribe_invoices(request).await {
+                Ok(response) => response.into_inner(),
+                Err(status) => {
+                    warn!(error = %status.message(), "invoice subscription failed; retrying");
+                    tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+                    continue;
+                }
+            };
+            loop {
+                let invoice = match stream.message().await {
+                    Ok(Some(invoice)) => invoice,
+                    Ok(None) => break,
+                    Err(status) => {
+                        warn!(error = %status.message(), "invoice stream dropped; resubscribing");
+                        break;
+                    }
+                };
+                if invoice.state != InvoiceState::Settled as i32 {
+                    continue;
+                }
+                settle_index = settle_index.max(invoice.settle_index);
+                let Ok(hash) = hash32(&invoice.r_hash) else {
+                    continue;
+                };
+                debug!(payment_hash = %hex::encode(hash), "lnd reported settlement");
+                if settled.send(hash).await.is_err() {
+                    return;
+                }
+            }
+            tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+        }
+    }
+
+    fn supports_hold_invoices(&self) -> bool {
+        true
+    }
+
+    async fn create_hold_invoice(
+        &self,
+        amount_msat: u64,
+        payment_hash: &[u8; 32],
+        description: Description<'_>,
+        expiry_secs: u64,
+    ) -> Result<Invoice, LnError> {
+        let request = invoicesrpc::AddHoldInvoiceRequest {
+            hash: payment_hash.to_vec(),
+            memo: description
+                .hash()
+                .map_or_else(|| description.text().to_string(), |_| String::new()),
+            description_hash: description.hash().map(|h| h.to_vec()).unwrap_or_default(),
+            value_msat: amount_msat as i64,
+            expiry: expiry_secs as i64,
+            ..Default::default()
+        };
+        let added = self
+            .invoices
+            .clone()
+            .add_hold_invoice(request)
+            .await
+            .map_err(node_err)?
+            .into_inner();
+        let decoded = decode_invoice(&added.payment_request)?;
+        Ok(Invoice {
+            payment_hash: decoded.payment_hash,
+            expires_at: decoded.expires_at,
+            bolt11: added.payment_request,
+        })
+    }
+
+    async fn settle_hold_invoice(&self, preimage: &[u8; 32]) -> Result<(), LnError> {
+        let request = invoicesrpc::SettleInvoiceMsg {
+            preimage: preimage.to_vec(),
+        };
+        self.invoices.clone().settle_invoice(request).await.map_err(node_err)?;
+        Ok(())
+    }
+
+    async fn cancel_hold_invoice(&self, payment_hash: &[u8; 32]) -> Result<(), LnError> {
+        let request = invoicesrpc::CancelInvoiceMsg {
+            payment_hash: payment_hash.to_vec(),
+        };
+        self.invoices.clone().cancel_invoice(request).await.map_err(node_err)?;
+        Ok(())
+    }
+
+    async fn keysends(&self, after: u64) -> Result<Vec<Keysend>, LnError> {
+        let request = lnrpc::ListInvoiceRequest {
+            index_offset: after,
+            num_max_invoices: 500,
+            ..Default::default()
+        };
+        let invoices = self
+            .lightning
+            .clone()
+            .list_invoices(request)
+            .await
+            .map_err(node_err)?
+            .into_inner()
+            .invoices;
+        let mut keysends = Vec::new();
+        for inv in invoices {
+            if !inv.is_keysend || inv.state != InvoiceState::Settled as i32 {
+                continue;
+            }
+            let Some(swap_id) = inv.htlcs.iter().find_map(|h| h.custom_records.get(&SWAP_ID_RECORD)) else {
+                continue;
+            };
+            keysends.push(Keysend {
+                payment_hash: hash32(&inv.r_hash)?,
+                preimage: hash32(&inv.r_preimage)?,
+                amount_msat: inv.amt_paid_msat.max(0) as u64,
+                swap_id: swap_id.clone(),
+                index: inv.add_index,
+            });
+        }
+        Ok(keysends)
+    }
+
+    async fn send_onchain(&self, address: &str, amount_sat: u64) -> Result<String, LnError> {
+        let request = lnrpc::SendCoinsRequest {
+            addr: address.to_string(),
+            amount: i64::try_from(amount_sat).map_err(|_| LnError::Node(format!("{amount_sat} sat is too much")))?,
+            ..Default::default()
+        };
+        let response = self.lightning.clone().send_coins(request).await.map_err(node_err)?;
+        Ok(response.into_inner().txid)
+    }
+
+    async fn wallet_tx(&self, txid: &str) -> Result<WalletTxStatus, LnError> {
+        // Down to the mempool.
+        let request = lnrpc::GetTransactionsRequest {
+            end_height: -1,
+            ..Default::default()
+        };
+        let txs = self
+            .lightning
+            .clone()
+            .get_transactions(request)
+            .await
+            .map_err(node_err)?
+            .into_inner();
+        let tx = txs.transactions.iter().find(|tx| tx.tx_hash == txid);
+        Ok(WalletTxStatus::of(tx.map(|tx| tx.num_confirmations.max(0) as u64)))
+    }
+}
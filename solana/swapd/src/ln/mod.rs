@@ -0,0 +1,324 @@
+//! The Lightning node operations the swap engine needs.
+
+pub mod cli;
+pub mod cln;
+#[cfg(feature = "eclair")]
+pub mod eclair;
+#[cfg(feature = "ldk")]
+pub mod ldk;
+#[cfg(feature = "lnd-grpc")]
+pub mod lnd;
+
+use std::{fmt, future::Future, str::FromStr};
+
+use lightning_invoice::Bolt11Invoice;
+use tokio::sync::mpsc;
+
+use crate::network::LnNetwork;
+
+/// What an invoice commits to in its description field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Description<'a> {
+    Text(&'a str),
+    /// Only the SHA-256 of this text goes into the invoice (`h` field), as LNURL-pay requires.
+    Hashed(&'a str),
+}
+
+impl Description<'_> {
+    pub fn text(&self) -> &str {
+        match self {
+            Self::Text(t) | Self::Hashed(t) => t,
+        }
+    }
+
+    pub fn hash(&self) -> Option<[u8; 32]> {
+        match self {
+            Self::Text(_) => None,
+            Self::Hashed(t) => Some(solana_sdk::hash::hash(t.as_bytes()).to_bytes()),
+        }
+    }
+}
+
+/// An invoice created on our node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invoice {
+    pub bolt11: String,
+    pub payment_hash: [u8; 32],
+    pub expires_at: i64,
+}
+
+/// The fields of a BOLT11 invoice the engine works with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedInvoice {
+    pub payment_hash: [u8; 32],
+    pub amount_msat: Option<u64>,
+    pub expires_at: i64,
+    /// Blocks the payee needs on the last hop; the route's total CLTV is at least this.
+    pub min_final_cltv_expiry_delta: u64,
+    /// Node id of the payee, compressed.
+    pub payee: [u8; 33],
+}
+
+pub fn decode_invoice(bolt11: &str) -> Result<DecodedInvoice, LnError> {
+    let invoice = Bolt11Invoice::from_str(bolt11.trim()).map_err(|e| LnError::Parse(e.to_string()))?;
+    let hash: &[u8] = invoice.payment_hash().as_ref();
+    let timestamp = invoice.duration_since_epoch().as_secs();
+    Ok(DecodedInvoice {
+        payment_hash: hash.try_into().map_err(|_| LnError::Parse("payment hash".into()))?,
+        amount_msat: invoice.amount_milli_satoshis(),
+        expires_at: timestamp.saturating_add(invoice.expiry_time().as_secs()) as i64,
+        min_final_cltv_expiry_delta: invoice.min_final_cltv_expiry_delta(),
+        payee: invoice.recover_payee_pub_key().serialize(),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvoiceStatus {
+    Open,
+    Settled,
+    /// A hold invoice whose HTLCs are locked in; it settles only once we release the preimage.
+    Accepted,
+    /// Expired or cancelled unpaid; it can no longer settle.
+    Canceled,
+    /// Some HTLC parts of a multi-part payment are in, but not the full amount. Never settle in this state;
+    /// see [`InvoiceLookup::status_for`].
+    Partial,
+}
+
+/// An invoice found on our node by payment hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvoiceLookup {
+    pub bolt11: String,
+    pub status: InvoiceStatus,
+    /// Sum of the HTLC parts currently held or settled.
+    pub received_msat: u64,
+}
+
+impl InvoiceLookup {
+    /// The node's status, counting parts against `amount_msat`: an open or accepted invoice whose parts don't
+    /// add up to the amount yet is [`InvoiceStatus::Partial`]. Nodes hold MPP parts until the set is complete,
+    /// so this only guards against a backend reporting a hold invoice accepted early.
+    pub fn status_for(&self, amount_msat: u64) -> InvoiceStatus {
+        match self.status {
+            InvoiceStatus::Open if self.received_msat > 0 => InvoiceStatus::Partial,
+            InvoiceStatus::Accepted if self.received_msat < amount_msat => InvoiceStatus::Partial,
+            status => status,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaymentStatus {
+    InFlight,
+    Succeeded {
+        preimage: [u8; 32],
+        fee_msat: u64,
+    },
+    /// Every attempt failed and none is outstanding; the invoice was not paid.
+    Failed {
+        reason: String,
+    },
+}
+
+/// Where a transaction sent from the node's on-chain wallet stands, as the wallet sees it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalletTxStatus {
+    Unconfirmed,
+    Confirmed,
+    /// No longer listed: replaced by a conflicting spend or abandoned, so its outputs will never exist.
+    Gone,
+}
+
+impl WalletTxStatus {
+    /// `confirmations` of a transaction the wallet lists, or `None` if it does not list it.
+    fn of(confirmations: Option<u64>) -> Self {
+        match confirmations {
+            None => Self::Gone,
+            Some(0) => Self::Unconfirmed,
+            Some(_) => Self::Confirmed,
+        }
+    }
+}
+
+/// Custom TLV record type in which a keysend payer puts the id of the swap quote it pays.
+pub const SWAP_ID_RECORD: u64 = 2_027_118_481;
+
+/// A settled keysend payment carrying [`SWAP_ID_RECORD`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keysend {
+    pub payment_hash: [u8; 32],
+    /// Chosen by the payer and revealed to us with the payment.
+    pub preimage: [u8; 32],
+    pub amount_msat: u64,
+    /// Value of the [`SWAP_ID_RECORD`] record.
+    pub swap_id: Vec<u8>,
+    /// Position in the node's invoice list; pass the highest seen to [`LnBackend::keysends`] to resume.
+    pub index: u64,
+}
+
+/// A route found by [`LnBackend::probe_route`]; nothing was sent along it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    /// Routing fees paying along it would cost.
+    pub fee_msat: u64,
+    pub hops: u32,
+}
+
+impl Route {
+    /// The fee in bps of `amount_msat`, rounded up.
+    pub fn fee_bps(&self, amount_msat: u64) -> u64 {
+        match amount_msat {
+            0 => 0,
+            amount => {
+                u64::try_from((u128::from(self.fee_msat) * 10_000).div_ceil(u128::from(amount))).unwrap_or(u64::MAX)
+            }
+        }
+    }
+}
+
+/// Balance across open channels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelBalance {
+    /// What we can send.
+    pub local_msat: u64,
+    /// What we can receive.
+    pub remote_msat: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LnError {
+    /// The node (or its CLI) returned an error.
+    Node(String),
+    /// The node answered with something we could not interpret.
+    Parse(String),
+    /// The backend does not implement this feature.
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for LnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Node(e) => write!(f, "lightning node error: {e}"),
+            Self::Parse(e) => write!(f, "unexpected lightning node response: {e}"),
+            Self::Unsupported(what) => write!(f, "this lightning backend does not support {what}"),
+        }
+    }
+}
+
+impl std::error::Error for LnError {}
+
+/// A network as the node names it, e.g. `bitcoin` or `regtest`.
+fn parse_network(name: Option<&str>) -> Result<LnNetwork, LnError> {
+    name.ok_or_else(|| LnError::Parse("node info without a network".into()))?
+        .parse()
+        .map_err(LnError::Parse)
+}
+
+/// A Lightning node. Lookups are by payment hash so every call is safe to repeat after a restart.
+pub trait LnBackend: Send + Sync + 'static {
+    /// Invoice for `amount_msat` settled by `preimage` (chosen by the daemon, so the escrow can be funded with
+    /// its hash before anything is handed to the user).
+    fn create_invoice(
+        &self,
+        amount_msat: u64,
+        preimage: &[u8; 32],
+        description: Description<'_>,
+        expiry_secs: u64,
+    ) -> impl Future<Output = Result<Invoice, LnError>> + Send;
+
+    /// `None` if the node has no invoice with this hash.
+    fn lookup_invoice(
+        &self,
+        payment_hash: &[u8; 32],
+    ) -> impl Future<Output = Result<Option<InvoiceLookup>, LnError>> + Send;
+
+    /// Pays `bolt11` over routes whose total CLTV delta is at most `max_cltv_blocks`, and returns once the
+    /// payment has settled or failed. Callers poll [`Self::payment_status`] rather than relying on the return
+    /// value, which is lost if the daemon restarts mid-payment.
+    fn pay_invoice(
+        &self,
+        bolt11: &str,
+        max_fee_msat: u64,
+        max_cltv_blocks: u32,
+    ) -> impl Future<Output = Result<(), LnError>> + Send;
+
+    /// `None` if the node never attempted a payment to this hash.
+    fn payment_status(
+        &self,
+        payment_hash: &[u8; 32],
+    ) -> impl Future<Output = Result<Option<PaymentStatus>, LnError>> + Send;
+
+    /// Totals over open channels, for liquidity checks and startup logs.
+    fn channel_balance(&self) -> impl Future<Output = Result<ChannelBalance, LnError>> + Send;
+
+    /// The Bitcoin network the node says it runs on, which startup checks against the Solana cluster.
+    fn network(&self) -> impl Future<Output = Result<LnNetwork, LnError>> + Send;
+
+    /// Sends the payment hash of every invoice that settles on our node to `settled` until the receiver is
+    /// dropped, reconnecting as needed. Backends without push notifications return at once and the engine
+    /// relies on polling alone.
+    fn watch_settlements(&self, settled: mpsc::Sender<[u8; 32]>) -> impl Future<Output = ()> + Send {
+        drop(settled);
+        async {}
+    }
+
+    /// Whether this backend implements the hold-invoice methods below.
+    fn supports_hold_invoices(&self) -> bool {
+        false
+    }
+
+    /// Hold invoice for `payment_hash`: the payer's HTLCs are accepted but only settled once we release the
+    /// preimage, so [`Self::lookup_invoice`] reports it `Accepted` when fully paid.
+    fn create_hold_invoice(
+        &self,
+        amount_msat: u64,
+        payment_hash: &[u8; 32],
+        description: Description<'_>,
+        expiry_secs: u64,
+    ) -> impl Future<Output = Result<Invoice, LnError>> + Send {
+        let _ = (amount_msat, payment_hash, description, expiry_secs);
+        async { Err(LnError::Unsupported("hold invoices")) }
+    }
+
+    fn settle_hold_invoice(&self, preimage: &[u8; 32]) -> impl Future<Output = Result<(), LnError>> + Send {
+        let _ = preimage;
+        async { Err(LnError::Unsupported("hold invoices")) }
+    }
+
+    /// Fails the held HTLCs back to the payer.
+    fn cancel_hold_invoice(&self, payment_hash: &[u8; 32]) -> impl Future<Output = Result<(), LnError>> + Send {
+        let _ = payment_hash;
+        async { Err(LnError::Unsupported("hold invoices")) }
+    }
+
+    /// Asks the node for a route delivering `amount_msat` to node `destination` for at most `max_fee_msat` in
+    /// fees, without sending anything. `None` when it knows of no such route. Route hints of private payees are
+    /// not considered.
+    fn probe_route(
+        &self,
+        destination: &[u8; 33],
+        amount_msat: u64,
+        max_fee_msat: u64,
+    ) -> impl Future<Output = Result<Option<Route>, LnError>> + Send {
+        let _ = (destination, amount_msat, max_fee_msat);
+        async { Err(LnError::Unsupported("route probing")) }
+    }
+
+    /// Settled keysend payments carrying [`SWAP_ID_RECORD`] with an index above `after`, oldest first.
+    fn keysends(&self, after: u64) -> impl Future<Output = Result<Vec<Keysend>, LnError>> + Send {
+        let _ = after;
+        async { Err(LnError::Unsupported("keysend swaps")) }
+    }
+
+    /// Pays `amount_sat` from the node's on-chain wallet to `address` and returns the txid.
+    fn send_onchain(&self, address: &str, amount_sat: u64) -> impl Future<Output = Result<String, LnError>> + Send {
+        let _ = (address, amount_sat);
+        async { Err(LnError::Unsupported("on-chain sends")) }
+    }
+
+    /// Where transaction `txid`, returned by [`Self::send_onchain`], stands in the node's wallet.
+    fn wallet_tx(&self, txid: &str) -> impl Future<Output = Result<WalletTxStatus, LnError>> + Send {
+        let _ = txid;
+        async { Err(LnError::Unsupported("on-chain sends")) }
+    }
+}
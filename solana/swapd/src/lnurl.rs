@@ -0,0 +1,196 @@
+//! LNURL-pay endpoint (LUD-06, LUD-16): "send sats, receive USDT". Each Solana address has its own pay link,
+//! `<public_url>/lnurlp/<address>` (or the Lightning address `<address>@<host>`). Paying it queues an
+//! LnToUsdt swap to that address, and the callback answers once the swap's invoice can be handed out: at once
+//! for a hold invoice, after the escrow is funded otherwise.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::SocketAddr,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    routing::get,
+    Json, Router,
+};
+use bech32::{ToBase32, Variant};
+use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+use crate::{
+    journal::Cause,
+    screening::{Screening, ScreeningError},
+    store::Store,
+    swap::{Swap, SwapState},
+};
+
+/// How often the callback re-reads the swap while waiting for its invoice.
+const INVOICE_POLL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone)]
+pub struct LnurlConfig {
+    pub listen: SocketAddr,
+    /// Externally reachable base URL, e.g. `https://swap.example.com`; callbacks are built from it.
+    pub public_url: String,
+    pub min_sendable_msat: u64,
+    pub max_sendable_msat: u64,
+    /// Token base units paid out per BTC received.
+    pub token_per_btc: u64,
+    /// Use hold invoices, so the callback answers without waiting for the escrow.
+    pub hold: bool,
+    /// Longest the callback waits for the invoice.
+    pub invoice_timeout: Duration,
+}
+
+impl LnurlConfig {
+    /// Net token amount for `amount_msat` at the configured rate.
+    pub fn token_amount(&self, amount_msat: u64) -> u64 {
+        (amount_msat as u128 * self.token_per_btc as u128 / 100_000_000_000) as u64
+    }
+
+    fn host(&self) -> &str {
+        let url = self
+            .public_url
+            .split_once("://")
+            .map_or(&*self.public_url, |(_, rest)| rest);
+        url.split('/').next().unwrap_or(url)
+    }
+
+    /// LNURL-pay metadata for `address`; its hash is what the invoice commits to, so it must be reproduced
+    /// byte for byte.
+    fn metadata(&self, address: &Pubkey) -> String {
+        json!([
+            ["text/plain", format!("Swap sats to USDT at {address}")],
+            ["text/identifier", format!("{address}@{}", self.host())],
+        ])
+        .to_string()
+    }
+}
+
+/// The bech32 `lnurl1…` string for `address`'s pay link.
+pub fn encode_lnurl(public_url: &str, address: &Pubkey) -> Result<String, bech32::Error> {
+    let url = format!("{}/lnurlp/{address}", public_url.trim_end_matches('/'));
+    Ok(bech32::encode("lnurl", url.as_bytes().to_base32(), Variant::Bech32)?.to_uppercase())
+}
+
+struct Server {
+    cfg: LnurlConfig,
+    store: Arc<Store>,
+    wake: Arc<Notify>,
+    screening: Arc<Screening>,
+}
+
+fn error(reason: impl Into<String>) -> Json<Value> {
+    Json(json!({ "status": "ERROR", "reason": reason.into() }))
+}
+
+async fn pay_request(State(server): State<Arc<Server>>, Path(address): Path<String>) -> Json<Value> {
+    let Ok(recipient) = Pubkey::from_str(&address) else {
+        return error("not a Solana address");
+    };
+    let cfg = &server.cfg;
+    Json(json!({
+        "tag": "payRequest",
+        "callback": format!("{}/lnurlp/{recipient}/callback", cfg.public_url.trim_end_matches('/')),
+        "minSendable": cfg.min_sendable_msat,
+        "maxSendable": cfg.max_sendable_msat,
+        "metadata": cfg.metadata(&recipient),
+    }))
+}
+
+async fn callback(
+    State(server): State<Arc<Server>>,
+    Path(address): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Json<Value> {
+    let cfg = &server.cfg;
+    let Ok(recipient) = Pubkey::from_str(&address) else {
+        return error("not a Solana address");
+    };
+    let Some(amount_msat) = query.get("amount").and_then(|a| a.parse::<u64>().ok()) else {
+        return error("missing amount");
+    };
+    if amount_msat < cfg.min_sendable_msat || amount_msat > cfg.max_sendable_msat {
+        return error(format!(
+            "amount must be between {} and {} msat",
+            cfg.min_sendable_msat, cfg.max_sendable_msat
+        ));
+    }
+    let token_amount = cfg.token_amount(amount_msat);
+    if token_amount == 0 {
+        return error("amount is too small");
+    }
+    let mut swap = Swap::ln_to_usdt(recipient, amount_msat, token_amount, cfg.hold);
+    swap.description = Some(cfg.metadata(&recipient));
+    match server.screening.check(&server.store, &swap, "lnurl") {
+        Ok(()) => {}
+        Err(ScreeningError::Denied(_)) => return error("payments to this address are not accepted"),
+        Err(e) => {
+            warn!(error = %e, "cannot screen lnurl swap");
+            return error("internal error");
+        }
+    }
+    match server.store.insert(&swap, &Cause::new("lnurl")) {
+        Ok(true) => {}
+        Ok(false) => return error("duplicate swap"),
+        Err(e) => {
+            warn!(error = %e, "cannot queue lnurl swap");
+            return error("internal error");
+        }
+    }
+    info!(swap = %swap.id, %recipient, amount_msat, token_amount, "lnurl swap queued");
+    server.wake.notify_one();
+
+    let started = Instant::now();
+    while started.elapsed() < cfg.invoice_timeout {
+        tokio::time::sleep(INVOICE_POLL).await;
+        let current = match server.store.get(&swap.id) {
+            Ok(Some(current)) => current,
+            Ok(None) => break,
+            Err(e) => {
+                warn!(swap = %swap.id, error = %e, "cannot read lnurl swap");
+                break;
+            }
+        };
+        if current.state == SwapState::Failed {
+            return error(current.error.unwrap_or_else(|| "swap failed".into()));
+        }
+        // to_json withholds the invoice until it is safe to pay.
+        if let Some(bolt11) = current.to_json()["bolt11"].as_str() {
+            return Json(json!({ "pr": bolt11, "routes": [] }));
+        }
+    }
+    error("timed out preparing the invoice; try again")
+}
+
+/// Serves the LNURL-pay routes on `cfg.listen` until `shutdown` resolves. Swaps pass `screening` before they
+/// are queued for the engine that owns `wake`.
+pub async fn serve(
+    cfg: LnurlConfig,
+    store: Arc<Store>,
+    wake: Arc<Notify>,
+    screening: Arc<Screening>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    let listen = cfg.listen;
+    let server = Arc::new(Server {
+        cfg,
+        store,
+        wake,
+        screening,
+    });
+    let app = Router::new()
+        .route("/.well-known/lnurlp/:address", get(pay_request))
+        .route("/lnurlp/:address", get(pay_request))
+        .route("/lnurlp/:address/callback", get(callback))
+        .with_state(server);
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    info!(%listen, "lnurl-pay listening");
+    axum::serve(listener, app).with_graceful_shutdown(shutdown).await
+}
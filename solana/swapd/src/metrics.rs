@@ -0,0 +1,344 @@
+//! Prometheus metrics, served at `/metrics` on `--metrics-listen`. Counters and histograms are bumped where
+//! things happen (the engine's transitions, Lightning payments, Solana sends, liquidity snapshots) in one
+//! process-wide [`Metrics`]; swap counts by state are read from the store at each scrape, and inventory is
+//! re-read from the node and chain every `inventory_interval`, so they hold across restarts. Each inventory
+//! refresh also audits the [`ledger`] against the balances read.
+
+use std::{future::Future, net::SocketAddr, sync::Arc, sync::OnceLock, time::Duration};
+
+use axum::{extract::State, http::header, response::IntoResponse, routing::get, Router};
+use intercom_swap_client::{
+    client::EscrowClient,
+    retry::{RetryEvent, SendOutcome},
+};
+use prometheus::{
+    exponential_buckets, Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use solana_sdk::pubkey::Pubkey;
+use tracing::{info, warn};
+
+use crate::{
+    exposure,
+    ledger::{self, Tolerance},
+    liquidity::Liquidity,
+    ln::LnBackend,
+    quote::Quote,
+    store::Store,
+    swap::{unix_now, Swap, SwapState},
+};
+
+pub struct Metrics {
+    registry: Registry,
+    /// Swaps per direction and state, refreshed from the store at each scrape.
+    pub swaps: IntGaugeVec,
+    /// State changes the engine made, by direction and new state.
+    pub transitions: IntCounterVec,
+    /// Seconds from queueing to completion, by direction.
+    pub settlement_seconds: HistogramVec,
+    /// Outgoing Lightning payments that failed, or whose call returned an error.
+    pub ln_payment_failures: IntCounter,
+    pub ln_routing_fees_msat: IntCounter,
+    /// Solana send retries, by kind: `rpc_error` or `resigned` (the blockhash expired unseen).
+    pub solana_tx_retries: IntCounterVec,
+    /// Solana sends by final outcome: `confirmed`, `failed`, `expired` or `pending`.
+    pub solana_txs: IntCounterVec,
+    pub channel_local_msat: IntGauge,
+    pub channel_remote_msat: IntGauge,
+    pub token_balance: IntGauge,
+    /// Lightning value in pending HTLCs: held hold-invoice payments and payments in flight. Refreshed from the
+    /// store at each scrape.
+    pub pending_htlc_msat: IntGauge,
+    /// Free liquidity net of active swaps and open quotes, by leg: `inbound_msat`, `outbound_msat`, `token`.
+    pub liquidity: IntGaugeVec,
+    /// Operator fees earned on completed quoted swaps, in token base units, by direction.
+    pub fee_revenue_tokens: IntCounterVec,
+    /// Compute unit price of the latest claim, in micro-lamports.
+    pub claim_compute_unit_price: IntGauge,
+    /// Claims sent as Jito bundles, by whether the block engine took them: `submitted` or `rejected`.
+    pub jito_bundles: IntCounterVec,
+    /// Control API calls refused by the rate limits, by action: `quote` or `create`.
+    pub api_rate_limited: IntCounterVec,
+    /// Signatures from a KMS or signing service, by outcome: `signed`, `invalid` or `failed`.
+    pub remote_signatures: IntCounterVec,
+    /// FROST signing sessions, by outcome: `signed`, `timed_out` or `failed`.
+    pub threshold_signatures: IntCounterVec,
+    /// Actual less ledger balance at the last audit, by account: `channels` (msat) or `wallet` (tokens).
+    pub ledger_drift: IntGaugeVec,
+    /// Scheduled database backups, by outcome: `stored` or `failed`.
+    pub backups: IntCounterVec,
+    /// Unix time of the last stored backup.
+    pub last_backup_timestamp: IntGauge,
+}
+
+/// The process's metrics.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("swapd".into()), None).expect("static prefix is valid");
+        let gauge_vec = |name: &str, help: &str, labels: &[&str]| {
+            let m = IntGaugeVec::new(Opts::new(name, help), labels).expect("static metric is valid");
+            registry.register(Box::new(m.clone())).expect("metric registered once");
+            m
+        };
+        let counter_vec = |name: &str, help: &str, labels: &[&str]| {
+            let m = IntCounterVec::new(Opts::new(name, help), labels).expect("static metric is valid");
+            registry.register(Box::new(m.clone())).expect("metric registered once");
+            m
+        };
+        let gauge = |name: &str, help: &str| {
+            let m = IntGauge::new(name, help).expect("static metric is valid");
+            registry.register(Box::new(m.clone())).expect("metric registered once");
+            m
+        };
+        let counter = |name: &str, help: &str| {
+            let m = IntCounter::new(name, help).expect("static metric is valid");
+            registry.register(Box::new(m.clone())).expect("metric registered once");
+            m
+        };
+        // 10s to ~11h.
+        let buckets = exponential_buckets(10.0, 2.0, 13).expect("static buckets are valid");
+        let settlement_seconds = HistogramVec::new(
+            HistogramOpts::new("swap_settlement_seconds", "Seconds from queueing to completion").buckets(buckets),
+            &["direction"],
+        )
+        .expect("static metric is valid");
+        registry
+            .register(Box::new(settlement_seconds.clone()))
+            .expect("metric registered once");
+        Self {
+            swaps: gauge_vec("swaps", "Swaps by direction and state", &["direction", "state"]),
+            transitions: counter_vec("swap_transitions_total", "Swap state changes", &["direction", "state"]),
+            settlement_seconds,
+            ln_payment_failures: counter("ln_payment_failures_total", "Failed outgoing Lightning payments"),
+            ln_routing_fees_msat: counter("ln_routing_fees_msat_total", "Routing fees paid on Lightning payments"),
+            solana_tx_retries: counter_vec("solana_tx_retries_total", "Solana transaction send retries", &["kind"]),
+            solana_txs: counter_vec("solana_txs_total", "Solana transactions by outcome", &["outcome"]),
+            channel_local_msat: gauge("channel_local_msat", "Lightning balance on our side of usable channels"),
+            channel_remote_msat: gauge("channel_remote_msat", "Lightning balance on the peers' side"),
+            token_balance: gauge("token_balance", "Operator token balance in base units"),
+            pending_htlc_msat: gauge("pending_htlc_msat", "Lightning value in pending HTLCs"),
+            liquidity: gauge_vec("liquidity", "Free liquidity net of commitments", &["leg"]),
+            fee_revenue_tokens: counter_vec(
+                "fee_revenue_tokens_total",
+                "Operator fees earned on completed swaps, in token base units",
+                &["direction"],
+            ),
+            claim_compute_unit_price: gauge("claim_compute_unit_price", "Compute unit price of the latest claim"),
+            jito_bundles: counter_vec("jito_bundles_total", "Claims sent as Jito bundles", &["outcome"]),
+            api_rate_limited: counter_vec(
+                "api_rate_limited_total",
+                "Control API calls refused by rate limits",
+                &["action"],
+            ),
+            remote_signatures: counter_vec(
+                "remote_signatures_total",
+                "Operator signatures requested from a KMS or signing service",
+                &["outcome"],
+            ),
+            threshold_signatures: counter_vec(
+                "threshold_signatures_total",
+                "FROST signing sessions for the operator key",
+                &["outcome"],
+            ),
+            ledger_drift: gauge_vec(
+                "ledger_drift",
+                "Actual balance less the ledger's, at the last audit",
+                &["account"],
+            ),
+            backups: counter_vec("backups_total", "Scheduled database backups", &["outcome"]),
+            last_backup_timestamp: gauge("last_backup_timestamp", "Unix time of the last stored backup"),
+            registry,
+        }
+    }
+
+    /// Counts `swap`'s move into its current state, and its settlement time and fee once completed. `quote` is
+    /// the quote it was accepted from, if any; swaps queued without one carry no recorded fee.
+    pub fn observe_transition(&self, swap: &Swap, quote: Option<&Quote>) {
+        let direction = swap.direction.as_str();
+        self.transitions
+            .with_label_values(&[direction, swap.state.as_str()])
+            .inc();
+        if swap.state != SwapState::Completed {
+            return;
+        }
+        let elapsed = unix_now().saturating_sub(swap.created_at).max(0);
+        self.settlement_seconds
+            .with_label_values(&[direction])
+            .observe(elapsed as f64);
+        if let Some(quote) = quote {
+            self.fee_revenue_tokens
+                .with_label_values(&[direction])
+                .inc_by(quote.fee_tokens());
+        }
+    }
+
+    pub fn observe_send_event(&self, event: &RetryEvent) {
+        let kind = match event {
+            RetryEvent::RpcError { .. } => "rpc_error",
+            RetryEvent::Resigned { .. } => "resigned",
+            RetryEvent::Confirm(_) => return,
+        };
+        self.solana_tx_retries.with_label_values(&[kind]).inc();
+    }
+
+    pub fn observe_send_outcome(&self, outcome: &SendOutcome) {
+        let outcome = match outcome {
+            SendOutcome::Confirmed { .. } => "confirmed",
+            SendOutcome::Failed { .. } | SendOutcome::Rejected { .. } => "failed",
+            SendOutcome::Expired { .. } => "expired",
+            SendOutcome::Pending { .. } => "pending",
+        };
+        self.solana_txs.with_label_values(&[outcome]).inc();
+    }
+
+    pub fn observe_balances(&self, local_msat: u64, remote_msat: u64, tokens: u64) {
+        self.channel_local_msat.set(clamp(local_msat));
+        self.channel_remote_msat.set(clamp(remote_msat));
+        self.token_balance.set(clamp(tokens));
+    }
+
+    pub fn observe_liquidity(&self, liquidity: &Liquidity) {
+        self.liquidity
+            .with_label_values(&["inbound_msat"])
+            .set(clamp(liquidity.inbound_msat));
+        self.liquidity
+            .with_label_values(&["outbound_msat"])
+            .set(clamp(liquidity.outbound_msat));
+        self.liquidity
+            .with_label_values(&["token"])
+            .set(clamp(liquidity.token_available));
+    }
+
+    /// The text exposition of every metric, with swap counts and pending HTLCs read from `store` first.
+    fn render(&self, store: &Store) -> Result<String, String> {
+        let counts = store.state_counts().map_err(|e| e.to_string())?;
+        self.swaps.reset();
+        for (direction, state, count) in counts {
+            self.swaps
+                .with_label_values(&[direction.as_str(), state.as_str()])
+                .set(clamp(count));
+        }
+        let active = store.active().map_err(|e| e.to_string())?;
+        self.pending_htlc_msat.set(clamp(exposure::pending_htlc_msat(&active)));
+        let mut out = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut out)
+            .map_err(|e| e.to_string())?;
+        String::from_utf8(out).map_err(|e| e.to_string())
+    }
+}
+
+fn clamp(value: u64) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricsConfig {
+    pub listen: SocketAddr,
+    /// How often channel, token and free liquidity gauges are re-read.
+    pub inventory_interval: Duration,
+}
+
+/// What inventory is read against.
+pub struct Inventory {
+    pub client: EscrowClient,
+    pub operator: Pubkey,
+    pub mint: Pubkey,
+    /// Routing fee budget reserved on outbound liquidity, as when quoting.
+    pub routing_fee_bps: u16,
+    /// Drift the ledger audit tolerates before alerting.
+    pub ledger_tolerance: Tolerance,
+}
+
+pub struct MetricsServer {
+    cfg: MetricsConfig,
+    store: Arc<Store>,
+    inventory: Inventory,
+}
+
+async fn scrape(State(store): State<Arc<Store>>) -> impl IntoResponse {
+    match metrics().render(&store) {
+        Ok(text) => (
+            axum::http::StatusCode::OK,
+            [(header::CONTENT_TYPE, TextEncoder::new().format_type().to_string())],
+            text,
+        ),
+        Err(e) => {
+            warn!(error = %e, "cannot render metrics");
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                [(header::CONTENT_TYPE, "text/plain".to_string())],
+                e,
+            )
+        }
+    }
+}
+
+impl MetricsServer {
+    pub fn new(cfg: MetricsConfig, store: Arc<Store>, inventory: Inventory) -> Self {
+        Self { cfg, store, inventory }
+    }
+
+    /// Serves `/metrics` until `shutdown` resolves, refreshing inventory from `ln` when there is a node.
+    pub async fn run<L: LnBackend>(
+        &self,
+        ln: Option<&L>,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> std::io::Result<()> {
+        let app = Router::new()
+            .route("/metrics", get(scrape))
+            .with_state(self.store.clone());
+        let listener = tokio::net::TcpListener::bind(self.cfg.listen).await?;
+        info!(listen = %self.cfg.listen, "metrics listening");
+        let server = axum::serve(listener, app).with_graceful_shutdown(shutdown);
+        tokio::pin!(server);
+        let mut interval = tokio::time::interval(self.cfg.inventory_interval);
+        loop {
+            tokio::select! {
+                served = &mut server => return served,
+                _ = interval.tick(), if ln.is_some() => {
+                    if let Some(ln) = ln {
+                        self.refresh_inventory(ln).await;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn refresh_inventory<L: LnBackend>(&self, ln: &L) {
+        let inv = &self.inventory;
+        // The snapshot records the balances and free liquidity itself.
+        if let Err(e) = Liquidity::snapshot(
+            ln,
+            &inv.client,
+            &self.store,
+            &inv.operator,
+            &inv.mint,
+            inv.routing_fee_bps,
+        )
+        .await
+        {
+            warn!(error = %e, "cannot refresh inventory metrics");
+        }
+        let balances = async {
+            let channels = ln.channel_balance().await.map_err(|e| e.to_string())?;
+            let tokens = inv
+                .client
+                .get_token_balance(&inv.operator, &inv.mint)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok::<_, String>((channels.local_msat, tokens))
+        };
+        let audited = balances.await.and_then(|(local_msat, tokens)| {
+            ledger::audit(&self.store, local_msat, tokens, inv.ledger_tolerance).map_err(|e| e.to_string())
+        });
+        if let Err(e) = audited {
+            warn!(error = %e, "cannot audit the ledger");
+        }
+    }
+}
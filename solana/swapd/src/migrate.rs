@@ -0,0 +1,54 @@
+//! Versioned schema migrations, compiled into the binary and applied forward-only when a database is opened.
+//! Each backend keeps its own list ([`Migration`]s numbered from 1) and records what it applied in a
+//! `schema_migrations` table. A database already past the newest version this binary knows was written by a
+//! later release, and is refused rather than run against.
+
+use std::fmt;
+
+/// One schema change. Shipped migrations are never edited; a later change is a new one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// The database was migrated by a newer release.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NewerSchema {
+    pub found: u32,
+    pub supported: u32,
+}
+
+impl fmt::Display for NewerSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "database schema is at version {}, newer than the {} this release supports; run a newer swapd",
+            self.found, self.supported
+        )
+    }
+}
+
+impl std::error::Error for NewerSchema {}
+
+/// The newest version in `migrations`; 0 when empty.
+pub fn latest(migrations: &[Migration]) -> u32 {
+    migrations.last().map_or(0, |m| m.version)
+}
+
+/// The migrations to apply, in order, to a database at `current`.
+pub fn pending(migrations: &'static [Migration], current: u32) -> Result<&'static [Migration], NewerSchema> {
+    debug_assert!(
+        migrations.iter().enumerate().all(|(i, m)| m.version as usize == i + 1),
+        "migrations must be numbered 1, 2, 3, …"
+    );
+    let supported = latest(migrations);
+    if current > supported {
+        return Err(NewerSchema {
+            found: current,
+            supported,
+        });
+    }
+    Ok(&migrations[current as usize..])
+}
@@ -0,0 +1,740 @@
+//! Maker side of peer-to-peer swap negotiation, independent of how the messages travel. The maker advertises
+//! an [`Offer`] per direction; a taker answers with a `take` naming an amount and its Solana key (and, for
+//! usdt-to-ln, its invoice). The [`Negotiator`] quotes and accepts that in one step, queues the swap for the
+//! engine and answers with the signed quote and the swap. From then on the peer is sent the swap whenever its
+//! state changes, which is how an ln-to-usdt taker learns the invoice once it is safe to pay. A [`Maker`] adds
+//! the pricing and liquidity sizing every transport shares.
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use intercom_swap_client::{client::EscrowClient, fees::FeeBounds};
+use serde_json::{json, Value};
+use solana_sdk::{pubkey::Pubkey, signature::Signature, signer::Signer};
+use tokio::sync::{watch, Notify};
+use tracing::{debug, info, warn};
+
+use crate::{
+    exposure::ExposureLimits,
+    feepolicy::{self, FeeInputs, FeePolicy, ROUTING_FEE_SAMPLE},
+    kms::Operator,
+    liquidity::Liquidity,
+    ln::{self, LnBackend},
+    quote::{self, Quote, QuoteConfig, QuoteError, QuoteRequest, Quoter},
+    rates::{Oracle, Rate},
+    reputation::ReputationPolicy,
+    screening::Screening,
+    store::{Store, StoreError},
+    swap::{unix_now, Direction, SwapState},
+};
+
+/// Prefix of every signed offer message, so the signature cannot be replayed as anything else.
+const OFFER_DOMAIN: &[u8] = b"intercom-swap/offer/v2";
+
+#[derive(Debug)]
+pub enum NegotiateError {
+    Malformed(String),
+    /// The maker has no price for the direction right now.
+    NoPrice(Direction),
+    Quote(QuoteError),
+    /// A status request for a swap this peer did not negotiate.
+    UnknownSwap(String),
+    Store(StoreError),
+}
+
+impl fmt::Display for NegotiateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(e) => write!(f, "malformed message: {e}"),
+            Self::NoPrice(direction) => write!(f, "no {direction} offer right now"),
+            Self::Quote(e) => write!(f, "{e}"),
+            Self::UnknownSwap(id) => write!(f, "unknown swap {id}"),
+            Self::Store(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for NegotiateError {}
+
+impl From<QuoteError> for NegotiateError {
+    fn from(e: QuoteError) -> Self {
+        Self::Quote(e)
+    }
+}
+
+impl From<StoreError> for NegotiateError {
+    fn from(e: StoreError) -> Self {
+        Self::Store(e)
+    }
+}
+
+/// Standing terms for one direction, as published to takers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Offer {
+    pub direction: Direction,
+    /// Key that signs the offer and its quotes and, for usdt-to-ln, the recipient of the taker's escrow.
+    pub operator: Pubkey,
+    pub mint: Pubkey,
+    pub token_per_btc: u64,
+    pub fee_bps: u16,
+    /// Token base units charged per swap on top of `fee_bps`.
+    pub flat_fee: u64,
+    pub min_amount_msat: u64,
+    /// Already capped by free liquidity when the maker checks it.
+    pub max_amount_msat: u64,
+    pub expires_at: i64,
+    pub signature: Signature,
+}
+
+impl Offer {
+    /// Terms at `cfg`, capped by `liquidity` and signed by `operator`.
+    pub fn new(
+        cfg: &QuoteConfig,
+        direction: Direction,
+        operator: &Operator,
+        liquidity: Option<&Liquidity>,
+        expires_at: i64,
+    ) -> Self {
+        let max_amount_msat = liquidity.map_or(cfg.max_amount_msat, |l| {
+            cfg.max_amount_msat.min(l.max_amount_msat(direction, cfg))
+        });
+        let mut offer = Self {
+            direction,
+            operator: operator.pubkey(),
+            mint: cfg.mint,
+            token_per_btc: cfg.token_per_btc,
+            fee_bps: cfg.fee_bps,
+            flat_fee: cfg.flat_fee,
+            min_amount_msat: cfg.min_amount_msat,
+            max_amount_msat,
+            expires_at,
+            signature: Signature::default(),
+        };
+        offer.signature = operator.sign_message(&offer.message());
+        offer
+    }
+
+    /// Bytes the operator signs: every term, fixed width, after [`OFFER_DOMAIN`].
+    pub fn message(&self) -> Vec<u8> {
+        let mut m = Vec::with_capacity(OFFER_DOMAIN.len() + 118);
+        m.extend_from_slice(OFFER_DOMAIN);
+        m.push(match self.direction {
+            Direction::LnToUsdt => 0,
+            Direction::UsdtToLn => 1,
+        });
+        m.extend_from_slice(self.operator.as_ref());
+        m.extend_from_slice(self.mint.as_ref());
+        m.extend_from_slice(&self.token_per_btc.to_le_bytes());
+        m.extend_from_slice(&self.fee_bps.to_le_bytes());
+        m.extend_from_slice(&self.flat_fee.to_le_bytes());
+        m.extend_from_slice(&self.min_amount_msat.to_le_bytes());
+        m.extend_from_slice(&self.max_amount_msat.to_le_bytes());
+        m.extend_from_slice(&self.expires_at.to_le_bytes());
+        m
+    }
+
+    /// Checks the signature against the offer's own operator key and that the offer is live at `now`.
+    pub fn verify(&self, now: i64) -> Result<(), QuoteError> {
+        if !self.signature.verify(self.operator.as_ref(), &self.message()) {
+            return Err(QuoteError::BadSignature);
+        }
+        if now >= self.expires_at {
+            return Err(QuoteError::Expired {
+                expires_at: self.expires_at,
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether any amount can be taken at all.
+    pub fn is_fillable(&self) -> bool {
+        self.max_amount_msat >= self.min_amount_msat
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "direction": self.direction.as_str(),
+            "operator": self.operator.to_string(),
+            "mint": self.mint.to_string(),
+            "tokenPerBtc": self.token_per_btc,
+            "feeBps": self.fee_bps,
+            "flatFee": self.flat_fee,
+            "minAmountMsat": self.min_amount_msat,
+            "maxAmountMsat": self.max_amount_msat,
+            "expiresAt": self.expires_at,
+            "signature": self.signature.to_string(),
+        })
+    }
+
+    pub fn from_json(v: &Value) -> Result<Self, NegotiateError> {
+        let malformed = |field: &str| NegotiateError::Malformed(format!("missing or invalid offer {field}"));
+        let parsed = |field: &str| v[field].as_str().ok_or_else(|| malformed(field));
+        let u64_field = |field: &str| v[field].as_u64().ok_or_else(|| malformed(field));
+        Ok(Self {
+            direction: parsed("direction")?.parse().map_err(|_| malformed("direction"))?,
+            operator: parsed("operator")?.parse().map_err(|_| malformed("operator"))?,
+            mint: parsed("mint")?.parse().map_err(|_| malformed("mint"))?,
+            token_per_btc: u64_field("tokenPerBtc")?,
+            fee_bps: u16::try_from(u64_field("feeBps")?).map_err(|_| malformed("feeBps"))?,
+            flat_fee: u64_field("flatFee")?,
+            min_amount_msat: u64_field("minAmountMsat")?,
+            max_amount_msat: u64_field("maxAmountMsat")?,
+            expires_at: v["expiresAt"].as_i64().ok_or_else(|| malformed("expiresAt"))?,
+            signature: parsed("signature")?.parse().map_err(|_| malformed("signature"))?,
+        })
+    }
+}
+
+/// What a taker sends the maker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    Take(Take),
+    /// The current state of a swap this peer negotiated.
+    Status {
+        swap_id: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Take {
+    pub direction: Direction,
+    pub amount_msat: u64,
+    /// USDT recipient (ln-to-usdt) or refund key of the taker's escrow (usdt-to-ln).
+    pub counterparty: Pubkey,
+    /// usdt-to-ln: the invoice the maker pays, for exactly `amount_msat`.
+    pub invoice: Option<String>,
+}
+
+impl Request {
+    pub fn from_json(v: &Value) -> Result<Self, NegotiateError> {
+        let malformed = |field: &str| NegotiateError::Malformed(format!("missing or invalid {field}"));
+        match v["type"].as_str() {
+            Some("take") => Ok(Self::Take(Take {
+                direction: v["direction"]
+                    .as_str()
+                    .and_then(|d| d.parse().ok())
+                    .ok_or_else(|| malformed("direction"))?,
+                amount_msat: v["amountMsat"].as_u64().ok_or_else(|| malformed("amountMsat"))?,
+                counterparty: v["counterparty"]
+                    .as_str()
+                    .and_then(|c| c.parse().ok())
+                    .ok_or_else(|| malformed("counterparty"))?,
+                invoice: v["invoice"].as_str().map(str::to_string),
+            })),
+            Some("status") => Ok(Self::Status {
+                swap_id: v["swapId"].as_str().ok_or_else(|| malformed("swapId"))?.to_string(),
+            }),
+            _ => Err(malformed("type")),
+        }
+    }
+}
+
+/// The reply for a request that could not be served.
+pub fn error_reply(e: &NegotiateError) -> Value {
+    json!({ "type": "error", "message": e.to_string() })
+}
+
+/// A swap negotiated with a peer over some transport, and the last state the peer was told about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerSession {
+    pub swap_id: String,
+    /// Transport name, e.g. `nostr`.
+    pub transport: String,
+    /// The peer's address on that transport.
+    pub peer: String,
+    pub notified_state: Option<String>,
+}
+
+/// A message owed to a peer because its swap moved.
+#[derive(Debug, Clone)]
+pub struct Update {
+    pub session: PeerSession,
+    pub state: SwapState,
+    pub message: Value,
+}
+
+/// Serves negotiation requests arriving over one transport.
+pub struct Negotiator {
+    pub transport: &'static str,
+    pub store: Arc<Store>,
+    pub operator: Arc<Operator>,
+    /// Issue hold invoices for ln-to-usdt swaps.
+    pub hold: bool,
+    pub funding_timeout_secs: i64,
+    /// Run on every swap before it is queued.
+    pub screening: Arc<Screening>,
+}
+
+impl Negotiator {
+    /// Quotes and accepts `take` from `peer` at `cfg`, priced by `terms`' fee policy plus any probed routing fee,
+    /// queuing its swap. The caller wakes the engine.
+    #[allow(clippy::too_many_arguments)]
+    pub fn take(
+        &self,
+        peer: &str,
+        take: &Take,
+        cfg: QuoteConfig,
+        liquidity: Option<Liquidity>,
+        terms: &Terms,
+        compute_unit_price_micro_lamports: u64,
+        probed_routing_fee_bps: Option<u64>,
+    ) -> Result<Value, NegotiateError> {
+        let quoter = Quoter {
+            cfg,
+            operator: &self.operator,
+            store: &self.store,
+            liquidity,
+            reputation: terms.reputation,
+            limits: terms.exposure,
+            fees: terms.fees.clone(),
+            compute_unit_price_micro_lamports,
+            probed_routing_fee_bps,
+        };
+        let quote = quoter.quote(&QuoteRequest {
+            direction: take.direction,
+            amount_msat: take.amount_msat,
+            counterparty: take.counterparty,
+            allow_partial: false,
+            destination: None,
+        })?;
+        let swap = quote::accept(
+            &self.store,
+            &self.operator.pubkey(),
+            &quote,
+            take.invoice.as_deref(),
+            self.hold,
+            self.funding_timeout_secs,
+            &self.screening,
+            self.transport,
+        )?;
+        self.store.insert_peer_session(&PeerSession {
+            swap_id: swap.id.clone(),
+            transport: self.transport.to_string(),
+            peer: peer.to_string(),
+            notified_state: Some(swap.state.as_str().to_string()),
+        })?;
+        Ok(json!({
+            "type": "accepted",
+            "operator": self.operator.pubkey().to_string(),
+            "quote": quote.to_json(),
+            "swap": swap.to_json(),
+        }))
+    }
+
+    /// The swap `swap_id`, if `peer` negotiated it here.
+    pub fn status(&self, peer: &str, swap_id: &str) -> Result<Value, NegotiateError> {
+        let unknown = || NegotiateError::UnknownSwap(swap_id.to_string());
+        let session = self.store.peer_session(swap_id)?.ok_or_else(unknown)?;
+        if session.transport != self.transport || session.peer != peer {
+            return Err(unknown());
+        }
+        let swap = self.store.get(swap_id)?.ok_or_else(unknown)?;
+        Ok(json!({ "type": "swap", "swap": swap.to_json() }))
+    }
+
+    /// Swaps whose state changed since their peer was last told.
+    pub fn updates(&self) -> Result<Vec<Update>, StoreError> {
+        let mut updates = Vec::new();
+        for session in self.store.peer_sessions(self.transport)? {
+            let Some(swap) = self.store.get(&session.swap_id)? else {
+                continue;
+            };
+            if session.notified_state.as_deref() != Some(swap.state.as_str()) {
+                updates.push(Update {
+                    state: swap.state,
+                    message: json!({ "type": "swap", "swap": swap.to_json() }),
+                    session,
+                });
+            }
+        }
+        Ok(updates)
+    }
+
+    /// Records that `update` reached its peer.
+    pub fn delivered(&self, update: &Update) -> Result<(), StoreError> {
+        self.store
+            .set_peer_notified(&update.session.swap_id, update.state.as_str())
+    }
+}
+
+/// Pricing and sizing of offers, shared by every transport.
+#[derive(Clone)]
+pub struct MakerConfig {
+    pub directions: Vec<Direction>,
+    /// Terms of every offer; `token_per_btc` is only used without an oracle.
+    pub quote: QuoteConfig,
+    /// Prices each direction at its spread when set.
+    pub oracle: Option<Oracle>,
+    /// How often offers are refreshed (price and liquidity); they expire after `offer_ttl`.
+    pub republish: Duration,
+    pub offer_ttl: Duration,
+    /// How often negotiated swaps are checked for state changes to report.
+    pub update_interval: Duration,
+    /// Size offers and takes against free liquidity.
+    pub check_liquidity: bool,
+    /// usdt-to-ln: probe the route to the payee before quoting, refusing when the node finds none within the
+    /// routing fee budget and charging the fee of the one it finds.
+    pub probe_routes: bool,
+}
+
+/// Offer terms that can change while the daemon runs (see [`crate::config`]); they override their
+/// [`MakerConfig`] counterparts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Terms {
+    pub fee_bps: u16,
+    /// Token base units charged per swap on top of `fee_bps`.
+    pub flat_fee: u64,
+    /// Oracle spread around the median.
+    pub spread_bps: u16,
+    pub min_amount_msat: u64,
+    pub max_amount_msat: u64,
+    /// Overrides of the above for each direction, whose liquidity costs differ.
+    pub ln_to_usdt: DirectionTerms,
+    pub usdt_to_ln: DirectionTerms,
+    /// `None` quotes any counterparty.
+    pub reputation: Option<ReputationPolicy>,
+    pub exposure: ExposureLimits,
+    /// How each quote's fee moves off `fee_bps`.
+    pub fees: FeePolicy,
+}
+
+impl Terms {
+    /// These terms with `direction`'s overrides applied.
+    pub fn for_direction(&self, direction: Direction) -> Self {
+        let overrides = match direction {
+            Direction::LnToUsdt => self.ln_to_usdt,
+            Direction::UsdtToLn => self.usdt_to_ln,
+        };
+        Self {
+            fee_bps: overrides.fee_bps.unwrap_or(self.fee_bps),
+            flat_fee: overrides.flat_fee.unwrap_or(self.flat_fee),
+            spread_bps: overrides.spread_bps.unwrap_or(self.spread_bps),
+            min_amount_msat: overrides.min_amount_msat.unwrap_or(self.min_amount_msat),
+            max_amount_msat: overrides.max_amount_msat.unwrap_or(self.max_amount_msat),
+            ln_to_usdt: DirectionTerms::default(),
+            usdt_to_ln: DirectionTerms::default(),
+            ..self.clone()
+        }
+    }
+}
+
+/// [`Terms`] that differ for one direction; `None` keeps the shared value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirectionTerms {
+    pub fee_bps: Option<u16>,
+    pub flat_fee: Option<u64>,
+    pub spread_bps: Option<u16>,
+    pub min_amount_msat: Option<u64>,
+    pub max_amount_msat: Option<u64>,
+}
+
+/// A [`Negotiator`] that prices and sizes what it offers; each transport runs one.
+pub struct Maker {
+    pub cfg: MakerConfig,
+    pub negotiator: Negotiator,
+    client: EscrowClient,
+    terms: watch::Receiver<Terms>,
+}
+
+impl Maker {
+    /// A maker whose terms stay as they are: `cfg`'s, quoting counterparties under `reputation` and
+    /// `exposure` at fees set by `fees`.
+    pub fn new(
+        cfg: MakerConfig,
+        negotiator: Negotiator,
+        client: EscrowClient,
+        reputation: Option<ReputationPolicy>,
+        exposure: ExposureLimits,
+        fees: FeePolicy,
+    ) -> Self {
+        let terms = Terms {
+            fee_bps: cfg.quote.fee_bps,
+            flat_fee: cfg.quote.flat_fee,
+            spread_bps: cfg.oracle.as_ref().map_or(0, |o| o.cfg.spread_bps),
+            min_amount_msat: cfg.quote.min_amount_msat,
+            max_amount_msat: cfg.quote.max_amount_msat,
+            ln_to_usdt: DirectionTerms::default(),
+            usdt_to_ln: DirectionTerms::default(),
+            reputation,
+            exposure,
+            fees,
+        };
+        Self::with_terms(cfg, negotiator, client, watch::channel(terms).1)
+    }
+
+    /// A maker that follows the terms published on `terms`.
+    pub fn with_terms(
+        cfg: MakerConfig,
+        negotiator: Negotiator,
+        client: EscrowClient,
+        terms: watch::Receiver<Terms>,
+    ) -> Self {
+        Self {
+            cfg,
+            negotiator,
+            client,
+            terms,
+        }
+    }
+
+    /// The terms in force now.
+    pub fn terms(&self) -> Terms {
+        self.terms.borrow().clone()
+    }
+
+    /// The oracle's current rate, if one is configured and answers.
+    pub async fn price(&self) -> Option<Rate> {
+        let oracle = self.cfg.oracle.as_ref()?;
+        match oracle.rate().await {
+            Ok(rate) => Some(rate),
+            Err(e) => {
+                warn!(transport = self.negotiator.transport, error = %e, "cannot price offers");
+                None
+            }
+        }
+    }
+
+    /// Mid-market token base units per BTC: the oracle's median without spread, or the fixed rate without an
+    /// oracle.
+    pub async fn mark_token_per_btc(&self) -> Option<u64> {
+        if self.cfg.oracle.is_none() {
+            return Some(self.cfg.quote.token_per_btc);
+        }
+        let mut rate = self.price().await?;
+        rate.cfg.spread_bps = 0;
+        Some(rate.token_per_btc(Direction::LnToUsdt))
+    }
+
+    /// Quote terms for `direction` under the current [`Terms`], or `None` when the oracle has no price.
+    fn quote_cfg(&self, direction: Direction, rate: Option<Rate>) -> Option<QuoteConfig> {
+        let terms = self.terms().for_direction(direction);
+        let token_per_btc = match &self.cfg.oracle {
+            Some(_) => {
+                let mut rate = rate?;
+                rate.cfg.spread_bps = terms.spread_bps;
+                rate.token_per_btc(direction)
+            }
+            None => self.cfg.quote.token_per_btc,
+        };
+        Some(QuoteConfig {
+            token_per_btc,
+            fee_bps: terms.fee_bps,
+            flat_fee: terms.flat_fee,
+            min_amount_msat: terms.min_amount_msat,
+            max_amount_msat: terms.max_amount_msat,
+            ..self.cfg.quote
+        })
+    }
+
+    /// What Solana transactions pay per compute unit now, when `fees` charges for it; 0 otherwise.
+    async fn compute_unit_price(&self, fees: &FeePolicy) -> u64 {
+        if !fees.needs_compute_unit_price() {
+            return 0;
+        }
+        match feepolicy::compute_unit_price(&self.client).await {
+            Ok(price) => price,
+            Err(e) => {
+                let floor = FeeBounds::default().min_micro_lamports;
+                warn!(error = %e, floor, "no recent prioritization fees; charging the floor");
+                floor
+            }
+        }
+    }
+
+    async fn liquidity<L: LnBackend>(&self, ln: &L) -> Option<Liquidity> {
+        if !self.cfg.check_liquidity {
+            return None;
+        }
+        let store = &self.negotiator.store;
+        let operator = self.negotiator.operator.pubkey();
+        let fee_bps = self.cfg.quote.max_routing_fee_bps;
+        match Liquidity::snapshot(ln, &self.client, store, &operator, &self.cfg.quote.mint, fee_bps).await {
+            Ok(liquidity) => Some(liquidity),
+            Err(e) => {
+                warn!(error = %e, "cannot read liquidity; offering nothing");
+                Some(Liquidity::default())
+            }
+        }
+    }
+
+    /// usdt-to-ln, when probing: the fee in bps of `amount_msat` of a route paying it to `destination` within
+    /// `cfg`'s routing fee budget. `None` when there is nothing to probe.
+    async fn probe<L: LnBackend>(
+        &self,
+        ln: &L,
+        cfg: &QuoteConfig,
+        direction: Direction,
+        destination: Option<&[u8; 33]>,
+        amount_msat: u64,
+    ) -> Result<Option<u64>, QuoteError> {
+        if !self.cfg.probe_routes || direction != Direction::UsdtToLn {
+            return Ok(None);
+        }
+        let Some(destination) = destination else {
+            return Ok(None);
+        };
+        let max_fee_msat = u128::from(amount_msat) * u128::from(cfg.max_routing_fee_bps) / 10_000;
+        let max_fee_msat = u64::try_from(max_fee_msat).unwrap_or(u64::MAX);
+        let payee = hex::encode(destination);
+        match ln.probe_route(destination, amount_msat, max_fee_msat).await {
+            Ok(Some(route)) => {
+                debug!(
+                    payee,
+                    amount_msat,
+                    fee_msat = route.fee_msat,
+                    hops = route.hops,
+                    "route probed"
+                );
+                Ok(Some(route.fee_bps(amount_msat)))
+            }
+            Ok(None) => Err(QuoteError::NoRoute(format!(
+                "no route to {payee} within {max_fee_msat} msat of fees"
+            ))),
+            Err(e) => {
+                warn!(payee, error = %e, "cannot probe route");
+                Err(QuoteError::NoRoute(format!("cannot probe the route to {payee}: {e}")))
+            }
+        }
+    }
+
+    /// Signed offers for every configured direction that has a price and can be filled now, at the highest fee
+    /// the fee policy charges within their range.
+    pub async fn offers<L: LnBackend>(&self, ln: &L, rate: Option<Rate>) -> Vec<Offer> {
+        let liquidity = self.liquidity(ln).await;
+        let ttl = i64::try_from(self.cfg.offer_ttl.as_secs()).unwrap_or(i64::MAX);
+        let expires_at = unix_now().saturating_add(ttl);
+        let fees = self.terms().fees;
+        let inputs = match fees.is_static() {
+            true => None,
+            false => match self.negotiator.store.recent_routing_fee_bps(ROUTING_FEE_SAMPLE) {
+                Ok(routing_fee_bps) => Some(FeeInputs {
+                    liquidity,
+                    routing_fee_bps,
+                    probed_routing_fee_bps: None,
+                    compute_unit_price_micro_lamports: self.compute_unit_price(&fees).await,
+                }),
+                Err(e) => {
+                    warn!(error = %e, "cannot read recent routing fees; offering nothing");
+                    return Vec::new();
+                }
+            },
+        };
+        let mut offers = Vec::new();
+        for &direction in &self.cfg.directions {
+            let Some(mut cfg) = self.quote_cfg(direction, rate) else {
+                continue;
+            };
+            if let Some(inputs) = &inputs {
+                cfg = fees.ceiling(&cfg, direction, inputs);
+            }
+            let offer = Offer::new(
+                &cfg,
+                direction,
+                &self.negotiator.operator,
+                liquidity.as_ref(),
+                expires_at,
+            );
+            if offer.is_fillable() {
+                offers.push(offer);
+            } else {
+                info!(%direction, "not enough liquidity to offer");
+            }
+        }
+        offers
+    }
+
+    /// Quotes `request` at the current price and liquidity, for takers on the control API rather than an
+    /// offer transport. A usdt-to-ln request naming its payee is refused unless a route to it can be probed.
+    pub async fn quote<L: LnBackend>(&self, ln: &L, request: &QuoteRequest) -> Result<Quote, NegotiateError> {
+        let no_price = NegotiateError::NoPrice(request.direction);
+        if !self.cfg.directions.contains(&request.direction) {
+            return Err(no_price);
+        }
+        let rate = self.price().await;
+        let cfg = self.quote_cfg(request.direction, rate).ok_or(no_price)?;
+        let probed_routing_fee_bps = self
+            .probe(
+                ln,
+                &cfg,
+                request.direction,
+                request.destination.as_ref(),
+                request.amount_msat,
+            )
+            .await?;
+        let terms = self.terms();
+        let quoter = Quoter {
+            cfg,
+            operator: &self.negotiator.operator,
+            store: &self.negotiator.store,
+            liquidity: self.liquidity(ln).await,
+            reputation: terms.reputation,
+            limits: terms.exposure,
+            compute_unit_price_micro_lamports: self.compute_unit_price(&terms.fees).await,
+            fees: terms.fees,
+            probed_routing_fee_bps,
+        };
+        Ok(quoter.quote(request)?)
+    }
+
+    /// Answers one message from `peer`, waking the engine when a take queued a swap.
+    pub async fn handle<L: LnBackend>(
+        &self,
+        ln: &L,
+        wake: &Notify,
+        rate: Option<Rate>,
+        peer: &str,
+        message: &Value,
+    ) -> Value {
+        let reply = match Request::from_json(message) {
+            Ok(Request::Take(take)) => self.take(ln, rate, peer, &take).await,
+            Ok(Request::Status { swap_id }) => self.negotiator.status(peer, &swap_id),
+            Err(e) => Err(e),
+        };
+        match reply {
+            Ok(reply) => {
+                if reply["type"] == "accepted" {
+                    wake.notify_one();
+                }
+                reply
+            }
+            Err(e) => error_reply(&e),
+        }
+    }
+
+    async fn take<L: LnBackend>(
+        &self,
+        ln: &L,
+        rate: Option<Rate>,
+        peer: &str,
+        take: &Take,
+    ) -> Result<Value, NegotiateError> {
+        if !self.cfg.directions.contains(&take.direction) {
+            return Err(NegotiateError::NoPrice(take.direction));
+        }
+        let cfg = self
+            .quote_cfg(take.direction, rate)
+            .ok_or(NegotiateError::NoPrice(take.direction))?;
+        // An invoice that does not decode is refused when accepted.
+        let payee = take
+            .invoice
+            .as_deref()
+            .and_then(|bolt11| ln::decode_invoice(bolt11).ok())
+            .map(|invoice| invoice.payee);
+        let probed = self
+            .probe(ln, &cfg, take.direction, payee.as_ref(), take.amount_msat)
+            .await?;
+        let liquidity = self.liquidity(ln).await;
+        let terms = self.terms();
+        let compute_unit_price = self.compute_unit_price(&terms.fees).await;
+        let reply = self
+            .negotiator
+            .take(peer, take, cfg, liquidity, &terms, compute_unit_price, probed)?;
+        info!(
+            transport = self.negotiator.transport,
+            peer,
+            direction = %take.direction,
+            amount_msat = take.amount_msat,
+            "negotiated swap accepted"
+        );
+        Ok(reply)
+    }
+}
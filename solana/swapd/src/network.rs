@@ -0,0 +1,357 @@
+//! Network profiles binding a Lightning network to the Solana cluster and escrow program swaps use, and the
+//! startup check that they agree. The cluster is identified by its genesis hash, not by the RPC URL, so a
+//! mainnet endpoint behind a proxy or under an odd hostname is still recognised; the Lightning network is the one
+//! the node reports, not `--ln-network`, which REST backends do not even connect with. Real value on one side and
+//! test value on the other (signet invoices settled against mainnet USDT, or the reverse) is refused unless
+//! explicitly allowed, as is anything other than what a [`Profile`] names.
+
+use std::{fmt, str::FromStr};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{hash::Hash, pubkey::Pubkey};
+use tracing::warn;
+
+use crate::ln::LnBackend;
+
+/// Genesis block hashes of the Bitcoin networks, in the byte order of Lightning's `chain_hash`.
+const BITCOIN_CHAIN_HASH: &str = "6fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000";
+const TESTNET_CHAIN_HASH: &str = "43497fd7f826957108f4a30fd9cec3aeba79972084e90ead01ea330900000000";
+const SIGNET_CHAIN_HASH: &str = "f61eee3b63a380a477a063af32b2bbc97c9ff9f01f2c4225e973988108000000";
+const REGTEST_CHAIN_HASH: &str = "06226e46111a0b59caaf126043eb5bbf28c34f3a5e332a1fc7b2b73cf188910f";
+
+/// Genesis hashes of the public clusters.
+const MAINNET_GENESIS: &str = "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d";
+const DEVNET_GENESIS: &str = "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG";
+const TESTNET_GENESIS: &str = "4uhcVJyU9pJkvQyS88uRDiswHXSCkY3zQawwpjk2NsNY";
+
+#[derive(Debug)]
+pub enum NetworkError {
+    Rpc(String),
+    /// The program id has no executable account on the cluster.
+    NoProgram {
+        program_id: Pubkey,
+        cluster: Cluster,
+    },
+    Mismatch(String),
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rpc(e) => write!(f, "network check: {e}"),
+            Self::NoProgram { program_id, cluster } => {
+                write!(f, "program {program_id} is not deployed on {cluster}")
+            }
+            Self::Mismatch(e) => write!(f, "{e}; pass --allow-mixed-networks if this is intended"),
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+/// Bitcoin network of the Lightning node, as `--ln-network` names it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LnNetwork {
+    Regtest,
+    Signet,
+    Testnet,
+    Mainnet,
+}
+
+impl LnNetwork {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Regtest => "regtest",
+            Self::Signet => "signet",
+            Self::Testnet => "testnet",
+            Self::Mainnet => "mainnet",
+        }
+    }
+
+    /// The same network as the `bitcoin` crate names it, for on-chain addresses.
+    pub fn bitcoin(self) -> bitcoin::Network {
+        match self {
+            Self::Regtest => bitcoin::Network::Regtest,
+            Self::Signet => bitcoin::Network::Signet,
+            Self::Testnet => bitcoin::Network::Testnet,
+            Self::Mainnet => bitcoin::Network::Bitcoin,
+        }
+    }
+
+    /// The network a `bitcoin` crate one is, if swapd knows it.
+    pub fn from_bitcoin(network: bitcoin::Network) -> Option<Self> {
+        match network {
+            bitcoin::Network::Regtest => Some(Self::Regtest),
+            bitcoin::Network::Signet => Some(Self::Signet),
+            bitcoin::Network::Testnet => Some(Self::Testnet),
+            bitcoin::Network::Bitcoin => Some(Self::Mainnet),
+            _ => None,
+        }
+    }
+
+    /// The network whose genesis hash is `hex`, in either byte order.
+    pub fn from_chain_hash(hex: &str) -> Option<Self> {
+        let hex = hex.to_ascii_lowercase();
+        let reversed: String = hex
+            .as_bytes()
+            .chunks(2)
+            .rev()
+            .map(|pair| String::from_utf8_lossy(pair).into_owned())
+            .collect();
+        [
+            (BITCOIN_CHAIN_HASH, Self::Mainnet),
+            (TESTNET_CHAIN_HASH, Self::Testnet),
+            (SIGNET_CHAIN_HASH, Self::Signet),
+            (REGTEST_CHAIN_HASH, Self::Regtest),
+        ]
+        .into_iter()
+        .find(|(hash, _)| *hash == hex || *hash == reversed)
+        .map(|(_, network)| network)
+    }
+}
+
+impl fmt::Display for LnNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LnNetwork {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "regtest" => Ok(Self::Regtest),
+            "signet" => Ok(Self::Signet),
+            "testnet" => Ok(Self::Testnet),
+            // LDK and bitcoind call it bitcoin.
+            "mainnet" | "bitcoin" => Ok(Self::Mainnet),
+            other => Err(format!("unknown Lightning network {other:?}")),
+        }
+    }
+}
+
+/// Solana cluster, told apart by its genesis hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cluster {
+    /// A local validator, or any cluster that is not one of the public ones.
+    Localnet,
+    Devnet,
+    Testnet,
+    MainnetBeta,
+}
+
+impl Cluster {
+    pub fn from_genesis(hash: &Hash) -> Self {
+        match hash.to_string().as_str() {
+            MAINNET_GENESIS => Self::MainnetBeta,
+            DEVNET_GENESIS => Self::Devnet,
+            TESTNET_GENESIS => Self::Testnet,
+            _ => Self::Localnet,
+        }
+    }
+
+    /// The cluster's public RPC endpoint.
+    pub fn default_rpc_url(self) -> &'static str {
+        match self {
+            Self::Localnet => "http://127.0.0.1:8899",
+            Self::Devnet => "https://api.devnet.solana.com",
+            Self::Testnet => "https://api.testnet.solana.com",
+            Self::MainnetBeta => "https://api.mainnet-beta.solana.com",
+        }
+    }
+}
+
+impl fmt::Display for Cluster {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Localnet => "localnet",
+            Self::Devnet => "devnet",
+            Self::Testnet => "testnet",
+            Self::MainnetBeta => "mainnet-beta",
+        })
+    }
+}
+
+/// A Lightning network and the Solana cluster swaps on it settle against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// regtest Lightning, local validator.
+    Regtest,
+    /// signet Lightning, devnet.
+    Signet,
+    /// regtest Lightning, devnet.
+    Devnet,
+    /// testnet Lightning, Solana testnet.
+    Testnet,
+    /// Bitcoin mainnet, mainnet-beta.
+    Mainnet,
+}
+
+impl Profile {
+    pub fn lightning(self) -> LnNetwork {
+        match self {
+            Self::Regtest | Self::Devnet => LnNetwork::Regtest,
+            Self::Signet => LnNetwork::Signet,
+            Self::Testnet => LnNetwork::Testnet,
+            Self::Mainnet => LnNetwork::Mainnet,
+        }
+    }
+
+    pub fn cluster(self) -> Cluster {
+        match self {
+            Self::Regtest => Cluster::Localnet,
+            Self::Signet | Self::Devnet => Cluster::Devnet,
+            Self::Testnet => Cluster::Testnet,
+            Self::Mainnet => Cluster::MainnetBeta,
+        }
+    }
+
+    /// The escrow program deployed for this profile's cluster. Local validators load the program built from
+    /// this tree, at its declared id.
+    pub fn program_id(self) -> Pubkey {
+        match self {
+            Self::Regtest | Self::Signet | Self::Devnet | Self::Testnet | Self::Mainnet => {
+                intercom_swap_client::PROGRAM_ID
+            }
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Regtest => "regtest",
+            Self::Signet => "signet",
+            Self::Devnet => "devnet",
+            Self::Testnet => "testnet",
+            Self::Mainnet => "mainnet",
+        })
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "regtest" => Ok(Self::Regtest),
+            "signet" => Ok(Self::Signet),
+            "devnet" => Ok(Self::Devnet),
+            "testnet" => Ok(Self::Testnet),
+            "mainnet" => Ok(Self::Mainnet),
+            other => Err(format!(
+                "unknown network profile {other:?}: expected regtest, signet, devnet, testnet or mainnet"
+            )),
+        }
+    }
+}
+
+/// What the daemon is about to run against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Networks {
+    /// As the node reports it; `None` without a Lightning node (`--tower-only`) or before it is asked.
+    pub lightning: Option<LnNetwork>,
+    pub cluster: Cluster,
+    pub program_id: Pubkey,
+}
+
+impl Networks {
+    /// Why these networks must not be run together, if they must not: not those of `profile`, or mainnet on
+    /// one side only.
+    pub fn mismatch(&self, profile: Option<Profile>) -> Option<String> {
+        if let Some(profile) = profile {
+            if self.cluster != profile.cluster() {
+                return Some(format!(
+                    "network profile {profile} is for Solana {}, but the RPC endpoint is on {}",
+                    profile.cluster(),
+                    self.cluster
+                ));
+            }
+            if self.program_id != profile.program_id() {
+                return Some(format!(
+                    "network profile {profile} uses program {}, not {}",
+                    profile.program_id(),
+                    self.program_id
+                ));
+            }
+            if let Some(ln) = self.lightning.filter(|&ln| ln != profile.lightning()) {
+                return Some(format!(
+                    "network profile {profile} is for Lightning {}, not {ln}",
+                    profile.lightning()
+                ));
+            }
+        }
+        let ln = self.lightning?;
+        let (ln_mainnet, solana_mainnet) = (ln == LnNetwork::Mainnet, self.cluster == Cluster::MainnetBeta);
+        (ln_mainnet != solana_mainnet).then(|| format!("Lightning {ln} cannot be swapped with Solana {}", self.cluster))
+    }
+}
+
+/// Identifies the cluster behind `rpc`, checks that `program_id` is deployed on it and that both go with
+/// `profile`. The Lightning side is checked once the node is up, by [`check_lightning`]. A mismatch is returned
+/// as an error unless `allow_mixed`, when it is only logged.
+pub async fn check(
+    rpc: &RpcClient,
+    program_id: &Pubkey,
+    profile: Option<Profile>,
+    allow_mixed: bool,
+) -> Result<Networks, NetworkError> {
+    let genesis = rpc
+        .get_genesis_hash()
+        .await
+        .map_err(|e| NetworkError::Rpc(e.to_string()))?;
+    let networks = Networks {
+        lightning: None,
+        cluster: Cluster::from_genesis(&genesis),
+        program_id: *program_id,
+    };
+    let program = rpc
+        .get_account_with_commitment(program_id, rpc.commitment())
+        .await
+        .map_err(|e| NetworkError::Rpc(e.to_string()))?
+        .value;
+    if !program.is_some_and(|account| account.executable) {
+        return Err(NetworkError::NoProgram {
+            program_id: *program_id,
+            cluster: networks.cluster,
+        });
+    }
+    judge(networks.mismatch(profile), allow_mixed)?;
+    Ok(networks)
+}
+
+/// Asks `ln` which network it runs on and checks that against `networks` as [`check`] found them, `profile`
+/// and `configured`, the `--ln-network` the backend was set up with.
+pub async fn check_lightning<L: LnBackend>(
+    ln: &L,
+    networks: Networks,
+    configured: LnNetwork,
+    profile: Option<Profile>,
+    allow_mixed: bool,
+) -> Result<Networks, NetworkError> {
+    let lightning = ln.network().await.map_err(|e| NetworkError::Rpc(e.to_string()))?;
+    let networks = Networks {
+        lightning: Some(lightning),
+        ..networks
+    };
+    let mismatch = (lightning != configured)
+        .then(|| format!("the Lightning node runs on {lightning}, not --ln-network {configured}"))
+        .or_else(|| networks.mismatch(profile));
+    judge(mismatch, allow_mixed)?;
+    Ok(networks)
+}
+
+fn judge(mismatch: Option<String>, allow_mixed: bool) -> Result<(), NetworkError> {
+    match mismatch {
+        Some(mismatch) if allow_mixed => {
+            warn!(
+                alert = "mixed_networks",
+                "{mismatch}; running anyway (--allow-mixed-networks)"
+            );
+            Ok(())
+        }
+        Some(mismatch) => Err(NetworkError::Mismatch(mismatch)),
+        None => Ok(()),
+    }
+}
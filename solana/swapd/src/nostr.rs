@@ -0,0 +1,196 @@
+//! Nostr transport for [`negotiate`](crate::negotiate). Offers go out as addressable NIP-78 events, one per
+//! direction, replaced on every republish and expiring (NIP-40) if the maker stops. Takers answer the maker's
+//! key with NIP-04 direct messages carrying negotiation requests; replies and swap updates go back the same
+//! way.
+
+use std::{collections::HashSet, fmt, future::Future, sync::Arc};
+
+use nostr_sdk::{
+    nips::nip04, Client, Event, EventBuilder, Filter, Keys, Kind, PublicKey, RelayPoolNotification, Tag, Timestamp,
+};
+use serde_json::Value;
+use tokio::sync::{broadcast::error::RecvError, Notify};
+use tracing::{info, warn};
+
+use crate::{
+    ln::LnBackend,
+    negotiate::{error_reply, Maker, NegotiateError},
+    rates::Rate,
+    store::StoreError,
+};
+
+/// NIP-78 application data; addressable, so each offer replaces the last one with the same `d` tag.
+pub const OFFER_KIND: u16 = 30_078;
+/// `t` tag on every offer and prefix of its `d` tag, so takers can find makers.
+pub const OFFER_TAG: &str = "intercom-swap";
+/// Negotiator transport name.
+pub const TRANSPORT: &str = "nostr";
+/// Store cursor: `created_at` of the newest direct message handled, to resubscribe from after a restart.
+const DM_CURSOR: &str = "nostr_dm_at";
+
+#[derive(Debug)]
+pub enum NostrError {
+    Relay(String),
+    Store(StoreError),
+}
+
+impl fmt::Display for NostrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Relay(e) => write!(f, "nostr: {e}"),
+            Self::Store(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for NostrError {}
+
+impl From<nostr_sdk::client::Error> for NostrError {
+    fn from(e: nostr_sdk::client::Error) -> Self {
+        Self::Relay(e.to_string())
+    }
+}
+
+impl From<StoreError> for NostrError {
+    fn from(e: StoreError) -> Self {
+        Self::Store(e)
+    }
+}
+
+pub struct NostrConfig {
+    pub keys: Keys,
+    pub relays: Vec<String>,
+}
+
+pub struct NostrMaker {
+    cfg: NostrConfig,
+    maker: Maker,
+}
+
+impl NostrMaker {
+    pub fn new(cfg: NostrConfig, maker: Maker) -> Self {
+        Self { cfg, maker }
+    }
+
+    /// Publishes offers and answers takers until `shutdown` resolves. Accepted swaps are picked up by the
+    /// engine that owns `wake`.
+    pub async fn run<L: LnBackend>(
+        &self,
+        ln: &L,
+        wake: Arc<Notify>,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(), NostrError> {
+        let nostr = Client::new(self.cfg.keys.clone());
+        for relay in &self.cfg.relays {
+            nostr.add_relay(relay.as_str()).await?;
+        }
+        nostr.connect().await;
+        let since = self.maker.negotiator.store.cursor(DM_CURSOR)?;
+        let filter = Filter::new()
+            .kind(Kind::EncryptedDirectMessage)
+            .pubkey(self.cfg.keys.public_key())
+            .since(Timestamp::from(since));
+        nostr.subscribe(vec![filter], None).await?;
+        info!(pubkey = %self.cfg.keys.public_key(), relays = self.cfg.relays.len(), "nostr maker started");
+
+        let mut notifications = nostr.notifications();
+        let mut seen = HashSet::new();
+        let mut republish = tokio::time::interval(self.maker.cfg.republish);
+        let mut updates = tokio::time::interval(self.maker.cfg.update_interval);
+        let mut rate = None;
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                _ = republish.tick() => {
+                    rate = self.maker.price().await;
+                    if let Err(e) = self.publish_offers(&nostr, ln, rate).await {
+                        warn!(error = %e, "cannot publish nostr offers");
+                    }
+                }
+                _ = updates.tick() => self.send_updates(&nostr).await,
+                notification = notifications.recv() => match notification {
+                    Ok(RelayPoolNotification::Event { event, .. })
+                        if event.kind == Kind::EncryptedDirectMessage && seen.insert(event.id) =>
+                    {
+                        self.on_message(&nostr, ln, &wake, rate, &event).await;
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(missed)) => warn!(missed, "nostr notifications lagged"),
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+        let _ = nostr.disconnect().await;
+        Ok(())
+    }
+
+    async fn publish_offers<L: LnBackend>(&self, nostr: &Client, ln: &L, rate: Option<Rate>) -> Result<(), NostrError> {
+        for offer in self.maker.offers(ln, rate).await {
+            let tags = [
+                Tag::identifier(format!("{OFFER_TAG}:{}", offer.direction)),
+                Tag::hashtag(OFFER_TAG),
+                Tag::expiration(Timestamp::from(offer.expires_at.max(0) as u64)),
+            ];
+            let event = EventBuilder::new(Kind::from(OFFER_KIND), offer.to_json().to_string(), tags);
+            nostr.send_event_builder(event).await?;
+        }
+        Ok(())
+    }
+
+    async fn on_message<L: LnBackend>(&self, nostr: &Client, ln: &L, wake: &Notify, rate: Option<Rate>, event: &Event) {
+        let sender = event.pubkey;
+        let content = match nip04::decrypt(self.cfg.keys.secret_key(), &sender, &event.content) {
+            Ok(content) => content,
+            Err(e) => return warn!(%sender, error = %e, "cannot decrypt nostr message"),
+        };
+        let reply = match serde_json::from_str::<Value>(&content) {
+            Ok(message) => self.maker.handle(ln, wake, rate, &sender.to_hex(), &message).await,
+            Err(e) => error_reply(&NegotiateError::Malformed(e.to_string())),
+        };
+        if let Err(e) = self.send(nostr, sender, &reply).await {
+            warn!(%sender, error = %e, "cannot reply over nostr");
+        }
+        let handled = event.created_at.as_u64();
+        let store = &self.maker.negotiator.store;
+        if let Err(e) = store.cursor(DM_CURSOR).and_then(|at| {
+            if handled > at {
+                store.set_cursor(DM_CURSOR, handled)
+            } else {
+                Ok(())
+            }
+        }) {
+            warn!(error = %e, "cannot save nostr cursor");
+        }
+    }
+
+    async fn send_updates(&self, nostr: &Client) {
+        let negotiator = &self.maker.negotiator;
+        let updates = match negotiator.updates() {
+            Ok(updates) => updates,
+            Err(e) => return warn!(error = %e, "cannot read nostr sessions"),
+        };
+        for update in updates {
+            let Ok(peer) = PublicKey::from_hex(&update.session.peer) else {
+                warn!(swap = %update.session.swap_id, "nostr session with a malformed peer key");
+                continue;
+            };
+            match self.send(nostr, peer, &update.message).await {
+                Ok(()) => {
+                    if let Err(e) = negotiator.delivered(&update) {
+                        warn!(swap = %update.session.swap_id, error = %e, "cannot record nostr update");
+                    }
+                }
+                // Retried next interval.
+                Err(e) => warn!(swap = %update.session.swap_id, error = %e, "cannot send nostr update"),
+            }
+        }
+    }
+
+    async fn send(&self, nostr: &Client, to: PublicKey, message: &Value) -> Result<(), NostrError> {
+        let event = EventBuilder::encrypted_direct_msg(&self.cfg.keys, to, message.to_string(), None)
+            .map_err(|e| NostrError::Relay(e.to_string()))?;
+        nostr.send_event_builder(event).await?;
+        Ok(())
+    }
+}
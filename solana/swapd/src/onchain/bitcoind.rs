@@ -0,0 +1,224 @@
+//! Bitcoin Core over JSON-RPC. HTLC addresses are imported into a watch-only descriptor wallet, created on
+//! first use, whose transactions are then all the daemon reads; the node needs no `txindex`. Each address is
+//! labelled with its script, so its funding is found however many HTLCs the wallet has seen since.
+
+use std::collections::HashSet;
+
+use bitcoin::{consensus, Address, OutPoint, Script, Transaction, Txid};
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use super::{decode_tx, ChainBackend, ChainError, ChainTx};
+
+/// `RPC_WALLET_NOT_FOUND`: `loadwallet` of a wallet that was never created.
+const WALLET_NOT_FOUND: i64 = -18;
+/// `RPC_WALLET_ALREADY_LOADED`.
+const WALLET_ALREADY_LOADED: i64 = -35;
+/// Wallet transactions read per history lookup, for spends: they carry no label, so only the latest are looked at.
+const HISTORY_DEPTH: u32 = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitcoindConfig {
+    /// RPC URL, e.g. `http://127.0.0.1:8332`.
+    pub url: String,
+    pub user: String,
+    pub password: String,
+    /// Watch-only wallet the HTLC addresses are imported into.
+    pub wallet: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Bitcoind {
+    http: Client,
+    cfg: BitcoindConfig,
+}
+
+impl Bitcoind {
+    /// Connects and loads the watch-only wallet, creating it if it does not exist.
+    pub async fn connect(cfg: BitcoindConfig) -> Result<Self, ChainError> {
+        let node = Self {
+            http: Client::new(),
+            cfg,
+        };
+        let wallet = node.cfg.wallet.clone();
+        match node.rpc(false, "loadwallet", json!([wallet])).await? {
+            Ok(_) => {}
+            Err((WALLET_ALREADY_LOADED, _)) => {}
+            Err((WALLET_NOT_FOUND, _)) => {
+                // Watch-only (no private keys) and blank.
+                node.call(false, "createwallet", json!([wallet, true, true])).await?;
+            }
+            Err((code, message)) => return Err(ChainError::Rejected(format!("loadwallet: {message} ({code})"))),
+        }
+        Ok(node)
+    }
+
+    /// Calls `method`, on the wallet if `wallet`; the node's error code and message if it refused.
+    async fn rpc(&self, wallet: bool, method: &str, params: Value) -> Result<Result<Value, (i64, String)>, ChainError> {
+        let base = self.cfg.url.trim_end_matches('/');
+        let url = match wallet {
+            true => format!("{base}/wallet/{}", self.cfg.wallet),
+            false => base.to_string(),
+        };
+        let body = json!({ "jsonrpc": "1.0", "id": "swapd", "method": method, "params": params });
+        // Refusals come back as HTTP 500 with the error in the body, so the status is not checked.
+        let response = self
+            .http
+            .post(url)
+            .basic_auth(&self.cfg.user, Some(&self.cfg.password))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| ChainError::Rpc(format!("{method}: {e}")))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| ChainError::Rpc(format!("{method}: {e}")))?;
+        let reply: Value =
+            serde_json::from_str(&text).map_err(|_| ChainError::Rpc(format!("{method}: {status}: {text}")))?;
+        Ok(match &reply["error"] {
+            Value::Null => Ok(reply["result"].clone()),
+            error => Err((
+                error["code"].as_i64().unwrap_or_default(),
+                error["message"].as_str().unwrap_or_default().to_string(),
+            )),
+        })
+    }
+
+    async fn call(&self, wallet: bool, method: &str, params: Value) -> Result<Value, ChainError> {
+        self.rpc(wallet, method, params)
+            .await?
+            .map_err(|(code, message)| ChainError::Rejected(format!("{method}: {message} ({code})")))
+    }
+}
+
+/// The wallet label of the HTLC paying to `script`.
+fn label(script: &Script) -> String {
+    format!("htlc-{}", script.to_hex_string())
+}
+
+/// The transactions of `txs` paying to `script`, and those spending their outputs to it.
+fn touching(script: &Script, txs: Vec<ChainTx>) -> Vec<ChainTx> {
+    let outputs: HashSet<OutPoint> = txs
+        .iter()
+        .flat_map(|c| {
+            let txid = c.tx.compute_txid();
+            c.tx.output
+                .iter()
+                .enumerate()
+                .filter(|(_, out)| out.script_pubkey.as_script() == script)
+                .map(move |(vout, _)| OutPoint::new(txid, vout as u32))
+        })
+        .collect();
+    txs.into_iter()
+        .filter(|c| {
+            c.tx.output.iter().any(|out| out.script_pubkey.as_script() == script)
+                || c.tx.input.iter().any(|input| outputs.contains(&input.previous_output))
+        })
+        .collect()
+}
+
+impl ChainBackend for Bitcoind {
+    fn name(&self) -> &'static str {
+        "bitcoind"
+    }
+
+    async fn watch(&self, address: &Address) -> Result<(), ChainError> {
+        let info = self
+            .call(false, "getdescriptorinfo", json!([format!("addr({address})")]))
+            .await?;
+        let descriptor = info["descriptor"]
+            .as_str()
+            .ok_or_else(|| ChainError::Parse("getdescriptorinfo without descriptor".into()))?;
+        // The HTLC is new, so there is nothing before now to rescan.
+        let imported = self
+            .call(
+                true,
+                "importdescriptors",
+                json!([[{ "desc": descriptor, "timestamp": "now", "label": label(&address.script_pubkey()) }]]),
+            )
+            .await?;
+        match imported.get(0) {
+            Some(result) if result["success"] == true => Ok(()),
+            result => Err(ChainError::Rejected(format!(
+                "importdescriptors: {}",
+                result.map_or_else(|| imported.to_string(), |r| r["error"].to_string())
+            ))),
+        }
+    }
+
+    async fn tip_height(&self) -> Result<u32, ChainError> {
+        let count = self.call(false, "getblockcount", json!([])).await?;
+        count
+            .as_u64()
+            .map(|h| h as u32)
+            .ok_or_else(|| ChainError::Parse(format!("getblockcount answered {count}")))
+    }
+
+    async fn history(&self, script: &Script) -> Result<Vec<ChainTx>, ChainError> {
+        // Payments to the HTLC by its label, spends of it among the latest transactions.
+        let mut entries = Vec::new();
+        for label in [label(script), "*".to_string()] {
+            let listed = self
+                .call(true, "listtransactions", json!([label, HISTORY_DEPTH, 0, true]))
+                .await?;
+            entries.extend(listed.as_array().cloned().unwrap_or_default());
+        }
+        // A transaction is listed once per output or input of the wallet's.
+        let txids: HashSet<&str> = entries.iter().filter_map(|e| e["txid"].as_str()).collect();
+        let mut txs = Vec::new();
+        for txid in txids {
+            let wallet_tx = self.call(true, "gettransaction", json!([txid, true])).await?;
+            let tx = decode_tx(wallet_tx["hex"].as_str().unwrap_or_default())?;
+            let height = wallet_tx["blockheight"].as_u64().map(|h| h as u32);
+            txs.push(ChainTx { tx, height });
+        }
+        Ok(touching(script, txs))
+    }
+
+    async fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError> {
+        let txid = self
+            .call(
+                false,
+                "sendrawtransaction",
+                json!([consensus::encode::serialize_hex(tx)]),
+            )
+            .await?;
+        txid.as_str()
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(|| ChainError::Parse(format!("sendrawtransaction answered {txid}")))
+    }
+
+    async fn fee_rate(&self, blocks: u16) -> Result<Option<u64>, ChainError> {
+        // BTC per kvB; absent while the node has too little data.
+        let estimate = self.call(false, "estimatesmartfee", json!([blocks])).await?;
+        Ok(estimate["feerate"]
+            .as_f64()
+            .filter(|r| *r > 0.0)
+            .map(|r| (r * 100_000.0).ceil() as u64))
+    }
+
+    /// HTLC fundings come from the Lightning node's wallet, so the watch-only one knows their outputs but not
+    /// their fees; the node reads them off the mempool, or off the block for one confirmed.
+    async fn fee(&self, tx: &ChainTx) -> Result<u64, ChainError> {
+        let txid = tx.tx.compute_txid().to_string();
+        let fee = match tx.height {
+            None => self.call(false, "getmempoolentry", json!([txid])).await?["fees"]["base"].clone(),
+            Some(height) => {
+                let hash = self.call(false, "getblockhash", json!([height])).await?;
+                let block = self.call(false, "getblock", json!([hash, 2])).await?;
+                block["tx"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .find(|t| t["txid"] == txid.as_str())
+                    .map_or(Value::Null, |t| t["fee"].clone())
+            }
+        };
+        // BTC.
+        fee.as_f64()
+            .map(|btc| (btc * 100_000_000.0).round() as u64)
+            .ok_or_else(|| ChainError::Parse(format!("no fee known for {txid}")))
+    }
+}
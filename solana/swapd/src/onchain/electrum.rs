@@ -0,0 +1,135 @@
+//! An Electrum server (`tcp://HOST:PORT`), which indexes every script, so HTLCs need no registering. One
+//! connection per request, over plain TCP; put a TLS-terminating proxy in front of a remote server.
+
+use std::time::Duration;
+
+use bitcoin::{consensus, Script, Transaction, Txid};
+use serde_json::{json, Value};
+use solana_sdk::hash::hash;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+
+use super::{decode_tx, ChainBackend, ChainError, ChainTx};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct Electrum {
+    /// `HOST:PORT`.
+    address: String,
+}
+
+impl Electrum {
+    pub fn new(url: &str) -> Result<Self, ChainError> {
+        let address = url
+            .strip_prefix("tcp://")
+            .ok_or_else(|| ChainError::Rpc(format!("electrum url {url:?} must be tcp://HOST:PORT")))?;
+        Ok(Self {
+            address: address.trim_end_matches('/').to_string(),
+        })
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value, ChainError> {
+        let request = async {
+            let (read, mut write) = TcpStream::connect(&self.address).await?.into_split();
+            let body = json!({ "jsonrpc": "2.0", "id": 0, "method": method, "params": params });
+            write.write_all(format!("{body}\n").as_bytes()).await?;
+            let mut line = String::new();
+            BufReader::new(read).read_line(&mut line).await?;
+            Ok::<_, std::io::Error>(line)
+        };
+        let line = tokio::time::timeout(TIMEOUT, request)
+            .await
+            .map_err(|_| ChainError::Rpc(format!("{method} timed out")))?
+            .map_err(|e| ChainError::Rpc(e.to_string()))?;
+        let response: Value = serde_json::from_str(&line).map_err(|e| ChainError::Parse(e.to_string()))?;
+        match &response["error"] {
+            Value::Null => Ok(response["result"].clone()),
+            error => Err(ChainError::Rejected(
+                error["message"]
+                    .as_str()
+                    .map_or_else(|| error.to_string(), str::to_string),
+            )),
+        }
+    }
+}
+
+/// The key Electrum indexes scripts by: their SHA-256, byte-reversed.
+fn script_hash(script: &Script) -> String {
+    let mut digest = hash(script.as_bytes()).to_bytes();
+    digest.reverse();
+    hex::encode(digest)
+}
+
+impl ChainBackend for Electrum {
+    fn name(&self) -> &'static str {
+        "electrum"
+    }
+
+    async fn tip_height(&self) -> Result<u32, ChainError> {
+        let header = self.call("blockchain.headers.subscribe", json!([])).await?;
+        header["height"]
+            .as_u64()
+            .map(|h| h as u32)
+            .ok_or_else(|| ChainError::Parse("header without height".into()))
+    }
+
+    async fn history(&self, script: &Script) -> Result<Vec<ChainTx>, ChainError> {
+        let entries = self
+            .call("blockchain.scripthash.get_history", json!([script_hash(script)]))
+            .await?;
+        let mut history = Vec::new();
+        for entry in entries.as_array().into_iter().flatten() {
+            let txid = entry["tx_hash"]
+                .as_str()
+                .ok_or_else(|| ChainError::Parse("history entry without tx_hash".into()))?;
+            let raw = self.call("blockchain.transaction.get", json!([txid])).await?;
+            let tx = decode_tx(raw.as_str().unwrap_or_default())?;
+            // 0 or -1 while in the mempool.
+            let height = entry["height"].as_i64().filter(|h| *h > 0).map(|h| h as u32);
+            history.push(ChainTx { tx, height });
+        }
+        Ok(history)
+    }
+
+    async fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError> {
+        let txid = self
+            .call(
+                "blockchain.transaction.broadcast",
+                json!([consensus::encode::serialize_hex(tx)]),
+            )
+            .await?;
+        txid.as_str()
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(|| ChainError::Parse(format!("broadcast answered {txid}")))
+    }
+
+    async fn fee_rate(&self, blocks: u16) -> Result<Option<u64>, ChainError> {
+        // BTC per kvB, or -1 without an estimate.
+        let rate = self.call("blockchain.estimatefee", json!([blocks])).await?;
+        Ok(rate
+            .as_f64()
+            .filter(|r| *r > 0.0)
+            .map(|r| (r * 100_000.0).ceil() as u64))
+    }
+
+    async fn fee(&self, tx: &ChainTx) -> Result<u64, ChainError> {
+        let mut spent = 0u64;
+        for input in &tx.tx.input {
+            let prevout = input.previous_output;
+            let raw = self
+                .call("blockchain.transaction.get", json!([prevout.txid.to_string()]))
+                .await?;
+            let previous = decode_tx(raw.as_str().unwrap_or_default())?;
+            let output = previous
+                .output
+                .get(prevout.vout as usize)
+                .ok_or_else(|| ChainError::Parse(format!("{prevout} spends a missing output")))?;
+            spent = spent.saturating_add(output.value.to_sat());
+        }
+        let paid: u64 = tx.tx.output.iter().map(|out| out.value.to_sat()).sum();
+        Ok(spent.saturating_sub(paid))
+    }
+}
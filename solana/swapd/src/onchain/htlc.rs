@@ -0,0 +1,206 @@
+//! The HTLC script: a Taproot output with an unspendable internal key and two leaves, one the taker spends
+//! with the preimage of the swap's payment hash, the other the operator spends once `timeout_height` is
+//! reached.
+//!
+//! ```text
+//! claim:  OP_SIZE 32 OP_EQUALVERIFY OP_SHA256 <payment_hash> OP_EQUALVERIFY <claim_key> OP_CHECKSIG
+//! refund: <timeout_height> OP_CHECKLOCKTIMEVERIFY OP_DROP <refund_key> OP_CHECKSIG
+//! ```
+
+use bitcoin::{
+    absolute::LockTime,
+    hashes::Hash as _,
+    opcodes::all::{OP_CHECKSIG, OP_CLTV, OP_DROP, OP_EQUALVERIFY, OP_SHA256, OP_SIZE},
+    script::Builder,
+    secp256k1::{Keypair, Message, Secp256k1, SecretKey, XOnlyPublicKey},
+    sighash::{Prevouts, SighashCache, TapSighashType},
+    taproot::{LeafVersion, TapLeafHash, TaprootBuilder, TaprootSpendInfo},
+    transaction::Version,
+    Address, Amount, Network, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
+};
+use rand::RngCore;
+use serde_json::{json, Value};
+use solana_sdk::hash::hash;
+
+use super::OnchainError;
+
+/// BIP 341's point with no known discrete log, so the output can only be spent through a leaf.
+const UNSPENDABLE_KEY: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e, 0x07, 0x8a, 0x5a,
+    0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+/// Virtual size of a refund: one script-path input, one output.
+pub(super) const REFUND_VSIZE: u64 = 150;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Htlc {
+    pub payment_hash: [u8; 32],
+    /// The taker's key, registered with the swap.
+    pub claim_key: XOnlyPublicKey,
+    /// A key generated for this HTLC; its secret is kept sealed by the vault.
+    pub refund_key: XOnlyPublicKey,
+    /// First block height the operator can refund at.
+    pub timeout_height: u32,
+}
+
+impl Htlc {
+    pub fn claim_script(&self) -> ScriptBuf {
+        Builder::new()
+            .push_opcode(OP_SIZE)
+            .push_int(32)
+            .push_opcode(OP_EQUALVERIFY)
+            .push_opcode(OP_SHA256)
+            .push_slice(self.payment_hash)
+            .push_opcode(OP_EQUALVERIFY)
+            .push_x_only_key(&self.claim_key)
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    pub fn refund_script(&self) -> ScriptBuf {
+        Builder::new()
+            .push_int(i64::from(self.timeout_height))
+            .push_opcode(OP_CLTV)
+            .push_opcode(OP_DROP)
+            .push_x_only_key(&self.refund_key)
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    pub fn spend_info(&self) -> TaprootSpendInfo {
+        let internal = XOnlyPublicKey::from_slice(&UNSPENDABLE_KEY).expect("BIP 341 point is on the curve");
+        TaprootBuilder::new()
+            .add_leaf(1, self.claim_script())
+            .and_then(|b| b.add_leaf(1, self.refund_script()))
+            .expect("two leaves at depth 1 make a complete tree")
+            .finalize(&Secp256k1::verification_only(), internal)
+            .expect("the tree is complete")
+    }
+
+    pub fn script_pubkey(&self) -> ScriptBuf {
+        ScriptBuf::new_p2tr_tweaked(self.spend_info().output_key())
+    }
+
+    pub fn address(&self, network: Network) -> Address {
+        Address::p2tr_tweaked(self.spend_info().output_key(), network)
+    }
+
+    /// The output of `tx` paying this HTLC, with its value in sats.
+    pub fn output_in(&self, tx: &Transaction) -> Option<(OutPoint, u64)> {
+        let script = self.script_pubkey();
+        tx.output
+            .iter()
+            .enumerate()
+            .find(|(_, out)| out.script_pubkey == script)
+            .map(|(vout, out)| (OutPoint::new(tx.compute_txid(), vout as u32), out.value.to_sat()))
+    }
+
+    /// Whether `tx` spends `outpoint`.
+    pub fn spends(tx: &Transaction, outpoint: &OutPoint) -> bool {
+        tx.input.iter().any(|input| input.previous_output == *outpoint)
+    }
+
+    /// The preimage `tx` reveals spending `outpoint` through the claim leaf; `None` for the refund leaf.
+    pub fn preimage_in(&self, tx: &Transaction, outpoint: &OutPoint) -> Option<[u8; 32]> {
+        let input = tx.input.iter().find(|input| input.previous_output == *outpoint)?;
+        input
+            .witness
+            .iter()
+            .filter_map(|item| <[u8; 32]>::try_from(item).ok())
+            .find(|item| hash(item).to_bytes() == self.payment_hash)
+    }
+
+    /// Spends `outpoint`, holding `value_sat`, through the refund leaf to `destination`, less `fee_sat`.
+    pub fn refund_tx(
+        &self,
+        refund_secret: &[u8],
+        outpoint: OutPoint,
+        value_sat: u64,
+        destination: ScriptBuf,
+        fee_sat: u64,
+    ) -> Result<Transaction, OnchainError> {
+        let secp = Secp256k1::new();
+        let secret = SecretKey::from_slice(refund_secret).map_err(|e| OnchainError::Script(e.to_string()))?;
+        let keypair = Keypair::from_secret_key(&secp, &secret);
+        if keypair.x_only_public_key().0 != self.refund_key {
+            return Err(OnchainError::Script(
+                "refund secret does not match the refund key".into(),
+            ));
+        }
+        let value = value_sat
+            .checked_sub(fee_sat)
+            .filter(|v| *v >= 330)
+            .ok_or_else(|| OnchainError::Script(format!("{value_sat} sat cannot pay a {fee_sat} sat refund fee")))?;
+        let mut tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::from_height(self.timeout_height).map_err(|e| OnchainError::Script(e.to_string()))?,
+            input: vec![TxIn {
+                previous_output: outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_LOCKTIME_NO_RBF,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey: destination,
+            }],
+        };
+        let prevouts = [TxOut {
+            value: Amount::from_sat(value_sat),
+            script_pubkey: self.script_pubkey(),
+        }];
+        let script = self.refund_script();
+        let sighash = SighashCache::new(&tx)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&prevouts),
+                TapLeafHash::from_script(&script, LeafVersion::TapScript),
+                TapSighashType::Default,
+            )
+            .map_err(|e| OnchainError::Script(e.to_string()))?;
+        let mut aux = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut aux);
+        let signature = secp.sign_schnorr_with_aux_rand(&Message::from_digest(sighash.to_byte_array()), &keypair, &aux);
+        let control = self
+            .spend_info()
+            .control_block(&(script.clone(), LeafVersion::TapScript))
+            .expect("the refund leaf is in the tree");
+        let witness = &mut tx.input[0].witness;
+        witness.push(signature.serialize());
+        witness.push(script.as_bytes());
+        witness.push(control.serialize());
+        Ok(tx)
+    }
+
+    /// What the taker needs to check the HTLC and claim it.
+    pub fn to_json(&self) -> Value {
+        let info = self.spend_info();
+        let control = |script: ScriptBuf| {
+            info.control_block(&(script, LeafVersion::TapScript))
+                .map(|c| hex::encode(c.serialize()))
+        };
+        json!({
+            "paymentHash": hex::encode(self.payment_hash),
+            "claimKey": self.claim_key.to_string(),
+            "refundKey": self.refund_key.to_string(),
+            "timeoutHeight": self.timeout_height,
+            "internalKey": hex::encode(UNSPENDABLE_KEY),
+            "claimScript": hex::encode(self.claim_script().as_bytes()),
+            "claimControlBlock": control(self.claim_script()),
+            "refundScript": hex::encode(self.refund_script().as_bytes()),
+        })
+    }
+}
+
+/// A new refund key: its secret, to seal, and public key.
+pub(super) fn refund_key() -> (SecretKey, XOnlyPublicKey) {
+    let secp = Secp256k1::new();
+    loop {
+        let mut bytes = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut bytes);
+        // Fails only for zero or values past the curve order.
+        if let Ok(secret) = SecretKey::from_slice(&bytes) {
+            return (secret, Keypair::from_secret_key(&secp, &secret).x_only_public_key().0);
+        }
+    }
+}
@@ -0,0 +1,749 @@
+//! On-chain fallback for the BTC leg of usdt-to-ln swaps whose Lightning payment keeps failing. A taker who
+//! registers a claim key with the swap ([`Registration`]) can be paid through a Taproot HTLC instead
+//! ([`Htlc`]): once the payment has failed `after_ln_failures` times, the [`Fallback`] locks the amount from the
+//! node's on-chain wallet to an output the taker claims with the preimage of the swap's payment hash, and the
+//! operator refunds after `timeout_height`. The timeout is set from the escrow's `refund_after`, less the claim
+//! margin, so the preimage revealed by the taker's claim still leaves time to claim the escrow with it. Funding
+//! and the spend are followed through a [`ChainBackend`]: bitcoind with a watch-only wallet, or an Electrum
+//! server. The HTLC's refund key is generated per swap and its secret sealed by the vault. The on-chain leg is
+//! booked in the ledger instead of `ln_paid`: the funding with its miner fee once seen on chain, then the claim
+//! or the refund. A funding the backend does not show is only sent again once the node's wallet reports it gone,
+//! never on the backend's word alone: a second output would pay the taker twice against one preimage.
+
+pub mod bitcoind;
+pub mod electrum;
+mod htlc;
+
+pub use htlc::Htlc;
+
+use std::{fmt, future::Future, str::FromStr, sync::Arc};
+
+use bitcoin::{secp256k1::XOnlyPublicKey, Address, Network, Script, ScriptBuf, Transaction, Txid};
+use serde_json::{json, Value};
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+use crate::{
+    ledger::Entry,
+    ln::{LnBackend, LnError, WalletTxStatus},
+    store::{Store, StoreError},
+    swap::{unix_now, Direction, Swap},
+};
+
+/// Seconds before an HTLC not seen funded is looked up in the node's wallet, or an unconfirmed refund broadcast
+/// again.
+const RESEND_SECS: i64 = 600;
+
+#[derive(Debug)]
+pub enum ChainError {
+    Rpc(String),
+    /// The backend answered and refused, e.g. a transaction it would not relay.
+    Rejected(String),
+    Parse(String),
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rpc(e) => write!(f, "chain backend request failed: {e}"),
+            Self::Rejected(e) => write!(f, "chain backend refused the request: {e}"),
+            Self::Parse(e) => write!(f, "unexpected chain backend response: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+#[derive(Debug)]
+pub enum OnchainError {
+    Chain(ChainError),
+    Ln(LnError),
+    Store(StoreError),
+    /// The HTLC or its refund could not be built.
+    Script(String),
+}
+
+impl fmt::Display for OnchainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Chain(e) => write!(f, "{e}"),
+            Self::Ln(e) => write!(f, "{e}"),
+            Self::Store(e) => write!(f, "{e}"),
+            Self::Script(e) => write!(f, "on-chain HTLC: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for OnchainError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Chain(e) => Some(e),
+            Self::Ln(e) => Some(e),
+            Self::Store(e) => Some(e),
+            Self::Script(_) => None,
+        }
+    }
+}
+
+impl From<ChainError> for OnchainError {
+    fn from(e: ChainError) -> Self {
+        Self::Chain(e)
+    }
+}
+
+impl From<LnError> for OnchainError {
+    fn from(e: LnError) -> Self {
+        Self::Ln(e)
+    }
+}
+
+impl From<StoreError> for OnchainError {
+    fn from(e: StoreError) -> Self {
+        Self::Store(e)
+    }
+}
+
+/// A transaction touching a watched script, with the height it confirmed at (`None` in the mempool).
+#[derive(Debug, Clone)]
+pub struct ChainTx {
+    pub tx: Transaction,
+    pub height: Option<u32>,
+}
+
+fn decode_tx(hex_tx: &str) -> Result<Transaction, ChainError> {
+    let bytes = hex::decode(hex_tx).map_err(|e| ChainError::Parse(e.to_string()))?;
+    bitcoin::consensus::deserialize(&bytes).map_err(|e| ChainError::Parse(e.to_string()))
+}
+
+/// Where the daemon reads the Bitcoin chain and broadcasts refunds.
+pub trait ChainBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Starts following `address`, for backends that only see what they were told about.
+    fn watch(&self, address: &Address) -> impl Future<Output = Result<(), ChainError>> + Send {
+        let _ = address;
+        async { Ok(()) }
+    }
+
+    fn tip_height(&self) -> impl Future<Output = Result<u32, ChainError>> + Send;
+
+    /// Every transaction paying to or spending from `script`, mempool included.
+    fn history(&self, script: &Script) -> impl Future<Output = Result<Vec<ChainTx>, ChainError>> + Send;
+
+    fn broadcast(&self, tx: &Transaction) -> impl Future<Output = Result<Txid, ChainError>> + Send;
+
+    /// Sat/vB to confirm within `blocks`, if the backend has an estimate.
+    fn fee_rate(&self, blocks: u16) -> impl Future<Output = Result<Option<u64>, ChainError>> + Send;
+
+    /// The miner fee `tx` paid, in sats.
+    fn fee(&self, tx: &ChainTx) -> impl Future<Output = Result<u64, ChainError>> + Send;
+}
+
+/// The chain backends swapd ships with.
+#[derive(Debug, Clone)]
+pub enum Chain {
+    Bitcoind(bitcoind::Bitcoind),
+    Electrum(electrum::Electrum),
+}
+
+impl ChainBackend for Chain {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Bitcoind(c) => c.name(),
+            Self::Electrum(c) => c.name(),
+        }
+    }
+
+    async fn watch(&self, address: &Address) -> Result<(), ChainError> {
+        match self {
+            Self::Bitcoind(c) => c.watch(address).await,
+            Self::Electrum(c) => c.watch(address).await,
+        }
+    }
+
+    async fn tip_height(&self) -> Result<u32, ChainError> {
+        match self {
+            Self::Bitcoind(c) => c.tip_height().await,
+            Self::Electrum(c) => c.tip_height().await,
+        }
+    }
+
+    async fn history(&self, script: &Script) -> Result<Vec<ChainTx>, ChainError> {
+        match self {
+            Self::Bitcoind(c) => c.history(script).await,
+            Self::Electrum(c) => c.history(script).await,
+        }
+    }
+
+    async fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError> {
+        match self {
+            Self::Bitcoind(c) => c.broadcast(tx).await,
+            Self::Electrum(c) => c.broadcast(tx).await,
+        }
+    }
+
+    async fn fee_rate(&self, blocks: u16) -> Result<Option<u64>, ChainError> {
+        match self {
+            Self::Bitcoind(c) => c.fee_rate(blocks).await,
+            Self::Electrum(c) => c.fee_rate(blocks).await,
+        }
+    }
+
+    async fn fee(&self, tx: &ChainTx) -> Result<u64, ChainError> {
+        match self {
+            Self::Bitcoind(c) => c.fee(tx).await,
+            Self::Electrum(c) => c.fee(tx).await,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HtlcStatus {
+    /// Written, and the node's send issued or about to be.
+    Funding,
+    Funded,
+    /// Timed out; the refund is broadcast but not confirmed.
+    Refunding,
+    /// Spent by the taker, revealing the preimage.
+    Claimed,
+    Refunded,
+}
+
+impl HtlcStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Funding => "funding",
+            Self::Funded => "funded",
+            Self::Refunding => "refunding",
+            Self::Claimed => "claimed",
+            Self::Refunded => "refunded",
+        }
+    }
+}
+
+impl fmt::Display for HtlcStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for HtlcStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "funding" => Ok(Self::Funding),
+            "funded" => Ok(Self::Funded),
+            "refunding" => Ok(Self::Refunding),
+            "claimed" => Ok(Self::Claimed),
+            "refunded" => Ok(Self::Refunded),
+            other => Err(format!("unknown HTLC status {other:?}")),
+        }
+    }
+}
+
+/// A taker's consent to be paid on-chain should Lightning fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registration {
+    pub swap_id: String,
+    pub claim_key: XOnlyPublicKey,
+    /// Failed Lightning payments counted since registering.
+    pub ln_failures: u32,
+    pub registered_at: i64,
+}
+
+impl Registration {
+    /// Why `swap`, with `htlc` opened for it if any, cannot take a claim key, if it cannot.
+    pub fn refusal(swap: &Swap, htlc: Option<&HtlcRecord>) -> Option<String> {
+        if swap.direction != Direction::UsdtToLn {
+            return Some(format!(
+                "swap {} is {}; only usdt-to-ln swaps pay on-chain",
+                swap.id, swap.direction
+            ));
+        }
+        if swap.state.is_terminal() || htlc.is_some() {
+            return Some(format!(
+                "swap {} can no longer take a claim key in state {}",
+                swap.id, swap.state
+            ));
+        }
+        None
+    }
+}
+
+/// An HTLC opened for a swap, as the daemon keeps it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtlcRecord {
+    pub swap_id: String,
+    pub htlc: Htlc,
+    pub amount_sat: u64,
+    pub address: String,
+    /// Secret of `htlc.refund_key`, sealed by the vault under the payment hash.
+    pub refund_secret: Vec<u8>,
+    pub status: HtlcStatus,
+    pub funding_txid: Option<String>,
+    pub funding_vout: Option<u32>,
+    /// The claim or refund.
+    pub spend_txid: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl HtlcRecord {
+    /// Everything the taker needs to check and claim the HTLC; never the refund secret.
+    pub fn to_json(&self) -> Value {
+        let mut json = self.htlc.to_json();
+        if let Value::Object(fields) = &mut json {
+            fields.extend(
+                [
+                    ("swapId", json!(self.swap_id)),
+                    ("address", json!(self.address)),
+                    ("amountSat", json!(self.amount_sat)),
+                    ("status", json!(self.status.as_str())),
+                    ("fundingTxid", json!(self.funding_txid)),
+                    ("fundingVout", json!(self.funding_vout)),
+                    ("spendTxid", json!(self.spend_txid)),
+                    ("createdAt", json!(self.created_at)),
+                    ("updatedAt", json!(self.updated_at)),
+                ]
+                .map(|(k, v)| (k.to_string(), v)),
+            );
+        }
+        json
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnchainConfig {
+    pub network: Network,
+    /// Failed Lightning payments before paying on-chain.
+    pub after_ln_failures: u32,
+    /// No HTLC when fewer blocks than this are left before the escrow must be claimed.
+    pub min_timeout_blocks: u32,
+    /// Where refunds go.
+    pub refund_script: ScriptBuf,
+    /// Blocks a refund should confirm within.
+    pub confirmation_target: u16,
+    /// Floor under the backend's fee estimate, in sat/vB.
+    pub min_fee_rate_sat_vb: u64,
+}
+
+/// Where an HTLC stands, as far as its swap is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    Pending,
+    /// The taker claimed, revealing this preimage.
+    Claimed([u8; 32]),
+    /// Refunded to the operator; the taker was not paid.
+    Refunded,
+}
+
+pub struct Fallback<C = Chain> {
+    store: Arc<Store>,
+    chain: C,
+    cfg: OnchainConfig,
+}
+
+impl<C: ChainBackend> Fallback<C> {
+    pub fn new(store: Arc<Store>, chain: C, cfg: OnchainConfig) -> Self {
+        Self { store, chain, cfg }
+    }
+
+    pub fn config(&self) -> &OnchainConfig {
+        &self.cfg
+    }
+
+    /// Opens and funds an HTLC paying `swap` to `claim_key`, refundable in `blocks`; `None` if that is less
+    /// than `min_timeout_blocks`.
+    pub async fn open<L: LnBackend>(
+        &self,
+        ln: &L,
+        swap: &Swap,
+        claim_key: &XOnlyPublicKey,
+        blocks: u32,
+    ) -> Result<Option<HtlcRecord>, OnchainError> {
+        if blocks < self.cfg.min_timeout_blocks {
+            return Ok(None);
+        }
+        let tip = self.chain.tip_height().await?;
+        let (secret, refund_key) = htlc::refund_key();
+        let htlc = Htlc {
+            payment_hash: swap.payment_hash,
+            claim_key: *claim_key,
+            refund_key,
+            timeout_height: tip.saturating_add(blocks),
+        };
+        let secret = Zeroizing::new(secret.secret_bytes());
+        let refund_secret = self
+            .store
+            .vault()
+            .seal_blob(&swap.payment_hash, secret.as_slice())
+            .map_err(StoreError::from)?;
+        let address = htlc.address(self.cfg.network);
+        self.chain.watch(&address).await?;
+        let now = unix_now();
+        let record = HtlcRecord {
+            swap_id: swap.id.clone(),
+            htlc,
+            amount_sat: swap.amount_msat.div_ceil(1000),
+            address: address.to_string(),
+            refund_secret,
+            status: HtlcStatus::Funding,
+            funding_txid: None,
+            funding_vout: None,
+            spend_txid: None,
+            created_at: now,
+            updated_at: now,
+        };
+        self.store.insert_htlc(&record)?;
+        self.fund(ln, &record).await?;
+        Ok(self.store.htlc(&swap.id)?)
+    }
+
+    /// Sends the HTLC's amount from the node's on-chain wallet.
+    async fn fund<L: LnBackend>(&self, ln: &L, record: &HtlcRecord) -> Result<(), OnchainError> {
+        let txid = ln.send_onchain(&record.address, record.amount_sat).await?;
+        info!(swap = %record.swap_id, address = %record.address, amount_sat = record.amount_sat, txid, "HTLC funded");
+        self.store.update_htlc(
+            &record.swap_id,
+            HtlcStatus::Funded,
+            Some((&txid, None)),
+            None,
+            unix_now(),
+        )?;
+        Ok(())
+    }
+
+    /// Follows the HTLC of `swap` on chain: records and books its funding, funds it again if it never showed up,
+    /// refunds it once timed out, and reports a claim's preimage.
+    pub async fn advance<L: LnBackend>(&self, ln: &L, swap: &Swap) -> Result<Progress, OnchainError> {
+        let Some(record) = self.store.htlc(&swap.id)? else {
+            return Err(OnchainError::Script(format!("swap {} has no HTLC", swap.id)));
+        };
+        if record.status == HtlcStatus::Refunded {
+            return Ok(Progress::Refunded);
+        }
+        let now = unix_now();
+        let history = self.chain.history(&record.htlc.script_pubkey()).await?;
+        let Some((funding, outpoint, value_sat)) = history.iter().find_map(|c| {
+            record
+                .htlc
+                .output_in(&c.tx)
+                .map(|(outpoint, value)| (c, outpoint, value))
+        }) else {
+            return self.unseen(ln, &record, now).await;
+        };
+        if record.funding_vout.is_none() {
+            let fee_sat = self.chain.fee(funding).await?;
+            self.store
+                .post_ledger_entry(&Entry::htlc_funded(swap, value_sat, fee_sat))?;
+            let txid = outpoint.txid.to_string();
+            self.store.update_htlc(
+                &swap.id,
+                HtlcStatus::Funded,
+                Some((&txid, Some(outpoint.vout))),
+                None,
+                now,
+            )?;
+        }
+        if let Some(spend) = history.iter().find(|c| Htlc::spends(&c.tx, &outpoint)) {
+            let txid = spend.tx.compute_txid().to_string();
+            if let Some(preimage) = record.htlc.preimage_in(&spend.tx, &outpoint) {
+                info!(swap = %swap.id, txid, "HTLC claimed on chain");
+                self.store.post_ledger_entry(&Entry::htlc_claimed(swap, value_sat))?;
+                self.store
+                    .update_htlc(&swap.id, HtlcStatus::Claimed, None, Some(&txid), now)?;
+                return Ok(Progress::Claimed(preimage));
+            }
+            // Our refund: final once confirmed.
+            if spend.height.is_some() {
+                let returned: u64 = spend.tx.output.iter().map(|out| out.value.to_sat()).sum();
+                let fee_sat = value_sat.saturating_sub(returned);
+                self.store
+                    .post_ledger_entry(&Entry::htlc_refunded(swap, value_sat, fee_sat))?;
+                self.store
+                    .update_htlc(&swap.id, HtlcStatus::Refunded, None, Some(&txid), now)?;
+                return Ok(Progress::Refunded);
+            }
+            return Ok(Progress::Pending);
+        }
+        if self.chain.tip_height().await? < record.htlc.timeout_height {
+            return Ok(Progress::Pending);
+        }
+        let refunding = record.status == HtlcStatus::Refunding;
+        if refunding && now - record.updated_at < RESEND_SECS {
+            return Ok(Progress::Pending);
+        }
+        let secret = self
+            .store
+            .vault()
+            .open_blob(&record.htlc.payment_hash, &record.refund_secret)
+            .map_err(StoreError::from)?;
+        let fee_rate = self
+            .chain
+            .fee_rate(self.cfg.confirmation_target)
+            .await?
+            .unwrap_or(0)
+            .max(self.cfg.min_fee_rate_sat_vb);
+        let tx = record.htlc.refund_tx(
+            &secret,
+            outpoint,
+            value_sat,
+            self.cfg.refund_script.clone(),
+            fee_rate.saturating_mul(htlc::REFUND_VSIZE),
+        )?;
+        let txid = match self.chain.broadcast(&tx).await {
+            Ok(txid) => txid.to_string(),
+            // Likely already in the mempool; checked again next step.
+            Err(e) if refunding => {
+                warn!(swap = %swap.id, error = %e, "HTLC refund rebroadcast failed");
+                return Ok(Progress::Pending);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if !refunding {
+            warn!(
+                alert = "onchain_htlc_refunded",
+                swap = %swap.id,
+                address = %record.address,
+                txid,
+                "on-chain HTLC timed out unclaimed; refunding"
+            );
+        }
+        self.store
+            .update_htlc(&swap.id, HtlcStatus::Refunding, None, Some(&txid), now)?;
+        Ok(Progress::Pending)
+    }
+
+    /// An HTLC whose funding output the backend does not show. One seen before is only out of the backend's
+    /// view (lagging, reorganised, or too old for it), and one the node's wallet still has will confirm or can
+    /// have its fee bumped there; only a funding the wallet no longer has is sent again. Checked every
+    /// `RESEND_SECS`.
+    async fn unseen<L: LnBackend>(&self, ln: &L, record: &HtlcRecord, now: i64) -> Result<Progress, OnchainError> {
+        let waiting = matches!(record.status, HtlcStatus::Funding | HtlcStatus::Funded);
+        if !waiting || record.funding_vout.is_some() || now - record.updated_at <= RESEND_SECS {
+            return Ok(Progress::Pending);
+        }
+        let Some(txid) = &record.funding_txid else {
+            // The send may have gone out before the daemon stopped; without its txid there is no telling.
+            warn!(
+                alert = "onchain_htlc_funding_unknown",
+                swap = %record.swap_id,
+                address = %record.address,
+                "HTLC funding was never recorded; check the node's wallet for a send to the address"
+            );
+            self.store
+                .update_htlc(&record.swap_id, record.status, None, None, now)?;
+            return Ok(Progress::Pending);
+        };
+        match ln.wallet_tx(txid).await {
+            Ok(WalletTxStatus::Gone) => {
+                warn!(
+                    swap = %record.swap_id,
+                    address = %record.address,
+                    txid,
+                    "HTLC funding dropped by the node's wallet; sending again"
+                );
+                self.fund(ln, record).await?;
+                return Ok(Progress::Pending);
+            }
+            Ok(WalletTxStatus::Unconfirmed) => warn!(
+                alert = "onchain_htlc_funding_unconfirmed",
+                swap = %record.swap_id,
+                txid,
+                "HTLC funding not seen on chain yet; bump its fee on the node if it is stuck"
+            ),
+            Ok(WalletTxStatus::Confirmed) => warn!(
+                swap = %record.swap_id,
+                txid,
+                backend = self.chain.name(),
+                "HTLC funding confirmed but not shown by the chain backend"
+            ),
+            Err(e) => warn!(
+                alert = "onchain_htlc_funding_unknown",
+                swap = %record.swap_id,
+                txid,
+                error = %e,
+                "HTLC funding not seen and the node's wallet cannot say whether it still has it; not sending again"
+            ),
+        }
+        // Checked again after another `RESEND_SECS`.
+        self.store
+            .update_htlc(&record.swap_id, record.status, None, None, now)?;
+        Ok(Progress::Pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::{
+        ln::{ChannelBalance, Description, Invoice, InvoiceLookup, PaymentStatus},
+        network::LnNetwork,
+        vault::Vault,
+    };
+
+    const TIP: u32 = 800_000;
+
+    /// A backend that shows nothing, as one lagging, reorganised or past its window does.
+    struct Blind;
+
+    impl ChainBackend for Blind {
+        fn name(&self) -> &'static str {
+            "blind"
+        }
+
+        async fn tip_height(&self) -> Result<u32, ChainError> {
+            Ok(TIP)
+        }
+
+        async fn history(&self, _: &Script) -> Result<Vec<ChainTx>, ChainError> {
+            Ok(Vec::new())
+        }
+
+        async fn broadcast(&self, _: &Transaction) -> Result<Txid, ChainError> {
+            Err(ChainError::Rejected("nothing is broadcast here".into()))
+        }
+
+        async fn fee_rate(&self, _: u16) -> Result<Option<u64>, ChainError> {
+            Ok(None)
+        }
+
+        async fn fee(&self, _: &ChainTx) -> Result<u64, ChainError> {
+            Ok(0)
+        }
+    }
+
+    /// A node whose wallet reports `status` for any transaction, and which records its sends.
+    struct Wallet {
+        status: WalletTxStatus,
+        sends: Mutex<Vec<String>>,
+    }
+
+    impl Wallet {
+        fn reporting(status: WalletTxStatus) -> Self {
+            Self {
+                status,
+                sends: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn sends(&self) -> usize {
+            self.sends.lock().unwrap().len()
+        }
+    }
+
+    impl LnBackend for Wallet {
+        async fn create_invoice(&self, _: u64, _: &[u8; 32], _: Description<'_>, _: u64) -> Result<Invoice, LnError> {
+            Err(LnError::Unsupported("invoices"))
+        }
+
+        async fn lookup_invoice(&self, _: &[u8; 32]) -> Result<Option<InvoiceLookup>, LnError> {
+            Ok(None)
+        }
+
+        async fn pay_invoice(&self, _: &str, _: u64, _: u32) -> Result<(), LnError> {
+            Err(LnError::Unsupported("payments"))
+        }
+
+        async fn payment_status(&self, _: &[u8; 32]) -> Result<Option<PaymentStatus>, LnError> {
+            Ok(None)
+        }
+
+        async fn channel_balance(&self) -> Result<ChannelBalance, LnError> {
+            Ok(ChannelBalance::default())
+        }
+
+        async fn network(&self) -> Result<LnNetwork, LnError> {
+            Ok(LnNetwork::Regtest)
+        }
+
+        async fn send_onchain(&self, address: &str, _: u64) -> Result<String, LnError> {
+            let mut sends = self.sends.lock().unwrap();
+            sends.push(address.to_string());
+            Ok(format!("{:064x}", sends.len()))
+        }
+
+        async fn wallet_tx(&self, _: &str) -> Result<WalletTxStatus, LnError> {
+            Ok(self.status)
+        }
+    }
+
+    /// A fallback over [`Blind`] holding the HTLC of a new swap, funded by transaction `ab…ab` with its output
+    /// `funding_vout` as last recorded, longer than `RESEND_SECS` ago.
+    fn funded(funding_vout: Option<u32>) -> (Fallback<Blind>, Swap) {
+        let store = Store::open(":memory:", Vault::new(&[7; 32])).unwrap();
+        let swap = Swap::ln_to_usdt(Pubkey::new_unique(), 100_000_000, 50_000_000, false);
+        let htlc = Htlc {
+            payment_hash: swap.payment_hash,
+            claim_key: htlc::refund_key().1,
+            refund_key: htlc::refund_key().1,
+            timeout_height: TIP + 144,
+        };
+        let address = htlc.address(Network::Regtest).to_string();
+        let then = unix_now() - RESEND_SECS - 1;
+        store
+            .insert_htlc(&HtlcRecord {
+                swap_id: swap.id.clone(),
+                htlc,
+                amount_sat: 100_000,
+                address,
+                refund_secret: Vec::new(),
+                status: HtlcStatus::Funded,
+                funding_txid: Some("ab".repeat(32)),
+                funding_vout,
+                spend_txid: None,
+                created_at: then,
+                updated_at: then,
+            })
+            .unwrap();
+        let cfg = OnchainConfig {
+            network: Network::Regtest,
+            after_ln_failures: 3,
+            min_timeout_blocks: 36,
+            refund_script: ScriptBuf::new(),
+            confirmation_target: 6,
+            min_fee_rate_sat_vb: 1,
+        };
+        (Fallback::new(Arc::new(store), Blind, cfg), swap)
+    }
+
+    #[tokio::test]
+    async fn funding_seen_before_is_not_sent_again_once_out_of_view() {
+        // Even a wallet that no longer lists it does not outweigh an output once seen on chain.
+        let (fallback, swap) = funded(Some(0));
+        let ln = Wallet::reporting(WalletTxStatus::Gone);
+        assert_eq!(fallback.advance(&ln, &swap).await.unwrap(), Progress::Pending);
+        assert_eq!(ln.sends(), 0);
+    }
+
+    #[tokio::test]
+    async fn funding_the_wallet_still_has_is_not_sent_again() {
+        for status in [WalletTxStatus::Unconfirmed, WalletTxStatus::Confirmed] {
+            let (fallback, swap) = funded(None);
+            let ln = Wallet::reporting(status);
+            assert_eq!(fallback.advance(&ln, &swap).await.unwrap(), Progress::Pending);
+            assert_eq!(ln.sends(), 0, "{status:?}");
+            let record = fallback.store.htlc(&swap.id).unwrap().unwrap();
+            assert_eq!(record.funding_txid, Some("ab".repeat(32)));
+        }
+    }
+
+    #[tokio::test]
+    async fn funding_dropped_from_the_mempool_is_sent_again_once() {
+        let (fallback, swap) = funded(None);
+        let ln = Wallet::reporting(WalletTxStatus::Gone);
+        assert_eq!(fallback.advance(&ln, &swap).await.unwrap(), Progress::Pending);
+        assert_eq!(ln.sends(), 1);
+        let record = fallback.store.htlc(&swap.id).unwrap().unwrap();
+        assert_eq!(record.funding_txid, Some(format!("{:064x}", 1)));
+        assert_eq!(record.funding_vout, None);
+        // The new send is given `RESEND_SECS` to show up before it is looked up in turn.
+        assert_eq!(fallback.advance(&ln, &swap).await.unwrap(), Progress::Pending);
+        assert_eq!(ln.sends(), 1);
+    }
+}
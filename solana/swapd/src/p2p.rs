@@ -0,0 +1,283 @@
+//! libp2p transport for [`negotiate`](crate::negotiate), so makers and takers can find each other without a
+//! relay or an HTTP endpoint. Offers, signed by the operator key, are gossiped on [`OFFER_TOPIC`], and
+//! gossipsub signs every message with the sending node's peer identity too. Takes, status requests and swap
+//! updates are JSON request/response exchanges over [`NEGOTIATE_PROTOCOL`] on noise-encrypted connections, so
+//! the peer id a request arrives from is authenticated and serves as the session's peer address.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use libp2p::{
+    futures::StreamExt,
+    gossipsub::{self, IdentTopic, MessageAuthenticity},
+    identity, noise,
+    request_response::{self, json, OutboundRequestId, ProtocolSupport},
+    swarm::{NetworkBehaviour, SwarmEvent},
+    tcp, yamux, Multiaddr, PeerId, StreamProtocol, SwarmBuilder,
+};
+use serde_json::Value;
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+use crate::{
+    ln::LnBackend,
+    negotiate::{Maker, Offer, Update},
+    swap::{unix_now, Direction},
+};
+
+/// Gossipsub topic carrying every maker's offers.
+pub const OFFER_TOPIC: &str = "intercom-swap/offers/v1";
+pub const NEGOTIATE_PROTOCOL: &str = "/intercom-swap/negotiate/1";
+/// Negotiator transport name.
+pub const TRANSPORT: &str = "libp2p";
+
+#[derive(Debug)]
+pub enum P2pError {
+    Identity(String),
+    Io(std::io::Error),
+    Transport(String),
+}
+
+impl fmt::Display for P2pError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Identity(e) => write!(f, "invalid p2p identity: {e}"),
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Transport(e) => write!(f, "p2p: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for P2pError {}
+
+impl From<std::io::Error> for P2pError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Loads the node's peer identity from `path`, generating an ed25519 key there on first start so the peer id
+/// stays the same across restarts.
+pub fn load_identity(path: &Path) -> Result<identity::Keypair, P2pError> {
+    match std::fs::read(path) {
+        Ok(bytes) => identity::Keypair::from_protobuf_encoding(&bytes).map_err(|e| P2pError::Identity(e.to_string())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let keypair = identity::Keypair::generate_ed25519();
+            let bytes = keypair
+                .to_protobuf_encoding()
+                .map_err(|e| P2pError::Identity(e.to_string()))?;
+            std::fs::write(path, bytes)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+            }
+            info!(path = %path.display(), peer_id = %keypair.public().to_peer_id(), "generated p2p identity");
+            Ok(keypair)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub struct P2pConfig {
+    pub identity: identity::Keypair,
+    pub listen: Vec<Multiaddr>,
+    /// Peers dialed at start to join the gossip mesh.
+    pub bootstrap: Vec<Multiaddr>,
+}
+
+#[derive(NetworkBehaviour)]
+struct Behaviour {
+    gossipsub: gossipsub::Behaviour,
+    negotiate: json::Behaviour<Value, Value>,
+}
+
+/// Another maker's live offer, as last gossiped.
+#[derive(Debug, Clone)]
+pub struct SeenOffer {
+    pub peer: PeerId,
+    pub offer: Offer,
+}
+
+pub struct P2pNode {
+    cfg: P2pConfig,
+    maker: Maker,
+    /// Verified offers from other operators, by operator and direction.
+    seen: Mutex<HashMap<(Pubkey, Direction), SeenOffer>>,
+}
+
+impl P2pNode {
+    pub fn new(cfg: P2pConfig, maker: Maker) -> Self {
+        Self {
+            cfg,
+            maker,
+            seen: Mutex::default(),
+        }
+    }
+
+    pub fn peer_id(&self) -> PeerId {
+        self.cfg.identity.public().to_peer_id()
+    }
+
+    /// Other operators' offers that have not expired.
+    pub fn seen_offers(&self) -> Vec<SeenOffer> {
+        let now = unix_now();
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.retain(|_, s| s.offer.expires_at > now);
+        seen.values().cloned().collect()
+    }
+
+    /// Gossips offers, answers negotiation requests and pushes swap updates until `shutdown` resolves.
+    /// Accepted swaps are picked up by the engine that owns `wake`.
+    pub async fn run<L: LnBackend>(
+        &self,
+        ln: &L,
+        wake: Arc<Notify>,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(), P2pError> {
+        let transport = |e: &dyn fmt::Display| P2pError::Transport(e.to_string());
+        let mut swarm = SwarmBuilder::with_existing_identity(self.cfg.identity.clone())
+            .with_tokio()
+            .with_tcp(tcp::Config::default(), noise::Config::new, yamux::Config::default)
+            .map_err(|e| transport(&e))?
+            .with_behaviour(|key| {
+                let gossip_cfg = gossipsub::ConfigBuilder::default()
+                    .validation_mode(gossipsub::ValidationMode::Strict)
+                    .build()?;
+                Ok(Behaviour {
+                    gossipsub: gossipsub::Behaviour::new(MessageAuthenticity::Signed(key.clone()), gossip_cfg)?,
+                    negotiate: json::Behaviour::new(
+                        [(StreamProtocol::new(NEGOTIATE_PROTOCOL), ProtocolSupport::Full)],
+                        request_response::Config::default(),
+                    ),
+                })
+            })
+            .map_err(|e| transport(&e))?
+            .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(300)))
+            .build();
+        let topic = IdentTopic::new(OFFER_TOPIC);
+        swarm
+            .behaviour_mut()
+            .gossipsub
+            .subscribe(&topic)
+            .map_err(|e| transport(&e))?;
+        for addr in &self.cfg.listen {
+            swarm.listen_on(addr.clone()).map_err(|e| transport(&e))?;
+        }
+        for addr in &self.cfg.bootstrap {
+            if let Err(e) = swarm.dial(addr.clone()) {
+                warn!(%addr, error = %e, "cannot dial p2p bootstrap peer");
+            }
+        }
+        info!(peer_id = %self.peer_id(), "p2p node started");
+
+        let negotiator = &self.maker.negotiator;
+        let mut in_flight: HashMap<OutboundRequestId, Update> = HashMap::new();
+        let mut republish = tokio::time::interval(self.maker.cfg.republish);
+        let mut updates = tokio::time::interval(self.maker.cfg.update_interval);
+        let mut rate = None;
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                _ = republish.tick() => {
+                    rate = self.maker.price().await;
+                    for offer in self.maker.offers(ln, rate).await {
+                        let data = offer.to_json().to_string().into_bytes();
+                        // Fails without mesh peers; the next republish tries again.
+                        if let Err(e) = swarm.behaviour_mut().gossipsub.publish(topic.clone(), data) {
+                            debug!(direction = %offer.direction, error = %e, "offer not gossiped");
+                        }
+                    }
+                }
+                _ = updates.tick() => {
+                    let pending = match negotiator.updates() {
+                        Ok(pending) => pending,
+                        Err(e) => {
+                            warn!(error = %e, "cannot read p2p sessions");
+                            continue;
+                        }
+                    };
+                    for update in pending {
+                        if in_flight.values().any(|u| u.session.swap_id == update.session.swap_id) {
+                            continue;
+                        }
+                        let Ok(peer) = PeerId::from_str(&update.session.peer) else {
+                            warn!(swap = %update.session.swap_id, "p2p session with a malformed peer id");
+                            continue;
+                        };
+                        let id = swarm.behaviour_mut().negotiate.send_request(&peer, update.message.clone());
+                        in_flight.insert(id, update);
+                    }
+                }
+                event = swarm.select_next_some() => match event {
+                    SwarmEvent::Behaviour(BehaviourEvent::Negotiate(request_response::Event::Message {
+                        peer,
+                        message,
+                    })) => match message {
+                        request_response::Message::Request { request, channel, .. } => {
+                            let reply = self.maker.handle(ln, &wake, rate, &peer.to_string(), &request).await;
+                            if swarm.behaviour_mut().negotiate.send_response(channel, reply).is_err() {
+                                warn!(%peer, "p2p peer left before the reply");
+                            }
+                        }
+                        // The taker acknowledged a swap update.
+                        request_response::Message::Response { request_id, .. } => {
+                            if let Some(update) = in_flight.remove(&request_id) {
+                                if let Err(e) = negotiator.delivered(&update) {
+                                    warn!(swap = %update.session.swap_id, error = %e, "cannot record p2p update");
+                                }
+                            }
+                        }
+                    },
+                    SwarmEvent::Behaviour(BehaviourEvent::Negotiate(request_response::Event::OutboundFailure {
+                        peer,
+                        request_id,
+                        error,
+                    })) => {
+                        // Retried next interval.
+                        if let Some(update) = in_flight.remove(&request_id) {
+                            debug!(%peer, swap = %update.session.swap_id, error = %error, "p2p update not delivered");
+                        }
+                    }
+                    SwarmEvent::Behaviour(BehaviourEvent::Gossipsub(gossipsub::Event::Message {
+                        propagation_source,
+                        message,
+                        ..
+                    })) => self.on_offer(propagation_source, message.source, &message.data),
+                    SwarmEvent::NewListenAddr { address, .. } => info!(%address, "p2p listening"),
+                    _ => {}
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Records a gossiped offer from another operator once its signature and expiry check out.
+    fn on_offer(&self, via: PeerId, author: Option<PeerId>, data: &[u8]) {
+        let offer = match serde_json::from_slice::<Value>(data) {
+            Ok(v) => Offer::from_json(&v).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+        .and_then(|offer| offer.verify(unix_now()).map(|()| offer).map_err(|e| e.to_string()));
+        let offer = match offer {
+            Ok(offer) => offer,
+            Err(e) => return debug!(%via, error = %e, "invalid offer ignored"),
+        };
+        if offer.operator == self.maker.negotiator.operator.pubkey() {
+            return;
+        }
+        debug!(operator = %offer.operator, direction = %offer.direction, "offer seen");
+        let peer = author.unwrap_or(via);
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.insert((offer.operator, offer.direction), SeenOffer { peer, offer });
+    }
+}
@@ -0,0 +1,441 @@
+//! Profit and loss of the swaps completed out of those created in a [`Period`], per swap and per UTC day. Each
+//! swap earns the operator fee the [`ledger`](crate::ledger) booked for it and costs the escrow fees and
+//! Lightning routing fees it paid; routing fees are converted to tokens at the swap's own price. Swaps are also
+//! marked with the BTC the [`hedge`](crate::hedge)r recorded for them, and credited or charged what hedging it
+//! made: the hedge orders' fills are matched to the swaps' first in, first out, and each swap gains the
+//! difference between the price its BTC was hedged at and its own price, less the venue's commission. The
+//! operator's inventory (wallet, escrows, channels) and the position left unhedged are marked to market at the
+//! price given to [`report`].
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    str::FromStr,
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    export::{self, Period, SECS_PER_DAY},
+    hedge::{HedgeOrder, Side},
+    ledger::{Account, Asset},
+    store::{Store, StoreError},
+    swap::{Direction, Swap, SwapState},
+};
+
+const MSAT_PER_BTC: u128 = 100_000_000_000;
+
+const SWAPS_HEADER: &str = "swap_id,direction,created_at,amount_msat,token_amount,fee_tokens,escrow_fee_tokens,\
+                            routing_fee_msat,routing_fee_tokens,net_tokens,hedge_fill_msat,hedge_price_token_per_btc,\
+                            hedge_fee_tokens,hedge_tokens\n";
+const DAYS_HEADER: &str = "day,swaps,volume_msat,fee_tokens,escrow_fee_tokens,routing_fee_msat,routing_fee_tokens,\
+                           net_tokens,hedged_msat,hedge_fee_tokens,hedge_tokens\n";
+
+/// What one completed swap earned and cost the operator. Token amounts are base units.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapPnl {
+    pub swap_id: String,
+    pub direction: Direction,
+    pub created_at: i64,
+    pub amount_msat: u64,
+    pub token_amount: u64,
+    pub fee_tokens: i64,
+    /// Platform and trade fees paid out of the operator's escrow (ln-to-usdt).
+    pub escrow_fee_tokens: i64,
+    /// usdt-to-ln: what the route took to pay the invoice.
+    pub routing_fee_msat: i64,
+    pub routing_fee_tokens: i64,
+    /// BTC the swap left to hedge; `None` if hedging was off when it completed.
+    pub hedge_fill_msat: Option<i64>,
+    /// Average price the hedge orders matched to the swap filled at; `None` while none has.
+    pub hedge_price_token_per_btc: Option<u64>,
+    /// The venue's commission on those orders, pro rata.
+    pub hedge_fee_tokens: i64,
+    /// What hedging made against the swap's own price, commission included; a loss when negative.
+    pub hedge_tokens: i64,
+}
+
+impl SwapPnl {
+    fn of(store: &Store, swap: &Swap, hedge: Hedge) -> Result<Self, StoreError> {
+        let balance = |account| store.ledger_swap_balance(&swap.id, account);
+        let routing_fee_msat = balance(Account::RoutingFees)?;
+        // Token base units per msat, as the swap was priced.
+        let routing_fee_tokens = match swap.amount_msat {
+            0 => 0,
+            amount => {
+                let tokens = i128::from(routing_fee_msat) * i128::from(swap.token_amount) / i128::from(amount);
+                i64::try_from(tokens).unwrap_or(i64::MAX)
+            }
+        };
+        // The hedged BTC at the swap's price, against what the hedge orders got or paid for it.
+        let at_swap_price = match swap.amount_msat {
+            0 => 0,
+            amount => i128::from(hedge.msat) * i128::from(swap.token_amount) / i128::from(amount),
+        };
+        let traded = match swap.direction {
+            Direction::LnToUsdt => i128::from(hedge.tokens) - at_swap_price,
+            Direction::UsdtToLn => at_swap_price - i128::from(hedge.tokens),
+        };
+        let hedge_tokens = i64::try_from(traded - i128::from(hedge.fee_tokens)).unwrap_or(i64::MIN);
+        Ok(Self {
+            swap_id: swap.id.clone(),
+            direction: swap.direction,
+            created_at: swap.created_at,
+            amount_msat: swap.amount_msat,
+            token_amount: swap.token_amount,
+            // Fee income is credited, so it shows as a negative balance.
+            fee_tokens: -balance(Account::FeeIncome)?,
+            escrow_fee_tokens: balance(Account::EscrowFees)?,
+            routing_fee_msat,
+            routing_fee_tokens,
+            hedge_fill_msat: store.hedge_fill(&swap.id)?,
+            hedge_price_token_per_btc: hedge.price_token_per_btc(),
+            hedge_fee_tokens: i64::try_from(hedge.fee_tokens).unwrap_or(i64::MAX),
+            hedge_tokens,
+        })
+    }
+
+    /// Fee less the fees paid to complete the swap, plus what hedging it made.
+    pub fn net_tokens(&self) -> i64 {
+        self.fee_tokens
+            .saturating_sub(self.escrow_fee_tokens)
+            .saturating_sub(self.routing_fee_tokens)
+            .saturating_add(self.hedge_tokens)
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "swapId": self.swap_id,
+            "direction": self.direction.to_string(),
+            "createdAt": self.created_at,
+            "amountMsat": self.amount_msat,
+            "tokenAmount": self.token_amount,
+            "feeTokens": self.fee_tokens,
+            "escrowFeeTokens": self.escrow_fee_tokens,
+            "routingFeeMsat": self.routing_fee_msat,
+            "routingFeeTokens": self.routing_fee_tokens,
+            "netTokens": self.net_tokens(),
+            "hedgeFillMsat": self.hedge_fill_msat,
+            "hedgePriceTokenPerBtc": self.hedge_price_token_per_btc,
+            "hedgeFeeTokens": self.hedge_fee_tokens,
+            "hedgeTokens": self.hedge_tokens,
+        })
+    }
+}
+
+/// Sums of [`SwapPnl`]s, for a day or the whole period.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub swaps: u64,
+    pub volume_msat: u64,
+    pub fee_tokens: i64,
+    pub escrow_fee_tokens: i64,
+    pub routing_fee_msat: i64,
+    pub routing_fee_tokens: i64,
+    /// Net of the hedged fills: long after ln-to-usdt swaps, short after usdt-to-ln ones.
+    pub hedged_msat: i64,
+    pub hedge_fee_tokens: i64,
+    pub hedge_tokens: i64,
+}
+
+impl Tally {
+    fn add(&mut self, swap: &SwapPnl) {
+        self.swaps += 1;
+        self.volume_msat = self.volume_msat.saturating_add(swap.amount_msat);
+        self.fee_tokens = self.fee_tokens.saturating_add(swap.fee_tokens);
+        self.escrow_fee_tokens = self.escrow_fee_tokens.saturating_add(swap.escrow_fee_tokens);
+        self.routing_fee_msat = self.routing_fee_msat.saturating_add(swap.routing_fee_msat);
+        self.routing_fee_tokens = self.routing_fee_tokens.saturating_add(swap.routing_fee_tokens);
+        self.hedged_msat = self.hedged_msat.saturating_add(swap.hedge_fill_msat.unwrap_or(0));
+        self.hedge_fee_tokens = self.hedge_fee_tokens.saturating_add(swap.hedge_fee_tokens);
+        self.hedge_tokens = self.hedge_tokens.saturating_add(swap.hedge_tokens);
+    }
+
+    pub fn net_tokens(&self) -> i64 {
+        self.fee_tokens
+            .saturating_sub(self.escrow_fee_tokens)
+            .saturating_sub(self.routing_fee_tokens)
+            .saturating_add(self.hedge_tokens)
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "swaps": self.swaps,
+            "volumeMsat": self.volume_msat,
+            "feeTokens": self.fee_tokens,
+            "escrowFeeTokens": self.escrow_fee_tokens,
+            "routingFeeMsat": self.routing_fee_msat,
+            "routingFeeTokens": self.routing_fee_tokens,
+            "netTokens": self.net_tokens(),
+            "hedgedMsat": self.hedged_msat,
+            "hedgeFeeTokens": self.hedge_fee_tokens,
+            "hedgeTokens": self.hedge_tokens,
+        })
+    }
+}
+
+/// The part of a swap's fill that hedge orders offset, and what they paid or received for it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Hedge {
+    msat: u64,
+    /// Received for it by sells, paid by buys.
+    tokens: u64,
+    fee_tokens: u64,
+}
+
+impl Hedge {
+    fn price_token_per_btc(&self) -> Option<u64> {
+        match self.msat {
+            0 => None,
+            msat => Some(u64::try_from(u128::from(self.tokens) * MSAT_PER_BTC / u128::from(msat)).unwrap_or(u64::MAX)),
+        }
+    }
+}
+
+/// Matches what the hedge orders filled to the recorded swap fills, first in, first out, by swap id. A fill first
+/// nets against older fills the other way, which leaves nothing to trade; an order offsets the oldest fills on
+/// the side it hedges at its own average price, sharing its commission pro rata, and what it filled beyond them
+/// is not matched.
+fn hedges(store: &Store) -> Result<HashMap<String, Hedge>, StoreError> {
+    let mut orders: Vec<HedgeOrder> = store.hedge_orders(None)?;
+    orders.retain(|o| o.execution.filled_sat > 0);
+    orders.reverse();
+    let mut orders = orders.into_iter().peekable();
+    // Fills not yet offset, oldest first; all long or all short, as opposite ones net.
+    let mut open = VecDeque::<(String, i64)>::new();
+    let mut hedges = HashMap::<String, Hedge>::new();
+    for (swap_id, fill_msat, recorded_at) in store.hedge_fills()? {
+        // An order placed in the second a fill was recorded came after it.
+        while let Some(order) = orders.next_if(|o| o.created_at < recorded_at) {
+            offset(&mut open, &order, &mut hedges);
+        }
+        let mut left = fill_msat;
+        while let Some((_, lot)) = open.front_mut().filter(|(_, lot)| lot.signum() == -left.signum()) {
+            let n = lot.abs().min(left.abs());
+            *lot += n * left.signum();
+            left -= n * left.signum();
+            if *lot == 0 {
+                open.pop_front();
+            }
+        }
+        if left != 0 {
+            open.push_back((swap_id, left));
+        }
+    }
+    for order in orders {
+        offset(&mut open, &order, &mut hedges);
+    }
+    Ok(hedges)
+}
+
+/// Offsets the oldest `open` fills with what `order` filled.
+fn offset(open: &mut VecDeque<(String, i64)>, order: &HedgeOrder, hedges: &mut HashMap<String, Hedge>) {
+    let execution = order.execution;
+    let filled = i64::try_from(execution.filled_sat.saturating_mul(1000)).unwrap_or(i64::MAX);
+    // A sell offsets long fills, a buy short ones.
+    let sign = match order.side {
+        Side::Sell => 1,
+        Side::Buy => -1,
+    };
+    let share = |total: u64, msat: i64| (u128::from(total) * msat as u128 / filled as u128) as u64;
+    let mut left = filled;
+    while let Some((swap_id, lot)) = open.front_mut().filter(|(_, lot)| lot.signum() == sign) {
+        let n = lot.abs().min(left);
+        let hedge = hedges.entry(swap_id.clone()).or_default();
+        hedge.msat += n as u64;
+        hedge.tokens += share(execution.cost_tokens, n);
+        hedge.fee_tokens += share(execution.fee_tokens, n);
+        *lot -= n * sign;
+        left -= n;
+        if *lot == 0 {
+            open.pop_front();
+        }
+        if left == 0 {
+            break;
+        }
+    }
+}
+
+/// What the operator holds per the ledger at the time of the report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Inventory {
+    pub wallet_tokens: i64,
+    /// Tokens in escrows that come to the operator.
+    pub escrow_tokens: i64,
+    pub channels_msat: i64,
+    /// The hedger's open position; 0 without hedging.
+    pub unhedged_msat: i64,
+    /// Mid-market token base units per BTC the BTC is marked at; `None` leaves it unvalued.
+    pub mark_token_per_btc: Option<u64>,
+}
+
+impl Inventory {
+    fn load(store: &Store, mark_token_per_btc: Option<u64>) -> Result<Self, StoreError> {
+        Ok(Self {
+            wallet_tokens: store.ledger_balance(Account::Wallet, Asset::Token)?,
+            escrow_tokens: store.ledger_balance(Account::Escrow, Asset::Token)?,
+            channels_msat: store.ledger_balance(Account::Channels, Asset::Msat)?,
+            unhedged_msat: store.hedge_position()?.unhedged_msat(),
+            mark_token_per_btc,
+        })
+    }
+
+    /// `msat` in token base units at the mark.
+    pub fn marked(&self, msat: i64) -> Option<i64> {
+        let mark = i128::from(self.mark_token_per_btc?);
+        i64::try_from(i128::from(msat) * mark / MSAT_PER_BTC as i128).ok()
+    }
+
+    /// Everything held, in token base units.
+    pub fn value_tokens(&self) -> Option<i64> {
+        let channels = self.marked(self.channels_msat)?;
+        Some(
+            self.wallet_tokens
+                .saturating_add(self.escrow_tokens)
+                .saturating_add(channels),
+        )
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "walletTokens": self.wallet_tokens,
+            "escrowTokens": self.escrow_tokens,
+            "channelsMsat": self.channels_msat,
+            "channelsTokens": self.marked(self.channels_msat),
+            "unhedgedMsat": self.unhedged_msat,
+            "unhedgedTokens": self.marked(self.unhedged_msat),
+            "markTokenPerBtc": self.mark_token_per_btc,
+            "valueTokens": self.value_tokens(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub period: Period,
+    /// Oldest first.
+    pub swaps: Vec<SwapPnl>,
+    /// Start of each UTC day with a completed swap, and its tally.
+    pub days: Vec<(i64, Tally)>,
+    pub total: Tally,
+    pub inventory: Inventory,
+}
+
+/// P&L of the swaps created in `period` that completed, with the inventory marked at `mark_token_per_btc`.
+pub fn report(store: &Store, period: &Period, mark_token_per_btc: Option<u64>) -> Result<Report, StoreError> {
+    let mut swaps = Vec::new();
+    let mut days = BTreeMap::<i64, Tally>::new();
+    let mut total = Tally::default();
+    let hedges = hedges(store)?;
+    for swap in store.swaps_created_between(period.from, period.to)? {
+        if swap.state != SwapState::Completed {
+            continue;
+        }
+        let pnl = SwapPnl::of(store, &swap, hedges.get(&swap.id).copied().unwrap_or_default())?;
+        let day = swap.created_at - swap.created_at.rem_euclid(SECS_PER_DAY);
+        days.entry(day).or_default().add(&pnl);
+        total.add(&pnl);
+        swaps.push(pnl);
+    }
+    Ok(Report {
+        period: *period,
+        swaps,
+        days: days.into_iter().collect(),
+        total,
+        inventory: Inventory::load(store, mark_token_per_btc)?,
+    })
+}
+
+/// The rows of a P&L CSV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakdown {
+    Day,
+    Swap,
+}
+
+impl FromStr for Breakdown {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day" => Ok(Self::Day),
+            "swap" => Ok(Self::Swap),
+            other => Err(format!("unknown breakdown {other:?}: expected day or swap")),
+        }
+    }
+}
+
+impl Report {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "period": self.period.to_string(),
+            "total": self.total.to_json(),
+            "days": self
+                .days
+                .iter()
+                .map(|(day, tally)| {
+                    let mut json = tally.to_json();
+                    json["day"] = export::date(*day).into();
+                    json
+                })
+                .collect::<Vec<_>>(),
+            "swaps": self.swaps.iter().map(SwapPnl::to_json).collect::<Vec<_>>(),
+            "inventory": self.inventory.to_json(),
+        })
+    }
+
+    /// One row per day or per swap; the inventory is left out, as it is not per period.
+    pub fn csv(&self, breakdown: Breakdown) -> String {
+        let rows: Vec<Vec<String>> = match breakdown {
+            Breakdown::Day => self
+                .days
+                .iter()
+                .map(|(day, t)| {
+                    vec![
+                        export::date(*day),
+                        t.swaps.to_string(),
+                        t.volume_msat.to_string(),
+                        t.fee_tokens.to_string(),
+                        t.escrow_fee_tokens.to_string(),
+                        t.routing_fee_msat.to_string(),
+                        t.routing_fee_tokens.to_string(),
+                        t.net_tokens().to_string(),
+                        t.hedged_msat.to_string(),
+                        t.hedge_fee_tokens.to_string(),
+                        t.hedge_tokens.to_string(),
+                    ]
+                })
+                .collect(),
+            Breakdown::Swap => self
+                .swaps
+                .iter()
+                .map(|s| {
+                    vec![
+                        s.swap_id.clone(),
+                        s.direction.to_string(),
+                        export::timestamp(s.created_at),
+                        s.amount_msat.to_string(),
+                        s.token_amount.to_string(),
+                        s.fee_tokens.to_string(),
+                        s.escrow_fee_tokens.to_string(),
+                        s.routing_fee_msat.to_string(),
+                        s.routing_fee_tokens.to_string(),
+                        s.net_tokens().to_string(),
+                        s.hedge_fill_msat.map(|f| f.to_string()).unwrap_or_default(),
+                        s.hedge_price_token_per_btc.map(|p| p.to_string()).unwrap_or_default(),
+                        s.hedge_fee_tokens.to_string(),
+                        s.hedge_tokens.to_string(),
+                    ]
+                })
+                .collect(),
+        };
+        let mut out = String::from(match breakdown {
+            Breakdown::Day => DAYS_HEADER,
+            Breakdown::Swap => SWAPS_HEADER,
+        });
+        for row in rows {
+            out.push_str(&row.iter().map(|f| export::escape(f)).collect::<Vec<_>>().join(","));
+            out.push('\n');
+        }
+        out
+    }
+}
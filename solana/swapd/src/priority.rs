@@ -0,0 +1,101 @@
+//! Adaptive compute unit price for claims. Each attempt is priced off the fees recently paid on the accounts a
+//! claim writes, at a percentile that rises with [`Urgency`], then scaled up while our own transactions have
+//! been failing to land and again for every resubmission of the same claim. The configured fee is the floor.
+
+use std::{collections::VecDeque, sync::Mutex};
+
+use intercom_swap_client::{
+    client::EscrowClient,
+    fees::{FeeBounds, PriorityLevel},
+    state::EscrowState,
+};
+use solana_sdk::pubkey::Pubkey;
+use tracing::debug;
+
+use crate::{jito::Urgency, metrics::metrics};
+
+/// Send outcomes the landing rate is taken over.
+const LANDING_WINDOW: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeControl {
+    /// Never pay less than this, micro-lamports per compute unit.
+    pub min_micro_lamports: u64,
+    /// Nor more than this, however close the deadline.
+    pub max_micro_lamports: u64,
+}
+
+/// Prices claims and tracks how our transactions land.
+pub struct FeeController {
+    cfg: FeeControl,
+    /// Latest outcomes, newest last: whether each signature landed.
+    landed: Mutex<VecDeque<bool>>,
+}
+
+impl FeeController {
+    pub fn new(cfg: FeeControl) -> Self {
+        Self {
+            cfg,
+            landed: Mutex::new(VecDeque::with_capacity(LANDING_WINDOW)),
+        }
+    }
+
+    /// Records whether one of our signatures landed (or expired unseen).
+    pub fn observe(&self, landed: bool) {
+        let mut outcomes = self.landed.lock().expect("landing window lock poisoned");
+        if outcomes.len() == LANDING_WINDOW {
+            outcomes.pop_front();
+        }
+        outcomes.push_back(landed);
+    }
+
+    /// Share of recent signatures that landed; 1.0 before any were sent.
+    pub fn landing_rate(&self) -> f64 {
+        let outcomes = self.landed.lock().expect("landing window lock poisoned");
+        if outcomes.is_empty() {
+            return 1.0;
+        }
+        outcomes.iter().filter(|&&landed| landed).count() as f64 / outcomes.len() as f64
+    }
+
+    /// Compute unit price of the `attempt`th (from 0) claim of `escrow`, `urgency` from its refund_after.
+    pub async fn claim_price(
+        &self,
+        client: &EscrowClient,
+        escrow: &EscrowState,
+        fee_payer: &Pubkey,
+        urgency: Urgency,
+        attempt: u32,
+    ) -> u64 {
+        let level = match urgency {
+            Urgency::Normal => PriorityLevel::Medium,
+            Urgency::Elevated => PriorityLevel::High,
+            Urgency::Critical => PriorityLevel::Urgent,
+        };
+        let bounds = FeeBounds {
+            min_micro_lamports: self.cfg.min_micro_lamports,
+            max_micro_lamports: self.cfg.max_micro_lamports.max(self.cfg.min_micro_lamports),
+        };
+        let market = match client.recommend_claim_fee(escrow, fee_payer, level).await {
+            Ok(fee) => fee.compute_unit_price_micro_lamports,
+            Err(e) => {
+                debug!(error = %e, "no recent prioritization fees; pricing from the floor");
+                self.cfg.min_micro_lamports
+            }
+        };
+        let landing_rate = self.landing_rate();
+        let landing = if landing_rate < 0.5 {
+            2.0
+        } else if landing_rate < 0.8 {
+            1.5
+        } else {
+            1.0
+        };
+        let resubmission = 1.5f64.powi(attempt.min(16) as i32);
+        let price = (market.clamp(bounds.min_micro_lamports, bounds.max_micro_lamports) as f64 * landing * resubmission)
+            .min(bounds.max_micro_lamports as f64) as u64;
+        metrics().claim_compute_unit_price.set(price as i64);
+        debug!(%urgency, attempt, market, landing_rate, price, "claim priced");
+        price
+    }
+}
@@ -0,0 +1,590 @@
+//! Request for quote: a taker asks for a price on a direction and amount and gets a [`Quote`] signed by the
+//! operator key, binding the rate, fee, escrow terms and an expiry. Accepting it re-verifies the signature and
+//! expiry before the daemon commits anything, so a taker can neither sit on a stale rate nor alter the terms.
+
+use std::fmt;
+
+use rand::RngCore;
+use serde_json::{json, Value};
+use solana_sdk::{hash::hash, pubkey::Pubkey, signature::Signature, signer::Signer};
+use zeroize::Zeroizing;
+
+use crate::{
+    exposure::{Exposure, ExposureLimits},
+    feepolicy::{FeeInputs, FeePolicy, ROUTING_FEE_SAMPLE},
+    journal::Cause,
+    kms::Operator,
+    liquidity::Liquidity,
+    ln::LnError,
+    reputation::ReputationPolicy,
+    screening::{Screening, ScreeningError},
+    store::{Store, StoreError},
+    swap::{unix_now, Direction, Swap},
+    vault::Preimage,
+};
+
+/// Prefix of every signed quote message, so the signature cannot be replayed as anything else.
+const DOMAIN: &[u8] = b"intercom-swap/quote/v2";
+const MSAT_PER_BTC: u128 = 100_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuoteConfig {
+    pub mint: Pubkey,
+    /// Token base units per whole BTC, before the fee.
+    pub token_per_btc: u64,
+    /// Operator fee, taken off the tokens paid out (LnToUsdt) or added to the tokens owed (UsdtToLn).
+    pub fee_bps: u16,
+    /// Token base units of operator fee per swap, on top of `fee_bps`.
+    pub flat_fee: u64,
+    pub ttl_secs: i64,
+    pub min_amount_msat: u64,
+    pub max_amount_msat: u64,
+    /// UsdtToLn: routing fee budget reserved on top of the amount when checking outbound capacity.
+    pub max_routing_fee_bps: u16,
+}
+
+impl QuoteConfig {
+    /// Net token amount the escrow carries for `amount_msat` in `direction`.
+    pub fn token_amount(&self, direction: Direction, amount_msat: u64) -> u64 {
+        let gross = u128::from(amount_msat) * u128::from(self.token_per_btc) / MSAT_PER_BTC;
+        let fee = gross * u128::from(self.fee_bps) / 10_000 + u128::from(self.flat_fee);
+        let net = match direction {
+            Direction::LnToUsdt => gross.saturating_sub(fee),
+            Direction::UsdtToLn => gross + fee,
+        };
+        u64::try_from(net).unwrap_or(u64::MAX)
+    }
+
+    /// LnToUsdt: the largest amount paying out at most `tokens`, inverting [`Self::token_amount`].
+    pub fn max_amount_for_tokens(&self, tokens: u64) -> u64 {
+        let per_btc = u128::from(self.token_per_btc) * u128::from(10_000 - self.fee_bps.min(10_000)) / 10_000;
+        match per_btc {
+            0 => u64::MAX,
+            rate => u64::try_from((u128::from(tokens) + u128::from(self.flat_fee)) * MSAT_PER_BTC / rate)
+                .unwrap_or(u64::MAX),
+        }
+    }
+}
+
+/// What a taker asks a quote for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuoteRequest {
+    pub direction: Direction,
+    pub amount_msat: u64,
+    /// Recipient of the USDT (LnToUsdt) or refund key of the taker's escrow (UsdtToLn).
+    pub counterparty: Pubkey,
+    /// Quote a smaller amount when liquidity cannot fill the whole request, instead of refusing.
+    pub allow_partial: bool,
+    /// usdt-to-ln: node id of the payee, so the route to it can be probed before quoting.
+    pub destination: Option<[u8; 33]>,
+}
+
+/// Signed terms of one swap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quote {
+    pub id: [u8; 16],
+    pub direction: Direction,
+    pub amount_msat: u64,
+    pub token_per_btc: u64,
+    pub fee_bps: u16,
+    pub flat_fee: u64,
+    /// Net tokens paid out (LnToUsdt) or the least the taker's escrow must hold (UsdtToLn).
+    pub token_amount: u64,
+    pub counterparty: Pubkey,
+    pub mint: Pubkey,
+    /// The operator's escrow and invoice use this hash (LnToUsdt). UsdtToLn swaps take the taker's invoice.
+    pub payment_hash: Option<[u8; 32]>,
+    pub expires_at: i64,
+    pub signature: Signature,
+}
+
+/// A quote as the daemon keeps it.
+#[derive(Debug, Clone)]
+pub struct QuoteRecord {
+    pub quote: Quote,
+    /// LnToUsdt: preimage of the quote's payment hash, never shown to the taker.
+    pub preimage: Option<Preimage>,
+    /// Swap the quote was accepted into.
+    pub swap_id: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum QuoteError {
+    AmountOutOfRange {
+        min: u64,
+        max: u64,
+    },
+    TooSmall,
+    /// One of the legs cannot be filled for the requested amount.
+    InsufficientLiquidity {
+        max_amount_msat: u64,
+    },
+    /// The counterparty's track record or screening rules it out.
+    Rejected(String),
+    /// The daemon already has as much outstanding as its [`ExposureLimits`] allow.
+    OverLimit(String),
+    Malformed(String),
+    BadSignature,
+    Expired {
+        expires_at: i64,
+    },
+    /// Not a quote this daemon issued, or its terms differ from what was issued.
+    Unknown,
+    AlreadyAccepted {
+        swap_id: String,
+    },
+    /// The taker's invoice does not match the quote.
+    Invoice(String),
+    /// usdt-to-ln: the operator's node found no route to the payee within the routing fee budget.
+    NoRoute(String),
+    Store(StoreError),
+}
+
+impl fmt::Display for QuoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AmountOutOfRange { min, max } => write!(f, "amount must be between {min} and {max} msat"),
+            Self::TooSmall => f.write_str("amount is too small to quote"),
+            Self::InsufficientLiquidity { max_amount_msat } => {
+                write!(
+                    f,
+                    "not enough liquidity; at most {max_amount_msat} msat can be filled now"
+                )
+            }
+            Self::Rejected(reason) => write!(f, "counterparty refused: {reason}"),
+            Self::OverLimit(reason) => write!(f, "over the exposure limits: {reason}"),
+            Self::Malformed(e) => write!(f, "malformed quote: {e}"),
+            Self::BadSignature => f.write_str("quote signature does not verify against the operator key"),
+            Self::Expired { expires_at } => write!(f, "quote expired at {expires_at}"),
+            Self::Unknown => f.write_str("quote was not issued by this daemon"),
+            Self::AlreadyAccepted { swap_id } => write!(f, "quote was already accepted as swap {swap_id}"),
+            Self::Invoice(e) => write!(f, "invoice does not match the quote: {e}"),
+            Self::NoRoute(reason) => write!(f, "cannot pay the invoice: {reason}"),
+            Self::Store(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for QuoteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Store(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<StoreError> for QuoteError {
+    fn from(e: StoreError) -> Self {
+        Self::Store(e)
+    }
+}
+
+impl From<ScreeningError> for QuoteError {
+    fn from(e: ScreeningError) -> Self {
+        match e {
+            ScreeningError::Denied(_) => Self::Rejected(e.to_string()),
+            ScreeningError::Store(e) => Self::Store(e),
+        }
+    }
+}
+
+impl From<LnError> for QuoteError {
+    fn from(e: LnError) -> Self {
+        Self::Invoice(e.to_string())
+    }
+}
+
+impl Quote {
+    /// The operator's fee in token base units: the gap between the net `token_amount` and the gross at the
+    /// quoted rate.
+    pub fn fee_tokens(&self) -> u64 {
+        let gross = u128::from(self.amount_msat) * u128::from(self.token_per_btc) / MSAT_PER_BTC;
+        let fee = gross * u128::from(self.fee_bps) / 10_000 + u128::from(self.flat_fee);
+        u64::try_from(fee).unwrap_or(u64::MAX)
+    }
+
+    /// Bytes the operator signs: every term, fixed width, after [`DOMAIN`].
+    pub fn message(&self) -> Vec<u8> {
+        let mut m = Vec::with_capacity(DOMAIN.len() + 168);
+        m.extend_from_slice(DOMAIN);
+        m.extend_from_slice(&self.id);
+        m.push(match self.direction {
+            Direction::LnToUsdt => 0,
+            Direction::UsdtToLn => 1,
+        });
+        m.extend_from_slice(&self.amount_msat.to_le_bytes());
+        m.extend_from_slice(&self.token_per_btc.to_le_bytes());
+        m.extend_from_slice(&self.fee_bps.to_le_bytes());
+        m.extend_from_slice(&self.flat_fee.to_le_bytes());
+        m.extend_from_slice(&self.token_amount.to_le_bytes());
+        m.extend_from_slice(self.counterparty.as_ref());
+        m.extend_from_slice(self.mint.as_ref());
+        m.extend_from_slice(&self.payment_hash.unwrap_or_default());
+        m.extend_from_slice(&self.expires_at.to_le_bytes());
+        m
+    }
+
+    /// Checks the signature against `operator` and that the quote is still live at `now`.
+    pub fn verify(&self, operator: &Pubkey, now: i64) -> Result<(), QuoteError> {
+        if !self.signature.verify(operator.as_ref(), &self.message()) {
+            return Err(QuoteError::BadSignature);
+        }
+        if now >= self.expires_at {
+            return Err(QuoteError::Expired {
+                expires_at: self.expires_at,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": hex::encode(self.id),
+            "direction": self.direction.as_str(),
+            "amountMsat": self.amount_msat,
+            "tokenPerBtc": self.token_per_btc,
+            "feeBps": self.fee_bps,
+            "flatFee": self.flat_fee,
+            "tokenAmount": self.token_amount,
+            "counterparty": self.counterparty.to_string(),
+            "mint": self.mint.to_string(),
+            "paymentHash": self.payment_hash.map(hex::encode),
+            "expiresAt": self.expires_at,
+            "signature": self.signature.to_string(),
+        })
+    }
+
+    pub fn from_json(v: &Value) -> Result<Self, QuoteError> {
+        let malformed = |field: &str| QuoteError::Malformed(format!("missing or invalid {field}"));
+        let str_field = |field: &str| v[field].as_str().ok_or_else(|| malformed(field));
+        let u64_field = |field: &str| v[field].as_u64().ok_or_else(|| malformed(field));
+        let payment_hash = match &v["paymentHash"] {
+            Value::Null => None,
+            h => Some(
+                h.as_str()
+                    .and_then(|h| hex::decode(h).ok())
+                    .and_then(|b| <[u8; 32]>::try_from(b).ok())
+                    .ok_or_else(|| malformed("paymentHash"))?,
+            ),
+        };
+        Ok(Self {
+            id: hex::decode(str_field("id")?)
+                .ok()
+                .and_then(|b| b.try_into().ok())
+                .ok_or_else(|| malformed("id"))?,
+            direction: str_field("direction")?.parse().map_err(|_| malformed("direction"))?,
+            amount_msat: u64_field("amountMsat")?,
+            token_per_btc: u64_field("tokenPerBtc")?,
+            fee_bps: u16::try_from(u64_field("feeBps")?).map_err(|_| malformed("feeBps"))?,
+            // Absent from quotes stored before flat fees; they had none.
+            flat_fee: v["flatFee"].as_u64().unwrap_or(0),
+            token_amount: u64_field("tokenAmount")?,
+            counterparty: str_field("counterparty")?
+                .parse()
+                .map_err(|_| malformed("counterparty"))?,
+            mint: str_field("mint")?.parse().map_err(|_| malformed("mint"))?,
+            payment_hash,
+            expires_at: v["expiresAt"].as_i64().ok_or_else(|| malformed("expiresAt"))?,
+            signature: str_field("signature")?.parse().map_err(|_| malformed("signature"))?,
+        })
+    }
+}
+
+/// Issues quotes signed by the operator key and records them in the store.
+pub struct Quoter<'a> {
+    pub cfg: QuoteConfig,
+    pub operator: &'a Operator,
+    pub store: &'a Store,
+    /// Free liquidity to size quotes against; `None` skips the check.
+    pub liquidity: Option<Liquidity>,
+    /// Limits by the counterparty's record; `None` quotes anyone.
+    pub reputation: Option<ReputationPolicy>,
+    /// Caps on what is outstanding, the new quote included.
+    pub limits: ExposureLimits,
+    /// Moves `cfg`'s fee for each quote; the default charges it as it is.
+    pub fees: FeePolicy,
+    /// What Solana transactions pay per compute unit now, for the policy's Solana fee.
+    pub compute_unit_price_micro_lamports: u64,
+    /// usdt-to-ln: routing fee of the route probed to the payee, in bps of the requested amount, charged on top
+    /// of the fee.
+    pub probed_routing_fee_bps: Option<u64>,
+}
+
+impl Quoter<'_> {
+    /// Quotes `request`. The fee is set at the requested amount, and a partial fill keeps it.
+    pub fn quote(&self, request: &QuoteRequest) -> Result<Quote, QuoteError> {
+        if request.amount_msat < self.cfg.min_amount_msat || request.amount_msat > self.cfg.max_amount_msat {
+            return Err(QuoteError::AmountOutOfRange {
+                min: self.cfg.min_amount_msat,
+                max: self.cfg.max_amount_msat,
+            });
+        }
+        let cfg = &self.priced(request)?;
+        let mut amount_msat = request.amount_msat;
+        if let Some(liquidity) = &self.liquidity {
+            let max_amount_msat = liquidity.max_amount_msat(request.direction, cfg);
+            if amount_msat > max_amount_msat {
+                if !request.allow_partial || max_amount_msat < cfg.min_amount_msat {
+                    return Err(QuoteError::InsufficientLiquidity { max_amount_msat });
+                }
+                amount_msat = max_amount_msat;
+            }
+        }
+        if let Some(policy) = &self.reputation {
+            let record = self.store.reputation(&request.counterparty)?;
+            if let Some(max_amount_msat) = policy.limit(&record).map_err(QuoteError::Rejected)? {
+                if amount_msat > max_amount_msat {
+                    if !request.allow_partial || max_amount_msat < cfg.min_amount_msat {
+                        return Err(QuoteError::Rejected(format!(
+                            "at most {max_amount_msat} msat until it has more completed swaps"
+                        )));
+                    }
+                    amount_msat = max_amount_msat;
+                }
+            }
+        }
+        if !self.limits.is_unlimited() {
+            let exposure = Exposure::load(self.store, &request.counterparty)?;
+            let max_amount_msat = self
+                .limits
+                .max_amount_msat(&exposure, request.direction, cfg)
+                .map_err(QuoteError::OverLimit)?;
+            if amount_msat > max_amount_msat {
+                if !request.allow_partial || max_amount_msat < cfg.min_amount_msat {
+                    return Err(QuoteError::OverLimit(format!(
+                        "at most {max_amount_msat} msat fits now"
+                    )));
+                }
+                amount_msat = max_amount_msat;
+            }
+        }
+        let token_amount = cfg.token_amount(request.direction, amount_msat);
+        if token_amount == 0 {
+            return Err(QuoteError::TooSmall);
+        }
+        let mut id = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut id);
+        let preimage = (request.direction == Direction::LnToUsdt).then(|| {
+            let mut preimage = Zeroizing::new([0u8; 32]);
+            rand::rngs::OsRng.fill_bytes(preimage.as_mut_slice());
+            preimage
+        });
+        let mut quote = Quote {
+            id,
+            direction: request.direction,
+            amount_msat,
+            token_per_btc: cfg.token_per_btc,
+            fee_bps: cfg.fee_bps,
+            flat_fee: cfg.flat_fee,
+            token_amount,
+            counterparty: request.counterparty,
+            mint: cfg.mint,
+            payment_hash: preimage.as_ref().map(|p| hash(p.as_slice()).to_bytes()),
+            expires_at: unix_now().saturating_add(cfg.ttl_secs),
+            signature: Signature::default(),
+        };
+        quote.signature = self.operator.sign_message(&quote.message());
+        self.store.insert_quote(&QuoteRecord {
+            quote: quote.clone(),
+            preimage: preimage.map(Preimage::Plain),
+            swap_id: None,
+        })?;
+        Ok(quote)
+    }
+
+    /// `cfg` with the fee the policy charges for `request`.
+    fn priced(&self, request: &QuoteRequest) -> Result<QuoteConfig, QuoteError> {
+        if self.fees.is_static() && self.probed_routing_fee_bps.is_none() {
+            return Ok(self.cfg);
+        }
+        let inputs = FeeInputs {
+            liquidity: self.liquidity,
+            routing_fee_bps: self.store.recent_routing_fee_bps(ROUTING_FEE_SAMPLE)?,
+            probed_routing_fee_bps: self.probed_routing_fee_bps,
+            compute_unit_price_micro_lamports: self.compute_unit_price_micro_lamports,
+        };
+        Ok(self
+            .fees
+            .apply(&self.cfg, request.direction, request.amount_msat, &inputs))
+    }
+}
+
+/// Verifies `quote` against `operator` and the issued record, screens its swap as arriving from `source` and
+/// queues it. UsdtToLn quotes need the taker's `bolt11` for exactly the quoted amount.
+#[allow(clippy::too_many_arguments)]
+pub fn accept(
+    store: &Store,
+    operator: &Pubkey,
+    quote: &Quote,
+    bolt11: Option<&str>,
+    hold: bool,
+    funding_timeout_secs: i64,
+    screening: &Screening,
+    source: &str,
+) -> Result<Swap, QuoteError> {
+    quote.verify(operator, unix_now())?;
+    let record = store.quote(&quote.id)?.ok_or(QuoteError::Unknown)?;
+    if record.quote != *quote {
+        return Err(QuoteError::Unknown);
+    }
+    if let Some(swap_id) = record.swap_id {
+        return Err(QuoteError::AlreadyAccepted { swap_id });
+    }
+    let swap = match quote.direction {
+        Direction::LnToUsdt => {
+            let (Some(payment_hash), Some(preimage)) = (quote.payment_hash, record.preimage) else {
+                return Err(QuoteError::Unknown);
+            };
+            Swap::ln_to_usdt_with_preimage(
+                quote.counterparty,
+                quote.amount_msat,
+                quote.token_amount,
+                hold,
+                payment_hash,
+                preimage,
+            )
+        }
+        Direction::UsdtToLn => {
+            let bolt11 = bolt11.ok_or_else(|| QuoteError::Invoice("usdt-to-ln quotes need the invoice".into()))?;
+            let swap = Swap::usdt_to_ln(bolt11, quote.counterparty, quote.token_amount, funding_timeout_secs)?;
+            if swap.amount_msat != quote.amount_msat {
+                return Err(QuoteError::Invoice(format!(
+                    "invoice is for {} msat, quote for {}",
+                    swap.amount_msat, quote.amount_msat
+                )));
+            }
+            swap
+        }
+    };
+    screening.check(store, &swap, source)?;
+    let cause = Cause::new(source).because(format!("accepted quote {}", hex::encode(quote.id)));
+    if !store.mark_quote_accepted(&quote.id, &swap.id)? || !store.insert(&swap, &cause)? {
+        return Err(QuoteError::AlreadyAccepted { swap_id: swap.id });
+    }
+    Ok(swap)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::Keypair;
+
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn signed(operator: &Operator) -> Quote {
+        let mut quote = Quote {
+            id: [1; 16],
+            direction: Direction::LnToUsdt,
+            amount_msat: 100_000_000,
+            token_per_btc: 65_000_000_000,
+            fee_bps: 50,
+            flat_fee: 10_000,
+            token_amount: 64_665_000,
+            counterparty: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            payment_hash: Some([2; 32]),
+            expires_at: NOW + 60,
+            signature: Signature::default(),
+        };
+        quote.signature = operator.sign_message(&quote.message());
+        quote
+    }
+
+    #[test]
+    fn signed_quote_verifies_until_it_expires() {
+        let operator = Operator::Local(Keypair::new());
+        let quote = signed(&operator);
+        quote.verify(&operator.pubkey(), NOW).unwrap();
+        quote.verify(&operator.pubkey(), NOW + 59).unwrap();
+        for now in [NOW + 60, NOW + 61] {
+            assert!(matches!(
+                quote.verify(&operator.pubkey(), now),
+                Err(QuoteError::Expired { expires_at }) if expires_at == NOW + 60
+            ));
+        }
+    }
+
+    #[test]
+    fn quote_signed_by_another_key_is_refused() {
+        let operator = Operator::Local(Keypair::new());
+        let quote = signed(&Operator::Local(Keypair::new()));
+        assert!(matches!(
+            quote.verify(&operator.pubkey(), NOW),
+            Err(QuoteError::BadSignature)
+        ));
+    }
+
+    #[test]
+    fn every_term_is_signed() {
+        let operator = Operator::Local(Keypair::new());
+        let quote = signed(&operator);
+        let tampered: [fn(&mut Quote); 12] = [
+            |q| q.id[0] ^= 1,
+            |q| q.direction = Direction::UsdtToLn,
+            |q| q.amount_msat += 1,
+            |q| q.token_per_btc += 1,
+            |q| q.fee_bps -= 1,
+            |q| q.flat_fee = 0,
+            |q| q.token_amount += 1,
+            |q| q.counterparty = Pubkey::new_unique(),
+            |q| q.mint = Pubkey::new_unique(),
+            |q| q.payment_hash = Some([3; 32]),
+            |q| q.payment_hash = None,
+            |q| q.expires_at += 3_600,
+        ];
+        for (i, tamper) in tampered.iter().enumerate() {
+            let mut changed = quote.clone();
+            tamper(&mut changed);
+            assert!(
+                matches!(changed.verify(&operator.pubkey(), NOW), Err(QuoteError::BadSignature)),
+                "change {i} went unnoticed"
+            );
+        }
+    }
+
+    #[test]
+    fn message_is_domain_separated() {
+        let quote = signed(&Operator::Local(Keypair::new()));
+        let message = quote.message();
+        assert!(message.starts_with(DOMAIN));
+        assert_eq!(message.len(), DOMAIN.len() + 16 + 1 + 8 * 4 + 2 + 32 * 3 + 8);
+    }
+
+    #[test]
+    fn json_round_trip_keeps_the_signature_valid() {
+        let operator = Operator::Local(Keypair::new());
+        let quote = signed(&operator);
+        let parsed = Quote::from_json(&quote.to_json()).unwrap();
+        assert_eq!(parsed, quote);
+        parsed.verify(&operator.pubkey(), NOW).unwrap();
+
+        let mut json = quote.to_json();
+        json["tokenAmount"] = json!(quote.token_amount * 2);
+        let raised = Quote::from_json(&json).unwrap();
+        assert!(matches!(
+            raised.verify(&operator.pubkey(), NOW),
+            Err(QuoteError::BadSignature)
+        ));
+    }
+
+    #[test]
+    fn malformed_json_is_refused() {
+        let quote = signed(&Operator::Local(Keypair::new()));
+        for (field, value) in [
+            ("id", json!("zz")),
+            ("feeBps", json!(70_000)),
+            ("paymentHash", json!("00")),
+            ("signature", json!("not base58")),
+            ("expiresAt", Value::Null),
+        ] {
+            let mut json = quote.to_json();
+            json[field] = value;
+            assert!(
+                matches!(Quote::from_json(&json), Err(QuoteError::Malformed(m)) if m.contains(field)),
+                "{field}"
+            );
+        }
+    }
+}
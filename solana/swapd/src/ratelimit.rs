@@ -0,0 +1,307 @@
+//! Token-bucket limits on the control API calls that cost the operator something, so a misbehaving client
+//! cannot exhaust quoting capacity or spam escrow creation. Each caller (API key name or token `sub`) and
+//! each client IP has a bucket per [`Action`] holding up to `burst` calls and refilling at `per_minute`; a
+//! call is refused, with the time until a call would pass, while either bucket is empty.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::metrics::metrics;
+
+/// Buckets kept before those of idle clients (full again) are dropped.
+const MAX_BUCKETS: usize = 10_000;
+
+/// A limited call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quote,
+    /// Queueing a swap, which funds or expects an escrow.
+    Create,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Quote => "quote",
+            Self::Create => "create",
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Calls regained per minute.
+    pub per_minute: u32,
+    /// Calls that can be made at once after a quiet spell.
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// `None` (no limit) when `per_minute` is 0.
+    pub fn new(per_minute: u32, burst: u32) -> Option<Self> {
+        (per_minute > 0).then_some(Self {
+            per_minute,
+            burst: burst.max(1),
+        })
+    }
+
+    fn time_for(&self, tokens: f64) -> Duration {
+        Duration::from_secs_f64(tokens * 60.0 / f64::from(self.per_minute))
+    }
+}
+
+/// Limits per action; `None` leaves it unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimits {
+    pub quote: Option<RateLimit>,
+    pub create: Option<RateLimit>,
+}
+
+impl RateLimits {
+    pub fn unlimited() -> Self {
+        Self {
+            quote: None,
+            create: None,
+        }
+    }
+
+    fn get(&self, action: Action) -> Option<RateLimit> {
+        match action {
+            Action::Quote => self.quote,
+            Action::Create => self.create,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimited {
+    pub action: Action,
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "too many {} calls; retry in {}s",
+            self.action,
+            self.retry_after.as_secs_f64().ceil()
+        )
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+/// Whose bucket.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Client {
+    Caller(String),
+    Ip(IpAddr),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    at: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: RateLimit, now: Instant) {
+        let regained = now.duration_since(self.at).as_secs_f64() * f64::from(limit.per_minute) / 60.0;
+        self.tokens = (self.tokens + regained).min(f64::from(limit.burst));
+        self.at = now;
+    }
+}
+
+pub struct RateLimiter {
+    limits: RateLimits,
+    buckets: Mutex<HashMap<(Action, Client), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a call from the buckets of `caller` and `ip`, or from neither if one is empty.
+    pub fn check(&self, action: Action, caller: &str, ip: Option<IpAddr>) -> Result<(), RateLimited> {
+        self.check_at(action, caller, ip, Instant::now())
+    }
+
+    fn check_at(&self, action: Action, caller: &str, ip: Option<IpAddr>, now: Instant) -> Result<(), RateLimited> {
+        let Some(limit) = self.limits.get(action) else {
+            return Ok(());
+        };
+        let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+        if buckets.len() >= MAX_BUCKETS {
+            buckets.retain(|(action, _), bucket| {
+                let Some(limit) = self.limits.get(*action) else {
+                    return false;
+                };
+                bucket.refill(limit, now);
+                bucket.tokens < f64::from(limit.burst)
+            });
+        }
+        let clients = [Some(Client::Caller(caller.to_string())), ip.map(Client::Ip)];
+        let mut wait = Duration::ZERO;
+        for client in clients.iter().flatten() {
+            let bucket = buckets.entry((action, client.clone())).or_insert(Bucket {
+                tokens: f64::from(limit.burst),
+                at: now,
+            });
+            bucket.refill(limit, now);
+            if bucket.tokens < 1.0 {
+                wait = wait.max(limit.time_for(1.0 - bucket.tokens));
+            }
+        }
+        if !wait.is_zero() {
+            metrics().api_rate_limited.with_label_values(&[action.as_str()]).inc();
+            return Err(RateLimited {
+                action,
+                retry_after: wait,
+            });
+        }
+        for client in clients.into_iter().flatten() {
+            if let Some(bucket) = buckets.get_mut(&(action, client)) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    const IP: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)));
+    const OTHER_IP: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)));
+
+    /// One quote a second, `burst` at once; creation unlimited.
+    fn limiter(burst: u32) -> RateLimiter {
+        RateLimiter::new(RateLimits {
+            quote: RateLimit::new(60, burst),
+            create: None,
+        })
+    }
+
+    fn secs(s: f64) -> Duration {
+        Duration::from_secs_f64(s)
+    }
+
+    /// Quotes by `caller` from `ip` at `at` that pass before the first refusal, and its retry delay.
+    fn drain(limiter: &RateLimiter, caller: &str, ip: Option<IpAddr>, at: Instant) -> (u32, Duration) {
+        let mut passed = 0;
+        loop {
+            match limiter.check_at(Action::Quote, caller, ip, at) {
+                Ok(()) => passed += 1,
+                Err(e) => return (passed, e.retry_after),
+            }
+        }
+    }
+
+    #[test]
+    fn burst_then_refusal() {
+        let t0 = Instant::now();
+        let limiter = limiter(3);
+        assert_eq!(drain(&limiter, "alice", IP, t0), (3, secs(1.0)));
+        let err = limiter.check_at(Action::Quote, "alice", IP, t0).unwrap_err();
+        assert_eq!(err.action, Action::Quote);
+        assert_eq!(err.to_string(), "too many quote calls; retry in 1s");
+    }
+
+    #[test]
+    fn refills_at_the_configured_rate() {
+        let t0 = Instant::now();
+        let limiter = limiter(3);
+        drain(&limiter, "alice", IP, t0);
+        // Half a call back: the wait shrinks but nothing passes yet.
+        assert_eq!(drain(&limiter, "alice", IP, t0 + secs(0.5)), (0, secs(0.5)));
+        assert_eq!(drain(&limiter, "alice", IP, t0 + secs(1.0)), (1, secs(1.0)));
+        assert_eq!(drain(&limiter, "alice", IP, t0 + secs(3.0)), (2, secs(1.0)));
+    }
+
+    #[test]
+    fn refill_stops_at_the_burst() {
+        let t0 = Instant::now();
+        let limiter = limiter(3);
+        drain(&limiter, "alice", IP, t0);
+        assert_eq!(drain(&limiter, "alice", IP, t0 + Duration::from_secs(3_600)).0, 3);
+    }
+
+    #[test]
+    fn callers_and_addresses_have_their_own_buckets() {
+        let t0 = Instant::now();
+        let limiter = limiter(2);
+        assert_eq!(drain(&limiter, "alice", IP, t0).0, 2);
+        // Another caller behind the same address shares its bucket.
+        assert!(limiter.check_at(Action::Quote, "bob", IP, t0).is_err());
+        // The refusal took nothing from bob's own bucket.
+        assert_eq!(drain(&limiter, "bob", OTHER_IP, t0).0, 2);
+        // Without an address only the caller is limited.
+        assert_eq!(drain(&limiter, "carol", None, t0).0, 2);
+        assert!(limiter.check_at(Action::Quote, "alice", None, t0).is_err());
+    }
+
+    #[test]
+    fn unlimited_actions_always_pass() {
+        let t0 = Instant::now();
+        let limiter = limiter(1);
+        for _ in 0..100 {
+            limiter.check_at(Action::Create, "alice", IP, t0).unwrap();
+        }
+        assert_eq!(drain(&limiter, "alice", IP, t0).0, 1);
+        let none = RateLimiter::new(RateLimits::unlimited());
+        for _ in 0..100 {
+            none.check_at(Action::Quote, "alice", IP, t0).unwrap();
+        }
+    }
+
+    #[test]
+    fn zero_rate_is_unlimited_and_zero_burst_is_one() {
+        assert_eq!(RateLimit::new(0, 10), None);
+        assert_eq!(
+            RateLimit::new(30, 0),
+            Some(RateLimit {
+                per_minute: 30,
+                burst: 1
+            })
+        );
+        let limiter = RateLimiter::new(RateLimits {
+            quote: RateLimit::new(30, 0),
+            create: None,
+        });
+        assert_eq!(drain(&limiter, "alice", None, Instant::now()), (1, secs(2.0)));
+    }
+
+    #[test]
+    fn idle_buckets_are_dropped_once_there_are_too_many() {
+        let t0 = Instant::now();
+        let limiter = limiter(2);
+        for n in 0..MAX_BUCKETS {
+            limiter.check_at(Action::Quote, &n.to_string(), None, t0).unwrap();
+        }
+        // Each has a call left of two, so none can be dropped yet.
+        limiter.check_at(Action::Quote, "alice", None, t0).unwrap();
+        assert_eq!(limiter.buckets.lock().unwrap().len(), MAX_BUCKETS + 1);
+        // Refilled, they are indistinguishable from new ones.
+        limiter.check_at(Action::Quote, "bob", None, t0 + secs(1.0)).unwrap();
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,174 @@
+//! BTC/USD pricing for quotes. Each [`PriceSource`] reports one price; the [`Oracle`] drops stale or failed
+//! sources, takes the median of the rest and applies the operator's spread for each swap direction.
+
+pub mod exchange;
+pub mod pyth;
+
+use std::{fmt, future::Future, time::Duration};
+
+use tracing::warn;
+
+use crate::swap::{unix_now, Direction};
+
+/// Prices are carried in millionths of a dollar per BTC, the base unit of a 6-decimal stablecoin.
+pub const MICRO: u64 = 1_000_000;
+
+/// One source's BTC/USD price.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Price {
+    pub micro_usd_per_btc: u64,
+    /// When the source published it; sources without their own timestamp report the fetch time.
+    pub published_at: i64,
+}
+
+#[derive(Debug)]
+pub enum RateError {
+    Http(String),
+    Parse(String),
+    /// Fewer fresh prices than the oracle requires.
+    NotEnoughSources {
+        fresh: usize,
+        required: usize,
+    },
+}
+
+impl fmt::Display for RateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(e) => write!(f, "price request failed: {e}"),
+            Self::Parse(e) => write!(f, "unexpected price response: {e}"),
+            Self::NotEnoughSources { fresh, required } => {
+                write!(f, "only {fresh} fresh price sources, {required} required")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RateError {}
+
+pub trait PriceSource: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn price(&self) -> impl Future<Output = Result<Price, RateError>> + Send;
+}
+
+/// The sources swapd ships with, so an [`Oracle`] can hold a mix of them.
+#[derive(Debug, Clone)]
+pub enum Source {
+    Pyth(pyth::Pyth),
+    Exchange(exchange::Exchange),
+}
+
+impl PriceSource for Source {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Pyth(s) => s.name(),
+            Self::Exchange(s) => s.name(),
+        }
+    }
+
+    async fn price(&self) -> Result<Price, RateError> {
+        match self {
+            Self::Pyth(s) => s.price().await,
+            Self::Exchange(s) => s.price().await,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OracleConfig {
+    /// Prices published longer ago than this are ignored.
+    pub max_age: Duration,
+    pub min_sources: usize,
+    /// Taken off the median when the operator buys BTC (LnToUsdt) and added when it sells (UsdtToLn).
+    pub spread_bps: u16,
+    /// Decimals of the swapped token; the rate is scaled from micro-dollars to its base units.
+    pub token_decimals: u8,
+}
+
+/// Median of the fresh sources, in micro-dollars, with the spread applied per direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    pub mid_micro_usd: u64,
+    pub sources: usize,
+    pub cfg: OracleConfig,
+}
+
+impl Rate {
+    /// Token base units per BTC for a swap in `direction`.
+    pub fn token_per_btc(&self, direction: Direction) -> u64 {
+        let mid = u128::from(self.mid_micro_usd);
+        let spread = mid * u128::from(self.cfg.spread_bps) / 10_000;
+        let micro = match direction {
+            Direction::LnToUsdt => mid - spread,
+            Direction::UsdtToLn => mid + spread,
+        };
+        micro_usd_to_tokens(micro, self.cfg.token_decimals)
+    }
+}
+
+/// `micro_usd` in base units of a dollar token with `token_decimals`.
+pub fn micro_usd_to_tokens(micro_usd: u128, token_decimals: u8) -> u64 {
+    let scaled = match token_decimals.checked_sub(6) {
+        Some(up) => micro_usd * 10u128.pow(u32::from(up)),
+        None => micro_usd / 10u128.pow(u32::from(6 - token_decimals)),
+    };
+    u64::try_from(scaled).unwrap_or(u64::MAX)
+}
+
+#[derive(Clone)]
+pub struct Oracle<S = Source> {
+    pub sources: Vec<S>,
+    pub cfg: OracleConfig,
+}
+
+impl<S: PriceSource> Oracle<S> {
+    /// Queries every source at once and aggregates the fresh answers.
+    pub async fn rate(&self) -> Result<Rate, RateError> {
+        let now = unix_now();
+        let max_age = i64::try_from(self.cfg.max_age.as_secs()).unwrap_or(i64::MAX);
+        let answers = futures_util::future::join_all(self.sources.iter().map(|s| s.price())).await;
+        let mut fresh = Vec::with_capacity(answers.len());
+        for (source, answer) in self.sources.iter().zip(answers) {
+            match answer {
+                Ok(price) if now.saturating_sub(price.published_at) <= max_age => fresh.push(price.micro_usd_per_btc),
+                Ok(price) => warn!(
+                    source = source.name(),
+                    published_at = price.published_at,
+                    "stale price ignored"
+                ),
+                Err(e) => warn!(source = source.name(), error = %e, "price source failed"),
+            }
+        }
+        if fresh.is_empty() || fresh.len() < self.cfg.min_sources {
+            return Err(RateError::NotEnoughSources {
+                fresh: fresh.len(),
+                required: self.cfg.min_sources.max(1),
+            });
+        }
+        Ok(Rate {
+            mid_micro_usd: median(&mut fresh),
+            sources: fresh.len(),
+            cfg: self.cfg,
+        })
+    }
+}
+
+/// Median, averaging the middle pair of an even count.
+fn median(values: &mut [u64]) -> u64 {
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    if values.len() % 2 == 1 {
+        values[mid]
+    } else {
+        ((u128::from(values[mid - 1]) + u128::from(values[mid])) / 2) as u64
+    }
+}
+
+/// A decimal string (e.g. `"64123.45"`) in micro-units, truncating digits past the sixth decimal.
+pub(crate) fn parse_micro(s: &str) -> Option<u64> {
+    let (int, frac) = s.trim().split_once('.').unwrap_or((s.trim(), ""));
+    let int: u64 = int.parse().ok()?;
+    let frac = format!("{:0<6}", &frac[..frac.len().min(6)]);
+    int.checked_mul(MICRO)?.checked_add(frac.parse().ok()?)
+}
@@ -0,0 +1,70 @@
+//! Pyth BTC/USD from a Hermes price service (`/v2/updates/price/latest`).
+
+use reqwest::Client;
+use serde_json::Value;
+
+use super::{Price, PriceSource, RateError};
+
+/// Public Hermes endpoint.
+pub const DEFAULT_HERMES_URL: &str = "https://hermes.pyth.network";
+/// Pyth's Crypto.BTC/USD price feed id.
+pub const BTC_USD_FEED: &str = "e62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43";
+
+#[derive(Debug, Clone)]
+pub struct Pyth {
+    http: Client,
+    url: String,
+    feed_id: String,
+}
+
+impl Pyth {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            http: Client::new(),
+            url: url.into(),
+            feed_id: BTC_USD_FEED.to_string(),
+        }
+    }
+}
+
+impl PriceSource for Pyth {
+    fn name(&self) -> &'static str {
+        "pyth"
+    }
+
+    async fn price(&self) -> Result<Price, RateError> {
+        let url = format!("{}/v2/updates/price/latest", self.url.trim_end_matches('/'));
+        let body: Value = self
+            .http
+            .get(&url)
+            .query(&[("ids[]", self.feed_id.as_str()), ("parsed", "true")])
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| RateError::Http(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| RateError::Parse(e.to_string()))?;
+        let price = &body["parsed"][0]["price"];
+        let mantissa: i64 = price["price"]
+            .as_str()
+            .and_then(|p| p.parse().ok())
+            .ok_or_else(|| RateError::Parse("pyth price without price".into()))?;
+        let expo = price["expo"]
+            .as_i64()
+            .ok_or_else(|| RateError::Parse("pyth price without expo".into()))?;
+        let published_at = price["publish_time"]
+            .as_i64()
+            .ok_or_else(|| RateError::Parse("pyth price without publish_time".into()))?;
+        let mantissa = u128::try_from(mantissa).map_err(|_| RateError::Parse("negative pyth price".into()))?;
+        // price = mantissa * 10^expo dollars; scale to micro-dollars.
+        let micro = match expo + 6 {
+            e if e >= 0 => mantissa * 10u128.pow(e as u32),
+            e => mantissa / 10u128.pow(e.unsigned_abs() as u32),
+        };
+        Ok(Price {
+            micro_usd_per_btc: u64::try_from(micro).unwrap_or(u64::MAX),
+            published_at,
+        })
+    }
+}
@@ -0,0 +1,208 @@
+//! Keeps the operator able to quote both directions. Each interval the rebalancer compares the local share
+//! of channel capacity with the configured target and, outside the tolerance band, starts a Lightning Loop or
+//! PeerSwap swap: out when too much is local (LnToUsdt needs inbound), in when too little is (UsdtToLn needs
+//! outbound). USDT inventory outside its bounds is only flagged; moving it is left to the operator.
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+use intercom_swap_client::client::EscrowClient;
+use solana_sdk::pubkey::Pubkey;
+use tokio::process::Command;
+use tracing::{error, info, warn};
+
+use crate::{ledger::Entry, ln::LnBackend, store::Store, swap::unix_now};
+
+/// Store cursor: when the last rebalancing swap was started.
+const LAST_REBALANCE: &str = "rebalance_started_at";
+
+/// How Lightning liquidity gets moved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RebalanceTool {
+    /// Lightning Loop's `loop out` / `loop in` against the LND node.
+    Loop { bin: String },
+    /// PeerSwap's `pscli swapout` / `swapin` with the peer on `channel_id`.
+    PeerSwap { bin: String, channel_id: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebalanceConfig {
+    pub interval: Duration,
+    /// `None` only flags USDT inventory.
+    pub tool: Option<RebalanceTool>,
+    /// Desired local share of channel capacity, in basis points.
+    pub target_local_bps: u16,
+    /// No swap while the local share is within this many basis points of the target.
+    pub tolerance_bps: u16,
+    /// Bounds on a single rebalancing swap.
+    pub min_swap_sat: u64,
+    pub max_swap_sat: u64,
+    /// Wait at least this long after starting a swap before starting another, so it can complete first.
+    pub cooldown: Duration,
+    /// Alert when the operator's USDT is outside these bounds (base units).
+    pub token_min: Option<u64>,
+    pub token_max: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Move {
+    /// Send sats off-chain, receive them on-chain: more inbound.
+    Out(u64),
+    /// Pay on-chain into the channel: more outbound.
+    In(u64),
+}
+
+pub struct Rebalancer {
+    store: Arc<Store>,
+    client: EscrowClient,
+    operator: Pubkey,
+    mint: Pubkey,
+    cfg: RebalanceConfig,
+}
+
+impl Rebalancer {
+    pub fn new(store: Arc<Store>, client: EscrowClient, operator: Pubkey, mint: Pubkey, cfg: RebalanceConfig) -> Self {
+        Self {
+            store,
+            client,
+            operator,
+            mint,
+            cfg,
+        }
+    }
+
+    /// Checks inventory every `interval` until `shutdown` resolves.
+    pub async fn run<L: LnBackend>(&self, ln: &L, shutdown: impl Future<Output = ()>) {
+        tokio::pin!(shutdown);
+        loop {
+            if let Err(e) = self.check_lightning(ln).await {
+                warn!(error = %e, "lightning rebalance check failed; retrying next interval");
+            }
+            self.check_tokens().await;
+            tokio::select! {
+                _ = &mut shutdown => return,
+                _ = tokio::time::sleep(self.cfg.interval) => {}
+            }
+        }
+    }
+
+    async fn check_lightning<L: LnBackend>(&self, ln: &L) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(tool) = &self.cfg.tool else {
+            return Ok(());
+        };
+        let balance = ln.channel_balance().await?;
+        let Some(step) = self.plan(balance.local_msat / 1000, balance.remote_msat / 1000) else {
+            return Ok(());
+        };
+        let cooldown = i64::try_from(self.cfg.cooldown.as_secs()).unwrap_or(i64::MAX);
+        let last = i64::try_from(self.store.cursor(LAST_REBALANCE)?).unwrap_or(i64::MAX);
+        if unix_now().saturating_sub(last) < cooldown {
+            info!(?step, "rebalance needed but the previous one is still cooling down");
+            return Ok(());
+        }
+        info!(
+            ?step,
+            local_msat = balance.local_msat,
+            remote_msat = balance.remote_msat,
+            "starting rebalance"
+        );
+        start(tool, step).await?;
+        self.store.set_cursor(LAST_REBALANCE, unix_now().max(0) as u64)?;
+        // Booked when started; the tool's fees show as ledger drift until adjusted.
+        let name = match tool {
+            RebalanceTool::Loop { .. } => "loop",
+            RebalanceTool::PeerSwap { .. } => "peerswap",
+        };
+        let entry = match step {
+            Move::Out(sat) => Entry::rebalance(true, sat.saturating_mul(1000), format!("{name} out")),
+            Move::In(sat) => Entry::rebalance(false, sat.saturating_mul(1000), format!("{name} in")),
+        };
+        self.store.post_ledger_entry(&entry)?;
+        Ok(())
+    }
+
+    /// The swap that brings the local share back to target, if it is outside the band.
+    fn plan(&self, local_sat: u64, remote_sat: u64) -> Option<Move> {
+        let capacity = u128::from(local_sat) + u128::from(remote_sat);
+        if capacity == 0 {
+            return None;
+        }
+        let local_bps = (u128::from(local_sat) * 10_000 / capacity) as i64;
+        let target_bps = i64::from(self.cfg.target_local_bps);
+        if (local_bps - target_bps).abs() <= i64::from(self.cfg.tolerance_bps) {
+            return None;
+        }
+        let target_sat = (capacity * u128::from(self.cfg.target_local_bps) / 10_000) as u64;
+        let (amount, step): (u64, fn(u64) -> Move) = if local_sat > target_sat {
+            (local_sat - target_sat, Move::Out)
+        } else {
+            (target_sat - local_sat, Move::In)
+        };
+        let amount = amount.min(self.cfg.max_swap_sat);
+        (amount >= self.cfg.min_swap_sat).then(|| step(amount))
+    }
+
+    async fn check_tokens(&self) {
+        if self.cfg.token_min.is_none() && self.cfg.token_max.is_none() {
+            return;
+        }
+        let balance = match self.client.get_token_balance(&self.operator, &self.mint).await {
+            Ok(balance) => balance,
+            Err(e) => return warn!(error = %e, "cannot read operator token balance"),
+        };
+        if self.cfg.token_min.is_some_and(|min| balance < min) {
+            error!(
+                alert = "usdt_inventory_low",
+                balance,
+                min = self.cfg.token_min,
+                "USDT inventory below target; ln-to-usdt quotes will shrink"
+            );
+        }
+        if self.cfg.token_max.is_some_and(|max| balance > max) {
+            error!(
+                alert = "usdt_inventory_high",
+                balance,
+                max = self.cfg.token_max,
+                "USDT inventory above target; consider selling some for BTC"
+            );
+        }
+    }
+}
+
+/// Starts the swap; the tool completes it in the background.
+async fn start(tool: &RebalanceTool, step: Move) -> Result<(), String> {
+    let mut cmd = match (tool, step) {
+        (RebalanceTool::Loop { bin }, Move::Out(sat)) => {
+            let mut cmd = Command::new(bin);
+            cmd.args(["out", "--amt", &sat.to_string(), "--force"]);
+            cmd
+        }
+        (RebalanceTool::Loop { bin }, Move::In(sat)) => {
+            let mut cmd = Command::new(bin);
+            cmd.args(["in", "--amt", &sat.to_string(), "--force"]);
+            cmd
+        }
+        (RebalanceTool::PeerSwap { bin, channel_id }, step) => {
+            let (verb, sat) = match step {
+                Move::Out(sat) => ("swapout", sat),
+                Move::In(sat) => ("swapin", sat),
+            };
+            let mut cmd = Command::new(bin);
+            cmd.args([
+                verb,
+                "--channel_id",
+                channel_id,
+                "--sat_amt",
+                &sat.to_string(),
+                "--asset",
+                "btc",
+            ]);
+            cmd
+        }
+    };
+    let out = cmd.output().await.map_err(|e| e.to_string())?;
+    if !out.status.success() {
+        return Err(String::from_utf8_lossy(&out.stderr).trim().to_string());
+    }
+    info!(output = %String::from_utf8_lossy(&out.stdout).trim(), "rebalance swap started");
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! Refunds every expired escrow the operator holds the refund key for.
+//!
+//! The engine refunds the swaps it tracks; this watcher also covers escrows funded outside the daemon (the JS
+//! tooling, manual Inits) or left behind by a failed swap, so capital is never stranded past refund_after.
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+use intercom_swap_client::{
+    client::EscrowClient,
+    filters::EscrowQuery,
+    retry::{RetryPolicy, SendOutcome},
+    rpc::EscrowRpc,
+    state::{EscrowState, EscrowStatus},
+    transaction::{self, TxOptions},
+};
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
+use tracing::{debug, info, warn};
+
+use crate::{error::SwapError, kms::Operator, metrics::metrics, store::Store};
+
+#[derive(Debug, Clone)]
+pub struct RefundWatcherConfig {
+    pub interval: Duration,
+    /// Refunds only race the recipient's claim, so a modest priority fee is enough.
+    pub tx: TxOptions,
+    pub retry: RetryPolicy,
+}
+
+pub struct RefundWatcher {
+    store: Arc<Store>,
+    client: EscrowClient,
+    operator: Arc<Operator>,
+    cfg: RefundWatcherConfig,
+}
+
+impl RefundWatcher {
+    pub fn new(store: Arc<Store>, client: EscrowClient, operator: Arc<Operator>, cfg: RefundWatcherConfig) -> Self {
+        Self {
+            store,
+            client,
+            operator,
+            cfg,
+        }
+    }
+
+    /// Scans every `interval` until `shutdown` resolves.
+    pub async fn run(&self, shutdown: impl Future<Output = ()>) {
+        tokio::pin!(shutdown);
+        loop {
+            match self.scan().await {
+                Ok(0) => {}
+                Ok(n) => info!(refunded = n, "refund scan done"),
+                Err(e) => warn!(error = %e, "refund scan failed; retrying next interval"),
+            }
+            tokio::select! {
+                _ = &mut shutdown => return,
+                _ = tokio::time::sleep(self.cfg.interval) => {}
+            }
+        }
+    }
+
+    /// Refunds the operator's active escrows past refund_after; returns how many were refunded.
+    pub async fn scan(&self) -> Result<usize, SwapError> {
+        let now = self.client.get_unix_timestamp().await?;
+        let query = EscrowQuery::default()
+            .status(EscrowStatus::Active)
+            .refund(self.operator.pubkey())
+            .refundable_at(now);
+        let mut refunded = 0;
+        for (address, escrow) in self.client.list_escrows(&query).await? {
+            // Swaps still in the engine's hands are refunded (or claimed) by their own state machine.
+            let id = hex::encode(escrow.payment_hash);
+            if self.store.get(&id)?.is_some_and(|swap| !swap.state.is_terminal()) {
+                continue;
+            }
+            match self.refund(&address, &escrow).await {
+                Ok(true) => refunded += 1,
+                Ok(false) => {}
+                Err(e) => warn!(escrow = %address, error = %e, "refund failed; retrying next scan"),
+            }
+        }
+        Ok(refunded)
+    }
+
+    async fn refund(&self, address: &Pubkey, escrow: &EscrowState) -> Result<bool, SwapError> {
+        let operator = self.operator.pubkey();
+        let blockhash = self.client.get_latest_blockhash().await?;
+        let mut tx =
+            transaction::refund_transaction(self.client.program_id(), escrow, &operator, &self.cfg.tx, blockhash);
+        tx.try_sign(&[&*self.operator], blockhash)?;
+        let outcome = self
+            .client
+            .send_with_retry(tx.clone(), &[&*self.operator], &self.cfg.retry, |event| {
+                metrics().observe_send_event(event);
+                debug!(escrow = %address, ?event, "refund progress");
+            })
+            .await?;
+        metrics().observe_send_outcome(&outcome);
+        if let Some(e) = outcome.error(self.client.program_id(), &tx) {
+            // NotActive: claimed since the scan listed it.
+            warn!(escrow = %address, error = %e, "refund rejected");
+            return Ok(false);
+        }
+        match outcome {
+            SendOutcome::Confirmed { signature, .. } => {
+                info!(escrow = %address, %signature, amount = escrow.net_amount, "refunded expired escrow");
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
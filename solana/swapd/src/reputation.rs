@@ -0,0 +1,146 @@
+//! Per-counterparty track record. Every swap that ends is recorded as an [`Outcome`] against the user's Solana
+//! key, and the operator can mark a swap disputed. A [`ReputationPolicy`] turns the record into a quote limit:
+//! keys without enough history get a small cap, keys with disputes or a poor completion rate are refused.
+
+use std::{fmt, str::FromStr};
+
+use serde_json::{json, Value};
+
+use crate::swap::{Direction, Swap, SwapState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    Completed,
+    /// LnToUsdt: the escrow was funded but the user never claimed it, so it was refunded.
+    ExpiredUnclaimed,
+    /// Ended before any funds moved to the user (unpaid invoice, unfunded or mismatched escrow), or the user
+    /// took their USDT back.
+    Cancelled,
+    /// Marked by the operator; replaces whatever outcome the swap had.
+    Disputed,
+}
+
+impl Outcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Completed => "completed",
+            Self::ExpiredUnclaimed => "expired_unclaimed",
+            Self::Cancelled => "cancelled",
+            Self::Disputed => "disputed",
+        }
+    }
+
+    /// How `swap` ended for its counterparty; `None` while it is still running.
+    pub fn of(swap: &Swap) -> Option<Self> {
+        match (swap.state, swap.direction) {
+            (SwapState::Completed, _) => Some(Self::Completed),
+            (SwapState::Refunded, Direction::LnToUsdt) => Some(Self::ExpiredUnclaimed),
+            (SwapState::Refunded, Direction::UsdtToLn) | (SwapState::Failed, _) => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Outcome {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "completed" => Ok(Self::Completed),
+            "expired_unclaimed" => Ok(Self::ExpiredUnclaimed),
+            "cancelled" => Ok(Self::Cancelled),
+            "disputed" => Ok(Self::Disputed),
+            other => Err(format!("unknown outcome {other:?}")),
+        }
+    }
+}
+
+/// Outcome counts for one counterparty.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Reputation {
+    pub completed: u64,
+    pub expired_unclaimed: u64,
+    pub cancelled: u64,
+    pub disputed: u64,
+    /// Lightning amount of the completed swaps.
+    pub completed_msat: u64,
+    pub last_outcome_at: Option<i64>,
+}
+
+impl Reputation {
+    /// Adds `count` swaps that ended as `outcome`.
+    pub fn add(&mut self, outcome: Outcome, count: u64, amount_msat: u64, at: i64) {
+        match outcome {
+            Outcome::Completed => {
+                self.completed += count;
+                self.completed_msat = self.completed_msat.saturating_add(amount_msat);
+            }
+            Outcome::ExpiredUnclaimed => self.expired_unclaimed += count,
+            Outcome::Cancelled => self.cancelled += count,
+            Outcome::Disputed => self.disputed += count,
+        }
+        self.last_outcome_at = self.last_outcome_at.max(Some(at));
+    }
+
+    pub fn total(&self) -> u64 {
+        self.completed + self.expired_unclaimed + self.cancelled + self.disputed
+    }
+
+    /// Completed swaps per 10 000 ended; 10 000 with no history.
+    pub fn completion_bps(&self) -> u64 {
+        match self.total() {
+            0 => 10_000,
+            total => self.completed * 10_000 / total,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "completed": self.completed,
+            "expiredUnclaimed": self.expired_unclaimed,
+            "cancelled": self.cancelled,
+            "disputed": self.disputed,
+            "completedMsat": self.completed_msat,
+            "completionBps": self.completion_bps(),
+            "lastOutcomeAt": self.last_outcome_at,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReputationPolicy {
+    /// Swaps a key needs before its completion rate counts; until then its quotes are capped at
+    /// `new_max_amount_msat`.
+    pub min_history: u64,
+    pub new_max_amount_msat: u64,
+    /// Refuse keys completing fewer swaps per 10 000 than this.
+    pub min_completion_bps: u16,
+    /// Refuse keys with more disputed swaps than this.
+    pub max_disputes: u64,
+}
+
+impl ReputationPolicy {
+    /// The largest amount `reputation` may be quoted (`None`: no limit), or why it is refused.
+    pub fn limit(&self, reputation: &Reputation) -> Result<Option<u64>, String> {
+        if reputation.disputed > self.max_disputes {
+            return Err(format!("{} disputed swaps", reputation.disputed));
+        }
+        if reputation.total() < self.min_history {
+            return Ok(Some(self.new_max_amount_msat));
+        }
+        let completion_bps = reputation.completion_bps();
+        if completion_bps < u64::from(self.min_completion_bps) {
+            return Err(format!(
+                "completion rate {completion_bps} bps is below {} bps",
+                self.min_completion_bps
+            ));
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,1043 @@
+//! REST/JSON control API for integrators who cannot use gRPC: the same operations as [`grpc`](crate::grpc),
+//! mapped onto [`control`](crate::control), with field names as in the daemon's other JSON. The OpenAPI
+//! document is generated from the request and response types below and served at `/openapi.json`. Swap
+//! progress is pushed over a [`ws`](crate::ws) endpoint on the same server. Every route but the document
+//! needs credentials with the scope it names (see [`auth`](crate::auth)), sent as a header or, for browsers
+//! opening the WebSocket, an `access_token` query parameter.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRef, FromRequestParts, Path, Query, State},
+    http::{
+        header::{AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_TYPE, RETRY_AFTER},
+        request::Parts,
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tracing::info;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::{
+    admin::{Admin, AdminOp},
+    auth::{credential, AuthError, Authenticator, Principal, Scope, API_KEY_HEADER},
+    control::{
+        idempotency_key, parse_claim_key, parse_node_id, parse_pubkey, parse_quote_id, Control, ControlError,
+        IdempotencyKey, Info, NewSwap,
+    },
+    export::Period,
+    ln::LnBackend,
+    negotiate::Maker,
+    pnl::Breakdown,
+    quote::{Quote, QuoteRequest},
+    ratelimit::{Action, RateLimiter},
+    retries::DeadLetter,
+    swap::{Direction, Swap, SwapState},
+};
+
+/// Negotiator transport name of the API's maker.
+pub const TRANSPORT: &str = "rest";
+
+/// Header carrying the client's idempotency key for quotes and new swaps.
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+pub struct RestConfig {
+    pub listen: SocketAddr,
+    /// Serve HTTPS (and WSS) with this certificate instead of plain HTTP.
+    #[cfg(feature = "tls")]
+    pub tls: Option<Arc<crate::tls::Tls>>,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "swapd",
+        description = "Control API of the intercom-swap LN <-> USDT swap daemon."
+    ),
+    paths(
+        get_info,
+        quote,
+        create_swap,
+        list_swaps,
+        get_swap,
+        cancel_swap,
+        register_onchain_fallback,
+        get_htlc,
+        export_swaps,
+        pnl,
+        list_dead_letters,
+        retry_dead_letter,
+        preview_admin,
+        confirm_admin
+    ),
+    modifiers(&BearerAuth),
+    security(("bearer" = [])),
+    components(schemas(
+        InfoBody,
+        DirectionTermsBody,
+        BalanceBody,
+        QuoteRequestBody,
+        QuoteBody,
+        CreateSwapBody,
+        AcceptQuoteBody,
+        LnToUsdtBody,
+        UsdtToLnBody,
+        SwapBody,
+        OnchainFallbackBody,
+        DeadLetterBody,
+        AdminOpBody,
+        InitConfigBody,
+        SetConfigBody,
+        WithdrawFeesBody,
+        ConfirmedBody,
+        ErrorBody,
+        Direction,
+        SwapState
+    ))
+)]
+pub struct ApiDoc;
+
+/// Declares the `Authorization: Bearer` scheme every operation uses.
+struct BearerAuth;
+
+impl utoipa::Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+
+        let scheme = HttpBuilder::new()
+            .scheme(HttpAuthScheme::Bearer)
+            .description(Some("An API key, or a JWT when the daemon accepts them"))
+            .build();
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme("bearer", SecurityScheme::Http(scheme));
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct InfoBody {
+    version: String,
+    /// Operator key: funds ln-to-usdt escrows and receives usdt-to-ln ones.
+    operator: String,
+    mint: String,
+    directions: Vec<Direction>,
+    /// Shared terms; `directionTerms` has what each direction is actually quoted at.
+    fee_bps: u16,
+    min_amount_msat: u64,
+    max_amount_msat: u64,
+    direction_terms: Vec<DirectionTermsBody>,
+    active_swaps: u64,
+    /// Absent when the node cannot be reached.
+    channel_balance: Option<BalanceBody>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct DirectionTermsBody {
+    direction: Direction,
+    fee_bps: u16,
+    /// Token base units per swap, on top of `feeBps`.
+    flat_fee: u64,
+    min_amount_msat: u64,
+    max_amount_msat: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct BalanceBody {
+    local_msat: u64,
+    remote_msat: u64,
+}
+
+impl From<Info> for InfoBody {
+    fn from(info: Info) -> Self {
+        Self {
+            version: info.version.to_string(),
+            operator: info.operator.to_string(),
+            mint: info.mint.to_string(),
+            directions: info.directions,
+            fee_bps: info.fee_bps,
+            min_amount_msat: info.min_amount_msat,
+            max_amount_msat: info.max_amount_msat,
+            direction_terms: info
+                .direction_terms
+                .iter()
+                .map(|t| DirectionTermsBody {
+                    direction: t.direction,
+                    fee_bps: t.fee_bps,
+                    flat_fee: t.flat_fee,
+                    min_amount_msat: t.min_amount_msat,
+                    max_amount_msat: t.max_amount_msat,
+                })
+                .collect(),
+            active_swaps: info.active_swaps,
+            channel_balance: info.channel_balance.map(|b| BalanceBody {
+                local_msat: b.local_msat,
+                remote_msat: b.remote_msat,
+            }),
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct QuoteRequestBody {
+    direction: Direction,
+    amount_msat: u64,
+    /// USDT recipient (ln-to-usdt) or refund key of the taker's escrow (usdt-to-ln).
+    counterparty: String,
+    /// Quote what liquidity and reputation allow instead of refusing a larger amount.
+    #[serde(default)]
+    allow_partial: bool,
+    /// usdt-to-ln: hex node id of the payee. When the daemon probes routes, the quote is refused unless one is
+    /// found and its routing fee is charged.
+    destination: Option<String>,
+}
+
+/// A signed, expiring quote, as `Quote::to_json`.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct QuoteBody {
+    /// Pass to `POST /v1/swaps` to accept.
+    id: String,
+    direction: Direction,
+    amount_msat: u64,
+    token_per_btc: u64,
+    fee_bps: u16,
+    /// Token base units, on top of `feeBps`.
+    flat_fee: u64,
+    token_amount: u64,
+    counterparty: String,
+    mint: String,
+    /// ln-to-usdt only.
+    payment_hash: Option<String>,
+    expires_at: i64,
+    signature: String,
+}
+
+impl From<&Quote> for QuoteBody {
+    fn from(quote: &Quote) -> Self {
+        Self {
+            id: hex::encode(quote.id),
+            direction: quote.direction,
+            amount_msat: quote.amount_msat,
+            token_per_btc: quote.token_per_btc,
+            fee_bps: quote.fee_bps,
+            flat_fee: quote.flat_fee,
+            token_amount: quote.token_amount,
+            counterparty: quote.counterparty.to_string(),
+            mint: quote.mint.to_string(),
+            payment_hash: quote.payment_hash.map(hex::encode),
+            expires_at: quote.expires_at,
+            signature: quote.signature.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum CreateSwapBody {
+    AcceptQuote(AcceptQuoteBody),
+    LnToUsdt(LnToUsdtBody),
+    UsdtToLn(UsdtToLnBody),
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct AcceptQuoteBody {
+    quote_id: String,
+    /// The taker's invoice for exactly the quoted amount; usdt-to-ln only.
+    bolt11: Option<String>,
+    /// Issue a hold invoice (ln-to-usdt).
+    #[serde(default)]
+    hold: bool,
+    /// usdt-to-ln; 1800 when absent.
+    funding_timeout_secs: Option<i64>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct LnToUsdtBody {
+    recipient: String,
+    amount_msat: u64,
+    /// Net token amount the user receives.
+    token_amount: u64,
+    #[serde(default)]
+    hold: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct UsdtToLnBody {
+    /// The user's invoice, paid once the escrow is verified.
+    bolt11: String,
+    refund: String,
+    /// Least net token amount the escrow must hold.
+    token_amount: u64,
+    /// 1800 when absent.
+    funding_timeout_secs: Option<i64>,
+}
+
+impl CreateSwapBody {
+    fn into_new(self) -> Result<NewSwap, ControlError> {
+        Ok(match self {
+            Self::AcceptQuote(b) => NewSwap::AcceptQuote {
+                quote_id: parse_quote_id(&b.quote_id)?,
+                bolt11: b.bolt11,
+                hold: b.hold,
+                funding_timeout_secs: b.funding_timeout_secs,
+            },
+            Self::LnToUsdt(b) => NewSwap::LnToUsdt {
+                recipient: parse_pubkey(&b.recipient, "recipient")?,
+                amount_msat: b.amount_msat,
+                token_amount: b.token_amount,
+                hold: b.hold,
+            },
+            Self::UsdtToLn(b) => NewSwap::UsdtToLn {
+                refund: parse_pubkey(&b.refund, "refund")?,
+                bolt11: b.bolt11,
+                token_amount: b.token_amount,
+                funding_timeout_secs: b.funding_timeout_secs,
+            },
+        })
+    }
+}
+
+/// A swap, as `Swap::to_json`.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct SwapBody {
+    /// Hex payment hash.
+    id: String,
+    direction: Direction,
+    state: SwapState,
+    payment_hash: String,
+    /// Withheld for ln-to-usdt plain invoices until the escrow backing them is funded.
+    bolt11: Option<String>,
+    amount_msat: u64,
+    hold: bool,
+    token_amount: u64,
+    /// USDT recipient (ln-to-usdt) or escrow refund key (usdt-to-ln).
+    counterparty: String,
+    refund_after: Option<i64>,
+    deadline: i64,
+    signature: Option<String>,
+    error: Option<String>,
+    cancel_requested: bool,
+    created_at: i64,
+    updated_at: i64,
+}
+
+impl From<&Swap> for SwapBody {
+    fn from(swap: &Swap) -> Self {
+        Self {
+            id: swap.id.clone(),
+            direction: swap.direction,
+            state: swap.state,
+            payment_hash: hex::encode(swap.payment_hash),
+            bolt11: swap.to_json()["bolt11"].as_str().map(str::to_string),
+            amount_msat: swap.amount_msat,
+            hold: swap.hold,
+            token_amount: swap.token_amount,
+            counterparty: swap.counterparty.to_string(),
+            refund_after: swap.refund_after,
+            deadline: swap.deadline,
+            signature: swap.signature.clone(),
+            error: swap.error.clone(),
+            cancel_requested: swap.cancel_requested,
+            created_at: swap.created_at,
+            updated_at: swap.updated_at,
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ListQuery {
+    /// Only swaps still in progress.
+    #[serde(default)]
+    active: bool,
+    /// At most this many; all when 0.
+    #[serde(default)]
+    limit: usize,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ExportQuery {
+    /// UTC `YYYY`, `YYYY-MM`, `YYYY-MM-DD`, or `FROM..TO` with both dates included.
+    period: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PnlQuery {
+    /// UTC `YYYY`, `YYYY-MM`, `YYYY-MM-DD`, or `FROM..TO` with both dates included.
+    period: String,
+    /// `day` or `swap` for a CSV with one row per day or swap instead of the JSON report.
+    breakdown: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DeadLetterQuery {
+    /// Include dead letters already sent back to the engine.
+    #[serde(default)]
+    resolved: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct OnchainFallbackBody {
+    /// Hex x-only key the taker claims the on-chain HTLC with.
+    claim_key: String,
+}
+
+/// A swap step the engine gave up on after exhausting its retries.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct DeadLetterBody {
+    id: i64,
+    swap_id: String,
+    /// The state the swap was stuck in.
+    state: SwapState,
+    attempts: u32,
+    last_error: String,
+    first_failed_at: i64,
+    dead_at: i64,
+    resolved_at: Option<i64>,
+}
+
+impl From<&DeadLetter> for DeadLetterBody {
+    fn from(letter: &DeadLetter) -> Self {
+        Self {
+            id: letter.id,
+            swap_id: letter.swap_id.clone(),
+            state: letter.state,
+            attempts: letter.attempts,
+            last_error: letter.last_error.clone(),
+            first_failed_at: letter.first_failed_at,
+            dead_at: letter.dead_at,
+            resolved_at: letter.resolved_at,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(tag = "operation", rename_all = "kebab-case")]
+enum AdminOpBody {
+    InitConfig(InitConfigBody),
+    SetConfig(SetConfigBody),
+    WithdrawFees(WithdrawFeesBody),
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct InitConfigBody {
+    fee_bps: u16,
+}
+
+/// Absent fields keep their current value.
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct SetConfigBody {
+    fee_collector: Option<String>,
+    fee_bps: Option<u16>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct WithdrawFeesBody {
+    mint: String,
+    /// Base units; the whole vault balance when absent.
+    #[serde(default)]
+    amount: u64,
+    /// Token account; the authority's associated account when absent.
+    destination: Option<String>,
+}
+
+impl AdminOpBody {
+    fn into_op(self) -> Result<AdminOp, ControlError> {
+        Ok(match self {
+            Self::InitConfig(b) => AdminOp::InitConfig { fee_bps: b.fee_bps },
+            Self::SetConfig(b) => AdminOp::SetConfig {
+                fee_collector: b.fee_collector.map(|k| parse_pubkey(&k, "feeCollector")).transpose()?,
+                fee_bps: b.fee_bps,
+            },
+            Self::WithdrawFees(b) => AdminOp::WithdrawFees {
+                mint: parse_pubkey(&b.mint, "mint")?,
+                amount: b.amount,
+                destination: b.destination.map(|k| parse_pubkey(&k, "destination")).transpose()?,
+            },
+        })
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct ConfirmedBody {
+    signature: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ErrorBody {
+    /// `invalid_argument`, `not_found`, `already_exists`, `failed_precondition`, `insufficient_liquidity`,
+    /// `rejected`, `unavailable`, `unauthenticated`, `permission_denied`, `rate_limited` or `internal`.
+    error: &'static str,
+    message: String,
+}
+
+pub(crate) struct ApiError(ControlError);
+
+impl From<ControlError> for ApiError {
+    fn from(e: ControlError) -> Self {
+        Self(e)
+    }
+}
+
+impl From<AuthError> for ApiError {
+    fn from(e: AuthError) -> Self {
+        Self(e.into())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, error) = match &self.0 {
+            ControlError::InvalidArgument(_) => (StatusCode::BAD_REQUEST, "invalid_argument"),
+            ControlError::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+            ControlError::AlreadyExists(_) => (StatusCode::CONFLICT, "already_exists"),
+            ControlError::FailedPrecondition(_) => (StatusCode::CONFLICT, "failed_precondition"),
+            ControlError::InsufficientLiquidity(_) => (StatusCode::UNPROCESSABLE_ENTITY, "insufficient_liquidity"),
+            ControlError::Rejected(_) => (StatusCode::FORBIDDEN, "rejected"),
+            ControlError::Unavailable(_) => (StatusCode::SERVICE_UNAVAILABLE, "unavailable"),
+            ControlError::Unauthenticated(_) => (StatusCode::UNAUTHORIZED, "unauthenticated"),
+            ControlError::PermissionDenied(_) => (StatusCode::FORBIDDEN, "permission_denied"),
+            ControlError::RateLimited(_) => (StatusCode::TOO_MANY_REQUESTS, "rate_limited"),
+            ControlError::Internal(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal"),
+        };
+        let body = ErrorBody {
+            error,
+            message: self.0.to_string(),
+        };
+        let mut response = (status, Json(body)).into_response();
+        if let ControlError::RateLimited(e) = &self.0 {
+            let secs = e.retry_after.as_secs_f64().ceil() as u64;
+            response.headers_mut().insert(RETRY_AFTER, secs.into());
+        }
+        response
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// What the handlers share.
+#[derive(Clone)]
+struct ApiState {
+    control: Control,
+    auth: Authenticator,
+    limiter: Arc<RateLimiter>,
+    /// Set with an authority key.
+    admin: Option<Arc<Admin>>,
+}
+
+impl FromRef<ApiState> for Option<Arc<Admin>> {
+    fn from_ref(state: &ApiState) -> Self {
+        state.admin.clone()
+    }
+}
+
+impl FromRef<ApiState> for Control {
+    fn from_ref(state: &ApiState) -> Self {
+        state.control.clone()
+    }
+}
+
+impl FromRef<ApiState> for Authenticator {
+    fn from_ref(state: &ApiState) -> Self {
+        state.auth.clone()
+    }
+}
+
+impl FromRef<ApiState> for Arc<RateLimiter> {
+    fn from_ref(state: &ApiState) -> Self {
+        state.limiter.clone()
+    }
+}
+
+/// The authenticated caller of a request.
+pub(crate) struct Caller {
+    principal: Principal,
+    /// Peer address of the connection.
+    ip: Option<IpAddr>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl Caller {
+    pub(crate) fn require(&self, scope: Scope) -> Result<(), ApiError> {
+        Ok(self.principal.require(scope)?)
+    }
+
+    /// Counts a call to `action` against the caller's and its address's rate limits.
+    fn limit(&self, action: Action) -> Result<(), ApiError> {
+        let limited = self.limiter.check(action, &self.principal.name, self.ip);
+        Ok(limited.map_err(ControlError::from)?)
+    }
+
+    /// The request's idempotency key, scoped to the caller.
+    fn idempotency_key(&self, headers: &HeaderMap) -> Result<Option<IdempotencyKey>, ApiError> {
+        let key = match headers.get(IDEMPOTENCY_KEY_HEADER) {
+            Some(value) => value
+                .to_str()
+                .map_err(|_| ControlError::InvalidArgument("idempotency key is not ASCII".into()))?,
+            None => "",
+        };
+        Ok(idempotency_key(&self.principal.name, key)?)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Caller
+where
+    Authenticator: FromRef<S>,
+    Arc<RateLimiter>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, ApiError> {
+        let header = |name: &str| parts.headers.get(name).and_then(|v| v.to_str().ok());
+        let query = Query::<HashMap<String, String>>::try_from_uri(&parts.uri).ok();
+        let token = query.as_ref().and_then(|q| q.get("access_token")).map(String::as_str);
+        let credential = credential(header(AUTHORIZATION.as_str()), header(API_KEY_HEADER)).or(token);
+        Ok(Self {
+            principal: Authenticator::from_ref(state).authenticate(credential)?,
+            ip: parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(peer)| peer.ip()),
+            limiter: FromRef::from_ref(state),
+        })
+    }
+}
+
+/// Daemon identity, terms and node balance.
+#[utoipa::path(get, path = "/v1/info", responses((status = 200, body = InfoBody)))]
+async fn get_info(caller: Caller, State(control): State<Control>) -> ApiResult<InfoBody> {
+    caller.require(Scope::Read)?;
+    Ok(Json(control.info().await?.into()))
+}
+
+/// A signed, expiring quote at the current price, sized to liquidity and the counterparty's record.
+#[utoipa::path(
+    post,
+    path = "/v1/quotes",
+    params(("Idempotency-Key" = Option<String>, Header, description = "Repeats within a day return the first result")),
+    request_body = QuoteRequestBody,
+    responses(
+        (status = 200, body = QuoteBody),
+        (status = 400, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 422, body = ErrorBody),
+        (status = 429, body = ErrorBody),
+        (status = 503, body = ErrorBody)
+    )
+)]
+async fn quote(
+    caller: Caller,
+    State(control): State<Control>,
+    headers: HeaderMap,
+    Json(body): Json<QuoteRequestBody>,
+) -> ApiResult<QuoteBody> {
+    caller.require(Scope::Create)?;
+    caller.limit(Action::Quote)?;
+    let key = caller.idempotency_key(&headers)?;
+    let request = QuoteRequest {
+        direction: body.direction,
+        amount_msat: body.amount_msat,
+        counterparty: parse_pubkey(&body.counterparty, "counterparty")?,
+        allow_partial: body.allow_partial,
+        destination: body
+            .destination
+            .as_deref()
+            .map(|id| parse_node_id(id, "destination"))
+            .transpose()?,
+    };
+    Ok(Json((&control.quote(request, key.as_ref()).await?).into()))
+}
+
+/// Queue a swap, either by accepting a quote or with explicit terms.
+#[utoipa::path(
+    post,
+    path = "/v1/swaps",
+    params(("Idempotency-Key" = Option<String>, Header, description = "Repeats within a day return the first result")),
+    request_body = CreateSwapBody,
+    responses(
+        (status = 200, body = SwapBody),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, body = ErrorBody),
+        (status = 429, body = ErrorBody),
+        (status = 503, body = ErrorBody)
+    )
+)]
+async fn create_swap(
+    caller: Caller,
+    State(control): State<Control>,
+    headers: HeaderMap,
+    Json(body): Json<CreateSwapBody>,
+) -> ApiResult<SwapBody> {
+    caller.require(Scope::Create)?;
+    caller.limit(Action::Create)?;
+    let key = caller.idempotency_key(&headers)?;
+    Ok(Json((&control.create(body.into_new()?, key.as_ref())?).into()))
+}
+
+/// Swaps, newest first.
+#[utoipa::path(get, path = "/v1/swaps", params(ListQuery), responses((status = 200, body = [SwapBody])))]
+async fn list_swaps(
+    caller: Caller,
+    State(control): State<Control>,
+    Query(query): Query<ListQuery>,
+) -> ApiResult<Vec<SwapBody>> {
+    caller.require(Scope::Read)?;
+    let swaps = control.list(query.active, query.limit)?;
+    Ok(Json(swaps.iter().map(SwapBody::from).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/swaps/{id}",
+    params(("id" = String, Path, description = "Hex payment hash")),
+    responses((status = 200, body = SwapBody), (status = 404, body = ErrorBody))
+)]
+async fn get_swap(caller: Caller, State(control): State<Control>, Path(id): Path<String>) -> ApiResult<SwapBody> {
+    caller.require(Scope::Read)?;
+    Ok(Json((&control.get(&id)?).into()))
+}
+
+/// Ask the engine to stop a swap before any funds move; it ends as `failed` on its next step.
+#[utoipa::path(
+    post,
+    path = "/v1/swaps/{id}/cancel",
+    params(("id" = String, Path, description = "Hex payment hash")),
+    responses(
+        (status = 200, body = SwapBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, body = ErrorBody)
+    )
+)]
+async fn cancel_swap(caller: Caller, State(control): State<Control>, Path(id): Path<String>) -> ApiResult<SwapBody> {
+    caller.require(Scope::Create)?;
+    Ok(Json((&control.cancel(&id)?).into()))
+}
+
+/// Opt a usdt-to-ln swap into being paid through an on-chain Taproot HTLC, claimable with `claimKey` and the
+/// payment preimage, should its Lightning payment keep failing.
+#[utoipa::path(
+    post,
+    path = "/v1/swaps/{id}/onchain-fallback",
+    params(("id" = String, Path, description = "Hex payment hash")),
+    request_body = OnchainFallbackBody,
+    responses(
+        (status = 200, body = SwapBody),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, body = ErrorBody)
+    )
+)]
+async fn register_onchain_fallback(
+    caller: Caller,
+    State(control): State<Control>,
+    Path(id): Path<String>,
+    Json(body): Json<OnchainFallbackBody>,
+) -> ApiResult<SwapBody> {
+    caller.require(Scope::Create)?;
+    let claim_key = parse_claim_key(&body.claim_key, "claimKey")?;
+    Ok(Json((&control.register_onchain_key(&id, &claim_key)?).into()))
+}
+
+/// The on-chain HTLC a swap is being paid through: its address, scripts and where it stands.
+#[utoipa::path(
+    get,
+    path = "/v1/swaps/{id}/htlc",
+    params(("id" = String, Path, description = "Hex payment hash")),
+    responses((status = 200, body = Object), (status = 404, body = ErrorBody))
+)]
+async fn get_htlc(
+    caller: Caller,
+    State(control): State<Control>,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    caller.require(Scope::Read)?;
+    Ok(Json(control.htlc(&id)?.to_json()).into_response())
+}
+
+/// Accounting CSV of the swaps created in a period: amounts, fee, payment hash and Solana signatures.
+#[utoipa::path(
+    get,
+    path = "/v1/export",
+    params(ExportQuery),
+    responses(
+        (status = 200, content_type = "text/csv", body = String),
+        (status = 400, body = ErrorBody),
+        (status = 403, body = ErrorBody)
+    )
+)]
+async fn export_swaps(
+    caller: Caller,
+    State(control): State<Control>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, ApiError> {
+    caller.require(Scope::Admin)?;
+    let period: Period = query.period.parse().map_err(ControlError::InvalidArgument)?;
+    let csv = control.export(&period)?;
+    let filename = format!("attachment; filename=\"swaps-{period}.csv\"");
+    Ok((
+        [
+            (CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (CONTENT_DISPOSITION, filename),
+        ],
+        csv,
+    )
+        .into_response())
+}
+
+/// Profit and loss of the swaps completed out of those created in a period, per day and per swap, with the
+/// operator's inventory marked to market.
+#[utoipa::path(
+    get,
+    path = "/v1/pnl",
+    params(PnlQuery),
+    responses(
+        (status = 200, description = "The report, or a CSV with `breakdown`", body = Object),
+        (status = 400, body = ErrorBody),
+        (status = 403, body = ErrorBody)
+    )
+)]
+async fn pnl(
+    caller: Caller,
+    State(control): State<Control>,
+    Query(query): Query<PnlQuery>,
+) -> Result<Response, ApiError> {
+    caller.require(Scope::Admin)?;
+    let period: Period = query.period.parse().map_err(ControlError::InvalidArgument)?;
+    let breakdown = query
+        .breakdown
+        .map(|b| b.parse::<Breakdown>())
+        .transpose()
+        .map_err(ControlError::InvalidArgument)?;
+    let report = control.pnl(&period).await?;
+    let Some(breakdown) = breakdown else {
+        return Ok(Json(report.to_json()).into_response());
+    };
+    let filename = format!("attachment; filename=\"pnl-{period}.csv\"");
+    Ok((
+        [
+            (CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (CONTENT_DISPOSITION, filename),
+        ],
+        report.csv(breakdown),
+    )
+        .into_response())
+}
+
+/// Swap steps the engine gave up on, newest first; open ones only unless `resolved` is set.
+#[utoipa::path(
+    get,
+    path = "/v1/dead-letters",
+    params(DeadLetterQuery),
+    responses((status = 200, body = [DeadLetterBody]), (status = 403, body = ErrorBody))
+)]
+async fn list_dead_letters(
+    caller: Caller,
+    State(control): State<Control>,
+    Query(query): Query<DeadLetterQuery>,
+) -> ApiResult<Vec<DeadLetterBody>> {
+    caller.require(Scope::Read)?;
+    let letters = control.dead_letters(query.resolved)?;
+    Ok(Json(letters.iter().map(DeadLetterBody::from).collect()))
+}
+
+/// Resolve a dead letter and hand its swap back to the engine, which steps it again with a fresh retry budget.
+#[utoipa::path(
+    post,
+    path = "/v1/dead-letters/{id}/retry",
+    params(("id" = i64, Path, description = "Dead letter id")),
+    responses(
+        (status = 200, body = SwapBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody)
+    )
+)]
+async fn retry_dead_letter(caller: Caller, State(control): State<Control>, Path(id): Path<i64>) -> ApiResult<SwapBody> {
+    caller.require(Scope::Admin)?;
+    Ok(Json((&control.retry_dead_letter(id)?).into()))
+}
+
+/// Simulate an InitConfig, SetConfig or WithdrawFees with the authority key and hold it for confirmation; the
+/// response shows the decoded effect on the platform config or fee vault.
+#[utoipa::path(
+    post,
+    path = "/v1/admin/previews",
+    request_body = AdminOpBody,
+    responses(
+        (status = 200, body = Object),
+        (status = 400, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 409, body = ErrorBody),
+        (status = 503, body = ErrorBody)
+    )
+)]
+async fn preview_admin(
+    caller: Caller,
+    State(admin): State<Option<Arc<Admin>>>,
+    Json(body): Json<AdminOpBody>,
+) -> ApiResult<serde_json::Value> {
+    caller.require(Scope::Admin)?;
+    let admin = admin.ok_or_else(no_admin)?;
+    let preview = admin.preview(body.into_op()?).await.map_err(ControlError::from)?;
+    Ok(Json(preview.to_json()))
+}
+
+/// Send a previewed operation, unless it expired or the chain changed so it would now do something else.
+#[utoipa::path(
+    post,
+    path = "/v1/admin/previews/{id}/confirm",
+    params(("id" = String, Path, description = "Preview id")),
+    responses(
+        (status = 200, body = ConfirmedBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, body = ErrorBody),
+        (status = 503, body = ErrorBody)
+    )
+)]
+async fn confirm_admin(
+    caller: Caller,
+    State(admin): State<Option<Arc<Admin>>>,
+    Path(id): Path<String>,
+) -> ApiResult<ConfirmedBody> {
+    caller.require(Scope::Admin)?;
+    let admin = admin.ok_or_else(no_admin)?;
+    let signature = admin.confirm(&id).await.map_err(ControlError::from)?;
+    Ok(Json(ConfirmedBody {
+        signature: signature.to_string(),
+    }))
+}
+
+fn no_admin() -> ControlError {
+    ControlError::Unavailable("admin operations need swapd started with --admin-keypair".into())
+}
+
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+pub struct RestApi {
+    cfg: RestConfig,
+    maker: Arc<Maker>,
+    auth: Authenticator,
+    limiter: Arc<RateLimiter>,
+    admin: Option<Arc<Admin>>,
+}
+
+impl RestApi {
+    /// `limiter` may be shared with the gRPC API, so its limits hold across both.
+    pub fn new(cfg: RestConfig, maker: Arc<Maker>, auth: Authenticator, limiter: Arc<RateLimiter>) -> Self {
+        Self {
+            cfg,
+            maker,
+            auth,
+            limiter,
+            admin: None,
+        }
+    }
+
+    /// Serves the admin endpoints, which send transactions signed by `admin`'s authority key.
+    pub fn with_admin(mut self, admin: Arc<Admin>) -> Self {
+        self.admin = Some(admin);
+        self
+    }
+
+    /// Serves the API until `shutdown` resolves. Swaps it queues are picked up by the engine that owns `wake`.
+    pub async fn run<L: LnBackend>(
+        &self,
+        ln: &L,
+        wake: Arc<Notify>,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> std::io::Result<()> {
+        let (control, calls) = Control::new(self.maker.clone(), wake);
+        let app = Router::new()
+            .route("/v1/info", get(get_info))
+            .route("/v1/quotes", post(quote))
+            .route("/v1/swaps", get(list_swaps).post(create_swap))
+            .route("/v1/swaps/:id", get(get_swap))
+            .route("/v1/swaps/:id/cancel", post(cancel_swap))
+            .route("/v1/swaps/:id/onchain-fallback", post(register_onchain_fallback))
+            .route("/v1/swaps/:id/htlc", get(get_htlc))
+            .route("/v1/export", get(export_swaps))
+            .route("/v1/pnl", get(pnl))
+            .route("/v1/dead-letters", get(list_dead_letters))
+            .route("/v1/dead-letters/:id/retry", post(retry_dead_letter))
+            .route("/v1/admin/previews", post(preview_admin))
+            .route("/v1/admin/previews/:id/confirm", post(confirm_admin))
+            .route("/v1/ws", get(crate::ws::upgrade))
+            .route("/openapi.json", get(openapi))
+            .with_state(ApiState {
+                control,
+                auth: self.auth.clone(),
+                limiter: self.limiter.clone(),
+                admin: self.admin.clone(),
+            });
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.cfg.tls {
+            let cfg = tls.server_config(&[b"h2", b"http/1.1"]);
+            return calls
+                .answer_until(ln, serve_tls(self.cfg.listen, cfg, app, shutdown))
+                .await;
+        }
+        let listener = tokio::net::TcpListener::bind(self.cfg.listen).await?;
+        info!(listen = %self.cfg.listen, "rest api listening");
+        let app = app.into_make_service_with_connect_info::<SocketAddr>();
+        let server = axum::serve(listener, app).with_graceful_shutdown(shutdown);
+        calls
+            .answer_until(ln, std::future::IntoFuture::into_future(server))
+            .await
+    }
+}
+
+/// Serves `app` over TLS until `shutdown` resolves, then lets open requests and WebSockets finish for a while.
+#[cfg(feature = "tls")]
+async fn serve_tls(
+    listen: SocketAddr,
+    cfg: Arc<tokio_rustls::rustls::ServerConfig>,
+    app: Router,
+    shutdown: impl Future<Output = ()>,
+) -> std::io::Result<()> {
+    let handle = axum_server::Handle::new();
+    let server = axum_server::bind_rustls(listen, axum_server::tls_rustls::RustlsConfig::from_config(cfg))
+        .handle(handle.clone())
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>());
+    info!(%listen, "rest api listening over tls");
+    tokio::pin!(server, shutdown);
+    tokio::select! {
+        served = &mut server => served,
+        _ = &mut shutdown => {
+            handle.graceful_shutdown(Some(std::time::Duration::from_secs(10)));
+            server.await
+        }
+    }
+}
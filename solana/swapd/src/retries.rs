@@ -0,0 +1,113 @@
+//! Persistent retry queue for swap steps that fail on something outside the daemon: a Solana send or read, a
+//! Lightning node call, the database. A failed step is retried with exponential backoff rather than on every
+//! tick, and the schedule survives restarts. After the last attempt the swap lands in the dead-letter table and
+//! an alert goes out; the engine leaves it alone until an operator resolves the letter. Swaps whose claim is
+//! close to refund_after are the exception: they keep being stepped whatever their schedule, since waiting
+//! would forfeit the funds.
+
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::swap::SwapState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepRetryPolicy {
+    /// Failed attempts of one step before it is dead-lettered.
+    pub max_attempts: u32,
+    /// Wait after the first failure, doubled after each further one up to `max_backoff`.
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for StepRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 20,
+            backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(600),
+        }
+    }
+}
+
+impl StepRetryPolicy {
+    /// When to retry after `attempts` failed attempts, or `None` to give up.
+    pub fn next_attempt_at(&self, attempts: u32, now: i64) -> Option<i64> {
+        if attempts >= self.max_attempts {
+            return None;
+        }
+        let backoff = self
+            .backoff
+            .saturating_mul(1 << attempts.saturating_sub(1).min(20))
+            .min(self.max_backoff);
+        Some(now.saturating_add(backoff.as_secs() as i64))
+    }
+}
+
+/// A swap whose step in `state` failed and is waiting to be retried.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepRetry {
+    pub swap_id: String,
+    /// The step that failed; a swap that moved on since starts counting again.
+    pub state: SwapState,
+    pub attempts: u32,
+    pub next_attempt_at: i64,
+    pub last_error: String,
+    pub first_failed_at: i64,
+}
+
+impl StepRetry {
+    /// The record after one more failure of `swap_id` in `state`, counting on from `previous` if it was the
+    /// same step.
+    pub fn failed(previous: Option<&Self>, swap_id: &str, state: SwapState, error: String, now: i64) -> Self {
+        match previous.filter(|p| p.state == state) {
+            Some(previous) => Self {
+                attempts: previous.attempts + 1,
+                last_error: error,
+                ..previous.clone()
+            },
+            None => Self {
+                swap_id: swap_id.to_string(),
+                state,
+                attempts: 1,
+                next_attempt_at: now,
+                last_error: error,
+                first_failed_at: now,
+            },
+        }
+    }
+
+    /// Whether the backoff still holds the swap back at `now`.
+    pub fn waiting(&self, state: SwapState, now: i64) -> bool {
+        self.state == state && self.next_attempt_at > now
+    }
+}
+
+/// A step given up on, kept for an operator to look into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetter {
+    pub id: i64,
+    pub swap_id: String,
+    pub state: SwapState,
+    pub attempts: u32,
+    pub last_error: String,
+    pub first_failed_at: i64,
+    pub dead_at: i64,
+    /// When an operator sent the swap back to the engine.
+    pub resolved_at: Option<i64>,
+}
+
+impl DeadLetter {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "swapId": self.swap_id,
+            "state": self.state.as_str(),
+            "attempts": self.attempts,
+            "lastError": self.last_error,
+            "firstFailedAt": self.first_failed_at,
+            "deadAt": self.dead_at,
+            "resolvedAt": self.resolved_at,
+        })
+    }
+}
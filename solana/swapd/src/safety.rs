@@ -0,0 +1,93 @@
+//! Timing checks between the Lightning leg and the escrow's refund_after.
+//!
+//! In a UsdtToLn swap the operator pays the user's invoice and claims the escrow with the preimage the payment
+//! reveals. An outgoing HTLC can stay in flight until its CLTV expiry, so a payment may succeed, and cost the
+//! operator its BTC, only blocks later. If that is past refund_after minus the time a claim needs, the user can
+//! refund the USDT as well. [`CltvSafety`] bounds the route's total CLTV so every payment resolves in time and
+//! rejects swaps whose invoice cannot be routed within that bound.
+
+use std::fmt;
+
+use crate::ln::DecodedInvoice;
+
+/// Mean Bitcoin block interval.
+pub const DEFAULT_BLOCK_TIME_SECS: i64 = 600;
+/// Headroom over the invoice's final CLTV delta for the intermediate hops of a route.
+pub const DEFAULT_ROUTE_CLTV_MARGIN_BLOCKS: u32 = 144;
+/// Hard cap on the total CLTV of any payment, matching LND's default `max-cltv-expiry`.
+pub const DEFAULT_MAX_CLTV_BLOCKS: u32 = 2016;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CltvSafety {
+    /// Seconds assumed per block when turning CLTV deltas into time. Err long: slow blocks delay expiry.
+    pub block_time_secs: i64,
+    /// Blocks reserved for intermediate hops on top of the invoice's final CLTV delta.
+    pub route_margin_blocks: u32,
+    pub max_cltv_blocks: u32,
+}
+
+impl Default for CltvSafety {
+    fn default() -> Self {
+        Self {
+            block_time_secs: DEFAULT_BLOCK_TIME_SECS,
+            route_margin_blocks: DEFAULT_ROUTE_CLTV_MARGIN_BLOCKS,
+            max_cltv_blocks: DEFAULT_MAX_CLTV_BLOCKS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SafetyError {
+    /// Not enough blocks left before refund_after for a route to the invoice's destination.
+    CltvWindow { needed_blocks: u32, available_blocks: u32 },
+}
+
+impl fmt::Display for SafetyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CltvWindow {
+                needed_blocks,
+                available_blocks,
+            } => write!(
+                f,
+                "payment may take {needed_blocks} blocks to resolve, but only {available_blocks} fit before the \
+                 escrow must be claimed"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SafetyError {}
+
+impl CltvSafety {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.block_time_secs <= 0 {
+            return Err("block time must be positive".into());
+        }
+        Ok(())
+    }
+
+    /// Largest total CLTV delta a payment made at `now` may use and still resolve by `claim_by`: refund_after
+    /// less the time the claim needs.
+    pub fn cltv_limit(&self, now: i64, claim_by: i64) -> u32 {
+        let window = claim_by.saturating_sub(now);
+        let blocks = window.max(0) / self.block_time_secs.max(1);
+        u32::try_from(blocks).unwrap_or(u32::MAX).min(self.max_cltv_blocks)
+    }
+
+    /// Checks that `invoice` can be paid at `now` within [`Self::cltv_limit`], leaving room for the route's
+    /// hops; returns the limit to pay with.
+    pub fn check_payment(&self, now: i64, invoice: &DecodedInvoice, claim_by: i64) -> Result<u32, SafetyError> {
+        let available_blocks = self.cltv_limit(now, claim_by);
+        let needed_blocks = u32::try_from(invoice.min_final_cltv_expiry_delta)
+            .unwrap_or(u32::MAX)
+            .saturating_add(self.route_margin_blocks);
+        if needed_blocks > available_blocks {
+            return Err(SafetyError::CltvWindow {
+                needed_blocks,
+                available_blocks,
+            });
+        }
+        Ok(available_blocks)
+    }
+}
@@ -0,0 +1,291 @@
+//! Compliance screening of swaps before they are accepted. Each [`Screener`] (an address-screening provider or
+//! the operator's own [`Rules`]) sees the counterparty's Solana address and the amounts and answers with a
+//! [`Verdict`]: allow, flag (accept, but alert for review) or deny. The strictest verdict wins, and a screener that
+//! cannot answer counts as the configured `on_error` action. Every screened swap is recorded with its outcome for
+//! audit, denied ones included.
+
+use std::{collections::HashMap, fmt, str::FromStr, sync::Arc};
+
+use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+use tracing::warn;
+
+use crate::{
+    store::{Store, StoreError},
+    swap::{unix_now, Direction, Swap},
+};
+
+#[derive(Debug)]
+pub enum ScreeningError {
+    /// The swap may not be accepted; the reason is the deciding screener's.
+    Denied(String),
+    Store(StoreError),
+}
+
+impl fmt::Display for ScreeningError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Denied(reason) => write!(f, "denied by screening: {reason}"),
+            Self::Store(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ScreeningError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Store(e) => Some(e),
+            Self::Denied(_) => None,
+        }
+    }
+}
+
+impl From<StoreError> for ScreeningError {
+    fn from(e: StoreError) -> Self {
+        Self::Store(e)
+    }
+}
+
+/// What a screening rule or provider answer does to a swap.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    Allow,
+    /// Accept the swap and raise a `screening_flagged` alert.
+    Flag,
+    #[default]
+    Deny,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Allow => "allow",
+            Self::Flag => "flag",
+            Self::Deny => "deny",
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(Self::Allow),
+            "flag" => Ok(Self::Flag),
+            "deny" => Ok(Self::Deny),
+            other => Err(format!("unknown screening action {other:?}")),
+        }
+    }
+}
+
+/// One screener's answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verdict {
+    pub action: Action,
+    /// Why, for the audit record and the alert; shown to the counterparty only as a denial.
+    pub reason: Option<String>,
+}
+
+impl Verdict {
+    pub fn allow() -> Self {
+        Self {
+            action: Action::Allow,
+            reason: None,
+        }
+    }
+
+    pub fn new(action: Action, reason: impl Into<String>) -> Self {
+        Self {
+            action,
+            reason: Some(reason.into()),
+        }
+    }
+}
+
+/// What a swap is screened on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subject<'a> {
+    pub direction: Direction,
+    /// Recipient of the USDT (LnToUsdt) or refund key of the counterparty's escrow (UsdtToLn).
+    pub counterparty: &'a Pubkey,
+    pub amount_msat: u64,
+    pub token_amount: u64,
+    /// Where the swap came from: the transport it was negotiated or created over (`grpc`, `rest`, `nostr`,
+    /// `libp2p`), `lnurl` or `cli`.
+    pub source: &'a str,
+}
+
+/// An address-screening provider or internal rule set. Screening runs while the swap is being accepted, so an
+/// implementation that asks a remote service should answer from a local cache or keep its timeout short.
+pub trait Screener: Send + Sync {
+    /// Recorded with each verdict.
+    fn name(&self) -> &str;
+
+    /// `Err` when the screener cannot answer; the swap then gets [`Screening`]'s `on_error` action.
+    fn screen(&self, subject: &Subject<'_>) -> Result<Verdict, String>;
+}
+
+/// The operator's own rules: listed addresses and large amounts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rules {
+    /// An address listed as allowed is exempt from the amount rule.
+    pub addresses: HashMap<Pubkey, Action>,
+    /// Swaps above this Lightning amount get `large_amount_action`.
+    pub large_amount_msat: Option<u64>,
+    pub large_amount_action: Action,
+}
+
+impl Rules {
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty() && self.large_amount_msat.is_none()
+    }
+}
+
+impl Screener for Rules {
+    fn name(&self) -> &str {
+        "rules"
+    }
+
+    fn screen(&self, subject: &Subject<'_>) -> Result<Verdict, String> {
+        let listed = match self.addresses.get(subject.counterparty) {
+            Some(Action::Allow) => return Ok(Verdict::allow()),
+            listed => {
+                listed.map(|&action| Verdict::new(action, format!("{} is on the {action} list", subject.counterparty)))
+            }
+        };
+        let large = self
+            .large_amount_msat
+            .filter(|&limit| subject.amount_msat > limit)
+            .map(|limit| Verdict::new(self.large_amount_action, format!("amount is over {limit} msat")));
+        Ok([listed, large]
+            .into_iter()
+            .flatten()
+            .max_by_key(|v| v.action)
+            .unwrap_or_else(Verdict::allow))
+    }
+}
+
+/// A screening outcome as recorded for audit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreeningRecord {
+    /// The swap that was screened; never stored when denied.
+    pub swap_id: String,
+    pub counterparty: Pubkey,
+    pub direction: Direction,
+    pub amount_msat: u64,
+    pub token_amount: u64,
+    pub source: String,
+    pub action: Action,
+    /// The screener whose verdict decided it, unless every one allowed it.
+    pub screener: Option<String>,
+    pub reason: Option<String>,
+    pub screened_at: i64,
+}
+
+impl ScreeningRecord {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "swapId": self.swap_id,
+            "counterparty": self.counterparty.to_string(),
+            "direction": self.direction.as_str(),
+            "amountMsat": self.amount_msat,
+            "tokenAmount": self.token_amount,
+            "source": self.source,
+            "action": self.action.as_str(),
+            "screener": self.screener,
+            "reason": self.reason,
+            "screenedAt": self.screened_at,
+        })
+    }
+}
+
+/// The screeners every intake path runs. Without any, every swap is allowed and nothing is recorded.
+#[derive(Clone, Default)]
+pub struct Screening {
+    screeners: Vec<Arc<dyn Screener>>,
+    /// What a screener that cannot answer counts as; denies by default.
+    pub on_error: Action,
+}
+
+impl Screening {
+    pub fn with_screener(mut self, screener: Arc<dyn Screener>) -> Self {
+        self.screeners.push(screener);
+        self
+    }
+
+    pub fn with_on_error(mut self, on_error: Action) -> Self {
+        self.on_error = on_error;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.screeners.is_empty()
+    }
+
+    /// Screens `swap`, arriving from `source`, and records the outcome.
+    pub fn check(&self, store: &Store, swap: &Swap, source: &str) -> Result<(), ScreeningError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let subject = Subject {
+            direction: swap.direction,
+            counterparty: &swap.counterparty,
+            amount_msat: swap.amount_msat,
+            token_amount: swap.token_amount,
+            source,
+        };
+        let mut decided: Option<(&str, Verdict)> = None;
+        for screener in &self.screeners {
+            let verdict = screener.screen(&subject).unwrap_or_else(|e| {
+                warn!(screener = screener.name(), error = %e, "screener did not answer");
+                Verdict::new(self.on_error, format!("screener unavailable: {e}"))
+            });
+            if verdict.action > decided.as_ref().map_or(Action::Allow, |(_, v)| v.action) {
+                decided = Some((screener.name(), verdict));
+            }
+        }
+        let (screener, verdict) = match decided {
+            Some((name, verdict)) => (Some(name.to_string()), verdict),
+            None => (None, Verdict::allow()),
+        };
+        store.insert_screening(&ScreeningRecord {
+            swap_id: swap.id.clone(),
+            counterparty: swap.counterparty,
+            direction: swap.direction,
+            amount_msat: swap.amount_msat,
+            token_amount: swap.token_amount,
+            source: source.to_string(),
+            action: verdict.action,
+            screener: screener.clone(),
+            reason: verdict.reason.clone(),
+            screened_at: unix_now(),
+        })?;
+        let screener = screener.unwrap_or_default();
+        let reason = verdict.reason.unwrap_or_default();
+        match verdict.action {
+            Action::Allow => Ok(()),
+            Action::Flag => {
+                warn!(
+                    alert = "screening_flagged",
+                    swap = %swap.id,
+                    counterparty = %swap.counterparty,
+                    screener,
+                    reason,
+                    "swap flagged by screening; accepted for review"
+                );
+                Ok(())
+            }
+            Action::Deny => {
+                warn!(swap = %swap.id, counterparty = %swap.counterparty, screener, reason, "swap denied by screening");
+                Err(ScreeningError::Denied(reason))
+            }
+        }
+    }
+}
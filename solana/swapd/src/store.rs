@@ -0,0 +1,2782 @@
+//! SQLite persistence for swaps. Every state transition is written before the engine moves on, so a restart
+//! resumes each swap from its last recorded state, and is appended to the swap [`journal`](crate::journal) in the
+//! same transaction. The swaps themselves can be kept in a database several instances share instead, through a
+//! [`SwapStore`].
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bitcoin::secp256k1::XOnlyPublicKey;
+use rusqlite::{params, Connection, OptionalExtension, Params, Row};
+use solana_sdk::{hash::hash, pubkey::Pubkey};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+use crate::{
+    auth::ApiKey,
+    control::IdempotentRequest,
+    engine::Checkpoint,
+    hedge::{Execution, HedgeOrder, Order, OrderStatus, Position},
+    journal::{self, Cause, EventKind, RawEvent, Rebuilt, SwapEvent},
+    keysend::KeysendQuote,
+    ledger::{Account, Asset, Entry, EntryKind},
+    migrate::{self, Migration, NewerSchema},
+    negotiate::PeerSession,
+    onchain::{Htlc, HtlcRecord, HtlcStatus, Registration},
+    quote::{Quote, QuoteRecord},
+    reputation::{Outcome, Reputation},
+    retries::{DeadLetter, StepRetry},
+    screening::ScreeningRecord,
+    swap::{unix_now, Direction, Swap, SwapState},
+    tolerance::AmountCheck,
+    tower::Watch,
+    vault::{Preimage, Vault, VaultError, SEALED_LEN},
+    webhook::{Delivery, DeliveryStatus},
+};
+
+/// The schema as of versioning; `IF NOT EXISTS` throughout, as databases from before it already have some of it.
+const V1_INITIAL: &str = "
+CREATE TABLE IF NOT EXISTS swaps (
+    id TEXT PRIMARY KEY,
+    direction TEXT NOT NULL,
+    state TEXT NOT NULL,
+    payment_hash BLOB NOT NULL,
+    preimage BLOB,
+    bolt11 TEXT,
+    amount_msat INTEGER NOT NULL,
+    hold INTEGER NOT NULL DEFAULT 0,
+    description TEXT,
+    token_amount INTEGER NOT NULL,
+    counterparty TEXT NOT NULL,
+    refund_after INTEGER,
+    deadline INTEGER NOT NULL,
+    signature TEXT,
+    error TEXT,
+    cancel_requested INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS swaps_state ON swaps (state);
+CREATE TABLE IF NOT EXISTS quotes (
+    id TEXT PRIMARY KEY,
+    quote TEXT NOT NULL,
+    preimage TEXT,
+    swap_id TEXT,
+    expires_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS keysend_quotes (
+    id TEXT PRIMARY KEY,
+    recipient TEXT NOT NULL,
+    amount_msat INTEGER NOT NULL,
+    token_amount INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    payment_hash TEXT,
+    created_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS swap_signatures (
+    swap_id TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    state TEXT NOT NULL,
+    recorded_at INTEGER NOT NULL,
+    PRIMARY KEY (swap_id, signature)
+);
+CREATE TABLE IF NOT EXISTS ledger_entries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL,
+    swap_id TEXT,
+    note TEXT,
+    created_at INTEGER NOT NULL
+);
+CREATE UNIQUE INDEX IF NOT EXISTS ledger_entries_once_per_swap ON ledger_entries (swap_id, kind)
+    WHERE swap_id IS NOT NULL;
+CREATE TABLE IF NOT EXISTS ledger_postings (
+    entry_id INTEGER NOT NULL REFERENCES ledger_entries (id),
+    account TEXT NOT NULL,
+    asset TEXT NOT NULL,
+    amount INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS ledger_postings_by_entry ON ledger_postings (entry_id);
+CREATE TABLE IF NOT EXISTS cursors (
+    name TEXT PRIMARY KEY,
+    value INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS counterparty_outcomes (
+    swap_id TEXT PRIMARY KEY,
+    counterparty TEXT NOT NULL,
+    outcome TEXT NOT NULL,
+    amount_msat INTEGER NOT NULL,
+    note TEXT,
+    recorded_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS counterparty_outcomes_by_counterparty ON counterparty_outcomes (counterparty);
+CREATE TABLE IF NOT EXISTS peer_sessions (
+    swap_id TEXT PRIMARY KEY,
+    transport TEXT NOT NULL,
+    peer TEXT NOT NULL,
+    notified_state TEXT
+);
+CREATE TABLE IF NOT EXISTS tower_watches (
+    payment_hash TEXT PRIMARY KEY,
+    refund_tx TEXT,
+    status TEXT NOT NULL,
+    error TEXT,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    url TEXT NOT NULL,
+    event TEXT NOT NULL,
+    swap_id TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at INTEGER NOT NULL,
+    last_error TEXT,
+    created_at INTEGER NOT NULL,
+    delivered_at INTEGER,
+    UNIQUE (url, swap_id, event)
+);
+CREATE INDEX IF NOT EXISTS webhook_deliveries_due ON webhook_deliveries (status, next_attempt_at);
+CREATE TABLE IF NOT EXISTS api_keys (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    key_hash TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    last_used_at INTEGER,
+    revoked_at INTEGER
+);
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    caller TEXT NOT NULL,
+    key TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    result TEXT,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (caller, key)
+);
+CREATE INDEX IF NOT EXISTS idempotency_keys_by_age ON idempotency_keys (created_at);
+CREATE TABLE IF NOT EXISTS checkpoint (
+    swap_id TEXT PRIMARY KEY,
+    state TEXT NOT NULL,
+    payment_in_flight INTEGER NOT NULL,
+    claim_failures INTEGER NOT NULL,
+    taken_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS screenings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    swap_id TEXT NOT NULL,
+    counterparty TEXT NOT NULL,
+    direction TEXT NOT NULL,
+    amount_msat INTEGER NOT NULL,
+    token_amount INTEGER NOT NULL,
+    source TEXT NOT NULL,
+    action TEXT NOT NULL,
+    screener TEXT,
+    reason TEXT,
+    screened_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS screenings_by_counterparty ON screenings (counterparty);
+";
+
+/// Retry schedule of failed swap steps, and the ones given up on ([`crate::retries`]).
+const V2_STEP_RETRIES: &str = "
+CREATE TABLE step_retries (
+    swap_id TEXT PRIMARY KEY,
+    state TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    next_attempt_at INTEGER NOT NULL,
+    last_error TEXT NOT NULL,
+    first_failed_at INTEGER NOT NULL
+);
+CREATE TABLE dead_letters (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    swap_id TEXT NOT NULL,
+    state TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    last_error TEXT NOT NULL,
+    first_failed_at INTEGER NOT NULL,
+    dead_at INTEGER NOT NULL,
+    resolved_at INTEGER
+);
+CREATE INDEX dead_letters_open ON dead_letters (swap_id) WHERE resolved_at IS NULL;
+";
+
+/// The swap journal ([`crate::journal`]), which nothing may change once written.
+const V3_SWAP_JOURNAL: &str = "
+CREATE TABLE swap_events (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    swap_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    from_state TEXT,
+    to_state TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    reason TEXT,
+    follows INTEGER,
+    snapshot TEXT NOT NULL,
+    recorded_at INTEGER NOT NULL
+);
+CREATE INDEX swap_events_by_swap ON swap_events (swap_id, seq);
+CREATE TRIGGER swap_events_no_update BEFORE UPDATE ON swap_events
+BEGIN
+    SELECT RAISE(ABORT, 'the swap journal is append-only');
+END;
+CREATE TRIGGER swap_events_no_delete BEFORE DELETE ON swap_events
+BEGIN
+    SELECT RAISE(ABORT, 'the swap journal is append-only');
+END;
+";
+
+/// Escrow amounts checked against their swaps ([`crate::tolerance`]), the latest per swap.
+const V4_AMOUNT_CHECKS: &str = "
+CREATE TABLE amount_checks (
+    swap_id TEXT PRIMARY KEY,
+    expected INTEGER NOT NULL,
+    actual INTEGER NOT NULL,
+    allowed INTEGER NOT NULL,
+    accepted INTEGER NOT NULL,
+    checked_at INTEGER NOT NULL
+);
+";
+
+const V5_HEDGES: &str = "
+CREATE TABLE hedge_fills (
+    swap_id TEXT PRIMARY KEY,
+    fill_msat INTEGER NOT NULL,
+    recorded_at INTEGER NOT NULL
+);
+CREATE TABLE hedge_orders (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    venue TEXT NOT NULL,
+    side TEXT NOT NULL,
+    amount_sat INTEGER NOT NULL,
+    client_order_id TEXT NOT NULL UNIQUE,
+    venue_order_id TEXT,
+    status TEXT NOT NULL,
+    error TEXT,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+";
+
+const V6_SWAP_FENCES: &str = "
+CREATE TABLE swap_fences (
+    swap_id TEXT PRIMARY KEY,
+    bundle_id TEXT NOT NULL,
+    fenced_at INTEGER NOT NULL
+);
+CREATE INDEX swap_fences_bundle ON swap_fences (bundle_id);
+";
+
+const V7_ONCHAIN_HTLCS: &str = "
+CREATE TABLE onchain_fallbacks (
+    swap_id TEXT PRIMARY KEY,
+    claim_key BLOB NOT NULL,
+    ln_failures INTEGER NOT NULL DEFAULT 0,
+    registered_at INTEGER NOT NULL
+);
+CREATE TABLE onchain_htlcs (
+    swap_id TEXT PRIMARY KEY,
+    payment_hash BLOB NOT NULL,
+    address TEXT NOT NULL,
+    amount_sat INTEGER NOT NULL,
+    timeout_height INTEGER NOT NULL,
+    claim_key BLOB NOT NULL,
+    refund_key BLOB NOT NULL,
+    refund_secret BLOB NOT NULL,
+    status TEXT NOT NULL,
+    funding_txid TEXT,
+    funding_vout INTEGER,
+    spend_txid TEXT,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+";
+
+const V8_HEDGE_EXECUTIONS: &str = "
+ALTER TABLE hedge_orders ADD COLUMN filled_sat INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE hedge_orders ADD COLUMN cost_tokens INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE hedge_orders ADD COLUMN fee_tokens INTEGER NOT NULL DEFAULT 0;
+";
+
+/// Applied in order, once each; see [`migrate`].
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        sql: V1_INITIAL,
+    },
+    Migration {
+        version: 2,
+        name: "step retries",
+        sql: V2_STEP_RETRIES,
+    },
+    Migration {
+        version: 3,
+        name: "swap journal",
+        sql: V3_SWAP_JOURNAL,
+    },
+    Migration {
+        version: 4,
+        name: "amount checks",
+        sql: V4_AMOUNT_CHECKS,
+    },
+    Migration {
+        version: 5,
+        name: "hedges",
+        sql: V5_HEDGES,
+    },
+    Migration {
+        version: 6,
+        name: "swap_fences",
+        sql: V6_SWAP_FENCES,
+    },
+    Migration {
+        version: 7,
+        name: "onchain htlcs",
+        sql: V7_ONCHAIN_HTLCS,
+    },
+    Migration {
+        version: 8,
+        name: "hedge executions",
+        sql: V8_HEDGE_EXECUTIONS,
+    },
+];
+
+pub(crate) const COLUMNS: &str = "id, direction, state, payment_hash, preimage, bolt11, amount_msat, token_amount, \
+                                  counterparty, refund_after, deadline, signature, error, created_at, updated_at, \
+                                  hold, description, cancel_requested";
+
+pub(crate) const EVENT_COLUMNS: &str =
+    "seq, swap_id, kind, from_state, to_state, actor, reason, follows, snapshot, recorded_at";
+
+pub(crate) const TERMINAL: &str = "('completed', 'refunded', 'failed')";
+
+#[derive(Debug)]
+pub enum StoreError {
+    Sqlite(rusqlite::Error),
+    #[cfg(feature = "postgres")]
+    Postgres(sqlx::Error),
+    Vault(VaultError),
+    /// A row that does not decode into a [`Swap`].
+    Corrupt {
+        id: String,
+        reason: String,
+    },
+    /// A ledger entry whose postings do not sum to zero per asset.
+    Unbalanced(String),
+    /// Written by a newer release.
+    NewerSchema(NewerSchema),
+    /// A swap written by someone else since it was read; see [`SwapStore::update`].
+    Conflict {
+        id: String,
+    },
+    /// A swap handed off to another host in bundle `bundle_id`; see [`SwapStore::fence`].
+    Fenced {
+        id: String,
+        bundle_id: String,
+    },
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sqlite(e) => write!(f, "database error: {e}"),
+            #[cfg(feature = "postgres")]
+            Self::Postgres(e) => write!(f, "database error: {e}"),
+            Self::Vault(e) => write!(f, "{e}"),
+            Self::Corrupt { id, reason } => write!(f, "swap {id} is corrupt: {reason}"),
+            Self::Unbalanced(kind) => write!(f, "ledger entry {kind} does not balance"),
+            Self::NewerSchema(e) => write!(f, "{e}"),
+            Self::Conflict { id } => write!(f, "swap {id} changed since it was read; not overwriting it"),
+            Self::Fenced { id, bundle_id } => write!(f, "swap {id} was handed off in bundle {bundle_id}"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Sqlite(e) => Some(e),
+            #[cfg(feature = "postgres")]
+            Self::Postgres(e) => Some(e),
+            Self::Vault(e) => Some(e),
+            Self::NewerSchema(e) => Some(e),
+            Self::Corrupt { .. } | Self::Unbalanced(_) | Self::Conflict { .. } | Self::Fenced { .. } => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::Sqlite(e)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<sqlx::Error> for StoreError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Postgres(e)
+    }
+}
+
+impl From<NewerSchema> for StoreError {
+    fn from(e: NewerSchema) -> Self {
+        Self::NewerSchema(e)
+    }
+}
+
+impl From<VaultError> for StoreError {
+    fn from(e: VaultError) -> Self {
+        Self::Vault(e)
+    }
+}
+
+/// Raw column values as either backend reads them; decoded outside the row closure so bad rows surface as
+/// [`StoreError::Corrupt`].
+pub(crate) struct RawSwap {
+    pub(crate) id: String,
+    pub(crate) direction: String,
+    pub(crate) state: String,
+    pub(crate) payment_hash: Vec<u8>,
+    pub(crate) preimage: Option<Vec<u8>>,
+    pub(crate) bolt11: Option<String>,
+    pub(crate) amount_msat: i64,
+    pub(crate) token_amount: i64,
+    pub(crate) counterparty: String,
+    pub(crate) refund_after: Option<i64>,
+    pub(crate) deadline: i64,
+    pub(crate) signature: Option<String>,
+    pub(crate) error: Option<String>,
+    pub(crate) created_at: i64,
+    pub(crate) updated_at: i64,
+    pub(crate) hold: bool,
+    pub(crate) description: Option<String>,
+    pub(crate) cancel_requested: bool,
+}
+
+impl RawSwap {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            direction: row.get(1)?,
+            state: row.get(2)?,
+            payment_hash: row.get(3)?,
+            preimage: row.get(4)?,
+            bolt11: row.get(5)?,
+            amount_msat: row.get(6)?,
+            token_amount: row.get(7)?,
+            counterparty: row.get(8)?,
+            refund_after: row.get(9)?,
+            deadline: row.get(10)?,
+            signature: row.get(11)?,
+            error: row.get(12)?,
+            created_at: row.get(13)?,
+            updated_at: row.get(14)?,
+            hold: row.get(15)?,
+            description: row.get(16)?,
+            cancel_requested: row.get(17)?,
+        })
+    }
+
+    pub(crate) fn decode(self) -> Result<Swap, StoreError> {
+        let corrupt = |reason: String| StoreError::Corrupt {
+            id: self.id.clone(),
+            reason,
+        };
+        let hash32 = |bytes: Vec<u8>| -> Result<[u8; 32], StoreError> {
+            bytes.try_into().map_err(|_| corrupt("expected 32 bytes".into()))
+        };
+        Ok(Swap {
+            direction: self.direction.parse().map_err(corrupt)?,
+            state: self.state.parse().map_err(corrupt)?,
+            payment_hash: hash32(self.payment_hash)?,
+            preimage: match self.preimage {
+                Some(sealed) if sealed.len() == SEALED_LEN => Some(Preimage::Sealed(sealed)),
+                Some(_) => return Err(corrupt("preimage is not sealed".into())),
+                None => None,
+            },
+            bolt11: self.bolt11,
+            amount_msat: self.amount_msat as u64,
+            hold: self.hold,
+            description: self.description,
+            token_amount: self.token_amount as u64,
+            counterparty: self
+                .counterparty
+                .parse::<Pubkey>()
+                .map_err(|e| corrupt(e.to_string()))?,
+            refund_after: self.refund_after,
+            deadline: self.deadline,
+            signature: self.signature,
+            error: self.error,
+            cancel_requested: self.cancel_requested,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            id: self.id,
+        })
+    }
+}
+
+/// Brings the database at `conn` to the newest schema version, refusing one already past it.
+fn migrate_sqlite(conn: &mut Connection, vault: &Vault) -> Result<(), StoreError> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at INTEGER NOT NULL
+        )",
+    )?;
+    let current: u32 = conn.query_row("SELECT COALESCE(MAX(version), 0) FROM schema_migrations", [], |r| {
+        r.get(0)
+    })?;
+    for migration in migrate::pending(MIGRATIONS, current)? {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql)?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, ?2, ?3)",
+            params![migration.version, migration.name, unix_now()],
+        )?;
+        tx.commit()?;
+        info!(version = migration.version, name = migration.name, "database migrated");
+    }
+    if current == 0 {
+        upgrade_unversioned(conn, vault)?;
+    }
+    Ok(())
+}
+
+/// Catches up databases from before versioning, whose tables may predate columns and formats the initial schema
+/// has. Harmless on new ones.
+fn upgrade_unversioned(conn: &Connection, vault: &Vault) -> Result<(), StoreError> {
+    add_column(conn, "swaps", "hold", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column(conn, "swaps", "description", "TEXT")?;
+    add_column(conn, "swaps", "cancel_requested", "INTEGER NOT NULL DEFAULT 0")?;
+    seal_plain_preimages(conn, vault)?;
+    // Databases from before the signature log still have each swap's latest signature.
+    conn.execute_batch(
+        "INSERT OR IGNORE INTO swap_signatures (swap_id, signature, state, recorded_at) \
+         SELECT id, signature, state, updated_at FROM swaps WHERE signature IS NOT NULL",
+    )?;
+    Ok(())
+}
+
+/// Adds a column introduced after `table` was first created, for databases from older versions.
+fn add_column(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<(), StoreError> {
+    let exists = conn
+        .prepare(&format!("SELECT 1 FROM pragma_table_info('{table}') WHERE name = ?1"))?
+        .exists([column])?;
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"))?;
+    }
+    Ok(())
+}
+
+/// Seals the preimages that versions before the vault stored in the clear, in swaps (32 bytes) and quotes
+/// (64 hex characters). Those are all LnToUsdt preimages, so the payment hash is their hash.
+fn seal_plain_preimages(conn: &Connection, vault: &Vault) -> Result<(), StoreError> {
+    let plain = |sql: &str| -> Result<Vec<(String, Zeroizing<Vec<u8>>)>, StoreError> {
+        Ok(conn
+            .prepare(sql)?
+            .query_map([], |r| Ok((r.get(0)?, Zeroizing::new(r.get(1)?))))?
+            .collect::<rusqlite::Result<_>>()?)
+    };
+    let seal =
+        |preimage: Zeroizing<[u8; 32]>| vault.seal(&hash(preimage.as_slice()).to_bytes(), &Preimage::Plain(preimage));
+    for (id, preimage) in plain("SELECT id, preimage FROM swaps WHERE length(preimage) = 32")? {
+        let mut bytes = Zeroizing::new([0u8; 32]);
+        bytes.copy_from_slice(&preimage);
+        conn.execute(
+            "UPDATE swaps SET preimage = ?2 WHERE id = ?1",
+            params![id, seal(bytes)?],
+        )?;
+    }
+    for (id, preimage) in plain("SELECT id, CAST(preimage AS BLOB) FROM quotes WHERE length(preimage) = 64")? {
+        let mut bytes = Zeroizing::new([0u8; 32]);
+        hex::decode_to_slice(preimage.as_slice(), bytes.as_mut_slice()).map_err(|e| StoreError::Corrupt {
+            id: id.clone(),
+            reason: format!("preimage: {e}"),
+        })?;
+        conn.execute(
+            "UPDATE quotes SET preimage = ?2 WHERE id = ?1",
+            params![id, hex::encode(seal(bytes)?)],
+        )?;
+    }
+    Ok(())
+}
+
+/// Swap writes buffered for live subscribers before the slowest starts missing them.
+const EVENT_BUFFER: usize = 1024;
+
+/// What a swap row held when it was read: [`SwapStore::update`] writes only over that, so an instance acting on
+/// a stale read cannot overwrite a newer write of another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Version {
+    pub state: SwapState,
+    pub updated_at: i64,
+}
+
+impl Version {
+    pub fn of(swap: &Swap) -> Self {
+        Self {
+            state: swap.state,
+            updated_at: swap.updated_at,
+        }
+    }
+}
+
+/// Where swaps and their signature log are kept: the daemon's SQLite file ([`SqliteSwaps`]) or a database
+/// several instances share (`postgres` module, `postgres` feature). Swaps reach it with their preimage sealed;
+/// [`Store`] seals them and tells subscribers about writes.
+pub trait SwapStore: Send + Sync {
+    /// Shown in logs, e.g. `sqlite`.
+    fn name(&self) -> &'static str;
+
+    /// Adds `swap`, logs its signature, if any, and journals it as created by `cause`; `false` if a swap with the
+    /// same id already exists.
+    fn insert(&self, swap: &Swap, cause: &Cause) -> Result<bool, StoreError>;
+
+    /// Writes the mutable fields of `swap`, logs its signature, if any, and journals the write as `cause`'s;
+    /// [`StoreError::Conflict`], writing nothing, unless the row is still as `read`, and [`StoreError::Fenced`]
+    /// if the swap was handed off.
+    fn update(&self, swap: &Swap, read: Version, cause: &Cause) -> Result<(), StoreError>;
+
+    /// Every transaction signature recorded for swap `id`, oldest first.
+    fn signatures_of(&self, id: &str) -> Result<Vec<String>, StoreError>;
+
+    fn get(&self, id: &str) -> Result<Option<Swap>, StoreError>;
+
+    /// Flags swap `id` for cancelling and journals the request; `false` if there is no such swap still running.
+    fn request_cancel(&self, id: &str, cause: &Cause) -> Result<bool, StoreError>;
+
+    /// Swaps not in a terminal state and not fenced, oldest first.
+    fn active(&self) -> Result<Vec<Swap>, StoreError>;
+
+    /// The swaps of [`Self::active`] no other instance holds a lease on, each now leased to `owner` until
+    /// `until`, oldest first. A lease held past `now` by someone else is left to them.
+    fn lease(&self, owner: &str, until: i64, now: i64) -> Result<Vec<Swap>, StoreError>;
+
+    /// Gives up `owner`'s leases, so other instances can take the swaps on without waiting them out.
+    fn release(&self, owner: &str) -> Result<(), StoreError>;
+
+    /// Fences swaps `ids` off as handed off in bundle `bundle_id`: left out of [`Self::active`] and refused by
+    /// [`Self::update`] from now on, for every instance sharing the store.
+    fn fence(&self, ids: &[String], bundle_id: &str, now: i64) -> Result<(), StoreError>;
+
+    /// Lifts the fences of bundle `bundle_id`; how many swaps come back.
+    fn release_fences(&self, bundle_id: &str) -> Result<usize, StoreError>;
+
+    /// Fenced swaps as `(swap_id, bundle_id, fenced_at)`, newest first.
+    fn fences(&self) -> Result<Vec<(String, String, i64)>, StoreError>;
+
+    /// Every swap, newest first.
+    fn all(&self) -> Result<Vec<Swap>, StoreError>;
+
+    /// Every swap with `counterparty`, newest first.
+    fn swaps_of(&self, counterparty: &Pubkey) -> Result<Vec<Swap>, StoreError>;
+
+    /// Swaps written at or after `since` (unix seconds), oldest write first.
+    fn swaps_updated_since(&self, since: i64) -> Result<Vec<Swap>, StoreError>;
+
+    /// Swaps created in `[from, to)` (unix seconds), oldest first.
+    fn swaps_created_between(&self, from: i64, to: i64) -> Result<Vec<Swap>, StoreError>;
+
+    /// How many swaps are in each direction and state.
+    fn state_counts(&self) -> Result<Vec<(Direction, SwapState, u64)>, StoreError>;
+
+    /// The journal of swap `id`, oldest event first.
+    fn journal(&self, id: &str) -> Result<Vec<SwapEvent>, StoreError>;
+
+    /// The newest event of every journaled swap.
+    fn latest_events(&self) -> Result<Vec<SwapEvent>, StoreError>;
+
+    /// Journals `swap`, which predates the journal, as it is.
+    fn import(&self, swap: &Swap) -> Result<(), StoreError>;
+
+    /// Overwrites the row of `swap`, or adds it back, with `swap` as journaled; nothing is journaled.
+    fn restore(&self, swap: &Swap) -> Result<(), StoreError>;
+}
+
+/// One row of a `GROUP BY direction, state` count.
+pub(crate) fn state_count(
+    direction: String,
+    state: String,
+    count: i64,
+) -> Result<(Direction, SwapState, u64), StoreError> {
+    let corrupt = |reason: String| StoreError::Corrupt {
+        id: format!("{direction}/{state}"),
+        reason,
+    };
+    Ok((
+        direction.parse().map_err(corrupt)?,
+        state.parse().map_err(corrupt)?,
+        count as u64,
+    ))
+}
+
+/// The stored form of `swap`'s preimage; [`Store`] has sealed it by the time a [`SwapStore`] sees it.
+pub(crate) fn stored_preimage(swap: &Swap) -> Option<&[u8]> {
+    match &swap.preimage {
+        Some(Preimage::Sealed(sealed)) => Some(sealed),
+        Some(Preimage::Plain(_)) | None => None,
+    }
+}
+
+/// Swaps in the daemon's own SQLite file, next to everything else it keeps.
+pub struct SqliteSwaps {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteSwaps {
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn query(&self, sql: &str, params: impl Params) -> Result<Vec<Swap>, StoreError> {
+        let raws = {
+            let conn = self.conn();
+            let mut stmt = conn.prepare(sql)?;
+            let rows = stmt.query_map(params, RawSwap::from_row)?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+        raws.into_iter().map(RawSwap::decode).collect()
+    }
+
+    fn query_events(&self, sql: &str, params: impl Params) -> Result<Vec<SwapEvent>, StoreError> {
+        let raws = {
+            let conn = self.conn();
+            let mut stmt = conn.prepare(sql)?;
+            let rows = stmt.query_map(params, raw_event)?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+        raws.into_iter().map(RawEvent::decode).collect()
+    }
+}
+
+/// Writes every column of `swap` with `verb` (`INSERT OR IGNORE` or `INSERT OR REPLACE`); the rows changed.
+fn write_row(conn: &Connection, verb: &str, swap: &Swap) -> Result<usize, StoreError> {
+    Ok(conn.execute(
+        &format!(
+            "{verb} INTO swaps ({COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, \
+             ?16, ?17, ?18)"
+        ),
+        params![
+            swap.id,
+            swap.direction.as_str(),
+            swap.state.as_str(),
+            swap.payment_hash.as_slice(),
+            stored_preimage(swap),
+            swap.bolt11,
+            swap.amount_msat as i64,
+            swap.token_amount as i64,
+            swap.counterparty.to_string(),
+            swap.refund_after,
+            swap.deadline,
+            swap.signature,
+            swap.error,
+            swap.created_at,
+            swap.updated_at,
+            swap.hold,
+            swap.description,
+            swap.cancel_requested,
+        ],
+    )?)
+}
+
+fn log_signature(conn: &Connection, swap: &Swap) -> Result<(), StoreError> {
+    if let Some(signature) = &swap.signature {
+        conn.execute(
+            "INSERT OR IGNORE INTO swap_signatures (swap_id, signature, state, recorded_at) VALUES (?1, ?2, ?3, ?4)",
+            params![swap.id, signature, swap.state.as_str(), swap.updated_at],
+        )?;
+    }
+    Ok(())
+}
+
+/// Appends the event of a write of `swap` to its journal, in the write's transaction. An update is journaled as a
+/// transition when it moved the swap on.
+fn append_event(conn: &Connection, swap: &Swap, kind: EventKind, cause: &Cause) -> Result<(), StoreError> {
+    let previous: Option<(i64, String)> = conn
+        .query_row(
+            "SELECT seq, to_state FROM swap_events WHERE swap_id = ?1 ORDER BY seq DESC LIMIT 1",
+            [&swap.id],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .optional()?;
+    let (follows, from) = previous.unzip();
+    let kind = match kind {
+        EventKind::Updated => journal::update_kind(from.as_deref(), swap.state),
+        kind => kind,
+    };
+    conn.execute(
+        &format!("INSERT INTO swap_events ({EVENT_COLUMNS}) VALUES (NULL, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"),
+        params![
+            swap.id,
+            kind.as_str(),
+            from,
+            swap.state.as_str(),
+            cause.actor,
+            cause.reason,
+            follows,
+            journal::snapshot(swap),
+            unix_now(),
+        ],
+    )?;
+    Ok(())
+}
+
+fn raw_event(row: &Row<'_>) -> rusqlite::Result<RawEvent> {
+    Ok(RawEvent {
+        seq: row.get(0)?,
+        swap_id: row.get(1)?,
+        kind: row.get(2)?,
+        from_state: row.get(3)?,
+        to_state: row.get(4)?,
+        actor: row.get(5)?,
+        reason: row.get(6)?,
+        follows: row.get(7)?,
+        snapshot: row.get(8)?,
+        recorded_at: row.get(9)?,
+    })
+}
+
+impl SwapStore for SqliteSwaps {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn insert(&self, swap: &Swap, cause: &Cause) -> Result<bool, StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let inserted = write_row(&tx, "INSERT OR IGNORE", swap)? == 1;
+        if inserted {
+            log_signature(&tx, swap)?;
+            append_event(&tx, swap, EventKind::Created, cause)?;
+        }
+        tx.commit()?;
+        Ok(inserted)
+    }
+
+    fn update(&self, swap: &Swap, read: Version, cause: &Cause) -> Result<(), StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let written = tx
+            .query_row(
+                &format!(
+                    "UPDATE swaps SET state = ?2, preimage = ?3, bolt11 = ?4, refund_after = ?5, deadline = ?6, \
+                     signature = ?7, error = ?8, updated_at = ?9 WHERE id = ?1 AND state = ?10 AND updated_at = ?11 \
+                     AND id NOT IN (SELECT swap_id FROM swap_fences) RETURNING {COLUMNS}"
+                ),
+                params![
+                    swap.id,
+                    swap.state.as_str(),
+                    stored_preimage(swap),
+                    swap.bolt11,
+                    swap.refund_after,
+                    swap.deadline,
+                    swap.signature,
+                    swap.error,
+                    swap.updated_at,
+                    read.state.as_str(),
+                    read.updated_at,
+                ],
+                RawSwap::from_row,
+            )
+            .optional()?;
+        let Some(written) = written else {
+            let fence = tx
+                .query_row(
+                    "SELECT bundle_id FROM swap_fences WHERE swap_id = ?1",
+                    [&swap.id],
+                    |r| r.get(0),
+                )
+                .optional()?;
+            return Err(match fence {
+                Some(bundle_id) => StoreError::Fenced {
+                    id: swap.id.clone(),
+                    bundle_id,
+                },
+                None => StoreError::Conflict { id: swap.id.clone() },
+            });
+        };
+        // As stored, with the cancel flag `update` leaves alone.
+        let written = written.decode()?;
+        log_signature(&tx, &written)?;
+        append_event(&tx, &written, EventKind::Updated, cause)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn signatures_of(&self, id: &str) -> Result<Vec<String>, StoreError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT signature FROM swap_signatures WHERE swap_id = ?1 ORDER BY recorded_at")?;
+        let signatures = stmt.query_map([id], |r| r.get(0))?.collect::<rusqlite::Result<_>>()?;
+        Ok(signatures)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<Swap>, StoreError> {
+        let raw = self
+            .conn()
+            .query_row(
+                &format!("SELECT {COLUMNS} FROM swaps WHERE id = ?1"),
+                [id],
+                RawSwap::from_row,
+            )
+            .optional()?;
+        raw.map(RawSwap::decode).transpose()
+    }
+
+    fn request_cancel(&self, id: &str, cause: &Cause) -> Result<bool, StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let flagged = tx
+            .query_row(
+                &format!(
+                    "UPDATE swaps SET cancel_requested = 1 WHERE id = ?1 AND state NOT IN {TERMINAL} \
+                     RETURNING {COLUMNS}"
+                ),
+                [id],
+                RawSwap::from_row,
+            )
+            .optional()?;
+        let Some(flagged) = flagged else {
+            return Ok(false);
+        };
+        append_event(&tx, &flagged.decode()?, EventKind::CancelRequested, cause)?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    fn active(&self) -> Result<Vec<Swap>, StoreError> {
+        self.query(
+            &format!(
+                "SELECT {COLUMNS} FROM swaps WHERE state NOT IN {TERMINAL} \
+                 AND id NOT IN (SELECT swap_id FROM swap_fences) ORDER BY created_at"
+            ),
+            [],
+        )
+    }
+
+    /// The file is the instance's own, so there is no one to share the swaps with.
+    fn lease(&self, _owner: &str, _until: i64, _now: i64) -> Result<Vec<Swap>, StoreError> {
+        self.active()
+    }
+
+    fn release(&self, _owner: &str) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    fn fence(&self, ids: &[String], bundle_id: &str, now: i64) -> Result<(), StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        for id in ids {
+            tx.execute(
+                "INSERT OR REPLACE INTO swap_fences (swap_id, bundle_id, fenced_at) VALUES (?1, ?2, ?3)",
+                params![id, bundle_id, now],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn release_fences(&self, bundle_id: &str) -> Result<usize, StoreError> {
+        Ok(self
+            .conn()
+            .execute("DELETE FROM swap_fences WHERE bundle_id = ?1", [bundle_id])?)
+    }
+
+    fn fences(&self) -> Result<Vec<(String, String, i64)>, StoreError> {
+        let conn = self.conn();
+        let mut stmt =
+            conn.prepare("SELECT swap_id, bundle_id, fenced_at FROM swap_fences ORDER BY fenced_at DESC, swap_id")?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn all(&self) -> Result<Vec<Swap>, StoreError> {
+        self.query(&format!("SELECT {COLUMNS} FROM swaps ORDER BY created_at DESC"), [])
+    }
+
+    fn swaps_of(&self, counterparty: &Pubkey) -> Result<Vec<Swap>, StoreError> {
+        self.query(
+            &format!("SELECT {COLUMNS} FROM swaps WHERE counterparty = ?1 ORDER BY created_at DESC"),
+            [counterparty.to_string()],
+        )
+    }
+
+    fn swaps_updated_since(&self, since: i64) -> Result<Vec<Swap>, StoreError> {
+        self.query(
+            &format!("SELECT {COLUMNS} FROM swaps WHERE updated_at >= ?1 ORDER BY updated_at"),
+            [since],
+        )
+    }
+
+    fn swaps_created_between(&self, from: i64, to: i64) -> Result<Vec<Swap>, StoreError> {
+        self.query(
+            &format!("SELECT {COLUMNS} FROM swaps WHERE created_at >= ?1 AND created_at < ?2 ORDER BY created_at"),
+            [from, to],
+        )
+    }
+
+    fn state_counts(&self) -> Result<Vec<(Direction, SwapState, u64)>, StoreError> {
+        let rows: Vec<(String, String, i64)> = {
+            let conn = self.conn();
+            let mut stmt = conn.prepare("SELECT direction, state, COUNT(*) FROM swaps GROUP BY direction, state")?;
+            let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+            rows.collect::<Result<_, _>>()?
+        };
+        rows.into_iter()
+            .map(|(direction, state, count)| state_count(direction, state, count))
+            .collect()
+    }
+
+    fn journal(&self, id: &str) -> Result<Vec<SwapEvent>, StoreError> {
+        self.query_events(
+            &format!("SELECT {EVENT_COLUMNS} FROM swap_events WHERE swap_id = ?1 ORDER BY seq"),
+            [id],
+        )
+    }
+
+    fn latest_events(&self) -> Result<Vec<SwapEvent>, StoreError> {
+        self.query_events(
+            &format!(
+                "SELECT {EVENT_COLUMNS} FROM swap_events WHERE seq IN (SELECT MAX(seq) FROM swap_events GROUP BY \
+                 swap_id)"
+            ),
+            [],
+        )
+    }
+
+    fn import(&self, swap: &Swap) -> Result<(), StoreError> {
+        append_event(&self.conn(), swap, EventKind::Imported, &Cause::new("journal"))
+    }
+
+    fn restore(&self, swap: &Swap) -> Result<(), StoreError> {
+        write_row(&self.conn(), "INSERT OR REPLACE", swap)?;
+        Ok(())
+    }
+}
+
+pub struct Store {
+    conn: Arc<Mutex<Connection>>,
+    swaps: Box<dyn SwapStore>,
+    events: broadcast::Sender<Swap>,
+    vault: Vault,
+}
+
+impl Store {
+    /// Opens the database at `path`, whose preimages `vault` seals.
+    pub fn open(path: impl AsRef<Path>, vault: Vault) -> Result<Self, StoreError> {
+        let mut conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        migrate_sqlite(&mut conn, &vault)?;
+        let conn = Arc::new(Mutex::new(conn));
+        Ok(Self {
+            swaps: Box::new(SqliteSwaps { conn: conn.clone() }),
+            conn,
+            events: broadcast::channel(EVENT_BUFFER).0,
+            vault,
+        })
+    }
+
+    /// Keeps swaps in `swaps` instead of the SQLite file, which keeps everything else.
+    pub fn with_swaps(mut self, swaps: Box<dyn SwapStore>) -> Self {
+        self.swaps = swaps;
+        self
+    }
+
+    /// Where swaps are kept, e.g. `sqlite`.
+    pub fn swap_store(&self) -> &'static str {
+        self.swaps.name()
+    }
+
+    /// Opens preimages for the engine to settle with.
+    pub(crate) fn vault(&self) -> &Vault {
+        &self.vault
+    }
+
+    /// `swap`'s preimage as stored.
+    fn sealed_preimage(&self, swap: &Swap) -> Result<Option<Vec<u8>>, StoreError> {
+        Ok(swap
+            .preimage
+            .as_ref()
+            .map(|p| self.vault.seal(&swap.payment_hash, p))
+            .transpose()?)
+    }
+
+    /// Writes a consistent copy of the database to `dest`, which must not exist, while it stays in use.
+    pub fn snapshot(&self, dest: &Path) -> Result<(), StoreError> {
+        self.conn().execute("VACUUM INTO ?1", params![dest.to_string_lossy()])?;
+        Ok(())
+    }
+
+    /// SQLite's own consistency check of the file: `ok`, or what is wrong with it.
+    pub fn integrity_check(&self) -> Result<String, StoreError> {
+        Ok(self.conn().query_row("PRAGMA integrity_check", [], |row| row.get(0))?)
+    }
+
+    /// Opens every swap's preimage with the vault, as the engine will; the number opened.
+    pub fn verify_preimages(&self) -> Result<usize, StoreError> {
+        let mut opened = 0;
+        for swap in self.all()? {
+            let Some(preimage) = &swap.preimage else {
+                continue;
+            };
+            self.vault
+                .open(&swap.payment_hash, preimage)
+                .map_err(|e| StoreError::Corrupt {
+                    id: swap.id.clone(),
+                    reason: format!("preimage: {e}"),
+                })?;
+            opened += 1;
+        }
+        Ok(opened)
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Adds `swap`, accepted by `cause`; `false` if a swap with the same payment hash already exists, which makes
+    /// intake idempotent.
+    pub fn insert(&self, swap: &Swap, cause: &Cause) -> Result<bool, StoreError> {
+        let swap = Swap {
+            preimage: self.sealed_preimage(swap)?.map(Preimage::Sealed),
+            ..swap.clone()
+        };
+        let inserted = self.swaps.insert(&swap, cause)?;
+        if inserted {
+            let _ = self.events.send(swap);
+        }
+        Ok(inserted)
+    }
+
+    /// Writes the mutable fields of `swap`, read in state `from`, on behalf of `cause` and bumps `updated_at`. A
+    /// plain preimage is sealed in `swap` too. [`StoreError::Conflict`] if the row was written since: the caller
+    /// has to read it again rather than act on what it holds.
+    pub fn update(&self, swap: &mut Swap, from: SwapState, cause: &Cause) -> Result<(), StoreError> {
+        let read = Version {
+            state: from,
+            updated_at: swap.updated_at,
+        };
+        // Strictly later, so two writes within a second still tell apart.
+        swap.updated_at = unix_now().max(read.updated_at + 1);
+        swap.preimage = self.sealed_preimage(swap)?.map(Preimage::Sealed);
+        if let Err(e) = self.swaps.update(swap, read, cause) {
+            swap.updated_at = read.updated_at;
+            return Err(e);
+        }
+        let _ = self.events.send(swap.clone());
+        Ok(())
+    }
+
+    /// Every transaction signature recorded for swap `id`, oldest first; not only its latest.
+    pub fn signatures_of(&self, id: &str) -> Result<Vec<String>, StoreError> {
+        self.swaps.signatures_of(id)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<Swap>, StoreError> {
+        self.swaps.get(id)
+    }
+
+    /// Flags swap `id` for the engine to cancel; `false` if there is no such swap still running. `update` leaves
+    /// the flag alone, so the engine's next read of the swap sees it.
+    pub fn request_cancel(&self, id: &str, cause: &Cause) -> Result<bool, StoreError> {
+        self.swaps.request_cancel(id, cause)
+    }
+
+    /// Every journaled write of swap `id`, oldest first.
+    pub fn journal(&self, id: &str) -> Result<Vec<SwapEvent>, StoreError> {
+        self.swaps.journal(id)
+    }
+
+    /// Checks every swap against the last event of its journal and restores the row from the event where the two
+    /// differ, as after a hand edit or a half-restored backup. Swaps from before the journal are journaled as
+    /// they are.
+    pub fn rebuild_from_journal(&self) -> Result<Rebuilt, StoreError> {
+        let mut latest: HashMap<String, SwapEvent> = self
+            .swaps
+            .latest_events()?
+            .into_iter()
+            .map(|event| (event.swap_id.clone(), event))
+            .collect();
+        let mut rebuilt = Rebuilt::default();
+        for swap in self.swaps.all()? {
+            match latest.remove(&swap.id) {
+                None => {
+                    self.swaps.import(&swap)?;
+                    rebuilt.imported += 1;
+                }
+                Some(event) if event.swap != swap => {
+                    warn!(
+                        alert = "journal_divergence",
+                        swap = %swap.id,
+                        state = %swap.state,
+                        journaled = %event.to,
+                        seq = event.seq,
+                        "swap differs from its journal; restoring it"
+                    );
+                    self.swaps.restore(&event.swap)?;
+                    rebuilt.restored += 1;
+                }
+                Some(_) => {}
+            }
+        }
+        for event in latest.into_values() {
+            warn!(
+                alert = "journal_divergence",
+                swap = %event.swap_id,
+                seq = event.seq,
+                "swap missing; restoring it from its journal"
+            );
+            self.swaps.restore(&event.swap)?;
+            rebuilt.restored += 1;
+        }
+        Ok(rebuilt)
+    }
+
+    /// Swaps the engine still has to drive, oldest first; those handed off to another host are left out.
+    pub fn active(&self) -> Result<Vec<Swap>, StoreError> {
+        self.swaps.active()
+    }
+
+    /// [`Self::active`], less the swaps another instance sharing the swap store holds, with the rest leased to
+    /// `owner` for `ttl`. Leasing again before `ttl` runs out keeps them.
+    pub fn lease_active(&self, owner: &str, ttl: Duration) -> Result<Vec<Swap>, StoreError> {
+        let now = unix_now();
+        self.swaps.lease(owner, now + ttl.as_secs() as i64, now)
+    }
+
+    /// Gives up the leases of [`Self::lease_active`] on stopping.
+    pub fn release_leases(&self, owner: &str) -> Result<(), StoreError> {
+        self.swaps.release(owner)
+    }
+
+    /// Every swap, newest first.
+    pub fn all(&self) -> Result<Vec<Swap>, StoreError> {
+        self.swaps.all()
+    }
+
+    /// Every swap with `counterparty`, newest first.
+    pub fn swaps_of(&self, counterparty: &Pubkey) -> Result<Vec<Swap>, StoreError> {
+        self.swaps.swaps_of(counterparty)
+    }
+
+    /// Swaps written at or after `since` (unix seconds), oldest write first.
+    pub fn swaps_updated_since(&self, since: i64) -> Result<Vec<Swap>, StoreError> {
+        self.swaps.swaps_updated_since(since)
+    }
+
+    /// Swaps created in `[from, to)` (unix seconds), oldest first.
+    pub fn swaps_created_between(&self, from: i64, to: i64) -> Result<Vec<Swap>, StoreError> {
+        self.swaps.swaps_created_between(from, to)
+    }
+
+    /// How many swaps are in each direction and state.
+    pub fn state_counts(&self) -> Result<Vec<(Direction, SwapState, u64)>, StoreError> {
+        self.swaps.state_counts()
+    }
+
+    /// Each swap as `insert` or `update` writes it, for live subscribers. Receivers that fall more than
+    /// [`EVENT_BUFFER`] writes behind lose the oldest and should re-read what they follow.
+    pub fn subscribe(&self) -> broadcast::Receiver<Swap> {
+        self.events.subscribe()
+    }
+
+    /// Adds or replaces the watch for `watch.payment_hash` (e.g. with a re-signed refund).
+    pub fn upsert_watch(&self, watch: &Watch) -> Result<(), StoreError> {
+        self.conn().execute(
+            "INSERT INTO tower_watches (payment_hash, refund_tx, status, error, created_at, updated_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6) ON CONFLICT (payment_hash) DO UPDATE SET refund_tx = ?2, status = ?3, \
+             error = ?4, updated_at = ?6",
+            params![
+                hex::encode(watch.payment_hash),
+                watch.refund_tx,
+                watch.status.as_str(),
+                watch.error,
+                watch.created_at,
+                watch.updated_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn update_watch(&self, watch: &mut Watch) -> Result<(), StoreError> {
+        watch.updated_at = unix_now();
+        self.upsert_watch(watch)
+    }
+
+    /// Watches still waiting for their escrow to settle.
+    pub fn active_watches(&self) -> Result<Vec<Watch>, StoreError> {
+        self.watches("WHERE status = 'watching'")
+    }
+
+    pub fn all_watches(&self) -> Result<Vec<Watch>, StoreError> {
+        self.watches("")
+    }
+
+    fn watches(&self, filter: &str) -> Result<Vec<Watch>, StoreError> {
+        type Row = (String, Option<String>, String, Option<String>, i64, i64);
+        let rows: Vec<Row> = {
+            let conn = self.conn();
+            let mut stmt = conn.prepare(&format!(
+                "SELECT payment_hash, refund_tx, status, error, created_at, updated_at FROM tower_watches {filter} \
+                 ORDER BY created_at"
+            ))?;
+            let rows = stmt.query_map([], |r| {
+                Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?, r.get(5)?))
+            })?;
+            rows.collect::<Result<_, _>>()?
+        };
+        rows.into_iter()
+            .map(|(hash, refund_tx, status, error, created_at, updated_at)| {
+                let corrupt = |reason: String| StoreError::Corrupt {
+                    id: hash.clone(),
+                    reason,
+                };
+                let payment_hash = hex::decode(&hash)
+                    .ok()
+                    .and_then(|b| <[u8; 32]>::try_from(b).ok())
+                    .ok_or_else(|| corrupt("payment hash".into()))?;
+                Ok(Watch {
+                    payment_hash,
+                    refund_tx,
+                    status: status.parse().map_err(corrupt)?,
+                    error,
+                    created_at,
+                    updated_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Stores an issued quote as signed, with its preimage if any.
+    pub fn insert_quote(&self, record: &QuoteRecord) -> Result<(), StoreError> {
+        let sealed = record
+            .preimage
+            .as_ref()
+            .zip(record.quote.payment_hash)
+            .map(|(p, payment_hash)| self.vault.seal(&payment_hash, p))
+            .transpose()?;
+        self.conn().execute(
+            "INSERT INTO quotes (id, quote, preimage, swap_id, expires_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                hex::encode(record.quote.id),
+                record.quote.to_json().to_string(),
+                sealed.map(hex::encode),
+                record.swap_id,
+                record.quote.expires_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn quote(&self, id: &[u8; 16]) -> Result<Option<QuoteRecord>, StoreError> {
+        let id_hex = hex::encode(id);
+        let row: Option<(String, Option<String>, Option<String>)> = self
+            .conn()
+            .query_row(
+                "SELECT quote, preimage, swap_id FROM quotes WHERE id = ?1",
+                [&id_hex],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+            )
+            .optional()?;
+        let Some((quote, preimage, swap_id)) = row else {
+            return Ok(None);
+        };
+        let corrupt = |reason: String| StoreError::Corrupt {
+            id: id_hex.clone(),
+            reason,
+        };
+        let quote = serde_json::from_str::<serde_json::Value>(&quote)
+            .map_err(|e| corrupt(e.to_string()))
+            .and_then(|v| Quote::from_json(&v).map_err(|e| corrupt(e.to_string())))?;
+        let preimage = preimage
+            .map(|p| {
+                hex::decode(p)
+                    .ok()
+                    .filter(|b| b.len() == SEALED_LEN)
+                    .map(Preimage::Sealed)
+                    .ok_or_else(|| corrupt("preimage".into()))
+            })
+            .transpose()?;
+        Ok(Some(QuoteRecord {
+            quote,
+            preimage,
+            swap_id,
+        }))
+    }
+
+    /// The quote swap `swap_id` was accepted from, if any.
+    pub fn quote_of_swap(&self, swap_id: &str) -> Result<Option<Quote>, StoreError> {
+        let id: Option<String> = self
+            .conn()
+            .query_row("SELECT id FROM quotes WHERE swap_id = ?1", [swap_id], |r| r.get(0))
+            .optional()?;
+        let Some(id) = id.and_then(|id| <[u8; 16]>::try_from(hex::decode(id).ok()?).ok()) else {
+            return Ok(None);
+        };
+        Ok(self.quote(&id)?.map(|record| record.quote))
+    }
+
+    /// Quotes neither accepted nor expired at `now`: terms the daemon may still be held to.
+    pub fn open_quotes(&self, now: i64) -> Result<Vec<Quote>, StoreError> {
+        let ids = self.ids("SELECT id FROM quotes WHERE swap_id IS NULL AND expires_at > ?1", now)?;
+        let quotes = ids.iter().map(|id| self.quote(id)).collect::<Result<Vec<_>, _>>()?;
+        Ok(quotes.into_iter().flatten().map(|r| r.quote).collect())
+    }
+
+    /// Ties quote `id` to the swap it was accepted into; `false` if it already was.
+    pub fn mark_quote_accepted(&self, id: &[u8; 16], swap_id: &str) -> Result<bool, StoreError> {
+        let n = self.conn().execute(
+            "UPDATE quotes SET swap_id = ?2 WHERE id = ?1 AND swap_id IS NULL",
+            params![hex::encode(id), swap_id],
+        )?;
+        Ok(n == 1)
+    }
+
+    pub fn insert_keysend_quote(&self, quote: &KeysendQuote) -> Result<(), StoreError> {
+        self.conn().execute(
+            "INSERT INTO keysend_quotes (id, recipient, amount_msat, token_amount, expires_at, payment_hash, \
+             created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                hex::encode(quote.id),
+                quote.recipient.to_string(),
+                quote.amount_msat as i64,
+                quote.token_amount as i64,
+                quote.expires_at,
+                quote.payment_hash.map(hex::encode),
+                quote.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn keysend_quote(&self, id: &[u8; 16]) -> Result<Option<KeysendQuote>, StoreError> {
+        type Row = (String, i64, i64, i64, Option<String>, i64);
+        let id_hex = hex::encode(id);
+        let row: Option<Row> = self
+            .conn()
+            .query_row(
+                "SELECT recipient, amount_msat, token_amount, expires_at, payment_hash, created_at \
+                 FROM keysend_quotes WHERE id = ?1",
+                [&id_hex],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?, r.get(5)?)),
+            )
+            .optional()?;
+        let Some((recipient, amount_msat, token_amount, expires_at, payment_hash, created_at)) = row else {
+            return Ok(None);
+        };
+        let corrupt = |reason: &str| StoreError::Corrupt {
+            id: id_hex.clone(),
+            reason: reason.into(),
+        };
+        Ok(Some(KeysendQuote {
+            id: *id,
+            recipient: recipient.parse().map_err(|_| corrupt("recipient"))?,
+            amount_msat: amount_msat as u64,
+            token_amount: token_amount as u64,
+            expires_at,
+            payment_hash: payment_hash
+                .map(|h| {
+                    hex::decode(h)
+                        .ok()
+                        .and_then(|b| <[u8; 32]>::try_from(b).ok())
+                        .ok_or_else(|| corrupt("payment hash"))
+                })
+                .transpose()?,
+            created_at,
+        }))
+    }
+
+    /// Keysend quotes neither paid nor expired at `now`.
+    pub fn open_keysend_quotes(&self, now: i64) -> Result<Vec<KeysendQuote>, StoreError> {
+        let ids = self.ids(
+            "SELECT id FROM keysend_quotes WHERE payment_hash IS NULL AND expires_at > ?1",
+            now,
+        )?;
+        let quotes = ids
+            .iter()
+            .map(|id| self.keysend_quote(id))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(quotes.into_iter().flatten().collect())
+    }
+
+    /// Hex ids of 16 bytes selected by `sql` with `now` bound to `?1`; malformed ids are skipped.
+    fn ids(&self, sql: &str, now: i64) -> Result<Vec<[u8; 16]>, StoreError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map([now], |r| r.get::<_, String>(0))?;
+        let ids = rows.collect::<Result<Vec<_>, _>>()?;
+        Ok(ids
+            .iter()
+            .filter_map(|id| hex::decode(id).ok().and_then(|b| b.try_into().ok()))
+            .collect())
+    }
+
+    /// Records the keysend that paid quote `id`; `false` if another one already did.
+    pub fn mark_keysend_quote_paid(&self, id: &[u8; 16], payment_hash: &[u8; 32]) -> Result<bool, StoreError> {
+        let n = self.conn().execute(
+            "UPDATE keysend_quotes SET payment_hash = ?2 WHERE id = ?1 AND payment_hash IS NULL",
+            params![hex::encode(id), hex::encode(payment_hash)],
+        )?;
+        Ok(n == 1)
+    }
+
+    /// Records how `swap` ended for its counterparty. A dispute replaces any other outcome; nothing replaces a
+    /// dispute except another one (e.g. with a new note).
+    pub fn record_outcome(&self, swap: &Swap, outcome: Outcome, note: Option<&str>) -> Result<(), StoreError> {
+        self.conn().execute(
+            "INSERT INTO counterparty_outcomes (swap_id, counterparty, outcome, amount_msat, note, recorded_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6) ON CONFLICT (swap_id) DO UPDATE SET outcome = ?3, note = ?5, \
+             recorded_at = ?6 WHERE outcome != 'disputed' OR ?3 = 'disputed'",
+            params![
+                swap.id,
+                swap.counterparty.to_string(),
+                outcome.as_str(),
+                swap.amount_msat as i64,
+                note,
+                unix_now(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// `counterparty`'s record; all zero for a key never seen.
+    pub fn reputation(&self, counterparty: &Pubkey) -> Result<Reputation, StoreError> {
+        let key = counterparty.to_string();
+        let all = self.reputations_where("WHERE counterparty = ?1", &[&key])?;
+        Ok(all.into_iter().next().map(|(_, r)| r).unwrap_or_default())
+    }
+
+    /// Every counterparty with a recorded outcome.
+    pub fn reputations(&self) -> Result<Vec<(Pubkey, Reputation)>, StoreError> {
+        self.reputations_where("", &[])
+    }
+
+    fn reputations_where(
+        &self,
+        filter: &str,
+        args: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<(Pubkey, Reputation)>, StoreError> {
+        type Row = (String, String, i64, i64, i64);
+        let rows: Vec<Row> = {
+            let conn = self.conn();
+            let mut stmt = conn.prepare(&format!(
+                "SELECT counterparty, outcome, COUNT(*), SUM(amount_msat), MAX(recorded_at) \
+                 FROM counterparty_outcomes {filter} GROUP BY counterparty, outcome ORDER BY counterparty"
+            ))?;
+            let rows = stmt.query_map(args, |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)))?;
+            rows.collect::<Result<_, _>>()?
+        };
+        let mut out: Vec<(Pubkey, Reputation)> = Vec::new();
+        for (key, outcome, count, amount_msat, at) in rows {
+            let corrupt = |reason: String| StoreError::Corrupt {
+                id: key.clone(),
+                reason,
+            };
+            let counterparty: Pubkey = key.parse().map_err(|_| corrupt("counterparty".into()))?;
+            let outcome: Outcome = outcome.parse().map_err(corrupt)?;
+            if out.last().map(|(k, _)| *k) != Some(counterparty) {
+                out.push((counterparty, Reputation::default()));
+            }
+            if let Some((_, reputation)) = out.last_mut() {
+                reputation.add(outcome, count as u64, amount_msat as u64, at);
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn insert_peer_session(&self, session: &PeerSession) -> Result<(), StoreError> {
+        self.conn().execute(
+            "INSERT INTO peer_sessions (swap_id, transport, peer, notified_state) VALUES (?1, ?2, ?3, ?4)",
+            params![session.swap_id, session.transport, session.peer, session.notified_state],
+        )?;
+        Ok(())
+    }
+
+    pub fn peer_session(&self, swap_id: &str) -> Result<Option<PeerSession>, StoreError> {
+        Ok(self
+            .peer_sessions_where("WHERE swap_id = ?1", swap_id)?
+            .into_iter()
+            .next())
+    }
+
+    /// Sessions on `transport` whose peer has not yet been told the swap ended.
+    pub fn peer_sessions(&self, transport: &str) -> Result<Vec<PeerSession>, StoreError> {
+        self.peer_sessions_where(
+            &format!("WHERE transport = ?1 AND (notified_state IS NULL OR notified_state NOT IN {TERMINAL})"),
+            transport,
+        )
+    }
+
+    fn peer_sessions_where(&self, filter: &str, arg: &str) -> Result<Vec<PeerSession>, StoreError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT swap_id, transport, peer, notified_state FROM peer_sessions {filter}"
+        ))?;
+        let rows = stmt.query_map([arg], |r| {
+            Ok(PeerSession {
+                swap_id: r.get(0)?,
+                transport: r.get(1)?,
+                peer: r.get(2)?,
+                notified_state: r.get(3)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    pub fn set_peer_notified(&self, swap_id: &str, state: &str) -> Result<(), StoreError> {
+        self.conn().execute(
+            "UPDATE peer_sessions SET notified_state = ?2 WHERE swap_id = ?1",
+            params![swap_id, state],
+        )?;
+        Ok(())
+    }
+
+    /// Resume position of a scan over an external list (e.g. the node's invoices); 0 if never set.
+    pub fn cursor(&self, name: &str) -> Result<u64, StoreError> {
+        let value: Option<i64> = self
+            .conn()
+            .query_row("SELECT value FROM cursors WHERE name = ?1", [name], |r| r.get(0))
+            .optional()?;
+        Ok(value.unwrap_or(0) as u64)
+    }
+
+    pub fn set_cursor(&self, name: &str, value: u64) -> Result<(), StoreError> {
+        self.conn().execute(
+            "INSERT INTO cursors (name, value) VALUES (?1, ?2) ON CONFLICT (name) DO UPDATE SET value = ?2",
+            params![name, value as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Posts `entry`; `false` if its swap already has an entry of its kind, so re-posting is harmless.
+    pub fn post_ledger_entry(&self, entry: &Entry) -> Result<bool, StoreError> {
+        if !entry.is_balanced() {
+            return Err(StoreError::Unbalanced(entry.kind.to_string()));
+        }
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let n = tx.execute(
+            "INSERT OR IGNORE INTO ledger_entries (kind, swap_id, note, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![entry.kind.as_str(), entry.swap_id, entry.note, unix_now()],
+        )?;
+        if n == 0 {
+            return Ok(false);
+        }
+        let id = tx.last_insert_rowid();
+        for posting in &entry.postings {
+            tx.execute(
+                "INSERT INTO ledger_postings (entry_id, account, asset, amount) VALUES (?1, ?2, ?3, ?4)",
+                params![id, posting.account.as_str(), posting.asset.as_str(), posting.amount],
+            )?;
+        }
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Every account's balance per asset.
+    pub fn ledger_balances(&self) -> Result<Vec<(Account, Asset, i64)>, StoreError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT account, asset, SUM(amount) FROM ledger_postings GROUP BY account, asset ORDER BY account, asset",
+        )?;
+        let rows = stmt
+            .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?, r.get(2)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.into_iter()
+            .map(|(account, asset, balance)| {
+                let corrupt = |reason: String| StoreError::Corrupt {
+                    id: format!("ledger account {account}"),
+                    reason,
+                };
+                Ok((
+                    account.parse().map_err(corrupt)?,
+                    asset.parse().map_err(corrupt)?,
+                    balance,
+                ))
+            })
+            .collect()
+    }
+
+    pub fn ledger_balance(&self, account: Account, asset: Asset) -> Result<i64, StoreError> {
+        let balance = self.conn().query_row(
+            "SELECT COALESCE(SUM(amount), 0) FROM ledger_postings WHERE account = ?1 AND asset = ?2",
+            params![account.as_str(), asset.as_str()],
+            |r| r.get(0),
+        )?;
+        Ok(balance)
+    }
+
+    /// What swap `id`'s entries have left in `account`.
+    pub fn ledger_swap_balance(&self, id: &str, account: Account) -> Result<i64, StoreError> {
+        let balance = self.conn().query_row(
+            "SELECT COALESCE(SUM(p.amount), 0) FROM ledger_postings p JOIN ledger_entries e ON e.id = p.entry_id \
+             WHERE e.swap_id = ?1 AND p.account = ?2",
+            params![id, account.as_str()],
+            |r| r.get(0),
+        )?;
+        Ok(balance)
+    }
+
+    /// Routing fees of the last `n` Lightning payments in bps of what they delivered; `None` before any.
+    pub fn recent_routing_fee_bps(&self, n: u32) -> Result<Option<u64>, StoreError> {
+        let (fees, delivered): (i64, i64) = self.conn().query_row(
+            "SELECT COALESCE(SUM(CASE WHEN account = ?2 THEN amount END), 0), \
+             COALESCE(SUM(CASE WHEN account = ?3 THEN amount END), 0) FROM ledger_postings \
+             WHERE entry_id IN (SELECT id FROM ledger_entries WHERE kind = ?1 ORDER BY id DESC LIMIT ?4)",
+            params![
+                EntryKind::LnPaid.as_str(),
+                Account::RoutingFees.as_str(),
+                Account::Counterparties.as_str(),
+                n
+            ],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )?;
+        Ok((delivered > 0).then(|| (fees.max(0) as u64).saturating_mul(10_000) / delivered as u64))
+    }
+
+    /// Whether the audit has opened `account`.
+    pub fn ledger_opened(&self, account: Account) -> Result<bool, StoreError> {
+        let opened = self.conn().query_row(
+            "SELECT EXISTS (SELECT 1 FROM ledger_entries WHERE kind = ?1 AND note = ?2)",
+            params![EntryKind::Opening.as_str(), account.as_str()],
+            |r| r.get(0),
+        )?;
+        Ok(opened)
+    }
+
+    /// Ids of entries whose postings do not sum to zero per asset; only an edit outside the daemon makes one.
+    pub fn unbalanced_ledger_entries(&self) -> Result<Vec<i64>, StoreError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT entry_id FROM ledger_postings GROUP BY entry_id, asset HAVING SUM(amount) != 0",
+        )?;
+        let ids = stmt.query_map([], |r| r.get(0))?.collect::<rusqlite::Result<_>>()?;
+        Ok(ids)
+    }
+
+    /// Queues `event` of `swap_id` for `url`; `false` if it was queued before, so re-queuing is harmless.
+    pub fn insert_webhook_delivery(
+        &self,
+        url: &str,
+        event: &str,
+        swap_id: &str,
+        payload: &str,
+        now: i64,
+    ) -> Result<bool, StoreError> {
+        let n = self.conn().execute(
+            "INSERT OR IGNORE INTO webhook_deliveries (url, event, swap_id, payload, status, next_attempt_at, \
+             created_at) VALUES (?1, ?2, ?3, ?4, 'pending', ?5, ?5)",
+            params![url, event, swap_id, payload, now],
+        )?;
+        Ok(n == 1)
+    }
+
+    /// Pending deliveries due by `now`, oldest first.
+    pub fn due_webhook_deliveries(&self, now: i64, limit: usize) -> Result<Vec<Delivery>, StoreError> {
+        self.webhook_deliveries_where(
+            &format!("WHERE status = 'pending' AND next_attempt_at <= ?1 ORDER BY id LIMIT {limit}"),
+            [now],
+        )
+    }
+
+    /// The delivery log, newest first, optionally only deliveries in `status`.
+    pub fn webhook_deliveries(&self, status: Option<DeliveryStatus>) -> Result<Vec<Delivery>, StoreError> {
+        match status {
+            Some(status) => self.webhook_deliveries_where("WHERE status = ?1 ORDER BY id DESC", [status.as_str()]),
+            None => self.webhook_deliveries_where("ORDER BY id DESC", []),
+        }
+    }
+
+    fn webhook_deliveries_where(&self, filter: &str, params: impl Params) -> Result<Vec<Delivery>, StoreError> {
+        type Row = (
+            i64,
+            String,
+            String,
+            String,
+            String,
+            String,
+            i64,
+            i64,
+            Option<String>,
+            i64,
+            Option<i64>,
+        );
+        let rows: Vec<Row> = {
+            let conn = self.conn();
+            let mut stmt = conn.prepare(&format!(
+                "SELECT id, url, event, swap_id, payload, status, attempts, next_attempt_at, last_error, created_at, \
+                 delivered_at FROM webhook_deliveries {filter}"
+            ))?;
+            let rows = stmt.query_map(params, |r| {
+                Ok((
+                    r.get(0)?,
+                    r.get(1)?,
+                    r.get(2)?,
+                    r.get(3)?,
+                    r.get(4)?,
+                    r.get(5)?,
+                    r.get(6)?,
+                    r.get(7)?,
+                    r.get(8)?,
+                    r.get(9)?,
+                    r.get(10)?,
+                ))
+            })?;
+            rows.collect::<Result<_, _>>()?
+        };
+        rows.into_iter()
+            .map(
+                |(
+                    id,
+                    url,
+                    event,
+                    swap_id,
+                    payload,
+                    status,
+                    attempts,
+                    next_attempt_at,
+                    last_error,
+                    created_at,
+                    delivered_at,
+                )| {
+                    Ok(Delivery {
+                        status: status.parse().map_err(|reason| StoreError::Corrupt {
+                            id: format!("webhook delivery {id}"),
+                            reason,
+                        })?,
+                        id,
+                        url,
+                        event,
+                        swap_id,
+                        payload,
+                        attempts: attempts as u32,
+                        next_attempt_at,
+                        last_error,
+                        created_at,
+                        delivered_at,
+                    })
+                },
+            )
+            .collect()
+    }
+
+    pub fn mark_webhook_delivered(&self, id: i64, attempts: u32, now: i64) -> Result<(), StoreError> {
+        self.conn().execute(
+            "UPDATE webhook_deliveries SET status = 'delivered', attempts = ?2, last_error = NULL, delivered_at = ?3 \
+             WHERE id = ?1",
+            params![id, attempts, now],
+        )?;
+        Ok(())
+    }
+
+    /// Records a failed attempt: retried at `retry_at`, or given up on if `None`.
+    pub fn mark_webhook_failed(
+        &self,
+        id: i64,
+        attempts: u32,
+        error: &str,
+        retry_at: Option<i64>,
+    ) -> Result<(), StoreError> {
+        let status = if retry_at.is_some() { "pending" } else { "failed" };
+        self.conn().execute(
+            "UPDATE webhook_deliveries SET status = ?2, attempts = ?3, last_error = ?4, \
+             next_attempt_at = COALESCE(?5, next_attempt_at) WHERE id = ?1",
+            params![id, status, attempts, error, retry_at],
+        )?;
+        Ok(())
+    }
+
+    /// Puts a given-up delivery back in the queue for immediate delivery; `false` if `id` is not one.
+    pub fn retry_webhook_delivery(&self, id: i64) -> Result<bool, StoreError> {
+        let n = self.conn().execute(
+            "UPDATE webhook_deliveries SET status = 'pending', attempts = 0, next_attempt_at = ?2 \
+             WHERE id = ?1 AND status = 'failed'",
+            params![id, unix_now()],
+        )?;
+        Ok(n == 1)
+    }
+
+    /// Records a new API key; `false` if its name or id is taken.
+    pub fn insert_api_key(&self, key: &ApiKey) -> Result<bool, StoreError> {
+        let n = self.conn().execute(
+            "INSERT OR IGNORE INTO api_keys (id, name, key_hash, scope, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                key.id,
+                key.name,
+                hex::encode(key.key_hash),
+                key.scope.as_str(),
+                key.created_at
+            ],
+        )?;
+        Ok(n == 1)
+    }
+
+    pub fn api_key(&self, id: &str) -> Result<Option<ApiKey>, StoreError> {
+        Ok(self.api_keys_where("WHERE id = ?1", [id])?.into_iter().next())
+    }
+
+    /// Every API key, revoked ones included, oldest first.
+    pub fn api_keys(&self) -> Result<Vec<ApiKey>, StoreError> {
+        self.api_keys_where("ORDER BY created_at, name", [])
+    }
+
+    fn api_keys_where(&self, filter: &str, params: impl Params) -> Result<Vec<ApiKey>, StoreError> {
+        type Row = (String, String, String, String, i64, Option<i64>, Option<i64>);
+        let rows: Vec<Row> = {
+            let conn = self.conn();
+            let mut stmt = conn.prepare(&format!(
+                "SELECT id, name, key_hash, scope, created_at, last_used_at, revoked_at FROM api_keys {filter}"
+            ))?;
+            let rows = stmt.query_map(params, |r| {
+                Ok((
+                    r.get(0)?,
+                    r.get(1)?,
+                    r.get(2)?,
+                    r.get(3)?,
+                    r.get(4)?,
+                    r.get(5)?,
+                    r.get(6)?,
+                ))
+            })?;
+            rows.collect::<Result<_, _>>()?
+        };
+        rows.into_iter()
+            .map(|(id, name, key_hash, scope, created_at, last_used_at, revoked_at)| {
+                let corrupt = |reason: String| StoreError::Corrupt {
+                    id: format!("api key {id}"),
+                    reason,
+                };
+                let key_hash = hex::decode(&key_hash)
+                    .ok()
+                    .and_then(|b| b.try_into().ok())
+                    .ok_or_else(|| corrupt("key hash is not 32 bytes of hex".into()))?;
+                let scope = scope.parse().map_err(corrupt)?;
+                Ok(ApiKey {
+                    id,
+                    name,
+                    key_hash,
+                    scope,
+                    created_at,
+                    last_used_at,
+                    revoked_at,
+                })
+            })
+            .collect()
+    }
+
+    pub fn touch_api_key(&self, id: &str, now: i64) -> Result<(), StoreError> {
+        self.conn()
+            .execute("UPDATE api_keys SET last_used_at = ?2 WHERE id = ?1", params![id, now])?;
+        Ok(())
+    }
+
+    /// Claims `caller`'s idempotency key `key` for a request with `fingerprint`, first forgetting keys claimed
+    /// before `expired_before`. `None` when the claim is new, otherwise the earlier request holding the key.
+    pub fn claim_idempotency_key(
+        &self,
+        caller: &str,
+        key: &str,
+        fingerprint: &str,
+        now: i64,
+        expired_before: i64,
+    ) -> Result<Option<IdempotentRequest>, StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM idempotency_keys WHERE created_at < ?1", [expired_before])?;
+        let n = tx.execute(
+            "INSERT OR IGNORE INTO idempotency_keys (caller, key, fingerprint, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![caller, key, fingerprint, now],
+        )?;
+        let earlier = match n {
+            1 => None,
+            _ => tx
+                .query_row(
+                    "SELECT fingerprint, result FROM idempotency_keys WHERE caller = ?1 AND key = ?2",
+                    params![caller, key],
+                    |r| {
+                        Ok(IdempotentRequest {
+                            fingerprint: r.get(0)?,
+                            result: r.get(1)?,
+                        })
+                    },
+                )
+                .optional()?,
+        };
+        tx.commit()?;
+        Ok(earlier)
+    }
+
+    /// Records `result`, the id of what the request claiming the key produced, for repeats to return.
+    pub fn finish_idempotency_key(&self, caller: &str, key: &str, result: &str) -> Result<(), StoreError> {
+        self.conn().execute(
+            "UPDATE idempotency_keys SET result = ?3 WHERE caller = ?1 AND key = ?2",
+            params![caller, key, result],
+        )?;
+        Ok(())
+    }
+
+    /// Drops an unfinished claim, after its request failed, so a retry runs it again.
+    pub fn release_idempotency_key(&self, caller: &str, key: &str) -> Result<(), StoreError> {
+        self.conn().execute(
+            "DELETE FROM idempotency_keys WHERE caller = ?1 AND key = ?2 AND result IS NULL",
+            params![caller, key],
+        )?;
+        Ok(())
+    }
+
+    /// Replaces the shutdown checkpoint with `entries`.
+    pub fn save_checkpoint(&self, entries: &[Checkpoint]) -> Result<(), StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM checkpoint", [])?;
+        for entry in entries {
+            tx.execute(
+                "INSERT INTO checkpoint (swap_id, state, payment_in_flight, claim_failures, taken_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    entry.swap_id,
+                    entry.state.as_str(),
+                    entry.payment_in_flight,
+                    entry.claim_failures,
+                    entry.taken_at
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Reads and clears the shutdown checkpoint, so it is only resumed from once.
+    pub fn take_checkpoint(&self) -> Result<Vec<Checkpoint>, StoreError> {
+        self.read_checkpoint(true)
+    }
+
+    /// The shutdown checkpoint, left in place.
+    pub fn checkpoint(&self) -> Result<Vec<Checkpoint>, StoreError> {
+        self.read_checkpoint(false)
+    }
+
+    /// Adds `entries` to the checkpoint, replacing those of the same swaps, for the next start to resume from.
+    pub fn extend_checkpoint(&self, entries: &[Checkpoint]) -> Result<(), StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        for entry in entries {
+            tx.execute(
+                "INSERT OR REPLACE INTO checkpoint (swap_id, state, payment_in_flight, claim_failures, taken_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    entry.swap_id,
+                    entry.state.as_str(),
+                    entry.payment_in_flight,
+                    entry.claim_failures,
+                    entry.taken_at
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn read_checkpoint(&self, clear: bool) -> Result<Vec<Checkpoint>, StoreError> {
+        type Row = (String, String, bool, u32, i64);
+        let rows: Vec<Row> = {
+            let mut conn = self.conn();
+            let tx = conn.transaction()?;
+            let rows = {
+                let mut stmt = tx.prepare(
+                    "SELECT swap_id, state, payment_in_flight, claim_failures, taken_at FROM checkpoint ORDER BY swap_id",
+                )?;
+                let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)))?;
+                rows.collect::<Result<_, _>>()?
+            };
+            if clear {
+                tx.execute("DELETE FROM checkpoint", [])?;
+            }
+            tx.commit()?;
+            rows
+        };
+        rows.into_iter()
+            .map(|(swap_id, state, payment_in_flight, claim_failures, taken_at)| {
+                let state = state.parse().map_err(|reason| StoreError::Corrupt {
+                    id: swap_id.clone(),
+                    reason,
+                })?;
+                Ok(Checkpoint {
+                    swap_id,
+                    state,
+                    payment_in_flight,
+                    claim_failures,
+                    taken_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Records `check` as the swap's latest escrow amount check.
+    pub fn record_amount_check(&self, check: &AmountCheck) -> Result<(), StoreError> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO amount_checks (swap_id, expected, actual, allowed, accepted, checked_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                check.swap_id,
+                check.expected as i64,
+                check.actual as i64,
+                check.allowed as i64,
+                check.accepted,
+                check.checked_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The latest escrow amount check of swap `id`, if the engine made one.
+    pub fn amount_check(&self, id: &str) -> Result<Option<AmountCheck>, StoreError> {
+        Ok(self
+            .conn()
+            .query_row(
+                "SELECT swap_id, expected, actual, allowed, accepted, checked_at FROM amount_checks WHERE swap_id = ?1",
+                [id],
+                |r| {
+                    Ok(AmountCheck {
+                        swap_id: r.get(0)?,
+                        expected: r.get::<_, i64>(1)? as u64,
+                        actual: r.get::<_, i64>(2)? as u64,
+                        allowed: r.get::<_, i64>(3)? as u64,
+                        accepted: r.get(4)?,
+                        checked_at: r.get(5)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    /// Fences swaps `ids` off as handed off in bundle `bundle_id`, in the swap store: no instance drives or writes
+    /// them from now on.
+    pub fn fence_swaps(&self, ids: &[String], bundle_id: &str, now: i64) -> Result<(), StoreError> {
+        self.swaps.fence(ids, bundle_id, now)
+    }
+
+    /// Lifts the fences of bundle `bundle_id`; returns how many swaps come back.
+    pub fn release_fences(&self, bundle_id: &str) -> Result<usize, StoreError> {
+        self.swaps.release_fences(bundle_id)
+    }
+
+    /// Fenced swaps as `(swap_id, bundle_id, fenced_at)`, newest first.
+    pub fn swap_fences(&self) -> Result<Vec<(String, String, i64)>, StoreError> {
+        self.swaps.fences()
+    }
+
+    /// Records the BTC a completed swap left the operator long (short when negative); once per swap.
+    pub fn record_hedge_fill(&self, swap_id: &str, fill_msat: i64, now: i64) -> Result<bool, StoreError> {
+        let n = self.conn().execute(
+            "INSERT OR IGNORE INTO hedge_fills (swap_id, fill_msat, recorded_at) VALUES (?1, ?2, ?3)",
+            params![swap_id, fill_msat, now],
+        )?;
+        Ok(n == 1)
+    }
+
+    /// The fill recorded for swap `id`, if it was hedged.
+    pub fn hedge_fill(&self, id: &str) -> Result<Option<i64>, StoreError> {
+        let fill = self
+            .conn()
+            .query_row("SELECT fill_msat FROM hedge_fills WHERE swap_id = ?1", [id], |r| {
+                r.get(0)
+            })
+            .optional()?;
+        Ok(fill)
+    }
+
+    /// Every recorded fill as `(swap_id, fill_msat, recorded_at)`, oldest first.
+    pub fn hedge_fills(&self) -> Result<Vec<(String, i64, i64)>, StoreError> {
+        let conn = self.conn();
+        let mut stmt =
+            conn.prepare("SELECT swap_id, fill_msat, recorded_at FROM hedge_fills ORDER BY recorded_at, rowid")?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+        let fills = rows.collect::<Result<_, _>>()?;
+        Ok(fills)
+    }
+
+    /// Fills against the orders that did not fail, closed ones for what they filled; see [`HedgeOrder::hedged_msat`].
+    pub fn hedge_position(&self) -> Result<Position, StoreError> {
+        let conn = self.conn();
+        let filled_msat = conn.query_row("SELECT COALESCE(SUM(fill_msat), 0) FROM hedge_fills", [], |r| r.get(0))?;
+        let hedged_sat: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(CASE status WHEN 'closed' THEN filled_sat ELSE amount_sat END \
+             * CASE side WHEN 'sell' THEN 1 ELSE -1 END), 0) FROM hedge_orders WHERE status != 'failed'",
+            [],
+            |r| r.get(0),
+        )?;
+        Ok(Position {
+            filled_msat,
+            hedged_msat: hedged_sat.saturating_mul(1000),
+        })
+    }
+
+    /// Writes `order` as pending on `venue`, before it is sent; returns its id.
+    pub fn insert_hedge_order(&self, venue: &str, order: &Order, now: i64) -> Result<i64, StoreError> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO hedge_orders (venue, side, amount_sat, client_order_id, status, created_at, updated_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+            params![
+                venue,
+                order.side.as_str(),
+                order.amount_sat as i64,
+                order.client_order_id,
+                OrderStatus::Pending.as_str(),
+                now,
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Sets order `id`'s status and error, and its venue id and execution when given.
+    pub fn update_hedge_order(
+        &self,
+        id: i64,
+        status: OrderStatus,
+        venue_order_id: Option<&str>,
+        execution: Option<&Execution>,
+        error: Option<&str>,
+        now: i64,
+    ) -> Result<(), StoreError> {
+        self.conn().execute(
+            "UPDATE hedge_orders SET status = ?2, venue_order_id = COALESCE(?3, venue_order_id), \
+             filled_sat = COALESCE(?4, filled_sat), cost_tokens = COALESCE(?5, cost_tokens), \
+             fee_tokens = COALESCE(?6, fee_tokens), error = ?7, updated_at = ?8 WHERE id = ?1",
+            params![
+                id,
+                status.as_str(),
+                venue_order_id,
+                execution.map(|e| e.filled_sat as i64),
+                execution.map(|e| e.cost_tokens as i64),
+                execution.map(|e| e.fee_tokens as i64),
+                error,
+                now,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Hedge orders, newest first; only those in `status` when set.
+    pub fn hedge_orders(&self, status: Option<OrderStatus>) -> Result<Vec<HedgeOrder>, StoreError> {
+        type Row = (
+            i64,
+            String,
+            String,
+            i64,
+            String,
+            Option<String>,
+            String,
+            (i64, i64, i64),
+            Option<String>,
+            i64,
+            i64,
+        );
+        let rows: Vec<Row> = {
+            let conn = self.conn();
+            let mut stmt = conn.prepare(
+                "SELECT id, venue, side, amount_sat, client_order_id, venue_order_id, status, filled_sat, cost_tokens, \
+                 fee_tokens, error, created_at, updated_at FROM hedge_orders WHERE ?1 IS NULL OR status = ?1 \
+                 ORDER BY id DESC",
+            )?;
+            let rows = stmt.query_map([status.map(|s| s.as_str())], |r| {
+                Ok((
+                    r.get(0)?,
+                    r.get(1)?,
+                    r.get(2)?,
+                    r.get(3)?,
+                    r.get(4)?,
+                    r.get(5)?,
+                    r.get(6)?,
+                    (r.get(7)?, r.get(8)?, r.get(9)?),
+                    r.get(10)?,
+                    r.get(11)?,
+                    r.get(12)?,
+                ))
+            })?;
+            rows.collect::<Result<_, _>>()?
+        };
+        rows.into_iter()
+            .map(
+                |(
+                    id,
+                    venue,
+                    side,
+                    amount_sat,
+                    client_order_id,
+                    venue_order_id,
+                    status,
+                    (filled_sat, cost_tokens, fee_tokens),
+                    error,
+                    created_at,
+                    updated_at,
+                )| {
+                    let corrupt = |reason: String| StoreError::Corrupt {
+                        id: format!("hedge order {id}"),
+                        reason,
+                    };
+                    Ok(HedgeOrder {
+                        side: side.parse().map_err(corrupt)?,
+                        status: status.parse().map_err(corrupt)?,
+                        id,
+                        venue,
+                        amount_sat: amount_sat as u64,
+                        client_order_id,
+                        venue_order_id,
+                        execution: Execution {
+                            filled_sat: filled_sat as u64,
+                            cost_tokens: cost_tokens as u64,
+                            fee_tokens: fee_tokens as u64,
+                        },
+                        error,
+                        created_at,
+                        updated_at,
+                    })
+                },
+            )
+            .collect()
+    }
+
+    /// Registers `claim_key` to pay swap `id` on-chain should Lightning fail, replacing an earlier key.
+    pub fn register_onchain_claim_key(&self, id: &str, claim_key: &XOnlyPublicKey, now: i64) -> Result<(), StoreError> {
+        self.conn().execute(
+            "INSERT INTO onchain_fallbacks (swap_id, claim_key, registered_at) VALUES (?1, ?2, ?3) \
+             ON CONFLICT (swap_id) DO UPDATE SET claim_key = excluded.claim_key, \
+             registered_at = excluded.registered_at",
+            params![id, claim_key.serialize().to_vec(), now],
+        )?;
+        Ok(())
+    }
+
+    pub fn onchain_registration(&self, id: &str) -> Result<Option<Registration>, StoreError> {
+        let row: Option<(Vec<u8>, i64, i64)> = self
+            .conn()
+            .query_row(
+                "SELECT claim_key, ln_failures, registered_at FROM onchain_fallbacks WHERE swap_id = ?1",
+                [id],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+            )
+            .optional()?;
+        row.map(|(claim_key, ln_failures, registered_at)| {
+            Ok(Registration {
+                swap_id: id.to_string(),
+                claim_key: XOnlyPublicKey::from_slice(&claim_key).map_err(|e| StoreError::Corrupt {
+                    id: id.to_string(),
+                    reason: format!("claim key: {e}"),
+                })?,
+                ln_failures: ln_failures as u32,
+                registered_at,
+            })
+        })
+        .transpose()
+    }
+
+    /// Counts a failed Lightning payment of registered swap `id`; the failures so far.
+    pub fn count_ln_failure(&self, id: &str) -> Result<u32, StoreError> {
+        let failures: i64 = self.conn().query_row(
+            "UPDATE onchain_fallbacks SET ln_failures = ln_failures + 1 WHERE swap_id = ?1 RETURNING ln_failures",
+            [id],
+            |r| r.get(0),
+        )?;
+        Ok(failures as u32)
+    }
+
+    pub fn insert_htlc(&self, record: &HtlcRecord) -> Result<(), StoreError> {
+        self.conn().execute(
+            "INSERT INTO onchain_htlcs (swap_id, payment_hash, address, amount_sat, timeout_height, claim_key, \
+             refund_key, refund_secret, status, funding_txid, funding_vout, spend_txid, created_at, updated_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            params![
+                record.swap_id,
+                record.htlc.payment_hash.to_vec(),
+                record.address,
+                record.amount_sat as i64,
+                record.htlc.timeout_height,
+                record.htlc.claim_key.serialize().to_vec(),
+                record.htlc.refund_key.serialize().to_vec(),
+                record.refund_secret,
+                record.status.as_str(),
+                record.funding_txid,
+                record.funding_vout,
+                record.spend_txid,
+                record.created_at,
+                record.updated_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Moves the HTLC of swap `id` to `status`, recording its funding transaction and output and its spend
+    /// when given.
+    pub fn update_htlc(
+        &self,
+        id: &str,
+        status: HtlcStatus,
+        funding: Option<(&str, Option<u32>)>,
+        spend_txid: Option<&str>,
+        now: i64,
+    ) -> Result<(), StoreError> {
+        let (funding_txid, funding_vout) = funding.unzip();
+        self.conn().execute(
+            "UPDATE onchain_htlcs SET status = ?2, funding_txid = COALESCE(?3, funding_txid), \
+             funding_vout = COALESCE(?4, funding_vout), spend_txid = COALESCE(?5, spend_txid), updated_at = ?6 \
+             WHERE swap_id = ?1",
+            params![
+                id,
+                status.as_str(),
+                funding_txid,
+                funding_vout.flatten(),
+                spend_txid,
+                now
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn htlc(&self, id: &str) -> Result<Option<HtlcRecord>, StoreError> {
+        Ok(self.htlcs_of(Some(id))?.pop())
+    }
+
+    /// On-chain HTLCs, newest first.
+    pub fn htlcs(&self) -> Result<Vec<HtlcRecord>, StoreError> {
+        self.htlcs_of(None)
+    }
+
+    fn htlcs_of(&self, id: Option<&str>) -> Result<Vec<HtlcRecord>, StoreError> {
+        type Row = (
+            String,
+            Vec<u8>,
+            String,
+            i64,
+            u32,
+            Vec<u8>,
+            Vec<u8>,
+            Vec<u8>,
+            String,
+            Option<String>,
+            Option<u32>,
+            Option<String>,
+            i64,
+            i64,
+        );
+        let rows: Vec<Row> = {
+            let conn = self.conn();
+            let mut stmt = conn.prepare(
+                "SELECT swap_id, payment_hash, address, amount_sat, timeout_height, claim_key, refund_key, \
+                 refund_secret, status, funding_txid, funding_vout, spend_txid, created_at, updated_at \
+                 FROM onchain_htlcs WHERE ?1 IS NULL OR swap_id = ?1 ORDER BY created_at DESC, swap_id",
+            )?;
+            let rows = stmt.query_map([id], |r| {
+                Ok((
+                    r.get(0)?,
+                    r.get(1)?,
+                    r.get(2)?,
+                    r.get(3)?,
+                    r.get(4)?,
+                    r.get(5)?,
+                    r.get(6)?,
+                    r.get(7)?,
+                    r.get(8)?,
+                    r.get(9)?,
+                    r.get(10)?,
+                    r.get(11)?,
+                    r.get(12)?,
+                    r.get(13)?,
+                ))
+            })?;
+            rows.collect::<Result<_, _>>()?
+        };
+        rows.into_iter()
+            .map(
+                |(
+                    swap_id,
+                    payment_hash,
+                    address,
+                    amount_sat,
+                    timeout_height,
+                    claim_key,
+                    refund_key,
+                    refund_secret,
+                    status,
+                    funding_txid,
+                    funding_vout,
+                    spend_txid,
+                    created_at,
+                    updated_at,
+                )| {
+                    let corrupt = |reason: String| StoreError::Corrupt {
+                        id: format!("htlc of {swap_id}"),
+                        reason,
+                    };
+                    let key = |bytes: &[u8]| XOnlyPublicKey::from_slice(bytes).map_err(|e| corrupt(e.to_string()));
+                    Ok(HtlcRecord {
+                        htlc: Htlc {
+                            payment_hash: payment_hash
+                                .try_into()
+                                .map_err(|_| corrupt("payment hash is not 32 bytes".into()))?,
+                            claim_key: key(&claim_key)?,
+                            refund_key: key(&refund_key)?,
+                            timeout_height,
+                        },
+                        status: status.parse().map_err(corrupt)?,
+                        swap_id,
+                        amount_sat: amount_sat as u64,
+                        address,
+                        refund_secret,
+                        funding_txid,
+                        funding_vout,
+                        spend_txid,
+                        created_at,
+                        updated_at,
+                    })
+                },
+            )
+            .collect()
+    }
+
+    pub fn insert_screening(&self, record: &ScreeningRecord) -> Result<(), StoreError> {
+        self.conn().execute(
+            "INSERT INTO screenings (swap_id, counterparty, direction, amount_msat, token_amount, source, action, \
+             screener, reason, screened_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                record.swap_id,
+                record.counterparty.to_string(),
+                record.direction.as_str(),
+                record.amount_msat as i64,
+                record.token_amount as i64,
+                record.source,
+                record.action.as_str(),
+                record.screener,
+                record.reason,
+                record.screened_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Screening outcomes, newest first, of `counterparty` or everyone; `limit` 0 lists all.
+    pub fn screenings(&self, counterparty: Option<&Pubkey>, limit: usize) -> Result<Vec<ScreeningRecord>, StoreError> {
+        type Row = (
+            String,
+            String,
+            String,
+            i64,
+            i64,
+            String,
+            String,
+            Option<String>,
+            Option<String>,
+            i64,
+        );
+        let rows: Vec<Row> = {
+            let conn = self.conn();
+            let mut stmt = conn.prepare(
+                "SELECT swap_id, counterparty, direction, amount_msat, token_amount, source, action, screener, \
+                 reason, screened_at FROM screenings WHERE ?1 IS NULL OR counterparty = ?1 ORDER BY id DESC \
+                 LIMIT ?2",
+            )?;
+            let limit = if limit == 0 { -1 } else { limit as i64 };
+            let rows = stmt.query_map(params![counterparty.map(Pubkey::to_string), limit], |r| {
+                Ok((
+                    r.get(0)?,
+                    r.get(1)?,
+                    r.get(2)?,
+                    r.get(3)?,
+                    r.get(4)?,
+                    r.get(5)?,
+                    r.get(6)?,
+                    r.get(7)?,
+                    r.get(8)?,
+                    r.get(9)?,
+                ))
+            })?;
+            rows.collect::<Result<_, _>>()?
+        };
+        rows.into_iter()
+            .map(
+                |(
+                    swap_id,
+                    counterparty,
+                    direction,
+                    amount_msat,
+                    token_amount,
+                    source,
+                    action,
+                    screener,
+                    reason,
+                    at,
+                )| {
+                    let corrupt = |reason: String| StoreError::Corrupt {
+                        id: swap_id.clone(),
+                        reason,
+                    };
+                    Ok(ScreeningRecord {
+                        counterparty: counterparty.parse().map_err(|_| corrupt("counterparty".into()))?,
+                        direction: direction.parse().map_err(|_| corrupt("direction".into()))?,
+                        amount_msat: amount_msat as u64,
+                        token_amount: token_amount as u64,
+                        source,
+                        action: action.parse().map_err(corrupt)?,
+                        screener,
+                        reason,
+                        screened_at: at,
+                        swap_id,
+                    })
+                },
+            )
+            .collect()
+    }
+
+    /// Every swap step waiting to be retried.
+    pub fn step_retries(&self) -> Result<Vec<StepRetry>, StoreError> {
+        let rows: Vec<(String, String, i64, i64, String, i64)> = {
+            let conn = self.conn();
+            let mut stmt = conn.prepare(
+                "SELECT swap_id, state, attempts, next_attempt_at, last_error, first_failed_at FROM step_retries",
+            )?;
+            let rows = stmt.query_map([], |r| {
+                Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?, r.get(5)?))
+            })?;
+            rows.collect::<Result<_, _>>()?
+        };
+        rows.into_iter()
+            .map(
+                |(swap_id, state, attempts, next_attempt_at, last_error, first_failed_at)| {
+                    Ok(StepRetry {
+                        state: state.parse().map_err(|reason| StoreError::Corrupt {
+                            id: swap_id.clone(),
+                            reason,
+                        })?,
+                        swap_id,
+                        attempts: attempts as u32,
+                        next_attempt_at,
+                        last_error,
+                        first_failed_at,
+                    })
+                },
+            )
+            .collect()
+    }
+
+    /// Adds or replaces the retry of `retry.swap_id`.
+    pub fn schedule_step_retry(&self, retry: &StepRetry) -> Result<(), StoreError> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO step_retries (swap_id, state, attempts, next_attempt_at, last_error, \
+             first_failed_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                retry.swap_id,
+                retry.state.as_str(),
+                retry.attempts,
+                retry.next_attempt_at,
+                retry.last_error,
+                retry.first_failed_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Drops the retry of `swap_id` once its step went through.
+    pub fn clear_step_retry(&self, swap_id: &str) -> Result<(), StoreError> {
+        self.conn()
+            .execute("DELETE FROM step_retries WHERE swap_id = ?1", [swap_id])?;
+        Ok(())
+    }
+
+    /// Gives up on `retry`: moves it to the dead-letter table at `now`; the letter's id.
+    pub fn dead_letter(&self, retry: &StepRetry, now: i64) -> Result<i64, StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO dead_letters (swap_id, state, attempts, last_error, first_failed_at, dead_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                retry.swap_id,
+                retry.state.as_str(),
+                retry.attempts,
+                retry.last_error,
+                retry.first_failed_at,
+                now
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        tx.execute("DELETE FROM step_retries WHERE swap_id = ?1", [&retry.swap_id])?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Dead letters, newest first; resolved ones too if `resolved`.
+    pub fn dead_letters(&self, resolved: bool) -> Result<Vec<DeadLetter>, StoreError> {
+        type Row = (i64, String, String, i64, String, i64, i64, Option<i64>);
+        let filter = if resolved { "" } else { "WHERE resolved_at IS NULL" };
+        let rows: Vec<Row> = {
+            let conn = self.conn();
+            let mut stmt = conn.prepare(&format!(
+                "SELECT id, swap_id, state, attempts, last_error, first_failed_at, dead_at, resolved_at \
+                 FROM dead_letters {filter} ORDER BY id DESC"
+            ))?;
+            let rows = stmt.query_map([], |r| {
+                Ok((
+                    r.get(0)?,
+                    r.get(1)?,
+                    r.get(2)?,
+                    r.get(3)?,
+                    r.get(4)?,
+                    r.get(5)?,
+                    r.get(6)?,
+                    r.get(7)?,
+                ))
+            })?;
+            rows.collect::<Result<_, _>>()?
+        };
+        rows.into_iter()
+            .map(
+                |(id, swap_id, state, attempts, last_error, first_failed_at, dead_at, resolved_at)| {
+                    Ok(DeadLetter {
+                        state: state.parse().map_err(|reason| StoreError::Corrupt {
+                            id: format!("dead letter {id}"),
+                            reason,
+                        })?,
+                        id,
+                        swap_id,
+                        attempts: attempts as u32,
+                        last_error,
+                        first_failed_at,
+                        dead_at,
+                        resolved_at,
+                    })
+                },
+            )
+            .collect()
+    }
+
+    /// Marks dead letter `id` resolved at `now`, sending its swap back to the engine; the swap id, or `None` if
+    /// there is no such letter still open.
+    pub fn resolve_dead_letter(&self, id: i64, now: i64) -> Result<Option<String>, StoreError> {
+        Ok(self
+            .conn()
+            .query_row(
+                "UPDATE dead_letters SET resolved_at = ?2 WHERE id = ?1 AND resolved_at IS NULL RETURNING swap_id",
+                params![id, now],
+                |r| r.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Revokes the key named `name`; `false` if there is no such key still in use.
+    pub fn revoke_api_key(&self, name: &str, now: i64) -> Result<bool, StoreError> {
+        let n = self.conn().execute(
+            "UPDATE api_keys SET revoked_at = ?2 WHERE name = ?1 AND revoked_at IS NULL",
+            params![name, now],
+        )?;
+        Ok(n == 1)
+    }
+}
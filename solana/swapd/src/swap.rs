@@ -0,0 +1,356 @@
+//! Persisted swap records and their state machine.
+//!
+//! Directions are named from the user's side. In [`Direction::LnToUsdt`] the user pays a Lightning invoice
+//! issued by this node and claims USDT from an escrow the daemon funded; in [`Direction::UsdtToLn`] the user
+//! funds an escrow payable to the daemon, which pays the user's invoice and claims with the preimage.
+
+use std::{fmt, str::FromStr};
+
+use rand::RngCore;
+use serde_json::{json, Value};
+use solana_sdk::{hash::hash, pubkey::Pubkey};
+use zeroize::Zeroizing;
+
+use crate::{
+    keysend::KeysendQuote,
+    ln::{decode_invoice, Keysend, LnError},
+    vault::Preimage,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "rest-api",
+    derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Direction {
+    /// User pays BTC over Lightning, receives USDT. The daemon funds the escrow and is its refund key.
+    LnToUsdt,
+    /// User pays USDT into an escrow, receives BTC over Lightning. The daemon is the escrow recipient.
+    UsdtToLn,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::LnToUsdt => "ln-to-usdt",
+            Self::UsdtToLn => "usdt-to-ln",
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ln-to-usdt" => Ok(Self::LnToUsdt),
+            "usdt-to-ln" => Ok(Self::UsdtToLn),
+            other => Err(format!("unknown direction {other:?}")),
+        }
+    }
+}
+
+/// Where a swap is. Each non-terminal state names the next external effect the engine is waiting on; the
+/// engine re-checks that effect before acting, so re-running a step after a crash never repeats it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "rest-api",
+    derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema),
+    serde(rename_all = "snake_case")
+)]
+pub enum SwapState {
+    /// Accepted, nothing done yet.
+    Created,
+    /// LnToUsdt: invoice exists on our node; the escrow is not funded yet.
+    InvoiceCreated,
+    /// LnToUsdt with a hold invoice: the user's HTLCs are held; funding the escrow next.
+    InvoiceAccepted,
+    /// LnToUsdt by keysend: the payment (and with it the preimage, chosen by the user) arrived; funding the
+    /// escrow next.
+    KeysendReceived,
+    /// LnToUsdt: escrow funded; waiting for the user to pay the invoice (hold invoice: settling it next).
+    EscrowFunded,
+    /// LnToUsdt: invoice paid; waiting for the user to claim (or for the refund timeout).
+    InvoiceSettled,
+    /// LnToUsdt: refund_after passed with the escrow still active; refund pending.
+    Refunding,
+    /// UsdtToLn: the user's escrow matches the swap; paying next.
+    EscrowVerified,
+    /// UsdtToLn: Lightning payment in flight.
+    Paying,
+    /// UsdtToLn: preimage known; claim pending.
+    Claiming,
+    /// The user got their side and so did we (user claimed, or we claimed).
+    Completed,
+    Refunded,
+    /// Stopped before any funds moved, or a step that can no longer succeed; see `error`.
+    Failed,
+}
+
+impl SwapState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::InvoiceCreated => "invoice_created",
+            Self::InvoiceAccepted => "invoice_accepted",
+            Self::KeysendReceived => "keysend_received",
+            Self::EscrowFunded => "escrow_funded",
+            Self::InvoiceSettled => "invoice_settled",
+            Self::Refunding => "refunding",
+            Self::EscrowVerified => "escrow_verified",
+            Self::Paying => "paying",
+            Self::Claiming => "claiming",
+            Self::Completed => "completed",
+            Self::Refunded => "refunded",
+            Self::Failed => "failed",
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Completed | Self::Refunded | Self::Failed)
+    }
+}
+
+impl fmt::Display for SwapState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SwapState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "created" => Self::Created,
+            "invoice_created" => Self::InvoiceCreated,
+            "invoice_accepted" => Self::InvoiceAccepted,
+            "keysend_received" => Self::KeysendReceived,
+            "escrow_funded" => Self::EscrowFunded,
+            "invoice_settled" => Self::InvoiceSettled,
+            "refunding" => Self::Refunding,
+            "escrow_verified" => Self::EscrowVerified,
+            "paying" => Self::Paying,
+            "claiming" => Self::Claiming,
+            "completed" => Self::Completed,
+            "refunded" => Self::Refunded,
+            "failed" => Self::Failed,
+            other => return Err(format!("unknown swap state {other:?}")),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Swap {
+    /// Hex payment hash; one swap per hash, as on-chain.
+    pub id: String,
+    pub direction: Direction,
+    pub state: SwapState,
+    pub payment_hash: [u8; 32],
+    /// LnToUsdt: generated at intake. UsdtToLn: learned from the Lightning payment. Sealed once stored.
+    pub preimage: Option<Preimage>,
+    /// LnToUsdt: our invoice, once created. UsdtToLn: the user's invoice.
+    pub bolt11: Option<String>,
+    pub amount_msat: u64,
+    /// LnToUsdt: our invoice is a hold invoice, settled only after the escrow is funded.
+    pub hold: bool,
+    /// LnToUsdt: text whose hash the invoice commits to instead of a plain description (LNURL-pay metadata).
+    pub description: Option<String>,
+    /// Net token amount the escrow pays its recipient (fees on top).
+    pub token_amount: u64,
+    /// The user's Solana key: escrow recipient (LnToUsdt) or expected refund key (UsdtToLn).
+    pub counterparty: Pubkey,
+    /// Escrow refund deadline (cluster time), once the escrow is known.
+    pub refund_after: Option<i64>,
+    /// LnToUsdt: invoice expiry. UsdtToLn: time by which the user must fund the escrow.
+    pub deadline: i64,
+    /// Last transaction the daemon sent for this swap.
+    pub signature: Option<String>,
+    pub error: Option<String>,
+    /// An operator asked to stop the swap; the engine does so while [`Self::is_cancellable`].
+    pub cancel_requested: bool,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Swap {
+    /// Span carried by everything done for this swap, including work on other tasks, so its whole timeline
+    /// shares the id (and payment hash, which Lightning logs use).
+    pub fn span(&self) -> tracing::Span {
+        tracing::info_span!(
+            "swap",
+            id = %self.id,
+            direction = %self.direction,
+            payment_hash = %hex::encode(self.payment_hash)
+        )
+    }
+
+    /// New LnToUsdt swap: the user will pay `amount_msat` and receive `token_amount` at `recipient`. The
+    /// preimage is generated here, so the payment hash (and swap id) is fixed from the start. With `hold`, the
+    /// invoice is a hold invoice handed out at once, and the escrow is funded only after the payment arrives.
+    pub fn ln_to_usdt(recipient: Pubkey, amount_msat: u64, token_amount: u64, hold: bool) -> Self {
+        let mut preimage = Zeroizing::new([0u8; 32]);
+        rand::rngs::OsRng.fill_bytes(preimage.as_mut_slice());
+        let payment_hash = hash(preimage.as_slice()).to_bytes();
+        Self::ln_to_usdt_with_preimage(
+            recipient,
+            amount_msat,
+            token_amount,
+            hold,
+            payment_hash,
+            Preimage::Plain(preimage),
+        )
+    }
+
+    /// [`Self::ln_to_usdt`] for a preimage of `payment_hash` chosen in advance, e.g. when a quote already
+    /// committed to the hash.
+    pub fn ln_to_usdt_with_preimage(
+        recipient: Pubkey,
+        amount_msat: u64,
+        token_amount: u64,
+        hold: bool,
+        payment_hash: [u8; 32],
+        preimage: Preimage,
+    ) -> Self {
+        let now = unix_now();
+        Self {
+            id: hex::encode(payment_hash),
+            direction: Direction::LnToUsdt,
+            state: SwapState::Created,
+            payment_hash,
+            preimage: Some(preimage),
+            bolt11: None,
+            amount_msat,
+            hold,
+            description: None,
+            token_amount,
+            counterparty: recipient,
+            refund_after: None,
+            deadline: 0,
+            signature: None,
+            error: None,
+            cancel_requested: false,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// LnToUsdt swap for a keysend that paid a [`KeysendQuote`]. The BTC leg is already in, so the swap starts
+    /// at funding the escrow for the keysend's payment hash.
+    pub fn from_keysend(quote: &KeysendQuote, keysend: &Keysend) -> Self {
+        let now = unix_now();
+        Self {
+            id: hex::encode(keysend.payment_hash),
+            direction: Direction::LnToUsdt,
+            state: SwapState::KeysendReceived,
+            payment_hash: keysend.payment_hash,
+            preimage: Some(Preimage::plain(keysend.preimage)),
+            bolt11: None,
+            amount_msat: keysend.amount_msat,
+            hold: false,
+            description: None,
+            token_amount: quote.token_amount,
+            counterparty: quote.recipient,
+            refund_after: None,
+            deadline: 0,
+            signature: None,
+            error: None,
+            cancel_requested: false,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// New UsdtToLn swap: the user funds an escrow for `bolt11`'s payment hash with at least `token_amount`
+    /// net, refundable to `refund`, within `funding_timeout_secs`.
+    pub fn usdt_to_ln(
+        bolt11: &str,
+        refund: Pubkey,
+        token_amount: u64,
+        funding_timeout_secs: i64,
+    ) -> Result<Self, LnError> {
+        let invoice = decode_invoice(bolt11)?;
+        let amount_msat = invoice
+            .amount_msat
+            .ok_or_else(|| LnError::Parse("invoice has no amount".into()))?;
+        let now = unix_now();
+        Ok(Self {
+            id: hex::encode(invoice.payment_hash),
+            direction: Direction::UsdtToLn,
+            state: SwapState::Created,
+            payment_hash: invoice.payment_hash,
+            preimage: None,
+            bolt11: Some(bolt11.trim().to_string()),
+            amount_msat,
+            hold: false,
+            description: None,
+            token_amount,
+            counterparty: refund,
+            refund_after: None,
+            deadline: now.saturating_add(funding_timeout_secs).min(invoice.expires_at),
+            signature: None,
+            error: None,
+            cancel_requested: false,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    /// Whether the swap can still be stopped without stranding anyone's funds: nothing sent on-chain by the
+    /// daemon, and for LnToUsdt no invoice the user may already have paid unless it is a hold invoice that can
+    /// be failed back. A UsdtToLn escrow the user already funded is refunded to them at `refund_after`.
+    pub fn is_cancellable(&self) -> bool {
+        match (self.direction, self.state) {
+            (_, SwapState::Created) => true,
+            (Direction::LnToUsdt, SwapState::InvoiceCreated | SwapState::InvoiceAccepted) => {
+                self.hold && self.signature.is_none()
+            }
+            _ => false,
+        }
+    }
+
+    /// JSON view for operators. Never includes the preimage, and withholds a plain invoice until the escrow
+    /// backing it is funded, so it cannot be paid into an unfunded swap. A hold invoice is safe to pay early:
+    /// it only settles once the escrow is funded.
+    pub fn to_json(&self) -> Value {
+        let bolt11 = match (self.direction, self.state) {
+            (Direction::LnToUsdt, SwapState::Created | SwapState::InvoiceCreated | SwapState::Failed) if !self.hold => {
+                None
+            }
+            _ => self.bolt11.as_deref(),
+        };
+        json!({
+            "id": self.id,
+            "direction": self.direction.as_str(),
+            "state": self.state.as_str(),
+            "paymentHash": hex::encode(self.payment_hash),
+            "bolt11": bolt11,
+            "amountMsat": self.amount_msat,
+            "hold": self.hold,
+            "tokenAmount": self.token_amount,
+            "counterparty": self.counterparty.to_string(),
+            "refundAfter": self.refund_after,
+            "deadline": self.deadline,
+            "signature": self.signature,
+            "error": self.error,
+            "cancelRequested": self.cancel_requested,
+            "createdAt": self.created_at,
+            "updatedAt": self.updated_at,
+        })
+    }
+}
+
+/// Wall-clock seconds, used for Lightning expiries (escrow deadlines use cluster time).
+pub fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
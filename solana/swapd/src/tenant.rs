@@ -0,0 +1,95 @@
+//! Several merchants on one daemon. Each tenant is a full, isolated swap stack: its own config file (operator
+//! key, Lightning node, fees, limits, listeners) and its own database, holding its swaps, control API keys and
+//! [`ledger`](crate::ledger), with preimages sealed under its own key. The process, the Solana RPC endpoints and
+//! the flags given on the command line are shared. Run the other commands against a tenant by pointing `--db`
+//! at its database.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Flags naming what a tenant must not share: its key, Lightning node, mint and listeners.
+pub const TENANT_FLAGS: &[&str] = &[
+    "keypair",
+    "signer",
+    "threshold_signer",
+    "mint",
+    "node",
+    "grpc_listen",
+    "rest_listen",
+    "lnurl_listen",
+    "metrics_listen",
+    "admin_keypair",
+];
+
+/// A tenant, written `ID=CONFIG`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TenantSpec {
+    /// Letters, digits, `-` and `_`; names its database and tags its logs.
+    pub id: String,
+    pub config: PathBuf,
+}
+
+impl FromStr for TenantSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id, config) = s
+            .split_once('=')
+            .ok_or_else(|| format!("tenant {s:?} is not ID=CONFIG"))?;
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+        if id.is_empty() || !id.chars().all(valid) {
+            return Err(format!("tenant id {id:?} must be letters, digits, - and _"));
+        }
+        if config.is_empty() {
+            return Err(format!("tenant {id} names no config file"));
+        }
+        Ok(Self {
+            id: id.to_string(),
+            config: config.into(),
+        })
+    }
+}
+
+impl fmt::Display for TenantSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.id, self.config.display())
+    }
+}
+
+impl TenantSpec {
+    /// The tenant's database beside `db`: `swapd.db` gives `swapd.ID.db`.
+    pub fn db(&self, db: &Path) -> PathBuf {
+        let stem = db.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+        let name = match db.extension() {
+            Some(ext) => format!("{stem}.{}.{}", self.id, ext.to_string_lossy()),
+            None => format!("{stem}.{}", self.id),
+        };
+        db.with_file_name(name)
+    }
+
+    /// The tenant's preimage key file given `--preimage-key file:PATH`: `PATH.ID`, generated on first start like
+    /// a single daemon's.
+    pub fn preimage_key(&self, key: &Path) -> PathBuf {
+        let mut path = key.as_os_str().to_owned();
+        path.push(format!(".{}", self.id));
+        path.into()
+    }
+}
+
+/// Checks that no two tenants share an id or config file.
+pub fn check_distinct(tenants: &[TenantSpec]) -> Result<(), String> {
+    for (i, a) in tenants.iter().enumerate() {
+        for b in &tenants[i + 1..] {
+            if a.id == b.id {
+                return Err(format!("tenant {} is given twice", a.id));
+            }
+            if a.config == b.config {
+                return Err(format!("tenants {} and {} share {}", a.id, b.id, a.config.display()));
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,278 @@
+//! t-of-n FROST (RFC 9591) Ed25519 signing, for operators who want no single machine able to claim or refund
+//! on their behalf. The operator key is a FROST group key whose shares sit with separate participant services;
+//! each signature takes two rounds with `threshold` of them, coordinated here:
+//!
+//! 1. `POST {url}/round1 {"session", "message": base64}` answers `{"identifier", "commitments"}`
+//! 2. `POST {url}/round2 {"session", "signing-package"}` answers `{"share"}`
+//!
+//! in the `frost-ed25519` serde encoding. Participants see the message in round 1 and can refuse anything they
+//! would not sign. The first `threshold` to commit are asked for shares; a round that does not gather enough
+//! within the share timeout fails the signature, and the send pipeline retries the transaction later.
+//!
+//! The setup file (JSON) holds the group's `public-key-package` from key generation, the `threshold` and the
+//! `participants`, each a `url` with an optional `token-env` naming the variable that holds its bearer token.
+
+use std::{collections::BTreeMap, fmt, path::Path, time::Duration};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use frost_ed25519::{self as frost, keys::PublicKeyPackage, round1::SigningCommitments, round2::SignatureShare};
+use futures_util::{future::try_join_all, stream::FuturesUnordered, StreamExt};
+use rand::RngCore;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::{Signer, SignerError},
+};
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use crate::metrics::metrics;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ThresholdConfig {
+    pub threshold: usize,
+    pub public_key_package: PublicKeyPackage,
+    pub participants: Vec<Participant>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Participant {
+    pub url: String,
+    /// Environment variable holding the participant's bearer token.
+    #[serde(default)]
+    pub token_env: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum ThresholdError {
+    Io(std::io::Error),
+    Config(String),
+    Participant {
+        url: String,
+        error: String,
+    },
+    /// Fewer than the threshold committed in time.
+    TooFewParticipants {
+        answered: usize,
+        threshold: usize,
+    },
+    /// The chosen participants did not all return shares in time.
+    Timeout,
+    Frost(frost::Error),
+}
+
+impl fmt::Display for ThresholdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "threshold signer setup: {e}"),
+            Self::Config(e) => write!(f, "threshold signer setup: {e}"),
+            Self::Participant { url, error } => write!(f, "signing participant {url}: {error}"),
+            Self::TooFewParticipants { answered, threshold } => {
+                write!(f, "{answered} of {threshold} signing participants committed in time")
+            }
+            Self::Timeout => f.write_str("signing participants did not return their shares in time"),
+            Self::Frost(e) => write!(f, "threshold signature: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ThresholdError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ThresholdError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<frost::Error> for ThresholdError {
+    fn from(e: frost::Error) -> Self {
+        Self::Frost(e)
+    }
+}
+
+impl Participant {
+    async fn call(&self, http: &reqwest::Client, round: u8, body: Value) -> Result<Value, ThresholdError> {
+        let error = |e: String| ThresholdError::Participant {
+            url: self.url.clone(),
+            error: e,
+        };
+        let mut request = http
+            .post(format!("{}/round{round}", self.url.trim_end_matches('/')))
+            .json(&body);
+        if let Some(var) = &self.token_env {
+            let token = std::env::var(var).map_err(|_| error(format!("environment variable {var} is not set")))?;
+            request = request.bearer_auth(token);
+        }
+        let response = request
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| error(e.to_string()))?;
+        response.json().await.map_err(|e| error(e.to_string()))
+    }
+
+    fn decode<T: serde::de::DeserializeOwned>(&self, value: &Value, field: &str) -> Result<T, ThresholdError> {
+        serde_json::from_value(value[field].clone()).map_err(|e| ThresholdError::Participant {
+            url: self.url.clone(),
+            error: format!("bad {field}: {e}"),
+        })
+    }
+}
+
+/// Signs as the FROST group by coordinating its participants.
+pub struct ThresholdSigner {
+    cfg: ThresholdConfig,
+    pubkey: Pubkey,
+    http: reqwest::Client,
+    share_timeout: Duration,
+}
+
+impl ThresholdSigner {
+    /// Reads the setup at `path`. Each round waits up to `share_timeout` for enough participants.
+    pub fn load(path: &Path, share_timeout: Duration) -> Result<Self, ThresholdError> {
+        let cfg: ThresholdConfig =
+            serde_json::from_slice(&std::fs::read(path)?).map_err(|e| ThresholdError::Config(e.to_string()))?;
+        if cfg.threshold == 0 || cfg.threshold > cfg.participants.len() {
+            return Err(ThresholdError::Config(format!(
+                "threshold {} with {} participants",
+                cfg.threshold,
+                cfg.participants.len()
+            )));
+        }
+        let group_key = cfg.public_key_package.verifying_key().serialize()?;
+        let pubkey = <[u8; 32]>::try_from(group_key.as_slice())
+            .map(Pubkey::new_from_array)
+            .map_err(|_| ThresholdError::Config("group key is not 32 bytes".into()))?;
+        let http = reqwest::Client::builder()
+            .timeout(share_timeout)
+            .build()
+            .unwrap_or_default();
+        Ok(Self {
+            cfg,
+            pubkey,
+            http,
+            share_timeout,
+        })
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.cfg.threshold
+    }
+
+    pub fn participants(&self) -> usize {
+        self.cfg.participants.len()
+    }
+
+    /// Runs both rounds for `message` and aggregates the shares.
+    pub async fn sign(&self, message: &[u8]) -> Result<Signature, ThresholdError> {
+        let result = self.run_session(message).await;
+        let outcome = match &result {
+            Ok(_) => "signed",
+            Err(ThresholdError::TooFewParticipants { .. } | ThresholdError::Timeout) => "timed_out",
+            Err(_) => "failed",
+        };
+        metrics().threshold_signatures.with_label_values(&[outcome]).inc();
+        result
+    }
+
+    async fn run_session(&self, message: &[u8]) -> Result<Signature, ThresholdError> {
+        let mut session = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut session);
+        let session = hex::encode(session);
+
+        let round1 = json!({ "session": session, "message": STANDARD.encode(message) });
+        let mut pending: FuturesUnordered<_> = self
+            .cfg
+            .participants
+            .iter()
+            .map(|p| {
+                let body = round1.clone();
+                async move { (p, p.call(&self.http, 1, body).await) }
+            })
+            .collect();
+        let deadline = Instant::now() + self.share_timeout;
+        let mut commitments = BTreeMap::new();
+        let mut signers = Vec::new();
+        while commitments.len() < self.cfg.threshold {
+            let (participant, response) = match tokio::time::timeout_at(deadline, pending.next()).await {
+                Ok(Some(answer)) => answer,
+                Ok(None) | Err(_) => break,
+            };
+            let committed = response.and_then(|r| {
+                let identifier: frost::Identifier = participant.decode(&r, "identifier")?;
+                let commitment: SigningCommitments = participant.decode(&r, "commitments")?;
+                if !self.cfg.public_key_package.verifying_shares().contains_key(&identifier) {
+                    return Err(ThresholdError::Participant {
+                        url: participant.url.clone(),
+                        error: "identifier is not in the group".into(),
+                    });
+                }
+                Ok((identifier, commitment))
+            });
+            match committed {
+                Ok((identifier, commitment)) => {
+                    if commitments.insert(identifier, commitment).is_none() {
+                        signers.push((identifier, participant));
+                    }
+                }
+                Err(e) => warn!(error = %e, "signing participant did not commit"),
+            }
+        }
+        if commitments.len() < self.cfg.threshold {
+            return Err(ThresholdError::TooFewParticipants {
+                answered: commitments.len(),
+                threshold: self.cfg.threshold,
+            });
+        }
+        drop(pending);
+
+        let package = frost::SigningPackage::new(commitments, message);
+        let round2 = json!({ "session": session, "signing-package": package });
+        let shares = try_join_all(signers.iter().map(|(identifier, participant)| {
+            let body = round2.clone();
+            async move {
+                let response = participant.call(&self.http, 2, body).await?;
+                let share: SignatureShare = participant.decode(&response, "share")?;
+                Ok::<_, ThresholdError>((*identifier, share))
+            }
+        }));
+        let shares: BTreeMap<_, _> = tokio::time::timeout(self.share_timeout, shares)
+            .await
+            .map_err(|_| ThresholdError::Timeout)??
+            .into_iter()
+            .collect();
+        let signature = frost::aggregate(&package, &shares, &self.cfg.public_key_package)?;
+        let signature = Signature::try_from(signature.serialize()?.as_slice())
+            .map_err(|_| ThresholdError::Config("aggregate signature is not 64 bytes".into()))?;
+        debug!(%session, signers = shares.len(), "threshold signature aggregated");
+        Ok(signature)
+    }
+}
+
+impl Signer for ThresholdSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    /// Blocks the calling thread (not the runtime's other tasks) until both rounds finish.
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let handle = tokio::runtime::Handle::current();
+        tokio::task::block_in_place(|| handle.block_on(self.sign(message)))
+            .map_err(|e| SignerError::Custom(e.to_string()))
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
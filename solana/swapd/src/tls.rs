@@ -0,0 +1,234 @@
+//! TLS for the control APIs, so gRPC, REST and the WebSocket feed can be exposed without a terminating proxy.
+//! The certificate comes from PEM files, re-read whenever either changes so renewals (e.g. by an ACME client)
+//! apply without a restart, or is generated at startup and self-signed, for development.
+
+use std::{
+    fmt,
+    fs::File,
+    future::Future,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use futures_util::{stream, Stream};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{
+    rustls::{
+        crypto::ring::sign::any_supported_type,
+        pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+        server::{ClientHello, ResolvesServerCert},
+        sign::CertifiedKey,
+        ServerConfig,
+    },
+    server::TlsStream,
+    TlsAcceptor,
+};
+use tracing::{debug, info, warn};
+
+/// How often the certificate files are checked for changes.
+const RELOAD_POLL: Duration = Duration::from_secs(30);
+/// Connections that have not completed the handshake by then are dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum TlsError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// The file holds no certificate or key, or one rustls cannot use.
+    Pem {
+        path: PathBuf,
+        reason: String,
+    },
+    SelfSigned(String),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "{}: {error}", path.display()),
+            Self::Pem { path, reason } => write!(f, "{}: {reason}", path.display()),
+            Self::SelfSigned(reason) => write!(f, "cannot generate a self-signed certificate: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for TlsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            Self::Pem { .. } | Self::SelfSigned(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TlsSource {
+    /// PEM certificate chain and private key (PKCS#8, PKCS#1 or SEC1).
+    Files { cert: PathBuf, key: PathBuf },
+    /// A certificate for `hostnames` generated at startup, its PEM written to `pem_out` for clients to trust.
+    SelfSigned { hostnames: Vec<String>, pem_out: PathBuf },
+}
+
+/// Hands out the certificate in force, which reloads replace.
+#[derive(Debug)]
+struct Resolver(RwLock<Arc<CertifiedKey>>);
+
+impl ResolvesServerCert for Resolver {
+    fn resolve(&self, _: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.0.read().expect("certificate lock poisoned").clone())
+    }
+}
+
+/// The APIs' certificate, shared by every server using it.
+pub struct Tls {
+    source: TlsSource,
+    resolver: Arc<Resolver>,
+}
+
+impl Tls {
+    pub fn load(source: TlsSource) -> Result<Self, TlsError> {
+        let key = match &source {
+            TlsSource::Files { cert, key } => load_files(cert, key)?,
+            TlsSource::SelfSigned { hostnames, pem_out } => self_signed(hostnames, pem_out)?,
+        };
+        Ok(Self {
+            source,
+            resolver: Arc::new(Resolver(RwLock::new(Arc::new(key)))),
+        })
+    }
+
+    /// A server config offering `alpn` protocols, e.g. `h2` for gRPC.
+    pub fn server_config(&self, alpn: &[&[u8]]) -> Arc<ServerConfig> {
+        let mut cfg = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_cert_resolver(self.resolver.clone());
+        cfg.alpn_protocols = alpn.iter().map(|p| p.to_vec()).collect();
+        Arc::new(cfg)
+    }
+
+    /// Reloads the certificate files whenever they change, until `shutdown` resolves. A pair that does not load
+    /// (e.g. the certificate written but not yet its key) is logged and the certificate in force kept.
+    pub async fn run(&self, shutdown: impl Future<Output = ()>) {
+        let TlsSource::Files { cert, key } = &self.source else {
+            return shutdown.await;
+        };
+        tokio::pin!(shutdown);
+        let mut modified = (modified(cert), modified(key));
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => return,
+                _ = tokio::time::sleep(RELOAD_POLL) => {
+                    let now = (modified(cert), modified(key));
+                    if now == modified {
+                        continue;
+                    }
+                    match load_files(cert, key) {
+                        Ok(loaded) => {
+                            modified = now;
+                            *self.resolver.0.write().expect("certificate lock poisoned") = Arc::new(loaded);
+                            info!(cert = %cert.display(), "tls certificate reloaded");
+                        }
+                        Err(e) => warn!(error = %e, "tls certificate not reloaded; keeping the current one"),
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, TlsError> {
+    File::open(path).map(BufReader::new).map_err(|error| TlsError::Io {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+fn load_files(cert: &Path, key: &Path) -> Result<CertifiedKey, TlsError> {
+    let certs = rustls_pemfile::certs(&mut open(cert)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| TlsError::Io {
+            path: cert.to_path_buf(),
+            error,
+        })?;
+    if certs.is_empty() {
+        return Err(TlsError::Pem {
+            path: cert.to_path_buf(),
+            reason: "no certificate".into(),
+        });
+    }
+    let pem_error = |reason: String| TlsError::Pem {
+        path: key.to_path_buf(),
+        reason,
+    };
+    let private = rustls_pemfile::private_key(&mut open(key)?)
+        .map_err(|e| pem_error(e.to_string()))?
+        .ok_or_else(|| pem_error("no private key".into()))?;
+    certified(certs, &private).map_err(pem_error)
+}
+
+fn certified(certs: Vec<CertificateDer<'static>>, key: &PrivateKeyDer<'_>) -> Result<CertifiedKey, String> {
+    let key = any_supported_type(key).map_err(|e| e.to_string())?;
+    Ok(CertifiedKey::new(certs, key))
+}
+
+fn self_signed(hostnames: &[String], pem_out: &Path) -> Result<CertifiedKey, TlsError> {
+    let failed = |e: rcgen::Error| TlsError::SelfSigned(e.to_string());
+    let cert = rcgen::generate_simple_self_signed(hostnames.to_vec()).map_err(failed)?;
+    let pem = cert.serialize_pem().map_err(failed)?;
+    std::fs::write(pem_out, pem).map_err(|error| TlsError::Io {
+        path: pem_out.to_path_buf(),
+        error,
+    })?;
+    let der = CertificateDer::from(cert.serialize_der().map_err(failed)?);
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(cert.serialize_private_key_der()));
+    warn!(
+        ?hostnames,
+        pem = %pem_out.display(),
+        "serving a self-signed tls certificate; for development only"
+    );
+    certified(vec![der], &key).map_err(TlsError::SelfSigned)
+}
+
+/// TLS connections accepted on `listener` under `cfg`. Handshakes run concurrently, so a slow client does
+/// not hold up the others; failed ones are dropped.
+pub fn incoming(listener: TcpListener, cfg: Arc<ServerConfig>) -> impl Stream<Item = io::Result<TlsStream<TcpStream>>> {
+    let acceptor = TlsAcceptor::from(cfg);
+    let (tx, rx) = mpsc::channel::<io::Result<TlsStream<TcpStream>>>(64);
+    tokio::spawn(async move {
+        loop {
+            let (tcp, peer) = tokio::select! {
+                // The server stopped.
+                _ = tx.closed() => return,
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        debug!(error = %e, "cannot accept connection");
+                        continue;
+                    }
+                },
+            };
+            let (acceptor, tx) = (acceptor.clone(), tx.clone());
+            tokio::spawn(async move {
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(tcp)).await {
+                    Ok(Ok(tls)) => {
+                        let _ = tx.send(Ok(tls)).await;
+                    }
+                    Ok(Err(e)) => debug!(%peer, error = %e, "tls handshake failed"),
+                    Err(_) => debug!(%peer, "tls handshake timed out"),
+                }
+            });
+        }
+    });
+    stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|conn| (conn, rx)) })
+}
@@ -0,0 +1,173 @@
+//! How far an escrow may fall short of the token amount its swap was priced at. Converting between msat and
+//! token base units rounds, so an escrow funded from the taker's own conversion rarely matches to the unit. An
+//! [`AmountTolerance`] accepts a shortfall up to the larger of an absolute amount and a share of the expected
+//! one; nothing short is accepted by default. Every check the engine makes before relying on an escrow is
+//! recorded with the swap as an [`AmountCheck`].
+
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AmountTolerance {
+    /// Token base units an escrow may be short whatever the amount.
+    pub tokens: u64,
+    /// Basis points of the expected amount an escrow may be short.
+    pub bps: u16,
+}
+
+impl AmountTolerance {
+    /// The most an escrow backing `expected` tokens may be short.
+    pub fn allowed(&self, expected: u64) -> u64 {
+        let relative = u128::from(expected) * u128::from(self.bps) / 10_000;
+        // Over 10_000 bps the share can exceed a u64; it allows everything then.
+        self.tokens.max(u64::try_from(relative).unwrap_or(u64::MAX))
+    }
+
+    /// Checks an escrow holding `actual` tokens net against the `expected` of swap `swap_id`.
+    pub fn check(&self, swap_id: &str, expected: u64, actual: u64, now: i64) -> AmountCheck {
+        let allowed = self.allowed(expected);
+        AmountCheck {
+            swap_id: swap_id.to_string(),
+            expected,
+            actual,
+            allowed,
+            accepted: actual.saturating_add(allowed) >= expected,
+            checked_at: now,
+        }
+    }
+}
+
+/// An escrow amount checked against its swap, with the tolerance that applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmountCheck {
+    pub swap_id: String,
+    /// Token amount the swap was priced at, net of fees.
+    pub expected: u64,
+    /// What the escrow holds net.
+    pub actual: u64,
+    /// Shortfall the tolerance allowed.
+    pub allowed: u64,
+    pub accepted: bool,
+    pub checked_at: i64,
+}
+
+impl AmountCheck {
+    /// How much the escrow is short; 0 when it holds enough or more.
+    pub fn shortfall(&self) -> u64 {
+        self.expected.saturating_sub(self.actual)
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "swapId": self.swap_id,
+            "expected": self.expected,
+            "actual": self.actual,
+            "allowed": self.allowed,
+            "shortfall": self.shortfall(),
+            "accepted": self.accepted,
+            "checkedAt": self.checked_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn accepted(tolerance: AmountTolerance, expected: u64, actual: u64) -> bool {
+        tolerance.check("swap", expected, actual, NOW).accepted
+    }
+
+    #[test]
+    fn default_accepts_nothing_short() {
+        let exact = AmountTolerance::default();
+        assert_eq!(exact.allowed(1_000_000), 0);
+        assert!(accepted(exact, 1_000_000, 1_000_000));
+        assert!(!accepted(exact, 1_000_000, 999_999));
+        assert!(!accepted(exact, 1, 0));
+    }
+
+    #[test]
+    fn absolute_tolerance_boundary() {
+        let t = AmountTolerance { tokens: 5, bps: 0 };
+        assert!(accepted(t, 1_000, 995));
+        assert!(!accepted(t, 1_000, 994));
+        // Small amounts get the whole allowance, down to nothing at all.
+        assert!(accepted(t, 5, 0));
+        assert!(!accepted(t, 6, 0));
+    }
+
+    #[test]
+    fn relative_tolerance_boundary() {
+        // 0.5% of 1_000_000.
+        let t = AmountTolerance { tokens: 0, bps: 50 };
+        assert_eq!(t.allowed(1_000_000), 5_000);
+        assert!(accepted(t, 1_000_000, 995_000));
+        assert!(!accepted(t, 1_000_000, 994_999));
+        // 0.5% of 199 rounds down to nothing.
+        assert_eq!(t.allowed(199), 0);
+        assert!(!accepted(t, 199, 198));
+    }
+
+    #[test]
+    fn larger_of_the_two_applies() {
+        let t = AmountTolerance { tokens: 100, bps: 10 };
+        // 0.1% of 50_000 is 50: the absolute 100 wins.
+        assert_eq!(t.allowed(50_000), 100);
+        assert!(accepted(t, 50_000, 49_900));
+        assert!(!accepted(t, 50_000, 49_899));
+        // 0.1% of 1_000_000 is 1_000: the share wins.
+        assert_eq!(t.allowed(1_000_000), 1_000);
+        assert!(accepted(t, 1_000_000, 999_000));
+        assert!(!accepted(t, 1_000_000, 998_999));
+    }
+
+    #[test]
+    fn more_than_expected_is_always_accepted() {
+        for t in [AmountTolerance::default(), AmountTolerance { tokens: 5, bps: 50 }] {
+            for (expected, actual) in [(1_000, 1_001), (1_000, u64::MAX), (0, 1), (0, 0)] {
+                let check = t.check("swap", expected, actual, NOW);
+                assert!(check.accepted, "{t:?} {expected} {actual}");
+                assert_eq!(check.shortfall(), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn extremes_do_not_overflow() {
+        let t = AmountTolerance {
+            tokens: u64::MAX,
+            bps: u16::MAX,
+        };
+        assert_eq!(t.allowed(u64::MAX), u64::MAX);
+        assert!(accepted(t, u64::MAX, 0));
+        let full = AmountTolerance { tokens: 0, bps: 10_000 };
+        assert_eq!(full.allowed(u64::MAX), u64::MAX);
+        let over = AmountTolerance {
+            tokens: 0,
+            bps: u16::MAX,
+        };
+        assert_eq!(over.allowed(u64::MAX), u64::MAX);
+        assert!(accepted(over, u64::MAX, 0));
+        assert!(!accepted(AmountTolerance { tokens: 0, bps: 9_999 }, u64::MAX, 0));
+    }
+
+    #[test]
+    fn check_records_the_shortfall() {
+        let check = AmountTolerance { tokens: 5, bps: 0 }.check("abc", 1_000, 994, NOW);
+        assert_eq!(check.shortfall(), 6);
+        assert_eq!(
+            check.to_json(),
+            json!({
+                "swapId": "abc",
+                "expected": 1_000,
+                "actual": 994,
+                "allowed": 5,
+                "shortfall": 6,
+                "accepted": false,
+                "checkedAt": NOW,
+            })
+        );
+    }
+}
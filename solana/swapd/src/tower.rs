@@ -0,0 +1,271 @@
+//! Watchtower mode: settles escrows on behalf of users who are offline.
+//!
+//! The program has no permissionless or delegated settlement path: Claim must be signed by the recipient and
+//! Refund by the refund key. A tower therefore cannot settle with its own key. What it can do:
+//!
+//! - Submit a Refund the user pre-signed against a durable nonce, once refund_after passes. The tower may
+//!   co-sign as fee payer, so the user does not need SOL at submission time.
+//! - Alert about unclaimed escrows of watched recipients as refund_after approaches. A Claim embeds the
+//!   preimage in its instruction data, so it cannot be pre-signed before the preimage is known.
+
+use std::{fmt, future::Future, str::FromStr, sync::Arc, time::Duration};
+
+use intercom_swap_client::{
+    client::EscrowClient,
+    filters::EscrowQuery,
+    partial_sign, pda,
+    retry::{RetryPolicy, SendOutcome},
+    rpc::EscrowRpc,
+    state::EscrowStatus,
+};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signer::Signer,
+    transaction::{uses_durable_nonce, Transaction},
+};
+use tracing::{debug, info, warn};
+
+use crate::{error::SwapError, kms::Operator, metrics::metrics, store::Store, swap::unix_now};
+
+/// Instruction tag of Refund in `intercom_swap_core::instruction`.
+const REFUND_TAG: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchStatus {
+    Watching,
+    Claimed,
+    Refunded,
+    /// The pre-signed refund can no longer land (its nonce advanced); the user has to supply a new one.
+    Void,
+}
+
+impl WatchStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Watching => "watching",
+            Self::Claimed => "claimed",
+            Self::Refunded => "refunded",
+            Self::Void => "void",
+        }
+    }
+}
+
+impl fmt::Display for WatchStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for WatchStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "watching" => Self::Watching,
+            "claimed" => Self::Claimed,
+            "refunded" => Self::Refunded,
+            "void" => Self::Void,
+            other => return Err(format!("unknown watch status {other:?}")),
+        })
+    }
+}
+
+/// An escrow watched on a user's behalf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watch {
+    pub payment_hash: [u8; 32],
+    /// Base64 Refund transaction signed by the refund key against a durable nonce.
+    pub refund_tx: Option<String>,
+    pub status: WatchStatus,
+    pub error: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Watch {
+    pub fn new(payment_hash: [u8; 32], refund_tx: Option<String>) -> Self {
+        let now = unix_now();
+        Self {
+            payment_hash,
+            refund_tx,
+            status: WatchStatus::Watching,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "paymentHash": hex::encode(self.payment_hash),
+            "hasRefundTx": self.refund_tx.is_some(),
+            "status": self.status.as_str(),
+            "error": self.error,
+            "createdAt": self.created_at,
+            "updatedAt": self.updated_at,
+        })
+    }
+}
+
+/// Checks that `tx` is a durable-nonce Refund of the escrow for `payment_hash` under `program_id`, and returns
+/// its nonce account.
+pub fn check_refund_tx(program_id: &Pubkey, payment_hash: &[u8; 32], tx: &Transaction) -> Result<Pubkey, String> {
+    let keys = &tx.message.account_keys;
+    let nonce_ix = uses_durable_nonce(tx).ok_or("refund transaction does not use a durable nonce")?;
+    let nonce_account = nonce_ix
+        .accounts
+        .first()
+        .and_then(|i| keys.get(*i as usize))
+        .ok_or("malformed nonce instruction")?;
+    let escrow = pda::find_escrow_pda(program_id, payment_hash).0;
+    let refunds_escrow = tx.message.instructions.iter().any(|ix| {
+        keys.get(ix.program_id_index as usize) == Some(program_id)
+            && ix.data.first() == Some(&REFUND_TAG)
+            && ix.accounts.get(1).and_then(|i| keys.get(*i as usize)) == Some(&escrow)
+    });
+    if !refunds_escrow {
+        return Err("transaction does not refund this escrow".into());
+    }
+    Ok(*nonce_account)
+}
+
+#[derive(Debug, Clone)]
+pub struct TowerConfig {
+    pub interval: Duration,
+    /// Recipients whose active escrows are watched for missed claims.
+    pub watch_recipients: Vec<Pubkey>,
+    /// Alert once an unclaimed escrow is this close to refund_after.
+    pub alert_margin_secs: i64,
+    pub retry: RetryPolicy,
+}
+
+pub struct Tower {
+    store: Arc<Store>,
+    client: EscrowClient,
+    /// Co-signs pre-signed refunds as fee payer when the user left that slot to the tower.
+    operator: Arc<Operator>,
+    cfg: TowerConfig,
+}
+
+impl Tower {
+    pub fn new(store: Arc<Store>, client: EscrowClient, operator: Arc<Operator>, cfg: TowerConfig) -> Self {
+        Self {
+            store,
+            client,
+            operator,
+            cfg,
+        }
+    }
+
+    /// Scans every `interval` until `shutdown` resolves.
+    pub async fn run(&self, shutdown: impl Future<Output = ()>) {
+        tokio::pin!(shutdown);
+        loop {
+            if let Err(e) = self.scan().await {
+                warn!(error = %e, "tower scan failed; retrying next interval");
+            }
+            tokio::select! {
+                _ = &mut shutdown => return,
+                _ = tokio::time::sleep(self.cfg.interval) => {}
+            }
+        }
+    }
+
+    pub async fn scan(&self) -> Result<(), SwapError> {
+        let now = self.client.get_unix_timestamp().await?;
+        for mut watch in self.store.active_watches()? {
+            if let Err(e) = self.check(&mut watch, now).await {
+                warn!(payment_hash = %hex::encode(watch.payment_hash), error = %e, "watch check failed");
+            }
+        }
+        for recipient in &self.cfg.watch_recipients {
+            let query = EscrowQuery::default()
+                .status(EscrowStatus::Active)
+                .recipient(*recipient);
+            for (address, escrow) in self.client.list_escrows(&query).await? {
+                let remaining = escrow.refund_after - now;
+                if remaining < self.cfg.alert_margin_secs {
+                    warn!(
+                        alert = "unclaimed_escrow",
+                        escrow = %address,
+                        %recipient,
+                        remaining_secs = remaining,
+                        "escrow is still unclaimed close to refund_after"
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn set(&self, watch: &mut Watch, status: WatchStatus, error: Option<String>) -> Result<(), SwapError> {
+        info!(payment_hash = %hex::encode(watch.payment_hash), from = %watch.status, to = %status, "watch");
+        watch.status = status;
+        watch.error = error;
+        self.store.update_watch(watch)?;
+        Ok(())
+    }
+
+    async fn check(&self, watch: &mut Watch, now: i64) -> Result<(), SwapError> {
+        let Some(escrow) = self.client.get_escrow(&watch.payment_hash).await? else {
+            return Ok(());
+        };
+        match escrow.status {
+            EscrowStatus::Claimed => return self.set(watch, WatchStatus::Claimed, None),
+            EscrowStatus::Refunded => return self.set(watch, WatchStatus::Refunded, None),
+            EscrowStatus::Active => {}
+        }
+        let Some(encoded) = watch.refund_tx.clone() else {
+            return Ok(());
+        };
+        if now < escrow.refund_after {
+            return Ok(());
+        }
+        let mut tx = match partial_sign::decode_base64(&encoded) {
+            Ok(tx) => tx,
+            Err(e) => return self.set(watch, WatchStatus::Void, Some(e.to_string())),
+        };
+        let nonce_account = match check_refund_tx(self.client.program_id(), &watch.payment_hash, &tx) {
+            Ok(account) => account,
+            Err(e) => return self.set(watch, WatchStatus::Void, Some(e)),
+        };
+        if !self.client.nonce_still_valid(&tx, &nonce_account).await? {
+            return self.set(
+                watch,
+                WatchStatus::Void,
+                Some("nonce advanced; the refund must be re-signed".into()),
+            );
+        }
+        if partial_sign::missing_signers(&tx).contains(&self.operator.pubkey()) {
+            partial_sign::partial_sign(&mut tx, &[&*self.operator])?;
+        }
+        if !partial_sign::is_fully_signed(&tx) {
+            return self.set(
+                watch,
+                WatchStatus::Void,
+                Some("refund transaction is missing signatures".into()),
+            );
+        }
+        // Durable-nonce transactions are never re-signed, so no signers are passed.
+        let outcome = self
+            .client
+            .send_with_retry(tx.clone(), &[], &self.cfg.retry, |event| {
+                metrics().observe_send_event(event);
+                debug!(payment_hash = %hex::encode(watch.payment_hash), ?event, "refund progress");
+            })
+            .await?;
+        metrics().observe_send_outcome(&outcome);
+        if let Some(e) = outcome.error(self.client.program_id(), &tx) {
+            // The escrow state decides on the next scan (e.g. NotActive after a last-moment claim).
+            warn!(payment_hash = %hex::encode(watch.payment_hash), error = %e, "pre-signed refund failed");
+            watch.error = Some(e.to_string());
+            self.store.update_watch(watch)?;
+            return Ok(());
+        }
+        if let SendOutcome::Confirmed { signature, .. } = outcome {
+            info!(payment_hash = %hex::encode(watch.payment_hash), %signature, "submitted pre-signed refund");
+            return self.set(watch, WatchStatus::Refunded, None);
+        }
+        Ok(())
+    }
+}
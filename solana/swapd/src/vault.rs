@@ -0,0 +1,340 @@
+//! Preimages sealed at rest. A preimage is worth the money it unlocks, so the database only holds it encrypted
+//! with ChaCha20-Poly1305 under a key kept outside the database, with the payment hash as associated data so a
+//! sealed preimage cannot be passed off as another swap's. In memory a [`Preimage`] is plain (and zeroed on
+//! drop) only from generation until it is first stored; swaps read back carry it sealed. Opening one is
+//! crate-private and done only where the engine settles: creating or settling the invoice, and claiming.
+//!
+//! The key comes from a [`VaultKeySource`], written as one string:
+//!
+//! - `path/to/key` or `file:path/to/key`: 64 hex characters, generated on first start if the file is missing
+//! - `env:NAME`: 64 hex characters in an environment variable
+//! - `aws-kms:KEY_ID` or `gcp-kms:projects/P/locations/L/keyRings/R/cryptoKeys/K`: a key generated on first
+//!   start and kept next to the database only wrapped by that KMS key ([`crate::kms::KeyWrap`]), so a copy of
+//!   the database directory is of no use without access to the KMS
+//!
+//! A key file next to the database travels with every copy of it, which is only fit for development.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use rand::RngCore;
+use tracing::info;
+use zeroize::Zeroizing;
+
+use crate::kms::{KeyWrap, KmsError, SignerSource};
+
+/// Format byte leading every sealed preimage.
+const VERSION: u8 = 1;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const KMS_TIMEOUT: Duration = Duration::from_secs(30);
+/// Length of a sealed preimage: version, nonce, ciphertext and tag.
+pub const SEALED_LEN: usize = 1 + NONCE_LEN + 32 + TAG_LEN;
+
+/// A swap's preimage as it is carried around.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Preimage {
+    /// Not stored yet.
+    Plain(Zeroizing<[u8; 32]>),
+    /// As stored: [`SEALED_LEN`] bytes.
+    Sealed(Vec<u8>),
+}
+
+impl Preimage {
+    pub fn plain(preimage: [u8; 32]) -> Self {
+        Self::Plain(Zeroizing::new(preimage))
+    }
+}
+
+impl fmt::Debug for Preimage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Plain(_) => f.write_str("Preimage(plain)"),
+            Self::Sealed(_) => f.write_str("Preimage(sealed)"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum VaultError {
+    Io(io::Error),
+    MissingEnv(String),
+    InvalidKey(String),
+    InvalidSource(String),
+    Kms(KmsError),
+    /// Wrong key, or the sealed bytes were altered or belong to another payment hash.
+    Unsealable,
+}
+
+impl fmt::Display for VaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "preimage key: {e}"),
+            Self::MissingEnv(name) => write!(f, "environment variable {name} is not set"),
+            Self::InvalidKey(e) => write!(f, "invalid preimage key: {e}"),
+            Self::InvalidSource(s) => write!(f, "unrecognized preimage key source: {s}"),
+            Self::Kms(e) => write!(f, "preimage key: {e}"),
+            Self::Unsealable => f.write_str("cannot open sealed preimage (wrong key or tampered)"),
+        }
+    }
+}
+
+impl std::error::Error for VaultError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Kms(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for VaultError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<KmsError> for VaultError {
+    fn from(e: KmsError) -> Self {
+        Self::Kms(e)
+    }
+}
+
+/// Where the preimage key is kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VaultKeySource {
+    File(PathBuf),
+    Env(String),
+    /// The key wrapped by `kms`, in the file `wrapped`; empty until [`Self::locate`] puts it by the database.
+    Kms {
+        kms: SignerSource,
+        wrapped: PathBuf,
+    },
+}
+
+impl FromStr for VaultKeySource {
+    type Err = VaultError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("file:") {
+            return Ok(Self::File(path.into()));
+        }
+        if let Some(var) = s.strip_prefix("env:") {
+            return Ok(Self::Env(var.to_string()));
+        }
+        if s.starts_with("aws-kms:") || s.starts_with("gcp-kms:") {
+            return Ok(Self::Kms {
+                kms: s.parse()?,
+                wrapped: PathBuf::new(),
+            });
+        }
+        if s.is_empty() || s.contains("://") {
+            return Err(VaultError::InvalidSource(s.to_string()));
+        }
+        Ok(Self::File(s.into()))
+    }
+}
+
+impl fmt::Display for VaultKeySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "file:{}", path.display()),
+            Self::Env(var) => write!(f, "env:{var}"),
+            Self::Kms { kms, .. } => write!(f, "{kms}"),
+        }
+    }
+}
+
+impl VaultKeySource {
+    /// The key file kept next to the database `db` when no source is configured.
+    pub fn beside(db: &Path) -> Self {
+        let mut path = db.as_os_str().to_owned();
+        path.push(".preimage-key");
+        Self::File(path.into())
+    }
+
+    /// Keeps a KMS-wrapped key next to the database `db`, unless it was placed elsewhere.
+    pub fn locate(self, db: &Path) -> Self {
+        match self {
+            Self::Kms { kms, wrapped } if wrapped.as_os_str().is_empty() => {
+                let mut path = db.as_os_str().to_owned();
+                path.push(".preimage-key.wrapped");
+                Self::Kms {
+                    kms,
+                    wrapped: path.into(),
+                }
+            }
+            source => source,
+        }
+    }
+
+    /// Whether this is a plain key file in the database `db`'s directory, so copied along with it.
+    pub fn is_beside(&self, db: &Path) -> bool {
+        let dir = |p: &Path| match p.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => fs::canonicalize(parent).ok(),
+            _ => std::env::current_dir().ok(),
+        };
+        match self {
+            Self::File(path) => dir(path).is_some_and(|d| Some(d) == dir(db)),
+            Self::Env(_) | Self::Kms { .. } => false,
+        }
+    }
+
+    pub async fn load(&self) -> Result<Vault, VaultError> {
+        let hex_key = match self {
+            Self::File(path) => match fs::read_to_string(path) {
+                Ok(text) => Zeroizing::new(text),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return generate(path),
+                Err(e) => return Err(e.into()),
+            },
+            Self::Env(var) => Zeroizing::new(std::env::var(var).map_err(|_| VaultError::MissingEnv(var.clone()))?),
+            Self::Kms { kms, wrapped } => return load_wrapped(kms, wrapped).await,
+        };
+        let mut key = Zeroizing::new([0u8; 32]);
+        hex::decode_to_slice(hex_key.trim(), key.as_mut_slice()).map_err(|e| VaultError::InvalidKey(e.to_string()))?;
+        Ok(Vault::new(&key))
+    }
+}
+
+/// Writes a new random key to `path`, readable by the owner only.
+fn generate(path: &Path) -> Result<Vault, VaultError> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    rand::rngs::OsRng.fill_bytes(key.as_mut_slice());
+    fs::write(path, Zeroizing::new(hex::encode(key.as_slice())).as_bytes())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+    info!(path = %path.display(), "generated preimage key; back it up with the database");
+    Ok(Vault::new(&key))
+}
+
+/// Opens the key `kms` wrapped into `path`, or generates one and writes it there wrapped.
+async fn load_wrapped(kms: &SignerSource, path: &Path) -> Result<Vault, VaultError> {
+    let wrap = KeyWrap::connect(kms.clone(), KMS_TIMEOUT).await?;
+    let wrapped = match fs::read(path) {
+        Ok(wrapped) => wrapped,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let mut key = Zeroizing::new([0u8; 32]);
+            rand::rngs::OsRng.fill_bytes(key.as_mut_slice());
+            fs::write(path, wrap.wrap(key.as_slice()).await?)?;
+            info!(path = %path.display(), %kms, "generated preimage key, wrapped by the KMS key");
+            return Ok(Vault::new(&key));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let key = wrap.unwrap(&wrapped).await?;
+    let key: &[u8; 32] = key
+        .as_slice()
+        .try_into()
+        .map_err(|_| VaultError::InvalidKey(format!("KMS unwrapped {} bytes, not 32", key.len())))?;
+    Ok(Vault::new(key))
+}
+
+pub struct Vault {
+    cipher: ChaCha20Poly1305,
+}
+
+impl Vault {
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+        }
+    }
+
+    /// The stored form of `preimage`, the preimage of `payment_hash`.
+    pub fn seal(&self, payment_hash: &[u8; 32], preimage: &Preimage) -> Result<Vec<u8>, VaultError> {
+        let plain = match preimage {
+            Preimage::Plain(plain) => plain,
+            Preimage::Sealed(sealed) => return Ok(sealed.clone()),
+        };
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: plain.as_slice(),
+                    aad: payment_hash,
+                },
+            )
+            .map_err(|_| VaultError::Unsealable)?;
+        let mut sealed = Vec::with_capacity(SEALED_LEN);
+        sealed.push(VERSION);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// The plain preimage, for settling with it; zeroed when dropped.
+    pub(crate) fn open(&self, payment_hash: &[u8; 32], preimage: &Preimage) -> Result<Zeroizing<[u8; 32]>, VaultError> {
+        let sealed = match preimage {
+            Preimage::Plain(plain) => return Ok(plain.clone()),
+            Preimage::Sealed(sealed) => sealed,
+        };
+        if sealed.len() != SEALED_LEN || sealed[0] != VERSION {
+            return Err(VaultError::Unsealable);
+        }
+        let (nonce, ciphertext) = sealed[1..].split_at(NONCE_LEN);
+        let plain = Zeroizing::new(
+            self.cipher
+                .decrypt(
+                    Nonce::from_slice(nonce),
+                    Payload {
+                        msg: ciphertext,
+                        aad: payment_hash,
+                    },
+                )
+                .map_err(|_| VaultError::Unsealable)?,
+        );
+        let mut preimage = Zeroizing::new([0u8; 32]);
+        preimage.copy_from_slice(plain.as_slice());
+        Ok(preimage)
+    }
+
+    /// `data` sealed for keeping outside the database (backups): nonce then ciphertext, bound to `context`.
+    pub(crate) fn seal_blob(&self, context: &[u8], data: &[u8]) -> Result<Vec<u8>, VaultError> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: data,
+                    aad: context,
+                },
+            )
+            .map_err(|_| VaultError::Unsealable)?;
+        Ok([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    /// Inverse of [`Self::seal_blob`].
+    pub(crate) fn open_blob(&self, context: &[u8], sealed: &[u8]) -> Result<Zeroizing<Vec<u8>>, VaultError> {
+        if sealed.len() < NONCE_LEN + TAG_LEN {
+            return Err(VaultError::Unsealable);
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: context,
+                },
+            )
+            .map(Zeroizing::new)
+            .map_err(|_| VaultError::Unsealable)
+    }
+}
@@ -0,0 +1,184 @@
+//! WebSocket push of swap progress at `/v1/ws` on the [`rest`](crate::rest) server, so frontends follow swaps
+//! without polling. A client subscribes to swap ids or accounts (the counterparty's Solana key) and gets the
+//! swap, as `Swap::to_json`, every time one it follows changes state: at once on subscribing, then on each
+//! transition (`created` once a quote is accepted, `escrow_funded`, `invoice_settled`, `completed` once
+//! claimed, `refunded`, `failed`, ...).
+//!
+//! Client messages: `{"type":"subscribe","swapId":…}`, `{"type":"subscribe","account":…}` and the same with
+//! `"unsubscribe"`. Server messages: `{"type":"swap","event":<state>,"swap":…}` and
+//! `{"type":"error","message":…}`.
+
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::Response,
+};
+use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::debug;
+
+use crate::{
+    auth::Scope,
+    control::{parse_pubkey, Control, ControlError},
+    rest::{ApiError, Caller},
+    swap::{Swap, SwapState},
+};
+
+/// What a client follows.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Topic {
+    Swap(String),
+    Account(Pubkey),
+}
+
+impl Topic {
+    /// `(subscribe, topic)` from a client message.
+    fn parse(text: &str) -> Result<(bool, Self), ControlError> {
+        let malformed = |what: &str| ControlError::InvalidArgument(what.to_string());
+        let v: Value = serde_json::from_str(text).map_err(|e| malformed(&e.to_string()))?;
+        let subscribe = match v["type"].as_str() {
+            Some("subscribe") => true,
+            Some("unsubscribe") => false,
+            _ => return Err(malformed("type must be subscribe or unsubscribe")),
+        };
+        let topic = match (v["swapId"].as_str(), v["account"].as_str()) {
+            (Some(id), None) => Self::Swap(id.to_string()),
+            (None, Some(account)) => Self::Account(parse_pubkey(account, "account")?),
+            _ => return Err(malformed("give one of swapId or account")),
+        };
+        Ok((subscribe, topic))
+    }
+
+    fn matches(&self, swap: &Swap) -> bool {
+        match self {
+            Self::Swap(id) => *id == swap.id,
+            Self::Account(account) => *account == swap.counterparty,
+        }
+    }
+
+    /// The swaps it covers now.
+    fn current(&self, control: &Control) -> Result<Vec<Swap>, ControlError> {
+        match self {
+            Self::Swap(id) => control.get(id).map(|swap| vec![swap]),
+            Self::Account(account) => control.swaps_of(account),
+        }
+    }
+}
+
+/// Needs the `read` scope, checked before the upgrade.
+pub(crate) async fn upgrade(
+    caller: Caller,
+    State(control): State<Control>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    caller.require(Scope::Read)?;
+    Ok(ws.on_upgrade(move |socket| Session::new(control, socket).run()))
+}
+
+struct Session {
+    control: Control,
+    socket: WebSocket,
+    topics: HashSet<Topic>,
+    /// Last state sent per swap, so rewrites without a transition are not repeated.
+    sent: HashMap<String, SwapState>,
+}
+
+impl Session {
+    fn new(control: Control, socket: WebSocket) -> Self {
+        Self {
+            control,
+            socket,
+            topics: HashSet::new(),
+            sent: HashMap::new(),
+        }
+    }
+
+    async fn run(mut self) {
+        // Subscribed before any snapshot is read, so no write falls between the two.
+        let mut writes = self.control.subscribe();
+        loop {
+            let open = tokio::select! {
+                message = self.socket.recv() => match message {
+                    Some(Ok(Message::Text(text))) => self.on_message(&text).await,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => false,
+                    Some(Ok(_)) => true,
+                },
+                write = writes.recv() => match write {
+                    Ok(swap) if self.topics.iter().any(|t| t.matches(&swap)) => self.push(&swap).await,
+                    Ok(_) => true,
+                    Err(RecvError::Lagged(missed)) => {
+                        debug!(missed, "websocket subscriber lagged; resyncing");
+                        self.resync().await
+                    }
+                    Err(RecvError::Closed) => false,
+                },
+            };
+            if !open {
+                break;
+            }
+        }
+    }
+
+    /// Handles one client message; `false` once the socket is gone.
+    async fn on_message(&mut self, text: &str) -> bool {
+        let (subscribe, topic) = match Topic::parse(text) {
+            Ok(parsed) => parsed,
+            Err(e) => return self.error(&e).await,
+        };
+        if !subscribe {
+            self.topics.remove(&topic);
+            return true;
+        }
+        let current = match topic.current(&self.control) {
+            Ok(current) => current,
+            Err(e) => return self.error(&e).await,
+        };
+        self.topics.insert(topic);
+        for swap in current {
+            // A new subscription always gets the current state, even if another topic sent it already.
+            self.sent.remove(&swap.id);
+            if !self.push(&swap).await {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Re-reads everything followed after missed writes.
+    async fn resync(&mut self) -> bool {
+        let topics: Vec<Topic> = self.topics.iter().cloned().collect();
+        for topic in topics {
+            let Ok(current) = topic.current(&self.control) else {
+                continue;
+            };
+            for swap in current {
+                if !self.push(&swap).await {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Sends `swap` unless the client already has its state; `false` once the socket is gone.
+    async fn push(&mut self, swap: &Swap) -> bool {
+        if self.sent.insert(swap.id.clone(), swap.state) == Some(swap.state) {
+            return true;
+        }
+        let message = json!({ "type": "swap", "event": swap.state.as_str(), "swap": swap.to_json() });
+        self.send(message).await
+    }
+
+    async fn error(&mut self, e: &ControlError) -> bool {
+        self.send(json!({ "type": "error", "message": e.to_string() })).await
+    }
+
+    async fn send(&mut self, message: Value) -> bool {
+        self.socket.send(Message::Text(message.to_string())).await.is_ok()
+    }
+}
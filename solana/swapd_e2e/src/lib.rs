@@ -0,0 +1,240 @@
+//! End-to-end harness for swapd: bitcoind and two Core Lightning nodes on regtest (`dev/ln-regtest`), a
+//! `solana-test-validator` with `ln_usdt_escrow` deployed, and the `swapd` binary running against all of them.
+//!
+//! [`Harness::start`] brings the stack up from scratch. It funds both nodes and opens a channel from the user's
+//! node (`cln-bob`) to the operator's (`cln-alice`), pushing half of it across so either side can pay. It then
+//! creates a mint, initializes the platform and trade configs and starts `swapd run` with the operator key. Tests
+//! queue swaps with the `swapd` subcommands and play the user's side with the user's node and [`Harness::user`].
+//! Dropping the harness stops swapd and the validator and takes the compose stack down. The run's directory,
+//! `swapd-e2e-*` under the system temp dir, keeps swapd's database and log and the validator's ledger for a
+//! post-mortem.
+//!
+//! Needs docker with compose, `solana-test-validator` and `cargo build-sbf`. The tests are `#[ignore]`d, so a plain
+//! `cargo test` skips them:
+//!
+//! ```text
+//! cargo test --manifest-path solana/swapd_e2e/Cargo.toml -- --ignored --test-threads=1
+//! ```
+//!
+//! `SWAPD_BIN` and `E2E_PROGRAM_SO` point at prebuilt binaries instead of building them.
+
+pub mod regtest;
+pub mod swapd;
+pub mod validator;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use intercom_swap_client::{
+    instruction::{self, InitArgs, RefundAfter},
+    state::EscrowState,
+    transaction, PROGRAM_ID,
+};
+use serde_json::Value;
+use solana_sdk::{
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{write_keypair_file, Keypair, Signer},
+};
+
+pub use crate::{
+    regtest::{Regtest, OPERATOR_NODE, USER_NODE},
+    swapd::Swapd,
+    validator::Validator,
+};
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Token base units minted to the operator and the user each: 1000 USDT at 6 decimals.
+pub const STARTING_TOKENS: u64 = 1_000_000_000;
+/// Platform and trade fee rates the configs are initialized with.
+pub const FEE_BPS: u16 = 50;
+/// Expiry of the user's invoices, well inside the escrows' refund delay.
+pub const INVOICE_EXPIRY_SECS: u64 = 3600;
+/// refund_after of the escrows the user funds, relative to cluster time.
+pub const REFUND_DELAY_SECS: i64 = 3 * 3600;
+
+/// The whole stack. Fields drop in order, so swapd stops before the validator and nodes it talks to.
+pub struct Harness {
+    pub swapd: Swapd,
+    pub validator: Validator,
+    pub regtest: Regtest,
+    /// swapd's key: mint authority, platform config authority and trade fee collector.
+    pub operator: Keypair,
+    /// The counterparty's key, receiving USDT in ln-to-usdt swaps and funding escrows in usdt-to-ln ones.
+    pub user: Keypair,
+    pub mint: Pubkey,
+    pub dir: PathBuf,
+}
+
+impl Harness {
+    pub fn start() -> Result<Self, BoxError> {
+        let dir = std::env::temp_dir().join(format!("swapd-e2e-{}", run_id()));
+        fs::create_dir_all(&dir)?;
+
+        let regtest = Regtest::up()?;
+        regtest.fund(OPERATOR_NODE, "1")?;
+        regtest.fund(USER_NODE, "1")?;
+        regtest.open_channel(USER_NODE, OPERATOR_NODE, 1_000_000, 500_000_000)?;
+
+        let validator = Validator::start(&dir)?;
+        let operator = Keypair::new();
+        let user = Keypair::new();
+        for key in [&operator, &user] {
+            validator.airdrop(&key.pubkey(), 10 * LAMPORTS_PER_SOL)?;
+        }
+        let mint = validator.create_mint(&operator, 6)?;
+        for key in [&operator, &user] {
+            validator.mint_to(&operator, &mint, &key.pubkey(), STARTING_TOKENS)?;
+        }
+        // swapd's --trade-fee-collector defaults to its own key, so the operator owns both configs.
+        let owner = operator.pubkey();
+        validator.send(
+            &[
+                instruction::init_config(&PROGRAM_ID, &owner, &owner, FEE_BPS),
+                instruction::init_trade_config(&PROGRAM_ID, &owner, FEE_BPS),
+            ],
+            &operator,
+            &[&operator],
+        )?;
+
+        let keypair = dir.join("operator.json");
+        write_keypair_file(&operator, &keypair)?;
+        let mut swapd = Swapd::new(dir.join("swapd.db"))?;
+        swapd.start(&[
+            "--rpc-url",
+            validator.rpc_url(),
+            "--keypair",
+            &format!("file:{}", keypair.display()),
+            "--mint",
+            &mint.to_string(),
+            "--ln-impl",
+            "cln",
+            "--ln-docker-compose-file",
+            &regtest.compose_file().display().to_string(),
+            "--ln-docker-service",
+            OPERATOR_NODE,
+            "--poll-interval-secs",
+            "1",
+        ])?;
+        Ok(Self {
+            swapd,
+            validator,
+            regtest,
+            operator,
+            user,
+            mint,
+            dir,
+        })
+    }
+
+    /// An invoice from the user's node: its bolt11 and payment hash.
+    pub fn user_invoice(&self, amount_msat: u64, label: &str) -> Result<(String, [u8; 32]), BoxError> {
+        let invoice = self.regtest.cln(
+            USER_NODE,
+            &[
+                "invoice",
+                &format!("{amount_msat}msat"),
+                label,
+                label,
+                &INVOICE_EXPIRY_SECS.to_string(),
+            ],
+        )?;
+        Ok((string(&invoice, "bolt11")?, bytes32(&invoice, "payment_hash")?))
+    }
+
+    /// Pays `bolt11` from the user's node and returns the preimage.
+    pub fn user_pay(&self, bolt11: &str) -> Result<[u8; 32], BoxError> {
+        let paid = self.regtest.cln(USER_NODE, &["pay", bolt11])?;
+        bytes32(&paid, "payment_preimage")
+    }
+
+    /// The user locks `amount` into an escrow for the operator, refundable to themselves.
+    pub fn user_fund_escrow(&self, payment_hash: [u8; 32], amount: u64) -> Result<(), BoxError> {
+        let args = InitArgs {
+            payment_hash,
+            recipient: self.operator.pubkey(),
+            refund: self.user.pubkey(),
+            refund_after: RefundAfter::Delay(REFUND_DELAY_SECS),
+            amount,
+            expected_platform_fee_bps: FEE_BPS,
+            expected_trade_fee_bps: FEE_BPS,
+            trade_fee_collector: self.operator.pubkey(),
+        };
+        let ixs =
+            transaction::init_instructions(&PROGRAM_ID, &self.user.pubkey(), &self.mint, &args, &Default::default());
+        self.validator.send(&ixs, &self.user, &[&self.user])
+    }
+
+    /// The user claims the escrow keyed by `payment_hash` with `preimage`.
+    pub fn user_claim(&self, payment_hash: [u8; 32], preimage: [u8; 32]) -> Result<(), BoxError> {
+        let escrow = self.validator.escrow(&payment_hash)?.ok_or("no escrow to claim")?;
+        let ixs = transaction::claim_instructions(
+            &PROGRAM_ID,
+            &escrow,
+            &self.user.pubkey(),
+            &preimage,
+            &Default::default(),
+        );
+        self.validator.send(&ixs, &self.user, &[&self.user])
+    }
+
+    pub fn escrow(&self, payment_hash: [u8; 32]) -> Result<EscrowState, BoxError> {
+        Ok(self
+            .validator
+            .escrow(&payment_hash)?
+            .ok_or_else(|| format!("no escrow for {}", hex::encode(payment_hash)))?)
+    }
+
+    pub fn tokens(&self, owner: &Pubkey) -> Result<u64, BoxError> {
+        self.validator.token_balance(owner, &self.mint)
+    }
+}
+
+/// Calls `f` until it succeeds, `tries` times `delay` apart.
+pub fn retry<T>(
+    label: &str,
+    tries: u32,
+    delay: Duration,
+    mut f: impl FnMut() -> Result<T, BoxError>,
+) -> Result<T, BoxError> {
+    let mut last = None;
+    for _ in 0..tries {
+        match f() {
+            Ok(value) => return Ok(value),
+            Err(e) => last = Some(e),
+        }
+        thread::sleep(delay);
+    }
+    let last = last.map(|e| e.to_string()).unwrap_or_default();
+    Err(format!("{label} failed after {tries} tries: {last}").into())
+}
+
+/// The repository root, which `dev/` and the other crates hang off.
+pub fn repo_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../..")
+}
+
+fn run_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    format!("{}-{nanos:08x}", std::process::id())
+}
+
+fn string(v: &Value, field: &str) -> Result<String, BoxError> {
+    Ok(v[field]
+        .as_str()
+        .ok_or_else(|| format!("{field} missing from {v}"))?
+        .to_string())
+}
+
+fn bytes32(v: &Value, field: &str) -> Result<[u8; 32], BoxError> {
+    let bytes = hex::decode(string(v, field)?)?;
+    Ok(bytes.try_into().map_err(|_| format!("{field} is not 32 bytes"))?)
+}
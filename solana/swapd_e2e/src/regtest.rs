@@ -0,0 +1,156 @@
+//! The `dev/ln-regtest` compose stack: bitcoind plus the Core Lightning nodes `cln-alice`, which swapd drives as
+//! the operator's, and `cln-bob`, the user's.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use serde_json::Value;
+
+use crate::{repo_root, retry, BoxError};
+
+pub const OPERATOR_NODE: &str = "cln-alice";
+pub const USER_NODE: &str = "cln-bob";
+
+const POLL: Duration = Duration::from_millis(500);
+
+pub struct Regtest {
+    compose_file: PathBuf,
+    /// Address of the miner wallet, which mines every block and funds the nodes.
+    miner: String,
+}
+
+impl Regtest {
+    /// Starts the stack on empty volumes, since stale `lightning-rpc` sockets and invoice labels from an earlier
+    /// run get in the way, and mines the miner wallet some spendable coins.
+    pub fn up() -> Result<Self, BoxError> {
+        let mut regtest = Self {
+            compose_file: repo_root().join("dev/ln-regtest/docker-compose.yml"),
+            miner: String::new(),
+        };
+        let _ = regtest.compose(&["down", "-v", "--remove-orphans"]);
+        regtest.compose(&["up", "-d"])?;
+        retry("bitcoind ready", 120, POLL, || regtest.btc(&["getblockchaininfo"]))?;
+        for node in [OPERATOR_NODE, USER_NODE] {
+            retry(&format!("{node} ready"), 120, POLL, || regtest.cln(node, &["getinfo"]))?;
+        }
+        // Already exists when the volume survived a `down` that failed.
+        let _ = regtest.btc(&["createwallet", "miner"]);
+        regtest.miner = text(&regtest.btc(&["-rpcwallet=miner", "getnewaddress"])?);
+        regtest.mine(101)?;
+        Ok(regtest)
+    }
+
+    pub fn compose_file(&self) -> &Path {
+        &self.compose_file
+    }
+
+    pub fn btc(&self, args: &[&str]) -> Result<Value, BoxError> {
+        let mut cmd = vec![
+            "exec",
+            "-T",
+            "bitcoind",
+            "bitcoin-cli",
+            "-regtest",
+            "-rpcuser=rpcuser",
+            "-rpcpassword=rpcpass",
+            "-rpcport=18443",
+        ];
+        cmd.extend(args);
+        let out = self.compose(&cmd)?;
+        Ok(serde_json::from_str(&out).unwrap_or_else(|_| Value::String(out.trim().to_string())))
+    }
+
+    pub fn cln(&self, node: &str, args: &[&str]) -> Result<Value, BoxError> {
+        let mut cmd = vec!["exec", "-T", node, "lightning-cli", "--network=regtest"];
+        cmd.extend(args);
+        Ok(serde_json::from_str(&self.compose(&cmd)?)?)
+    }
+
+    pub fn mine(&self, blocks: u32) -> Result<(), BoxError> {
+        self.btc(&[
+            "-rpcwallet=miner",
+            "generatetoaddress",
+            &blocks.to_string(),
+            &self.miner,
+        ])?;
+        Ok(())
+    }
+
+    /// Sends `btc` on-chain to `node` and waits until its wallet sees it confirmed.
+    pub fn fund(&self, node: &str, btc: &str) -> Result<(), BoxError> {
+        let address = self.cln(node, &["newaddr"])?["bech32"]
+            .as_str()
+            .ok_or("newaddr returned no bech32 address")?
+            .to_string();
+        self.btc(&["-rpcwallet=miner", "sendtoaddress", &address, btc])?;
+        self.mine(6)?;
+        retry(&format!("{node} funded"), 80, POLL, || {
+            let funds = self.cln(node, &["listfunds"])?;
+            let outputs = funds["outputs"].as_array().cloned().unwrap_or_default();
+            match outputs.iter().any(|o| o["status"] == "confirmed") {
+                true => Ok(()),
+                false => Err("no confirmed output yet".into()),
+            }
+        })
+    }
+
+    /// Opens a `sats` channel from `from` to `to`, pushing `push_msat` of it to `to`, and waits until it is usable.
+    pub fn open_channel(&self, from: &str, to: &str, sats: u64, push_msat: u64) -> Result<(), BoxError> {
+        let peer = text(&self.cln(to, &["getinfo"])?["id"]);
+        self.cln(from, &["connect", &format!("{peer}@{to}:9735")])?;
+        retry("fundchannel", 30, Duration::from_secs(1), || {
+            self.cln(
+                from,
+                &[
+                    "-k",
+                    "fundchannel",
+                    &format!("id={peer}"),
+                    &format!("amount={sats}"),
+                    &format!("push_msat={push_msat}"),
+                ],
+            )
+        })?;
+        self.mine(6)?;
+        retry("channel active", 120, POLL, || {
+            let channels = self.cln(from, &["listpeerchannels", &peer])?;
+            let state = channels["channels"][0]["state"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            match state.as_str() {
+                "CHANNELD_NORMAL" => Ok(()),
+                _ => Err(format!("channel state {state:?}").into()),
+            }
+        })
+    }
+
+    fn compose(&self, args: &[&str]) -> Result<String, BoxError> {
+        let out = Command::new("docker")
+            .args(["compose", "-f"])
+            .arg(&self.compose_file)
+            .args(args)
+            .output()?;
+        if !out.status.success() {
+            return Err(format!(
+                "docker compose {}: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&out.stderr).trim()
+            )
+            .into());
+        }
+        Ok(String::from_utf8(out.stdout)?)
+    }
+}
+
+impl Drop for Regtest {
+    fn drop(&mut self) {
+        let _ = self.compose(&["down", "-v", "--remove-orphans"]);
+    }
+}
+
+fn text(v: &Value) -> String {
+    v.as_str().map(str::to_string).unwrap_or_else(|| v.to_string())
+}
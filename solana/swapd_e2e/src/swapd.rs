@@ -0,0 +1,119 @@
+//! The `swapd` binary: the daemon under test, and the subcommands that queue and inspect its swaps.
+
+use std::{
+    fs::File,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+
+use crate::{repo_root, BoxError};
+
+pub struct Swapd {
+    bin: PathBuf,
+    db: PathBuf,
+    daemon: Option<Child>,
+}
+
+impl Swapd {
+    /// swapd on database `db`, built first unless `SWAPD_BIN` names a binary.
+    pub fn new(db: PathBuf) -> Result<Self, BoxError> {
+        Ok(Self {
+            bin: binary()?,
+            db,
+            daemon: None,
+        })
+    }
+
+    /// Starts `swapd run` with `args`, logging to `<db>.log`.
+    pub fn start(&mut self, args: &[&str]) -> Result<(), BoxError> {
+        // Creates the database and its preimage key up front, so the daemon and the first subcommand cannot
+        // race to generate the key.
+        self.json(&["list"])?;
+        let log = File::create(self.db.with_extension("log"))?;
+        let child = Command::new(&self.bin)
+            .arg("--db")
+            .arg(&self.db)
+            .arg("run")
+            .args(args)
+            .env("RUST_LOG", "swapd=debug,info")
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .stdin(Stdio::null())
+            .spawn()?;
+        self.daemon = Some(child);
+        Ok(())
+    }
+
+    /// Runs a subcommand against the database and parses the JSON it prints.
+    pub fn json(&self, args: &[&str]) -> Result<Value, BoxError> {
+        let out = Command::new(&self.bin).arg("--db").arg(&self.db).args(args).output()?;
+        if !out.status.success() {
+            return Err(format!(
+                "swapd {}: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&out.stderr).trim()
+            )
+            .into());
+        }
+        Ok(serde_json::from_slice(&out.stdout)?)
+    }
+
+    pub fn show(&self, id: &str) -> Result<Value, BoxError> {
+        self.json(&["show", id])
+    }
+
+    /// Polls swap `id` until it reaches `state`, failing as soon as it ends in another terminal state or the
+    /// daemon exits.
+    pub fn wait_for(&mut self, id: &str, state: &str, timeout: Duration) -> Result<Value, BoxError> {
+        let started = Instant::now();
+        loop {
+            let swap = self.show(id)?;
+            let current = swap["state"].as_str().unwrap_or_default();
+            if current == state {
+                return Ok(swap);
+            }
+            if matches!(current, "completed" | "refunded" | "failed") {
+                return Err(format!("swap {id} ended {current}, not {state}: {}", swap["error"]).into());
+            }
+            if let Some(status) = self.daemon.as_mut().map(Child::try_wait).transpose()?.flatten() {
+                return Err(format!("swapd exited ({status}) with swap {id} {current}").into());
+            }
+            if started.elapsed() > timeout {
+                return Err(format!("swap {id} still {current} after {timeout:?}, waiting for {state}").into());
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+    }
+}
+
+impl Drop for Swapd {
+    fn drop(&mut self) {
+        if let Some(mut daemon) = self.daemon.take() {
+            let _ = daemon.kill();
+            let _ = daemon.wait();
+        }
+    }
+}
+
+/// `SWAPD_BIN`, or a debug build of swapd.
+fn binary() -> Result<PathBuf, BoxError> {
+    if let Some(path) = std::env::var_os("SWAPD_BIN") {
+        return Ok(path.into());
+    }
+    let crate_dir = repo_root().join("solana/swapd");
+    let target = crate_dir.join("target");
+    let status = Command::new("cargo")
+        .args(["build", "--bin", "swapd", "--manifest-path"])
+        .arg(crate_dir.join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target)
+        .status()?;
+    if !status.success() {
+        return Err(format!("building swapd failed: {status}").into());
+    }
+    Ok(target.join("debug/swapd"))
+}
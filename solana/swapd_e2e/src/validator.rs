@@ -0,0 +1,196 @@
+//! `solana-test-validator` with `ln_usdt_escrow` loaded at its production id, and the few token and escrow
+//! operations the harness needs against it.
+
+use std::{
+    net::{Ipv4Addr, TcpListener},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use intercom_swap_client::{
+    pda,
+    state::{self, EscrowState},
+    PROGRAM_ID,
+};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
+
+use crate::{repo_root, retry, BoxError};
+
+pub struct Validator {
+    child: Child,
+    rpc_url: String,
+    rpc: RpcClient,
+}
+
+impl Validator {
+    /// Starts a fresh validator with its ledger under `dir` and waits for its RPC.
+    pub fn start(dir: &Path) -> Result<Self, BoxError> {
+        let program = program_so()?;
+        let rpc_port = rpc_port()?;
+        let faucet_port = free_port()?;
+        let log = std::fs::File::create(dir.join("validator.log"))?;
+        let child = Command::new("solana-test-validator")
+            .arg("--reset")
+            .arg("--ledger")
+            .arg(dir.join("ledger"))
+            .args(["--bind-address", "127.0.0.1"])
+            .args(["--rpc-port", &rpc_port.to_string()])
+            .args(["--faucet-port", &faucet_port.to_string()])
+            .arg("--bpf-program")
+            .arg(PROGRAM_ID.to_string())
+            .arg(program)
+            .arg("--quiet")
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .stdin(Stdio::null())
+            .spawn()?;
+        let rpc_url = format!("http://127.0.0.1:{rpc_port}");
+        let validator = Self {
+            child,
+            rpc: RpcClient::new_with_commitment(rpc_url.clone(), CommitmentConfig::confirmed()),
+            rpc_url,
+        };
+        retry("validator ready", 120, Duration::from_millis(500), || {
+            Ok(validator.rpc.get_version()?)
+        })?;
+        Ok(validator)
+    }
+
+    pub fn rpc_url(&self) -> &str {
+        &self.rpc_url
+    }
+
+    pub fn rpc(&self) -> &RpcClient {
+        &self.rpc
+    }
+
+    pub fn airdrop(&self, to: &Pubkey, lamports: u64) -> Result<(), BoxError> {
+        let signature = self.rpc.request_airdrop(to, lamports)?;
+        retry("airdrop", 60, Duration::from_millis(500), || {
+            match self.rpc.confirm_transaction(&signature)? {
+                true => Ok(()),
+                false => Err("not confirmed yet".into()),
+            }
+        })
+    }
+
+    /// Signs `ixs` with `signers`, `payer` paying, and sends them once confirmed.
+    pub fn send(&self, ixs: &[Instruction], payer: &Keypair, signers: &[&Keypair]) -> Result<(), BoxError> {
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let tx = Transaction::new_signed_with_payer(ixs, Some(&payer.pubkey()), signers, blockhash);
+        self.rpc.send_and_confirm_transaction(&tx)?;
+        Ok(())
+    }
+
+    /// A new mint with `authority` as its mint authority.
+    pub fn create_mint(&self, authority: &Keypair, decimals: u8) -> Result<Pubkey, BoxError> {
+        let mint = Keypair::new();
+        let rent = self
+            .rpc
+            .get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)?;
+        let ixs = [
+            system_instruction::create_account(
+                &authority.pubkey(),
+                &mint.pubkey(),
+                rent,
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint(
+                &spl_token::id(),
+                &mint.pubkey(),
+                &authority.pubkey(),
+                None,
+                decimals,
+            )?,
+        ];
+        self.send(&ixs, authority, &[authority, &mint])?;
+        Ok(mint.pubkey())
+    }
+
+    /// Mints `amount` into `owner`'s associated token account, creating it first.
+    pub fn mint_to(&self, authority: &Keypair, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Result<(), BoxError> {
+        let ixs = [
+            create_associated_token_account_idempotent(&authority.pubkey(), owner, mint, &spl_token::id()),
+            spl_token::instruction::mint_to(
+                &spl_token::id(),
+                mint,
+                &get_associated_token_address(owner, mint),
+                &authority.pubkey(),
+                &[],
+                amount,
+            )?,
+        ];
+        self.send(&ixs, authority, &[authority])
+    }
+
+    /// Balance of `owner`'s associated token account, zero if it does not exist.
+    pub fn token_balance(&self, owner: &Pubkey, mint: &Pubkey) -> Result<u64, BoxError> {
+        let ata = get_associated_token_address(owner, mint);
+        let account = self.rpc.get_account_with_commitment(&ata, self.rpc.commitment())?.value;
+        Ok(match account {
+            Some(account) => spl_token::state::Account::unpack(&account.data)?.amount,
+            None => 0,
+        })
+    }
+
+    pub fn escrow(&self, payment_hash: &[u8; 32]) -> Result<Option<EscrowState>, BoxError> {
+        let address = pda::find_escrow_pda(&PROGRAM_ID, payment_hash).0;
+        let account = self
+            .rpc
+            .get_account_with_commitment(&address, self.rpc.commitment())?
+            .value;
+        Ok(account.map(|a| state::decode_escrow(&a.data)).transpose()?)
+    }
+}
+
+impl Drop for Validator {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The program binary: `E2E_PROGRAM_SO`, or built with `cargo build-sbf`.
+fn program_so() -> Result<PathBuf, BoxError> {
+    if let Some(path) = std::env::var_os("E2E_PROGRAM_SO") {
+        return Ok(path.into());
+    }
+    let manifest = repo_root().join("solana/ln_usdt_escrow/Cargo.toml");
+    let status = Command::new("cargo")
+        .args(["build-sbf", "--manifest-path"])
+        .arg(&manifest)
+        .status()?;
+    if !status.success() {
+        return Err(format!("cargo build-sbf failed: {status}").into());
+    }
+    Ok(repo_root().join("solana/ln_usdt_escrow/target/deploy/ln_usdt_escrow.so"))
+}
+
+fn free_port() -> Result<u16, BoxError> {
+    Ok(TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?.local_addr()?.port())
+}
+
+/// A free RPC port whose successor, where the validator serves its websocket, is free too.
+fn rpc_port() -> Result<u16, BoxError> {
+    for _ in 0..200 {
+        let port = free_port()?;
+        if port < u16::MAX && TcpListener::bind((Ipv4Addr::LOCALHOST, port + 1)).is_ok() {
+            return Ok(port);
+        }
+    }
+    Err("no free RPC port with a free websocket port after it".into())
+}
@@ -0,0 +1,76 @@
+//! Full swaps in both directions through the running daemon; see the crate docs for what they need.
+
+use std::time::Duration;
+
+use intercom_swap_client::state::EscrowStatus;
+use solana_sdk::signature::Signer;
+use swapd_e2e::{BoxError, Harness};
+
+const TIMEOUT: Duration = Duration::from_secs(120);
+const AMOUNT_MSAT: u64 = 100_000_000;
+const TOKEN_AMOUNT: u64 = 50_000_000;
+
+#[test]
+#[ignore = "needs docker, solana-test-validator and cargo build-sbf; run with --ignored"]
+fn swaps_in_both_directions() -> Result<(), BoxError> {
+    let mut harness = Harness::start()?;
+    ln_to_usdt(&mut harness)?;
+    usdt_to_ln(&mut harness)?;
+    Ok(())
+}
+
+/// swapd funds an escrow for the user, who pays its invoice from their node and claims with the preimage.
+fn ln_to_usdt(h: &mut Harness) -> Result<(), BoxError> {
+    let user = h.user.pubkey();
+    let before = h.tokens(&user)?;
+    let swap = h.swapd.json(&[
+        "ln-to-usdt",
+        "--recipient",
+        &user.to_string(),
+        "--amount-msat",
+        &AMOUNT_MSAT.to_string(),
+        "--token-amount",
+        &TOKEN_AMOUNT.to_string(),
+    ])?;
+    let id = swap["id"].as_str().ok_or("no swap id")?.to_string();
+
+    let funded = h.swapd.wait_for(&id, "escrow_funded", TIMEOUT)?;
+    let payment_hash: [u8; 32] = hex::decode(funded["paymentHash"].as_str().ok_or("no payment hash")?)?
+        .try_into()
+        .map_err(|_| "payment hash is not 32 bytes")?;
+    let escrow = h.escrow(payment_hash)?;
+    assert_eq!(escrow.recipient, user);
+    assert_eq!(escrow.net_amount, TOKEN_AMOUNT);
+
+    let preimage = h.user_pay(funded["bolt11"].as_str().ok_or("no invoice on the funded swap")?)?;
+    h.user_claim(payment_hash, preimage)?;
+    h.swapd.wait_for(&id, "completed", TIMEOUT)?;
+    assert_eq!(h.escrow(payment_hash)?.status, EscrowStatus::Claimed);
+    assert_eq!(h.tokens(&user)?, before + TOKEN_AMOUNT);
+    Ok(())
+}
+
+/// The user funds an escrow for swapd, which pays the user's invoice and claims with the preimage it learns.
+fn usdt_to_ln(h: &mut Harness) -> Result<(), BoxError> {
+    let operator = h.operator.pubkey();
+    let before = h.tokens(&operator)?;
+    let (bolt11, payment_hash) = h.user_invoice(AMOUNT_MSAT, "usdt-to-ln")?;
+    let swap = h.swapd.json(&[
+        "usdt-to-ln",
+        "--bolt11",
+        &bolt11,
+        "--refund",
+        &h.user.pubkey().to_string(),
+        "--token-amount",
+        &TOKEN_AMOUNT.to_string(),
+    ])?;
+    let id = swap["id"].as_str().ok_or("no swap id")?.to_string();
+
+    h.user_fund_escrow(payment_hash, TOKEN_AMOUNT)?;
+    h.swapd.wait_for(&id, "completed", TIMEOUT)?;
+    assert_eq!(h.escrow(payment_hash)?.status, EscrowStatus::Claimed);
+    assert_eq!(h.tokens(&operator)?, before + TOKEN_AMOUNT);
+    let invoices = h.regtest.cln(swapd_e2e::USER_NODE, &["listinvoices", "usdt-to-ln"])?;
+    assert_eq!(invoices["invoices"][0]["status"], "paid");
+    Ok(())
+}
@@ -19,6 +19,9 @@ export const LN_USDT_ESCROW_PROGRAM_ID = new PublicKey('4RS6xpspM1V2K7FKSqeSH6VV
 const ESCROW_SEED = Buffer.from('escrow');
 const CONFIG_SEED = Buffer.from('config');
 const TRADE_CONFIG_SEED = Buffer.from('trade_config');
+const RECIPIENT_INDEX_SEED = Buffer.from('recipient_index');
+const REFUND_INDEX_SEED = Buffer.from('refund_index');
+const ESCROW_INDEX_CAPACITY = 16;
 
 function hexToBytes(hex) {
   const h = String(hex || '').trim().toLowerCase();
@@ -71,6 +74,21 @@ export function deriveTradeConfigPda(feeCollector, programId = LN_USDT_ESCROW_PR
   return { pda, bump };
 }
 
+export function deriveRecipientIndexPda(recipient, programId = LN_USDT_ESCROW_PROGRAM_ID) {
+  if (!(recipient instanceof PublicKey)) throw new Error('recipient must be a PublicKey');
+  const [pda, bump] = PublicKey.findProgramAddressSync(
+    [RECIPIENT_INDEX_SEED, Buffer.from(recipient.toBytes())],
+    programId
+  );
+  return { pda, bump };
+}
+
+export function deriveRefundIndexPda(refund, programId = LN_USDT_ESCROW_PROGRAM_ID) {
+  if (!(refund instanceof PublicKey)) throw new Error('refund must be a PublicKey');
+  const [pda, bump] = PublicKey.findProgramAddressSync([REFUND_INDEX_SEED, Buffer.from(refund.toBytes())], programId);
+  return { pda, bump };
+}
+
 export async function deriveVaultAta(escrowPda, mint) {
   return getAssociatedTokenAddress(mint, escrowPda, true, TOKEN_PROGRAM_ID, ASSOCIATED_TOKEN_PROGRAM_ID);
 }
@@ -89,7 +107,8 @@ export function buildInitInstruction({
   paymentHashHex,
   recipient,
   refund,
-  refundAfterUnix,
+  refundAfterUnix = null,
+  refundAfterSecs = null,
   amount,
   expectedPlatformFeeBps,
   expectedTradeFeeBps,
@@ -109,13 +128,21 @@ export function buildInitInstruction({
   if (!(tradeCollectorPk instanceof PublicKey)) throw new Error('tradeFeeCollector must be a PublicKey');
   const wantTradeCfg = deriveTradeConfigPda(tradeCollectorPk, programId).pda;
   if (!wantTradeCfg.equals(tradeConfigPda)) throw new Error('tradeConfigPda mismatch (derived vs provided)');
+  const { pda: recipientIndexPda } = deriveRecipientIndexPda(recipient, programId);
+  const { pda: refundIndexPda } = deriveRefundIndexPda(refund, programId);
+  // Relative mode (tag 9): the program adds refundAfterSecs to its own clock, avoiding client clock skew.
+  const relative = refundAfterSecs !== null && refundAfterSecs !== undefined;
+  if (relative === (refundAfterUnix !== null && refundAfterUnix !== undefined)) {
+    throw new Error('Provide exactly one of refundAfterUnix or refundAfterSecs');
+  }
+  if (relative && !(BigInt(refundAfterSecs) > 0n)) throw new Error('refundAfterSecs must be positive');
   const paymentHash = hexToBytes(paymentHashHex);
   const data = Buffer.concat([
-    Buffer.from([0]), // Init tag
+    Buffer.from([relative ? 9 : 0]), // Init tag (0 = absolute, 9 = relative)
     paymentHash,
     Buffer.from(recipient.toBytes()),
     Buffer.from(refund.toBytes()),
-    i64Le(refundAfterUnix),
+    i64Le(relative ? refundAfterSecs : refundAfterUnix),
     u64Le(amount),
     u16Le(expectedPlatformFeeBps),
     u16Le(expectedTradeFeeBps),
@@ -138,6 +165,8 @@ export function buildInitInstruction({
       { pubkey: platformFeeVaultAta, isSigner: false, isWritable: true },
       { pubkey: tradeConfigPda, isSigner: false, isWritable: false },
       { pubkey: tradeFeeVaultAta, isSigner: false, isWritable: true },
+      { pubkey: recipientIndexPda, isSigner: false, isWritable: true },
+      { pubkey: refundIndexPda, isSigner: false, isWritable: true },
     ],
     data,
   });
@@ -150,6 +179,7 @@ export function buildClaimInstruction({
   recipientTokenAccount,
   platformFeeVaultAta,
   tradeFeeVaultAta,
+  creator = null,
   programId = LN_USDT_ESCROW_PROGRAM_ID,
 }) {
   const { pda: escrowPda } = deriveEscrowPda(paymentHashHex, programId);
@@ -168,6 +198,8 @@ export function buildClaimInstruction({
         { pubkey: platformFeeVaultAta, isSigner: false, isWritable: true },
         { pubkey: tradeFeeVaultAta, isSigner: false, isWritable: true },
         { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+        // v4 escrows: creator receives the vault ATA rent when the program closes it.
+        ...(creator ? [{ pubkey: creator, isSigner: false, isWritable: true }] : []),
       ],
       data,
     });
@@ -177,6 +209,7 @@ export function buildRefundInstruction({
   paymentHashHex,
   refund,
   refundTokenAccount,
+  creator = null,
   programId = LN_USDT_ESCROW_PROGRAM_ID,
 }) {
   const { pda: escrowPda } = deriveEscrowPda(paymentHashHex, programId);
@@ -191,6 +224,7 @@ export function buildRefundInstruction({
         { pubkey: refundTokenAccount, isSigner: false, isWritable: true },
         { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
         { pubkey: SYSVAR_CLOCK_PUBKEY, isSigner: false, isWritable: false },
+        ...(creator ? [{ pubkey: creator, isSigner: false, isWritable: true }] : []),
       ],
       data,
     });
@@ -260,8 +294,10 @@ export function decodeEscrowState(data) {
     };
   }
 
-  if (v === 3) {
+  if (v === 3 || v === 4) {
+    // v4 appends the escrow creator (rent destination) to the v3 layout.
     if (buf.length < 263) throw new Error('Escrow account too small (v3)');
+    if (v === 4 && buf.length < 295) throw new Error('Escrow account too small (v4)');
     const status = buf.readUInt8(1);
     const paymentHash = buf.subarray(2, 34);
     const recipient = new PublicKey(buf.subarray(34, 66));
@@ -277,6 +313,7 @@ export function decodeEscrowState(data) {
     const tradeFeeCollector = new PublicKey(buf.subarray(198, 230));
     const vault = new PublicKey(buf.subarray(230, 262));
     const bump = buf.readUInt8(262);
+    const creator = v === 4 ? new PublicKey(buf.subarray(263, 295)) : null;
     return {
       v,
       status,
@@ -299,6 +336,7 @@ export function decodeEscrowState(data) {
       feeCollector: platformFeeCollector, // platform collector (legacy name)
       vault,
       bump,
+      creator,
     };
   }
 
@@ -329,6 +367,53 @@ export function decodeTradeConfigState(data) {
   return { v, authority, feeCollector, feeBps, bump };
 }
 
+// Recipient/refund index PDAs keep a ring buffer of the most recent escrow addresses. The index is best-effort,
+// not authoritative: anyone can create escrows naming any recipient or refund key and evict a wallet's real
+// entries, so treat the addresses as hints and fetch each escrow before trusting it.
+export function decodeEscrowIndexState(data) {
+  const buf = Buffer.from(data);
+  const len = 1 + 32 + 8 + 32 * ESCROW_INDEX_CAPACITY + 1;
+  if (buf.length < len) throw new Error('Escrow index account too small');
+  const v = buf.readUInt8(0);
+  if (v !== 1) throw new Error(`Unsupported escrow index version v=${v}`);
+  const owner = new PublicKey(buf.subarray(1, 33));
+  const total = buf.readBigUInt64LE(33);
+  const count = total < BigInt(ESCROW_INDEX_CAPACITY) ? Number(total) : ESCROW_INDEX_CAPACITY;
+  // Newest first.
+  const escrows = [];
+  for (let i = 0; i < count; i += 1) {
+    const slot = Number((total - 1n - BigInt(i)) % BigInt(ESCROW_INDEX_CAPACITY));
+    const off = 41 + slot * 32;
+    escrows.push(new PublicKey(buf.subarray(off, off + 32)));
+  }
+  const bump = buf.readUInt8(len - 1);
+  return { v, owner, total, escrows, bump };
+}
+
+export async function getRecipientEscrowIndex(
+  connection,
+  recipient,
+  programId = LN_USDT_ESCROW_PROGRAM_ID,
+  commitment = 'confirmed'
+) {
+  const { pda } = deriveRecipientIndexPda(recipient, programId);
+  const info = await connection.getAccountInfo(pda, commitment);
+  if (!info) return null;
+  return decodeEscrowIndexState(info.data);
+}
+
+export async function getRefundEscrowIndex(
+  connection,
+  refund,
+  programId = LN_USDT_ESCROW_PROGRAM_ID,
+  commitment = 'confirmed'
+) {
+  const { pda } = deriveRefundIndexPda(refund, programId);
+  const info = await connection.getAccountInfo(pda, commitment);
+  if (!info) return null;
+  return decodeEscrowIndexState(info.data);
+}
+
 export async function getConfigState(connection, programId = LN_USDT_ESCROW_PROGRAM_ID, commitment = 'confirmed') {
   const { pda } = deriveConfigPda(programId);
   const info = await connection.getAccountInfo(pda, commitment);
@@ -462,7 +547,8 @@ export async function createEscrowTx({
   paymentHashHex,
   recipient,
   refund,
-  refundAfterUnix,
+  refundAfterUnix = null,
+  refundAfterSecs = null,
   amount,
   expectedPlatformFeeBps,
   expectedTradeFeeBps,
@@ -483,6 +569,7 @@ export async function createEscrowTx({
     recipient,
     refund,
     refundAfterUnix,
+    refundAfterSecs,
     amount,
     expectedPlatformFeeBps,
     expectedTradeFeeBps,
@@ -509,6 +596,13 @@ export async function createEscrowTx({
   return { tx, escrowPda, vault, platformFeeVaultAta, tradeConfigPda, tradeFeeVaultAta };
 }
 
+// Creator account for claim/refund: the caller's when given (null for a v3 escrow), else read from the escrow.
+async function resolveEscrowCreator(connection, paymentHashHex, creator, programId) {
+  if (creator !== undefined) return creator;
+  const escrowState = await getEscrowState(connection, paymentHashHex, programId);
+  return escrowState?.creator ?? null;
+}
+
 export async function claimEscrowTx({
   connection,
   recipient,
@@ -517,6 +611,7 @@ export async function claimEscrowTx({
   paymentHashHex,
   preimageHex,
   tradeFeeCollector,
+  creator = undefined,
   computeUnitLimit = null,
   computeUnitPriceMicroLamports = null,
   programId = LN_USDT_ESCROW_PROGRAM_ID,
@@ -527,6 +622,7 @@ export async function claimEscrowTx({
   const platformFeeVaultAta = await deriveFeeVaultAta(configPda, mint);
   const { pda: tradeConfigPda } = deriveTradeConfigPda(tradeFeeCollector, programId);
   const tradeFeeVaultAta = await deriveTradeFeeVaultAta(tradeConfigPda, mint);
+  const escrowCreator = await resolveEscrowCreator(connection, paymentHashHex, creator, programId);
   const claimIxFactory = buildClaimInstruction({
     preimageHex,
     paymentHashHex,
@@ -534,6 +630,7 @@ export async function claimEscrowTx({
     recipientTokenAccount,
     platformFeeVaultAta,
     tradeFeeVaultAta,
+    creator: escrowCreator,
     programId,
   });
   const tx = new Transaction();
@@ -552,16 +649,19 @@ export async function refundEscrowTx({
   refundTokenAccount,
   mint,
   paymentHashHex,
+  creator = undefined,
   computeUnitLimit = null,
   computeUnitPriceMicroLamports = null,
   programId = LN_USDT_ESCROW_PROGRAM_ID,
 }) {
   const { pda: escrowPda } = deriveEscrowPda(paymentHashHex, programId);
   const vault = await deriveVaultAta(escrowPda, mint);
+  const escrowCreator = await resolveEscrowCreator(connection, paymentHashHex, creator, programId);
   const refundIxFactory = buildRefundInstruction({
     paymentHashHex,
     refund: refund.publicKey,
     refundTokenAccount,
+    creator: escrowCreator,
     programId,
   });
   const tx = new Transaction();
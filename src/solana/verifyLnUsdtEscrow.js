@@ -72,7 +72,7 @@ export async function verifyLnUsdtEscrowOnchain({
     return { ok: false, error: 'escrow account not found on chain', state: null };
   }
 
-  if (![2, 3].includes(state.v)) {
+  if (![2, 3, 4].includes(state.v)) {
     return { ok: false, error: `escrow state version unsupported v=${state.v}`, state };
   }
   if (state.status !== 0) {
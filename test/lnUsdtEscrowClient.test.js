@@ -0,0 +1,165 @@
+import test from 'node:test';
+import assert from 'node:assert/strict';
+
+import { Keypair } from '@solana/web3.js';
+
+import {
+  claimEscrowTx,
+  decodeEscrowState,
+  refundEscrowTx,
+  LN_USDT_ESCROW_PROGRAM_ID,
+} from '../src/solana/lnUsdtEscrowClient.js';
+
+const paymentHashHex = '44'.repeat(32);
+const key = () => Keypair.generate().publicKey;
+
+// v3 layout, plus the creator for v4.
+function escrowData({ v, creator = null, fields }) {
+  const buf = Buffer.alloc(v === 4 ? 295 : 263);
+  buf.writeUInt8(v, 0);
+  buf.writeUInt8(0, 1); // active
+  Buffer.from(paymentHashHex, 'hex').copy(buf, 2);
+  fields.recipient.toBuffer().copy(buf, 34);
+  fields.refund.toBuffer().copy(buf, 66);
+  buf.writeBigInt64LE(1_700_000_000n, 98);
+  fields.mint.toBuffer().copy(buf, 106);
+  buf.writeBigUInt64LE(1_000n, 138);
+  buf.writeBigUInt64LE(5n, 146);
+  buf.writeUInt16LE(50, 154);
+  fields.platformFeeCollector.toBuffer().copy(buf, 156);
+  buf.writeBigUInt64LE(3n, 188);
+  buf.writeUInt16LE(30, 196);
+  fields.tradeFeeCollector.toBuffer().copy(buf, 198);
+  fields.vault.toBuffer().copy(buf, 230);
+  buf.writeUInt8(254, 262);
+  if (creator) creator.toBuffer().copy(buf, 263);
+  return buf;
+}
+
+function escrowFields() {
+  return {
+    recipient: key(),
+    refund: key(),
+    mint: key(),
+    platformFeeCollector: key(),
+    tradeFeeCollector: key(),
+    vault: key(),
+  };
+}
+
+// Serves `data` as the escrow account (or no account when null), counting lookups.
+function escrowConnection(data) {
+  const connection = {
+    lookups: 0,
+    async getLatestBlockhash() {
+      return { blockhash: '11111111111111111111111111111111', lastValidBlockHeight: 0 };
+    },
+    async getAccountInfo() {
+      connection.lookups += 1;
+      return data ? { data } : null;
+    },
+  };
+  return connection;
+}
+
+test('ln-usdt-escrow decode: v4 escrow carries its creator', () => {
+  const fields = escrowFields();
+  const creator = key();
+  const state = decodeEscrowState(escrowData({ v: 4, creator, fields }));
+  assert.equal(state.v, 4);
+  assert.equal(state.status, 0);
+  assert.equal(state.paymentHashHex, paymentHashHex);
+  assert.equal(state.recipient.toBase58(), fields.recipient.toBase58());
+  assert.equal(state.refund.toBase58(), fields.refund.toBase58());
+  assert.equal(state.refundAfter, 1_700_000_000n);
+  assert.equal(state.netAmount, 1_000n);
+  assert.equal(state.platformFeeAmount, 5n);
+  assert.equal(state.platformFeeBps, 50);
+  assert.equal(state.tradeFeeAmount, 3n);
+  assert.equal(state.tradeFeeBps, 30);
+  assert.equal(state.tradeFeeCollector.toBase58(), fields.tradeFeeCollector.toBase58());
+  assert.equal(state.vault.toBase58(), fields.vault.toBase58());
+  assert.equal(state.bump, 254);
+  assert.equal(state.creator.toBase58(), creator.toBase58());
+});
+
+test('ln-usdt-escrow decode: v3 escrow decodes the same fields without a creator', () => {
+  const fields = escrowFields();
+  const state = decodeEscrowState(escrowData({ v: 3, fields }));
+  assert.equal(state.v, 3);
+  assert.equal(state.netAmount, 1_000n);
+  assert.equal(state.feeAmount, 8n);
+  assert.equal(state.vault.toBase58(), fields.vault.toBase58());
+  assert.equal(state.bump, 254);
+  assert.equal(state.creator, null);
+});
+
+test('ln-usdt-escrow decode: v4 escrow cut at the v3 length is rejected', () => {
+  const data = escrowData({ v: 4, creator: key(), fields: escrowFields() });
+  assert.throws(() => decodeEscrowState(data.subarray(0, 263)), /too small \(v4\)/);
+});
+
+test('ln-usdt-escrow tx builders: claim and refund read the creator from the escrow', async () => {
+  const fields = escrowFields();
+  const creator = key();
+  const claimArgs = {
+    recipient: Keypair.generate(),
+    recipientTokenAccount: key(),
+    mint: fields.mint,
+    paymentHashHex,
+    preimageHex: '55'.repeat(32),
+    tradeFeeCollector: fields.tradeFeeCollector,
+    programId: LN_USDT_ESCROW_PROGRAM_ID,
+  };
+  const refundArgs = {
+    refund: Keypair.generate(),
+    refundTokenAccount: key(),
+    mint: fields.mint,
+    paymentHashHex,
+    programId: LN_USDT_ESCROW_PROGRAM_ID,
+  };
+
+  const v4 = escrowConnection(escrowData({ v: 4, creator, fields }));
+  const { tx: claim } = await claimEscrowTx({ ...claimArgs, connection: v4 });
+  const { tx: refund } = await refundEscrowTx({ ...refundArgs, connection: v4 });
+  assert.equal(v4.lookups, 2);
+  for (const tx of [claim, refund]) {
+    const last = tx.instructions[0].keys.at(-1);
+    assert.equal(last.pubkey.toBase58(), creator.toBase58());
+    assert.equal(last.isWritable, true);
+    assert.equal(last.isSigner, false);
+  }
+  assert.equal(claim.instructions[0].keys.length, 8);
+  assert.equal(refund.instructions[0].keys.length, 7);
+
+  // v3 escrows, and escrows not found, take no creator account.
+  for (const data of [escrowData({ v: 3, fields }), null]) {
+    const connection = escrowConnection(data);
+    const { tx: v3Claim } = await claimEscrowTx({ ...claimArgs, connection });
+    const { tx: v3Refund } = await refundEscrowTx({ ...refundArgs, connection });
+    assert.equal(v3Claim.instructions[0].keys.length, 7);
+    assert.equal(v3Refund.instructions[0].keys.length, 6);
+  }
+});
+
+test('ln-usdt-escrow tx builders: a given creator skips the escrow lookup', async () => {
+  const fields = escrowFields();
+  const stored = key();
+  const given = key();
+  const connection = escrowConnection(escrowData({ v: 4, creator: stored, fields }));
+  const refundArgs = {
+    connection,
+    refund: Keypair.generate(),
+    refundTokenAccount: key(),
+    mint: fields.mint,
+    paymentHashHex,
+    programId: LN_USDT_ESCROW_PROGRAM_ID,
+  };
+
+  const { tx: withGiven } = await refundEscrowTx({ ...refundArgs, creator: given });
+  assert.equal(withGiven.instructions[0].keys.at(-1).pubkey.toBase58(), given.toBase58());
+  // null means a v3 escrow: no trailing account, whatever the chain says.
+  const { tx: withNull } = await refundEscrowTx({ ...refundArgs, creator: null });
+  assert.equal(withNull.instructions[0].keys.length, 6);
+  assert.equal(connection.lookups, 0);
+});
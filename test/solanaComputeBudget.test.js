@@ -4,14 +4,17 @@ import assert from 'node:assert/strict';
 import { ComputeBudgetProgram, Keypair, PublicKey } from '@solana/web3.js';
 
 import { buildComputeBudgetIxs } from '../src/solana/computeBudget.js';
-import { createEscrowTx, claimEscrowTx, LN_USDT_ESCROW_PROGRAM_ID } from '../src/solana/lnUsdtEscrowClient.js';
+import { createEscrowTx, claimEscrowTx, refundEscrowTx, LN_USDT_ESCROW_PROGRAM_ID } from '../src/solana/lnUsdtEscrowClient.js';
 
 function dummyConnection() {
   return {
-    // Enough for our tx builders (they only need a recent blockhash).
+    // Enough for our tx builders (they need a recent blockhash, and look up escrows for their creator).
     async getLatestBlockhash() {
       return { blockhash: '11111111111111111111111111111111', lastValidBlockHeight: 0 };
     },
+    async getAccountInfo() {
+      return null;
+    },
   };
 }
 
@@ -105,3 +108,43 @@ test('ln-usdt-escrow tx builders: include compute budget ixs when configured', a
   assert.equal(claimTx.feePayer.toBase58(), recipientKp.publicKey.toBase58());
 });
 
+
+test('ln-usdt-escrow tx builders: v4 escrows get the creator as trailing account', async () => {
+  const connection = dummyConnection();
+  const mint = Keypair.generate().publicKey;
+  const paymentHashHex = '22'.repeat(32);
+  const creator = Keypair.generate().publicKey;
+
+  const recipient = Keypair.generate();
+  const claimArgs = {
+    connection,
+    recipient,
+    recipientTokenAccount: Keypair.generate().publicKey,
+    mint,
+    paymentHashHex,
+    preimageHex: '33'.repeat(32),
+    tradeFeeCollector: Keypair.generate().publicKey,
+    programId: LN_USDT_ESCROW_PROGRAM_ID,
+  };
+  const { tx: v3Claim } = await claimEscrowTx(claimArgs);
+  const { tx: v4Claim } = await claimEscrowTx({ ...claimArgs, creator });
+  const v3Keys = v3Claim.instructions[0].keys;
+  const v4Keys = v4Claim.instructions[0].keys;
+  assert.equal(v4Keys.length, v3Keys.length + 1);
+  assert.equal(v4Keys.at(-1).pubkey.toBase58(), creator.toBase58());
+  assert.equal(v4Keys.at(-1).isWritable, true);
+
+  const refund = Keypair.generate();
+  const refundArgs = {
+    connection,
+    refund,
+    refundTokenAccount: Keypair.generate().publicKey,
+    mint,
+    paymentHashHex,
+    programId: LN_USDT_ESCROW_PROGRAM_ID,
+  };
+  const { tx: v3Refund } = await refundEscrowTx(refundArgs);
+  const { tx: v4Refund } = await refundEscrowTx({ ...refundArgs, creator });
+  assert.equal(v4Refund.instructions[0].keys.length, v3Refund.instructions[0].keys.length + 1);
+  assert.equal(v4Refund.instructions[0].keys.at(-1).pubkey.toBase58(), creator.toBase58());
+});